@@ -0,0 +1,103 @@
+// Drops pieces where the attract mode's AI would, straight onto a Board with
+// no clock or gravity, and reports the lines it cleared, the holes it left and
+// how long it took to think. Run with
+// `cargo run --release --example ai_benchmark -- [pieces] [seed]`.
+
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use tet_rs::ai;
+use tet_rs::board::{Board, FIELD_HEIGHT};
+use tet_rs::piece::randomizer::RandomizerKind;
+use tet_rs::piece::rotation::RotationKind;
+use tet_rs::piece::Piece;
+
+/// Pieces dropped when no count is given
+const DEFAULT_PIECES: u32 = 1_000;
+
+/// How the AI got on
+struct Report {
+    /// Pieces dropped before the count ran out or the stack topped out
+    placed: u32,
+    lines: usize,
+    /// Holes in the stack left at the end
+    holes: usize,
+    /// Rows the stack stands at the end
+    height: u32,
+    /// Time spent deciding where pieces go
+    thinking: Duration,
+}
+
+/// Deals `pieces` pieces from `seed` and drops each where the AI picks
+fn run(pieces: u32, seed: u64) -> (Board, Report) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut randomizer = RandomizerKind::Bag.build();
+    let mut board = Board::default();
+    let mut report = Report {
+        placed: 0,
+        lines: 0,
+        holes: 0,
+        height: 0,
+        thinking: Duration::default(),
+    };
+
+    while report.placed < pieces {
+        let kind = randomizer.next(&mut rng);
+        let piece = match board.spawn(kind.def(), 1, RotationKind::default(), 0) {
+            Some(piece) => piece,
+            None => break,
+        };
+        let start = Instant::now();
+        let place = ai::best_placement(&board, &piece);
+        report.thinking += start.elapsed();
+        let (x, rot) = match place {
+            Some(place) => place,
+            None => break,
+        };
+
+        board.lock(&board.landing(&Piece { x, rot, ..piece }));
+        let full = board.full_rows();
+        board.clear_rows(&full);
+        report.lines += full.len();
+        report.placed += 1;
+    }
+
+    report.holes = board.holes();
+    report.height = FIELD_HEIGHT - board.highest_occupied_row();
+    (board, report)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let pieces = args.next().map_or(Ok(DEFAULT_PIECES), |a| a.parse())?;
+    let seed = args.next().map_or(Ok(0), |a| a.parse())?;
+
+    let (board, report) = run(pieces, seed);
+    println!("{}", board.to_ascii());
+    println!(
+        "{} of {} pieces, {} lines, {} holes, {} rows high",
+        report.placed, pieces, report.lines, report.holes, report.height
+    );
+    println!(
+        "{:.1} µs a piece ({:.2?} in all)",
+        report.thinking.as_secs_f64() * 1e6 / report.placed.max(1) as f64,
+        report.thinking
+    );
+
+    Ok(())
+}
+
+#[test]
+fn the_ai_keeps_the_stack_down() {
+    let (_, report) = run(200, 1);
+    assert_eq!(report.placed, 200);
+    // four cells a piece, ten a line
+    assert!(report.lines >= 70, "only {} lines", report.lines);
+    assert!(
+        report.height < FIELD_HEIGHT / 2,
+        "{} rows high",
+        report.height
+    );
+}
@@ -0,0 +1,58 @@
+// Records res/replays/demo.bin, the sprint built into the game for the
+// attract mode and --demo, by letting the AI play it out. Run it again
+// whenever the replay format changes.
+
+use tet_rs::ai;
+use tet_rs::mode::GameMode;
+use tet_rs::piece::randomizer::RandomizerKind;
+use tet_rs::sim::replay::{Replay, VERSION};
+use tet_rs::sim::{Ending, Event, Simulation, MIN_PREVIEWS};
+
+const DEMO_PATH: &str = "res/replays/demo.bin";
+
+/// Deals a sprint the AI gets through without topping out
+const SEED: u64 = 1;
+
+/// Gives up on a run that's gone on for ten minutes
+const MAX_TICKS: usize = 12_000;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let sim = Simulation::new(
+        GameMode::Sprint,
+        MIN_PREVIEWS,
+        Some(SEED),
+        RandomizerKind::Bag,
+    );
+    let mut replay = Replay::record(&sim);
+    // played exactly as it'll be played back
+    let mut sim = replay.simulation();
+
+    let mut target = None;
+    while sim.ending.is_none() && replay.inputs.len() < MAX_TICKS {
+        let inputs = match (sim.active_piece, target) {
+            (Some(piece), Some(target)) => ai::steer(&piece, target, sim.ticker),
+            _ => Default::default(),
+        };
+        replay.inputs.push(inputs);
+        for event in sim.step(inputs) {
+            if let Event::PieceSpawned { piece } = event {
+                target = ai::best_placement(&sim.board, &piece);
+            }
+        }
+    }
+
+    match sim.ending {
+        Some(Ending::Cleared { millis }) => replay.millis = Some(millis),
+        ending => return Err(format!("the sprint didn't finish: {:?}", ending).into()),
+    }
+    replay.save(DEMO_PATH.as_ref())?;
+    println!(
+        "Wrote {} (version {}, {} ticks, {} ms)",
+        DEMO_PATH,
+        VERSION,
+        replay.inputs.len(),
+        replay.millis.unwrap_or(0)
+    );
+
+    Ok(())
+}
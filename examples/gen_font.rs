@@ -0,0 +1,25 @@
+// Regenerates res/textures/font.png from the glyph table in
+// src/graphics/font.rs. Pass --check to only compare the shipped atlas
+// against the table, exiting with an error if they have drifted apart.
+
+use tet_rs::graphics::font;
+
+const ATLAS_PATH: &str = "res/textures/font.png";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    if std::env::args().any(|arg| arg == "--check") {
+        let shipped = image::open(ATLAS_PATH)?.to_rgba8();
+        font::verify_atlas(&shipped)?;
+        println!("{} matches the glyph table", ATLAS_PATH);
+    } else {
+        let atlas = font::generate_atlas();
+        atlas.save(ATLAS_PATH)?;
+        println!(
+            "Wrote {} ({:016x})",
+            ATLAS_PATH,
+            font::atlas_checksum(&atlas)
+        );
+    }
+
+    Ok(())
+}
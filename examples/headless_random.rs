@@ -0,0 +1,97 @@
+// Plays random moves through a Simulation with no window, until a number of
+// pieces have locked or the stack tops out, then prints the field and the
+// score. Run with `cargo run --example headless_random -- [pieces] [seed]`.
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use tet_rs::ai;
+use tet_rs::board::{Board, FIELD_WIDTH};
+use tet_rs::mode::GameMode;
+use tet_rs::piece::randomizer::RandomizerKind;
+use tet_rs::piece::Piece;
+use tet_rs::sim::{Event, Simulation, DEFAULT_PREVIEWS};
+
+/// Pieces played when no count is given
+const DEFAULT_PIECES: u32 = 100;
+
+/// Ticks a piece is given to lock before the run is given up on
+const MAX_TICKS_PER_PIECE: u32 = 1_000;
+
+/// An endless run played with random moves, and how many pieces locked in it
+fn play(pieces: u32, seed: u64) -> (Simulation, u32) {
+    let mut sim = Simulation::new(
+        GameMode::Endless,
+        DEFAULT_PREVIEWS,
+        Some(seed),
+        RandomizerKind::Bag,
+    );
+    sim.countdown = 0;
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let mut target = None;
+    let mut locked = 0;
+    let mut ticks = 0;
+    while locked < pieces && sim.ending.is_none() && ticks < pieces * MAX_TICKS_PER_PIECE {
+        ticks += 1;
+        let inputs = match (sim.active_piece, target) {
+            (Some(piece), Some(target)) => ai::steer(&piece, target, sim.ticker),
+            _ => Default::default(),
+        };
+        for event in sim.step(inputs) {
+            match event {
+                Event::PieceSpawned { piece } => {
+                    target = random_placement(&sim.board, &piece, &mut rng)
+                }
+                Event::PieceLocked { .. } => locked += 1,
+                _ => (),
+            }
+        }
+    }
+    (sim, locked)
+}
+
+/// Somewhere `piece` can be turned and slid to from the top of `board`, as
+/// (x, rotation), picked at random
+fn random_placement(board: &Board, piece: &Piece, rng: &mut impl Rng) -> Option<(i8, u8)> {
+    let places: Vec<(i8, u8)> = (0..4)
+        .flat_map(|rot| (-4..FIELD_WIDTH as i8).map(move |x| (x, rot)))
+        .filter(|&(x, rot)| board.fits(&Piece { x, rot, ..*piece }))
+        .collect();
+    places.choose(rng).copied()
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let pieces = args.next().map_or(Ok(DEFAULT_PIECES), |a| a.parse())?;
+    let seed = args.next().map_or(Ok(0), |a| a.parse())?;
+
+    let (sim, locked) = play(pieces, seed);
+    println!("{}", sim.board.to_ascii());
+    println!(
+        "{} pieces, {} lines, {} points{}",
+        locked,
+        sim.lines,
+        sim.score,
+        if sim.ending.is_some() {
+            " (topped out)"
+        } else {
+            ""
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn plays_until_the_pieces_run_out_or_the_stack_tops_out() {
+    let (sim, locked) = play(30, 1);
+    assert!(
+        locked == 30 || sim.ending.is_some(),
+        "stopped at {}",
+        locked
+    );
+    assert!(locked > 0);
+    assert!(!sim.board.is_empty() || sim.lines > 0);
+}
@@ -0,0 +1,62 @@
+// Reads a replay and prints how its run was set up, then plays it back to
+// find its final score and check it ends when it says it did. Run with
+// `cargo run --example replay_info -- [path]`; with no path, the demo built
+// into the game is read.
+
+use tet_rs::mode::format_time;
+use tet_rs::sim::replay::{Replay, DEMO};
+use tet_rs::sim::{Ending, Simulation};
+
+/// `replay` played out from start to finish
+fn resimulate(replay: &Replay) -> Simulation {
+    let mut sim = replay.simulation();
+    for &inputs in &replay.inputs {
+        sim.step(inputs);
+    }
+    sim
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let replay = match std::env::args().nth(1) {
+        Some(path) => Replay::load(path.as_ref())?,
+        None => Replay::from_bytes(DEMO)?,
+    };
+
+    println!("Mode:       {}", replay.mode.name());
+    println!("Seed:       {}", replay.seed);
+    println!("Randomizer: {}", replay.randomizer.name());
+    println!("Rotation:   {}", replay.rotation.name());
+    println!(
+        "Handling:   DAS {}, ARR {}",
+        replay.handling.das, replay.handling.arr
+    );
+    println!("Ticks:      {}", replay.inputs.len());
+    if let Some(millis) = replay.millis {
+        println!("Time:       {}", format_time(millis));
+    }
+
+    let sim = resimulate(&replay);
+    println!("Score:      {}", sim.score);
+    println!("Lines:      {}", sim.lines);
+    match (sim.ending, replay.millis) {
+        (Some(Ending::Cleared { millis }), Some(recorded)) if millis != recorded => Err(format!(
+            "played back in {}, not {}",
+            format_time(millis),
+            format_time(recorded)
+        )
+        .into()),
+        (None, _) => Err("the recording runs out before the run ends".into()),
+        _ => Ok(()),
+    }
+}
+
+#[test]
+fn the_demo_plays_back_to_its_time() {
+    let replay = Replay::from_bytes(DEMO).unwrap();
+    let sim = resimulate(&replay);
+    assert_eq!(
+        sim.ending,
+        replay.millis.map(|millis| Ending::Cleared { millis })
+    );
+    assert!(sim.score > 0);
+}
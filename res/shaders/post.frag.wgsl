@@ -0,0 +1,45 @@
+[[block]]
+struct Params {
+    darken: f32;
+    radius: f32;
+    texel: vec2<f32>;
+};
+
+[[group(0), binding(0)]]
+var t_frame: texture_2d<f32>;
+[[group(0), binding(1)]]
+var s_frame: sampler;
+[[group(0), binding(2)]]
+var<uniform> params: Params;
+
+[[stage(fragment)]]
+fn main(
+    [[location(0)]] v_tex_coords: vec2<f32>,
+) -> [[location(0)]] vec4<f32> {
+    // box blur, which a radius of 0 leaves as a plain copy
+    let radius: i32 = i32(params.radius);
+    var sum: vec4<f32> = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+    var y: i32 = -radius;
+    loop {
+        if (y > radius) {
+            break;
+        }
+        var x: i32 = -radius;
+        loop {
+            if (x > radius) {
+                break;
+            }
+            let offset: vec2<f32> = vec2<f32>(f32(x), f32(y)) * params.texel;
+            sum = sum + textureSampleLevel(t_frame, s_frame, v_tex_coords + offset, 0.0);
+            continuing {
+                x = x + 1;
+            }
+        }
+        continuing {
+            y = y + 1;
+        }
+    }
+    let side: f32 = f32(radius * 2 + 1);
+    let color: vec4<f32> = sum / (side * side);
+    return vec4<f32>(color.rgb * (1.0 - params.darken), 1.0);
+}
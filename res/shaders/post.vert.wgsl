@@ -0,0 +1,18 @@
+struct VertexOutput {
+    [[location(0)]] v_tex_coords: vec2<f32>;
+    [[builtin(position)]] position: vec4<f32>;
+};
+
+// one triangle big enough to cover the whole frame, worked out from the index
+// so there's no vertex buffer to keep around
+[[stage(vertex)]]
+fn main(
+    [[builtin(vertex_index)]] index: u32,
+) -> VertexOutput {
+    let x: f32 = f32(i32(index & 1u) * 4 - 1);
+    let y: f32 = f32(i32(index >> 1u) * 4 - 1);
+    var out: VertexOutput;
+    out.v_tex_coords = vec2<f32>((x + 1.0) * 0.5, (1.0 - y) * 0.5);
+    out.position = vec4<f32>(x, y, 0.0, 1.0);
+    return out;
+}
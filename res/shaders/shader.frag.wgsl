@@ -0,0 +1,7 @@
+[[stage(fragment)]]
+fn main(
+    [[location(0)]] v_tex_coords: vec2<f32>,
+    [[location(1)]] v_color: vec4<f32>,
+) -> [[location(0)]] vec4<f32> {
+    return v_color;
+}
@@ -0,0 +1,54 @@
+[[block]]
+struct Uniforms {
+    u_proj: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> uniforms: Uniforms;
+
+struct VertexOutput {
+    [[location(0)]] v_tex_coords: vec2<f32>;
+    [[location(1)]] v_color: vec4<f32>;
+    [[builtin(position)]] position: vec4<f32>;
+};
+
+// vertex colours are sRGB, which an sRGB target expects to be given as linear
+fn to_linear(c: f32) -> f32 {
+    if (c <= 0.04045) {
+        return c / 12.92;
+    }
+    return pow((c + 0.055) / 1.055, 2.4);
+}
+
+// for targets that store colours as they're given
+[[stage(vertex)]]
+fn main(
+    [[location(0)]] a_position: vec3<f32>,
+    [[location(1)]] a_color: vec4<f32>,
+    [[location(2)]] a_tex_coords: vec2<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.v_tex_coords = a_tex_coords;
+    out.v_color = a_color;
+    out.position = uniforms.u_proj * vec4<f32>(a_position, 1.0);
+    return out;
+}
+
+// for sRGB targets, which encode what they're given on the way out
+[[stage(vertex)]]
+fn main_srgb(
+    [[location(0)]] a_position: vec3<f32>,
+    [[location(1)]] a_color: vec4<f32>,
+    [[location(2)]] a_tex_coords: vec2<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.v_tex_coords = a_tex_coords;
+    out.v_color = vec4<f32>(
+        to_linear(a_color.r),
+        to_linear(a_color.g),
+        to_linear(a_color.b),
+        a_color.a
+    );
+    out.position = uniforms.u_proj * vec4<f32>(a_position, 1.0);
+    return out;
+}
@@ -0,0 +1,16 @@
+[[group(1), binding(0)]]
+var t_diffuse: texture_2d<f32>;
+[[group(1), binding(1)]]
+var s_diffuse: sampler;
+
+[[stage(fragment)]]
+fn main(
+    [[location(0)]] v_tex_coords: vec2<f32>,
+    [[location(1)]] v_color: vec4<f32>,
+) -> [[location(0)]] vec4<f32> {
+    let tex_color: vec4<f32> = textureSample(t_diffuse, s_diffuse, v_tex_coords);
+    if (tex_color.a == 0.0) {
+        discard;
+    }
+    return tex_color * v_color;
+}
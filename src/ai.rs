@@ -0,0 +1,197 @@
+//! A simple player for the attract mode demo.
+//!
+//! Every place a piece can be dropped is tried and the board it would leave
+//! behind weighed up, with the weights of a well known hand tuned player:
+//! lower stacks with fewer holes and a flatter top are better, and so are
+//! cleared lines.
+
+use crate::board::{Board, Cell, FIELD_HEIGHT, FIELD_WIDTH};
+use crate::piece::Piece;
+use crate::sim::Inputs;
+
+/// Where to drop `piece` on `board`, as (x, rotation), weighing stack height,
+/// holes and bumpiness against lines cleared. `None` if the piece has nowhere
+/// to go.
+pub fn best_placement(board: &Board, piece: &Piece) -> Option<(i8, u8)> {
+    let mut best: Option<((i8, u8), f32)> = None;
+    for rot in 0..4 {
+        for x in -4..FIELD_WIDTH as i8 {
+            let candidate = Piece { x, rot, ..*piece };
+            if !board.fits(&candidate) {
+                continue;
+            }
+
+            let mut after = *board;
+            after.lock(&board.landing(&candidate));
+            let score = placement_score(&after);
+            if best.map_or(true, |(_, b)| score > b) {
+                best = Some(((x, rot), score));
+            }
+        }
+    }
+
+    best.map(|(target, _)| target)
+}
+
+/// Keys to hold on tick `ticker` to bring `piece` round to `target`, as
+/// (x, rotation), and then drop it. Keys are let go of every other tick so
+/// each press counts as a new one
+pub fn steer(piece: &Piece, target: (i8, u8), ticker: u64) -> Inputs {
+    let (x, rot) = target;
+    let press = ticker % 2 == 0;
+    let mut inputs = Inputs::default();
+    if piece.rot != rot {
+        inputs.rot_right = press;
+    } else if piece.x < x {
+        inputs.right = press;
+    } else if piece.x > x {
+        inputs.left = press;
+    } else {
+        inputs.down = true;
+    }
+    inputs
+}
+
+/// How good a board looks once its full rows are cleared; higher is better
+pub fn placement_score(board: &Board) -> f32 {
+    let width = FIELD_WIDTH as usize;
+    let rows: Vec<&[Cell]> = board
+        .cells
+        .chunks(width)
+        .filter(|row| row.iter().any(|&c| c == Cell::Empty))
+        .collect();
+    let lines = FIELD_HEIGHT as usize - rows.len();
+
+    // the rows left over settle at the bottom of the field
+    let mut heights = vec![0; width];
+    let mut holes = 0;
+    for x in 0..width {
+        let top = rows.iter().position(|row| row[x] != Cell::Empty);
+        if let Some(top) = top {
+            heights[x] = rows.len() - top;
+            holes += rows[top..]
+                .iter()
+                .filter(|row| row[x] == Cell::Empty)
+                .count();
+        }
+    }
+    let height: usize = heights.iter().sum();
+    let bumpiness: usize = heights
+        .windows(2)
+        .map(|w| (w[0] as isize - w[1] as isize).abs() as usize)
+        .sum();
+
+    -0.51 * height as f32 + 0.76 * lines as f32 - 0.36 * holes as f32 - 0.18 * bumpiness as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::rotation::RotationKind;
+    use crate::piece::Tetromino;
+
+    fn spawn(t: Tetromino) -> Piece {
+        Piece::new(t.def(), 1, RotationKind::Srs)
+    }
+
+    /// `board` with `piece` dropped where [`best_placement`] says
+    fn play(board: &Board, piece: Piece) -> Board {
+        let (x, rot) = best_placement(board, &piece).expect("nowhere to go");
+        let mut piece = Piece { x, rot, ..piece };
+        while board.fits(&Piece {
+            y: piece.y + 1,
+            ..piece
+        }) {
+            piece.y += 1;
+        }
+        let mut after = *board;
+        after.lock(&piece);
+        after
+    }
+
+    #[test]
+    fn drops_an_i_down_the_well() {
+        let board = Board::from_ascii(&"#########.\n".repeat(4)).unwrap();
+        let after = play(&board, spawn(Tetromino::I));
+        assert_eq!(after.full_rows().len(), 4, "\n{}", after.to_ascii());
+    }
+
+    #[test]
+    fn fills_a_gap_its_shape_fits() {
+        let board = Board::from_ascii(
+            "####..####
+             ####..####",
+        )
+        .unwrap();
+        let after = play(&board, spawn(Tetromino::O));
+        assert_eq!(after.full_rows().len(), 2, "\n{}", after.to_ascii());
+    }
+
+    #[test]
+    fn never_covers_an_open_gap() {
+        let board = Board::from_ascii("###.######").unwrap();
+        let after = play(&board, spawn(Tetromino::T));
+        let hole = (3 + (FIELD_HEIGHT - 1) * FIELD_WIDTH) as usize;
+        let above = hole - FIELD_WIDTH as usize;
+        assert!(
+            after.cells[hole] != Cell::Empty || after.cells[above] == Cell::Empty,
+            "\n{}",
+            after.to_ascii()
+        );
+    }
+
+    #[test]
+    fn gives_up_with_nowhere_to_go() {
+        let board = Board::from_ascii(&"#########.\n".repeat(FIELD_HEIGHT as usize)).unwrap();
+        assert_eq!(best_placement(&board, &spawn(Tetromino::O)), None);
+    }
+
+    #[test]
+    fn steers_by_turning_then_moving_then_dropping() {
+        let piece = spawn(Tetromino::T);
+        let turn = steer(&piece, (piece.x, (piece.rot + 1) % 4), 0);
+        assert!(turn.rot_right && !turn.left && !turn.right && !turn.down);
+        // turning first, a tick at a time
+        assert_eq!(
+            steer(&piece, (0, (piece.rot + 1) % 4), 1),
+            Inputs::default()
+        );
+
+        let right = steer(&piece, (piece.x + 2, piece.rot), 0);
+        assert!(right.right && !right.rot_right);
+        let left = steer(&piece, (piece.x - 2, piece.rot), 0);
+        assert!(left.left && !left.rot_right);
+
+        let there = (piece.x, piece.rot);
+        assert!(steer(&piece, there, 0).down && steer(&piece, there, 1).down);
+    }
+
+    #[test]
+    fn a_flat_stack_beats_a_bumpy_one() {
+        // as many cells either way
+        let flat = Board::from_ascii(&"####......\n".repeat(2)).unwrap();
+        let bumpy = Board::from_ascii(&"#.#.......\n".repeat(4)).unwrap();
+        assert!(placement_score(&flat) > placement_score(&bumpy));
+    }
+
+    #[test]
+    fn a_hole_counts_against_a_board() {
+        let open = Board::from_ascii(
+            "##........
+             ##........",
+        )
+        .unwrap();
+        let covered = Board::from_ascii(
+            "##........
+             #.........",
+        )
+        .unwrap();
+        assert!(placement_score(&open) > placement_score(&covered));
+    }
+
+    #[test]
+    fn cleared_lines_count_for_a_board() {
+        let cleared = Board::from_ascii("##########").unwrap();
+        assert!(placement_score(&cleared) > placement_score(&Board::default()));
+    }
+}
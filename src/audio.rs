@@ -0,0 +1,684 @@
+//! Sound effects and music.
+//!
+//! An [`AudioSystem`] plays a short sound for each [`SoundId`]. The sounds are
+//! synthesized when it starts, unless there's a file to play instead in
+//! `sounds`, named after the sound and ending in `.wav` or `.ogg`, looked for
+//! wherever other [assets](crate::graphics::assets) are.
+//!
+//! Music is streamed from `music` among the assets, one looping `.ogg` file
+//! per [`Track`], and crossfades when the track asked for changes. It's on a
+//! channel of its own, so it has its own volume. There's no music built in: a track without a
+//! file is skipped, leaving it quiet.
+//!
+//! Each sound plays on a [`Channel`] with a volume of its own, under a master
+//! volume that covers everything and can be muted.
+//!
+//! Builds without the `audio` feature, and machines without an output device,
+//! get a system that plays nothing, so the game runs the same either way.
+
+use serde::{Deserialize, Serialize};
+
+use std::time::Duration;
+#[cfg(feature = "audio")]
+use std::time::Instant;
+
+#[cfg(feature = "audio")]
+use crate::graphics::assets;
+
+/// Samples per second of the synthesized sounds
+const SAMPLE_RATE: u32 = 44_100;
+
+/// How loud the synthesized sounds are at full volume, out of 1
+const AMPLITUDE: f32 = 0.25;
+
+/// Time the synthesized sounds take to fade in, in seconds, which keeps them
+/// from clicking as they start
+const ATTACK: f32 = 0.003;
+
+/// Loudest a volume setting goes, in percent
+pub const MAX_VOLUME: u32 = 100;
+
+/// Plays of one sound allowed at once. The oldest is cut off to make room
+#[cfg(feature = "audio")]
+const MAX_VOICES: usize = 3;
+
+/// Shortest time between two plays of one sound. Plays closer together than
+/// this are dropped, so fast key repeat doesn't pile sounds up
+#[cfg(feature = "audio")]
+const MIN_REPEAT: Duration = Duration::from_millis(35);
+
+/// Time one track takes to fade into another, in seconds
+#[cfg(feature = "audio")]
+const CROSSFADE: f32 = 0.75;
+
+/// How much faster hurried music plays. Its pitch goes up along with it
+#[cfg(feature = "audio")]
+const HURRIED_SPEED: f32 = 1.12;
+
+/// Something the game makes a sound for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SoundId {
+    Shift,
+    Rotate,
+    SoftDrop,
+    Lock,
+    LineClear,
+    Tetris,
+    LevelUp,
+    GameOver,
+    /// A gravity step, while the metronome ticks
+    Metronome,
+}
+
+impl SoundId {
+    pub const ALL: &'static [SoundId] = &[
+        SoundId::Shift,
+        SoundId::Rotate,
+        SoundId::SoftDrop,
+        SoundId::Lock,
+        SoundId::LineClear,
+        SoundId::Tetris,
+        SoundId::LevelUp,
+        SoundId::GameOver,
+        SoundId::Metronome,
+    ];
+
+    /// Name of the sound's file in `sounds`, without the extension
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::Shift => "shift",
+            Self::Rotate => "rotate",
+            Self::SoftDrop => "soft_drop",
+            Self::Lock => "lock",
+            Self::LineClear => "line_clear",
+            Self::Tetris => "tetris",
+            Self::LevelUp => "level_up",
+            Self::GameOver => "game_over",
+            Self::Metronome => "metronome",
+        }
+    }
+
+    /// What's synthesized for the sound when there's no file for it
+    fn tone(self) -> Tone {
+        let tone = |wave, from, to, millis| Tone {
+            wave,
+            from,
+            to,
+            millis,
+        };
+        match self {
+            Self::Shift => tone(Wave::Square, 220.0, 220.0, 30),
+            Self::Rotate => tone(Wave::Square, 330.0, 440.0, 40),
+            Self::SoftDrop => tone(Wave::Sine, 160.0, 140.0, 20),
+            Self::Lock => tone(Wave::Sine, 110.0, 70.0, 80),
+            Self::LineClear => tone(Wave::Square, 523.0, 784.0, 180),
+            Self::Tetris => tone(Wave::Square, 523.0, 1046.0, 400),
+            Self::LevelUp => tone(Wave::Sine, 660.0, 990.0, 250),
+            Self::GameOver => tone(Wave::Square, 330.0, 110.0, 700),
+            Self::Metronome => tone(Wave::Sine, 1320.0, 1320.0, 15),
+        }
+    }
+}
+
+/// Sounds turned up and down together
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    /// Everything, on top of the other channels
+    Master,
+    Music,
+    Effects,
+}
+
+impl Channel {
+    pub const ALL: &'static [Channel] = &[Channel::Master, Channel::Music, Channel::Effects];
+}
+
+/// Volume of each channel, from 0 for silent to 1 for full
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Mix {
+    master: f32,
+    music: f32,
+    effects: f32,
+    muted: bool,
+}
+
+impl Default for Mix {
+    fn default() -> Self {
+        Self {
+            master: 1.0,
+            music: 1.0,
+            effects: 1.0,
+            muted: false,
+        }
+    }
+}
+
+impl Mix {
+    fn volume(&self, channel: Channel) -> f32 {
+        match channel {
+            Channel::Master => self.master,
+            Channel::Music => self.music,
+            Channel::Effects => self.effects,
+        }
+    }
+
+    fn volume_mut(&mut self, channel: Channel) -> &mut f32 {
+        match channel {
+            Channel::Master => &mut self.master,
+            Channel::Music => &mut self.music,
+            Channel::Effects => &mut self.effects,
+        }
+    }
+
+    /// How loud `channel` comes out, under the master volume and muting
+    fn level(&self, channel: Channel) -> f32 {
+        if self.muted {
+            return 0.0;
+        }
+        match channel {
+            Channel::Master => self.master,
+            _ => self.master * self.volume(channel),
+        }
+    }
+}
+
+/// A piece of music
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Track {
+    Menu,
+    Game,
+}
+
+impl Track {
+    /// Name of the track's file in `music`, without the extension
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::Menu => "menu",
+            Self::Game => "game",
+        }
+    }
+}
+
+/// What a state wants from the music
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Music {
+    /// `track`, sped up if `hurried`, as when the stack is nearly at the top
+    Play { track: Track, hurried: bool },
+    /// Whatever's playing, held where it is until it's asked for again
+    Paused,
+}
+
+/// Volume of each sound, in percent, on top of the overall effects volume
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoundVolumes {
+    pub shift: u32,
+    pub rotate: u32,
+    pub soft_drop: u32,
+    pub lock: u32,
+    pub line_clear: u32,
+    pub tetris: u32,
+    pub level_up: u32,
+    pub game_over: u32,
+    pub metronome: u32,
+}
+
+impl Default for SoundVolumes {
+    fn default() -> Self {
+        Self {
+            shift: MAX_VOLUME,
+            rotate: MAX_VOLUME,
+            soft_drop: MAX_VOLUME,
+            lock: MAX_VOLUME,
+            line_clear: MAX_VOLUME,
+            tetris: MAX_VOLUME,
+            level_up: MAX_VOLUME,
+            game_over: MAX_VOLUME,
+            metronome: MAX_VOLUME,
+        }
+    }
+}
+
+impl SoundVolumes {
+    pub fn get(&self, sound: SoundId) -> u32 {
+        match sound {
+            SoundId::Shift => self.shift,
+            SoundId::Rotate => self.rotate,
+            SoundId::SoftDrop => self.soft_drop,
+            SoundId::Lock => self.lock,
+            SoundId::LineClear => self.line_clear,
+            SoundId::Tetris => self.tetris,
+            SoundId::LevelUp => self.level_up,
+            SoundId::GameOver => self.game_over,
+            SoundId::Metronome => self.metronome,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Wave {
+    Sine,
+    Square,
+}
+
+/// A note sliding from one pitch to another and dying away
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Tone {
+    wave: Wave,
+    /// Pitch at the start and the end, in hertz
+    from: f32,
+    to: f32,
+    millis: u32,
+}
+
+impl Tone {
+    /// The tone as mono samples at [`SAMPLE_RATE`]
+    fn synthesize(&self) -> Vec<f32> {
+        let count = (SAMPLE_RATE * self.millis / 1000) as usize;
+        let mut phase = 0.0f32;
+        (0..count)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let progress = i as f32 / count as f32;
+                let pitch = self.from + (self.to - self.from) * progress;
+                phase = (phase + pitch / SAMPLE_RATE as f32).fract();
+                let sample = match self.wave {
+                    Wave::Sine => (phase * std::f32::consts::TAU).sin(),
+                    // square waves are loud for their amplitude, so they're
+                    // turned down to about match
+                    Wave::Square => {
+                        if phase < 0.5 {
+                            0.5
+                        } else {
+                            -0.5
+                        }
+                    }
+                };
+                // fades in quickly and out all the way, ending on silence
+                let envelope = (t / ATTACK).min(1.0) * (1.0 - progress).powi(2);
+                sample * envelope * AMPLITUDE
+            })
+            .collect()
+    }
+}
+
+/// A sound ready to play
+#[cfg(feature = "audio")]
+#[derive(Clone)]
+struct Clip {
+    channels: u16,
+    sample_rate: u32,
+    samples: Vec<f32>,
+}
+
+#[cfg(feature = "audio")]
+impl Clip {
+    /// The file for `sound` in `sounds`, or else its synthesized tone
+    fn load(sound: SoundId) -> Self {
+        use rodio::Source;
+
+        for extension in &["wav", "ogg"] {
+            let name = format!("sounds/{}.{}", sound.file_name(), extension);
+            let path = match assets::find(&name) {
+                Ok(path) => path,
+                Err(_) => continue,
+            };
+            let file = match std::fs::File::open(&path) {
+                Ok(file) => file,
+                Err(_) => continue,
+            };
+            match rodio::Decoder::new(std::io::BufReader::new(file)) {
+                Ok(decoder) => {
+                    return Self {
+                        channels: decoder.channels(),
+                        sample_rate: decoder.sample_rate(),
+                        samples: decoder.convert_samples().collect(),
+                    }
+                }
+                Err(e) => eprintln!("Couldn't read {}, synthesizing it: {}", path.display(), e),
+            }
+        }
+
+        Self {
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            samples: sound.tone().synthesize(),
+        }
+    }
+}
+
+/// The output device and what's playing on it
+#[cfg(feature = "audio")]
+struct Output {
+    // dropping the stream closes the device, so it's kept for as long as
+    // anything's played
+    _stream: rodio::OutputStream,
+    handle: rodio::OutputStreamHandle,
+    /// By position in [`SoundId::ALL`]
+    clips: Vec<Clip>,
+    voices: Vec<Vec<rodio::Sink>>,
+    last_played: Vec<Option<Instant>>,
+    /// Track asked for, fading in until it's at full volume
+    music: Option<Stream>,
+    /// Tracks fading out after another was asked for
+    fading: Vec<Stream>,
+    /// Tracks that couldn't be played, so they aren't tried every frame
+    missing: Vec<Track>,
+}
+
+/// A track looping on its own sink
+#[cfg(feature = "audio")]
+struct Stream {
+    track: Track,
+    /// Whether it's played at [`HURRIED_SPEED`]
+    hurried: bool,
+    sink: rodio::Sink,
+    /// How far it's faded in, from 0 for silent to 1 for full volume
+    level: f32,
+}
+
+#[cfg(feature = "audio")]
+impl Output {
+    /// Starts `track` looping from silence, sped up if `hurried`. If its file
+    /// can't be played that's logged and it's skipped from then on
+    fn start(&mut self, track: Track, hurried: bool) -> Option<Stream> {
+        use rodio::Source;
+
+        let name = format!("music/{}.ogg", track.file_name());
+        let opened = assets::find(&name)
+            .map_err(|e| e.to_string())
+            .and_then(|path| std::fs::File::open(path).map_err(|e| e.to_string()))
+            .and_then(|file| {
+                rodio::Decoder::new(std::io::BufReader::new(file)).map_err(|e| e.to_string())
+            })
+            .and_then(|decoder| {
+                let sink = rodio::Sink::try_new(&self.handle).map_err(|e| e.to_string())?;
+                Ok((decoder, sink))
+            });
+        match opened {
+            Ok((decoder, sink)) => {
+                sink.set_volume(0.0);
+                let speed = if hurried { HURRIED_SPEED } else { 1.0 };
+                sink.append(decoder.repeat_infinite().speed(speed));
+                Some(Stream {
+                    track,
+                    hurried,
+                    sink,
+                    level: 0.0,
+                })
+            }
+            Err(e) => {
+                eprintln!("Couldn't play {}, skipping it: {}", name, e);
+                self.missing.push(track);
+                None
+            }
+        }
+    }
+}
+
+/// Plays sound effects, or nothing at all if there's nowhere to play them
+pub struct AudioSystem {
+    mix: Mix,
+    #[cfg(feature = "audio")]
+    output: Option<Output>,
+}
+
+impl AudioSystem {
+    /// Opens the default output device. Without one, or without the `audio`
+    /// feature, sounds are quietly skipped
+    pub fn new() -> Self {
+        #[cfg(feature = "audio")]
+        {
+            let output = match rodio::OutputStream::try_default() {
+                Ok((stream, handle)) => Some(Output {
+                    _stream: stream,
+                    handle,
+                    clips: SoundId::ALL
+                        .iter()
+                        .map(|&sound| Clip::load(sound))
+                        .collect(),
+                    voices: SoundId::ALL.iter().map(|_| Vec::new()).collect(),
+                    last_played: vec![None; SoundId::ALL.len()],
+                    music: None,
+                    fading: Vec::new(),
+                    missing: Vec::new(),
+                }),
+                Err(e) => {
+                    eprintln!("Warning: no sound, couldn't open an output device: {}", e);
+                    None
+                }
+            };
+            Self {
+                mix: Mix::default(),
+                output,
+            }
+        }
+        #[cfg(not(feature = "audio"))]
+        Self::silent()
+    }
+
+    /// A system that never plays anything
+    pub fn silent() -> Self {
+        Self {
+            mix: Mix::default(),
+            #[cfg(feature = "audio")]
+            output: None,
+        }
+    }
+
+    /// Whether sounds are actually heard
+    pub fn is_enabled(&self) -> bool {
+        #[cfg(feature = "audio")]
+        return self.output.is_some();
+        #[cfg(not(feature = "audio"))]
+        false
+    }
+
+    /// Sets how loud `channel` is, from 0 for silent to 1 for full. Anything
+    /// outside that is clamped to it. Music already playing follows straight
+    /// away
+    pub fn set_volume(&mut self, channel: Channel, volume: f32) {
+        // max and min also turn NaN into 0
+        *self.mix.volume_mut(channel) = volume.max(0.0).min(1.0);
+    }
+
+    /// How loud `channel` is set to be, leaving out the master volume
+    pub fn volume(&self, channel: Channel) -> f32 {
+        self.mix.volume(channel)
+    }
+
+    /// Silences everything, without forgetting the volumes
+    pub fn set_muted(&mut self, muted: bool) {
+        self.mix.muted = muted;
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.mix.muted
+    }
+
+    /// Plays `sound` at `volume`, from 0 for silent to 1 for full, under the
+    /// effects and master volumes
+    pub fn play(&mut self, sound: SoundId, volume: f32) {
+        #[cfg(feature = "audio")]
+        {
+            let volume = volume.max(0.0).min(1.0) * self.mix.level(Channel::Effects);
+            let output = match &mut self.output {
+                Some(output) if volume > 0.0 => output,
+                _ => return,
+            };
+            let index = SoundId::ALL.iter().position(|&s| s == sound).unwrap();
+
+            let now = Instant::now();
+            if let Some(last) = output.last_played[index] {
+                if now - last < MIN_REPEAT {
+                    return;
+                }
+            }
+            output.last_played[index] = Some(now);
+
+            let voices = &mut output.voices[index];
+            voices.retain(|voice| !voice.empty());
+            if voices.len() >= MAX_VOICES {
+                voices.remove(0).stop();
+            }
+
+            let sink = match rodio::Sink::try_new(&output.handle) {
+                Ok(sink) => sink,
+                Err(e) => {
+                    eprintln!("Couldn't play {}: {}", sound.file_name(), e);
+                    return;
+                }
+            };
+            let clip = &output.clips[index];
+            sink.set_volume(volume);
+            sink.append(rodio::buffer::SamplesBuffer::new(
+                clip.channels,
+                clip.sample_rate,
+                clip.samples.clone(),
+            ));
+            voices.push(sink);
+        }
+        #[cfg(not(feature = "audio"))]
+        let _ = (sound, volume);
+    }
+
+    /// Fades towards what `music` asks for, or out to silence if it's None,
+    /// at the music and master volumes. Called every frame, `dt` after the
+    /// last
+    pub fn update_music(&mut self, music: Option<Music>, dt: Duration) {
+        #[cfg(feature = "audio")]
+        {
+            let volume = self.mix.level(Channel::Music);
+            let output = match &mut self.output {
+                Some(output) => output,
+                None => return,
+            };
+            let playing = output.music.as_ref().map(|stream| stream.track);
+            let was_hurried = output.music.as_ref().map_or(false, |stream| stream.hurried);
+            let (track, hurried, paused) = match music {
+                Some(Music::Play { track, hurried }) => (Some(track), hurried, false),
+                Some(Music::Paused) => (playing, was_hurried, true),
+                None => (None, false, false),
+            };
+            let track = track.filter(|track| !output.missing.contains(track));
+            // a sink can't change speed as it plays, so hurrying crossfades
+            // into a faster copy of the track
+            if track != playing || hurried != was_hurried {
+                output.fading.extend(output.music.take());
+                output.music = track.and_then(|track| output.start(track, hurried));
+            }
+
+            let step = dt.as_secs_f32() / CROSSFADE;
+            if let Some(stream) = &mut output.music {
+                if paused {
+                    stream.sink.pause();
+                } else {
+                    stream.sink.play();
+                }
+                stream.level = (stream.level + step).min(1.0);
+                stream.sink.set_volume(stream.level * volume);
+            }
+            for stream in &mut output.fading {
+                stream.level = (stream.level - step).max(0.0);
+                stream.sink.set_volume(stream.level * volume);
+            }
+            // dropping a sink stops it
+            output.fading.retain(|stream| stream.level > 0.0);
+        }
+        #[cfg(not(feature = "audio"))]
+        let _ = (music, dt);
+    }
+}
+
+impl Default for AudioSystem {
+    fn default() -> Self {
+        Self::silent()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn channels_come_out_under_the_master_volume() {
+        let mut audio = AudioSystem::silent();
+        audio.set_volume(Channel::Master, 0.5);
+        audio.set_volume(Channel::Music, 0.4);
+        audio.set_volume(Channel::Effects, 0.8);
+        assert!(close(audio.mix.level(Channel::Music), 0.2));
+        assert!(close(audio.mix.level(Channel::Effects), 0.4));
+        // the master volume isn't taken twice over
+        assert!(close(audio.mix.level(Channel::Master), 0.5));
+
+        audio.set_volume(Channel::Master, 0.0);
+        for &channel in Channel::ALL {
+            assert_eq!(audio.mix.level(channel), 0.0);
+        }
+    }
+
+    #[test]
+    fn volumes_are_clamped_to_silent_and_full() {
+        let mut audio = AudioSystem::silent();
+        for &(set, got) in &[
+            (1.5, 1.0),
+            (-0.25, 0.0),
+            (0.3, 0.3),
+            (f32::INFINITY, 1.0),
+            (f32::NAN, 0.0),
+        ] {
+            audio.set_volume(Channel::Effects, set);
+            assert_eq!(audio.volume(Channel::Effects), got, "set to {}", set);
+        }
+    }
+
+    #[test]
+    fn muting_silences_everything_and_keeps_the_volumes() {
+        let mut audio = AudioSystem::silent();
+        audio.set_volume(Channel::Music, 0.6);
+        audio.set_muted(true);
+        assert!(audio.is_muted());
+        for &channel in Channel::ALL {
+            assert_eq!(audio.mix.level(channel), 0.0);
+        }
+        assert_eq!(audio.volume(Channel::Music), 0.6);
+
+        audio.set_muted(false);
+        assert!(close(audio.mix.level(Channel::Music), 0.6));
+    }
+
+    #[test]
+    fn synthesized_sounds_start_and_end_quietly() {
+        for &sound in SoundId::ALL {
+            let tone = sound.tone();
+            let samples = tone.synthesize();
+            assert_eq!(
+                samples.len(),
+                (SAMPLE_RATE * tone.millis / 1000) as usize,
+                "{:?}",
+                sound
+            );
+            assert!(samples.iter().all(|s| s.abs() <= AMPLITUDE), "{:?}", sound);
+            // no click going in or out
+            assert!(samples[0].abs() < 1e-3, "{:?} starts loud", sound);
+            assert!(
+                samples.last().unwrap().abs() < 1e-3,
+                "{:?} ends loud",
+                sound
+            );
+        }
+    }
+
+    #[test]
+    fn sounds_are_full_volume_until_turned_down() {
+        let mut volumes = SoundVolumes::default();
+        assert!(SoundId::ALL.iter().all(|&s| volumes.get(s) == MAX_VOLUME));
+        volumes.lock = 30;
+        assert_eq!(volumes.get(SoundId::Lock), 30);
+        assert_eq!(volumes.get(SoundId::LineClear), MAX_VOLUME);
+    }
+}
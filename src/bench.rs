@@ -0,0 +1,155 @@
+//! Measuring how fast the game runs, for `--bench`.
+//!
+//! The simulation is timed on its own, stepped with keys picked from a fixed
+//! seed so every run plays the same games, and never touches the GPU. How
+//! long frames take to draw is measured by the binary, which owns the window,
+//! and handed to [`FrameBench::new`] to be summed up.
+
+use std::time::{Duration, Instant};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::Serialize;
+
+use crate::mode::GameMode;
+use crate::piece::randomizer::RandomizerKind;
+use crate::sim::{Inputs, Simulation, MAX_PREVIEWS};
+
+/// Seed every benchmarked game and its keys are picked from
+pub const BENCH_SEED: u64 = 0x7e7_2b5;
+
+/// Ticks the simulation is stepped through unless told otherwise
+pub const DEFAULT_BENCH_TICKS: u64 = 2_000_000;
+
+/// Longest the scripted player holds the same keys, in ticks
+const MAX_HOLD_TICKS: u32 = 8;
+
+/// How fast the simulation stepped
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct SimBench {
+    pub ticks: u64,
+    /// Games played through, a new one starting whenever one ends
+    pub games: u32,
+    pub seconds: f64,
+    pub ticks_per_second: f64,
+}
+
+/// How long frames took to draw, and how much they sent to the GPU
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct FrameBench {
+    pub frames: usize,
+    pub mean_millis: f64,
+    pub p95_millis: f64,
+    pub upload_bytes_per_frame: f64,
+}
+
+/// Everything measured, printed when the benchmark is done
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct BenchReport {
+    pub simulation: SimBench,
+    /// Left out unless a window was opened to draw in
+    pub render: Option<FrameBench>,
+}
+
+/// Steps endless games for `ticks` ticks with keys held at random. Picking
+/// the keys is timed along with the stepping, but is cheap next to it
+pub fn simulate(ticks: u64) -> SimBench {
+    let mut rng = StdRng::seed_from_u64(BENCH_SEED);
+    let mut games = 1;
+    let mut sim = game(BENCH_SEED);
+    let mut inputs = Inputs::default();
+    let mut hold = 0;
+    let start = Instant::now();
+    for _ in 0..ticks {
+        if hold == 0 {
+            inputs = scripted_inputs(&mut rng);
+            hold = rng.gen_range(1..=MAX_HOLD_TICKS);
+        }
+        hold -= 1;
+        sim.step(inputs);
+
+        if sim.ending.is_some() {
+            sim = game(BENCH_SEED + games as u64);
+            games += 1;
+        }
+    }
+
+    let seconds = start.elapsed().as_secs_f64();
+    SimBench {
+        ticks,
+        games,
+        seconds,
+        ticks_per_second: ticks as f64 / seconds.max(f64::EPSILON),
+    }
+}
+
+/// A fresh endless game dealt from `seed`, past its countdown
+fn game(seed: u64) -> Simulation {
+    let mut sim = Simulation::new(
+        GameMode::Endless,
+        MAX_PREVIEWS,
+        Some(seed),
+        RandomizerKind::default(),
+    );
+    sim.countdown = 0;
+    sim
+}
+
+/// Keys a player mashing away might hold: mostly moving and turning, and
+/// dropping often enough that pieces lock and lines clear
+fn scripted_inputs(rng: &mut StdRng) -> Inputs {
+    let horizontal = rng.gen_range(0..3);
+    let turn = rng.gen_range(0..4);
+    Inputs {
+        left: horizontal == 1,
+        right: horizontal == 2,
+        down: rng.gen_bool(0.4),
+        rot_right: turn == 1,
+        rot_left: turn == 2,
+    }
+}
+
+impl FrameBench {
+    /// Sums up frames that took `times` to draw and sent `uploads` bytes each
+    pub fn new(times: &[Duration], uploads: &[u64]) -> Self {
+        let mut millis: Vec<f64> = times.iter().map(|t| t.as_secs_f64() * 1000.0).collect();
+        millis.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len().max(1) as f64;
+        let p95 = match millis.len() {
+            0 => 0.0,
+            len => millis[(len * 95 / 100).min(len - 1)],
+        };
+        let uploads: Vec<f64> = uploads.iter().map(|&bytes| bytes as f64).collect();
+        Self {
+            frames: millis.len(),
+            mean_millis: mean(&millis),
+            p95_millis: p95,
+            upload_bytes_per_frame: mean(&uploads),
+        }
+    }
+}
+
+impl BenchReport {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+impl std::fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sim = &self.simulation;
+        writeln!(
+            f,
+            "simulation: {} ticks over {} games in {:.3}s, {:.0} ticks/s",
+            sim.ticks, sim.games, sim.seconds, sim.ticks_per_second
+        )?;
+        if let Some(render) = &self.render {
+            writeln!(
+                f,
+                "render: {} frames, {:.3}ms mean, {:.3}ms 95th percentile, {:.0} bytes \
+                 uploaded a frame",
+                render.frames, render.mean_millis, render.p95_millis, render.upload_bytes_per_frame
+            )?;
+        }
+        Ok(())
+    }
+}
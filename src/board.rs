@@ -0,0 +1,764 @@
+//! The playfield and the rules for what pieces can do on it.
+
+use rand::Rng;
+
+use crate::piece::rotation::RotationKind;
+use crate::piece::{Color, Piece, PieceDef};
+
+pub const FIELD_WIDTH: u32 = 10;
+pub const FIELD_HEIGHT: u32 = 20;
+
+/// Rows at the top of the field new pieces appear in. They stand in for the
+/// hidden rows above the field other games spawn into: a piece that locks
+/// without reaching below them has locked out
+pub const SPAWN_ROWS: u32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cell {
+    Empty,
+    Full(Color),
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self::Empty
+    }
+}
+
+/// Cells of the field, row by row from the top left
+pub type Field = [Cell; (FIELD_WIDTH * FIELD_HEIGHT) as usize];
+
+/// The stack of locked cells. The falling piece isn't part of it until it
+/// [locks](Self::lock); a [`Simulation`](crate::sim::Simulation) keeps the two
+/// apart. Start from [`Board::default`] for an empty field or
+/// [`Board::from_ascii`] for a drawn one
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Board {
+    pub cells: Field,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self {
+            cells: [Cell::Empty; (FIELD_WIDTH * FIELD_HEIGHT) as usize],
+        }
+    }
+}
+
+impl Board {
+    /// A board drawn as rows of `.` for empty and `#` for filled cells, top
+    /// row first, e.g. `"....\n#..#\n####"`. Rows sit at the bottom of the
+    /// field and may be shorter than it; filled cells are grey. None if a row
+    /// is too wide, there are too many rows or a cell is neither
+    pub fn from_ascii(text: &str) -> Option<Self> {
+        let rows: Vec<&str> = text
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect();
+        if rows.len() > FIELD_HEIGHT as usize {
+            return None;
+        }
+
+        let mut board = Self::default();
+        let top = FIELD_HEIGHT as usize - rows.len();
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() > FIELD_WIDTH as usize {
+                return None;
+            }
+            for (x, c) in row.chars().enumerate() {
+                board.cells[x + (top + y) * FIELD_WIDTH as usize] = match c {
+                    '.' => Cell::Empty,
+                    '#' => Cell::Full(Color::Grey),
+                    _ => return None,
+                };
+            }
+        }
+        Some(board)
+    }
+
+    /// The whole field in the format [`from_ascii`](Self::from_ascii) reads,
+    /// one line per row
+    pub fn to_ascii(&self) -> String {
+        self.cells
+            .chunks(FIELD_WIDTH as usize)
+            .map(|row| {
+                row.iter()
+                    .map(|&c| if c == Cell::Empty { '.' } else { '#' })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Whether `piece` is inside the field without overlapping the stack
+    pub fn fits(&self, piece: &Piece) -> bool {
+        piece.cells().all(|(x, y)| {
+            x >= 0
+                && x < FIELD_WIDTH as isize
+                && y >= 0
+                && y < FIELD_HEIGHT as isize
+                && self.cells[(x + y * FIELD_WIDTH as isize) as usize] == Cell::Empty
+        })
+    }
+
+    /// Where `piece` comes to rest dropped straight down from where it is,
+    /// a cell of its own size at a time
+    pub fn landing(&self, piece: &Piece) -> Piece {
+        let mut landed = *piece;
+        loop {
+            let below = Piece {
+                y: landed.y + landed.scale as i8,
+                ..landed
+            };
+            if !self.fits(&below) {
+                return landed;
+            }
+            landed = below;
+        }
+    }
+
+    /// Adds `piece` to the stack. Cells off the field are dropped
+    pub fn lock(&mut self, piece: &Piece) {
+        for (x, y) in piece.cells() {
+            if x < 0 || x >= FIELD_WIDTH as isize || y < 0 || y >= FIELD_HEIGHT as isize {
+                // off the field; checking the offset alone would wrap it onto
+                // the neighbouring row
+                continue;
+            }
+            self.cells[(x + y * FIELD_WIDTH as isize) as usize] = Cell::Full(piece.kind.color);
+        }
+    }
+
+    /// `piece` turned `turn` quarter turns clockwise, moved to the first of
+    /// its kicks where it fits. None if it's blocked wherever it tries
+    pub fn rotate(&self, piece: &Piece, turn: u8) -> Option<Piece> {
+        let scale = piece.scale as i8;
+        piece
+            .kicks(turn)
+            .iter()
+            .map(|&(x, y)| Piece {
+                x: piece.x + x * scale,
+                y: piece.y + y * scale,
+                rot: (piece.rot + turn) % 4,
+                ..*piece
+            })
+            .find(|turned| self.fits(turned))
+    }
+
+    /// A new `kind` of piece at the top of the field, turned `turn` quarter
+    /// turns clockwise if that isn't blocked (an initial rotation). None if
+    /// even the unturned piece doesn't fit, i.e. the stack has topped out
+    pub fn spawn(
+        &self,
+        kind: &'static PieceDef,
+        scale: u8,
+        rotation: RotationKind,
+        turn: u8,
+    ) -> Option<Piece> {
+        let piece = Piece::new(kind, scale, rotation);
+        let turned = Piece {
+            rot: (piece.rot + turn) % 4,
+            ..piece
+        };
+        if self.fits(&turned) {
+            Some(turned)
+        } else if self.fits(&piece) {
+            Some(piece)
+        } else {
+            None
+        }
+    }
+
+    /// Rows with no empty cell left, from the top down
+    pub fn full_rows(&self) -> Vec<i8> {
+        self.cells
+            .chunks(FIELD_WIDTH as usize)
+            .enumerate()
+            .filter(|(_, row)| row.iter().all(|&c| c != Cell::Empty))
+            .map(|(y, _)| y as i8)
+            .collect()
+    }
+
+    /// Deletes `rows`, moving everything above them down
+    pub fn clear_rows(&mut self, rows: &[i8]) {
+        remove_rows(&mut self.cells, rows, Cell::Empty);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.iter().all(|&c| c == Cell::Empty)
+    }
+
+    /// Row of the highest filled cell, counted from the top, or
+    /// `FIELD_HEIGHT` if the board is empty
+    pub fn highest_occupied_row(&self) -> u32 {
+        self.cells
+            .iter()
+            .position(|&c| c != Cell::Empty)
+            .map_or(FIELD_HEIGHT, |i| i as u32 / FIELD_WIDTH)
+    }
+
+    /// Empty cells with a filled one somewhere above them in their column
+    pub fn holes(&self) -> usize {
+        let width = FIELD_WIDTH as usize;
+        (0..width)
+            .map(|x| {
+                let column = self.cells[x..].iter().step_by(width);
+                column
+                    .skip_while(|&&c| c == Cell::Empty)
+                    .filter(|&&c| c == Cell::Empty)
+                    .count()
+            })
+            .sum()
+    }
+
+    /// Fills the bottom `rows` rows with garbage, each missing a single cell. A
+    /// hole never lines up with the ones below it more than twice in a row, so
+    /// the stack can always be dug through
+    pub fn add_garbage(&mut self, rows: u32, rng: &mut impl Rng) {
+        let mut last_hole = None;
+        let mut run = 0;
+        for y in FIELD_HEIGHT - rows..FIELD_HEIGHT {
+            let mut hole = rng.gen_range(0..FIELD_WIDTH);
+            if last_hole == Some(hole) && run >= 2 {
+                // move it anywhere else
+                hole = (hole + rng.gen_range(1..FIELD_WIDTH)) % FIELD_WIDTH;
+            }
+            run = if last_hole == Some(hole) { run + 1 } else { 1 };
+            last_hole = Some(hole);
+
+            for x in 0..FIELD_WIDTH {
+                self.cells[(x + y * FIELD_WIDTH) as usize] = if x == hole {
+                    Cell::Empty
+                } else {
+                    Cell::Full(Color::Grey)
+                };
+            }
+        }
+    }
+
+    /// Pushes the stack up `rows` rows and fills the bottom with garbage,
+    /// returning whether anything was pushed off the top
+    pub fn raise(&mut self, rows: u32, rng: &mut impl Rng) -> bool {
+        let rows = rows.min(FIELD_HEIGHT);
+        let shift = (rows * FIELD_WIDTH) as usize;
+        let overflow = self.cells[..shift].iter().any(|&c| c != Cell::Empty);
+        self.cells.copy_within(shift.., 0);
+        self.add_garbage(rows, rng);
+        overflow
+    }
+
+    /// Removes the bottom half of the field, moving everything above it down
+    pub fn drop_bottom_half(&mut self) {
+        let half = FIELD_HEIGHT / 2;
+        for y in (0..FIELD_HEIGHT).rev() {
+            for x in 0..FIELD_WIDTH {
+                self.cells[(x + y * FIELD_WIDTH) as usize] = if y >= half {
+                    self.cells[(x + (y - half) * FIELD_WIDTH) as usize]
+                } else {
+                    Cell::Empty
+                };
+            }
+        }
+    }
+}
+
+/// Deletes `rows` from anything laid out like the field, moving what's above
+/// them down and filling the top with `empty`
+pub fn remove_rows<T: Copy>(cells: &mut [T], rows: &[i8], empty: T) {
+    let width = FIELD_WIDTH as usize;
+    let mut rows = rows.to_vec();
+    // going top down, shifting a row never moves one still to be removed
+    rows.sort_unstable();
+    for row in rows {
+        let end = (row as usize + 1) * width;
+        cells.copy_within(..end - width, width);
+        for cell in &mut cells[..width] {
+            *cell = empty;
+        }
+    }
+}
+
+/// Whether `piece` locking where it is ends the run, all of it still being
+/// inside the [`SPAWN_ROWS`] (scaled up for big pieces)
+pub fn locked_out(piece: &Piece) -> bool {
+    let rows = (SPAWN_ROWS * piece.scale as u32) as isize;
+    piece.cells().all(|(_, y)| y < rows)
+}
+
+/// Points for clearing `lines` lines with a single piece
+pub fn clear_score(lines: usize) -> u64 {
+    match lines {
+        0 => 0,
+        1 => 100,
+        2 => 300,
+        3 => 500,
+        4 => 800,
+        5 => 1200,
+        _ => unreachable!("a piece clears at most five lines"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::piece::Tetromino;
+
+    /// Column of the empty cell in row `y`, if it has exactly one
+    fn hole(board: &Board, y: u32) -> Option<u32> {
+        let row = &board.cells[(y * FIELD_WIDTH) as usize..((y + 1) * FIELD_WIDTH) as usize];
+        let mut empty = (0..FIELD_WIDTH).filter(|&x| row[x as usize] == Cell::Empty);
+        match (empty.next(), empty.next()) {
+            (Some(x), None) => Some(x),
+            _ => None,
+        }
+    }
+
+    fn i_piece() -> Piece {
+        Piece::new(Tetromino::I.def(), 1, RotationKind::Srs)
+    }
+
+    fn inside(piece: &Piece) -> bool {
+        piece
+            .cells()
+            .all(|(x, y)| x >= 0 && x < FIELD_WIDTH as isize && y >= 0 && y < FIELD_HEIGHT as isize)
+    }
+
+    #[test]
+    fn fits_stops_at_every_edge() {
+        let board = Board::default();
+        let piece = Piece { y: 5, ..i_piece() };
+        assert!(board.fits(&Piece { x: 0, ..piece }));
+        assert!(!board.fits(&Piece { x: -1, ..piece }));
+        assert!(board.fits(&Piece { x: 6, ..piece }));
+        assert!(!board.fits(&Piece { x: 7, ..piece }));
+        // flat, the I sits in the second row of its box
+        assert!(board.fits(&Piece { y: -1, ..piece }));
+        assert!(!board.fits(&Piece { y: -2, ..piece }));
+        assert!(board.fits(&Piece { y: 18, ..piece }));
+        assert!(!board.fits(&Piece { y: 19, ..piece }));
+    }
+
+    #[test]
+    fn a_piece_lands_on_whatever_is_under_it() {
+        let board = Board::from_ascii(
+            "..........
+             .#........
+             ..........",
+        )
+        .unwrap();
+        let flat = Piece { x: 0, ..i_piece() };
+        let landed = board.landing(&flat);
+        assert!(board.fits(&landed));
+        // flat, the I sits in the second row of its box, above the filled cell
+        assert_eq!(landed.y, FIELD_HEIGHT as i8 - 4);
+        let clear = Piece { x: 4, ..i_piece() };
+        assert_eq!(board.landing(&clear).y, FIELD_HEIGHT as i8 - 2);
+    }
+
+    #[test]
+    fn holes_are_counted_under_the_top_of_each_column() {
+        let board = Board::from_ascii(
+            ".#..#.....
+             .#........
+             ..#.#.....
+             ##.##.....",
+        )
+        .unwrap();
+        // two under the second column, one under the fifth, none in the open
+        // third column
+        assert_eq!(board.holes(), 3);
+        assert_eq!(Board::default().holes(), 0);
+    }
+
+    #[test]
+    fn big_pieces_stop_at_every_edge_in_steps_of_two() {
+        let board = Board::default();
+        for &kind in Tetromino::ALL {
+            for rot in 0..4 {
+                let piece = Piece {
+                    rot,
+                    ..Piece::new(kind.def(), 2, RotationKind::Srs)
+                };
+                // the box is in minos, two field cells to a side
+                let (left, top, width, height) = piece.bounds();
+                let (left, top) = (2 * left as i8, 2 * top as i8);
+                let (width, height) = (2 * width as i8, 2 * height as i8);
+                let right = FIELD_WIDTH as i8 - left - width;
+                let floor = FIELD_HEIGHT as i8 - top - height;
+                let at = |x, y| Piece { x, y, ..piece };
+                let what = format!("{:?} turned {}", kind, rot);
+
+                assert!(board.fits(&at(-left, -top)), "{} in the corner", what);
+                assert!(!board.fits(&at(-left - 2, -top)), "{} past the left", what);
+                assert!(board.fits(&at(right, floor)), "{} in the far corner", what);
+                assert!(
+                    !board.fits(&at(right + 2, floor)),
+                    "{} past the right",
+                    what
+                );
+                assert!(
+                    !board.fits(&at(right, floor + 2)),
+                    "{} past the floor",
+                    what
+                );
+                // half a mino over is still over
+                assert!(
+                    !board.fits(&at(right + 1, floor)),
+                    "{} half past the right",
+                    what
+                );
+                assert!(
+                    !board.fits(&at(right, floor + 1)),
+                    "{} half past the floor",
+                    what
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn a_big_piece_stops_on_the_stack_a_mino_up() {
+        let board = Board::from_ascii(
+            "....##....
+             ....##....",
+        )
+        .unwrap();
+        let piece = Piece {
+            x: 2,
+            ..Piece::new(Tetromino::O.def(), 2, RotationKind::Srs)
+        };
+        let (_, top, _, height) = piece.bounds();
+        let resting = FIELD_HEIGHT as i8 - 2 - 2 * (top + height) as i8;
+        assert!(board.fits(&Piece {
+            y: resting,
+            ..piece
+        }));
+        assert!(!board.fits(&Piece {
+            y: resting + 2,
+            ..piece
+        }));
+        assert!(!board.fits(&Piece {
+            y: resting + 1,
+            ..piece
+        }));
+    }
+
+    #[test]
+    fn turning_against_a_wall_kicks_off_it() {
+        let board = Board::default();
+        // standing on end in the first and last columns
+        for &x in &[-2, FIELD_WIDTH as i8 - 3] {
+            let piece = Piece {
+                x,
+                y: 8,
+                rot: 1,
+                ..i_piece()
+            };
+            assert!(board.fits(&piece));
+            for &turn in &[1, 3] {
+                let turned = board.rotate(&piece, turn).expect("no kick found");
+                assert!(inside(&turned), "x {} turn {}", x, turn);
+                assert_eq!(turned.rot, (1 + turn) % 4);
+            }
+        }
+    }
+
+    #[test]
+    fn turning_on_the_floor_kicks_up_off_it() {
+        let board = Board::default();
+        let piece = Piece {
+            y: FIELD_HEIGHT as i8 - 3,
+            rot: 2,
+            ..Piece::new(Tetromino::T.def(), 1, RotationKind::Srs)
+        };
+        assert!(board.fits(&piece));
+        assert!(!board.fits(&Piece {
+            y: piece.y + 1,
+            ..piece
+        }));
+        for &turn in &[1, 3] {
+            let turned = board.rotate(&piece, turn).expect("no kick found");
+            assert!(inside(&turned));
+        }
+    }
+
+    #[test]
+    fn turning_in_a_shaft_is_blocked() {
+        let board = Board::from_ascii(&"####.#####\n".repeat(8)).unwrap();
+        let piece = Piece {
+            x: 2,
+            y: FIELD_HEIGHT as i8 - 4,
+            rot: 1,
+            ..i_piece()
+        };
+        assert!(board.fits(&piece));
+        assert!(board.rotate(&piece, 1).is_none());
+        assert!(board.rotate(&piece, 3).is_none());
+    }
+
+    #[test]
+    fn clears_the_bottom_row() {
+        let mut board = Board::from_ascii(
+            "#.........
+             .#........
+             ##########",
+        )
+        .unwrap();
+        assert_eq!(board.full_rows(), vec![FIELD_HEIGHT as i8 - 1]);
+        board.clear_rows(&board.full_rows());
+        let expected = Board::from_ascii(
+            "#.........
+             .#........",
+        )
+        .unwrap();
+        assert_eq!(board.to_ascii(), expected.to_ascii());
+    }
+
+    #[test]
+    fn clears_the_top_row() {
+        let mut field = "##########\n".to_string();
+        field.push_str(&"#########.\n".repeat(FIELD_HEIGHT as usize - 1));
+        let mut board = Board::from_ascii(&field).unwrap();
+        assert_eq!(board.full_rows(), vec![0]);
+        board.clear_rows(&[0]);
+        let mut expected = "..........\n".to_string();
+        expected.push_str(&"#########.\n".repeat(FIELD_HEIGHT as usize - 1));
+        assert_eq!(board.to_ascii(), expected.trim_end());
+    }
+
+    #[test]
+    fn clears_the_top_and_bottom_rows_together() {
+        let mut field = "##########\n".to_string();
+        field.push_str(&".........#\n".repeat(FIELD_HEIGHT as usize - 2));
+        field.push_str("##########");
+        let mut board = Board::from_ascii(&field).unwrap();
+        board.clear_rows(&board.full_rows());
+        let mut expected = "..........\n".repeat(2);
+        expected.push_str(&".........#\n".repeat(FIELD_HEIGHT as usize - 2));
+        assert_eq!(board.to_ascii(), expected.trim_end());
+    }
+
+    #[test]
+    fn a_blocked_spawn_tops_out() {
+        let board = Board::from_ascii(&"....##....\n".repeat(FIELD_HEIGHT as usize)).unwrap();
+        assert!(board
+            .spawn(Tetromino::O.def(), 1, RotationKind::Srs, 0)
+            .is_none());
+    }
+
+    #[test]
+    fn a_blocked_initial_rotation_spawns_unturned() {
+        // room for the I lying flat in its spawn row, but not standing up
+        let mut field = "..........\n".repeat(2);
+        field.push_str(&"##########\n".repeat(FIELD_HEIGHT as usize - 2));
+        let board = Board::from_ascii(&field).unwrap();
+        let piece = board
+            .spawn(Tetromino::I.def(), 1, RotationKind::Srs, 1)
+            .unwrap();
+        assert_eq!(piece.rot, 0);
+        assert_eq!(
+            board
+                .spawn(Tetromino::I.def(), 1, RotationKind::Srs, 0)
+                .unwrap()
+                .rot,
+            0
+        );
+    }
+
+    #[test]
+    fn locking_over_the_top_drops_the_cells_outside() {
+        let mut board = Board::default();
+        // standing on end, half of it over the top
+        let piece = Piece {
+            y: -2,
+            rot: 1,
+            ..Piece::new(Tetromino::I.def(), 1, RotationKind::Srs)
+        };
+        board.lock(&piece);
+        let inside = piece.cells().filter(|&(_, y)| y >= 0).count();
+        assert_eq!(inside, 2);
+        assert_eq!(
+            board.cells.iter().filter(|&&c| c != Cell::Empty).count(),
+            inside
+        );
+        // nothing wrapped around onto the bottom rows
+        assert!(board.cells[(FIELD_WIDTH * (FIELD_HEIGHT - 2)) as usize..]
+            .iter()
+            .all(|&c| c == Cell::Empty));
+    }
+
+    #[test]
+    fn locking_past_the_sides_does_not_wrap_onto_the_next_row() {
+        for &x in &[-2, FIELD_WIDTH as i8 - 2] {
+            let mut board = Board::default();
+            let piece = Piece {
+                x,
+                y: 5,
+                ..Piece::new(Tetromino::I.def(), 1, RotationKind::Srs)
+            };
+            board.lock(&piece);
+            let filled: Vec<usize> = (0..board.cells.len())
+                .filter(|&i| board.cells[i] != Cell::Empty)
+                .collect();
+            assert_eq!(filled.len(), 2, "x {}", x);
+            for i in filled {
+                let (cx, cy) = (
+                    i as isize % FIELD_WIDTH as isize,
+                    i as isize / FIELD_WIDTH as isize,
+                );
+                assert!(
+                    piece.cells().any(|c| c == (cx, cy)),
+                    "x {} wrapped to {:?}",
+                    x,
+                    (cx, cy)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn a_piece_only_locks_out_inside_the_spawn_rows() {
+        let piece = Piece::new(Tetromino::T.def(), 1, RotationKind::Srs);
+        assert!(locked_out(&piece));
+        assert!(!locked_out(&Piece { y: 1, ..piece }));
+        let big = Piece::new(Tetromino::T.def(), 2, RotationKind::Srs);
+        assert!(locked_out(&big));
+        assert!(!locked_out(&Piece { y: 2, ..big }));
+    }
+
+    #[test]
+    fn garbage_rows_have_one_hole_each() {
+        for seed in 0..50 {
+            let mut board = Board::default();
+            board.add_garbage(9, &mut StdRng::seed_from_u64(seed));
+            for y in 0..FIELD_HEIGHT - 9 {
+                assert!((0..FIELD_WIDTH)
+                    .all(|x| board.cells[(x + y * FIELD_WIDTH) as usize] == Cell::Empty));
+            }
+            for y in FIELD_HEIGHT - 9..FIELD_HEIGHT {
+                assert!(hole(&board, y).is_some(), "seed {} row {}", seed, y);
+            }
+        }
+    }
+
+    #[test]
+    fn garbage_holes_line_up_at_most_twice() {
+        for seed in 0..200 {
+            let mut board = Board::default();
+            board.add_garbage(FIELD_HEIGHT, &mut StdRng::seed_from_u64(seed));
+            let holes: Vec<u32> = (0..FIELD_HEIGHT)
+                .map(|y| hole(&board, y).unwrap())
+                .collect();
+            for run in holes.windows(3) {
+                assert!(
+                    !(run[0] == run[1] && run[1] == run[2]),
+                    "seed {} holes {:?}",
+                    seed,
+                    holes
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn clearing_between_garbage_rows_keeps_the_rest_in_order() {
+        let mut board = Board::from_ascii(
+            "#.........
+             ##########
+             ####.#####
+             ##########
+             #######.##",
+        )
+        .unwrap();
+        board.clear_rows(&board.full_rows());
+        let expected = Board::from_ascii(
+            "#.........
+             ####.#####
+             #######.##",
+        )
+        .unwrap();
+        assert_eq!(board.to_ascii(), expected.to_ascii());
+    }
+
+    #[test]
+    fn raising_pushes_the_stack_up_over_new_garbage() {
+        let mut board = Board::from_ascii(
+            "#.........
+             ##.#######
+             ####.#####",
+        )
+        .unwrap();
+        let before = board.to_ascii();
+        assert!(!board.raise(2, &mut StdRng::seed_from_u64(1)));
+
+        let after = board.to_ascii();
+        let rows = FIELD_HEIGHT as usize;
+        let before: Vec<&str> = before.lines().collect();
+        let after: Vec<&str> = after.lines().collect();
+        // everything moves up two rows, the garbage coming in under it
+        assert_eq!(after[..rows - 2], before[2..]);
+        for y in FIELD_HEIGHT - 2..FIELD_HEIGHT {
+            assert!(hole(&board, y).is_some(), "row {}", y);
+        }
+    }
+
+    #[test]
+    fn raising_anything_off_the_top_tops_out() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut board = Board::default();
+        board.cells[(4 + FIELD_WIDTH) as usize] = Cell::Full(Color::Red);
+        // the second row reaches the top, then goes over it
+        assert!(!board.raise(1, &mut rng));
+        assert_eq!(board.cells[4], Cell::Full(Color::Red));
+        assert!(board.raise(1, &mut rng));
+        assert!(board.cells[..FIELD_WIDTH as usize]
+            .iter()
+            .all(|&c| c == Cell::Empty));
+
+        // more rows than the field has fill it with garbage and no more
+        let mut board = Board::default();
+        assert!(!board.raise(FIELD_HEIGHT + 5, &mut rng));
+        for y in 0..FIELD_HEIGHT {
+            assert!(hole(&board, y).is_some(), "row {}", y);
+        }
+        assert!(board.raise(FIELD_HEIGHT + 5, &mut rng));
+    }
+
+    #[test]
+    fn the_highest_row_is_the_tallest_column() {
+        assert_eq!(Board::default().highest_occupied_row(), FIELD_HEIGHT);
+
+        let mut board = Board::from_ascii("........#.").unwrap();
+        assert_eq!(board.highest_occupied_row(), FIELD_HEIGHT - 1);
+
+        // a lone tall column counts, however low the rest are
+        board = Board::from_ascii(
+            "#.........
+             #.........
+             #......#..
+             #.#######.",
+        )
+        .unwrap();
+        assert_eq!(board.highest_occupied_row(), FIELD_HEIGHT - 4);
+
+        board.cells[FIELD_WIDTH as usize - 1] = Cell::Full(Color::Red);
+        assert_eq!(board.highest_occupied_row(), 0);
+    }
+
+    #[test]
+    fn the_highest_row_falls_as_lines_clear() {
+        let mut board = Board::from_ascii(
+            "....#.....
+             ##########
+             ##########",
+        )
+        .unwrap();
+        assert_eq!(board.highest_occupied_row(), FIELD_HEIGHT - 3);
+        board.clear_rows(&board.full_rows());
+        assert_eq!(board.highest_occupied_row(), FIELD_HEIGHT - 1);
+    }
+}
@@ -0,0 +1,49 @@
+//! Copying text to the system clipboard.
+//!
+//! The windowing library has no clipboard of its own, so the text is handed to
+//! the first of the platform's copy commands that's installed.
+
+use std::error::Error;
+use std::io::{ErrorKind, Write};
+use std::process::{Command, Stdio};
+
+/// Commands that put what they read on the clipboard, in the order they're
+/// tried, with their arguments
+#[cfg(target_os = "macos")]
+const COMMANDS: &[(&str, &[&str])] = &[("pbcopy", &[])];
+#[cfg(windows)]
+const COMMANDS: &[(&str, &[&str])] = &[("clip", &[])];
+#[cfg(not(any(target_os = "macos", windows)))]
+const COMMANDS: &[(&str, &[&str])] = &[
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+];
+
+/// Puts `text` on the clipboard
+pub fn copy(text: &str) -> Result<(), Box<dyn Error>> {
+    for &(program, args) in COMMANDS {
+        let mut child = match Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        // closing its input lets the command know the text is all there
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        let status = child.wait()?;
+        return if status.success() {
+            Ok(())
+        } else {
+            Err(format!("{} failed ({})", program, status).into())
+        };
+    }
+    Err("no clipboard command found".into())
+}
@@ -0,0 +1,385 @@
+//! Options kept in `tetrs.toml` between runs, in the data directory.
+//!
+//! The file is read once at startup and written back whenever an option is
+//! changed from inside the game. Anything missing from it takes its default
+//! and anything it doesn't know about is ignored, so an old file keeps working.
+//! An option whose value doesn't fit takes its default too, without losing the
+//! others.
+//!
+//! Files are stamped with the [version](CONFIG_VERSION) that wrote them, like
+//! the score files are. One from an older version is brought up to date by
+//! [`migrate`] before it's read, for changes that can't be made by adding an
+//! option, like renaming one or changing what its value means.
+
+use std::cell::RefCell;
+use std::error::Error;
+use std::path::Path;
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+use toml::value::Table;
+
+use crate::audio::{Channel, SoundId, SoundVolumes, MAX_VOLUME};
+use crate::game::input::{KeyMap, PadMap};
+use crate::game::limiter::{DEFAULT_FPS_CAP, FPS_CAPS};
+use crate::graphics::post::PIXEL_SCALES;
+use crate::graphics::theme::{COLORBLIND_THEME, DEFAULT_THEME};
+use crate::paths;
+use crate::piece::randomizer::RandomizerKind;
+use crate::piece::rotation::RotationKind;
+use crate::sim::replay::GhostDisplay;
+use crate::sim::{Handling, MAX_START_LEVEL, TICK_MILLIS};
+use crate::ui::layout::UI_SCALES;
+
+/// Name of the config file in the [data directory](paths::data_dir)
+pub const CONFIG_FILE: &str = "tetrs.toml";
+
+/// Version of the config written by this build. Files from before versions
+/// were kept count as version 0
+pub const CONFIG_VERSION: u32 = 1;
+
+/// The config as shared by the states that read and change it
+pub type SharedConfig = Rc<RefCell<Config>>;
+
+/// How the metronome keeps time with gravity
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Metronome {
+    /// Only when turned on during a run, pulsing the field border
+    Off,
+    /// From the start of a run, pulsing the field border
+    Pulse,
+    /// From the start of a run, pulsing the border and ticking along
+    Tick,
+}
+
+impl Default for Metronome {
+    fn default() -> Self {
+        Self::Off
+    }
+}
+
+impl Metronome {
+    pub const ALL: &'static [Metronome] = &[Metronome::Off, Metronome::Pulse, Metronome::Tick];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Pulse => "Pulse",
+            Self::Tick => "Tick",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Version of the file the config was read from, see [`CONFIG_VERSION`]
+    pub version: u32,
+
+    /// Size of the window when it opens, in pixels
+    pub width: u32,
+    pub height: u32,
+
+    /// Whether the window covers the whole screen
+    pub fullscreen: bool,
+
+    /// Whether frames wait for the display to refresh
+    pub vsync: bool,
+
+    /// Whether edges are smoothed with multisampling
+    pub msaa: bool,
+
+    /// Window pixels across each pixel drawn, for a chunky look, or 1 to
+    /// draw at the window's resolution. One of [`PIXEL_SCALES`]
+    pub pixel_scale: u32,
+
+    /// Size of the text and menus, in percent. One of [`UI_SCALES`]
+    pub ui_scale: u32,
+
+    /// Most frames drawn a second, or 0 for as many as the display allows.
+    /// One of [`FPS_CAPS`]
+    pub fps_cap: u32,
+
+    /// Whether big clears jolt the playfield
+    pub screen_shake: bool,
+
+    /// Whether the falling piece glides between cells instead of jumping
+    pub smooth_movement: bool,
+
+    /// Whether pieces drift down behind the main menu
+    pub menu_background: bool,
+
+    /// How much of the fastest sprint is shown racing alongside a sprint
+    pub ghost: GhostDisplay,
+
+    /// Metronome in modes where it's a [free](crate::mode::MetronomeRule::Free)
+    /// practice aid. Elsewhere it starts off, though it still ticks if this
+    /// says so once it's turned on
+    pub metronome: Metronome,
+
+    /// Whether the window title shows the mode and clock of the game going on
+    pub title_info: bool,
+
+    /// Name of the colour theme, built in or from a file in `themes/`
+    pub theme: String,
+
+    /// Whether pieces are drawn in colours safe for colour blindness and
+    /// marked with patterns, whatever the theme
+    pub colorblind: bool,
+
+    /// TrueType font to draw text with instead of the built in bitmap one, if
+    /// not empty. Only builds with the `ttf` feature can use it
+    pub font: String,
+
+    /// Level games are started at
+    pub start_level: u32,
+
+    /// How games pick their pieces
+    pub randomizer: RandomizerKind,
+
+    /// How the pieces turn and kick
+    pub rotation: RotationKind,
+
+    /// How long a sideways key is held before the piece starts sliding, in
+    /// milliseconds (delayed auto shift)
+    pub das_millis: u64,
+
+    /// How long the piece takes to slide each cell, in milliseconds (auto
+    /// repeat rate). Less than a tick slides it all the way at once
+    pub arr_millis: u64,
+
+    /// Volume of everything, in percent, on top of the music and sound effects
+    pub master_volume: u32,
+
+    /// Whether the game is silent, whatever the volumes
+    pub muted: bool,
+
+    /// Volume of the sound effects, in percent
+    pub sfx_volume: u32,
+
+    /// Volume of the music, in percent
+    pub music_volume: u32,
+
+    /// Volume of each sound effect, in percent of [`sfx_volume`](Self::sfx_volume)
+    pub sound_volumes: SoundVolumes,
+
+    /// Keys the game is played with
+    pub keys: KeyMap,
+
+    /// Gamepad buttons the game is played with, alongside the keys
+    pub pad: PadMap,
+
+    /// Whether F8 dumps the game for a bug report in release builds too.
+    /// Debug builds always can
+    pub debug_dumps: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            width: 800,
+            height: 600,
+            fullscreen: false,
+            vsync: false,
+            msaa: false,
+            pixel_scale: 1,
+            ui_scale: 100,
+            fps_cap: DEFAULT_FPS_CAP,
+            screen_shake: true,
+            smooth_movement: true,
+            menu_background: true,
+            ghost: GhostDisplay::default(),
+            metronome: Metronome::default(),
+            title_info: false,
+            theme: DEFAULT_THEME.to_string(),
+            colorblind: false,
+            font: String::new(),
+            start_level: 1,
+            randomizer: RandomizerKind::default(),
+            rotation: RotationKind::default(),
+            das_millis: 2 * TICK_MILLIS,
+            arr_millis: 2 * TICK_MILLIS,
+            master_volume: MAX_VOLUME,
+            muted: false,
+            sfx_volume: MAX_VOLUME,
+            music_volume: MAX_VOLUME * 7 / 10,
+            sound_volumes: SoundVolumes::default(),
+            keys: KeyMap::default(),
+            pad: PadMap::default(),
+            debug_dumps: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut table: Table = toml::from_str(&std::fs::read_to_string(path)?)?;
+        migrate(&mut table);
+        let mut config = Self::from_table(table)?;
+        config.width = config.width.max(1);
+        config.height = config.height.max(1);
+        config.start_level = config.start_level.clamp(1, MAX_START_LEVEL);
+        config.master_volume = config.master_volume.min(MAX_VOLUME);
+        config.sfx_volume = config.sfx_volume.min(MAX_VOLUME);
+        config.music_volume = config.music_volume.min(MAX_VOLUME);
+        if !FPS_CAPS.contains(&config.fps_cap) {
+            config.fps_cap = DEFAULT_FPS_CAP;
+        }
+        if !PIXEL_SCALES.contains(&config.pixel_scale) {
+            config.pixel_scale = 1;
+        }
+        if !UI_SCALES.contains(&config.ui_scale) {
+            config.ui_scale = 100;
+        }
+        Ok(config)
+    }
+
+    /// Config with the options set in `table`, leaving any whose value doesn't
+    /// fit at its default
+    fn from_table(table: Table) -> Result<Self, Box<dyn Error>> {
+        let mut options = match toml::Value::try_from(Self::default())? {
+            toml::Value::Table(options) => options,
+            _ => unreachable!("config isn't a table"),
+        };
+        for (key, value) in table {
+            let previous = options.insert(key.clone(), value);
+            if toml::Value::Table(options.clone())
+                .try_into::<Self>()
+                .is_err()
+            {
+                eprintln!("Ignoring config option {}, its value doesn't fit", key);
+                match previous {
+                    Some(previous) => options.insert(key, previous),
+                    None => options.remove(&key),
+                };
+            }
+        }
+        Ok(toml::Value::Table(options).try_into()?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        paths::create_parent(path)?;
+        std::fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Reads the config at `path`, writing out the defaults if there's none.
+    /// A file that can't be read is moved aside to `<path>.bad` and replaced
+    pub fn load_or_create(path: &Path) -> Self {
+        if !path.exists() {
+            let config = Self::default();
+            config
+                .save(path)
+                .unwrap_or_else(|e| eprintln!("Couldn't write config: {}", e));
+            return config;
+        }
+
+        Self::load(path).unwrap_or_else(|e| {
+            let mut aside = path.as_os_str().to_owned();
+            aside.push(".bad");
+            let aside = std::path::PathBuf::from(aside);
+            eprintln!(
+                "Couldn't read config, moving it to {}: {}",
+                aside.display(),
+                e
+            );
+            if let Err(e) = std::fs::rename(path, &aside) {
+                eprintln!("Couldn't move config aside: {}", e);
+            }
+            let config = Self::default();
+            config
+                .save(path)
+                .unwrap_or_else(|e| eprintln!("Couldn't write config: {}", e));
+            config
+        })
+    }
+
+    /// How frames should be presented, given the vsync setting. Without vsync
+    /// this asks for the lowest latency, which graphics falls back from if the
+    /// surface can't do it
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        if self.vsync {
+            wgpu::PresentMode::Fifo
+        } else {
+            wgpu::PresentMode::Mailbox
+        }
+    }
+
+    /// Theme to draw with, given the theme and colour blindness settings
+    pub fn theme_name(&self) -> &str {
+        if self.colorblind {
+            COLORBLIND_THEME
+        } else {
+            &self.theme
+        }
+    }
+
+    /// Scale the screens are laid out at, given the UI scale setting
+    pub fn layout_scale(&self) -> f32 {
+        self.ui_scale as f32 / 100.0
+    }
+
+    /// Samples per pixel, given the antialiasing setting
+    pub fn sample_count(&self) -> u32 {
+        if self.msaa {
+            crate::graphics::MSAA_SAMPLES
+        } else {
+            1
+        }
+    }
+
+    /// How loud `sound` plays on the effects channel, from 0 for silent to 1
+    /// for full
+    pub fn sound_volume(&self, sound: SoundId) -> f32 {
+        self.sound_volumes.get(sound).min(MAX_VOLUME) as f32 / MAX_VOLUME as f32
+    }
+
+    /// Volume setting of `channel`, in percent
+    pub fn volume_percent(&self, channel: Channel) -> u32 {
+        match channel {
+            Channel::Master => self.master_volume,
+            Channel::Music => self.music_volume,
+            Channel::Effects => self.sfx_volume,
+        }
+    }
+
+    pub fn volume_percent_mut(&mut self, channel: Channel) -> &mut u32 {
+        match channel {
+            Channel::Master => &mut self.master_volume,
+            Channel::Music => &mut self.music_volume,
+            Channel::Effects => &mut self.sfx_volume,
+        }
+    }
+
+    /// How loud `channel` is set to be, from 0 for silent to 1 for full
+    pub fn volume(&self, channel: Channel) -> f32 {
+        self.volume_percent(channel).min(MAX_VOLUME) as f32 / MAX_VOLUME as f32
+    }
+
+    /// Sideways key repeat, rounded down to whole ticks
+    pub fn handling(&self) -> Handling {
+        Handling {
+            das: self.das_millis / TICK_MILLIS,
+            arr: self.arr_millis / TICK_MILLIS,
+        }
+    }
+}
+
+/// Brings the options in `table` from the version of config that wrote them up
+/// to [`CONFIG_VERSION`], one version at a time
+pub fn migrate(table: &mut Table) {
+    let mut version = table
+        .get("version")
+        .and_then(toml::Value::as_integer)
+        .unwrap_or(0);
+    while version < CONFIG_VERSION as i64 {
+        match version {
+            // version 1 only started stamping files, the options are the same
+            0 => (),
+            _ => unreachable!("no migration from config version {}", version),
+        }
+        version += 1;
+    }
+    table.insert("version".to_string(), toml::Value::Integer(version));
+}
@@ -0,0 +1,662 @@
+//! A small, stable way to run the game from another program.
+//!
+//! [`Game`] plays one run: it's made from a [`GameConfig`], told which
+//! buttons go down and come up with [`Game::apply`], and moved along with
+//! [`Game::tick`], [`TICKS_PER_SECOND`] times a second of play. Between ticks
+//! the field, the falling piece, the queue and the score can be read off it,
+//! and [`Game::events`] hands over what the last tick did. Nothing here needs
+//! a window or a GPU, so the crate can be built without its `frontend`
+//! feature to embed just the rules.
+//!
+//! ```
+//! use tet_rs::embed::{Button, Game, GameConfig, GameEvent, Input};
+//! use tet_rs::mode::GameMode;
+//!
+//! let mut config = GameConfig::new(GameMode::Marathon);
+//! config.seed = Some(7);
+//! let mut game = Game::new(config);
+//! game.skip_countdown();
+//!
+//! // hold left until the first piece lands against the wall
+//! game.apply(Input::Press(Button::Left));
+//! let mut locked = false;
+//! while !locked {
+//!     game.tick();
+//!     for event in game.events() {
+//!         match event {
+//!             GameEvent::Locked => locked = true,
+//!             GameEvent::ToppedOut | GameEvent::Ended(_) => panic!("lost already"),
+//!             _ => (),
+//!         }
+//!     }
+//! }
+//! game.apply(Input::Release(Button::Left));
+//!
+//! let (_, rows) = game.field_size();
+//! assert!((0..rows).any(|y| game.cell(0, y).is_some()));
+//! ```
+//!
+//! What's in this module keeps working from one version to the next. Types
+//! marked `#[non_exhaustive]` may grow: match them with a `_` arm and build
+//! them from their constructors. Accessors handing out the rules' own types,
+//! like [`Game::board`] and [`Game::stats`], are for a frontend that draws
+//! the run in full, and change with the rules, as does [`Game::simulation`],
+//! which reaches past all of this for saving and dumping a run.
+
+use crate::board::{Board, Cell, FIELD_HEIGHT, FIELD_WIDTH};
+use crate::game::puzzle::Puzzle;
+use crate::game::stats::GameStats;
+use crate::mode::GameMode;
+use crate::piece::randomizer::RandomizerKind;
+use crate::piece::rotation::RotationKind;
+use crate::piece::set::PieceSet;
+use crate::piece::{Color, Piece, Tetromino};
+use crate::sim::{
+    BoardEffect, BoardEffectType, Event, Handling, Inputs, Simulation, DEFAULT_PREVIEWS,
+};
+
+pub use crate::sim::{Ending, TICKS_PER_SECOND};
+
+/// How a run is set up
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct GameConfig {
+    pub mode: GameMode,
+    /// Pieces shown coming up next
+    pub previews: usize,
+    /// Level to start at, in modes where the level goes up
+    pub start_level: u32,
+    /// Seed the pieces are dealt from, or None for a different run each time
+    pub seed: Option<u64>,
+    pub randomizer: RandomizerKind,
+    pub rotation: RotationKind,
+    /// How held sideways buttons repeat
+    pub handling: Handling,
+}
+
+impl GameConfig {
+    /// A run of `mode` with the game's default settings
+    pub fn new(mode: GameMode) -> Self {
+        Self {
+            mode,
+            previews: DEFAULT_PREVIEWS,
+            start_level: 1,
+            seed: None,
+            randomizer: RandomizerKind::default(),
+            rotation: RotationKind::default(),
+            handling: Handling::default(),
+        }
+    }
+}
+
+/// Something the player can hold down
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Button {
+    Left,
+    Right,
+    /// Soft drop
+    Down,
+    RotateRight,
+    RotateLeft,
+}
+
+impl Button {
+    pub const ALL: &'static [Button] = &[
+        Button::Left,
+        Button::Right,
+        Button::Down,
+        Button::RotateRight,
+        Button::RotateLeft,
+    ];
+}
+
+impl Inputs {
+    /// Whether `button` is among the keys held
+    pub fn is_held(&self, button: Button) -> bool {
+        match button {
+            Button::Left => self.left,
+            Button::Right => self.right,
+            Button::Down => self.down,
+            Button::RotateRight => self.rot_right,
+            Button::RotateLeft => self.rot_left,
+        }
+    }
+}
+
+/// A button going down or coming up. It stays that way for every tick until
+/// it changes again
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Input {
+    Press(Button),
+    Release(Button),
+}
+
+/// Something that happened on the last tick
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum GameEvent {
+    /// A new piece started falling from the top
+    Spawned,
+    /// Gravity moved the falling piece down a row
+    Fell,
+    /// The falling piece moved sideways, by one cell or more
+    Shifted,
+    /// The falling piece turned
+    Rotated,
+    /// Holding down moved the falling piece down a row
+    SoftDropped,
+    /// The piece that just locked was put there with more presses than it
+    /// needed, covering `cells` like [`Game::active_cells`] gives them
+    FinesseFault {
+        cells: Vec<(isize, isize)>,
+        color: Color,
+    },
+    /// The falling piece locked into the stack
+    Locked,
+    /// Rows were filled, counted from the top, and are clearing. They count
+    /// as `lines` lines, worth `score` points, and leave the field empty if
+    /// `perfect`
+    Cleared {
+        rows: Vec<usize>,
+        lines: u32,
+        perfect: bool,
+        score: u64,
+    },
+    /// The level went up to `level`
+    LeveledUp { level: u32 },
+    /// The mode's goal was met, the clock stopping at `millis`
+    GoalReached { millis: u64 },
+    /// The mode was beaten outright. The run is over once that's been shown,
+    /// and [`Ended`](Self::Ended) follows
+    Beaten,
+    /// The stack reached the top. The run is over once that's been shown,
+    /// and [`Ended`](Self::Ended) follows
+    ToppedOut,
+    /// The run ended, and ticking changes nothing more
+    Ended(Ending),
+}
+
+/// One run of the game
+pub struct Game {
+    sim: Simulation,
+    held: Inputs,
+    events: Vec<GameEvent>,
+    /// Whether [`GameEvent::ToppedOut`] has been handed over already
+    topped_out: bool,
+}
+
+impl Game {
+    pub fn new(config: GameConfig) -> Self {
+        let mut sim = Simulation::with_level(
+            config.mode,
+            config.previews,
+            config.start_level,
+            config.seed,
+            config.randomizer,
+        );
+        sim.set_rotation(config.rotation);
+        sim.handling = config.handling;
+        Self::from_simulation(sim)
+    }
+
+    /// Plays on from `sim`, like a run loaded from a save or a puzzle
+    pub fn from_simulation(sim: Simulation) -> Self {
+        Self {
+            sim,
+            held: Inputs::default(),
+            events: Vec::new(),
+            topped_out: false,
+        }
+    }
+
+    /// Presses or lets go of a button, from the next tick on
+    pub fn apply(&mut self, input: Input) {
+        let (button, held) = match input {
+            Input::Press(button) => (button, true),
+            Input::Release(button) => (button, false),
+        };
+        let key = match button {
+            Button::Left => &mut self.held.left,
+            Button::Right => &mut self.held.right,
+            Button::Down => &mut self.held.down,
+            Button::RotateRight => &mut self.held.rot_right,
+            Button::RotateLeft => &mut self.held.rot_left,
+        };
+        *key = held;
+    }
+
+    /// Whether `button` is down
+    pub fn is_held(&self, button: Button) -> bool {
+        self.held.is_held(button)
+    }
+
+    /// Moves the run along by one tick with the buttons held as they are.
+    /// Events the last tick left undrained are dropped
+    pub fn tick(&mut self) {
+        self.tick_detailed();
+    }
+
+    /// Like [`tick`](Self::tick), also returning everything that happened in
+    /// the simulation's own terms. Unlike [`events`](Self::events) these
+    /// change with the rules
+    pub fn tick_detailed(&mut self) -> Vec<Event> {
+        let events = self.sim.step(self.held);
+        self.events.clear();
+        for event in &events {
+            let summary = match event {
+                Event::PieceSpawned { .. } => GameEvent::Spawned,
+                Event::GravityStep => GameEvent::Fell,
+                Event::PieceShifted => GameEvent::Shifted,
+                Event::PieceRotated => GameEvent::Rotated,
+                Event::SoftDropStep => GameEvent::SoftDropped,
+                Event::FinesseFault { piece } => GameEvent::FinesseFault {
+                    cells: piece.cells().collect(),
+                    color: piece.kind.color,
+                },
+                Event::PieceLocked { .. } => GameEvent::Locked,
+                &Event::LinesCleared {
+                    lines,
+                    perfect,
+                    score,
+                } => GameEvent::Cleared {
+                    rows: match &self.sim.effect {
+                        Some(BoardEffect {
+                            ty: BoardEffectType::LinesCleared { lines },
+                            ..
+                        }) => lines.iter().map(|&row| row as usize).collect(),
+                        _ => Vec::new(),
+                    },
+                    lines,
+                    perfect,
+                    score,
+                },
+                Event::LevelUp { level } => GameEvent::LeveledUp { level: *level },
+                Event::GoalReached { millis } => GameEvent::GoalReached { millis: *millis },
+                Event::MarathonCleared => GameEvent::Beaten,
+                Event::Finished(ending) => {
+                    if *ending == Ending::ToppedOut && !self.topped_out {
+                        // ended without a pause to show it
+                        self.topped_out = true;
+                        self.events.push(GameEvent::ToppedOut);
+                    }
+                    GameEvent::Ended(*ending)
+                }
+            };
+            self.events.push(summary);
+        }
+        // topping out is told as it happens, not once it's been shown
+        if self.sim.topped_out() && !self.topped_out {
+            self.topped_out = true;
+            self.events.push(GameEvent::ToppedOut);
+        }
+        events
+    }
+
+    /// What the last tick did, oldest first
+    pub fn events(&mut self) -> impl Iterator<Item = GameEvent> + '_ {
+        self.events.drain(..)
+    }
+
+    /// Columns and rows of the field
+    pub fn field_size(&self) -> (usize, usize) {
+        (FIELD_WIDTH as usize, FIELD_HEIGHT as usize)
+    }
+
+    /// Colour of the locked cell `x` columns from the left and `y` rows from
+    /// the top, or None if it's empty or off the field
+    pub fn cell(&self, x: usize, y: usize) -> Option<Color> {
+        let (width, height) = self.field_size();
+        if x >= width || y >= height {
+            return None;
+        }
+        match self.sim.board.cells[x + y * width] {
+            Cell::Full(color) => Some(color),
+            Cell::Empty => None,
+        }
+    }
+
+    /// Cells the falling piece covers, as columns and rows from the top left.
+    /// Rows above the field are negative. Empty while nothing is falling
+    pub fn active_cells(&self) -> Vec<(isize, isize)> {
+        self.sim
+            .active_piece
+            .map_or_else(Vec::new, |piece| piece.cells().collect())
+    }
+
+    /// Colour of the falling piece, if there is one
+    pub fn active_color(&self) -> Option<Color> {
+        self.sim.active_piece.map(|piece| piece.kind.color)
+    }
+
+    /// Pieces coming up, next first. A piece outside the seven, from a
+    /// custom set, is None
+    pub fn queue(&self) -> Vec<Option<Tetromino>> {
+        self.sim
+            .next_pieces
+            .iter()
+            .map(|piece| piece.tetromino())
+            .collect()
+    }
+
+    /// Piece put on hold. There's no holding in this game yet, so it's
+    /// always None
+    pub fn hold(&self) -> Option<Tetromino> {
+        None
+    }
+
+    pub fn score(&self) -> u64 {
+        self.sim.score
+    }
+
+    pub fn lines(&self) -> u32 {
+        self.sim.lines
+    }
+
+    pub fn level(&self) -> u32 {
+        self.sim.level()
+    }
+
+    /// Whether the run has ended, so ticking changes nothing more
+    pub fn is_over(&self) -> bool {
+        self.sim.ending.is_some()
+    }
+
+    pub fn mode(&self) -> GameMode {
+        self.sim.mode
+    }
+
+    /// Ticks left to count down before the first piece falls
+    pub fn countdown(&self) -> u64 {
+        self.sim.countdown
+    }
+
+    /// Ticks played so far, counting down included
+    pub fn ticker(&self) -> u64 {
+        self.sim.ticker
+    }
+
+    /// Whether the first piece has fallen, so the clock is running
+    pub fn has_started(&self) -> bool {
+        self.sim.start_tick.is_some()
+    }
+
+    /// Ticks since the first piece fell
+    pub fn elapsed_ticks(&self) -> u64 {
+        self.sim.elapsed_ticks()
+    }
+
+    /// Milliseconds the clock stopped at, once a timed goal has been met
+    pub fn finish_time(&self) -> Option<u64> {
+        self.sim.finish_time
+    }
+
+    /// Whether the stack has reached the top, though the run may not have
+    /// ended yet
+    pub fn topped_out(&self) -> bool {
+        self.sim.topped_out()
+    }
+
+    /// Board effect play is waiting on, like lines clearing or the end of
+    /// the run being shown
+    pub fn effect(&self) -> Option<&BoardEffect> {
+        self.sim.effect.as_ref()
+    }
+
+    /// Effects shown while play goes on, oldest first
+    pub fn flourishes(&self) -> &[BoardEffect] {
+        &self.sim.flourishes
+    }
+
+    /// How the run has been played so far
+    pub fn stats(&self) -> &GameStats {
+        &self.sim.stats
+    }
+
+    /// Cells pieces have locked into
+    pub fn board(&self) -> &Board {
+        &self.sim.board
+    }
+
+    /// Tick the cell `x` columns from the left and `y` rows from the top was
+    /// last filled on
+    pub fn locked_at(&self, x: usize, y: usize) -> u64 {
+        self.sim.placed_at[x + y * FIELD_WIDTH as usize]
+    }
+
+    /// The falling piece, if there is one
+    pub fn active_piece(&self) -> Option<Piece> {
+        self.sim.active_piece
+    }
+
+    /// Pieces coming up, next first
+    pub fn next_pieces(&self) -> &[Piece] {
+        &self.sim.next_pieces
+    }
+
+    /// Rows of garbage that haven't been cleared yet
+    pub fn garbage_left(&self) -> u32 {
+        self.sim.garbage_left
+    }
+
+    /// Placements that took more presses than they needed
+    pub fn faults(&self) -> u32 {
+        self.sim.faults
+    }
+
+    /// Whether pieces drop straight onto the stack (20G)
+    pub fn instant_gravity(&self) -> bool {
+        self.sim.instant_gravity
+    }
+
+    /// How much of the wait for the next rising row is left, from 1 down to
+    /// 0, in modes where rows rise
+    pub fn rise_left(&self) -> Option<f32> {
+        if self.sim.rise_ticks > 0 {
+            Some(self.sim.rise_left as f32 / self.sim.rise_ticks as f32)
+        } else {
+            None
+        }
+    }
+
+    /// Seed the pieces are dealt from
+    pub fn seed(&self) -> u64 {
+        self.sim.seed
+    }
+
+    pub fn randomizer(&self) -> RandomizerKind {
+        self.sim.randomizer
+    }
+
+    pub fn rotation(&self) -> RotationKind {
+        self.sim.rotation
+    }
+
+    /// What pieces there are to deal
+    pub fn pieces(&self) -> PieceSet {
+        self.sim.pieces
+    }
+
+    /// Setup being played, in puzzle mode
+    pub fn puzzle(&self) -> Option<&Puzzle> {
+        self.sim.puzzle.as_ref()
+    }
+
+    /// Starts play straight away, without counting down first
+    pub fn skip_countdown(&mut self) {
+        self.sim.countdown = 0;
+    }
+
+    /// Whether placements are checked for finesse
+    pub fn finesse(&self) -> bool {
+        self.sim.finesse
+    }
+
+    /// Checks placements for finesse from now on, or stops
+    pub fn set_finesse(&mut self, on: bool) {
+        self.sim.finesse = on;
+    }
+
+    /// Garbage rows this run's clears have earned against an opponent since
+    /// this was last asked, to send them with [`send_garbage`](Self::send_garbage)
+    pub fn take_garbage(&mut self) -> u32 {
+        std::mem::take(&mut self.sim.garbage_out)
+    }
+
+    /// Pushes `rows` of garbage up under the stack as soon as the field isn't
+    /// busy clearing lines, like an opponent's clears do. More than the
+    /// field's height is the same as its height
+    pub fn send_garbage(&mut self, rows: u32) {
+        self.sim.garbage_in = self.sim.garbage_in.saturating_add(rows.min(FIELD_HEIGHT));
+    }
+
+    /// Whether the tools below can be used, as only practice runs allow
+    pub fn is_practice(&self) -> bool {
+        self.sim.mode == GameMode::Practice
+    }
+
+    /// In practice, deals the piece `index` in the set being played next.
+    /// Does nothing in other runs or past the end of the set
+    pub fn pick_next(&mut self, index: usize) {
+        if let (true, Some(kind)) = (self.is_practice(), self.sim.pieces.pieces.get(index)) {
+            self.sim.pick_next(kind);
+        }
+    }
+
+    /// Whether gravity is switched off, as it can be in practice
+    pub fn is_frozen(&self) -> bool {
+        self.sim.frozen
+    }
+
+    /// In practice, switches gravity off, or back on
+    pub fn set_frozen(&mut self, frozen: bool) {
+        if self.is_practice() {
+            self.sim.frozen = frozen;
+        }
+    }
+
+    /// In practice, empties the field
+    pub fn clear_field(&mut self) {
+        if self.is_practice() {
+            self.sim.clear_board();
+        }
+    }
+
+    /// In practice, takes back the last piece placed, which comes next
+    /// again
+    pub fn undo(&mut self) {
+        if self.is_practice() {
+            self.sim.undo();
+        }
+    }
+
+    /// The rules underneath, for saving or dumping the run
+    pub fn simulation(&self) -> &Simulation {
+        &self.sim
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An endless run from a fixed seed, ticked until its first piece falls
+    fn spawned() -> Game {
+        let mut config = GameConfig::new(GameMode::Endless);
+        config.seed = Some(1);
+        let mut game = Game::new(config);
+        game.skip_countdown();
+        while game.active_cells().is_empty() {
+            game.tick();
+        }
+        game
+    }
+
+    #[test]
+    fn topping_out_is_told_as_it_happens_and_the_end_once_shown() {
+        let mut game = spawned();
+        game.send_garbage(FIELD_HEIGHT);
+        game.tick();
+        let events: Vec<GameEvent> = game.events().collect();
+        assert_eq!(events, vec![GameEvent::ToppedOut]);
+        assert!(!game.is_over());
+
+        let mut ticks = 1;
+        let events = loop {
+            game.tick();
+            let events: Vec<GameEvent> = game.events().collect();
+            if !events.is_empty() {
+                break events;
+            }
+            ticks += 1;
+        };
+        assert_eq!(events, vec![GameEvent::Ended(Ending::ToppedOut)]);
+        assert_eq!(ticks, 3 * TICKS_PER_SECOND);
+        assert!(game.is_over());
+    }
+
+    #[test]
+    fn garbage_sent_is_capped_at_the_field_height() {
+        let mut game = spawned();
+        game.send_garbage(u32::MAX);
+        game.send_garbage(3);
+        assert_eq!(game.sim.garbage_in, FIELD_HEIGHT + 3);
+
+        // however much piles up, it never wraps around
+        game.sim.garbage_in = u32::MAX - 1;
+        game.send_garbage(u32::MAX);
+        assert_eq!(game.sim.garbage_in, u32::MAX);
+        game.tick();
+        assert_eq!(
+            game.events().collect::<Vec<_>>(),
+            vec![GameEvent::ToppedOut]
+        );
+    }
+
+    #[test]
+    fn locked_cells_remember_the_tick_they_locked_on() {
+        let mut game = spawned();
+        game.apply(Input::Press(Button::Down));
+        let mut cells = game.active_cells();
+        while !game.events().any(|event| event == GameEvent::Locked) {
+            cells = game.active_cells();
+            game.tick();
+        }
+        let locked = game.ticker();
+        for (x, y) in cells {
+            assert!(game.cell(x as usize, y as usize).is_some());
+            assert_eq!(game.locked_at(x as usize, y as usize), locked);
+        }
+    }
+
+    /// Index in the set of a piece that isn't the one coming next
+    fn other_than_next(game: &Game) -> usize {
+        let next = game.sim.next_pieces[0].kind;
+        (0..game.sim.pieces.pieces.len())
+            .find(|&i| !std::ptr::eq(next, &game.sim.pieces.pieces[i]))
+            .unwrap()
+    }
+
+    #[test]
+    fn practice_tools_only_work_in_practice() {
+        let mut game = spawned();
+        let next = game.sim.next_pieces[0].kind;
+        game.pick_next(other_than_next(&game));
+        game.set_frozen(true);
+        assert!(std::ptr::eq(game.sim.next_pieces[0].kind, next));
+        assert!(!game.is_frozen());
+
+        let mut config = GameConfig::new(GameMode::Practice);
+        config.seed = Some(1);
+        let mut game = Game::new(config);
+        let index = other_than_next(&game);
+        game.pick_next(index);
+        game.set_frozen(true);
+        assert!(std::ptr::eq(
+            game.sim.next_pieces[0].kind,
+            &game.sim.pieces.pieces[index]
+        ));
+        assert!(game.is_frozen());
+    }
+}
@@ -1,14 +1,110 @@
-use crate::graphics::GraphicsState;
+#[cfg(feature = "frontend")]
+use std::any::Any;
 
+#[cfg(feature = "frontend")]
+use crate::audio::{Music, SoundId};
+#[cfg(feature = "frontend")]
+use crate::game::input::InputSnapshot;
+#[cfg(feature = "frontend")]
+use crate::graphics::{post::Effect, Frame, GraphicsState};
+
+pub mod achievements;
+#[cfg(feature = "frontend")]
+pub mod gamepad;
+#[cfg(feature = "frontend")]
+pub mod input;
+pub mod limiter;
+pub mod net;
+pub mod puzzle;
+pub mod session;
+pub mod stats;
+pub mod timestep;
+
+#[cfg(feature = "frontend")]
 pub enum StateChange {
     None,
     Quit,
     Push(Box<dyn GameState>),
     Pop,
+    /// Pops, handing the value to the state underneath through
+    /// [`GameState::on_result`]
+    PopWith(Box<dyn Any>),
     Swap(Box<dyn GameState>),
 }
 
+#[cfg(feature = "frontend")]
 pub trait GameState {
-    fn update(&mut self, window: &glfw::Window, dt: std::time::Duration) -> StateChange;
-    fn render(&self, graphics: &GraphicsState) -> Result<(), wgpu::SwapChainError>;
+    fn update(&mut self, input: &InputSnapshot, dt: std::time::Duration) -> StateChange;
+    fn render(&self, graphics: &GraphicsState, frame: &mut Frame);
+
+    /// Whether the states underneath still show through this one, in which
+    /// case they're drawn first
+    fn is_overlay(&self) -> bool {
+        false
+    }
+
+    /// Effect an overlay wants the states underneath drawn through, such as
+    /// dimming the game behind a menu. They're drawn as they are if it's None
+    fn backdrop(&self) -> Option<Effect> {
+        None
+    }
+
+    /// Called when the state above this one pops with a value. Whatever it
+    /// returns is carried out straight away
+    fn on_result(&mut self, _result: Box<dyn Any>) -> StateChange {
+        StateChange::None
+    }
+
+    /// Called when the state goes onto the stack, before its first update
+    fn on_enter(&mut self) {}
+
+    /// Called when the state leaves the stack, by a pop or a swap
+    fn on_exit(&mut self) {}
+
+    /// Called when another state is pushed on top of this one
+    fn on_pause(&mut self) {}
+
+    /// Called when the state above this one pops, making this the top again
+    fn on_resume(&mut self) {}
+
+    /// Called on the topmost state when the window comes back after being
+    /// minimised, sent to the background or out of focus
+    fn on_return(&mut self) {}
+
+    /// Called on every state in the stack when the window changes size
+    fn on_resize(&mut self, _width: u32, _height: u32) {}
+
+    /// Called on every state in the stack, top first, when the window closes
+    fn on_quit(&mut self) {}
+
+    /// What's going on in this state, for the window title. The topmost
+    /// state with something to say is shown
+    fn title_info(&self) -> Option<String> {
+        None
+    }
+
+    /// Sounds the state wants played since it was last asked, in order.
+    /// Only the topmost state is asked, once a frame after its update
+    fn take_sounds(&mut self) -> Vec<SoundId> {
+        Vec::new()
+    }
+
+    /// Whether the state is taking typed text or a key to bind, which leaves
+    /// hotkeys like muting to it
+    fn takes_text(&self) -> bool {
+        false
+    }
+
+    /// Whether the state looks the same from frame to frame while it's left
+    /// alone, so it can be drawn less often once input stops. Only the
+    /// topmost state is asked
+    fn is_static(&self) -> bool {
+        false
+    }
+
+    /// Music to play while the state is in the stack. The topmost state that
+    /// asks for some is heard, and if none does it's quiet
+    fn music(&self) -> Option<Music> {
+        None
+    }
 }
@@ -0,0 +1,588 @@
+//! Achievements and the lifetime progress they are derived from.
+//!
+//! Each achievement is a row of [`Achievement::ALL`] with a [`Goal`], so adding
+//! one is a matter of adding a row. The game keeps a [`RunStats`] up to date as
+//! a run goes on; [`evaluate`] says which goals it and the lifetime [`Totals`]
+//! meet, and [`Achievements`] remembers which have been unlocked. Unlocks are
+//! re-derived from the totals whenever progress is loaded, so an unlock that
+//! was missed (e.g. by a crash before saving) fixes itself.
+
+use std::convert::TryInto;
+use std::io::Read;
+use std::path::Path;
+
+use crate::embed::GameEvent;
+use crate::mode::GameMode;
+
+/// What has to happen for an achievement to unlock
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Goal {
+    /// Clear at least `lines` lines with one piece
+    Clear { lines: u32 },
+    /// Leave the field completely empty after a clear
+    PerfectClear,
+    /// Finish a sprint in under `millis` milliseconds
+    Sprint { millis: u64 },
+    /// Reach the end of a marathon
+    MarathonCleared,
+    /// Climb to `level` in one run
+    Level(u32),
+    /// Play `millis` milliseconds of one run at 20G
+    InstantGravity { millis: u64 },
+    /// Clear `lines` lines over every run
+    TotalLines(u32),
+    /// Finish `games` runs
+    GamesFinished(u32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Achievement {
+    pub name: &'static str,
+    pub goal: Goal,
+}
+
+impl Achievement {
+    /// Every achievement, in the order they're listed. Unlocks are stored by
+    /// position, so new ones go at the end
+    pub const ALL: &'static [Achievement] = &[
+        Achievement {
+            name: "First Tetris",
+            goal: Goal::Clear { lines: 4 },
+        },
+        Achievement {
+            name: "Perfect Clear",
+            goal: Goal::PerfectClear,
+        },
+        Achievement {
+            name: "Quick Sprint",
+            goal: Goal::Sprint { millis: 60_000 },
+        },
+        Achievement {
+            name: "Marathoner",
+            goal: Goal::MarathonCleared,
+        },
+        Achievement {
+            name: "Regular",
+            goal: Goal::GamesFinished(100),
+        },
+        Achievement {
+            name: "Centurion",
+            goal: Goal::TotalLines(100),
+        },
+        Achievement {
+            name: "Level 10",
+            goal: Goal::Level(10),
+        },
+        Achievement {
+            name: "Sprinter",
+            goal: Goal::Sprint { millis: 180_000 },
+        },
+        Achievement {
+            name: "Gravity Well",
+            goal: Goal::InstantGravity { millis: 300_000 },
+        },
+    ];
+
+    fn bit(&self) -> u32 {
+        1 << Self::ALL.iter().position(|a| a == self).unwrap()
+    }
+}
+
+/// What the run being played has done, as far as achievements go
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RunStats {
+    /// Most lines a single piece cleared
+    pub best_clear: u32,
+
+    /// Whether a clear left the field empty
+    pub perfect_clear: bool,
+
+    /// Time a sprint was finished in
+    pub sprint_millis: Option<u64>,
+
+    /// Whether a marathon was played to the end
+    pub marathon_cleared: bool,
+
+    /// Highest level climbed to, not counting the one the run started on
+    pub level: u32,
+
+    /// Time played at 20G
+    pub instant_gravity_millis: u64,
+}
+
+/// Progress over every run
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Totals {
+    /// Runs played to the end
+    pub games_finished: u32,
+
+    /// Lines cleared over every run
+    pub lines_cleared: u32,
+}
+
+impl RunStats {
+    /// Takes in something that happened during a run of `mode`
+    pub fn observe(&mut self, mode: GameMode, event: &GameEvent) {
+        match *event {
+            GameEvent::LeveledUp { level } => self.level = self.level.max(level),
+            GameEvent::Cleared { lines, perfect, .. } => {
+                self.best_clear = self.best_clear.max(lines);
+                self.perfect_clear |= perfect;
+            }
+            GameEvent::GoalReached { millis } if mode == GameMode::Sprint => {
+                self.sprint_millis = Some(millis)
+            }
+            GameEvent::Beaten => self.marathon_cleared = true,
+            _ => (),
+        }
+    }
+}
+
+/// Whether a run of `mode` counts towards achievements. Unranked modes don't,
+/// and neither do runs that were `assisted`
+pub fn counts(mode: GameMode, assisted: bool) -> bool {
+    mode.is_ranked() && !assisted
+}
+
+impl Goal {
+    /// Whether `run`, or the `totals` over every run, meet the goal
+    pub fn met(self, run: &RunStats, totals: &Totals) -> bool {
+        match self {
+            Self::Clear { lines } => run.best_clear >= lines,
+            Self::PerfectClear => run.perfect_clear,
+            Self::Sprint { millis } => run.sprint_millis.map_or(false, |m| m < millis),
+            Self::MarathonCleared => run.marathon_cleared,
+            Self::Level(level) => run.level >= level,
+            Self::InstantGravity { millis } => run.instant_gravity_millis >= millis,
+            Self::TotalLines(lines) => totals.lines_cleared >= lines,
+            Self::GamesFinished(games) => totals.games_finished >= games,
+        }
+    }
+}
+
+/// Every achievement whose goal `run` and `totals` meet, unlocked already or not
+pub fn evaluate(run: &RunStats, totals: &Totals) -> Vec<&'static Achievement> {
+    Achievement::ALL
+        .iter()
+        .filter(|a| a.goal.met(run, totals))
+        .collect()
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Achievements {
+    pub totals: Totals,
+
+    /// Bit set of unlocked achievements, by position in [`Achievement::ALL`]
+    unlocked: u32,
+}
+
+impl Achievements {
+    pub fn is_unlocked(&self, achievement: &Achievement) -> bool {
+        self.unlocked & achievement.bit() != 0
+    }
+
+    /// How far along an achievement is, as (current, goal)
+    pub fn progress(&self, achievement: &Achievement) -> (u32, u32) {
+        match achievement.goal {
+            Goal::GamesFinished(games) => (self.totals.games_finished.min(games), games),
+            Goal::TotalLines(lines) => (self.totals.lines_cleared.min(lines), lines),
+            _ => (self.is_unlocked(achievement) as u32, 1),
+        }
+    }
+
+    /// Unlocks whatever `run` has earned so far, returning what's new
+    pub fn record(&mut self, run: &RunStats) -> Vec<&'static Achievement> {
+        let mut earned = evaluate(run, &self.totals);
+        earned.retain(|a| self.unlock(a));
+        earned
+    }
+
+    /// Adds `run`, which cleared `lines`, to the totals now that it's over,
+    /// returning what it unlocked
+    pub fn finish_run(&mut self, run: &RunStats, lines: u32) -> Vec<&'static Achievement> {
+        self.totals.games_finished = self.totals.games_finished.saturating_add(1);
+        self.totals.lines_cleared = self.totals.lines_cleared.saturating_add(lines);
+        self.record(run)
+    }
+
+    /// Marks `achievement` as unlocked, returning whether it wasn't already
+    fn unlock(&mut self, achievement: &Achievement) -> bool {
+        let new = !self.is_unlocked(achievement);
+        self.unlocked |= achievement.bit();
+        new
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut file = std::fs::File::open(path)?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+
+        // header, games finished, unlocked set, then lines cleared, which
+        // files from before it was counted don't have
+        let valid_length = contents.len() == 16 || contents.len() == 20;
+        if !valid_length || &contents[0..8] != b"tet.rs a" {
+            return Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "invalid format",
+            )));
+        }
+
+        let lines_cleared = match contents.get(16..20) {
+            Some(bytes) => u32::from_le_bytes(bytes.try_into()?),
+            None => 0,
+        };
+        let mut achievements = Self {
+            totals: Totals {
+                games_finished: u32::from_le_bytes(contents[8..12].try_into()?),
+                lines_cleared,
+            },
+            unlocked: u32::from_le_bytes(contents[12..16].try_into()?),
+        };
+        achievements.record(&RunStats::default());
+
+        Ok(achievements)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let mut contents = Vec::with_capacity(20);
+        contents.extend_from_slice(b"tet.rs a");
+        contents.extend_from_slice(&self.totals.games_finished.to_le_bytes());
+        contents.extend_from_slice(&self.unlocked.to_le_bytes());
+        contents.extend_from_slice(&self.totals.lines_cleared.to_le_bytes());
+        crate::paths::create_parent(path)?;
+        std::fs::write(path, contents)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(unlocked: &[&'static Achievement]) -> Vec<&'static str> {
+        unlocked.iter().map(|a| a.name).collect()
+    }
+
+    /// Runs `events` of a run of `mode` through fresh progress, returning
+    /// what they unlocked
+    fn unlocked_by(mode: GameMode, events: &[GameEvent]) -> Vec<&'static str> {
+        let mut run = RunStats::default();
+        let mut achievements = Achievements::default();
+        let mut unlocked = Vec::new();
+        for event in events {
+            run.observe(mode, event);
+            unlocked.extend(names(&achievements.record(&run)));
+        }
+        unlocked
+    }
+
+    fn cleared(lines: u32, perfect: bool) -> GameEvent {
+        GameEvent::Cleared {
+            rows: Vec::new(),
+            lines,
+            perfect,
+            score: 0,
+        }
+    }
+
+    #[test]
+    fn a_tetris_unlocks_only_first_tetris() {
+        let events = [cleared(1, false), cleared(4, false), cleared(4, false)];
+        assert_eq!(
+            unlocked_by(GameMode::Endless, &events),
+            vec!["First Tetris"]
+        );
+    }
+
+    #[test]
+    fn a_perfect_clear_unlocks_perfect_clear() {
+        let events = [cleared(2, false), cleared(2, true)];
+        assert_eq!(
+            unlocked_by(GameMode::Endless, &events),
+            vec!["Perfect Clear"]
+        );
+    }
+
+    #[test]
+    fn a_fast_sprint_unlocks_both_sprint_goals() {
+        let events = [cleared(3, false), GameEvent::GoalReached { millis: 59_999 }];
+        assert_eq!(
+            unlocked_by(GameMode::Sprint, &events),
+            vec!["Quick Sprint", "Sprinter"]
+        );
+    }
+
+    #[test]
+    fn a_slow_sprint_unlocks_only_the_slower_goal() {
+        let events = [GameEvent::GoalReached { millis: 60_000 }];
+        assert_eq!(unlocked_by(GameMode::Sprint, &events), vec!["Sprinter"]);
+    }
+
+    #[test]
+    fn goals_reached_outside_sprint_are_not_sprint_times() {
+        let events = [GameEvent::GoalReached { millis: 1_000 }];
+        assert!(unlocked_by(GameMode::Cheese { rows: 3 }, &events).is_empty());
+    }
+
+    #[test]
+    fn levels_unlock_at_ten() {
+        let events: Vec<GameEvent> = (2..=11)
+            .map(|level| GameEvent::LeveledUp { level })
+            .collect();
+        assert_eq!(
+            unlocked_by(GameMode::Endless, &events[..8]),
+            Vec::<&str>::new()
+        );
+        assert_eq!(unlocked_by(GameMode::Endless, &events), vec!["Level 10"]);
+    }
+
+    #[test]
+    fn finishing_a_marathon_unlocks_marathoner() {
+        let events = [GameEvent::Beaten];
+        assert_eq!(unlocked_by(GameMode::Marathon, &events), vec!["Marathoner"]);
+    }
+
+    #[test]
+    fn events_that_change_nothing_unlock_nothing() {
+        let events = [
+            GameEvent::Fell,
+            GameEvent::Shifted,
+            GameEvent::Rotated,
+            GameEvent::SoftDropped,
+        ];
+        assert!(unlocked_by(GameMode::Endless, &events).is_empty());
+    }
+
+    #[test]
+    fn unlocks_are_only_announced_once() {
+        let mut run = RunStats::default();
+        let mut achievements = Achievements::default();
+        run.observe(GameMode::Endless, &cleared(4, false));
+        assert_eq!(names(&achievements.record(&run)), vec!["First Tetris"]);
+        assert!(achievements.record(&run).is_empty());
+        assert!(achievements.is_unlocked(&Achievement::ALL[0]));
+    }
+
+    #[test]
+    fn lifetime_goals_count_finished_runs() {
+        let mut achievements = Achievements::default();
+        let run = RunStats::default();
+        for _ in 0..99 {
+            assert!(achievements.finish_run(&run, 1).is_empty());
+        }
+        assert_eq!(
+            names(&achievements.finish_run(&run, 1)),
+            vec!["Regular", "Centurion"]
+        );
+        assert_eq!(achievements.progress(&Achievement::ALL[4]), (100, 100));
+    }
+
+    #[test]
+    fn loading_unlocks_what_the_totals_earned() {
+        let path = std::env::temp_dir().join(format!(
+            "tetrs_achievements_test_{}.bin",
+            std::process::id()
+        ));
+        let mut achievements = Achievements::default();
+        // as if the game went down between counting the lines and unlocking
+        achievements.totals.lines_cleared = 150;
+        achievements.save(&path).unwrap();
+        let loaded = Achievements::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let centurion = Achievement::ALL
+            .iter()
+            .find(|a| a.name == "Centurion")
+            .unwrap();
+        assert!(loaded.is_unlocked(centurion));
+        assert_eq!(loaded.totals, achievements.totals);
+    }
+
+    #[test]
+    fn assisted_and_unranked_runs_do_not_count() {
+        assert!(counts(GameMode::Sprint, false));
+        assert!(!counts(GameMode::Sprint, true));
+        assert!(!counts(GameMode::Zen, false));
+        assert!(!counts(GameMode::Practice, false));
+    }
+
+    /// Progress just short of `goal`, and just enough to meet it
+    fn short_of_and_meeting(goal: Goal) -> [(RunStats, Totals); 2] {
+        let run = RunStats::default();
+        let totals = Totals::default();
+        match goal {
+            Goal::Clear { lines } => [
+                (
+                    RunStats {
+                        best_clear: lines - 1,
+                        ..run
+                    },
+                    totals,
+                ),
+                (
+                    RunStats {
+                        best_clear: lines,
+                        ..run
+                    },
+                    totals,
+                ),
+            ],
+            Goal::PerfectClear => [
+                (run, totals),
+                (
+                    RunStats {
+                        perfect_clear: true,
+                        ..run
+                    },
+                    totals,
+                ),
+            ],
+            Goal::Sprint { millis } => [
+                (
+                    RunStats {
+                        sprint_millis: Some(millis),
+                        ..run
+                    },
+                    totals,
+                ),
+                (
+                    RunStats {
+                        sprint_millis: Some(millis - 1),
+                        ..run
+                    },
+                    totals,
+                ),
+            ],
+            Goal::MarathonCleared => [
+                (run, totals),
+                (
+                    RunStats {
+                        marathon_cleared: true,
+                        ..run
+                    },
+                    totals,
+                ),
+            ],
+            Goal::Level(level) => [
+                (
+                    RunStats {
+                        level: level - 1,
+                        ..run
+                    },
+                    totals,
+                ),
+                (RunStats { level, ..run }, totals),
+            ],
+            Goal::InstantGravity { millis } => [
+                (
+                    RunStats {
+                        instant_gravity_millis: millis - 1,
+                        ..run
+                    },
+                    totals,
+                ),
+                (
+                    RunStats {
+                        instant_gravity_millis: millis,
+                        ..run
+                    },
+                    totals,
+                ),
+            ],
+            Goal::TotalLines(lines) => [
+                (
+                    run,
+                    Totals {
+                        lines_cleared: lines - 1,
+                        ..totals
+                    },
+                ),
+                (
+                    run,
+                    Totals {
+                        lines_cleared: lines,
+                        ..totals
+                    },
+                ),
+            ],
+            Goal::GamesFinished(games) => [
+                (
+                    run,
+                    Totals {
+                        games_finished: games - 1,
+                        ..totals
+                    },
+                ),
+                (
+                    run,
+                    Totals {
+                        games_finished: games,
+                        ..totals
+                    },
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn each_goal_is_met_right_at_its_mark() {
+        for achievement in Achievement::ALL {
+            let [(run, totals), (run_met, totals_met)] = short_of_and_meeting(achievement.goal);
+            assert!(
+                !achievement.goal.met(&run, &totals),
+                "{} met too early",
+                achievement.name
+            );
+            assert!(
+                achievement.goal.met(&run_met, &totals_met),
+                "{} not met",
+                achievement.name
+            );
+            assert!(names(&evaluate(&run_met, &totals_met)).contains(&achievement.name));
+        }
+    }
+
+    #[test]
+    fn nothing_done_meets_no_goal() {
+        assert!(evaluate(&RunStats::default(), &Totals::default()).is_empty());
+    }
+
+    #[test]
+    fn five_minutes_at_20g_unlocks_gravity_well() {
+        let mut achievements = Achievements::default();
+        let run = RunStats {
+            instant_gravity_millis: 299_999,
+            ..RunStats::default()
+        };
+        assert!(achievements.record(&run).is_empty());
+        let run = RunStats {
+            instant_gravity_millis: 300_000,
+            ..run
+        };
+        assert_eq!(names(&achievements.record(&run)), vec!["Gravity Well"]);
+    }
+
+    #[test]
+    fn a_damaged_file_is_turned_away() {
+        let path = std::env::temp_dir().join(format!(
+            "tetrs_achievements_damaged_{}.bin",
+            std::process::id()
+        ));
+        let mut achievements = Achievements::default();
+        achievements.finish_run(&RunStats::default(), 10);
+        achievements.save(&path).unwrap();
+        let good = std::fs::read(&path).unwrap();
+
+        for bad in &[&good[..19], &good[..12], &b"tet.rs b"[..]] {
+            std::fs::write(&path, bad).unwrap();
+            assert!(Achievements::load(&path).is_err());
+        }
+        // but files from before lines were counted still load
+        std::fs::write(&path, &good[..16]).unwrap();
+        let old = Achievements::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(old.totals.games_finished, 1);
+        assert_eq!(old.totals.lines_cleared, 0);
+    }
+}
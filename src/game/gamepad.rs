@@ -0,0 +1,162 @@
+//! Gamepads, read through gilrs and fed into the [`InputSnapshot`] as
+//! [`PadButton`]s next to the keyboard.
+//!
+//! Every connected pad counts as one: a button is down while it's down on any
+//! of them. The left stick presses the d-pad buttons once it's tilted past
+//! [`STICK_DEADZONE`], so states never see analog input. Pads can come and go
+//! mid-game; one that's unplugged lets go of everything it held.
+//!
+//! Builds without the `gamepad` feature, and machines where gilrs can't start,
+//! get [`Gamepads`] that never press anything.
+
+#[cfg(feature = "gamepad")]
+use std::collections::HashMap;
+
+use super::input::{InputSnapshot, PadButton};
+
+/// How far the left stick has to tilt along an axis to press that way on the
+/// d-pad, out of 1
+pub const STICK_DEADZONE: f32 = 0.5;
+
+/// Buttons down and where the left stick points, on one pad
+#[cfg(feature = "gamepad")]
+#[derive(Clone, Debug, Default)]
+struct PadState {
+    buttons: Vec<PadButton>,
+    stick: [f32; 2],
+}
+
+#[cfg(feature = "gamepad")]
+impl PadState {
+    /// Buttons down, the stick counting as the d-pad
+    fn down(&self) -> impl Iterator<Item = PadButton> + '_ {
+        let [x, y] = self.stick;
+        let stick = [
+            (x <= -STICK_DEADZONE, PadButton::DPadLeft),
+            (x >= STICK_DEADZONE, PadButton::DPadRight),
+            // up is positive
+            (y >= STICK_DEADZONE, PadButton::DPadUp),
+            (y <= -STICK_DEADZONE, PadButton::DPadDown),
+        ];
+        let tilted: Vec<PadButton> = stick
+            .iter()
+            .filter(|&&(tilted, _)| tilted)
+            .map(|&(_, button)| button)
+            .collect();
+        self.buttons.iter().copied().chain(tilted)
+    }
+}
+
+/// Every gamepad plugged in
+pub struct Gamepads {
+    #[cfg(feature = "gamepad")]
+    gilrs: Option<gilrs::Gilrs>,
+    #[cfg(feature = "gamepad")]
+    pads: HashMap<gilrs::GamepadId, PadState>,
+    /// Buttons the snapshot was last told are down
+    down: Vec<PadButton>,
+}
+
+impl Gamepads {
+    /// Starts listening for gamepads. Without the `gamepad` feature, or if
+    /// that fails, there just aren't any
+    pub fn new() -> Self {
+        #[cfg(feature = "gamepad")]
+        {
+            let gilrs = gilrs::Gilrs::new()
+                .map_err(|e| eprintln!("Warning: no gamepads, couldn't read them: {}", e))
+                .ok();
+            Self {
+                gilrs,
+                pads: HashMap::new(),
+                down: Vec::new(),
+            }
+        }
+        #[cfg(not(feature = "gamepad"))]
+        Self { down: Vec::new() }
+    }
+
+    /// Reads what the pads did since the last poll, pressing and releasing
+    /// buttons in `snapshot` to match
+    pub fn poll(&mut self, snapshot: &mut InputSnapshot) {
+        #[cfg(feature = "gamepad")]
+        {
+            use gilrs::{Axis, EventType};
+
+            let gilrs = match &mut self.gilrs {
+                Some(gilrs) => gilrs,
+                None => return,
+            };
+            while let Some(event) = gilrs.next_event() {
+                let pad = self.pads.entry(event.id).or_default();
+                match event.event {
+                    EventType::ButtonPressed(button, _) => {
+                        if let Some(button) = pad_button(button) {
+                            if !pad.buttons.contains(&button) {
+                                pad.buttons.push(button);
+                            }
+                        }
+                    }
+                    EventType::ButtonReleased(button, _) => {
+                        if let Some(button) = pad_button(button) {
+                            pad.buttons.retain(|&b| b != button);
+                        }
+                    }
+                    EventType::AxisChanged(Axis::LeftStickX, value, _) => pad.stick[0] = value,
+                    EventType::AxisChanged(Axis::LeftStickY, value, _) => pad.stick[1] = value,
+                    EventType::Disconnected => {
+                        self.pads.remove(&event.id);
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        #[cfg(feature = "gamepad")]
+        let down: Vec<PadButton> = self.pads.values().flat_map(PadState::down).collect();
+        #[cfg(not(feature = "gamepad"))]
+        let down: Vec<PadButton> = Vec::new();
+
+        for &button in &down {
+            if !self.down.contains(&button) {
+                snapshot.press_pad(button);
+            }
+        }
+        for &button in &self.down {
+            if !down.contains(&button) {
+                snapshot.release_pad(button);
+            }
+        }
+        self.down = down;
+    }
+}
+
+impl Default for Gamepads {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The button gilrs calls `button`, if it's one the game reads
+#[cfg(feature = "gamepad")]
+fn pad_button(button: gilrs::Button) -> Option<PadButton> {
+    use gilrs::Button;
+
+    Some(match button {
+        Button::South => PadButton::South,
+        Button::East => PadButton::East,
+        Button::West => PadButton::West,
+        Button::North => PadButton::North,
+        // gilrs calls the shoulder buttons triggers, and the triggers
+        // second triggers
+        Button::LeftTrigger => PadButton::LeftShoulder,
+        Button::RightTrigger => PadButton::RightShoulder,
+        Button::Select => PadButton::Select,
+        Button::Start => PadButton::Start,
+        Button::DPadUp => PadButton::DPadUp,
+        Button::DPadDown => PadButton::DPadDown,
+        Button::DPadLeft => PadButton::DPadLeft,
+        Button::DPadRight => PadButton::DPadRight,
+        _ => return None,
+    })
+}
@@ -0,0 +1,706 @@
+//! Rules for turning held keys into movement.
+
+use serde::{Deserialize, Serialize};
+use winit::event::{MouseButton, VirtualKeyCode as Key};
+
+use crate::graphics::font::has_glyph;
+use crate::ui::layout::{Layout, DEFAULT_SCALE};
+
+pub use crate::sim::OpposingInputs;
+
+/// Where a key is in its press, tracked from one tick to the next
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum KeyState {
+    Pressed,
+    Holding,
+    Released,
+}
+
+impl Default for KeyState {
+    fn default() -> Self {
+        Self::Released
+    }
+}
+
+impl KeyState {
+    /// State on the next tick, given whether the key is down then
+    pub fn next(self, down: bool) -> Self {
+        match (down, self) {
+            (true, Self::Released) => Self::Pressed,
+            (true, _) => Self::Holding,
+            (false, _) => Self::Released,
+        }
+    }
+}
+
+/// A button on a gamepad, named by where it sits so any layout maps onto it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PadButton {
+    /// Face buttons: A and B, X and Y on an Xbox pad
+    South,
+    East,
+    West,
+    North,
+    LeftShoulder,
+    RightShoulder,
+    Select,
+    Start,
+    /// Also pressed by tilting the left stick
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+impl PadButton {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::South => "South",
+            Self::East => "East",
+            Self::West => "West",
+            Self::North => "North",
+            Self::LeftShoulder => "L shoulder",
+            Self::RightShoulder => "R shoulder",
+            Self::Select => "Select",
+            Self::Start => "Start",
+            Self::DPadUp => "D-pad up",
+            Self::DPadDown => "D-pad down",
+            Self::DPadLeft => "D-pad left",
+            Self::DPadRight => "D-pad right",
+        }
+    }
+}
+
+/// A key, a gamepad button or a mouse button
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Control {
+    Key(Key),
+    Pad(PadButton),
+    Mouse(MouseButton),
+}
+
+/// Which keys and buttons are down this frame, which went down lately, where
+/// the mouse is, and the text typed and wheel turned since the last frame.
+/// Built up from window and gamepad events, or by hand to script a state
+/// without either. Every gamepad counts as one
+#[derive(Clone, Debug, Default)]
+pub struct InputSnapshot {
+    held: Vec<Control>,
+    /// Frame each control last went down on, so a tap that's released before
+    /// the next tick still counts
+    presses: Vec<(Control, u64)>,
+    /// Frame each control last came up on
+    releases: Vec<(Control, u64)>,
+    /// Frames ended so far
+    frame: u64,
+    /// Size of the framebuffer, in physical pixels
+    framebuffer: (u32, u32),
+    /// UI scale the screens are laid out at, if it's been set
+    ui_scale: Option<f32>,
+    /// Where the cursor is across the window and down it, out of 1, while
+    /// it's over the window
+    cursor: Option<[f32; 2]>,
+    /// Frame the cursor last moved on
+    cursor_moved: u64,
+    pub text: String,
+    /// Lines the mouse wheel was turned by, positive away from the player
+    pub scroll: f32,
+}
+
+impl InputSnapshot {
+    /// A frame with exactly `held` down and nothing typed
+    pub fn holding(held: &[Key]) -> Self {
+        Self {
+            held: held.iter().map(|&key| Control::Key(key)).collect(),
+            ..Self::default()
+        }
+    }
+
+    pub fn is_down(&self, key: Key) -> bool {
+        self.held.contains(&Control::Key(key))
+    }
+
+    pub fn is_pad_down(&self, button: PadButton) -> bool {
+        self.held.contains(&Control::Pad(button))
+    }
+
+    /// Current frame number
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Whether `key` went down after frame `since`, even if it's been let go
+    /// again
+    pub fn pressed_since(&self, key: Key, since: u64) -> bool {
+        self.presses_since(since).any(|k| k == key)
+    }
+
+    /// Keys that went down after frame `since`. A `since` of 0 stands for no
+    /// earlier read, which has nothing to compare against, so gives none
+    pub fn presses_since(&self, since: u64) -> impl Iterator<Item = Key> + '_ {
+        self.controls_pressed_since(since)
+            .filter_map(|control| match control {
+                Control::Key(key) => Some(key),
+                _ => None,
+            })
+    }
+
+    /// Gamepad buttons that went down after frame `since`, like
+    /// [`presses_since`](Self::presses_since)
+    pub fn pad_presses_since(&self, since: u64) -> impl Iterator<Item = PadButton> + '_ {
+        self.controls_pressed_since(since)
+            .filter_map(|control| match control {
+                Control::Pad(button) => Some(button),
+                _ => None,
+            })
+    }
+
+    /// Whether the left mouse button went down after frame `since`
+    pub fn clicked_since(&self, since: u64) -> bool {
+        self.controls_pressed_since(since)
+            .any(|control| control == Control::Mouse(MouseButton::Left))
+    }
+
+    /// Whether the cursor moved after frame `since`
+    pub fn cursor_moved_since(&self, since: u64) -> bool {
+        since != 0 && self.cursor_moved > since
+    }
+
+    /// Where the cursor is on the canvas drawn to, if it's over the window
+    pub fn cursor(&self) -> Option<[f32; 2]> {
+        let [x, y] = self.cursor?;
+        let canvas = self.layout()?.canvas;
+        Some([canvas.x + x * canvas.width, canvas.y + y * canvas.height])
+    }
+
+    /// Layout of the framebuffer, once its size is known
+    pub fn layout(&self) -> Option<Layout> {
+        match self.framebuffer {
+            (0, _) | (_, 0) => None,
+            (width, height) => Some(Layout::for_framebuffer(
+                width,
+                height,
+                self.ui_scale.unwrap_or(DEFAULT_SCALE),
+            )),
+        }
+    }
+
+    fn controls_pressed_since(&self, since: u64) -> impl Iterator<Item = Control> + '_ {
+        self.presses
+            .iter()
+            .filter(move |&&(_, frame)| since != 0 && frame > since)
+            .map(|&(control, _)| control)
+    }
+
+    /// Whether an action bound to `key` and `button` is down, given it was
+    /// last read on frame `since`. A tap of either since then counts. While
+    /// one is held and the other isn't, whichever changed last wins, so
+    /// letting go of a button overrides a key that was already down
+    pub fn either_down(&self, key: Key, button: Option<PadButton>, since: u64) -> bool {
+        let key = Control::Key(key);
+        let button = match button {
+            Some(button) => Control::Pad(button),
+            None => return self.control_down(key, since),
+        };
+        let tapped = |control| self.controls_pressed_since(since).any(|c| c == control);
+        if tapped(key) || tapped(button) {
+            return true;
+        }
+        match (self.held.contains(&key), self.held.contains(&button)) {
+            (true, true) => true,
+            (false, false) => false,
+            (true, false) => self.edge(&self.releases, button) <= self.edge(&self.presses, key),
+            (false, true) => self.edge(&self.releases, key) <= self.edge(&self.presses, button),
+        }
+    }
+
+    fn control_down(&self, control: Control, since: u64) -> bool {
+        self.held.contains(&control) || self.controls_pressed_since(since).any(|c| c == control)
+    }
+
+    /// Frame `control` last went through one of `edges`, if it has
+    fn edge(&self, edges: &[(Control, u64)], control: Control) -> Option<u64> {
+        edges
+            .iter()
+            .find(|&&(c, _)| c == control)
+            .map(|&(_, frame)| frame)
+    }
+
+    pub fn press(&mut self, key: Key) {
+        self.press_control(Control::Key(key));
+    }
+
+    pub fn release(&mut self, key: Key) {
+        self.release_control(Control::Key(key));
+    }
+
+    pub fn press_pad(&mut self, button: PadButton) {
+        self.press_control(Control::Pad(button));
+    }
+
+    pub fn release_pad(&mut self, button: PadButton) {
+        self.release_control(Control::Pad(button));
+    }
+
+    pub fn press_mouse(&mut self, button: MouseButton) {
+        self.press_control(Control::Mouse(button));
+    }
+
+    pub fn release_mouse(&mut self, button: MouseButton) {
+        self.release_control(Control::Mouse(button));
+    }
+
+    /// Puts the cursor `position` across the window and down it, out of 1, or
+    /// takes it off the window
+    pub fn move_cursor(&mut self, position: Option<[f32; 2]>) {
+        if position != self.cursor {
+            self.cursor = position;
+            self.cursor_moved = self.frame;
+        }
+    }
+
+    /// Sets the size of the framebuffer the cursor is over, in physical pixels
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.framebuffer = (width, height);
+    }
+
+    /// Sets the UI scale the screens the cursor is over are laid out at
+    pub fn set_ui_scale(&mut self, scale: f32) {
+        self.ui_scale = Some(scale);
+    }
+
+    fn press_control(&mut self, control: Control) {
+        // held keys are repeated by the OS, which isn't a new press
+        if !self.held.contains(&control) {
+            self.held.push(control);
+            self.presses.retain(|&(c, _)| c != control);
+            self.presses.push((control, self.frame));
+        }
+    }
+
+    fn release_control(&mut self, control: Control) {
+        if self.held.contains(&control) {
+            self.held.retain(|&c| c != control);
+            self.releases.retain(|&(c, _)| c != control);
+            self.releases.push((control, self.frame));
+        }
+    }
+
+    /// Lets go of everything held, for when the window stops hearing about
+    /// keys, like while it's minimised
+    pub fn release_all(&mut self) {
+        for control in std::mem::take(&mut self.held) {
+            self.releases.retain(|&(c, _)| c != control);
+            self.releases.push((control, self.frame));
+        }
+    }
+
+    /// Whether anything is held down, or was pressed, moved, typed or
+    /// scrolled this frame
+    pub fn is_active(&self) -> bool {
+        !self.held.is_empty()
+            || self.presses.iter().any(|&(_, frame)| frame == self.frame)
+            || self.cursor_moved == self.frame
+            || !self.text.is_empty()
+            || self.scroll != 0.0
+    }
+
+    /// Moves on to the next frame, forgetting the text typed and the wheel
+    /// turned in this one
+    pub fn end_frame(&mut self) {
+        self.text.clear();
+        self.scroll = 0.0;
+        self.frame += 1;
+    }
+}
+
+/// Something a player can bind a key to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    RotateRight,
+    RotateLeft,
+    /// Picks the highlighted menu entry. Space always does this too, and
+    /// either rotation still does in menus
+    Confirm,
+    /// Leaves the current screen or game. Escape always does this too, so a
+    /// player can't rebind their way out of every menu
+    Back,
+    /// Starts the run over from a fresh board, once held down for a moment
+    Restart,
+}
+
+impl Action {
+    pub const ALL: &'static [Action] = &[
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::RotateRight,
+        Action::RotateLeft,
+        Action::Confirm,
+        Action::Back,
+        Action::Restart,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Up => "Up",
+            Self::Down => "Soft drop",
+            Self::Left => "Move left",
+            Self::Right => "Move right",
+            Self::RotateRight => "Rotate CW",
+            Self::RotateLeft => "Rotate CCW",
+            Self::Confirm => "Confirm",
+            Self::Back => "Back",
+            Self::Restart => "Restart",
+        }
+    }
+}
+
+/// Keys a player's actions are bound to. Everything else (the toggles and
+/// the practice keys) is bound once and shared by every player
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyMap {
+    pub up: Key,
+    pub down: Key,
+    pub left: Key,
+    pub right: Key,
+    pub rot_right: Key,
+    pub rot_left: Key,
+    pub confirm: Key,
+    pub back: Key,
+    pub restart: Key,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self::PLAYER_ONE
+    }
+}
+
+impl KeyMap {
+    /// Arrows with Z and X, also used by every menu
+    pub const PLAYER_ONE: KeyMap = KeyMap {
+        up: Key::Up,
+        down: Key::Down,
+        left: Key::Left,
+        right: Key::Right,
+        rot_right: Key::Z,
+        rot_left: Key::X,
+        confirm: Key::Return,
+        back: Key::Escape,
+        restart: Key::R,
+    };
+
+    /// WASD with Q and E, for the second player in versus
+    pub const PLAYER_TWO: KeyMap = KeyMap {
+        up: Key::W,
+        down: Key::S,
+        left: Key::A,
+        right: Key::D,
+        rot_right: Key::Q,
+        rot_left: Key::E,
+        confirm: Key::Return,
+        back: Key::Escape,
+        restart: Key::R,
+    };
+
+    /// Key bound to `action`
+    pub fn key(&self, action: Action) -> Key {
+        match action {
+            Action::Up => self.up,
+            Action::Down => self.down,
+            Action::Left => self.left,
+            Action::Right => self.right,
+            Action::RotateRight => self.rot_right,
+            Action::RotateLeft => self.rot_left,
+            Action::Confirm => self.confirm,
+            Action::Back => self.back,
+            Action::Restart => self.restart,
+        }
+    }
+
+    /// Whether any action is bound to `key`
+    pub fn binds(&self, key: Key) -> bool {
+        Action::ALL.iter().any(|&action| self.key(action) == key)
+    }
+
+    fn key_mut(&mut self, action: Action) -> &mut Key {
+        match action {
+            Action::Up => &mut self.up,
+            Action::Down => &mut self.down,
+            Action::Left => &mut self.left,
+            Action::Right => &mut self.right,
+            Action::RotateRight => &mut self.rot_right,
+            Action::RotateLeft => &mut self.rot_left,
+            Action::Confirm => &mut self.confirm,
+            Action::Back => &mut self.back,
+            Action::Restart => &mut self.restart,
+        }
+    }
+
+    /// Binds `key` to `action`. If another action had it, that one takes the
+    /// key `action` had before, so every action keeps a key; it's returned
+    pub fn bind(&mut self, action: Action, key: Key) -> Option<Action> {
+        let old = self.key(action);
+        let other = Action::ALL
+            .iter()
+            .copied()
+            .find(|&other| other != action && self.key(other) == key);
+        if let Some(other) = other {
+            *self.key_mut(other) = old;
+        }
+        *self.key_mut(action) = key;
+        other
+    }
+}
+
+/// Gamepad buttons a player's actions are bound to, alongside their keys. An
+/// action can be left without one
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PadMap {
+    pub up: Option<PadButton>,
+    pub down: Option<PadButton>,
+    pub left: Option<PadButton>,
+    pub right: Option<PadButton>,
+    pub rot_right: Option<PadButton>,
+    pub rot_left: Option<PadButton>,
+    pub confirm: Option<PadButton>,
+    pub back: Option<PadButton>,
+    pub restart: Option<PadButton>,
+}
+
+impl Default for PadMap {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+impl PadMap {
+    /// The d-pad or left stick to move, South and East to rotate, Start to
+    /// leave and Select to restart, also used by every menu
+    pub const STANDARD: PadMap = PadMap {
+        up: Some(PadButton::DPadUp),
+        down: Some(PadButton::DPadDown),
+        left: Some(PadButton::DPadLeft),
+        right: Some(PadButton::DPadRight),
+        rot_right: Some(PadButton::South),
+        rot_left: Some(PadButton::East),
+        // South already picks menu entries, as rotating does
+        confirm: None,
+        back: Some(PadButton::Start),
+        restart: Some(PadButton::Select),
+    };
+
+    /// Nothing bound, for a player on the keyboard only
+    pub const NONE: PadMap = PadMap {
+        up: None,
+        down: None,
+        left: None,
+        right: None,
+        rot_right: None,
+        rot_left: None,
+        confirm: None,
+        back: None,
+        restart: None,
+    };
+
+    /// Button bound to `action`, if there is one
+    pub fn button(&self, action: Action) -> Option<PadButton> {
+        match action {
+            Action::Up => self.up,
+            Action::Down => self.down,
+            Action::Left => self.left,
+            Action::Right => self.right,
+            Action::RotateRight => self.rot_right,
+            Action::RotateLeft => self.rot_left,
+            Action::Confirm => self.confirm,
+            Action::Back => self.back,
+            Action::Restart => self.restart,
+        }
+    }
+
+    fn button_mut(&mut self, action: Action) -> &mut Option<PadButton> {
+        match action {
+            Action::Up => &mut self.up,
+            Action::Down => &mut self.down,
+            Action::Left => &mut self.left,
+            Action::Right => &mut self.right,
+            Action::RotateRight => &mut self.rot_right,
+            Action::RotateLeft => &mut self.rot_left,
+            Action::Confirm => &mut self.confirm,
+            Action::Back => &mut self.back,
+            Action::Restart => &mut self.restart,
+        }
+    }
+
+    /// Binds `button` to `action`, swapping with whichever action had it like
+    /// [`KeyMap::bind`] does; it's returned
+    pub fn bind(&mut self, action: Action, button: PadButton) -> Option<Action> {
+        let old = self.button(action);
+        let other = Action::ALL
+            .iter()
+            .copied()
+            .find(|&other| other != action && self.button(other) == Some(button));
+        if let Some(other) = other {
+            *self.button_mut(other) = old;
+        }
+        *self.button_mut(action) = Some(button);
+        other
+    }
+}
+
+/// Drawn in place of a typed character the font has no glyph for
+pub const FALLBACK_CHAR: char = '?';
+
+/// A line of text being typed in, like a name for the leaderboard. It's fed
+/// the characters typed each frame, so keyboard layouts and shift work the way
+/// the player expects
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TextField {
+    text: String,
+    max_chars: usize,
+}
+
+impl TextField {
+    /// An empty field taking up to `max_chars` characters
+    pub fn new(max_chars: usize) -> Self {
+        Self {
+            text: String::new(),
+            max_chars,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Adds `typed` to the end, putting [`FALLBACK_CHAR`] for characters the
+    /// font can't draw and dropping whatever doesn't fit
+    pub fn type_text(&mut self, typed: &str) {
+        for c in typed.chars().filter(|c| !c.is_control()) {
+            if self.text.chars().count() >= self.max_chars {
+                break;
+            }
+            self.text.push(if has_glyph(c) { c } else { FALLBACK_CHAR });
+        }
+    }
+
+    /// Takes off the last character, if there is one
+    pub fn backspace(&mut self) {
+        self.text.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether `key` counts as down for a read after one on frame `since`,
+    /// as the game reads it
+    fn down(snapshot: &InputSnapshot, key: Key, since: u64) -> bool {
+        snapshot.is_down(key) || snapshot.pressed_since(key, since)
+    }
+
+    /// A snapshot far enough along that it has been read before
+    fn started() -> InputSnapshot {
+        let mut snapshot = InputSnapshot::default();
+        snapshot.end_frame();
+        snapshot
+    }
+
+    #[test]
+    fn a_tap_between_ticks_is_one_press() {
+        let mut snapshot = started();
+        let mut read = snapshot.frame();
+        snapshot.end_frame();
+
+        // down and up again before the next tick looks
+        snapshot.press(Key::Left);
+        snapshot.release(Key::Left);
+        snapshot.end_frame();
+        assert!(!snapshot.is_down(Key::Left));
+
+        let mut state = KeyState::Released;
+        let mut states = Vec::new();
+        for _ in 0..3 {
+            state = state.next(down(&snapshot, Key::Left, read));
+            states.push(state);
+            read = snapshot.frame();
+            snapshot.end_frame();
+        }
+        assert_eq!(
+            states,
+            vec![KeyState::Pressed, KeyState::Released, KeyState::Released]
+        );
+    }
+
+    #[test]
+    fn a_held_key_is_pressed_once_then_held() {
+        let mut snapshot = started();
+        let mut read = snapshot.frame();
+        snapshot.press(Key::Z);
+        let mut state = KeyState::Released;
+        let mut states = Vec::new();
+        for _ in 0..3 {
+            snapshot.end_frame();
+            // the OS repeating the key while it's held
+            snapshot.press(Key::Z);
+            state = state.next(down(&snapshot, Key::Z, read));
+            states.push(state);
+            read = snapshot.frame();
+        }
+        assert_eq!(
+            states,
+            vec![KeyState::Pressed, KeyState::Holding, KeyState::Holding]
+        );
+    }
+
+    #[test]
+    fn the_first_read_sees_no_presses() {
+        let mut snapshot = InputSnapshot::default();
+        snapshot.press(Key::Left);
+        assert!(!snapshot.pressed_since(Key::Left, 0));
+        assert!(snapshot.is_down(Key::Left));
+    }
+
+    #[test]
+    fn letting_go_of_a_button_overrides_a_held_key() {
+        let mut snapshot = started();
+        snapshot.press(Key::Left);
+        snapshot.end_frame();
+        snapshot.press_pad(PadButton::DPadLeft);
+        snapshot.end_frame();
+        let read = snapshot.frame();
+        assert!(snapshot.either_down(Key::Left, Some(PadButton::DPadLeft), read));
+
+        snapshot.release_pad(PadButton::DPadLeft);
+        snapshot.end_frame();
+        assert!(!snapshot.either_down(Key::Left, Some(PadButton::DPadLeft), read));
+        assert!(snapshot.either_down(Key::Left, None, read));
+    }
+
+    #[test]
+    fn releasing_everything_lets_go_of_held_keys() {
+        let mut snapshot = started();
+        snapshot.press(Key::Down);
+        snapshot.press_pad(PadButton::South);
+        snapshot.release_all();
+        assert!(!snapshot.is_down(Key::Down));
+        assert!(!snapshot.is_pad_down(PadButton::South));
+    }
+
+    #[test]
+    fn binding_a_taken_key_swaps_it() {
+        let mut keys = KeyMap::PLAYER_ONE;
+        assert_eq!(keys.bind(Action::Left, Key::Z), Some(Action::RotateRight));
+        assert_eq!(keys.left, Key::Z);
+        assert_eq!(keys.rot_right, Key::Left);
+    }
+}
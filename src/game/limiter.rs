@@ -0,0 +1,66 @@
+//! Keeps frames from coming faster than they're wanted.
+//!
+//! Without vsync, or with a present mode that doesn't block, the main loop
+//! would draw as fast as it can and keep a core busy. [`FrameLimiter::wait`]
+//! is called once a frame and sleeps away whatever is left of the frame's
+//! budget. Sleeping is only accurate to a millisecond or so, so the last
+//! stretch is spun away instead. Updates measure the time between frames as
+//! they always have, so a cap slows drawing, not the game.
+
+use std::time::{Duration, Instant};
+
+/// Caps frames can be limited to, in frames per second. 0 leaves them
+/// uncapped
+pub const FPS_CAPS: &[u32] = &[30, 60, 120, 144, 240, 0];
+
+/// Cap frames are limited to unless the config says otherwise
+pub const DEFAULT_FPS_CAP: u32 = 120;
+
+/// Frame rate while nobody is looking: the window is in the background or
+/// the screen hasn't changed in a while
+pub const LOW_POWER_FPS: u32 = 15;
+
+/// How much of the wait is spun away rather than slept, for accuracy
+const SPIN_TIME: Duration = Duration::from_millis(1);
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameLimiter {
+    /// When the last frame was let through
+    last: Option<Instant>,
+}
+
+impl FrameLimiter {
+    /// Waits until the next frame is due at `fps` frames per second, or
+    /// returns straight away if `fps` is 0. A frame that ran late starts the
+    /// count again rather than being made up for with shorter ones
+    pub fn wait(&mut self, fps: u32) {
+        let now = Instant::now();
+        if fps == 0 {
+            self.last = Some(now);
+            return;
+        }
+
+        let budget = Duration::from_secs(1) / fps;
+        let due = match self.last {
+            Some(last) if last + budget > now => last + budget,
+            _ => now,
+        };
+        if let Some(sleep) = (due - now).checked_sub(SPIN_TIME) {
+            std::thread::sleep(sleep);
+        }
+        while Instant::now() < due {
+            std::hint::spin_loop();
+        }
+        self.last = Some(due);
+    }
+}
+
+/// Frame rate to limit frames to given the `cap` picked, which is 0 for
+/// uncapped, and whether to save power
+pub fn frame_rate(cap: u32, low_power: bool) -> u32 {
+    match (cap, low_power) {
+        (_, false) => cap,
+        (0, true) => LOW_POWER_FPS,
+        (cap, true) => cap.min(LOW_POWER_FPS),
+    }
+}
@@ -0,0 +1,408 @@
+//! Online versus: a tiny message protocol over TCP.
+//!
+//! Each side plays its own board and only tells the other what it needs to
+//! know. Messages are framed as a little-endian `u32` payload length followed
+//! by the payload, whose first byte says what kind of message it is. Sockets
+//! are non-blocking so the game loop can poll them once a frame.
+
+use std::convert::TryInto;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::piece::randomizer::RandomizerKind;
+use crate::piece::rotation::RotationKind;
+
+/// Bumped whenever a message changes shape, so mismatched builds refuse to play
+pub const PROTOCOL_VERSION: u8 = 3;
+
+/// Largest payload accepted, well above anything the game sends
+pub const MAX_MESSAGE_LEN: usize = 1024;
+
+/// How long joining waits for the host to answer
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    /// Sent by the host once someone joins: the seed both boards are dealt
+    /// from, how, and how the pieces turn
+    Start {
+        version: u8,
+        seed: u64,
+        randomizer: RandomizerKind,
+        rotation: RotationKind,
+    },
+    /// Garbage rows to push into the receiver's field
+    Garbage { rows: u32 },
+    /// The sender's field, one byte per cell from the top left, non-zero if filled
+    Snapshot { cells: Vec<u8> },
+    /// The sender's stack reached the top
+    ToppedOut,
+}
+
+impl Message {
+    fn encode(&self) -> Vec<u8> {
+        let mut payload = Vec::new();
+        match self {
+            Self::Start {
+                version,
+                seed,
+                randomizer,
+                rotation,
+            } => {
+                payload.push(0);
+                payload.push(*version);
+                payload.extend_from_slice(&seed.to_le_bytes());
+                let randomizer = RandomizerKind::ALL
+                    .iter()
+                    .position(|r| r == randomizer)
+                    .unwrap();
+                payload.push(randomizer as u8);
+                let rotation = RotationKind::ALL
+                    .iter()
+                    .position(|r| r == rotation)
+                    .unwrap();
+                payload.push(rotation as u8);
+            }
+            Self::Garbage { rows } => {
+                payload.push(1);
+                payload.extend_from_slice(&rows.to_le_bytes());
+            }
+            Self::Snapshot { cells } => {
+                payload.push(2);
+                payload.extend_from_slice(cells);
+            }
+            Self::ToppedOut => payload.push(3),
+        }
+
+        let mut frame = Vec::with_capacity(4 + payload.len());
+        frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        frame.extend(payload);
+        frame
+    }
+
+    fn decode(payload: &[u8]) -> Result<Self, NetError> {
+        let (&kind, rest) = payload.split_first().ok_or(NetError::Malformed)?;
+        let message = match (kind, rest.len()) {
+            // an older build's start, which is only read far enough to turn
+            // it away by its version
+            (0, 9) | (0, 10) => Self::Start {
+                version: rest[0],
+                seed: u64::from_le_bytes(rest[1..9].try_into().unwrap()),
+                randomizer: RandomizerKind::default(),
+                rotation: RotationKind::default(),
+            },
+            (0, 11) => Self::Start {
+                version: rest[0],
+                seed: u64::from_le_bytes(rest[1..9].try_into().unwrap()),
+                randomizer: *RandomizerKind::ALL
+                    .get(rest[9] as usize)
+                    .ok_or(NetError::Malformed)?,
+                rotation: *RotationKind::ALL
+                    .get(rest[10] as usize)
+                    .ok_or(NetError::Malformed)?,
+            },
+            (1, 4) => Self::Garbage {
+                rows: u32::from_le_bytes(rest.try_into().unwrap()),
+            },
+            (2, _) => Self::Snapshot {
+                cells: rest.to_vec(),
+            },
+            (3, 0) => Self::ToppedOut,
+            _ => return Err(NetError::Malformed),
+        };
+        Ok(message)
+    }
+}
+
+#[derive(Debug)]
+pub enum NetError {
+    /// The other side closed the connection
+    Disconnected,
+    /// The other side sent something that isn't a message
+    Malformed,
+    /// The other side speaks protocol version `theirs`
+    WrongVersion {
+        theirs: u8,
+    },
+    Io(io::Error),
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Disconnected => write!(f, "opponent disconnected"),
+            Self::Malformed => write!(f, "opponent sent garbled data"),
+            Self::WrongVersion { theirs } => write!(
+                f,
+                "opponent speaks protocol {}, this is {}",
+                theirs, PROTOCOL_VERSION
+            ),
+            Self::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for NetError {}
+
+impl From<io::Error> for NetError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::UnexpectedEof => Self::Disconnected,
+            _ => Self::Io(e),
+        }
+    }
+}
+
+/// Waits for an opponent to join on a port
+pub struct Host {
+    listener: TcpListener,
+}
+
+impl Host {
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self { listener })
+    }
+
+    /// The opponent's connection, if one has come in since the last call
+    pub fn accept(&self) -> io::Result<Option<Connection>> {
+        match self.listener.accept() {
+            Ok((stream, _)) => Connection::new(stream).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+pub struct Connection {
+    stream: TcpStream,
+
+    /// Bytes received that don't make up a whole message yet
+    inbox: Vec<u8>,
+
+    /// Bytes queued that the socket hasn't taken yet
+    outbox: Vec<u8>,
+
+    /// Whether the other side has closed the connection
+    closed: bool,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        // messages are tiny and time sensitive
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            inbox: Vec::new(),
+            outbox: Vec::new(),
+            closed: false,
+        })
+    }
+
+    /// Joins a host at `address` ("host:port"), waiting up to [`CONNECT_TIMEOUT`]
+    pub fn connect(address: &str) -> io::Result<Self> {
+        let mut last_error = None;
+        for addr in address.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+                Ok(stream) => return Self::new(stream),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error
+            .unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "address did not resolve")))
+    }
+
+    /// Queues `message`; it goes out on the next [`poll`](Self::poll)
+    pub fn send(&mut self, message: &Message) {
+        self.outbox.extend(message.encode());
+    }
+
+    /// Sends whatever is queued and returns every message that has arrived.
+    /// Messages that came in before a disconnect are still returned; the error
+    /// follows on the next call
+    pub fn poll(&mut self) -> Result<Vec<Message>, NetError> {
+        if self.closed {
+            return Err(NetError::Disconnected);
+        }
+
+        while !self.outbox.is_empty() {
+            match self.stream.write(&self.outbox) {
+                Ok(0) => return Err(NetError::Disconnected),
+                Ok(n) => {
+                    self.outbox.drain(..n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let mut buf = [0; 1024];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => {
+                    self.closed = true;
+                    break;
+                }
+                Ok(n) => self.inbox.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        unframe(&mut self.inbox)
+    }
+}
+
+/// The whole messages at the front of `inbox`, taken out of it. A message
+/// still arriving is left for next time
+fn unframe(inbox: &mut Vec<u8>) -> Result<Vec<Message>, NetError> {
+    let mut messages = Vec::new();
+    while inbox.len() >= 4 {
+        let len = u32::from_le_bytes(inbox[..4].try_into().unwrap()) as usize;
+        if len > MAX_MESSAGE_LEN {
+            return Err(NetError::Malformed);
+        }
+        if inbox.len() < 4 + len {
+            break;
+        }
+        messages.push(Message::decode(&inbox[4..4 + len])?);
+        inbox.drain(..4 + len);
+    }
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn every_message() -> Vec<Message> {
+        vec![
+            Message::Start {
+                version: PROTOCOL_VERSION,
+                seed: 0x0123_4567_89ab_cdef,
+                randomizer: RandomizerKind::Bag,
+                rotation: RotationKind::Ars,
+            },
+            Message::Garbage { rows: 4 },
+            Message::Garbage { rows: u32::MAX },
+            Message::Snapshot {
+                cells: (0..200).map(|i| (i % 3) as u8).collect(),
+            },
+            Message::Snapshot { cells: Vec::new() },
+            Message::ToppedOut,
+        ]
+    }
+
+    #[test]
+    fn every_message_reads_back_the_same() {
+        let sent = every_message();
+        let mut inbox: Vec<u8> = sent.iter().flat_map(Message::encode).collect();
+        assert_eq!(unframe(&mut inbox).unwrap(), sent);
+        assert!(inbox.is_empty());
+    }
+
+    #[test]
+    fn a_message_still_arriving_waits_for_the_rest() {
+        let frame = Message::Garbage { rows: 2 }.encode();
+        let mut inbox = Vec::new();
+        for &byte in &frame[..frame.len() - 1] {
+            inbox.push(byte);
+            assert_eq!(unframe(&mut inbox).unwrap(), vec![]);
+        }
+        inbox.push(frame[frame.len() - 1]);
+        assert_eq!(
+            unframe(&mut inbox).unwrap(),
+            vec![Message::Garbage { rows: 2 }]
+        );
+    }
+
+    #[test]
+    fn oversized_messages_are_turned_away() {
+        let mut inbox = ((MAX_MESSAGE_LEN + 1) as u32).to_le_bytes().to_vec();
+        assert!(matches!(unframe(&mut inbox), Err(NetError::Malformed)));
+
+        // the largest there can be still goes through
+        let cells = vec![1; MAX_MESSAGE_LEN - 1];
+        let mut inbox = Message::Snapshot {
+            cells: cells.clone(),
+        }
+        .encode();
+        assert_eq!(
+            unframe(&mut inbox).unwrap(),
+            vec![Message::Snapshot { cells }]
+        );
+    }
+
+    #[test]
+    fn cut_short_or_unknown_messages_are_turned_away() {
+        for payload in &[
+            &[][..],
+            &[1, 4, 0, 0][..],
+            &[1, 4, 0, 0, 0, 0][..],
+            &[0, PROTOCOL_VERSION, 1, 2, 3][..],
+            &[3, 0][..],
+            &[4][..],
+        ] {
+            let mut inbox = (payload.len() as u32).to_le_bytes().to_vec();
+            inbox.extend_from_slice(payload);
+            assert!(
+                matches!(unframe(&mut inbox), Err(NetError::Malformed)),
+                "{:?}",
+                payload
+            );
+        }
+
+        // a start naming a randomizer or rotation this build doesn't have
+        for &(randomizer, rotation) in &[(RandomizerKind::ALL.len() as u8, 0), (0, 9)] {
+            let mut payload = vec![0, PROTOCOL_VERSION];
+            payload.extend_from_slice(&7u64.to_le_bytes());
+            payload.extend_from_slice(&[randomizer, rotation]);
+            assert!(matches!(
+                Message::decode(&payload),
+                Err(NetError::Malformed)
+            ));
+        }
+    }
+
+    #[test]
+    fn messages_cross_a_real_connection() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let mut joined = Connection::connect(&address).unwrap();
+        let mut host = Connection::new(listener.accept().unwrap().0).unwrap();
+
+        let sent = every_message();
+        for message in &sent {
+            joined.send(message);
+        }
+        let mut received = Vec::new();
+        for _ in 0..200 {
+            joined.poll().unwrap();
+            received.extend(host.poll().unwrap());
+            if received.len() == sent.len() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(received, sent);
+
+        // hanging up is noticed rather than waited on
+        drop(joined);
+        let mut result = Ok(Vec::new());
+        for _ in 0..200 {
+            result = host.poll();
+            if result.is_err() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert!(matches!(result, Err(NetError::Disconnected)));
+    }
+}
@@ -0,0 +1,264 @@
+//! Puzzle files: a starting field and a fixed queue of pieces to clear it with.
+//!
+//! A puzzle is a plain text file with [`HEIGHT`] rows of [`WIDTH`] characters,
+//! `.` for an empty cell and `#` for a filled one, top row first, followed by a
+//! line with the piece queue as letters (e.g. `TSZI`). Blank lines around the
+//! queue are ignored. No row may start out full, as nothing could score it.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Cells across the field
+pub const WIDTH: usize = 10;
+
+/// Rows in the field
+pub const HEIGHT: usize = 20;
+
+/// Letters a piece queue can be made of
+pub const PIECE_LETTERS: &str = "IOTSZJL";
+
+/// Where puzzle files are looked for
+pub const PUZZLE_DIR: &str = "puzzles";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Puzzle {
+    /// Filled cells, row by row from the top
+    pub cells: Vec<bool>,
+
+    /// Pieces to play, in order, as letters from [`PIECE_LETTERS`]
+    pub queue: Vec<char>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PuzzleError {
+    /// The file ended before all the rows were read
+    MissingRows { found: usize },
+    /// Row `line` isn't [`WIDTH`] cells wide
+    WrongWidth { line: usize, found: usize },
+    /// Row `line` has something other than `.` or `#` in it
+    UnknownCell { line: usize, found: char },
+    /// Row `line` is already full
+    FullRow { line: usize },
+    /// There is no piece queue after the rows
+    MissingQueue,
+    /// The queue on line `line` has a letter that isn't a piece
+    UnknownPiece { line: usize, found: char },
+    /// Something follows the queue on line `line`
+    TrailingLine { line: usize },
+}
+
+impl fmt::Display for PuzzleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingRows { found } => {
+                write!(f, "expected {} rows, found only {}", HEIGHT, found)
+            }
+            Self::WrongWidth { line, found } => write!(
+                f,
+                "line {}: expected {} cells, found {}",
+                line, WIDTH, found
+            ),
+            Self::UnknownCell { line, found } => write!(
+                f,
+                "line {}: unknown cell {:?}, expected '.' or '#'",
+                line, found
+            ),
+            Self::FullRow { line } => write!(f, "line {}: the row is already full", line),
+            Self::MissingQueue => write!(f, "no piece queue after the rows"),
+            Self::UnknownPiece { line, found } => write!(
+                f,
+                "line {}: unknown piece {:?}, expected one of {}",
+                line, found, PIECE_LETTERS
+            ),
+            Self::TrailingLine { line } => {
+                write!(f, "line {}: unexpected text after the queue", line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PuzzleError {}
+
+impl Puzzle {
+    pub fn parse(text: &str) -> Result<Self, PuzzleError> {
+        // line numbers are 1-based to match what an editor shows
+        let mut lines = text.lines().enumerate().map(|(i, l)| (i + 1, l.trim()));
+
+        let mut cells = Vec::with_capacity(WIDTH * HEIGHT);
+        for found in 0..HEIGHT {
+            let (line, row) = lines.next().ok_or(PuzzleError::MissingRows { found })?;
+            if row.chars().count() != WIDTH {
+                return Err(PuzzleError::WrongWidth {
+                    line,
+                    found: row.chars().count(),
+                });
+            }
+            for c in row.chars() {
+                match c {
+                    '.' => cells.push(false),
+                    '#' => cells.push(true),
+                    found => return Err(PuzzleError::UnknownCell { line, found }),
+                }
+            }
+            if cells[cells.len() - WIDTH..].iter().all(|&c| c) {
+                return Err(PuzzleError::FullRow { line });
+            }
+        }
+
+        let mut rest = lines.filter(|(_, l)| !l.is_empty());
+        let (line, queue) = rest.next().ok_or(PuzzleError::MissingQueue)?;
+        if let Some(found) = queue.chars().find(|&c| !PIECE_LETTERS.contains(c)) {
+            return Err(PuzzleError::UnknownPiece { line, found });
+        }
+        if let Some((line, _)) = rest.next() {
+            return Err(PuzzleError::TrailingLine { line });
+        }
+
+        Ok(Self {
+            cells,
+            queue: queue.chars().collect(),
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(Self::parse(&text)?)
+    }
+}
+
+/// Puzzle files in `dir`, sorted by name
+pub fn list(dir: &str) -> Vec<PathBuf> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension().map_or(false, |ext| ext == "txt"))
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{Ending, Event, Inputs, Simulation};
+
+    /// The field of `puzzles/01-well.txt`: a well down the right of four
+    /// rows
+    fn well() -> String {
+        let mut text = "..........\n".repeat(HEIGHT - 4);
+        text.push_str(&"#########.\n".repeat(4));
+        text
+    }
+
+    /// How a run of `puzzle` ends with a turn at the start and `held` after
+    fn play(puzzle: Puzzle, turn: Inputs, held: Inputs) -> Ending {
+        let mut sim = Simulation::from_puzzle(puzzle);
+        while sim.active_piece.is_none() {
+            sim.step(Inputs::default());
+        }
+        sim.step(turn);
+        for _ in 0..10_000 {
+            for event in sim.step(held) {
+                if let Event::Finished(ending) = event {
+                    return ending;
+                }
+            }
+        }
+        panic!("the puzzle never ended");
+    }
+
+    #[test]
+    fn parses_the_field_and_the_queue() {
+        let puzzle = Puzzle::parse(&format!("{}\nIT\n", well())).unwrap();
+        assert_eq!(puzzle.queue, vec!['I', 'T']);
+        assert_eq!(puzzle.cells.len(), WIDTH * HEIGHT);
+        assert_eq!(puzzle.cells.iter().filter(|&&c| c).count(), 4 * 9);
+        assert!(!puzzle.cells[WIDTH * HEIGHT - 1]);
+        assert!(puzzle.cells[WIDTH * (HEIGHT - 4)]);
+    }
+
+    #[test]
+    fn reports_what_is_wrong_and_where() {
+        let short = "..........\n".repeat(HEIGHT - 1);
+        assert_eq!(
+            Puzzle::parse(&short),
+            Err(PuzzleError::MissingRows { found: HEIGHT - 1 })
+        );
+        assert_eq!(
+            Puzzle::parse(&format!("{}.........\nI", short)),
+            Err(PuzzleError::WrongWidth {
+                line: HEIGHT,
+                found: 9
+            })
+        );
+        assert_eq!(
+            Puzzle::parse(&format!("{}....x.....\nI", short)),
+            Err(PuzzleError::UnknownCell {
+                line: HEIGHT,
+                found: 'x'
+            })
+        );
+        assert_eq!(Puzzle::parse(&well()), Err(PuzzleError::MissingQueue));
+        assert_eq!(
+            Puzzle::parse(&format!("{}\nIQ", well())),
+            Err(PuzzleError::UnknownPiece {
+                line: HEIGHT + 2,
+                found: 'Q'
+            })
+        );
+        assert_eq!(
+            Puzzle::parse(&format!("{}I\n\nT\n", well())),
+            Err(PuzzleError::TrailingLine { line: HEIGHT + 3 })
+        );
+    }
+
+    #[test]
+    fn rejects_rows_that_start_out_full() {
+        // clearing these all at once would score more lines than a piece can
+        let mut text = "..........\n".repeat(HEIGHT - 6);
+        text.push_str(&"##########\n".repeat(6));
+        text.push('I');
+        assert_eq!(
+            Puzzle::parse(&text),
+            Err(PuzzleError::FullRow { line: HEIGHT - 5 })
+        );
+    }
+
+    #[test]
+    fn the_bundled_puzzles_parse() {
+        let paths = list(concat!(env!("CARGO_MANIFEST_DIR"), "/puzzles"));
+        assert!(!paths.is_empty());
+        for path in paths {
+            Puzzle::load(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        }
+    }
+
+    #[test]
+    fn emptying_the_field_wins() {
+        let puzzle = Puzzle::parse(&format!("{}I", well())).unwrap();
+        let turn = Inputs {
+            rot_right: true,
+            ..Inputs::default()
+        };
+        let held = Inputs {
+            right: true,
+            down: true,
+            ..Inputs::default()
+        };
+        assert_eq!(play(puzzle, turn, held), Ending::Won);
+    }
+
+    #[test]
+    fn running_out_of_pieces_loses() {
+        let puzzle = Puzzle::parse(&format!("{}O", well())).unwrap();
+        let held = Inputs {
+            down: true,
+            ..Inputs::default()
+        };
+        assert_eq!(play(puzzle, held, held), Ending::ToppedOut);
+    }
+}
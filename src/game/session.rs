@@ -0,0 +1,83 @@
+//! What's kept for as long as the game is open and forgotten once it closes.
+//!
+//! The states that start runs and take their results back share one
+//! [`Session`], like they share the config.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+
+use crate::game::stats::GameStats;
+use crate::mode::GameMode;
+
+/// Runs of each mode kept to compare the next one against
+pub const RECENT_RUNS: usize = 5;
+
+/// The session as shared by the states
+pub type SharedSession = Rc<RefCell<Session>>;
+
+#[derive(Clone, Debug, Default)]
+pub struct Session {
+    /// Statistics of the last [`RECENT_RUNS`] finished runs of each mode,
+    /// oldest first
+    recent: HashMap<GameMode, VecDeque<GameStats>>,
+}
+
+impl Session {
+    /// Keeps a finished run of `mode`, letting go of the oldest one kept if
+    /// there are already [`RECENT_RUNS`]
+    pub fn record(&mut self, mode: GameMode, stats: GameStats) {
+        let runs = self.recent.entry(mode).or_default();
+        if runs.len() == RECENT_RUNS {
+            runs.pop_front();
+        }
+        runs.push_back(stats);
+    }
+
+    /// The last run of `mode` finished, if there's been one
+    pub fn last(&self, mode: GameMode) -> Option<&GameStats> {
+        self.recent.get(&mode).and_then(VecDeque::back)
+    }
+
+    /// Runs of `mode` kept, oldest first
+    pub fn recent(&self, mode: GameMode) -> impl Iterator<Item = &GameStats> {
+        self.recent.get(&mode).into_iter().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(lines: u32) -> GameStats {
+        GameStats {
+            lines,
+            ..GameStats::default()
+        }
+    }
+
+    #[test]
+    fn the_last_run_is_kept_for_its_own_mode() {
+        let mut session = Session::default();
+        assert_eq!(session.last(GameMode::Endless), None);
+        session.record(GameMode::Endless, run(1));
+        session.record(GameMode::Endless, run(2));
+        session.record(GameMode::Sprint, run(3));
+        assert_eq!(session.last(GameMode::Endless), Some(&run(2)));
+        assert_eq!(session.last(GameMode::Sprint), Some(&run(3)));
+        assert_eq!(session.last(GameMode::Marathon), None);
+    }
+
+    #[test]
+    fn only_the_most_recent_runs_are_kept() {
+        let mut session = Session::default();
+        for lines in 0..RECENT_RUNS as u32 + 2 {
+            session.record(GameMode::Endless, run(lines));
+        }
+        let kept: Vec<u32> = session
+            .recent(GameMode::Endless)
+            .map(|stats| stats.lines)
+            .collect();
+        assert_eq!(kept, [2, 3, 4, 5, 6]);
+    }
+}
@@ -0,0 +1,578 @@
+//! Per-run gameplay statistics.
+//!
+//! [`GameStats`] knows nothing about the board or the renderer; the game feeds it
+//! one call per frame, one per spawned piece and one per locked piece and reads
+//! the totals back at the end of the run. Pieces are counted as they spawn, so
+//! the [`Droughts`] come out the same whichever randomizer dealt them.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::sim::TICK_MILLIS;
+
+/// Number of distinct piece shapes
+pub const PIECE_KINDS: usize = 7;
+
+/// Most lines a single tetromino can clear. Bigger pieces clearing more count
+/// as this many
+pub const MAX_CLEAR: usize = 4;
+
+/// Lines between one split and the next
+pub const SPLIT_LINES: u32 = 10;
+
+/// Frames of play between one [`Sample`] and the next
+pub const SAMPLE_TICKS: u64 = 60;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GameStats {
+    /// Pieces locked into the field, by shape index
+    pub pieces: [u32; PIECE_KINDS],
+
+    /// Pieces locked into the field that aren't one of the seven
+    pub other_pieces: u32,
+
+    /// Placements that cleared 1, 2, 3 and 4 or more lines
+    pub clears: [u32; MAX_CLEAR],
+
+    /// Total lines cleared
+    pub lines: u32,
+
+    /// Consecutive placements that have cleared lines, up to the latest one
+    pub combo: u32,
+
+    /// Longest streak of line clearing placements
+    pub max_combo: u32,
+
+    /// Frames of play
+    pub ticks: u64,
+
+    /// Frames each piece spent between spawning and locking, in order of play
+    pub decision_ticks: Vec<u64>,
+
+    /// Frame at which the active piece spawned
+    pub spawned_at: u64,
+
+    /// Frames of play it took to clear every [`SPLIT_LINES`] lines: the first
+    /// is when the 10th line went, the second the 20th, and so on
+    pub splits: Vec<u64>,
+
+    /// How long each shape has been kept waiting
+    pub droughts: Droughts,
+
+    /// Where the pieces went
+    pub columns: Heatmap,
+
+    /// How the run was going every [`SAMPLE_TICKS`], and as it ended
+    pub samples: Vec<Sample>,
+}
+
+/// Score and lines some way into a run
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Sample {
+    /// Frames of play so far
+    pub ticks: u64,
+    pub score: u64,
+    pub lines: u32,
+}
+
+/// Pieces spawned since each shape last did, by shape index
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Droughts(pub [u32; PIECE_KINDS]);
+
+impl Droughts {
+    /// Records a piece of shape `kind` spawning, None for pieces from other
+    /// sets, which every shape waits through
+    pub fn spawned(&mut self, kind: Option<usize>) {
+        for (i, since) in self.0.iter_mut().enumerate() {
+            if Some(i) == kind {
+                *since = 0;
+            } else {
+                *since += 1;
+            }
+        }
+    }
+
+    /// Pieces spawned since shape `kind` last did
+    pub fn of(&self, kind: usize) -> u32 {
+        self.0[kind]
+    }
+}
+
+/// Cells placed in each column, leftmost first. Columns right of the last
+/// one to get a cell aren't kept. Serializable so runs can be added up
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Heatmap(pub Vec<u32>);
+
+impl Heatmap {
+    /// Records a cell placed in `column`
+    pub fn add(&mut self, column: usize) {
+        if column >= self.0.len() {
+            self.0.resize(column + 1, 0);
+        }
+        self.0[column] += 1;
+    }
+
+    /// Cells placed in `column`
+    pub fn count(&self, column: usize) -> u32 {
+        self.0.get(column).copied().unwrap_or(0)
+    }
+
+    /// How busy `column` was next to the busiest one, from 0 to 1
+    pub fn heat(&self, column: usize) -> f32 {
+        match self.0.iter().max() {
+            Some(&max) if max > 0 => self.count(column) as f32 / max as f32,
+            _ => 0.0,
+        }
+    }
+
+    /// Column with the most cells, the leftmost of any tied, None if none
+    /// were placed
+    pub fn busiest(&self) -> Option<usize> {
+        let max = *self.0.iter().max().filter(|&&max| max > 0)?;
+        self.0.iter().position(|&count| count == max)
+    }
+
+    /// Column of the first `columns` with the fewest cells, the leftmost of
+    /// any tied, None if none were placed
+    pub fn quietest(&self, columns: usize) -> Option<usize> {
+        self.busiest()?;
+        (0..columns).min_by_key(|&column| self.count(column))
+    }
+
+    /// Adds the cells of `other` to these, for totals over many runs
+    pub fn merge(&mut self, other: &Heatmap) {
+        for (column, &count) in other.0.iter().enumerate() {
+            if column >= self.0.len() {
+                self.0.resize(column + 1, 0);
+            }
+            self.0[column] += count;
+        }
+    }
+}
+
+impl GameStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts one frame of play
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+    }
+
+    /// Notes down `score` and the lines cleared so far, to chart later
+    pub fn sample(&mut self, score: u64) {
+        self.samples.push(Sample {
+            ticks: self.ticks,
+            score,
+            lines: self.lines,
+        });
+    }
+
+    /// Records a piece of shape `kind` entering the field. `kind` is None for
+    /// pieces from other sets
+    pub fn piece_spawned(&mut self, kind: Option<usize>) {
+        self.spawned_at = self.ticks;
+        self.droughts.spawned(kind);
+    }
+
+    /// Records a piece of shape `kind` locking into the field and clearing
+    /// `lines`. `kind` is None for pieces from other sets
+    pub fn piece_placed(&mut self, kind: Option<usize>, lines: usize) {
+        self.decision_ticks.push(self.ticks - self.spawned_at);
+        match kind {
+            Some(kind) => {
+                assert!(kind < PIECE_KINDS, "Invalid piece kind {}", kind);
+                self.pieces[kind] += 1;
+            }
+            None => self.other_pieces += 1,
+        }
+        if lines == 0 {
+            self.combo = 0;
+        } else {
+            self.clears[lines.min(MAX_CLEAR) - 1] += 1;
+            self.lines += lines as u32;
+            // a big clear can pass more than one split at once
+            while self.splits.len() < (self.lines / SPLIT_LINES) as usize {
+                self.splits.push(self.ticks);
+            }
+            self.combo += 1;
+            self.max_combo = u32::max(self.max_combo, self.combo);
+        }
+    }
+
+    /// Goes back to what was counted in `earlier`, as when placements are
+    /// undone. The time played and the samples taken along the way are kept
+    pub fn rewind_to(&mut self, earlier: GameStats) {
+        let ticks = self.ticks;
+        let samples = std::mem::take(&mut self.samples);
+        *self = Self {
+            ticks,
+            samples,
+            ..earlier
+        };
+    }
+
+    /// Total pieces placed
+    pub fn total_pieces(&self) -> u32 {
+        self.pieces.iter().sum::<u32>() + self.other_pieces
+    }
+
+    /// Time spent playing, given how long a frame lasts
+    pub fn play_time(&self, frame_time: f32) -> Duration {
+        Duration::from_secs_f64(self.ticks as f64 * frame_time as f64)
+    }
+
+    /// Median time from spawn to lock, if any piece was placed
+    pub fn median_decision_time(&self, frame_time: f32) -> Option<Duration> {
+        let mut ticks = self.decision_ticks.clone();
+        ticks.sort_unstable();
+        let median = *ticks.get(ticks.len() / 2)?;
+        Some(Duration::from_secs_f64(median as f64 * frame_time as f64))
+    }
+
+    pub fn pieces_per_second(&self, frame_time: f32) -> f32 {
+        let secs = self.play_time(frame_time).as_secs_f32();
+        if secs > 0.0 {
+            self.total_pieces() as f32 / secs
+        } else {
+            0.0
+        }
+    }
+
+    /// Score at the latest sample, which is the final score once the run is
+    /// over
+    pub fn score(&self) -> u64 {
+        self.samples.last().map_or(0, |sample| sample.score)
+    }
+
+    /// Share of the lines cleared that went in clears of four, from 0 to 1
+    pub fn tetris_rate(&self) -> f32 {
+        if self.lines > 0 {
+            (self.clears[MAX_CLEAR - 1] * MAX_CLEAR as u32) as f32 / self.lines as f32
+        } else {
+            0.0
+        }
+    }
+}
+
+/// How one run did against another
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StatsDelta {
+    pub score: i64,
+    pub pieces_per_second: f32,
+    /// Change in [`GameStats::tetris_rate`], from -1 to 1
+    pub tetris_rate: f32,
+}
+
+/// How run `a` did against run `b`: positive where `a` did more
+pub fn diff(a: &GameStats, b: &GameStats) -> StatsDelta {
+    let frame_time = TICK_MILLIS as f32 / 1000.0;
+    StatsDelta {
+        score: a.score() as i64 - b.score() as i64,
+        pieces_per_second: a.pieces_per_second(frame_time) - b.pieces_per_second(frame_time),
+        tetris_rate: a.tetris_rate() - b.tetris_rate(),
+    }
+}
+
+impl StatsDelta {
+    /// Each change as its label, the change written out with its sign, and
+    /// whether it's up or down. Changes too small to show count as neither
+    pub fn changes(&self) -> [(&'static str, String, Ordering); 3] {
+        let pps = (self.pieces_per_second * 100.0).round() / 100.0;
+        let rate = (self.tetris_rate * 100.0).round();
+        [
+            ("Score", signed(self.score as f64, 0), self.score.cmp(&0)),
+            ("PPS", signed(pps.into(), 2), sign(pps)),
+            ("Tetris", format!("{}%", signed(rate.into(), 0)), sign(rate)),
+        ]
+    }
+}
+
+/// `value` with `decimals` decimal places, signed unless it's nothing
+fn signed(value: f64, decimals: usize) -> String {
+    if value == 0.0 {
+        format!("{:.*}", decimals, 0.0)
+    } else {
+        format!("{:+.*}", decimals, value)
+    }
+}
+
+fn sign(value: f32) -> Ordering {
+    value.partial_cmp(&0.0).unwrap_or(Ordering::Equal)
+}
+
+impl fmt::Display for StatsDelta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (label, change, _)) in self.changes().iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} {}", label, change)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plays out `placements` as (shape, lines cleared), `ticks` frames apart
+    fn play(placements: &[(Option<usize>, usize)], ticks: u64) -> GameStats {
+        let mut stats = GameStats::new();
+        for &(kind, lines) in placements {
+            stats.piece_spawned(kind);
+            for _ in 0..ticks {
+                stats.tick();
+            }
+            stats.piece_placed(kind, lines);
+        }
+        stats
+    }
+
+    #[test]
+    fn counts_pieces_by_shape() {
+        let stats = play(&[(Some(0), 0), (Some(2), 0), (Some(0), 0), (None, 0)], 1);
+        assert_eq!(stats.pieces, [2, 0, 1, 0, 0, 0, 0]);
+        assert_eq!(stats.other_pieces, 1);
+        assert_eq!(stats.total_pieces(), 4);
+    }
+
+    #[test]
+    fn sorts_clears_by_size() {
+        let stats = play(
+            &[
+                (Some(0), 1),
+                (Some(0), 2),
+                (Some(0), 3),
+                (Some(0), 4),
+                (None, 5),
+            ],
+            1,
+        );
+        // five lines at once from a pentomino counts with the tetrises
+        assert_eq!(stats.clears, [1, 1, 1, 2]);
+        assert_eq!(stats.lines, 15);
+    }
+
+    #[test]
+    fn combo_breaks_on_a_placement_without_a_clear() {
+        let stats = play(
+            &[
+                (Some(0), 1),
+                (Some(0), 1),
+                (Some(0), 1),
+                (Some(1), 0),
+                (Some(0), 2),
+            ],
+            1,
+        );
+        assert_eq!(stats.max_combo, 3);
+        assert_eq!(stats.combo, 1);
+    }
+
+    #[test]
+    fn pieces_per_second_follows_the_frame_time() {
+        let stats = play(&[(Some(0), 0); 10], 8);
+        assert_eq!(stats.ticks, 80);
+        assert_eq!(stats.play_time(0.125), Duration::from_secs(10));
+        assert!((stats.pieces_per_second(0.125) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn nothing_played_is_zero_pieces_per_second() {
+        assert_eq!(GameStats::new().pieces_per_second(0.05), 0.0);
+        assert_eq!(GameStats::new().median_decision_time(0.05), None);
+    }
+
+    #[test]
+    fn decision_time_is_the_median_from_spawn_to_lock() {
+        let mut stats = GameStats::new();
+        for &ticks in &[4, 1, 10] {
+            stats.piece_spawned(Some(0));
+            for _ in 0..ticks {
+                stats.tick();
+            }
+            stats.piece_placed(Some(0), 0);
+        }
+        assert_eq!(stats.decision_ticks, vec![4, 1, 10]);
+        assert_eq!(
+            stats.median_decision_time(0.125),
+            Some(Duration::from_millis(500))
+        );
+    }
+
+    #[test]
+    fn a_split_is_taken_as_each_tenth_line_goes() {
+        // a tetris every ten frames: 12 lines at 30, 20 at 50, 32 at 80, 40 at
+        // 100
+        let stats = play(&[(Some(0), 4); 10], 10);
+        assert_eq!(stats.lines, 40);
+        assert_eq!(stats.splits, vec![30, 50, 80, 100]);
+
+        let short = play(&[(Some(0), 3); 3], 10);
+        assert_eq!(short.lines, 9);
+        assert!(short.splits.is_empty());
+    }
+
+    #[test]
+    fn a_clear_passing_several_splits_takes_each_of_them() {
+        let mut stats = play(&[(Some(0), 1)], 7);
+        stats.piece_spawned(None);
+        for _ in 0..5 {
+            stats.tick();
+        }
+        stats.piece_placed(None, 25);
+        assert_eq!(stats.lines, 26);
+        assert_eq!(stats.splits, vec![12, 12]);
+    }
+
+    /// Droughts after pieces of `kinds` spawn, in order
+    fn droughts(kinds: &[Option<usize>]) -> Droughts {
+        let mut droughts = Droughts::default();
+        for &kind in kinds {
+            droughts.spawned(kind);
+        }
+        droughts
+    }
+
+    #[test]
+    fn a_shape_spawning_ends_only_its_own_drought() {
+        // I, O, T, I, S
+        let seen = droughts(&[Some(0), Some(1), Some(2), Some(0), Some(3)]);
+        assert_eq!(seen.0, [1, 3, 2, 0, 5, 5, 5]);
+        assert_eq!(droughts(&[]).0, [0; PIECE_KINDS]);
+    }
+
+    #[test]
+    fn pieces_from_other_sets_keep_every_shape_waiting() {
+        let seen = droughts(&[Some(0), None, None]);
+        assert_eq!(seen.of(0), 2);
+        assert_eq!(seen.of(6), 3);
+    }
+
+    #[test]
+    fn a_long_i_drought_counts_every_piece_in_it() {
+        let mut kinds = vec![Some(0)];
+        kinds.extend((0..13).map(|i| Some(1 + i % 6)));
+        let mut stats = play(&kinds.iter().map(|&k| (k, 0)).collect::<Vec<_>>(), 1);
+        assert_eq!(stats.droughts.of(0), 13);
+
+        // counted as it spawns, before it's placed
+        stats.piece_spawned(Some(0));
+        assert_eq!(stats.droughts.of(0), 0);
+        assert_eq!(stats.droughts.of(1), 1);
+    }
+
+    fn heatmap(columns: &[usize]) -> Heatmap {
+        let mut heatmap = Heatmap::default();
+        for &column in columns {
+            heatmap.add(column);
+        }
+        heatmap
+    }
+
+    #[test]
+    fn the_heatmap_counts_cells_by_column() {
+        let heatmap = heatmap(&[3, 3, 4, 9, 3, 4]);
+        assert_eq!(heatmap.count(3), 3);
+        assert_eq!(heatmap.count(4), 2);
+        assert_eq!(heatmap.count(0), 0);
+        assert_eq!(heatmap.count(40), 0);
+        assert_eq!(heatmap.heat(3), 1.0);
+        assert!((heatmap.heat(9) - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!(heatmap.heat(5), 0.0);
+        assert_eq!(heatmap.busiest(), Some(3));
+        assert_eq!(heatmap.quietest(10), Some(0));
+    }
+
+    #[test]
+    fn the_heatmap_breaks_ties_to_the_left() {
+        let heatmap = heatmap(&[0, 1, 1, 2, 2, 5, 6]);
+        assert_eq!(heatmap.busiest(), Some(1));
+        // the unplaced columns past the last one with a cell count too
+        assert_eq!(heatmap.quietest(10), Some(3));
+        assert_eq!(heatmap.quietest(3), Some(0));
+    }
+
+    #[test]
+    fn an_empty_heatmap_has_no_busiest_or_quietest_column() {
+        for empty in &[Heatmap::default(), Heatmap(vec![0; 10])] {
+            assert_eq!(empty.busiest(), None);
+            assert_eq!(empty.quietest(10), None);
+            assert_eq!(empty.heat(0), 0.0);
+        }
+    }
+
+    #[test]
+    fn heatmaps_add_up_and_read_back() {
+        let mut total = heatmap(&[0, 1, 1]);
+        total.merge(&heatmap(&[1, 7]));
+        total.merge(&Heatmap::default());
+        assert_eq!(total, Heatmap(vec![1, 3, 0, 0, 0, 0, 0, 1]));
+
+        let text = toml::to_string(&Lifetime {
+            columns: total.clone(),
+        })
+        .unwrap();
+        let read: Lifetime = toml::from_str(&text).unwrap();
+        assert_eq!(read.columns, total);
+    }
+
+    /// Somewhere a heatmap could be kept over many runs
+    #[derive(Serialize, Deserialize)]
+    struct Lifetime {
+        columns: Heatmap,
+    }
+
+    /// A run of `placements` a second apart, ending on `score`
+    fn finished(placements: &[(Option<usize>, usize)], score: u64) -> GameStats {
+        let mut stats = play(placements, 1000 / TICK_MILLIS);
+        stats.sample(score);
+        stats
+    }
+
+    #[test]
+    fn the_tetris_rate_is_the_share_of_lines_cleared_four_at_a_time() {
+        assert_eq!(GameStats::new().tetris_rate(), 0.0);
+        let stats = play(&[(Some(0), 4), (Some(1), 2), (Some(2), 2)], 1);
+        assert_eq!(stats.tetris_rate(), 0.5);
+    }
+
+    #[test]
+    fn diff_is_positive_where_the_first_run_did_more() {
+        let better = finished(&[(Some(0), 4), (Some(1), 0)], 1200);
+        let worse = finished(
+            &[(Some(0), 1), (Some(1), 1), (Some(2), 0), (Some(3), 0)],
+            200,
+        );
+        let delta = diff(&better, &worse);
+        assert_eq!(delta.score, 1000);
+        assert!((delta.pieces_per_second - 0.0).abs() < 1e-4);
+        assert_eq!(delta.tetris_rate, 1.0);
+        assert_eq!(diff(&worse, &better).score, -1000);
+        assert_eq!(diff(&better, &better), StatsDelta::default());
+    }
+
+    #[test]
+    fn changes_are_written_with_their_sign() {
+        let delta = StatsDelta {
+            score: 1500,
+            pieces_per_second: -0.123,
+            tetris_rate: 0.0001,
+        };
+        assert_eq!(
+            delta.changes(),
+            [
+                ("Score", "+1500".to_string(), Ordering::Greater),
+                ("PPS", "-0.12".to_string(), Ordering::Less),
+                ("Tetris", "0%".to_string(), Ordering::Equal),
+            ]
+        );
+        assert_eq!(delta.to_string(), "Score +1500, PPS -0.12, Tetris 0%");
+    }
+}
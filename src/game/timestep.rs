@@ -0,0 +1,129 @@
+//! Turns however much time passed between frames into whole ticks.
+//!
+//! Only time the states are updated through counts. While the window is
+//! minimised the main loop stops updating them altogether, so their clocks
+//! stop too, rather than running on unseen or catching up once it's back.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use crate::sim::TICK_MILLIS;
+
+/// Most ticks a single frame catches up on. Time past that is dropped, so a
+/// stall (dragging the window, a breakpoint) pauses the game instead of
+/// fast-forwarding through it afterwards
+pub const MAX_CATCH_UP_TICKS: u32 = 5;
+
+/// Ticks run by every timestep together since they were last taken
+static TICKS_RUN: AtomicU32 = AtomicU32::new(0);
+
+/// Ticks run by every timestep together since this was last called, for
+/// seeing how many a frame took
+pub fn take_ticks_run() -> u32 {
+    TICKS_RUN.swap(0, Ordering::Relaxed)
+}
+
+/// Runs updates at a fixed rate, however irregular the frames are
+#[derive(Clone, Copy, Debug)]
+pub struct FixedTimestep {
+    /// Length of a tick
+    step: Duration,
+
+    /// Time passed that hasn't been ticked away yet
+    accum: Duration,
+
+    /// Ticks run so far
+    ticker: u64,
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(TICK_MILLIS))
+    }
+}
+
+impl FixedTimestep {
+    pub fn new(step: Duration) -> Self {
+        Self {
+            step,
+            accum: Duration::from_secs(0),
+            ticker: 0,
+        }
+    }
+
+    /// Adds the time a frame took, to be ticked away with [`tick`](Self::tick)
+    pub fn accumulate(&mut self, dt: Duration) {
+        self.accum = (self.accum + dt).min(self.step * MAX_CATCH_UP_TICKS);
+    }
+
+    /// Whether another tick is due, counting it if so. Meant to be looped on
+    /// until it returns false
+    pub fn tick(&mut self) -> bool {
+        if self.accum < self.step {
+            return false;
+        }
+        self.accum -= self.step;
+        self.ticker += 1;
+        TICKS_RUN.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    /// Ticks run so far
+    pub fn ticker(&self) -> u64 {
+        self.ticker
+    }
+
+    /// How far the time left over has got towards the next tick, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        (self.accum.as_secs_f32() / self.step.as_secs_f32()).min(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ticks due now, taking them all
+    fn drain(timestep: &mut FixedTimestep) -> u32 {
+        let mut ticks = 0;
+        while timestep.tick() {
+            ticks += 1;
+        }
+        ticks
+    }
+
+    #[test]
+    fn a_long_stall_catches_up_only_so_far() {
+        let mut timestep = FixedTimestep::default();
+        timestep.accumulate(Duration::from_secs(5));
+        assert_eq!(drain(&mut timestep), MAX_CATCH_UP_TICKS);
+        assert_eq!(timestep.ticker(), MAX_CATCH_UP_TICKS as u64);
+
+        // and nothing of it is left over for the next frame
+        assert_eq!(timestep.progress(), 0.0);
+        timestep.accumulate(Duration::from_millis(TICK_MILLIS));
+        assert_eq!(drain(&mut timestep), 1);
+    }
+
+    #[test]
+    fn short_frames_add_up_to_a_tick() {
+        let mut timestep = FixedTimestep::new(Duration::from_millis(16));
+        timestep.accumulate(Duration::from_millis(10));
+        assert_eq!(drain(&mut timestep), 0);
+        assert!((timestep.progress() - 0.625).abs() < 1e-6);
+        timestep.accumulate(Duration::from_millis(10));
+        assert_eq!(drain(&mut timestep), 1);
+        assert!((timestep.progress() - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn steady_frames_tick_at_the_fixed_rate() {
+        let mut timestep = FixedTimestep::new(Duration::from_millis(10));
+        let mut ticks = 0;
+        for _ in 0..1000 {
+            timestep.accumulate(Duration::from_millis(7));
+            ticks += drain(&mut timestep);
+        }
+        assert_eq!(ticks, 700);
+    }
+}
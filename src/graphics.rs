@@ -1,10 +1,39 @@
+use std::borrow::Cow;
+use std::cell::{Cell, Ref, RefCell};
+use std::sync::{Arc, Mutex};
+
 use cgmath::prelude::*;
-use image::GenericImageView;
 use wgpu::util::DeviceExt;
 
+use crate::ui::layout::{Layout, Rect, DEFAULT_SCALE};
+
+pub mod assets;
+pub mod atlas;
+pub mod chart;
+pub mod debug;
+pub mod falling;
+pub mod features;
+pub mod font;
 pub mod lines;
+pub mod mesh;
+pub mod panel;
+pub mod particles;
+pub mod post;
+pub mod profiler;
+pub mod screenshot;
 pub mod shader;
 pub mod text;
+pub mod theme;
+pub mod transform;
+#[cfg(feature = "ttf")]
+pub mod ttf;
+
+/// Matrices a frame can be drawn with at once. Slot 0 holds the screens' usual
+/// projection, see [`GraphicsState::update_projection`]
+pub const TRANSFORM_SLOTS: usize = 16;
+
+/// Bytes between slots, the most any adapter needs uniform bindings aligned to
+const TRANSFORM_STRIDE: usize = 256;
 
 #[rustfmt::skip]
 pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
@@ -15,19 +44,425 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
 );
 
 #[repr(C)]
-#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
     pub position: [f32; 3],
-    pub color: [f32; 3],
+    /// Red, green, blue and alpha, from 0 to 1. Colours are sRGB, as the theme
+    /// and any palette give them, and made linear in the vertex shader when
+    /// the frame is stored as sRGB, so they come out on screen unchanged
+    pub color: [f32; 4],
     pub tex_coords: [f32; 2],
 }
 
+/// `color` at the given opacity, from 0 for invisible to 1 for solid
+pub fn with_alpha(color: [f32; 3], alpha: f32) -> [f32; 4] {
+    [color[0], color[1], color[2], alpha]
+}
+
+/// `color` drawn solid
+pub fn opaque(color: [f32; 3]) -> [f32; 4] {
+    with_alpha(color, 1.0)
+}
+
 impl From<cgmath::Vector2<f32>> for Vertex {
     fn from(vec: cgmath::Vector2<f32>) -> Self {
         Self {
             position: [vec.x, vec.y, 0.0],
             tex_coords: [0.0, 0.0],
-            color: [0.0, 0.00625, 0.025],
+            color: [0.0, 0.00625, 0.025, 1.0],
+        }
+    }
+}
+
+/// Whether colours drawn into `format` are encoded as sRGB on the way in,
+/// and so have to be given as linear
+pub fn is_srgb(format: wgpu::TextureFormat) -> bool {
+    matches!(
+        format,
+        wgpu::TextureFormat::Rgba8UnormSrgb | wgpu::TextureFormat::Bgra8UnormSrgb
+    )
+}
+
+/// The linear value of the sRGB colour component `c`, both from 0 to 1
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Image a frame ends up in
+enum FrameTarget {
+    /// Next image for the window
+    SwapChain(wgpu::SwapChainTexture),
+    /// Image of the same size and format, off screen
+    Texture(wgpu::TextureView),
+    /// Smaller image drawn into, to be blown up onto the next image for the
+    /// window by [`GraphicsState::end_frame`]
+    Scaled {
+        view: wgpu::TextureView,
+        output: wgpu::SwapChainTexture,
+    },
+}
+
+/// The image being drawn this frame, which every visible state draws into in
+/// turn. It's shown once dropped, or once [`GraphicsState::end_frame`] is done
+/// with it
+///
+/// Each pass onto it is still submitted as soon as it's recorded: meshes and
+/// effect parameters are rewritten between passes, and the queue carries out
+/// every write before the next submission, so holding passes back for one
+/// submission at the end would draw them all with the last pass's data.
+pub struct Frame {
+    target: FrameTarget,
+
+    /// Multisampled image drawn into and resolved onto `target` after every
+    /// pass, if antialiasing is on
+    msaa: Option<wgpu::TextureView>,
+
+    /// What the first pass clears the image to
+    background: wgpu::Color,
+
+    /// Whether a pass has cleared the image yet
+    cleared: bool,
+}
+
+impl Frame {
+    /// Starts a render pass onto the frame. The first pass clears it to the
+    /// background, later ones draw over what's already there
+    pub fn begin_pass<'a>(
+        &'a mut self,
+        encoder: &'a mut wgpu::CommandEncoder,
+    ) -> wgpu::RenderPass<'a> {
+        let load = if self.cleared {
+            wgpu::LoadOp::Load
+        } else {
+            wgpu::LoadOp::Clear(self.background)
+        };
+        self.cleared = true;
+
+        let target = match &self.target {
+            FrameTarget::SwapChain(texture) => &texture.view,
+            FrameTarget::Texture(view) | FrameTarget::Scaled { view, .. } => view,
+        };
+        let (view, resolve_target) = match &self.msaa {
+            Some(msaa) => (msaa, Some(target)),
+            None => (target, None),
+        };
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                ops: wgpu::Operations { load, store: true },
+                resolve_target,
+                view,
+            }],
+            depth_stencil_attachment: None,
+        })
+    }
+}
+
+/// Projection filling the frame with `view`, given in the world units the
+/// screens are laid out in
+pub fn view_projection(view: &Rect) -> cgmath::Matrix4<f32> {
+    cgmath::ortho(
+        view.x,
+        view.x + view.width,
+        view.y + view.height,
+        view.y,
+        -1.0,
+        1.0,
+    )
+}
+
+/// Backend names that can be asked for by hand, for working around drivers
+pub const BACKEND_NAMES: &[(&str, wgpu::BackendBit)] = &[
+    ("vulkan", wgpu::BackendBit::VULKAN),
+    ("dx12", wgpu::BackendBit::DX12),
+    ("dx11", wgpu::BackendBit::DX11),
+    ("metal", wgpu::BackendBit::METAL),
+    ("gl", wgpu::BackendBit::GL),
+];
+
+/// The backend called `name`, ignoring case
+pub fn backend_from_name(name: &str) -> Option<wgpu::BackendBit> {
+    BACKEND_NAMES
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|&(_, backend)| backend)
+}
+
+/// Adapter preferences tried in turn, in case the fast one can't be had
+const POWER_PREFERENCES: &[wgpu::PowerPreference] = &[
+    wgpu::PowerPreference::HighPerformance,
+    wgpu::PowerPreference::LowPower,
+];
+
+/// Samples per pixel with antialiasing on
+pub const MSAA_SAMPLES: u32 = 4;
+
+/// The sample count to actually use when `requested` is asked for. Every
+/// format that can be rendered to is guaranteed to take 4 samples, while other
+/// counts aren't, so anything else falls back to no antialiasing
+pub fn supported_sample_count(requested: u32) -> u32 {
+    if requested == MSAA_SAMPLES {
+        MSAA_SAMPLES
+    } else {
+        1
+    }
+}
+
+/// Present modes to try, best first, when `preferred` is asked for. Fifo is
+/// the only mode every surface has to support, so it's always the last resort
+fn present_mode_candidates(preferred: wgpu::PresentMode) -> &'static [wgpu::PresentMode] {
+    use wgpu::PresentMode::*;
+    match preferred {
+        Fifo => &[Fifo],
+        Mailbox => &[Mailbox, Immediate, Fifo],
+        Immediate => &[Immediate, Mailbox, Fifo],
+    }
+}
+
+/// Device errors are sent here rather than panicking straight away, so that
+/// trying a swap chain the surface won't take isn't fatal
+#[derive(Clone, Default)]
+struct ErrorCatcher {
+    /// Error raised since catching began, or None when not catching
+    caught: Arc<Mutex<Option<Option<String>>>>,
+}
+
+impl ErrorCatcher {
+    /// Makes this the handler for `device`'s errors. Outside of
+    /// [`catch`](Self::catch) they panic just as they would have
+    fn install(&self, device: &wgpu::Device) {
+        let caught = self.caught.clone();
+        device.on_uncaptured_error(move |error| match &mut *caught.lock().unwrap() {
+            Some(slot) => *slot = Some(error.to_string()),
+            None => panic!("wgpu error: {}", error),
+        });
+    }
+
+    /// Runs `f`, giving back the first error the device raised meanwhile
+    fn catch<T>(&self, f: impl FnOnce() -> T) -> (T, Option<String>) {
+        *self.caught.lock().unwrap() = Some(None);
+        let result = f();
+        let error = self.caught.lock().unwrap().take().flatten();
+        (result, error)
+    }
+}
+
+/// Creates a swap chain presenting with `preferred`, or the next best mode the
+/// surface takes, leaving the mode used in `sc_desc`
+fn create_swap_chain(
+    device: &wgpu::Device,
+    surface: &wgpu::Surface,
+    sc_desc: &mut wgpu::SwapChainDescriptor,
+    preferred: wgpu::PresentMode,
+    errors: &ErrorCatcher,
+) -> wgpu::SwapChain {
+    let candidates = present_mode_candidates(preferred);
+    for &mode in &candidates[..candidates.len() - 1] {
+        sc_desc.present_mode = mode;
+        match errors.catch(|| device.create_swap_chain(surface, sc_desc)) {
+            (swap_chain, None) => return swap_chain,
+            (_, Some(error)) => eprintln!(
+                "Warning: couldn't present with {:?}, trying the next mode: {}",
+                mode, error
+            ),
+        }
+    }
+    sc_desc.present_mode = wgpu::PresentMode::Fifo;
+    device.create_swap_chain(surface, sc_desc)
+}
+
+/// The game's icon, ready to give to the window
+pub fn window_icon() -> Result<winit::window::Icon, Box<dyn std::error::Error>> {
+    let image = image::load_from_memory(&assets::ICON.load()?)?.to_rgba8();
+    let (width, height) = image.dimensions();
+    Ok(winit::window::Icon::from_rgba(
+        image.into_raw(),
+        width,
+        height,
+    )?)
+}
+
+/// Image the frame is drawn into before being resolved onto the swap chain,
+/// if `sample_count` calls for one
+fn create_msaa_texture(
+    device: &wgpu::Device,
+    sc_desc: &wgpu::SwapChainDescriptor,
+    sample_count: u32,
+) -> Option<wgpu::Texture> {
+    if sample_count == 1 {
+        return None;
+    }
+    Some(device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("msaa_texture"),
+        size: wgpu::Extent3d {
+            width: sc_desc.width,
+            height: sc_desc.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format: sc_desc.format,
+        usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+    }))
+}
+
+/// Texture registered with [`GraphicsState::register_texture`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextureId(usize);
+
+/// Sends `image` to the GPU as an sRGB texture, bound with a sampler that
+/// scales it with `filter`
+fn create_texture_bind_group(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+    image: &image::RgbaImage,
+    filter: wgpu::FilterMode,
+    label: &str,
+) -> wgpu::BindGroup {
+    let texture = device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            label: Some(label),
+            mip_level_count: 1,
+            sample_count: 1,
+            size: wgpu::Extent3d {
+                width: image.width(),
+                height: image.height(),
+                depth_or_array_layers: 1,
+            },
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        },
+        image,
+    );
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: filter,
+        min_filter: filter,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        ..Default::default()
+    });
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some(label),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&sampler),
+            },
+        ],
+    })
+}
+
+/// Why graphics couldn't be set up
+#[derive(Debug)]
+pub enum GraphicsInitError {
+    /// No backend had an adapter that can draw to the window
+    NoAdapter,
+    /// The adapter refused to hand out a device
+    DeviceRequestFailed(wgpu::RequestDeviceError),
+    /// A texture or other asset couldn't be read or decoded, with the file
+    /// it was read from
+    AssetLoadFailed(String, String),
+}
+
+impl GraphicsInitError {
+    /// `asset` couldn't be used because of `error`
+    fn asset(asset: &assets::Asset, error: &dyn std::error::Error) -> Self {
+        let path = asset
+            .file()
+            .map_or_else(|| asset.path.to_string(), |p| p.display().to_string());
+        Self::AssetLoadFailed(path, error.to_string())
+    }
+}
+
+impl std::fmt::Display for GraphicsInitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoAdapter => write!(
+                f,
+                "no graphics adapter can draw to the window; \
+                 check your drivers support Vulkan, Metal or DirectX 12, \
+                 or pick another backend with --backend"
+            ),
+            Self::DeviceRequestFailed(e) => write!(f, "couldn't open the graphics device: {}", e),
+            Self::AssetLoadFailed(path, e) => write!(
+                f,
+                "couldn't load {}: {}; delete it to use the built in copy",
+                path, e
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GraphicsInitError {}
+
+/// Texture a TrueType font's glyphs are packed into
+#[cfg(feature = "ttf")]
+struct TtfAtlas {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+}
+
+#[cfg(feature = "ttf")]
+impl TtfAtlas {
+    fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            label: Some("ttf_texture"),
+            mip_level_count: 1,
+            sample_count: 1,
+            size: wgpu::Extent3d {
+                width: ttf::ATLAS_SIZE,
+                height: ttf::ATLAS_SIZE,
+                depth_or_array_layers: 1,
+            },
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // glyphs are scaled from one size, so they're smoothed rather than
+        // blocky like the bitmap font's
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("ttf_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+        Self {
+            texture,
+            bind_group,
         }
     }
 }
@@ -40,50 +475,129 @@ pub struct GraphicsState {
     pub swap_chain: wgpu::SwapChain,
     pub pipeline: wgpu::RenderPipeline,
     pub text_pipeline: wgpu::RenderPipeline,
-    pub mat_buffer_bind_group: wgpu::BindGroup,
+    /// Line polygon mode copy of `pipeline`, if the adapter can draw lines
+    pub wireframe_pipeline: Option<wgpu::RenderPipeline>,
+    /// Whether flat geometry is shown as a wireframe instead of text and fills
+    pub wireframe: bool,
+    /// One matrix per transform slot, [`TRANSFORM_STRIDE`] bytes apart
     pub mat_buffer: wgpu::Buffer,
-    pub text_texture_bind_group: wgpu::BindGroup,
+    /// Binding of each transform slot
+    mat_buffer_bind_groups: Vec<wgpu::BindGroup>,
+    /// Layout of every texture's bind group, a view and then a sampler
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    /// Textures registered so far, by [`TextureId`]
+    textures: Vec<wgpu::BindGroup>,
+    /// Bitmap font atlas, see [`text_bind_group`](Self::text_bind_group)
+    font_texture: TextureId,
+    /// Atlas of the TrueType font text is drawn with, if there is one
+    #[cfg(feature = "ttf")]
+    ttf_atlas: Option<TtfAtlas>,
+    pub features: features::NegotiatedFeatures,
+    /// Times the swap chain had to be rebuilt with a different format
+    pub swap_chain_rebuilds: u32,
+    /// Samples per pixel the pipelines and `msaa_texture` were made with
+    sample_count: u32,
+    msaa_texture: Option<wgpu::Texture>,
+    /// Window pixels across each one drawn, see [`set_pixel_scale`](Self::set_pixel_scale)
+    pixel_scale: u32,
+    /// Size of the text and menus, see [`layout`](Self::layout)
+    pub ui_scale: f32,
+    /// Off screen target and pipeline overlays draw what's behind them through
+    post: post::PostProcess,
+    /// Where frame time goes, for the overlay
+    pub profiler: profiler::Profiler,
+    /// Buffers flat geometry is drawn from
+    pub geometry_mesh: RefCell<mesh::MeshBuffer>,
+    /// Buffers text is drawn from
+    pub text_mesh: RefCell<mesh::MeshBuffer>,
+    /// Builders geometry and text are put together in, kept between frames
+    pub scratch: mesh::MeshPool,
+    /// Colours everything is drawn in
+    pub theme: theme::Theme,
+    adapter: wgpu::Adapter,
+    pipeline_sources: PipelineSources,
+    /// Present mode asked for, which `sc_desc` may have had to fall back from
+    preferred_present_mode: wgpu::PresentMode,
+    /// Whether `preferred_present_mode` changed since the swap chain was made
+    present_mode_pending: bool,
+    /// Bytes written to buffers and textures since they were last taken, see
+    /// [`take_uploaded_bytes`](Self::take_uploaded_bytes)
+    uploaded_bytes: Cell<u64>,
+    errors: ErrorCatcher,
 }
 
 impl GraphicsState {
-    pub async fn new(window: &glfw::Window) -> Self {
-        let instance = wgpu::Instance::new(wgpu::BackendBit::VULKAN);
+    /// Sets up drawing to `window` on `backend`, or on whichever of the
+    /// platform's main backends works if it's None
+    pub async fn new(
+        window: &winit::window::Window,
+        backend: Option<wgpu::BackendBit>,
+        present_mode: wgpu::PresentMode,
+        sample_count: u32,
+    ) -> Result<Self, GraphicsInitError> {
+        let backend = backend.unwrap_or(wgpu::BackendBit::PRIMARY);
+        let instance = wgpu::Instance::new(backend);
         let surface = unsafe { instance.create_surface(window) };
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                compatible_surface: Some(&surface),
-                power_preference: wgpu::PowerPreference::HighPerformance,
-            })
-            .await
-            .expect("Failed to get wgpu adapter");
+        let mut adapter = None;
+        for &power_preference in POWER_PREFERENCES {
+            adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    compatible_surface: Some(&surface),
+                    power_preference,
+                })
+                .await;
+            if adapter.is_some() {
+                break;
+            }
+            eprintln!("Warning: no {:?} adapter found", power_preference);
+        }
+        let adapter = adapter.ok_or(GraphicsInitError::NoAdapter)?;
+        let info = adapter.get_info();
+        eprintln!("Drawing with {} on {:?}", info.name, info.backend);
+        let features =
+            features::NegotiatedFeatures::negotiate(features::OPTIONAL, adapter.features());
+        if !features.denied.is_empty() {
+            eprintln!(
+                "Warning: adapter doesn't support {:?}; anything using it is disabled",
+                features.denied
+            );
+        }
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
                     limits: wgpu::Limits::default(),
-                    features: wgpu::Features::NON_FILL_POLYGON_MODE,
+                    features: features.granted,
                     label: Some("device"),
                 },
                 None,
             )
             .await
-            .expect("Failed to get wgpu device + queue");
-        let (width, height) = window.get_framebuffer_size();
-        let sc_desc = wgpu::SwapChainDescriptor {
-            format: adapter.get_swap_chain_preferred_format(&surface).unwrap(),
-            width: width as u32,
-            height: height as u32,
-            present_mode: wgpu::PresentMode::Mailbox,
+            .map_err(GraphicsInitError::DeviceRequestFailed)?;
+        let errors = ErrorCatcher::default();
+        errors.install(&device);
+        let size = window.inner_size();
+        let mut sc_desc = wgpu::SwapChainDescriptor {
+            // the adapter was picked for being compatible with the surface
+            format: adapter
+                .get_swap_chain_preferred_format(&surface)
+                .ok_or(GraphicsInitError::NoAdapter)?,
+            width: size.width,
+            height: size.height,
+            present_mode,
             usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
         };
-        let swap_chain = device.create_swap_chain(&surface, &sc_desc);
-        let vertex_module = shader::create_shader(&device, "res/shaders/shader.vert.spv").unwrap();
-        let fragment_module =
-            shader::create_shader(&device, "res/shaders/shader.frag.spv").unwrap();
+        let swap_chain = create_swap_chain(&device, &surface, &mut sc_desc, present_mode, &errors);
+        let vertex_module = shader::load(&device, &assets::SHADER_VERT)?;
+        let fragment_module = shader::load(&device, &assets::SHADER_FRAG)?;
 
         let mat = cgmath::Matrix4::<f32>::identity();
         let raw: [[f32; 4]; 4] = mat.into();
+        let mut contents = vec![0; TRANSFORM_SLOTS * TRANSFORM_STRIDE];
+        for slot in contents.chunks_exact_mut(TRANSFORM_STRIDE) {
+            slot[..std::mem::size_of_val(&raw)].copy_from_slice(bytemuck::cast_slice(&raw));
+        }
         let mat_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            contents: bytemuck::cast_slice(&raw),
+            contents: &contents,
             label: Some("mat_buffer"),
             usage: wgpu::BufferUsage::COPY_DST | wgpu::BufferUsage::UNIFORM,
         });
@@ -101,48 +615,39 @@ impl GraphicsState {
                     },
                 }],
             });
-        let mat_buffer_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: mat_buffer.as_entire_binding(),
-            }],
-            label: Some("mat_buffer_bind_group"),
-            layout: &mat_buffer_bind_group_layout,
-        });
-        let text_texture = {
-            let text_texture_img = image::open("res/textures/font.png").unwrap();
-            let rgba = text_texture_img.to_rgba8();
-            let size = text_texture_img.dimensions();
-
-            device.create_texture_with_data(
-                &queue,
-                &wgpu::TextureDescriptor {
-                    dimension: wgpu::TextureDimension::D2,
-                    format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                    label: Some("text_texture"),
-                    mip_level_count: 1,
-                    sample_count: 1,
-                    size: wgpu::Extent3d {
-                        width: size.0,
-                        height: size.1,
-                        depth_or_array_layers: 1,
-                    },
-                    usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
-                },
-                &rgba,
-            )
+        let mat_buffer_bind_groups = (0..TRANSFORM_SLOTS)
+            .map(|slot| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                            buffer: &mat_buffer,
+                            offset: (slot * TRANSFORM_STRIDE) as wgpu::BufferAddress,
+                            size: wgpu::BufferSize::new(std::mem::size_of_val(&raw) as u64),
+                        }),
+                    }],
+                    label: Some("mat_buffer_bind_group"),
+                    layout: &mat_buffer_bind_group_layout,
+                })
+            })
+            .collect();
+        let font_image = {
+            let asset = &assets::FONT;
+            let failed = |e: &dyn std::error::Error| GraphicsInitError::asset(asset, e);
+            let data = asset.load().map_err(|e| failed(&e))?;
+            let rgba = image::load_from_memory(&data)
+                .map_err(|e| failed(&e))?
+                .to_rgba8();
+            if let Err(e) = font::verify_atlas(&rgba) {
+                // a font swapped in on purpose is bound to differ
+                if cfg!(debug_assertions) && !asset.is_overridden() {
+                    panic!("{}; regenerate it with `cargo run --example gen_font`", e);
+                }
+                eprintln!("Warning: {}", e);
+            }
+            rgba
         };
-        let text_texture_view = text_texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let text_texture_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
-        let text_texture_bind_group_layout =
+        let texture_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[
                     wgpu::BindGroupLayoutEntry {
@@ -165,30 +670,504 @@ impl GraphicsState {
                         },
                     },
                 ],
-                label: Some("text_texture_bind_group_layout"),
+                label: Some("texture_bind_group_layout"),
             });
-        let text_texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("text_texture_bind_group"),
-            layout: &text_texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&text_texture_view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&text_texture_sampler),
-                },
-            ],
-        });
+        // the font's pixels are meant to be seen, so it isn't smoothed
+        let font_bind_group = create_texture_bind_group(
+            &device,
+            &queue,
+            &texture_bind_group_layout,
+            &font_image,
+            wgpu::FilterMode::Nearest,
+            "text_texture",
+        );
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("pipeline_layout"),
-            bind_group_layouts: &[
-                &mat_buffer_bind_group_layout,
-                &text_texture_bind_group_layout,
-            ],
+            bind_group_layouts: &[&mat_buffer_bind_group_layout, &texture_bind_group_layout],
             push_constant_ranges: &[],
         });
+        let pipeline_sources = PipelineSources {
+            layout: pipeline_layout,
+            vertex: vertex_module,
+            fragment: fragment_module,
+            text_fragment: shader::load(&device, &assets::TEXQUAD_FRAG)?,
+        };
+        let sample_count = supported_sample_count(sample_count);
+        let pipelines = pipeline_sources.build(&device, sc_desc.format, sample_count, &features);
+        let msaa_texture = create_msaa_texture(&device, &sc_desc, sample_count);
+        let post = post::PostProcess::new(&device, sc_desc.format, sample_count)?;
+        let geometry_mesh = RefCell::new(mesh::MeshBuffer::new(&device, "geometry"));
+        let text_mesh = RefCell::new(mesh::MeshBuffer::new(&device, "text"));
+
+        Ok(Self {
+            surface,
+            device,
+            queue,
+            sc_desc,
+            swap_chain,
+            pipeline: pipelines.pipeline,
+            mat_buffer,
+            mat_buffer_bind_groups,
+            text_pipeline: pipelines.text_pipeline,
+            wireframe_pipeline: pipelines.wireframe_pipeline,
+            wireframe: false,
+            texture_bind_group_layout,
+            textures: vec![font_bind_group],
+            font_texture: TextureId(0),
+            #[cfg(feature = "ttf")]
+            ttf_atlas: None,
+            features,
+            adapter,
+            pipeline_sources,
+            swap_chain_rebuilds: 0,
+            sample_count,
+            msaa_texture,
+            pixel_scale: 1,
+            ui_scale: DEFAULT_SCALE,
+            post,
+            profiler: profiler::Profiler::default(),
+            geometry_mesh,
+            scratch: mesh::MeshPool::default(),
+            text_mesh,
+            theme: theme::Theme::default(),
+            preferred_present_mode: present_mode,
+            present_mode_pending: false,
+            uploaded_bytes: Cell::new(0),
+            errors,
+        })
+    }
+
+    /// Writes `geometry` into `mesh`, handing it back to be drawn from. Each
+    /// draw from the same mesh needs its own submit, as a later write replaces
+    /// what an earlier one left there
+    pub fn upload<'a>(
+        &self,
+        mesh: &'a RefCell<mesh::MeshBuffer>,
+        geometry: &mesh::MeshBuilder,
+    ) -> Ref<'a, mesh::MeshBuffer> {
+        #[cfg(feature = "ttf")]
+        self.write_ttf_atlas();
+        mesh.borrow_mut().write(&self.device, &self.queue, geometry);
+        self.count_upload(
+            std::mem::size_of_val(geometry.vertices()) + std::mem::size_of_val(geometry.indices()),
+        );
+        mesh.borrow()
+    }
+
+    /// Adds `bytes` sent to the GPU to the running count
+    fn count_upload(&self, bytes: usize) {
+        self.uploaded_bytes
+            .set(self.uploaded_bytes.get() + bytes as u64);
+    }
+
+    /// Bytes sent to the GPU since the last call, for the benchmark
+    pub fn take_uploaded_bytes(&self) -> u64 {
+        self.uploaded_bytes.replace(0)
+    }
+
+    /// Atlas text is drawn from, the TrueType font's if one is in use
+    pub fn text_bind_group(&self) -> &wgpu::BindGroup {
+        #[cfg(feature = "ttf")]
+        if let Some(atlas) = &self.ttf_atlas {
+            return &atlas.bind_group;
+        }
+        self.texture(self.font_texture)
+    }
+
+    /// Sends `image` to the GPU to be drawn from, `filter` deciding how it's
+    /// scaled, and gives back what to draw it with
+    pub fn register_texture(
+        &mut self,
+        label: &str,
+        image: &image::RgbaImage,
+        filter: wgpu::FilterMode,
+    ) -> TextureId {
+        let bind_group = create_texture_bind_group(
+            &self.device,
+            &self.queue,
+            &self.texture_bind_group_layout,
+            image,
+            filter,
+            label,
+        );
+        self.textures.push(bind_group);
+        TextureId(self.textures.len() - 1)
+    }
+
+    /// Bind group to draw from texture `id` with, as group 1 of the text
+    /// pipeline
+    pub fn texture(&self, id: TextureId) -> &wgpu::BindGroup {
+        &self.textures[id.0]
+    }
+
+    /// Draws text with the TrueType font at `path`, or with the bitmap font if
+    /// it's empty or can't be loaded
+    #[cfg(feature = "ttf")]
+    pub fn set_font(&mut self, path: &str) {
+        let font = if path.is_empty() {
+            None
+        } else {
+            ttf::TtfFont::load(path)
+                .map_err(|e| eprintln!("Couldn't load font {}: {}", path, e))
+                .ok()
+        };
+        self.ttf_atlas = font
+            .as_ref()
+            .map(|_| TtfAtlas::new(&self.device, &self.texture_bind_group_layout));
+        ttf::set_active(font);
+    }
+
+    /// Draws text with the bitmap font, which is the only one there is without
+    /// the `ttf` feature
+    #[cfg(not(feature = "ttf"))]
+    pub fn set_font(&mut self, path: &str) {
+        if !path.is_empty() {
+            eprintln!(
+                "Can't draw text with {}: built without the ttf feature",
+                path
+            );
+        }
+    }
+
+    /// Sends the TrueType font's atlas to the GPU, if glyphs were added to it
+    #[cfg(feature = "ttf")]
+    fn write_ttf_atlas(&self) {
+        let atlas = match &self.ttf_atlas {
+            Some(atlas) => atlas,
+            None => return,
+        };
+        ttf::with_active(|font| {
+            if let Some(pixels) = font.take_changes() {
+                self.queue.write_texture(
+                    wgpu::ImageCopyTexture {
+                        texture: &atlas.texture,
+                        mip_level: 0,
+                        origin: wgpu::Origin3d::ZERO,
+                    },
+                    pixels,
+                    wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: std::num::NonZeroU32::new(4 * ttf::ATLAS_SIZE),
+                        rows_per_image: None,
+                    },
+                    wgpu::Extent3d {
+                        width: ttf::ATLAS_SIZE,
+                        height: ttf::ATLAS_SIZE,
+                        depth_or_array_layers: 1,
+                    },
+                );
+                self.count_upload(pixels.len());
+            }
+        });
+    }
+
+    /// Pipeline flat geometry should be drawn with
+    pub fn geometry_pipeline(&self) -> &wgpu::RenderPipeline {
+        match &self.wireframe_pipeline {
+            Some(wireframe) if self.wireframe => wireframe,
+            _ => &self.pipeline,
+        }
+    }
+
+    /// Prepares draw batch number `batch` of flat geometry, which is left alone
+    /// unless the wireframe view is on
+    pub fn inspect_geometry<'m>(
+        &self,
+        mesh: &'m mesh::MeshBuilder,
+        batch: usize,
+    ) -> Cow<'m, mesh::MeshBuilder> {
+        if self.wireframe {
+            Cow::Owned(debug::wireframe(
+                mesh.clone(),
+                batch,
+                self.wireframe_pipeline.is_none(),
+            ))
+        } else {
+            Cow::Borrowed(mesh)
+        }
+    }
+
+    /// An empty builder to put a frame's geometry or text together in, which
+    /// goes back to be used again once it's dropped
+    pub fn scratch_mesh(&self) -> mesh::PooledMesh<'_> {
+        self.scratch.take()
+    }
+
+    /// Takes the next image from the swap chain to draw a frame into
+    pub fn begin_frame(&self) -> Result<Frame, wgpu::SwapChainError> {
+        let texture = self.swap_chain.get_current_frame()?.output;
+        if self.pixel_scale > 1 {
+            return Ok(self.frame_into(FrameTarget::Scaled {
+                view: self.scaled_view(),
+                output: texture,
+            }));
+        }
+        Ok(self.frame_into(FrameTarget::SwapChain(texture)))
+    }
+
+    /// Finishes `frame`, blowing it up onto the window first if it was drawn
+    /// at a pixel scale, and shows it
+    pub fn end_frame(&self, frame: Frame) {
+        if let FrameTarget::Scaled { output, .. } = &frame.target {
+            self.blit(&output.view);
+        }
+    }
+
+    /// Window pixels across each pixel drawn
+    pub fn pixel_scale(&self) -> u32 {
+        self.pixel_scale
+    }
+
+    /// Draws frames at a fraction of the window's resolution, each pixel
+    /// covering `scale` by `scale` pixels of the window. 1 draws at the
+    /// window's own
+    pub fn set_pixel_scale(&mut self, scale: u32) {
+        let scale = scale.max(1);
+        if scale != self.pixel_scale {
+            self.pixel_scale = scale;
+            self.fit_frame();
+        }
+    }
+
+    /// Layout of the screens for the swap chain's size, at the UI scale
+    pub fn layout(&self) -> Layout {
+        Layout::for_framebuffer(self.sc_desc.width, self.sc_desc.height, self.ui_scale)
+    }
+
+    /// Size frames are drawn at, in pixels: the swap chain's, divided by the
+    /// pixel scale
+    pub fn render_size(&self) -> (u32, u32) {
+        (
+            (self.sc_desc.width / self.pixel_scale).max(1),
+            (self.sc_desc.height / self.pixel_scale).max(1),
+        )
+    }
+
+    /// The swap chain's description, at the size frames are drawn at
+    fn render_desc(&self) -> wgpu::SwapChainDescriptor {
+        let (width, height) = self.render_size();
+        wgpu::SwapChainDescriptor {
+            width,
+            height,
+            ..self.sc_desc.clone()
+        }
+    }
+
+    /// Left, top, width and height of the part of the window a scaled frame
+    /// covers, in pixels, centred with the remainder around it
+    fn scaled_viewport(&self) -> [f32; 4] {
+        let (width, height) = self.render_size();
+        let (width, height) = (width * self.pixel_scale, height * self.pixel_scale);
+        let left = self.sc_desc.width.saturating_sub(width) / 2;
+        let top = self.sc_desc.height.saturating_sub(height) / 2;
+        [left as f32, top as f32, width as f32, height as f32]
+    }
+
+    /// Where the point `position` across the window and down it, out of 1,
+    /// lies across and down the frame drawn, which a scaled frame doesn't
+    /// quite fill
+    pub fn frame_fraction(&self, position: [f32; 2]) -> [f32; 2] {
+        let [left, top, width, height] = self.scaled_viewport();
+        [
+            (position[0] * self.sc_desc.width as f32 - left) / width,
+            (position[1] * self.sc_desc.height as f32 - top) / height,
+        ]
+    }
+
+    /// Remakes what's sized to the frame for the swap chain's size and the
+    /// pixel scale
+    fn fit_frame(&mut self) {
+        self.msaa_texture =
+            create_msaa_texture(&self.device, &self.render_desc(), self.sample_count);
+        self.post.discard_target();
+        self.update_projection();
+    }
+
+    fn frame_into(&self, target: FrameTarget) -> Frame {
+        Frame {
+            target,
+            msaa: self
+                .msaa_texture
+                .as_ref()
+                .map(|t| t.create_view(&wgpu::TextureViewDescriptor::default())),
+            background: self.theme.clear_color(is_srgb(self.sc_desc.format)),
+            cleared: false,
+        }
+    }
+
+    /// Fits the swap chain to `width` by `height` physical pixels. A zero
+    /// size, as a minimised window has, is ignored
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.sc_desc.width = width;
+            self.sc_desc.height = height;
+            self.recreate_swap_chain(self.sc_desc.present_mode);
+            self.fit_frame();
+        }
+    }
+
+    /// The screens' usual view of the world, sized to the frame
+    pub fn projection(&self) -> cgmath::Matrix4<f32> {
+        view_projection(&Rect::canvas(
+            self.sc_desc.width as f32,
+            self.sc_desc.height as f32,
+        ))
+    }
+
+    /// Makes `proj` the projection for whatever is submitted next
+    pub fn write_projection(&self, proj: cgmath::Matrix4<f32>) {
+        self.write_transform(0, proj);
+    }
+
+    /// Makes `matrix` the one in `slot` for whatever is submitted next. Panics
+    /// if there's no such slot
+    pub fn write_transform(&self, slot: usize, matrix: cgmath::Matrix4<f32>) {
+        assert!(slot < TRANSFORM_SLOTS, "no transform slot {}", slot);
+        let raw: [[f32; 4]; 4] = matrix.into();
+        self.queue.write_buffer(
+            &self.mat_buffer,
+            (slot * TRANSFORM_STRIDE) as wgpu::BufferAddress,
+            bytemuck::cast_slice(&raw),
+        );
+        self.count_upload(std::mem::size_of_val(&raw));
+    }
+
+    /// What to bind as group 0 to draw with the matrix in `slot`
+    pub fn transform_bind_group(&self, slot: usize) -> &wgpu::BindGroup {
+        &self.mat_buffer_bind_groups[slot]
+    }
+
+    /// Goes back to the usual projection. Done before every frame, so only
+    /// something that changed it needs to call this
+    pub fn update_projection(&self) {
+        self.write_projection(self.projection());
+    }
+
+    /// Samples per pixel frames are drawn with
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// Switches to drawing with `sample_count` samples per pixel, or the
+    /// closest that's supported, rebuilding the pipelines if that changes it
+    pub fn set_sample_count(&mut self, sample_count: u32) {
+        let sample_count = supported_sample_count(sample_count);
+        if sample_count != self.sample_count {
+            self.sample_count = sample_count;
+            self.rebuild_pipelines();
+            self.msaa_texture =
+                create_msaa_texture(&self.device, &self.render_desc(), sample_count);
+        }
+    }
+
+    /// Remakes the pipelines for the current format and sample count
+    fn rebuild_pipelines(&mut self) {
+        let pipelines = self.pipeline_sources.build(
+            &self.device,
+            self.sc_desc.format,
+            self.sample_count,
+            &self.features,
+        );
+        self.pipeline = pipelines.pipeline;
+        self.text_pipeline = pipelines.text_pipeline;
+        self.wireframe_pipeline = pipelines.wireframe_pipeline;
+        self.post
+            .rebuild(&self.device, self.sc_desc.format, self.sample_count);
+    }
+
+    /// Mode frames are actually presented with, after any fallback
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.sc_desc.present_mode
+    }
+
+    /// Asks for frames to be presented with `mode`, or the next best mode the
+    /// surface supports. The swap chain isn't touched until
+    /// [`apply_present_mode`](Self::apply_present_mode), so this is safe to
+    /// call with a frame on the go
+    pub fn set_present_mode(&mut self, mode: wgpu::PresentMode) {
+        if mode != self.preferred_present_mode {
+            self.preferred_present_mode = mode;
+            self.present_mode_pending = true;
+        }
+    }
+
+    /// Recreates the swap chain if another present mode was asked for, which
+    /// has to happen between frames, returning the
+    /// [`present_mode_fallback`](Self::present_mode_fallback) if it changed
+    pub fn apply_present_mode(&mut self) -> Option<String> {
+        if !std::mem::take(&mut self.present_mode_pending) {
+            return None;
+        }
+        self.recreate_swap_chain(self.preferred_present_mode);
+        self.present_mode_fallback()
+    }
+
+    /// Says so if the surface didn't take the present mode asked for and
+    /// another is being used
+    pub fn present_mode_fallback(&self) -> Option<String> {
+        if self.sc_desc.present_mode == self.preferred_present_mode {
+            None
+        } else {
+            Some(format!(
+                "{:?} isn't supported, using {:?}",
+                self.preferred_present_mode, self.sc_desc.present_mode
+            ))
+        }
+    }
+
+    fn recreate_swap_chain(&mut self, preferred: wgpu::PresentMode) {
+        self.swap_chain = create_swap_chain(
+            &self.device,
+            &self.surface,
+            &mut self.sc_desc,
+            preferred,
+            &self.errors,
+        );
+    }
+
+    /// Recreates the swap chain with the surface's current preferred format, or
+    /// with `format` if given, rebuilding the pipelines if the format changed
+    pub fn rebuild_swap_chain(&mut self, format: Option<wgpu::TextureFormat>) {
+        let format = format
+            .or_else(|| self.adapter.get_swap_chain_preferred_format(&self.surface))
+            .unwrap_or(self.sc_desc.format);
+
+        if format != self.sc_desc.format {
+            self.sc_desc.format = format;
+            self.rebuild_pipelines();
+            self.msaa_texture =
+                create_msaa_texture(&self.device, &self.render_desc(), self.sample_count);
+            self.swap_chain_rebuilds += 1;
+            eprintln!("Swap chain format changed to {:?}", format);
+        }
+
+        self.recreate_swap_chain(self.sc_desc.present_mode);
+    }
+}
+
+struct Pipelines {
+    pipeline: wgpu::RenderPipeline,
+    text_pipeline: wgpu::RenderPipeline,
+    wireframe_pipeline: Option<wgpu::RenderPipeline>,
+}
+
+/// Everything the pipelines are made from besides the target format, kept
+/// around so they can be rebuilt when the swap chain format changes
+struct PipelineSources {
+    layout: wgpu::PipelineLayout,
+    vertex: wgpu::ShaderModule,
+    fragment: wgpu::ShaderModule,
+    text_fragment: wgpu::ShaderModule,
+}
+
+impl PipelineSources {
+    fn build(
+        &self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        features: &features::NegotiatedFeatures,
+    ) -> Pipelines {
         let vblayout = wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
             step_mode: wgpu::InputStepMode::Vertex,
@@ -201,25 +1180,27 @@ impl GraphicsState {
                 },
                 wgpu::VertexAttribute {
                     // color
-                    format: wgpu::VertexFormat::Float32x3,
+                    format: wgpu::VertexFormat::Float32x4,
                     offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
                     shader_location: 1,
                 },
                 wgpu::VertexAttribute {
                     // tex coords
                     format: wgpu::VertexFormat::Float32x2,
-                    offset: std::mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+                    offset: std::mem::size_of::<[f32; 7]>() as wgpu::BufferAddress,
                     shader_location: 2,
                 },
             ],
         };
+        // vertex colours need making linear for a target that encodes them
+        let vertex_entry = if is_srgb(format) { "main_srgb" } else { "main" };
         let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            layout: Some(&pipeline_layout),
+            layout: Some(&self.layout),
             label: Some("pipeline"),
             vertex: wgpu::VertexState {
                 buffers: &[vblayout.clone()],
-                entry_point: "main",
-                module: &vertex_module,
+                entry_point: vertex_entry,
+                module: &self.vertex,
             },
             primitive: wgpu::PrimitiveState {
                 topology: wgpu::PrimitiveTopology::TriangleList,
@@ -234,41 +1215,79 @@ impl GraphicsState {
             multisample: wgpu::MultisampleState {
                 alpha_to_coverage_enabled: false,
                 mask: !0,
-                count: 1,
+                count: sample_count,
             },
             fragment: Some(wgpu::FragmentState {
                 entry_point: "main",
-                module: &fragment_module,
+                module: &self.fragment,
                 targets: &[wgpu::ColorTargetState {
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    format: sc_desc.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    format,
                     write_mask: wgpu::ColorWrite::all(),
                 }],
             }),
         });
-        let text_frag_module =
-            shader::create_shader(&device, "res/shaders/texquad.frag.spv").unwrap();
+        let wireframe_pipeline = if features.wireframe_lines() {
+            Some(
+                device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    layout: Some(&self.layout),
+                    label: Some("wireframe_pipeline"),
+                    vertex: wgpu::VertexState {
+                        buffers: &[vblayout.clone()],
+                        entry_point: vertex_entry,
+                        module: &self.vertex,
+                    },
+                    primitive: wgpu::PrimitiveState {
+                        topology: wgpu::PrimitiveTopology::TriangleList,
+                        strip_index_format: None,
+                        front_face: wgpu::FrontFace::Ccw,
+                        // show back facing and degenerate triangles too
+                        cull_mode: None,
+                        polygon_mode: wgpu::PolygonMode::Line,
+                        conservative: false,
+                        clamp_depth: false,
+                    },
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState {
+                        alpha_to_coverage_enabled: false,
+                        mask: !0,
+                        count: sample_count,
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        entry_point: "main",
+                        module: &self.fragment,
+                        targets: &[wgpu::ColorTargetState {
+                            blend: Some(wgpu::BlendState::REPLACE),
+                            format,
+                            write_mask: wgpu::ColorWrite::all(),
+                        }],
+                    }),
+                }),
+            )
+        } else {
+            None
+        };
         let text_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            layout: Some(&pipeline_layout),
+            layout: Some(&self.layout),
             label: Some("text_pipeline"),
             vertex: wgpu::VertexState {
                 buffers: &[vblayout],
-                entry_point: "main",
-                module: &vertex_module,
+                entry_point: vertex_entry,
+                module: &self.vertex,
             },
             fragment: Some(wgpu::FragmentState {
                 targets: &[wgpu::ColorTargetState {
                     blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    format: sc_desc.format,
+                    format,
                     write_mask: wgpu::ColorWrite::all(),
                 }],
                 entry_point: "main",
-                module: &text_frag_module,
+                module: &self.text_fragment,
             }),
             depth_stencil: None,
             multisample: wgpu::MultisampleState {
                 alpha_to_coverage_enabled: false,
-                count: 1,
+                count: sample_count,
                 mask: !0,
             },
             primitive: wgpu::PrimitiveState {
@@ -281,26 +1300,10 @@ impl GraphicsState {
                 clamp_depth: false,
             },
         });
-
-        Self {
-            surface,
-            device,
-            queue,
-            sc_desc,
-            swap_chain,
+        Pipelines {
             pipeline,
-            mat_buffer,
-            mat_buffer_bind_group,
             text_pipeline,
-            text_texture_bind_group,
-        }
-    }
-
-    pub fn resize(&mut self, width: u32, height: u32) {
-        if width > 0 && height > 0 {
-            self.sc_desc.width = width;
-            self.sc_desc.height = height;
-            self.swap_chain = self.device.create_swap_chain(&self.surface, &self.sc_desc);
+            wireframe_pipeline,
         }
     }
 }
@@ -0,0 +1,251 @@
+//! Files the game ships with, and where they're looked for.
+//!
+//! Assets live under a `res` directory, which is looked for in order: the
+//! directory named by [`ASSETS_VAR`], `res` next to the executable, then `res`
+//! in the working directory, so the game runs from a release zip or an
+//! installed binary as well as from the repository. The first file found is
+//! used, and can be swapped without rebuilding. The essential ones have a copy
+//! built into the executable, used when there's no file anywhere.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+
+/// Environment variable naming a directory to look for assets in before any
+/// other, laid out like `res`
+pub const ASSETS_VAR: &str = "TETRS_ASSETS";
+
+/// Name of the directory assets are kept in
+const RES_DIR: &str = "res";
+
+pub struct Asset {
+    /// Where a replacement is looked for, relative to the asset directories
+    pub path: &'static str,
+    /// Copy used when there's none
+    pub embedded: &'static [u8],
+}
+
+/// Atlas text is drawn from, see [`font`](super::font)
+pub const FONT: Asset = Asset {
+    path: "textures/font.png",
+    embedded: include_bytes!("../../res/textures/font.png"),
+};
+
+/// Picture in the window's title bar and the taskbar
+pub const ICON: Asset = Asset {
+    path: "textures/icon.png",
+    embedded: include_bytes!("../../res/textures/icon.png"),
+};
+
+/// Shaders flat geometry and text are drawn with
+pub const SHADER_VERT: Asset = Asset {
+    path: "shaders/shader.vert.wgsl",
+    embedded: include_bytes!("../../res/shaders/shader.vert.wgsl"),
+};
+pub const SHADER_FRAG: Asset = Asset {
+    path: "shaders/shader.frag.wgsl",
+    embedded: include_bytes!("../../res/shaders/shader.frag.wgsl"),
+};
+pub const TEXQUAD_FRAG: Asset = Asset {
+    path: "shaders/texquad.frag.wgsl",
+    embedded: include_bytes!("../../res/shaders/texquad.frag.wgsl"),
+};
+
+/// Shaders overlays draw what's behind them through, see [`post`](super::post)
+pub const POST_VERT: Asset = Asset {
+    path: "shaders/post.vert.wgsl",
+    embedded: include_bytes!("../../res/shaders/post.vert.wgsl"),
+};
+pub const POST_FRAG: Asset = Asset {
+    path: "shaders/post.frag.wgsl",
+    embedded: include_bytes!("../../res/shaders/post.frag.wgsl"),
+};
+
+impl Asset {
+    /// The file on disk replacing the built in copy, if there is one
+    pub fn file(&self) -> Option<PathBuf> {
+        find(self.path).ok()
+    }
+
+    /// Whether there's a file on disk replacing the built in copy
+    pub fn is_overridden(&self) -> bool {
+        self.file().is_some()
+    }
+
+    /// The file on disk if there is one, otherwise the built in copy. Fails if
+    /// the file is there but can't be read
+    pub fn load(&self) -> std::io::Result<Cow<'static, [u8]>> {
+        match self.file() {
+            Some(path) => std::fs::read(path).map(Cow::Owned),
+            None => Ok(Cow::Borrowed(self.embedded)),
+        }
+    }
+
+    /// Like [`load`](Self::load), for assets that are text
+    pub fn load_text(&self) -> std::io::Result<Cow<'static, str>> {
+        let text = match self.load()? {
+            Cow::Borrowed(data) => std::str::from_utf8(data).map(Cow::Borrowed),
+            Cow::Owned(data) => String::from_utf8(data)
+                .map(Cow::Owned)
+                .map_err(|e| e.utf8_error()),
+        };
+        text.map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+}
+
+/// An asset that isn't in any of the directories assets are looked for in
+#[derive(Debug)]
+pub struct Missing {
+    pub path: String,
+    /// Where it was looked for, in order
+    pub tried: Vec<PathBuf>,
+}
+
+impl fmt::Display for Missing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "couldn't find {}; looked for", self.path)?;
+        for (i, path) in self.tried.iter().enumerate() {
+            let separator = if i == 0 { " " } else { ", " };
+            write!(f, "{}{}", separator, path.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Missing {}
+
+/// Directories assets are looked for in, most important first: the one
+/// named by [`ASSETS_VAR`], `res` next to the executable, then `res` in the
+/// working directory, each only once
+pub fn dirs() -> Vec<PathBuf> {
+    let var = std::env::var_os(ASSETS_VAR).map(PathBuf::from);
+    let exe = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(RES_DIR)));
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    for dir in vec![var, exe, Some(PathBuf::from(RES_DIR))]
+        .into_iter()
+        .flatten()
+    {
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+/// The file at `path` in the first of the [asset directories](dirs) that has
+/// one
+pub fn find(path: &str) -> Result<PathBuf, Missing> {
+    find_in(&dirs(), path)
+}
+
+/// The file or directory at `path` in the first of `dirs` that has one
+pub fn find_in(dirs: &[PathBuf], path: &str) -> Result<PathBuf, Missing> {
+    let tried: Vec<PathBuf> = dirs.iter().map(|dir| dir.join(path)).collect();
+    match tried.iter().find(|candidate| candidate.exists()) {
+        Some(found) => Ok(found.clone()),
+        None => Err(Missing {
+            path: path.to_string(),
+            tried,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    /// A fresh directory under the system's temporary one, for `name`
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("tetrs_assets_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn put(dir: &Path, path: &str, contents: &str) {
+        let file = dir.join(path);
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(file, contents).unwrap();
+    }
+
+    #[test]
+    fn the_first_directory_with_the_file_wins() {
+        let root = temp_dir("order");
+        let dirs: Vec<PathBuf> = ["var", "exe", "cwd"].iter().map(|d| root.join(d)).collect();
+        let path = "textures/font.png";
+        put(&dirs[1], path, "next to the executable");
+        put(&dirs[2], path, "in the working directory");
+        assert_eq!(find_in(&dirs, path).unwrap(), dirs[1].join(path));
+
+        put(&dirs[0], path, "from the variable");
+        assert_eq!(find_in(&dirs, path).unwrap(), dirs[0].join(path));
+
+        std::fs::remove_file(dirs[0].join(path)).unwrap();
+        std::fs::remove_file(dirs[1].join(path)).unwrap();
+        assert_eq!(find_in(&dirs, path).unwrap(), dirs[2].join(path));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn a_missing_asset_names_everywhere_it_was_looked_for() {
+        let root = temp_dir("missing");
+        let dirs = vec![root.join("var"), root.join("exe"), PathBuf::from(RES_DIR)];
+        let missing = find_in(&dirs, "shaders/nowhere.wgsl").unwrap_err();
+        let tried: Vec<PathBuf> = dirs
+            .iter()
+            .map(|d| d.join("shaders/nowhere.wgsl"))
+            .collect();
+        assert_eq!(missing.tried, tried);
+
+        let message = missing.to_string();
+        assert!(message.starts_with("couldn't find shaders/nowhere.wgsl"));
+        let mut rest = &message[..];
+        for path in &tried {
+            // in the order they were tried
+            let at = rest
+                .find(&path.display().to_string())
+                .expect("a place left out");
+            rest = &rest[at..];
+        }
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn the_variable_overrides_files_and_the_built_in_copy_fills_in() {
+        // the only test that touches the variable, as it's shared by the
+        // whole process
+        let var = temp_dir("var");
+        let asset = Asset {
+            path: "textures/tetrs_test_only.png",
+            embedded: b"built in",
+        };
+        std::env::set_var(ASSETS_VAR, &var);
+        let looked_in = dirs();
+        assert_eq!(looked_in[0], var);
+        let exe = std::env::current_exe().unwrap();
+        assert_eq!(looked_in[1], exe.parent().unwrap().join(RES_DIR));
+        assert_eq!(looked_in.last(), Some(&PathBuf::from(RES_DIR)));
+
+        assert!(!asset.is_overridden());
+        assert_eq!(asset.load_text().unwrap(), "built in");
+        put(&var, asset.path, "from the variable");
+        assert_eq!(asset.file(), Some(var.join(asset.path)));
+        assert_eq!(asset.load_text().unwrap(), "from the variable");
+
+        // a directory named twice is only looked in once
+        std::env::set_var(ASSETS_VAR, RES_DIR);
+        let dirs = dirs();
+        let res = dirs.iter().filter(|&d| d == Path::new(RES_DIR)).count();
+        assert_eq!(res, 1);
+        assert_eq!(dirs[0], PathBuf::from(RES_DIR));
+
+        std::env::remove_var(ASSETS_VAR);
+        std::fs::remove_dir_all(&var).unwrap();
+    }
+}
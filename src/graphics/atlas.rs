@@ -0,0 +1,155 @@
+//! Quads drawn with part of a texture on them, like glyphs of the font.
+//!
+//! An [`Atlas`] says where the pictures in a texture are, as a grid of equal
+//! tiles, by name, or both. A [`SpriteBatch`] turns pictures placed on screen
+//! into quads in a mesh, which the text pipeline draws with whichever texture
+//! is bound. The flat pipelines ignore the texture, so sprites drawn with them
+//! come out as plain coloured quads.
+
+use std::collections::HashMap;
+
+use super::{mesh::MeshBuilder, Vertex};
+use crate::ui::layout::X_PER_Y;
+
+/// Part of a texture, in texture coordinates from 0 to 1, y growing down
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub origin: [f32; 2],
+    pub size: [f32; 2],
+}
+
+impl Region {
+    /// All of the texture, for sprites drawn without one
+    pub const WHOLE: Region = Region {
+        origin: [0.0, 0.0],
+        size: [1.0, 1.0],
+    };
+
+    /// The columns of this region from `left` across `width`, both in
+    /// fractions of its width
+    pub fn columns(self, left: f32, width: f32) -> Region {
+        Region {
+            origin: [self.origin[0] + left * self.size[0], self.origin[1]],
+            size: [width * self.size[0], self.size[1]],
+        }
+    }
+}
+
+/// Where the pictures in a texture are
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Atlas {
+    /// Tiles across and down, if the texture is split into a grid
+    grid: Option<(u32, u32)>,
+
+    named: HashMap<&'static str, Region>,
+}
+
+impl Atlas {
+    /// A texture split into `columns` by `rows` equal tiles, numbered left to
+    /// right and then top to bottom
+    pub fn grid(columns: u32, rows: u32) -> Self {
+        Self {
+            grid: Some((columns, rows)),
+            named: HashMap::new(),
+        }
+    }
+
+    /// Gives `region` the name `name`, replacing whatever had it
+    pub fn with_region(mut self, name: &'static str, region: Region) -> Self {
+        self.named.insert(name, region);
+        self
+    }
+
+    /// Tile number `index` of the grid. Panics if the atlas isn't a grid
+    pub fn tile(&self, index: usize) -> Region {
+        let (columns, rows) = self.grid.expect("atlas has no grid");
+        let size = [1.0 / columns as f32, 1.0 / rows as f32];
+        let (column, row) = (index as u32 % columns, index as u32 / columns);
+        Region {
+            origin: [column as f32 * size[0], row as f32 * size[1]],
+            size,
+        }
+    }
+
+    /// The region called `name`, if there is one
+    pub fn region(&self, name: &str) -> Option<Region> {
+        self.named.get(name).copied()
+    }
+}
+
+/// A picture placed on screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprite {
+    /// Part of the texture drawn
+    pub region: Region,
+
+    /// Top left corner and size before it's turned, in world units
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+
+    /// Multiplied with the texture's colour, see [`Vertex::color`]
+    pub color: [f32; 4],
+
+    /// Turn about its middle, clockwise as it looks on screen, in radians
+    pub rotation: f32,
+}
+
+impl Sprite {
+    /// `region` drawn unturned over the rectangle at `position` of `size`
+    pub fn new(region: Region, position: [f32; 2], size: [f32; 2], color: [f32; 4]) -> Self {
+        Self {
+            region,
+            position,
+            size,
+            color,
+            rotation: 0.0,
+        }
+    }
+
+    /// The same sprite turned by `rotation`
+    pub fn rotated(self, rotation: f32) -> Self {
+        Self { rotation, ..self }
+    }
+}
+
+/// Adds sprites to a mesh as a quad each
+pub struct SpriteBatch<'a> {
+    mesh: &'a mut MeshBuilder,
+}
+
+impl<'a> SpriteBatch<'a> {
+    pub fn new(mesh: &'a mut MeshBuilder) -> Self {
+        Self { mesh }
+    }
+
+    pub fn draw(&mut self, sprite: &Sprite) {
+        let [width, height] = sprite.size;
+        let centre = [
+            sprite.position[0] + width / 2.0,
+            sprite.position[1] + height / 2.0,
+        ];
+        let (sin, cos) = sprite.rotation.sin_cos();
+        let [u, v] = sprite.region.origin;
+        let [u_size, v_size] = sprite.region.size;
+
+        // turned as it looks on screen, where x units are narrower than y ones
+        let vertex = |x: f32, y: f32| {
+            let (dx, dy) = ((x - 0.5) * width / X_PER_Y, (y - 0.5) * height);
+            Vertex {
+                position: [
+                    centre[0] + (dx * cos - dy * sin) * X_PER_Y,
+                    centre[1] + dx * sin + dy * cos,
+                    0.0,
+                ],
+                color: sprite.color,
+                tex_coords: [u + x * u_size, v + y * v_size],
+            }
+        };
+        self.mesh.quad([
+            vertex(0.0, 0.0),
+            vertex(0.0, 1.0),
+            vertex(1.0, 0.0),
+            vertex(1.0, 1.0),
+        ]);
+    }
+}
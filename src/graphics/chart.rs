@@ -0,0 +1,220 @@
+//! Line charts, numbers over time drawn into a rectangle of the screen.
+//!
+//! An [`Axis`] widens a range out to round numbers and picks where its ticks
+//! go, and a [`Chart`] maps values along two of them into a [`Rect`], larger
+//! values higher up. What gets drawn, and how, is left to the caller: the
+//! points a chart gives back go straight into the [`lines`](super::lines)
+//! helpers.
+
+use cgmath::Vector2;
+
+use crate::ui::layout::Rect;
+
+/// Multiples of a power of ten ticks are spaced by, smallest first
+const NICE_STEPS: [f32; 4] = [1.0, 2.0, 5.0, 10.0];
+
+/// A range of values, running from one tick to another
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Axis {
+    pub min: f32,
+    pub max: f32,
+    /// Distance between ticks, a power of ten times 1, 2 or 5
+    pub step: f32,
+}
+
+impl Axis {
+    /// Axis covering `min` to `max` with no more than `max_ticks` ticks on
+    /// it, ends included, its ends moved out to the nearest ticks. A range
+    /// with nothing in it is widened to one step above its value
+    pub fn nice(min: f32, max: f32, max_ticks: usize) -> Self {
+        let max = if max > min { max } else { min + 1.0 };
+        let intervals = max_ticks.max(2) - 1;
+        let mut step = nice_step((max - min) / intervals as f32);
+        // moving the ends out to ticks can take an interval more
+        while (max / step).ceil() - (min / step).floor() > intervals as f32 {
+            step = nice_step(step * 1.5);
+        }
+        Self {
+            min: (min / step).floor() * step,
+            max: (max / step).ceil() * step,
+            step,
+        }
+    }
+
+    /// Values ticks are drawn at, from `min` to `max`
+    pub fn ticks(&self) -> Vec<f32> {
+        let count = ((self.max - self.min) / self.step).round() as usize;
+        (0..=count)
+            .map(|i| self.min + i as f32 * self.step)
+            .collect()
+    }
+
+    /// How far along the axis `value` is, 0 at `min` and 1 at `max`
+    pub fn fraction(&self, value: f32) -> f32 {
+        (value - self.min) / (self.max - self.min)
+    }
+}
+
+/// Smallest power of ten times 1, 2 or 5 that's at least `raw`
+fn nice_step(raw: f32) -> f32 {
+    let magnitude = 10f32.powf(raw.log10().floor());
+    NICE_STEPS
+        .iter()
+        .map(|&multiple| multiple * magnitude)
+        .find(|&step| step >= raw * (1.0 - f32::EPSILON))
+        .unwrap_or(10.0 * magnitude)
+}
+
+/// Two axes laid over an area of the screen, `x` across and `y` up
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Chart {
+    pub rect: Rect,
+    pub x: Axis,
+    pub y: Axis,
+}
+
+impl Chart {
+    pub fn new(rect: Rect, x: Axis, y: Axis) -> Self {
+        Self { rect, x, y }
+    }
+
+    /// Where the value (`x`, `y`) lies on screen
+    pub fn point(&self, x: f32, y: f32) -> Vector2<f32> {
+        Vector2::new(
+            self.rect.x + self.x.fraction(x) * self.rect.width,
+            self.rect.y + (1.0 - self.y.fraction(y)) * self.rect.height,
+        )
+    }
+
+    /// Where each of `values` lies on screen, to draw a line through
+    pub fn series(&self, values: &[(f32, f32)]) -> Vec<Vector2<f32>> {
+        values.iter().map(|&(x, y)| self.point(x, y)).collect()
+    }
+
+    /// The same area and `x` axis, measured against `y` instead, for a
+    /// second series with its own scale
+    pub fn with_y(&self, y: Axis) -> Self {
+        Self { y, ..*self }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() <= 1e-4 * f32::max(1.0, b.abs())
+    }
+
+    fn is_nice(step: f32) -> bool {
+        let magnitude = 10f32.powf(step.log10().floor());
+        NICE_STEPS
+            .iter()
+            .any(|&multiple| close(step, multiple * magnitude))
+    }
+
+    #[test]
+    fn steps_are_one_two_or_five_times_a_power_of_ten() {
+        for &(raw, step) in &[
+            (0.07, 0.1),
+            (0.3, 0.5),
+            (1.0, 1.0),
+            (1.1, 2.0),
+            (2.0, 2.0),
+            (3.0, 5.0),
+            (7.0, 10.0),
+            (12.0, 20.0),
+            (45.0, 50.0),
+            (999.0, 1000.0),
+            (5_000.0, 5_000.0),
+        ] {
+            assert!(
+                close(nice_step(raw), step),
+                "{} gave {}",
+                raw,
+                nice_step(raw)
+            );
+        }
+    }
+
+    #[test]
+    fn an_axis_covers_its_range_from_tick_to_tick() {
+        let axis = Axis::nice(0.0, 9_870.0, 6);
+        assert_eq!(axis.step, 2_000.0);
+        assert_eq!(
+            axis.ticks(),
+            vec![0.0, 2_000.0, 4_000.0, 6_000.0, 8_000.0, 10_000.0]
+        );
+
+        let axis = Axis::nice(0.0, 1.0, 11);
+        let ticks = axis.ticks();
+        assert_eq!(ticks.len(), 11);
+        assert!(close(ticks[10], 1.0), "ticks drifted to {}", ticks[10]);
+    }
+
+    #[test]
+    fn an_axis_never_has_more_ticks_than_asked_for() {
+        let values = [0.0, 0.4, 3.0, 13.0, 87.0, 99.5, 120.0, 1_234.0, 98_765.0];
+        for &min in &values {
+            for &max in values.iter().filter(|&&max| max > min) {
+                for max_ticks in 2..12 {
+                    let axis = Axis::nice(min, max, max_ticks);
+                    let ticks = axis.ticks();
+                    let case = format!("{} to {} in {}: {:?}", min, max, max_ticks, axis);
+                    assert!(ticks.len() <= max_ticks, "{}", case);
+                    assert!(axis.min <= min && axis.max >= max, "{}", case);
+                    assert!(is_nice(axis.step), "{}", case);
+                    assert!(close(ticks[0], axis.min), "{}", case);
+                    assert!(close(*ticks.last().unwrap(), axis.max), "{}", case);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_flat_or_backwards_range_still_has_room() {
+        for &(min, max) in &[(0.0, 0.0), (500.0, 500.0), (7.0, 3.0)] {
+            let axis = Axis::nice(min, max, 5);
+            assert!(axis.min <= min && axis.max > min, "{:?}", axis);
+            assert!(axis.ticks().len() >= 2, "{:?}", axis);
+            assert!(axis.fraction(min).is_finite());
+        }
+        // as a single sample has
+        let chart = Chart::new(
+            Rect {
+                x: 0.0,
+                y: 0.0,
+                width: 1.0,
+                height: 1.0,
+            },
+            Axis::nice(0.0, 0.0, 5),
+            Axis::nice(40.0, 40.0, 5),
+        );
+        let points = chart.series(&[(0.0, 40.0)]);
+        assert_eq!(points.len(), 1);
+        assert!(points[0].x.is_finite() && points[0].y.is_finite());
+    }
+
+    #[test]
+    fn a_chart_puts_larger_values_further_right_and_higher_up() {
+        let rect = Rect {
+            x: 1.0,
+            y: 2.0,
+            width: 4.0,
+            height: 2.0,
+        };
+        let chart = Chart::new(rect, Axis::nice(0.0, 10.0, 6), Axis::nice(0.0, 100.0, 6));
+        // y runs down the screen, so the bottom left is the far edge
+        assert_eq!(chart.point(0.0, 0.0), Vector2::new(1.0, 4.0));
+        assert_eq!(chart.point(10.0, 100.0), Vector2::new(5.0, 2.0));
+        assert_eq!(chart.point(5.0, 50.0), Vector2::new(3.0, 3.0));
+        assert_eq!(
+            chart.series(&[(0.0, 0.0), (10.0, 100.0)]),
+            vec![Vector2::new(1.0, 4.0), Vector2::new(5.0, 2.0)]
+        );
+
+        let lines = chart.with_y(Axis::nice(0.0, 40.0, 5));
+        assert_eq!((lines.rect, lines.x), (chart.rect, chart.x));
+        assert_eq!(lines.point(10.0, 40.0), Vector2::new(5.0, 2.0));
+    }
+}
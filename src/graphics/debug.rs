@@ -0,0 +1,57 @@
+//! Wireframe view of the flat geometry, for inspecting what actually gets drawn.
+//!
+//! Each draw batch is recoloured with its own colour so batches can be told
+//! apart. With a line polygon mode pipeline the triangles are drawn as they
+//! are; without one, every triangle edge is turned into a thin line on the CPU.
+
+use cgmath::Vector2;
+
+use super::mesh::MeshBuilder;
+use super::{lines, opaque};
+
+/// Thickness of the edges generated on the CPU
+const EDGE_THICKNESS: f32 = 0.004;
+
+/// Colours cycled through by batch index
+pub const BATCH_COLORS: &[[f32; 3]] = &[
+    [1.0, 0.2, 0.2],
+    [0.2, 1.0, 0.2],
+    [0.3, 0.5, 1.0],
+    [1.0, 1.0, 0.2],
+    [1.0, 0.2, 1.0],
+    [0.2, 1.0, 1.0],
+];
+
+/// Recolours a batch for the wireframe view, replacing each triangle by its
+/// outline when `outline` is set
+pub fn wireframe(mesh: MeshBuilder, batch: usize, outline: bool) -> MeshBuilder {
+    let color = BATCH_COLORS[batch % BATCH_COLORS.len()];
+
+    let mut mesh = if outline {
+        let vertices = mesh.vertices();
+        let mut pairs = Vec::with_capacity(mesh.indices().len() * 2);
+        for tri in mesh.indices().chunks_exact(3) {
+            let corners = [
+                vertices[tri[0] as usize].position,
+                vertices[tri[1] as usize].position,
+                vertices[tri[2] as usize].position,
+            ];
+            for i in 0..3 {
+                let (a, b) = (corners[i], corners[(i + 1) % 3]);
+                pairs.push(Vector2::new(a[0], a[1]));
+                pairs.push(Vector2::new(b[0], b[1]));
+            }
+        }
+        let mut outlines = MeshBuilder::new();
+        lines::render_lines_pairs(&mut outlines, &pairs, EDGE_THICKNESS, lines::LineCap::Butt);
+        outlines
+    } else {
+        mesh
+    };
+
+    for vertex in mesh.vertices_mut() {
+        vertex.color = opaque(color);
+    }
+
+    mesh
+}
@@ -0,0 +1,138 @@
+//! Pieces drifting down behind the main menu, for something to look at.
+//!
+//! Pieces are placed in fractions of the screen, x across and y down, so they
+//! cover whatever shape the window is, and sized in world y units so they stay
+//! square. They're stepped once a tick and drawn as a sprite for each mino,
+//! faded most of the way into the background so text over them stays easy to
+//! read.
+
+use rand::Rng;
+
+use super::atlas::{Region, Sprite, SpriteBatch};
+use super::{mesh::MeshBuilder, opaque, theme::Theme};
+use crate::piece::Tetromino;
+use crate::ui::layout::{Rect, X_PER_Y};
+
+/// Pieces on screen at once
+pub const PIECE_COUNT: usize = 12;
+
+/// Side of a mino, in world y units. Bigger pieces fall faster, as if nearer
+const MIN_SIZE: f32 = 0.025;
+const MAX_SIZE: f32 = 0.06;
+
+/// Screen heights fallen each tick by the smallest and biggest pieces
+const MIN_SPEED: f32 = 0.0012;
+const MAX_SPEED: f32 = 0.0035;
+
+/// Fastest a piece turns, in radians per tick either way
+const MAX_SPIN: f32 = 0.02;
+
+/// How far pieces are faded into the background
+const FADE: f32 = 0.85;
+
+/// Part of a mino's side left clear around it, so the cells show
+const MINO_INSET: f32 = 0.06;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct FallingPiece {
+    tetromino: Tetromino,
+
+    /// Middle of the piece, in fractions of the screen
+    position: [f32; 2],
+
+    /// Side of a mino, and screen heights fallen each tick
+    size: f32,
+    speed: f32,
+
+    /// Turn, and how much more it turns each tick, in radians
+    angle: f32,
+    spin: f32,
+}
+
+impl FallingPiece {
+    /// A piece of any kind at `y` and anywhere across
+    fn random(y: f32, rng: &mut impl Rng) -> Self {
+        let depth = rng.gen_range(0.0..1.0);
+        Self {
+            tetromino: Tetromino::random(rng),
+            position: [rng.gen_range(0.0..1.0), y],
+            size: MIN_SIZE + (MAX_SIZE - MIN_SIZE) * depth,
+            speed: MIN_SPEED + (MAX_SPEED - MIN_SPEED) * depth,
+            angle: rng.gen_range(0.0..std::f32::consts::TAU),
+            spin: rng.gen_range(-MAX_SPIN..MAX_SPIN),
+        }
+    }
+
+    /// Farthest any mino reaches from the middle, in world y units. The screen
+    /// is at least one unit tall, so it's no farther in screen heights
+    fn reach(&self) -> f32 {
+        self.size * 2.0 * std::f32::consts::SQRT_2
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FallingPieces {
+    pieces: Vec<FallingPiece>,
+}
+
+impl FallingPieces {
+    /// Pieces spread all the way down the screen, so it doesn't start empty
+    pub fn new(rng: &mut impl Rng) -> Self {
+        Self {
+            pieces: (0..PIECE_COUNT)
+                .map(|_| FallingPiece::random(rng.gen_range(0.0..1.0), rng))
+                .collect(),
+        }
+    }
+
+    /// Moves everything on by a tick. Pieces that fell off the bottom start
+    /// again above the top, as a new piece
+    pub fn update(&mut self, rng: &mut impl Rng) {
+        for piece in &mut self.pieces {
+            piece.position[1] += piece.speed;
+            piece.angle += piece.spin;
+            if piece.position[1] - piece.reach() > 1.0 {
+                let mut next = FallingPiece::random(0.0, rng);
+                next.position[1] = -next.reach();
+                *piece = next;
+            }
+        }
+    }
+
+    /// Adds a sprite for every mino to `mesh`, spread over `canvas`, in the
+    /// colours of `theme`
+    pub fn render(&self, canvas: &Rect, theme: &Theme, mesh: &mut MeshBuilder) {
+        let mut batch = SpriteBatch::new(mesh);
+        for piece in &self.pieces {
+            let color = opaque(theme.fade(theme.piece(piece.tetromino.color()), FADE));
+            let centre = [
+                canvas.x + piece.position[0] * canvas.width,
+                canvas.y + piece.position[1] * canvas.height,
+            ];
+            let (sin, cos) = piece.angle.sin_cos();
+            let side = (1.0 - MINO_INSET * 2.0) * piece.size;
+            let size = [side * X_PER_Y, side];
+
+            // each mino turns about its own middle, which is moved around the
+            // middle of the piece's box by as much
+            let shape = piece.tetromino.shape().as_bytes();
+            for (i, _) in shape.iter().enumerate().filter(|(_, &c)| c == b'#') {
+                let x = ((i % 4) as f32 - 1.5) * piece.size;
+                let y = ((i / 4) as f32 - 1.5) * piece.size;
+                let middle = [
+                    centre[0] + (x * cos - y * sin) * X_PER_Y,
+                    centre[1] + x * sin + y * cos,
+                ];
+                batch.draw(
+                    &Sprite::new(
+                        Region::WHOLE,
+                        [middle[0] - size[0] / 2.0, middle[1] - size[1] / 2.0],
+                        size,
+                        color,
+                    )
+                    .rotated(piece.angle),
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! Negotiation of optional device features.
+//!
+//! Nothing the game needs in order to run sits behind a feature. Anything that
+//! does is only requested when the adapter has it, and whatever wants to use it
+//! checks [`NegotiatedFeatures`] first and turns itself off otherwise.
+
+/// Features used when the adapter has them but which the game can do without
+pub const OPTIONAL: wgpu::Features = wgpu::Features::from_bits_truncate(
+    wgpu::Features::NON_FILL_POLYGON_MODE.bits() | wgpu::Features::TIMESTAMP_QUERY.bits(),
+);
+
+/// Systems that depend on an optional feature, by the name they're shown under
+/// on the diagnostics overlay
+pub const SYSTEMS: &[(&str, wgpu::Features)] = &[
+    ("wireframe lines", wgpu::Features::NON_FILL_POLYGON_MODE),
+    ("gpu timings", wgpu::Features::TIMESTAMP_QUERY),
+];
+
+/// Outcome of matching the features we'd like against what the adapter offers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NegotiatedFeatures {
+    /// Features the device was created with
+    pub granted: wgpu::Features,
+
+    /// Features we asked for that the adapter doesn't support
+    pub denied: wgpu::Features,
+}
+
+impl NegotiatedFeatures {
+    pub fn negotiate(desired: wgpu::Features, available: wgpu::Features) -> Self {
+        Self {
+            granted: desired & available,
+            denied: desired - available,
+        }
+    }
+
+    /// Whether every feature in `features` was granted
+    pub fn has(&self, features: wgpu::Features) -> bool {
+        self.granted.contains(features)
+    }
+
+    /// Whether the wireframe view can draw with a line polygon mode pipeline,
+    /// rather than outlining every triangle on the CPU
+    pub fn wireframe_lines(&self) -> bool {
+        self.has(wgpu::Features::NON_FILL_POLYGON_MODE)
+    }
+
+    /// Whether the profiler can time passes on the GPU with timestamp queries
+    pub fn gpu_timings(&self) -> bool {
+        self.has(wgpu::Features::TIMESTAMP_QUERY)
+    }
+
+    /// Each of [`SYSTEMS`] along with whether it's on
+    pub fn status(&self) -> impl Iterator<Item = (&'static str, bool)> + '_ {
+        SYSTEMS
+            .iter()
+            .map(move |&(name, features)| (name, self.has(features)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_available_turns_every_system_off() {
+        let features = NegotiatedFeatures::negotiate(OPTIONAL, wgpu::Features::empty());
+        assert!(features.granted.is_empty());
+        assert_eq!(features.denied, OPTIONAL);
+        assert!(!features.wireframe_lines());
+        assert!(!features.gpu_timings());
+        assert!(features.status().all(|(_, on)| !on));
+    }
+
+    #[test]
+    fn only_the_systems_whose_features_are_missing_turn_off() {
+        let available = wgpu::Features::NON_FILL_POLYGON_MODE | wgpu::Features::DEPTH_CLAMPING;
+        let features = NegotiatedFeatures::negotiate(OPTIONAL, available);
+        assert_eq!(features.granted, wgpu::Features::NON_FILL_POLYGON_MODE);
+        assert_eq!(features.denied, wgpu::Features::TIMESTAMP_QUERY);
+        assert!(features.wireframe_lines());
+        assert!(!features.gpu_timings());
+        let status: Vec<_> = features.status().collect();
+        assert_eq!(status, [("wireframe lines", true), ("gpu timings", false)]);
+    }
+
+    #[test]
+    fn every_system_depends_on_a_feature_we_ask_for() {
+        for &(name, features) in SYSTEMS {
+            assert!(OPTIONAL.contains(features), "{} isn't asked for", name);
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! Canonical bitmap font. `GLYPHS` is the single source of truth for both the
+//! shipped atlas image (`res/textures/font.png`, regenerated with
+//! `cargo run --example gen_font`) and the character lookup used by the text
+//! renderer.
+
+pub const GLYPH_SIZE: u32 = 8;
+pub const ATLAS_COLUMNS: u32 = 16;
+pub const ATLAS_ROWS: u32 = 8;
+
+/// Tile drawn for characters that have no glyph
+pub const FALLBACK_TILE: usize = (ATLAS_COLUMNS * ATLAS_ROWS) as usize - 1;
+const FALLBACK_GLYPH: [u8; 8] = [0xff, 0x81, 0x81, 0x81, 0x81, 0x81, 0x81, 0xff];
+const TILES: usize = (ATLAS_COLUMNS * ATLAS_ROWS) as usize;
+
+/// Blank columns between one glyph and the next in proportional text
+pub const GLYPH_GAP: u32 = 1;
+
+/// Columns a blank glyph like the space takes in proportional text
+const BLANK_WIDTH: u8 = 3;
+
+/// Glyph bitmaps, one byte per row with the most significant bit on the left.
+/// A glyph's position in this table is its tile index in the atlas.
+#[rustfmt::skip]
+pub const GLYPHS: &[(char, [u8; 8])] = &[
+    ('a', [0x00, 0x00, 0x00, 0x38, 0x04, 0x3c, 0x44, 0x3a]),
+    ('b', [0x00, 0x40, 0x40, 0x58, 0x64, 0x44, 0x44, 0x38]),
+    ('c', [0x00, 0x00, 0x00, 0x3c, 0x40, 0x40, 0x40, 0x3c]),
+    ('d', [0x00, 0x04, 0x04, 0x3c, 0x44, 0x44, 0x44, 0x38]),
+    ('e', [0x00, 0x00, 0x00, 0x38, 0x44, 0x78, 0x40, 0x38]),
+    ('f', [0x00, 0x38, 0x44, 0x40, 0x40, 0x78, 0x40, 0x40]),
+    ('g', [0x02, 0x3c, 0x44, 0x78, 0x20, 0x3c, 0x44, 0x78]),
+    ('h', [0x00, 0x40, 0x40, 0x78, 0x44, 0x44, 0x44, 0x44]),
+    ('i', [0x00, 0x10, 0x00, 0x30, 0x10, 0x10, 0x10, 0x7c]),
+    ('j', [0x00, 0x04, 0x00, 0x0c, 0x04, 0x04, 0x44, 0x38]),
+    ('k', [0x00, 0x40, 0x40, 0x48, 0x50, 0x60, 0x50, 0x4c]),
+    ('l', [0x00, 0x70, 0x10, 0x10, 0x20, 0x20, 0x20, 0x1c]),
+    ('m', [0x00, 0x00, 0x00, 0x6c, 0x52, 0x52, 0x52, 0x52]),
+    ('n', [0x00, 0x00, 0x00, 0x78, 0x44, 0x44, 0x44, 0x44]),
+    ('o', [0x00, 0x00, 0x00, 0x38, 0x44, 0x44, 0x44, 0x38]),
+    ('p', [0x00, 0x00, 0x00, 0xf8, 0x44, 0x44, 0x78, 0xc0]),
+    ('q', [0x00, 0x00, 0x00, 0x3e, 0x44, 0x44, 0x3c, 0x04]),
+    ('r', [0x00, 0x00, 0x00, 0x58, 0x64, 0x44, 0x40, 0x40]),
+    ('s', [0x00, 0x00, 0x00, 0x3c, 0x40, 0x38, 0x04, 0x78]),
+    ('t', [0x00, 0x40, 0x40, 0x78, 0x40, 0x40, 0x40, 0x38]),
+    ('u', [0x00, 0x00, 0x00, 0x44, 0x44, 0x44, 0x44, 0x3c]),
+    ('v', [0x00, 0x00, 0x00, 0x44, 0x44, 0x24, 0x14, 0x08]),
+    ('w', [0x00, 0x00, 0x00, 0x42, 0x52, 0x52, 0x52, 0x2c]),
+    ('x', [0x00, 0x00, 0x00, 0x6c, 0x28, 0x10, 0x10, 0x6c]),
+    ('y', [0x00, 0x00, 0x00, 0x44, 0x44, 0x3c, 0x04, 0x78]),
+    ('z', [0x00, 0x00, 0x00, 0x7c, 0x04, 0x38, 0x40, 0x7c]),
+    ('A', [0x3c, 0x42, 0x42, 0x7e, 0x42, 0x42, 0x42, 0x42]),
+    ('B', [0x7c, 0x42, 0x42, 0x7c, 0x42, 0x42, 0x42, 0x7c]),
+    ('C', [0x3c, 0x42, 0x40, 0x40, 0x40, 0x40, 0x42, 0x3c]),
+    ('D', [0x7c, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x7c]),
+    ('E', [0x7e, 0x40, 0x40, 0x7c, 0x40, 0x40, 0x40, 0x7e]),
+    ('F', [0x7f, 0x40, 0x40, 0x7e, 0x40, 0x40, 0x40, 0x40]),
+    ('G', [0x3e, 0x40, 0x40, 0x5e, 0x42, 0x42, 0x42, 0x3c]),
+    ('H', [0x42, 0x42, 0x42, 0x7e, 0x42, 0x42, 0x42, 0x42]),
+    ('I', [0x7c, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x7c]),
+    ('J', [0x7e, 0x04, 0x04, 0x04, 0x44, 0x44, 0x44, 0x38]),
+    ('K', [0x44, 0x44, 0x48, 0x70, 0x48, 0x44, 0x44, 0x44]),
+    ('L', [0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x7c]),
+    ('M', [0x76, 0x49, 0x49, 0x49, 0x49, 0x49, 0x49, 0x49]),
+    ('N', [0x61, 0x51, 0x51, 0x49, 0x49, 0x45, 0x45, 0x43]),
+    ('O', [0x7e, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x7e]),
+    ('P', [0x7e, 0x41, 0x41, 0x7e, 0x40, 0x40, 0x40, 0x40]),
+    ('Q', [0x7e, 0x42, 0x42, 0x42, 0x42, 0x4a, 0x44, 0x7a]),
+    ('R', [0x7c, 0x42, 0x42, 0x7c, 0x50, 0x48, 0x44, 0x42]),
+    ('S', [0x7c, 0x82, 0x80, 0x7c, 0x02, 0x02, 0x82, 0x7c]),
+    ('T', [0xfe, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10]),
+    ('U', [0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x3c]),
+    ('V', [0x42, 0x42, 0x44, 0x24, 0x28, 0x28, 0x10, 0x10]),
+    ('W', [0x49, 0x49, 0x49, 0x49, 0x49, 0x49, 0x49, 0x36]),
+    ('X', [0x41, 0x22, 0x14, 0x08, 0x08, 0x14, 0x22, 0x41]),
+    ('Y', [0x42, 0x42, 0x42, 0x3e, 0x02, 0x02, 0x02, 0x3c]),
+    ('Z', [0x7e, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x7e]),
+    (' ', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+    ('0', [0x38, 0x44, 0x44, 0x54, 0x44, 0x44, 0x44, 0x38]),
+    ('1', [0x10, 0x30, 0x50, 0x50, 0x10, 0x10, 0x10, 0x7c]),
+    ('2', [0x38, 0x44, 0x44, 0x44, 0x08, 0x30, 0x40, 0x7c]),
+    ('3', [0x7c, 0x04, 0x04, 0x38, 0x04, 0x04, 0x04, 0x78]),
+    ('4', [0x24, 0x44, 0x44, 0x7c, 0x04, 0x04, 0x04, 0x04]),
+    ('5', [0x7c, 0x40, 0x40, 0x38, 0x04, 0x44, 0x44, 0x38]),
+    ('6', [0x38, 0x44, 0x40, 0x78, 0x44, 0x44, 0x44, 0x38]),
+    ('7', [0x7c, 0x04, 0x04, 0x08, 0x08, 0x10, 0x10, 0x10]),
+    ('8', [0x38, 0x44, 0x44, 0x38, 0x44, 0x44, 0x44, 0x38]),
+    ('9', [0x38, 0x44, 0x44, 0x3c, 0x04, 0x04, 0x44, 0x38]),
+    ('!', [0x38, 0x38, 0x18, 0x18, 0x10, 0x00, 0x18, 0x18]),
+    ('?', [0x38, 0x44, 0x02, 0x04, 0x18, 0x00, 0x18, 0x18]),
+    ('@', [0x7c, 0x82, 0x82, 0xba, 0xaa, 0xb4, 0x80, 0x7c]),
+    ('#', [0x11, 0xdd, 0x22, 0x22, 0xbb, 0x44, 0x44, 0x44]),
+    ('$', [0x08, 0x3c, 0x4a, 0x48, 0x3c, 0x92, 0x7c, 0x10]),
+    ('%', [0x44, 0xa8, 0x48, 0x10, 0x10, 0x24, 0x2a, 0x44]),
+    ('"', [0x24, 0x24, 0x24, 0x48, 0x00, 0x00, 0x00, 0x00]),
+    ('\'', [0x20, 0x20, 0x20, 0x40, 0x00, 0x00, 0x00, 0x00]),
+    ('&', [0x38, 0x44, 0x40, 0x3e, 0x44, 0x44, 0x44, 0x3a]),
+    ('(', [0x18, 0x20, 0x40, 0x40, 0x40, 0x40, 0x20, 0x18]),
+    (')', [0x30, 0x08, 0x04, 0x04, 0x04, 0x04, 0x08, 0x30]),
+    ('*', [0x00, 0x54, 0x38, 0x7c, 0x38, 0x54, 0x00, 0x00]),
+    ('+', [0x00, 0x10, 0x10, 0x7c, 0x10, 0x10, 0x00, 0x00]),
+    (',', [0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x30]),
+    ('-', [0x00, 0x00, 0x00, 0x7c, 0x00, 0x00, 0x00, 0x00]),
+    ('.', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18]),
+    ('/', [0x04, 0x04, 0x08, 0x08, 0x10, 0x10, 0x20, 0x20]),
+    (':', [0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x18, 0x18]),
+    (';', [0x00, 0x00, 0x18, 0x18, 0x00, 0x18, 0x18, 0x30]),
+    ('<', [0x00, 0x08, 0x10, 0x20, 0x40, 0x20, 0x10, 0x08]),
+    ('>', [0x00, 0x40, 0x20, 0x10, 0x08, 0x10, 0x20, 0x40]),
+    ('=', [0x00, 0x00, 0x00, 0x7c, 0x00, 0x7c, 0x00, 0x00]),
+    ('[', [0x3c, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x3c]),
+    (']', [0x3c, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x3c]),
+    ('{', [0x1c, 0x20, 0x20, 0x40, 0x20, 0x20, 0x20, 0x1c]),
+    ('}', [0x70, 0x08, 0x08, 0x04, 0x08, 0x08, 0x08, 0x70]),
+    ('|', [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10]),
+    ('\\', [0x20, 0x20, 0x10, 0x10, 0x08, 0x08, 0x04, 0x04]),
+];
+
+const fn build_lookup() -> [u8; 128] {
+    let mut lookup = [FALLBACK_TILE as u8; 128];
+    let mut i = 0;
+    while i < GLYPHS.len() {
+        lookup[GLYPHS[i].0 as u32 as usize] = i as u8;
+        i += 1;
+    }
+    lookup
+}
+
+/// ASCII code to atlas tile, generated from `GLYPHS` at compile time
+const LOOKUP: [u8; 128] = build_lookup();
+
+/// First drawn column of `bitmap` and how many columns it spans from there
+const fn bitmap_span(bitmap: &[u8; 8]) -> (u8, u8) {
+    let mut columns = 0u8;
+    let mut i = 0;
+    while i < bitmap.len() {
+        columns |= bitmap[i];
+        i += 1;
+    }
+    if columns == 0 {
+        return (0, BLANK_WIDTH);
+    }
+    let (first, last) = (columns.leading_zeros(), columns.trailing_zeros());
+    (first as u8, (GLYPH_SIZE - first - last) as u8)
+}
+
+const fn build_spans() -> [(u8, u8); TILES] {
+    let mut spans = [bitmap_span(&FALLBACK_GLYPH); TILES];
+    let mut i = 0;
+    while i < GLYPHS.len() {
+        spans[i] = bitmap_span(&GLYPHS[i].1);
+        i += 1;
+    }
+    spans
+}
+
+/// Atlas tile to the columns of its glyph, generated from `GLYPHS` at compile
+/// time
+const SPANS: [(u8, u8); TILES] = build_spans();
+
+/// Returns the atlas tile holding the glyph for `c`, or `FALLBACK_TILE`
+pub fn glyph_tile(c: char) -> usize {
+    if c.is_ascii() {
+        LOOKUP[c as usize] as usize
+    } else {
+        FALLBACK_TILE
+    }
+}
+
+/// First column of the glyph for `c` that's drawn in and how many columns it
+/// spans from there, for fitting it in proportional text
+pub fn glyph_span(c: char) -> (u32, u32) {
+    let (first, width) = SPANS[glyph_tile(c)];
+    (first as u32, width as u32)
+}
+
+/// Whether `c` has a glyph of its own in the atlas
+pub fn has_glyph(c: char) -> bool {
+    glyph_tile(c) != FALLBACK_TILE
+}
+
+/// Renders the canonical atlas image from `GLYPHS`
+pub fn generate_atlas() -> image::RgbaImage {
+    let mut atlas = image::RgbaImage::new(ATLAS_COLUMNS * GLYPH_SIZE, ATLAS_ROWS * GLYPH_SIZE);
+
+    let tiles = GLYPHS
+        .iter()
+        .map(|(_, bitmap)| bitmap)
+        .enumerate()
+        .chain(std::iter::once((FALLBACK_TILE, &FALLBACK_GLYPH)));
+
+    for (tile, bitmap) in tiles {
+        let tile_x = (tile as u32 % ATLAS_COLUMNS) * GLYPH_SIZE;
+        let tile_y = (tile as u32 / ATLAS_COLUMNS) * GLYPH_SIZE;
+        for (y, row) in bitmap.iter().enumerate() {
+            for x in 0..GLYPH_SIZE {
+                if row & (0x80 >> x) != 0 {
+                    atlas.put_pixel(tile_x + x, tile_y + y as u32, image::Rgba([255; 4]));
+                }
+            }
+        }
+    }
+
+    atlas
+}
+
+/// FNV-1a hash of the atlas coverage mask. Only whether a pixel is drawn
+/// matters, so palette and encoding differences don't affect it.
+pub fn atlas_checksum(atlas: &image::RgbaImage) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    hash = (hash ^ atlas.width() as u64).wrapping_mul(0x0100_0000_01b3);
+    hash = (hash ^ atlas.height() as u64).wrapping_mul(0x0100_0000_01b3);
+    for pixel in atlas.pixels() {
+        let covered = (pixel[3] > 0) as u64;
+        hash = (hash ^ covered).wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// Fails if `atlas` has drifted from what `GLYPHS` describes
+pub fn verify_atlas(atlas: &image::RgbaImage) -> Result<(), AtlasMismatch> {
+    let expected = atlas_checksum(&generate_atlas());
+    let found = atlas_checksum(atlas);
+    if expected == found {
+        Ok(())
+    } else {
+        Err(AtlasMismatch { expected, found })
+    }
+}
+
+#[derive(Debug)]
+pub struct AtlasMismatch {
+    pub expected: u64,
+    pub found: u64,
+}
+
+impl std::fmt::Display for AtlasMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "font atlas checksum {:016x} does not match glyph table checksum {:016x}",
+            self.found, self.expected
+        )
+    }
+}
+
+impl std::error::Error for AtlasMismatch {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shipped() -> image::RgbaImage {
+        image::load_from_memory(include_bytes!("../../res/textures/font.png"))
+            .unwrap()
+            .to_rgba8()
+    }
+
+    #[test]
+    fn the_shipped_atlas_matches_the_glyph_table() {
+        if let Err(e) = verify_atlas(&shipped()) {
+            panic!("{}; regenerate it with `cargo run --example gen_font`", e);
+        }
+    }
+
+    #[test]
+    fn a_single_pixel_of_drift_is_caught() {
+        let mut atlas = generate_atlas();
+        let tile = glyph_tile('A') as u32;
+        let (x, y) = (
+            (tile % ATLAS_COLUMNS) * GLYPH_SIZE,
+            (tile / ATLAS_COLUMNS) * GLYPH_SIZE,
+        );
+        let pixel = atlas.get_pixel_mut(x, y);
+        pixel[3] = 255 - pixel[3];
+        assert!(verify_atlas(&atlas).is_err());
+
+        // colour alone doesn't count, only what's drawn
+        let mut tinted = generate_atlas();
+        for pixel in tinted.pixels_mut().filter(|p| p[3] > 0) {
+            pixel[0] = 40;
+        }
+        assert!(verify_atlas(&tinted).is_ok());
+    }
+}
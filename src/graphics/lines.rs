@@ -1,18 +1,39 @@
+use std::cell::RefCell;
+
 use cgmath::prelude::*;
 use cgmath::Vector2;
 
-use super::Vertex;
+use super::mesh::MeshBuilder;
+use crate::ui::layout::X_PER_Y;
+
+/// How the ends of a line are finished
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineCap {
+    /// Cut off square right at the end point
+    Butt,
+    /// Carried on square half the thickness past the end point, so lines
+    /// meeting at it close up
+    Square,
+    /// Rounded off in a half circle around the end point
+    Round,
+}
+
+/// Sides of a round cap's half circle for each world unit of thickness
+const ROUND_SEGMENTS_PER_UNIT: f32 = 400.0;
+
+/// Fewest and most sides a round cap's half circle is drawn with
+const MIN_ROUND_SEGMENTS: u32 = 2;
+const MAX_ROUND_SEGMENTS: u32 = 12;
 
+/// Adds a line to `mesh` between each pair of `positions`, ended with `cap`
 pub fn render_lines_pairs(
+    mesh: &mut MeshBuilder,
     positions: &[Vector2<f32>],
     mut thickness: f32,
-    index_offset: usize,
-) -> (Vec<Vertex>, Vec<u16>) {
+    cap: LineCap,
+) {
     thickness /= 2.0;
 
-    let mut vertices = Vec::with_capacity(positions.len() * 4);
-    let mut indices = Vec::with_capacity(positions.len() * 6);
-
     for pair in positions.chunks_exact(2) {
         let (v1, v2) = (pair[0], pair[1]);
 
@@ -28,24 +49,813 @@ pub fn render_lines_pairs(
         let dir = (v2 - v1).normalize();
         let across = Vector2::new(-dir.y, dir.x);
 
-        let base_vtx = (index_offset + vertices.len()) as u16;
-        vertices.extend_from_slice(&[
-            (v1 + across * thickness).into(), // top left
-            (v2 + across * thickness).into(), // top right
-            (v1 - across * thickness).into(), // bottom left
-            (v2 - across * thickness).into(), // bottom right
-        ]);
+        let (v1, v2) = match cap {
+            LineCap::Square => (v1 - dir * thickness, v2 + dir * thickness),
+            LineCap::Butt | LineCap::Round => (v1, v2),
+        };
+        if cap == LineCap::Round {
+            render_round_cap(mesh, v1, -dir, thickness);
+            render_round_cap(mesh, v2, dir, thickness);
+        }
 
         // ccw should maintain in any situation
-        indices.extend_from_slice(&[
-            base_vtx,
-            base_vtx + 1,
-            base_vtx + 2, // top left triangle
-            base_vtx + 1,
-            base_vtx + 3,
-            base_vtx + 2, // bottom right triangle
-        ]);
+        mesh.extend(
+            &[
+                (v1 + across * thickness).into(), // top left
+                (v2 + across * thickness).into(), // top right
+                (v1 - across * thickness).into(), // bottom left
+                (v2 - across * thickness).into(), // bottom right
+            ],
+            &[
+                0, 1, 2, // top left triangle
+                1, 3, 2, // bottom right triangle
+            ],
+        );
+    }
+}
+
+/// Adds a half circle `radius` big to `mesh`, bulging from `center` towards
+/// `out`, with more sides the bigger it is
+fn render_round_cap(mesh: &mut MeshBuilder, center: Vector2<f32>, out: Vector2<f32>, radius: f32) {
+    let segments = ((radius * 2.0 * ROUND_SEGMENTS_PER_UNIT).ceil() as u32)
+        .clamp(MIN_ROUND_SEGMENTS, MAX_ROUND_SEGMENTS);
+    let across = Vector2::new(-out.y, out.x);
+    let rim = |i: u32| {
+        let angle = std::f32::consts::PI * i as f32 / segments as f32;
+        center + (across * angle.cos() + out * angle.sin()) * radius
+    };
+    for i in 0..segments {
+        push_triangle(mesh, center, rim(i), rim(i + 1));
+    }
+}
+
+/// Whether a line is drawn whole or broken up
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineStyle {
+    Solid,
+    /// Runs `dash` long with `gap` left between them, in world units
+    Dashed {
+        dash: f32,
+        gap: f32,
+    },
+    /// Square dots as long as the line is thick, starting every `spacing`
+    Dotted {
+        spacing: f32,
+    },
+}
+
+/// Longest a miter join may reach out from its corner, in half thicknesses,
+/// before the corner is cut off with a bevel instead
+const MITER_LIMIT: f32 = 2.0;
+
+/// Points closer together than this (squared) are taken as the same point
+const SAME_POINT: f32 = 1e-10;
+
+/// Turns sharper than this, as the cross product of the directions either
+/// side, aren't taken as going straight on
+const STRAIGHT: f32 = 1e-4;
+
+/// How a polyline meets itself at a point
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Join {
+    /// Both edges of the strip are pushed out to where they meet, this far
+    /// along the left of both segments
+    Miter(Vector2<f32>),
+    /// Each segment ends square, and a triangle fills the gap on the outside
+    Bevel,
+    /// The line ends here
+    Cap,
+}
+
+/// Room the polylines are worked out in, kept from one line to the next so
+/// drawing them allocates nothing once it's grown to fit
+#[derive(Default)]
+struct Scratch {
+    /// Points squeezed into y units by [`render_polyline_even`]
+    squeezed: Vec<Vector2<f32>>,
+    /// The line with its first point again at the end if it's closed
+    path: Vec<Vector2<f32>>,
+    /// Points of the dash being cut out of `path`
+    run: Vec<Vector2<f32>>,
+    /// Points of a strip left after [`simplify_into`]
+    kept: Vec<Vector2<f32>>,
+    /// How the strip meets itself at each of `kept`
+    joins: Vec<Join>,
+}
+
+thread_local! {
+    static SCRATCH: RefCell<Scratch> = RefCell::new(Scratch::default());
+}
+
+/// `points` without repeats or points the line goes straight through, put in
+/// `kept`
+fn simplify_into(points: &[Vector2<f32>], closed: bool, kept: &mut Vec<Vector2<f32>>) {
+    kept.clear();
+    for &point in points {
+        if kept
+            .last()
+            .map_or(true, |&last| (point - last).magnitude2() > SAME_POINT)
+        {
+            kept.push(point);
+        }
+    }
+    if closed {
+        while kept.len() > 1 && (kept[kept.len() - 1] - kept[0]).magnitude2() <= SAME_POINT {
+            kept.pop();
+        }
+    }
+
+    let straight = |a: Vector2<f32>, b: Vector2<f32>, c: Vector2<f32>| {
+        let (before, after) = ((b - a).normalize(), (c - b).normalize());
+        before.perp_dot(after).abs() <= STRAIGHT && before.dot(after) > 0.0
+    };
+    // ends of an open line stay put
+    let mut i = if closed { 0 } else { 1 };
+    while kept.len() > 2 && i + (!closed as usize) < kept.len() {
+        let n = kept.len();
+        if straight(kept[(i + n - 1) % n], kept[i], kept[(i + 1) % n]) {
+            kept.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+}
+
+/// Adds a line `thickness` thick to `mesh` running through each of `points`
+/// in turn, and back to the first if it's `closed`, broken up as `style` says.
+/// Corners are mitered, or beveled where they're too sharp for a miter to stay
+/// close
+pub fn render_polyline(
+    mesh: &mut MeshBuilder,
+    points: &[Vector2<f32>],
+    thickness: f32,
+    closed: bool,
+    style: LineStyle,
+) {
+    SCRATCH.with(|scratch| {
+        polyline(
+            mesh,
+            &mut scratch.borrow_mut(),
+            points,
+            thickness,
+            closed,
+            style,
+        )
+    });
+}
+
+/// [`render_polyline`] working in `scratch`
+fn polyline(
+    mesh: &mut MeshBuilder,
+    scratch: &mut Scratch,
+    points: &[Vector2<f32>],
+    thickness: f32,
+    closed: bool,
+    style: LineStyle,
+) {
+    let Scratch {
+        path,
+        run,
+        kept,
+        joins,
+        ..
+    } = scratch;
+    let (dash, gap) = match style {
+        LineStyle::Solid => return render_strip(mesh, points, thickness, closed, kept, joins),
+        LineStyle::Dashed { dash, gap } => (dash, gap),
+        LineStyle::Dotted { spacing } => (thickness, spacing - thickness),
+    };
+    if dash <= 0.0 || gap <= 0.0 {
+        return render_strip(mesh, points, thickness, closed, kept, joins);
+    }
+    dashes_into(points, closed, dash, gap, path, run, |run| {
+        render_strip(mesh, run, thickness, false, kept, joins)
+    });
+}
+
+/// Hands `each` the stretches of `points` that are drawn when the line is
+/// broken into `dash` long runs with `gap` between them, working in `path` and
+/// `run`. The pattern carries on round corners, and segments too short to hold
+/// a dash that would otherwise be skipped are drawn whole
+fn dashes_into(
+    points: &[Vector2<f32>],
+    closed: bool,
+    dash: f32,
+    gap: f32,
+    path: &mut Vec<Vector2<f32>>,
+    run: &mut Vec<Vector2<f32>>,
+    mut each: impl FnMut(&[Vector2<f32>]),
+) {
+    path.clear();
+    path.extend_from_slice(points);
+    if closed && !points.is_empty() {
+        path.push(points[0]);
+    }
+
+    run.clear();
+    // how far into the current dash and gap the line has got
+    let mut phase = 0.0;
+    for segment in path.windows(2) {
+        let (start, end) = (segment[0], segment[1]);
+        let length = (end - start).magnitude();
+        if length * length <= SAME_POINT {
+            continue;
+        }
+        let dir = (end - start) / length;
+
+        let mut along = 0.0;
+        let mut drawn = false;
+        while along < length {
+            if phase < dash {
+                if run.is_empty() {
+                    run.push(start + dir * along);
+                }
+                let step = f32::min(dash - phase, length - along);
+                along += step;
+                phase += step;
+                run.push(start + dir * along);
+                drawn = true;
+                if phase >= dash {
+                    each(run);
+                    run.clear();
+                }
+            } else {
+                let step = f32::min(dash + gap - phase, length - along);
+                along += step;
+                phase += step;
+                if phase >= dash + gap {
+                    phase = 0.0;
+                }
+            }
+        }
+        if !drawn && length < dash {
+            each(&[start, end]);
+        }
+    }
+    if !run.is_empty() {
+        each(run);
+    }
+}
+
+/// Adds a solid line through `points` to `mesh`, as [`render_polyline`] does,
+/// working in `kept` and `joins`
+fn render_strip(
+    mesh: &mut MeshBuilder,
+    points: &[Vector2<f32>],
+    thickness: f32,
+    closed: bool,
+    kept: &mut Vec<Vector2<f32>>,
+    joins: &mut Vec<Join>,
+) {
+    simplify_into(points, closed, kept);
+    let points = &kept[..];
+    let closed = closed && points.len() > 2;
+    if points.len() < 2 {
+        return;
+    }
+    let half = thickness / 2.0;
+    let n = points.len();
+    let segments = if closed { n } else { n - 1 };
+
+    // left of segment `i`, going from point `i` to the one after
+    let across = |i: usize| {
+        let dir = (points[(i + 1) % n] - points[i]).normalize();
+        Vector2::new(-dir.y, dir.x)
+    };
+    let join = |i: usize| {
+        if !closed && (i == 0 || i == n - 1) {
+            return Join::Cap;
+        }
+        let (before, after) = (across((i + n - 1) % n), across(i));
+        let sum = before + after;
+        // turning right back on itself leaves nothing to miter towards
+        if sum.magnitude2() <= SAME_POINT {
+            return Join::Bevel;
+        }
+        let miter = sum.normalize();
+        let cos = miter.dot(after);
+        if cos * MITER_LIMIT < 1.0 {
+            Join::Bevel
+        } else {
+            Join::Miter(miter * (half / cos))
+        }
+    };
+    joins.clear();
+    joins.extend((0..n).map(join));
+
+    for i in 0..segments {
+        let (start, end) = (points[i], points[(i + 1) % n]);
+        let square = across(i) * half;
+        let offset = |join: Join| match join {
+            Join::Miter(offset) => offset,
+            Join::Bevel | Join::Cap => square,
+        };
+        let (start_offset, end_offset) = (offset(joins[i]), offset(joins[(i + 1) % n]));
+
+        // same layout and winding as render_lines_pairs
+        mesh.extend(
+            &[
+                (start + start_offset).into(),
+                (end + end_offset).into(),
+                (start - start_offset).into(),
+                (end - end_offset).into(),
+            ],
+            &[0, 1, 2, 1, 3, 2],
+        );
+    }
+
+    for (i, _) in joins.iter().enumerate().filter(|&(_, &j)| j == Join::Bevel) {
+        let (before, after) = (across((i + n - 1) % n), across(i));
+        // the gap is on the outside of the turn, away from where it heads
+        let outside = if before.perp_dot(after) > 0.0 {
+            -half
+        } else {
+            half
+        };
+        push_triangle(
+            mesh,
+            points[i],
+            points[i] + before * outside,
+            points[i] + after * outside,
+        );
+    }
+}
+
+/// Adds a triangle to `mesh` wound the way lines are, unless it has no area
+fn push_triangle(mesh: &mut MeshBuilder, a: Vector2<f32>, b: Vector2<f32>, c: Vector2<f32>) {
+    let area = (b - a).perp_dot(c - a);
+    if area.abs() <= SAME_POINT {
+        return;
+    }
+    let (b, c) = if area > 0.0 { (c, b) } else { (b, c) };
+    mesh.extend(&[a.into(), b.into(), c.into()], &[0, 1, 2]);
+}
+
+/// Adds a polyline as [`render_polyline`] does, in world space where an x unit
+/// is narrower on screen than a y unit. `thickness` and the lengths in `style`
+/// are in y units, and the line is made as thick on screen running up and down
+/// as running across
+pub fn render_polyline_even(
+    mesh: &mut MeshBuilder,
+    points: &[Vector2<f32>],
+    thickness: f32,
+    closed: bool,
+    style: LineStyle,
+) {
+    let start = mesh.vertices().len();
+    SCRATCH.with(|scratch| {
+        let scratch = &mut *scratch.borrow_mut();
+        let mut squeezed = std::mem::take(&mut scratch.squeezed);
+        squeezed.clear();
+        squeezed.extend(points.iter().map(|p| Vector2::new(p.x / X_PER_Y, p.y)));
+        polyline(mesh, scratch, &squeezed, thickness, closed, style);
+        scratch.squeezed = squeezed;
+    });
+    for vertex in &mut mesh.vertices_mut()[start..] {
+        vertex.position[0] *= X_PER_Y;
+    }
+}
+
+/// Corners of the outlines around `cells`, in cells, one closed loop for each
+/// separate group. Cells that only touch at a corner aren't told apart
+pub fn cell_outlines(cells: &[(i32, i32)]) -> Vec<Vec<Vector2<f32>>> {
+    let mut outlines = Outlines::default();
+    outlines.trace(cells.iter().copied());
+    outlines.iter().map(<[_]>::to_vec).collect()
+}
+
+/// Outlines around groups of cells as [`cell_outlines`] finds them, traced
+/// over the last ones so the room they took is kept
+#[derive(Clone, Debug, Default)]
+pub struct Outlines {
+    cells: Vec<(i32, i32)>,
+    /// Edges of the cells not yet followed, from and to
+    edges: Vec<((i32, i32), (i32, i32))>,
+    /// Corners of every loop, one after the other
+    corners: Vec<Vector2<f32>>,
+    /// Where each loop in `corners` ends
+    ends: Vec<usize>,
+}
+
+impl Outlines {
+    /// Traces the outlines around `cells`, forgetting the last ones
+    pub fn trace(&mut self, cells: impl IntoIterator<Item = (i32, i32)>) {
+        self.cells.clear();
+        self.cells.extend(cells);
+        self.corners.clear();
+        self.ends.clear();
+        let cells = &self.cells;
+        let filled = |x: i32, y: i32| cells.contains(&(x, y));
+
+        // edges no other cell shares, going clockwise on screen around each cell
+        self.edges.clear();
+        for &(x, y) in cells {
+            let sides = [
+                ((x, y - 1), (x, y), (x + 1, y)),
+                ((x + 1, y), (x + 1, y), (x + 1, y + 1)),
+                ((x, y + 1), (x + 1, y + 1), (x, y + 1)),
+                ((x - 1, y), (x, y + 1), (x, y)),
+            ];
+            for &((nx, ny), from, to) in &sides {
+                if !filled(nx, ny) {
+                    self.edges.push((from, to));
+                }
+            }
+        }
+
+        let corner = |(x, y): (i32, i32)| Vector2::new(x as f32, y as f32);
+        while let Some((start, mut to)) = self.edges.pop() {
+            self.corners.push(corner(start));
+            while to != start {
+                self.corners.push(corner(to));
+                match self.edges.iter().position(|&(from, _)| from == to) {
+                    Some(next) => to = self.edges.swap_remove(next).1,
+                    None => break,
+                }
+            }
+            self.ends.push(self.corners.len());
+        }
+    }
+
+    /// Corners of each loop traced last
+    pub fn iter(&self) -> impl Iterator<Item = &[Vector2<f32>]> {
+        let starts = std::iter::once(0).chain(self.ends.iter().copied());
+        starts
+            .zip(&self.ends)
+            .map(move |(start, &end)| &self.corners[start..end])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simplify(points: &[Vector2<f32>], closed: bool) -> Vec<Vector2<f32>> {
+        let mut kept = Vec::new();
+        simplify_into(points, closed, &mut kept);
+        kept
+    }
+
+    fn dashes(
+        points: &[Vector2<f32>],
+        closed: bool,
+        dash: f32,
+        gap: f32,
+    ) -> Vec<Vec<Vector2<f32>>> {
+        let mut runs = Vec::new();
+        let (mut path, mut run) = (Vec::new(), Vec::new());
+        dashes_into(points, closed, dash, gap, &mut path, &mut run, |run| {
+            runs.push(run.to_vec())
+        });
+        runs
+    }
+
+    fn point(mesh: &MeshBuilder, index: u32) -> Vector2<f32> {
+        let position = mesh.vertices()[index as usize].position;
+        Vector2::new(position[0], position[1])
+    }
+
+    /// Twice the signed area of each triangle in `mesh`
+    fn areas(mesh: &MeshBuilder) -> Vec<f32> {
+        mesh.indices()
+            .chunks_exact(3)
+            .map(|t| {
+                let (a, b, c) = (point(mesh, t[0]), point(mesh, t[1]), point(mesh, t[2]));
+                (b - a).perp_dot(c - a)
+            })
+            .collect()
+    }
+
+    /// Checks every triangle has area and is wound as [`render_lines_pairs`]
+    /// winds its quads
+    fn assert_sound(mesh: &MeshBuilder) {
+        for area in areas(mesh) {
+            assert!(area < -SAME_POINT, "triangle with area {}", area);
+        }
+    }
+
+    fn has_vertex(mesh: &MeshBuilder, at: Vector2<f32>) -> bool {
+        (0..mesh.vertices().len() as u32).any(|i| (point(mesh, i) - at).magnitude() < 1e-5)
+    }
+
+    fn l_shape() -> Vec<Vector2<f32>> {
+        vec![
+            Vector2::new(0.0, 0.0),
+            Vector2::new(1.0, 0.0),
+            Vector2::new(1.0, 1.0),
+        ]
+    }
+
+    fn square() -> Vec<Vector2<f32>> {
+        vec![
+            Vector2::new(0.0, 0.0),
+            Vector2::new(1.0, 0.0),
+            Vector2::new(1.0, 1.0),
+            Vector2::new(0.0, 1.0),
+        ]
+    }
+
+    #[test]
+    fn an_l_is_two_quads_mitered_at_the_corner() {
+        let mut mesh = MeshBuilder::new();
+        render_polyline(&mut mesh, &l_shape(), 0.2, false, LineStyle::Solid);
+        assert_eq!(mesh.vertices().len(), 8);
+        assert_eq!(mesh.indices().len(), 12);
+        assert_sound(&mesh);
+
+        // both edges meet where they cross, inside and outside the turn
+        assert!(has_vertex(&mesh, Vector2::new(1.1, -0.1)));
+        assert!(has_vertex(&mesh, Vector2::new(0.9, 0.1)));
+        // and the ends are cut square
+        assert!(has_vertex(&mesh, Vector2::new(0.0, 0.1)));
+        assert!(has_vertex(&mesh, Vector2::new(0.9, 1.0)));
+    }
+
+    #[test]
+    fn a_closed_square_joins_back_up_with_itself() {
+        let mut mesh = MeshBuilder::new();
+        render_polyline(&mut mesh, &square(), 0.2, true, LineStyle::Solid);
+        assert_eq!(mesh.vertices().len(), 16);
+        assert_eq!(mesh.indices().len(), 24);
+        assert_sound(&mesh);
+
+        // the first corner is mitered like the rest, not left open
+        assert!(has_vertex(&mesh, Vector2::new(-0.1, -0.1)));
+        assert!(has_vertex(&mesh, Vector2::new(0.1, 0.1)));
+        assert!(!has_vertex(&mesh, Vector2::new(0.0, 0.1)));
+
+        // repeating the first point at the end closes it no differently
+        let mut repeated = MeshBuilder::new();
+        let mut points = square();
+        points.push(points[0]);
+        render_polyline(&mut repeated, &points, 0.2, true, LineStyle::Solid);
+        assert_eq!(repeated.vertices(), mesh.vertices());
+    }
+
+    #[test]
+    fn repeated_and_straight_through_points_are_dropped() {
+        let points = [
+            Vector2::new(0.0, 0.0),
+            Vector2::new(0.0, 0.0),
+            Vector2::new(0.25, 0.0),
+            Vector2::new(0.5, 0.0),
+            Vector2::new(1.0, 0.0),
+            Vector2::new(1.0, 0.0),
+            Vector2::new(1.0, 0.5),
+            Vector2::new(1.0, 1.0),
+        ];
+        let mut mesh = MeshBuilder::new();
+        render_polyline(&mut mesh, &points, 0.2, false, LineStyle::Solid);
+        assert_eq!(mesh.vertices().len(), 8);
+        assert_sound(&mesh);
+
+        let mut plain = MeshBuilder::new();
+        render_polyline(&mut plain, &l_shape(), 0.2, false, LineStyle::Solid);
+        assert_eq!(mesh.vertices(), plain.vertices());
+    }
+
+    #[test]
+    fn a_sharp_corner_is_beveled() {
+        let points = [
+            Vector2::new(0.0, 0.0),
+            Vector2::new(1.0, 0.0),
+            Vector2::new(0.0, 0.1),
+        ];
+        let mut mesh = MeshBuilder::new();
+        render_polyline(&mut mesh, &points, 0.2, false, LineStyle::Solid);
+        // two quads and the triangle filling the outside of the turn
+        assert_eq!(mesh.vertices().len(), 11);
+        assert_eq!(mesh.indices().len(), 15);
+        assert_sound(&mesh);
+        // nothing reaches far past the corner as a miter would
+        for i in 0..mesh.vertices().len() as u32 {
+            assert!(point(&mesh, i).x < 1.0 + 0.1 * MITER_LIMIT);
+        }
+    }
+
+    #[test]
+    fn too_few_points_draw_nothing() {
+        let mut mesh = MeshBuilder::new();
+        render_polyline(&mut mesh, &[], 0.2, true, LineStyle::Solid);
+        let one = [Vector2::new(0.5, 0.5)];
+        render_polyline(&mut mesh, &one, 0.2, false, LineStyle::Solid);
+        render_polyline(&mut mesh, &[one[0], one[0]], 0.2, true, LineStyle::Solid);
+        assert!(mesh.is_empty());
     }
 
-    (vertices, indices)
+    #[test]
+    fn outlines_go_round_each_separate_group_of_cells() {
+        // an L of three cells and one on its own
+        let outlines = cell_outlines(&[(0, 0), (0, 1), (1, 1), (4, 4)]);
+        assert_eq!(outlines.len(), 2);
+        let mut corners: Vec<usize> = outlines
+            .iter()
+            .map(|outline| simplify(outline, true).len())
+            .collect();
+        corners.sort_unstable();
+        assert_eq!(corners, vec![4, 6]);
+
+        for outline in &outlines {
+            let mut mesh = MeshBuilder::new();
+            render_polyline(&mut mesh, outline, 0.1, true, LineStyle::Solid);
+            assert_sound(&mesh);
+        }
+    }
+
+    #[test]
+    fn tracing_again_forgets_the_last_outlines() {
+        let mut outlines = Outlines::default();
+        outlines.trace(vec![(0, 0), (4, 4)]);
+        outlines.trace(vec![(2, 2), (2, 3)]);
+        let traced: Vec<&[Vector2<f32>]> = outlines.iter().collect();
+        assert_eq!(traced, cell_outlines(&[(2, 2), (2, 3)]));
+    }
+
+    fn length(run: &[Vector2<f32>]) -> f32 {
+        run.windows(2).map(|w| (w[1] - w[0]).magnitude()).sum()
+    }
+
+    #[test]
+    fn a_dashed_segment_is_cut_into_runs_and_gaps() {
+        let segment = [Vector2::new(0.0, 0.0), Vector2::new(10.0, 0.0)];
+        let runs = dashes(&segment, false, 2.0, 1.0);
+        let spans: Vec<(f32, f32)> = runs.iter().map(|r| (r[0].x, length(r))).collect();
+        // the last dash is cut short by the end of the line
+        assert_eq!(spans, vec![(0.0, 2.0), (3.0, 2.0), (6.0, 2.0), (9.0, 1.0)]);
+
+        let mut mesh = MeshBuilder::new();
+        let style = LineStyle::Dashed {
+            dash: 2.0,
+            gap: 1.0,
+        };
+        render_polyline(&mut mesh, &segment, 0.2, false, style);
+        assert_eq!(mesh.vertices().len(), 4 * 4);
+        assert_sound(&mesh);
+    }
+
+    #[test]
+    fn dots_are_as_long_as_the_line_is_thick() {
+        let segment = [Vector2::new(0.0, 0.0), Vector2::new(2.5, 0.0)];
+        let mut mesh = MeshBuilder::new();
+        let style = LineStyle::Dotted { spacing: 1.0 };
+        render_polyline(&mut mesh, &segment, 0.25, false, style);
+        assert_eq!(mesh.vertices().len(), 3 * 4);
+        assert_sound(&mesh);
+        for dot in mesh.vertices().chunks_exact(4) {
+            let xs: Vec<f32> = dot.iter().map(|v| v.position[0]).collect();
+            let width = xs.iter().cloned().fold(f32::MIN, f32::max)
+                - xs.iter().cloned().fold(f32::MAX, f32::min);
+            assert_eq!(width, 0.25);
+        }
+    }
+
+    #[test]
+    fn the_dash_pattern_carries_on_round_corners() {
+        // a dash starts before the corner and finishes after it
+        let bend = [
+            Vector2::new(0.0, 0.0),
+            Vector2::new(1.0, 0.0),
+            Vector2::new(1.0, 5.0),
+        ];
+        let runs = dashes(&bend, false, 2.0, 1.0);
+        assert_eq!(
+            runs[0],
+            vec![
+                Vector2::new(0.0, 0.0),
+                Vector2::new(1.0, 0.0),
+                Vector2::new(1.0, 1.0),
+            ]
+        );
+        assert_eq!(runs[1][0], Vector2::new(1.0, 2.0));
+        assert_eq!(runs.iter().map(|r| length(r)).sum::<f32>(), 2.0 + 2.0);
+
+        // and a gap does the same
+        let bend = [
+            Vector2::new(0.0, 0.0),
+            Vector2::new(2.5, 0.0),
+            Vector2::new(2.5, 5.0),
+        ];
+        let runs = dashes(&bend, false, 2.0, 1.0);
+        assert_eq!(runs[1][0], Vector2::new(2.5, 0.5));
+    }
+
+    #[test]
+    fn a_segment_too_short_for_a_dash_still_shows() {
+        // the second segment falls wholly in the gap after the first dash
+        let points = [
+            Vector2::new(0.0, 0.0),
+            Vector2::new(2.25, 0.0),
+            Vector2::new(2.25, 0.5),
+        ];
+        let runs = dashes(&points, false, 2.0, 1.0);
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[1], vec![points[1], points[2]]);
+    }
+
+    #[test]
+    fn a_closed_dashed_line_dashes_its_last_side_too() {
+        let runs = dashes(&square(), true, 0.5, 0.5);
+        // a dash starting at each corner, back along to the first one
+        assert_eq!(runs.len(), 4);
+        assert_eq!(runs.iter().map(|r| length(r)).sum::<f32>(), 4.0 * 0.5);
+        assert!(runs.iter().any(|r| r[0] == Vector2::new(0.0, 1.0)));
+    }
+
+    /// Whether any triangle of `mesh` covers `at`
+    fn covers(mesh: &MeshBuilder, at: Vector2<f32>) -> bool {
+        mesh.indices().chunks_exact(3).any(|t| {
+            let (a, b, c) = (point(mesh, t[0]), point(mesh, t[1]), point(mesh, t[2]));
+            let sides = [
+                (b - a).perp_dot(at - a),
+                (c - b).perp_dot(at - b),
+                (a - c).perp_dot(at - c),
+            ];
+            sides.iter().all(|&s| s <= 0.0) || sides.iter().all(|&s| s >= 0.0)
+        })
+    }
+
+    fn one_line(thickness: f32, cap: LineCap) -> MeshBuilder {
+        let mut mesh = MeshBuilder::new();
+        let pair = [Vector2::new(0.0, 0.0), Vector2::new(1.0, 0.0)];
+        render_lines_pairs(&mut mesh, &pair, thickness, cap);
+        mesh
+    }
+
+    #[test]
+    fn butt_caps_stop_at_the_end_points() {
+        let mesh = one_line(0.25, LineCap::Butt);
+        assert_eq!(mesh.vertices().len(), 4);
+        assert_eq!(mesh.indices().len(), 6);
+        assert_sound(&mesh);
+        assert!(covers(&mesh, Vector2::new(0.999, 0.1)));
+        assert!(!covers(&mesh, Vector2::new(1.01, 0.0)));
+    }
+
+    #[test]
+    fn square_caps_reach_half_the_thickness_past_the_end_points() {
+        let mesh = one_line(0.25, LineCap::Square);
+        assert_eq!(mesh.vertices().len(), 4);
+        assert_sound(&mesh);
+        assert!(has_vertex(&mesh, Vector2::new(-0.125, 0.125)));
+        assert!(has_vertex(&mesh, Vector2::new(1.125, -0.125)));
+    }
+
+    #[test]
+    fn round_caps_have_more_sides_the_thicker_the_line() {
+        // a quad, then a fan of triangles at each end
+        let sides = |thickness: f32| {
+            let mesh = one_line(thickness, LineCap::Round);
+            assert_sound(&mesh);
+            (mesh.vertices().len() - 4) / (2 * 3)
+        };
+        assert_eq!(sides(1.0 / 128.0), 4);
+        assert_eq!(sides(1.0 / 64.0), 7);
+        assert_eq!(sides(1e-4), MIN_ROUND_SEGMENTS as usize);
+        assert_eq!(sides(1.0), MAX_ROUND_SEGMENTS as usize);
+
+        let mesh = one_line(0.25, LineCap::Round);
+        assert!(covers(&mesh, Vector2::new(1.1, 0.0)));
+        assert!(covers(&mesh, Vector2::new(-0.1, 0.0)));
+        // but not the corners a square cap would fill
+        assert!(!covers(&mesh, Vector2::new(1.12, 0.12)));
+    }
+
+    #[test]
+    fn the_field_grid_has_no_pinholes_at_its_crossings() {
+        // drawn as the field draws its grid, with the edges added in, and the
+        // rows half as thick as the columns
+        let (width, height, thickness) = (4, 5, 0.02);
+        let mut mesh = MeshBuilder::new();
+        let mut rows = Vec::new();
+        for y in 0..=height {
+            let y = y as f32 / height as f32;
+            rows.extend(&[Vector2::new(0.0, y), Vector2::new(1.0, y)]);
+        }
+        render_lines_pairs(&mut mesh, &rows, thickness / 2.0, LineCap::Square);
+        let mut columns = Vec::new();
+        for x in 0..=width {
+            let x = x as f32 / width as f32;
+            columns.extend(&[Vector2::new(x, 0.0), Vector2::new(x, 1.0)]);
+        }
+        render_lines_pairs(&mut mesh, &columns, thickness, LineCap::Square);
+        assert_sound(&mesh);
+
+        // every crossing, edges and corners too, is covered all the way over
+        let (across, up) = (thickness / 2.0, thickness / 4.0);
+        for x in 0..=width {
+            for y in 0..=height {
+                let at = Vector2::new(x as f32 / width as f32, y as f32 / height as f32);
+                for &(dx, dy) in &[
+                    (-1.0, -1.0),
+                    (-1.0, 1.0),
+                    (1.0, -1.0),
+                    (1.0, 1.0),
+                    (0.0, 0.0),
+                ] {
+                    let probe = at + Vector2::new(dx * across, dy * up) * 0.99;
+                    assert!(covers(&mesh, probe), "pinhole at {:?}", probe);
+                }
+            }
+        }
+    }
 }
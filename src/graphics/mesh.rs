@@ -0,0 +1,269 @@
+//! Geometry built up for a frame, and the buffers it's drawn from.
+//!
+//! Geometry is rebuilt every frame, but rather than creating buffers for it
+//! each time, it's written into buffers that only get replaced when it no
+//! longer fits. Indices are 32 bits wide, so no mesh gets big enough to wrap
+//! them around.
+//!
+//! The builders are kept from frame to frame too: a [`MeshPool`] hands out
+//! builders emptied of last frame's geometry but not of the room it took, so
+//! once the game has settled building a frame doesn't allocate.
+
+use std::cell::RefCell;
+use std::ops::{Deref, DerefMut};
+
+use super::Vertex;
+
+/// Vertices or indices room is made for when a buffer is first created
+const INITIAL_CAPACITY: usize = 1024;
+
+/// Vertices and the triangles made of them. Geometry is added counting
+/// indices from its own first vertex, and moved along to wherever it ends up
+#[derive(Clone, Default)]
+pub struct MeshBuilder {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `vertices`, and triangles made of `indices` into them
+    pub fn extend(&mut self, vertices: &[Vertex], indices: &[u32]) {
+        let base = self.vertices.len() as u32;
+        self.indices.extend(indices.iter().map(|i| base + i));
+        self.vertices.extend_from_slice(vertices);
+    }
+
+    /// Adds a quad from its top left, bottom left, top right and bottom right
+    /// corners
+    pub fn quad(&mut self, corners: [Vertex; 4]) {
+        self.extend(&corners, &[0, 1, 2, 2, 1, 3]);
+    }
+
+    /// Adds everything in `other`, drawn over what's here already
+    pub fn append(&mut self, other: &MeshBuilder) {
+        self.extend(&other.vertices, &other.indices);
+    }
+
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    /// Vertices to move or recolour in place. Only ever as many as were added
+    pub fn vertices_mut(&mut self) -> &mut [Vertex] {
+        &mut self.vertices
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Removes all geometry, keeping the room it took
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+    }
+}
+
+/// Builders that have been used and given back, to be used again
+#[derive(Default)]
+pub struct MeshPool {
+    free: RefCell<Vec<MeshBuilder>>,
+}
+
+impl MeshPool {
+    /// An empty builder, which goes back to the pool when it's dropped
+    pub fn take(&self) -> PooledMesh<'_> {
+        let mesh = self.free.borrow_mut().pop().unwrap_or_default();
+        PooledMesh {
+            pool: self,
+            mesh: Some(mesh),
+        }
+    }
+}
+
+/// A builder lent out by a [`MeshPool`]. Used like a [`MeshBuilder`]
+pub struct PooledMesh<'a> {
+    pool: &'a MeshPool,
+
+    /// Only None while it's being given back
+    mesh: Option<MeshBuilder>,
+}
+
+impl Deref for PooledMesh<'_> {
+    type Target = MeshBuilder;
+
+    fn deref(&self) -> &MeshBuilder {
+        self.mesh.as_ref().expect("mesh was given back")
+    }
+}
+
+impl DerefMut for PooledMesh<'_> {
+    fn deref_mut(&mut self) -> &mut MeshBuilder {
+        self.mesh.as_mut().expect("mesh was given back")
+    }
+}
+
+impl Drop for PooledMesh<'_> {
+    fn drop(&mut self) {
+        if let Some(mut mesh) = self.mesh.take() {
+            mesh.clear();
+            self.pool.free.borrow_mut().push(mesh);
+        }
+    }
+}
+
+pub struct MeshBuffer {
+    label: &'static str,
+    vertices: wgpu::Buffer,
+    indices: wgpu::Buffer,
+    vertex_capacity: usize,
+    index_capacity: usize,
+
+    /// Indices written by the last [`write`](Self::write)
+    index_count: u32,
+}
+
+fn vertex_buffer(device: &wgpu::Device, label: &str, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: (capacity * std::mem::size_of::<Vertex>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn index_buffer(device: &wgpu::Device, label: &str, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: (capacity * std::mem::size_of::<u32>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsage::INDEX | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// Room for `needed` items, leaving slack so a slowly growing mesh doesn't
+/// reallocate every frame
+fn grown(needed: usize) -> usize {
+    needed.next_power_of_two().max(INITIAL_CAPACITY)
+}
+
+impl MeshBuffer {
+    pub fn new(device: &wgpu::Device, label: &'static str) -> Self {
+        Self {
+            label,
+            vertices: vertex_buffer(device, label, INITIAL_CAPACITY),
+            indices: index_buffer(device, label, INITIAL_CAPACITY),
+            vertex_capacity: INITIAL_CAPACITY,
+            index_capacity: INITIAL_CAPACITY,
+            index_count: 0,
+        }
+    }
+
+    /// Replaces the contents with `mesh`, reallocating only if it doesn't
+    /// fit. Takes effect for work submitted after the call
+    pub fn write(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, mesh: &MeshBuilder) {
+        let (vertices, indices) = (mesh.vertices(), mesh.indices());
+
+        if vertices.len() > self.vertex_capacity {
+            self.vertex_capacity = grown(vertices.len());
+            self.vertices = vertex_buffer(device, self.label, self.vertex_capacity);
+        }
+        if indices.len() > self.index_capacity {
+            self.index_capacity = grown(indices.len());
+            self.indices = index_buffer(device, self.label, self.index_capacity);
+        }
+
+        if !vertices.is_empty() {
+            queue.write_buffer(&self.vertices, 0, bytemuck::cast_slice(vertices));
+        }
+        if !indices.is_empty() {
+            queue.write_buffer(&self.indices, 0, bytemuck::cast_slice(indices));
+        }
+        self.index_count = indices.len() as u32;
+    }
+
+    /// The whole vertex buffer, which may be longer than what was written
+    pub fn vertices(&self) -> wgpu::BufferSlice<'_> {
+        self.vertices.slice(..)
+    }
+
+    /// The whole index buffer, which may be longer than what was written
+    pub fn indices(&self) -> wgpu::BufferSlice<'_> {
+        self.indices.slice(..)
+    }
+
+    /// Indices written last, which is how many to draw
+    pub fn index_count(&self) -> u32 {
+        self.index_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::text::render_text;
+
+    fn vertex(x: f32) -> Vertex {
+        Vertex {
+            position: [x, 0.0, 0.0],
+            color: [1.0; 4],
+            tex_coords: [0.0; 2],
+        }
+    }
+
+    #[test]
+    fn geometry_is_moved_along_past_what_came_before() {
+        let mut mesh = MeshBuilder::new();
+        mesh.extend(&[vertex(0.0), vertex(1.0), vertex(2.0)], &[0, 1, 2]);
+        mesh.extend(&[vertex(3.0), vertex(4.0), vertex(5.0)], &[2, 1, 0]);
+        assert_eq!(mesh.indices(), &[0, 1, 2, 5, 4, 3]);
+
+        let mut other = MeshBuilder::new();
+        other.quad([vertex(6.0), vertex(7.0), vertex(8.0), vertex(9.0)]);
+        mesh.append(&other);
+        assert_eq!(&mesh.indices()[6..], &[6, 7, 8, 8, 7, 9]);
+        assert_eq!(mesh.vertices().len(), 10);
+    }
+
+    #[test]
+    fn text_past_sixteen_bit_indices_does_not_wrap() {
+        let mut mesh = MeshBuilder::new();
+        let line = "The quick brown fox jumps over the lazy dog. ".repeat(40);
+        for row in 0..12 {
+            render_text(&mut mesh, &line, 0.0, row as f32, 0.05, [1.0; 3]);
+        }
+        let vertices = mesh.vertices().len();
+        assert!(vertices > 70_000, "only {} vertices", vertices);
+
+        // each glyph is a quad of its own four vertices, in order
+        for (glyph, quad) in mesh.indices().chunks(6).enumerate() {
+            let first = glyph as u32 * 4;
+            assert!(
+                quad.iter().all(|&i| i >= first && i < first + 4),
+                "{}",
+                glyph
+            );
+        }
+        assert_eq!(*mesh.indices().iter().max().unwrap() as usize, vertices - 1);
+    }
+
+    #[test]
+    fn pooled_meshes_come_back_empty() {
+        let pool = MeshPool::default();
+        {
+            let mut mesh = pool.take();
+            mesh.quad([vertex(0.0), vertex(1.0), vertex(2.0), vertex(3.0)]);
+        }
+        let mesh = pool.take();
+        assert!(mesh.is_empty());
+        assert!(mesh.vertices().is_empty());
+    }
+}
@@ -0,0 +1,103 @@
+//! Framed boxes for the playfield, the next queue and anything else that
+//! wants setting apart from the background.
+
+use cgmath::Vector2;
+
+use super::lines::{render_polyline_even, LineStyle};
+use super::mesh::MeshBuilder;
+use super::{opaque, Vertex};
+use crate::ui::layout::{Rect, X_PER_Y};
+
+/// How far shadows reach in from the walls, in border widths
+const SHADOW_DEPTH: f32 = 1.5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PanelStyle {
+    /// Colour behind the panel's contents, if it isn't see-through
+    pub fill: Option<[f32; 3]>,
+
+    pub border: [f32; 3],
+
+    /// Width of the border, in y units. It's made as wide on screen along the
+    /// sides as along the top and bottom
+    pub thickness: f32,
+
+    /// Colour the fill darkens to along the inside of the walls, if it does
+    pub shadow: Option<[f32; 3]>,
+}
+
+/// Adds a panel around `rect` to `mesh`, to go under whatever is drawn in it.
+/// The border goes around the outside, so it never covers what's inside
+pub fn draw_panel(mesh: &mut MeshBuilder, rect: &Rect, style: &PanelStyle) {
+    draw_panel_fill(mesh, rect, style);
+    draw_panel_border(mesh, rect, style);
+}
+
+/// Adds the fill and shadow of a panel, without its border, for drawing
+/// something between the two
+pub fn draw_panel_fill(mesh: &mut MeshBuilder, rect: &Rect, style: &PanelStyle) {
+    // colours are for the top left, bottom left, top right and bottom right
+    let mut quad = |x: f32, y: f32, width: f32, height: f32, colors: [[f32; 3]; 4]| {
+        let vertex = |x: f32, y: f32, color: [f32; 3]| Vertex {
+            position: [x, y, 0.0],
+            color: opaque(color),
+            tex_coords: [0.0, 0.0],
+        };
+        mesh.quad([
+            vertex(x, y, colors[0]),
+            vertex(x, y + height, colors[1]),
+            vertex(x + width, y, colors[2]),
+            vertex(x + width, y + height, colors[3]),
+        ]);
+    };
+
+    let (left, top, width, height) = (rect.x, rect.y, rect.width, rect.height);
+    let (right, bottom) = (left + width, top + height);
+    let (thick_x, thick_y) = (style.thickness * X_PER_Y, style.thickness);
+
+    if let Some(fill) = style.fill {
+        quad(left, top, width, height, [fill; 4]);
+
+        // each strip fades from the shadow at its wall into the fill
+        if let Some(shadow) = style.shadow {
+            let (depth_x, depth_y) = (thick_x * SHADOW_DEPTH, thick_y * SHADOW_DEPTH);
+            quad(left, top, depth_x, height, [shadow, shadow, fill, fill]);
+            quad(
+                right - depth_x,
+                top,
+                depth_x,
+                height,
+                [fill, fill, shadow, shadow],
+            );
+            quad(left, top, width, depth_y, [shadow, fill, shadow, fill]);
+            quad(
+                left,
+                bottom - depth_y,
+                width,
+                depth_y,
+                [fill, shadow, fill, shadow],
+            );
+        }
+    }
+}
+
+/// Adds the border of a panel, without its fill
+pub fn draw_panel_border(mesh: &mut MeshBuilder, rect: &Rect, style: &PanelStyle) {
+    let (left, top) = (rect.x, rect.y);
+    let (right, bottom) = (left + rect.width, top + rect.height);
+    let (thick_x, thick_y) = (style.thickness * X_PER_Y, style.thickness);
+
+    // centred half a border out, so its inside edge runs along the rect
+    let (out_x, out_y) = (thick_x / 2.0, thick_y / 2.0);
+    let corners = [
+        Vector2::new(left - out_x, top - out_y),
+        Vector2::new(right + out_x, top - out_y),
+        Vector2::new(right + out_x, bottom + out_y),
+        Vector2::new(left - out_x, bottom + out_y),
+    ];
+    let border_start = mesh.vertices().len();
+    render_polyline_even(mesh, &corners, style.thickness, true, LineStyle::Solid);
+    for vertex in &mut mesh.vertices_mut()[border_start..] {
+        vertex.color = opaque(style.border);
+    }
+}
@@ -0,0 +1,218 @@
+//! Short-lived specks thrown off by things happening on the board.
+//!
+//! Particles live in field cells, y growing downwards, and are stepped once a
+//! tick. They fall under gravity and fade into the background as they age,
+//! and are drawn as small sprites added to whatever batch the board is drawing.
+
+use rand::Rng;
+
+use super::atlas::{Region, Sprite, SpriteBatch};
+use super::{
+    mesh::MeshBuilder,
+    opaque,
+    theme::{PieceColors, Theme},
+};
+use crate::piece::Color;
+
+/// Most particles alive at once. Bursts past this are cut short
+pub const MAX_PARTICLES: usize = 4096;
+
+/// Downwards speed gained each tick, in cells per tick
+const GRAVITY: f32 = 0.04;
+
+/// Side of a particle when it's spawned, in cells. It shrinks as it fades
+const SIZE: f32 = 0.3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Particle {
+    pub position: [f32; 2],
+
+    /// Cells moved each tick
+    pub velocity: [f32; 2],
+
+    /// Ticks left before it disappears, and how many it started with
+    pub life: u32,
+    pub max_life: u32,
+
+    /// Kind of mino it came off, coloured by the theme it's drawn with
+    pub color: Color,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ParticleSystem {
+    particles: Vec<Particle>,
+}
+
+impl ParticleSystem {
+    /// Particles still alive
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    /// Adds `particle`, returning whether there was room for it
+    pub fn emit(&mut self, particle: Particle) -> bool {
+        if self.particles.len() >= MAX_PARTICLES || particle.life == 0 {
+            return false;
+        }
+        self.particles.push(particle);
+        true
+    }
+
+    /// Throws `count` particles of `color` out of the cell at (`x`, `y`),
+    /// mostly upwards, each lasting `life` ticks
+    pub fn burst(
+        &mut self,
+        x: f32,
+        y: f32,
+        color: Color,
+        count: u32,
+        life: u32,
+        rng: &mut impl Rng,
+    ) {
+        for _ in 0..count {
+            let emitted = self.emit(Particle {
+                position: [x + rng.gen_range(0.0..1.0), y + rng.gen_range(0.0..1.0)],
+                velocity: [rng.gen_range(-0.25..0.25), rng.gen_range(-0.5..0.0)],
+                life,
+                max_life: life,
+                color,
+            });
+            if !emitted {
+                break;
+            }
+        }
+    }
+
+    /// Moves everything on by a tick, dropping particles that ran out
+    pub fn update(&mut self) {
+        for p in &mut self.particles {
+            p.velocity[1] += GRAVITY;
+            p.position[0] += p.velocity[0];
+            p.position[1] += p.velocity[1];
+            p.life -= 1;
+        }
+        self.particles.retain(|p| p.life > 0);
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    /// Adds a sprite for every particle to `mesh`, with cells `cell` wide and
+    /// tall, in the colours of `palette` fading into the background of `theme`
+    pub fn render(
+        &self,
+        cell: (f32, f32),
+        palette: &PieceColors,
+        theme: &Theme,
+        mesh: &mut MeshBuilder,
+    ) {
+        let mut batch = SpriteBatch::new(mesh);
+        for p in &self.particles {
+            let fade = p.life as f32 / p.max_life as f32;
+            let color = opaque(theme.fade(palette.get(p.color), 1.0 - fade));
+            let side = SIZE * fade;
+            batch.draw(&Sprite::new(
+                Region::WHOLE,
+                [
+                    (p.position[0] - side / 2.0) * cell.0,
+                    (p.position[1] - side / 2.0) * cell.1,
+                ],
+                [side * cell.0, side * cell.1],
+                color,
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn speck(life: u32) -> Particle {
+        Particle {
+            position: [0.0, 0.0],
+            velocity: [0.0, 0.0],
+            life,
+            max_life: life,
+            color: Color::Cyan,
+        }
+    }
+
+    #[test]
+    fn particles_last_exactly_their_life() {
+        let mut system = ParticleSystem::default();
+        system.emit(speck(1));
+        system.emit(speck(3));
+        system.update();
+        assert_eq!(system.particles().len(), 1);
+        system.update();
+        assert_eq!(system.particles().len(), 1);
+        system.update();
+        assert!(system.particles().is_empty());
+    }
+
+    #[test]
+    fn a_lifeless_particle_is_never_added() {
+        let mut system = ParticleSystem::default();
+        assert!(!system.emit(speck(0)));
+        assert!(system.particles().is_empty());
+    }
+
+    #[test]
+    fn bursts_stop_at_the_cap() {
+        let mut system = ParticleSystem::default();
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..3 {
+            system.burst(0.0, 0.0, Color::Red, 2_000, 10, &mut rng);
+        }
+        assert_eq!(system.particles().len(), MAX_PARTICLES);
+        assert!(!system.emit(speck(5)));
+
+        // room made by particles running out is used again
+        for _ in 0..10 {
+            system.update();
+        }
+        assert!(system.particles().is_empty());
+        system.burst(0.0, 0.0, Color::Red, 100, 10, &mut rng);
+        assert_eq!(system.particles().len(), 100);
+    }
+
+    #[test]
+    fn back_to_back_bursts_keep_both() {
+        let mut system = ParticleSystem::default();
+        let mut rng = StdRng::seed_from_u64(0);
+        system.burst(0.0, 19.0, Color::Red, 40, 10, &mut rng);
+        system.update();
+        system.burst(0.0, 18.0, Color::Blue, 40, 10, &mut rng);
+        let colors = |c| system.particles().iter().filter(|p| p.color == c).count();
+        assert_eq!((colors(Color::Red), colors(Color::Blue)), (40, 40));
+    }
+
+    #[test]
+    fn bursts_start_in_their_cell_and_fall() {
+        let mut system = ParticleSystem::default();
+        let mut rng = StdRng::seed_from_u64(0);
+        system.burst(3.0, 7.0, Color::Green, 50, 200, &mut rng);
+        for p in system.particles() {
+            assert!(p.position[0] >= 3.0 && p.position[0] < 4.0);
+            assert!(p.position[1] >= 7.0 && p.position[1] < 8.0);
+            assert!(p.velocity[1] <= 0.0, "thrown upwards");
+        }
+        for _ in 0..100 {
+            system.update();
+        }
+        assert!(system.particles().iter().all(|p| p.position[1] > 8.0));
+    }
+
+    #[test]
+    fn clearing_removes_everything() {
+        let mut system = ParticleSystem::default();
+        system.burst(0.0, 0.0, Color::Red, 10, 10, &mut StdRng::seed_from_u64(0));
+        system.clear();
+        assert!(system.particles().is_empty());
+    }
+}
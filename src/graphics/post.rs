@@ -0,0 +1,396 @@
+//! Drawing what's behind an overlay through an effect, like dimming or
+//! blurring the game under a menu.
+//!
+//! The states under the overlay are drawn off screen first, into a texture of
+//! the swap chain's size and format, which is then drawn onto the frame as one
+//! triangle covering it. The texture is only made when something first draws
+//! into it and is dropped whenever the swap chain changes, so frames without an
+//! effect don't pay for it.
+//!
+//! Sharing the swap chain's format means an sRGB frame stays sRGB the whole
+//! way: colours are decoded to linear when the texture is sampled and encoded
+//! again on the way out, so a blur mixes them in linear space and an unchanged
+//! pixel comes out exactly as it went in.
+//!
+//! With a [pixel scale](GraphicsState::set_pixel_scale) above 1 every frame is
+//! drawn into a second, smaller texture like this, which is then blown up onto
+//! the window by a whole number of pixels each way, nearest pixel first. The
+//! same triangle does it, drawn into just the middle of the window so the
+//! pixels stay square and what's left over is a border.
+
+use std::cell::{Ref, RefCell};
+
+use super::{assets, shader, Frame, FrameTarget, GraphicsInitError, GraphicsState};
+
+/// Pixel scales that can be picked, 1 drawing at the window's own resolution
+pub const PIXEL_SCALES: &[u32] = &[1, 2, 3, 4];
+
+/// Furthest a blur reaches, in pixels either way. Every pixel reads a square
+/// twice this plus one pixels across, so it's kept small
+pub const MAX_BLUR_RADIUS: u32 = 8;
+
+/// How what's behind an overlay is changed on its way onto the frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Effect {
+    /// Scales colours down by `amount`, from 0 for unchanged to 1 for black
+    Darken { amount: f32 },
+    /// Averages each pixel with the ones up to `radius` pixels away, then
+    /// darkens it by `darken`
+    Blur { radius: u32, darken: f32 },
+}
+
+impl Effect {
+    /// Darkening, blur radius and the size of a pixel in texture coordinates,
+    /// as the shader takes them
+    fn params(self, width: u32, height: u32) -> [f32; 4] {
+        let (darken, radius) = match self {
+            Effect::Darken { amount } => (amount, 0),
+            Effect::Blur { radius, darken } => (darken, radius.min(MAX_BLUR_RADIUS)),
+        };
+        [
+            darken.max(0.0).min(1.0),
+            radius as f32,
+            1.0 / width as f32,
+            1.0 / height as f32,
+        ]
+    }
+}
+
+/// The off screen texture, and its binding for being drawn from
+struct Target {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Everything drawing through an effect needs besides the texture's contents
+pub(super) struct PostProcess {
+    bind_group_layout: wgpu::BindGroupLayout,
+    layout: wgpu::PipelineLayout,
+    vertex: wgpu::ShaderModule,
+    fragment: wgpu::ShaderModule,
+    pipeline: wgpu::RenderPipeline,
+    /// Draws a scaled frame onto the window, which is never multisampled
+    blit_pipeline: wgpu::RenderPipeline,
+    sampler: wgpu::Sampler,
+    /// The effect's [`params`](Effect::params)
+    params: wgpu::Buffer,
+    /// Made the first time it's needed, see the module docs
+    target: RefCell<Option<Target>>,
+    /// What a scaled frame is drawn into, made the same way
+    scaled: RefCell<Option<Target>>,
+}
+
+impl PostProcess {
+    pub(super) fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Result<Self, GraphicsInitError> {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("post_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    count: None,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    count: None,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: false,
+                        filtering: true,
+                    },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    count: None,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("post_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let vertex = shader::load(device, &assets::POST_VERT)?;
+        let fragment = shader::load(device, &assets::POST_FRAG)?;
+        let pipeline = build_pipeline(device, &layout, &vertex, &fragment, format, sample_count);
+        let blit_pipeline = build_pipeline(device, &layout, &vertex, &fragment, format, 1);
+        // every pixel is read from the middle of one, so nothing needs filtering
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let params = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("post_params"),
+            size: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Ok(Self {
+            bind_group_layout,
+            layout,
+            vertex,
+            fragment,
+            pipeline,
+            blit_pipeline,
+            sampler,
+            params,
+            target: RefCell::new(None),
+            scaled: RefCell::new(None),
+        })
+    }
+
+    /// Remakes the pipeline for a new format or sample count, dropping the
+    /// texture as it may be the wrong format now
+    pub(super) fn rebuild(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) {
+        self.pipeline = build_pipeline(
+            device,
+            &self.layout,
+            &self.vertex,
+            &self.fragment,
+            format,
+            sample_count,
+        );
+        self.blit_pipeline = build_pipeline(
+            device,
+            &self.layout,
+            &self.vertex,
+            &self.fragment,
+            format,
+            1,
+        );
+        self.discard_target();
+    }
+
+    /// Drops the textures, for when the frame changes size. The next frame
+    /// drawn into one makes a new one
+    pub(super) fn discard_target(&mut self) {
+        *self.target.get_mut() = None;
+        *self.scaled.get_mut() = None;
+    }
+
+    /// The texture, made to match `desc` if there isn't one
+    fn target(&self, device: &wgpu::Device, desc: &wgpu::SwapChainDescriptor) -> Ref<Target> {
+        self.made(&self.target, device, desc, "offscreen_texture")
+    }
+
+    /// The texture a scaled frame is drawn into, made to match `desc` if
+    /// there isn't one
+    fn scaled(&self, device: &wgpu::Device, desc: &wgpu::SwapChainDescriptor) -> Ref<Target> {
+        self.made(&self.scaled, device, desc, "scaled_texture")
+    }
+
+    fn made<'a>(
+        &self,
+        slot: &'a RefCell<Option<Target>>,
+        device: &wgpu::Device,
+        desc: &wgpu::SwapChainDescriptor,
+        label: &str,
+    ) -> Ref<'a, Target> {
+        if slot.borrow().is_none() {
+            let target = self.create_target(device, desc, label);
+            *slot.borrow_mut() = Some(target);
+        }
+        Ref::map(slot.borrow(), |target| {
+            target.as_ref().expect("target was just made")
+        })
+    }
+
+    fn create_target(
+        &self,
+        device: &wgpu::Device,
+        sc_desc: &wgpu::SwapChainDescriptor,
+        label: &str,
+    ) -> Target {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: sc_desc.width,
+                height: sc_desc.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: sc_desc.format,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT
+                | wgpu::TextureUsage::SAMPLED
+                | wgpu::TextureUsage::COPY_SRC,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("post_bind_group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.params.as_entire_binding(),
+                },
+            ],
+        });
+        Target {
+            texture,
+            bind_group,
+        }
+    }
+}
+
+fn build_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    vertex: &wgpu::ShaderModule,
+    fragment: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        layout: Some(layout),
+        label: Some("post_pipeline"),
+        vertex: wgpu::VertexState {
+            buffers: &[],
+            entry_point: "main",
+            module: vertex,
+        },
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+            clamp_depth: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            alpha_to_coverage_enabled: false,
+            mask: !0,
+            count: sample_count,
+        },
+        fragment: Some(wgpu::FragmentState {
+            entry_point: "main",
+            module: fragment,
+            targets: &[wgpu::ColorTargetState {
+                blend: Some(wgpu::BlendState::REPLACE),
+                format,
+                write_mask: wgpu::ColorWrite::all(),
+            }],
+        }),
+    })
+}
+
+impl GraphicsState {
+    /// Starts a frame that's drawn off screen, to be put onto another one
+    /// through an effect by [`composite`](Self::composite). It has to be
+    /// finished before anything else is drawn onto the other frame
+    pub fn begin_offscreen(&self) -> Frame {
+        let target = self.post.target(&self.device, &self.render_desc());
+        let view = target
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        self.frame_into(FrameTarget::Texture(view))
+    }
+
+    /// Draws the last frame drawn off screen onto `frame` through `effect`,
+    /// covering whatever `frame` had
+    pub fn composite(&self, frame: &mut Frame, effect: Effect) {
+        let desc = self.render_desc();
+        let target = self.post.target(&self.device, &desc);
+        let params = effect.params(desc.width, desc.height);
+        self.queue
+            .write_buffer(&self.post.params, 0, bytemuck::cast_slice(&params));
+        self.count_upload(std::mem::size_of_val(&params));
+
+        let mut command_buf = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("post_command_buf"),
+            });
+        {
+            let mut pass = frame.begin_pass(&mut command_buf);
+            pass.set_pipeline(&self.post.pipeline);
+            pass.set_bind_group(0, &target.bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        self.queue.submit(std::iter::once(command_buf.finish()));
+    }
+
+    /// View of the texture a frame is drawn into while the pixel scale is
+    /// above 1, to be blown up onto the window by [`blit`](Self::blit)
+    pub(super) fn scaled_view(&self) -> wgpu::TextureView {
+        self.post
+            .scaled(&self.device, &self.render_desc())
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    /// Draws the scaled frame onto `output`, each of its pixels a square of
+    /// the pixel scale's size, bordered in the background colour
+    pub(super) fn blit(&self, output: &wgpu::TextureView) {
+        let desc = self.render_desc();
+        let target = self.post.scaled(&self.device, &desc);
+        let params = Effect::Darken { amount: 0.0 }.params(desc.width, desc.height);
+        self.queue
+            .write_buffer(&self.post.params, 0, bytemuck::cast_slice(&params));
+        self.count_upload(std::mem::size_of_val(&params));
+
+        let [x, y, width, height] = self.scaled_viewport();
+        let mut command_buf = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("blit_command_buf"),
+            });
+        {
+            let mut pass = command_buf.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("blit_pass"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: output,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(
+                            self.theme.clear_color(super::is_srgb(self.sc_desc.format)),
+                        ),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            pass.set_viewport(x, y, width, height, 0.0, 1.0);
+            pass.set_pipeline(&self.post.blit_pipeline);
+            pass.set_bind_group(0, &target.bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        self.queue.submit(std::iter::once(command_buf.finish()));
+    }
+}
@@ -0,0 +1,305 @@
+//! Timings of named parts of each frame, and an overlay showing them.
+//!
+//! Code being measured opens a [`Scope`] with [`Profiler::scope`] and the time
+//! until it's dropped is added to the current frame under that name. While the
+//! profiler is off a scope doesn't even read the clock.
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use super::{opaque, text, Frame, GraphicsState, Vertex};
+use crate::ui::layout::Rect;
+
+/// Frames kept for averages and the graph
+pub const HISTORY: usize = 120;
+
+/// How far back the frame rate is averaged over
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Frame time the graph's full height stands for
+const GRAPH_SCALE: Duration = Duration::from_micros(33_333);
+
+/// Frame time above which a bar is drawn as over budget
+const FRAME_BUDGET: Duration = Duration::from_micros(16_667);
+
+/// Time spent in each section, in the order they were first entered
+type Timings = Vec<(&'static str, Duration)>;
+
+#[derive(Debug, Default)]
+pub struct Profiler {
+    enabled: Cell<bool>,
+
+    /// Sections timed so far this frame
+    current: RefCell<Timings>,
+
+    /// Finished frames, oldest first
+    frames: RefCell<VecDeque<Timings>>,
+
+    /// Time between the starts of the frames in the last `RATE_WINDOW`,
+    /// oldest first, and their total
+    intervals: RefCell<VecDeque<Duration>>,
+    interval_total: Cell<Duration>,
+
+    /// Fixed ticks run by the last frame's update
+    ticks: Cell<u32>,
+
+    /// Frame rate frames are being limited to, or 0 if they aren't
+    cap: Cell<u32>,
+}
+
+/// Times a section until dropped
+pub struct Scope<'a> {
+    profiler: &'a Profiler,
+    name: &'static str,
+
+    /// When the section was entered, or None if the profiler is off
+    start: Option<Instant>,
+}
+
+impl Drop for Scope<'_> {
+    fn drop(&mut self) {
+        if let Some(start) = self.start {
+            self.profiler.record(self.name, start.elapsed());
+        }
+    }
+}
+
+impl Profiler {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.get()
+    }
+
+    /// Turns timing on or off. Turning it off forgets what was recorded
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.set(enabled);
+        if !enabled {
+            self.current.borrow_mut().clear();
+            self.frames.borrow_mut().clear();
+            self.intervals.borrow_mut().clear();
+            self.interval_total.set(Duration::from_secs(0));
+        }
+    }
+
+    /// Starts timing `name`, until the returned guard is dropped
+    pub fn scope(&self, name: &'static str) -> Scope<'_> {
+        Scope {
+            profiler: self,
+            name,
+            start: if self.is_enabled() {
+                Some(Instant::now())
+            } else {
+                None
+            },
+        }
+    }
+
+    fn record(&self, name: &'static str, time: Duration) {
+        let mut current = self.current.borrow_mut();
+        match current.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, total)) => *total += time,
+            None => current.push((name, time)),
+        }
+    }
+
+    /// Notes the frame rate frames are being limited to, 0 for none, to be
+    /// shown alongside the rate they come at
+    pub fn set_cap(&self, fps: u32) {
+        self.cap.set(fps);
+    }
+
+    /// Closes the current frame, starting a new one
+    pub fn end_frame(&self) {
+        if !self.is_enabled() {
+            return;
+        }
+        let finished = std::mem::take(&mut *self.current.borrow_mut());
+        let mut frames = self.frames.borrow_mut();
+        if frames.len() == HISTORY {
+            frames.pop_front();
+        }
+        frames.push_back(finished);
+    }
+
+    /// Counts a frame that started `dt` after the one before and ran `ticks`
+    /// fixed ticks, for the frame rate
+    pub fn record_frame(&self, dt: Duration, ticks: u32) {
+        if !self.is_enabled() {
+            return;
+        }
+        self.ticks.set(ticks);
+        let mut intervals = self.intervals.borrow_mut();
+        let mut total = self.interval_total.get() + dt;
+        intervals.push_back(dt);
+        while total > RATE_WINDOW && intervals.len() > 1 {
+            total -= intervals.pop_front().unwrap_or_default();
+        }
+        self.interval_total.set(total);
+    }
+
+    /// Frames a second and the mean time between them, over the last
+    /// `RATE_WINDOW`
+    pub fn frame_rate(&self) -> (f32, Duration) {
+        let count = self.intervals.borrow().len() as u32;
+        let total = self.interval_total.get();
+        if count == 0 || total == Duration::from_secs(0) {
+            return (0.0, Duration::from_secs(0));
+        }
+        (count as f32 / total.as_secs_f32(), total / count)
+    }
+
+    /// Fixed ticks run by the last frame's update
+    pub fn ticks(&self) -> u32 {
+        self.ticks.get()
+    }
+
+    /// Sections of the last finished frame
+    pub fn last_frame(&self) -> Timings {
+        self.frames.borrow().back().cloned().unwrap_or_default()
+    }
+
+    /// Mean time spent in `name` over the frames kept, counting frames that
+    /// didn't enter it as zero
+    pub fn average(&self, name: &str) -> Duration {
+        let frames = self.frames.borrow();
+        if frames.is_empty() {
+            return Duration::from_secs(0);
+        }
+        let total: Duration = frames
+            .iter()
+            .flat_map(|f| f.iter())
+            .filter(|(n, _)| *n == name)
+            .map(|&(_, time)| time)
+            .sum();
+        total / frames.len() as u32
+    }
+
+    /// Time spent across all sections, for each frame kept, oldest first
+    pub fn frame_totals(&self) -> Vec<Duration> {
+        self.frames
+            .borrow()
+            .iter()
+            .map(|f| f.iter().map(|&(_, time)| time).sum())
+            .collect()
+    }
+}
+
+fn millis(time: Duration) -> f32 {
+    time.as_secs_f32() * 1000.0
+}
+
+/// Draws the frame rate, the last frame's timings, their averages, which
+/// optional systems the device's features left on and a graph of recent frames
+/// in the top left corner, over whatever is already in `frame`
+pub fn render_overlay(graphics: &GraphicsState, frame: &mut Frame) {
+    const TEXT_SIZE: f32 = 0.02;
+    const MARGIN: f32 = 0.02;
+    const BAR_WIDTH: f32 = 0.004;
+    const GRAPH_HEIGHT: f32 = 0.1;
+
+    let profiler = &graphics.profiler;
+    if !profiler.is_enabled() {
+        return;
+    }
+
+    // same projection the screens use, so the overlay scales along with them
+    graphics.update_projection();
+    let canvas = Rect::canvas(
+        graphics.sc_desc.width as f32,
+        graphics.sc_desc.height as f32,
+    );
+
+    let left = canvas.x + MARGIN;
+    let mut y = canvas.y + MARGIN;
+
+    // text
+    let mut text = graphics.scratch_mesh();
+    let (fps, interval) = profiler.frame_rate();
+    let cap = match profiler.cap.get() {
+        0 => "uncapped".to_string(),
+        cap => format!("cap {}", cap),
+    };
+    let rate = format!(
+        "{:>5.1} fps ({}) {:>6.2}ms {} ticks",
+        fps,
+        cap,
+        millis(interval),
+        profiler.ticks()
+    );
+    text::render_text(&mut text, &rate, left, y, TEXT_SIZE, [1.0, 1.0, 1.0]);
+    y += TEXT_SIZE;
+    for (name, time) in profiler.last_frame() {
+        let line = format!(
+            "{:<8}{:>6.2}ms avg {:>6.2}ms",
+            name,
+            millis(time),
+            millis(profiler.average(name))
+        );
+        text::render_text(&mut text, &line, left, y, TEXT_SIZE, [1.0, 1.0, 1.0]);
+        y += TEXT_SIZE;
+    }
+    for (name, on) in graphics.features.status() {
+        let (state, color) = if on {
+            ("on", [1.0, 1.0, 1.0])
+        } else {
+            ("off", [1.0, 0.4, 0.4])
+        };
+        let line = format!("{:<16}{}", name, state);
+        text::render_text(&mut text, &line, left, y, TEXT_SIZE, color);
+        y += TEXT_SIZE;
+    }
+
+    // graph, one bar per frame growing up from the bottom edge
+    let bottom = y + MARGIN + GRAPH_HEIGHT;
+    let mut mesh = graphics.scratch_mesh();
+    for (i, total) in profiler.frame_totals().into_iter().enumerate() {
+        let height = (total.as_secs_f32() / GRAPH_SCALE.as_secs_f32()).min(1.0) * GRAPH_HEIGHT;
+        let color = if total > FRAME_BUDGET {
+            [1.0, 0.2, 0.2]
+        } else {
+            [0.2, 1.0, 0.2]
+        };
+        let x = left + i as f32 * BAR_WIDTH;
+        let vertex = |dx: f32, dy: f32| Vertex {
+            position: [x + dx, bottom - dy, 0.0],
+            color: opaque(color),
+            tex_coords: [0.0, 0.0],
+        };
+        mesh.quad([
+            vertex(0.0, height),
+            vertex(0.0, 0.0),
+            vertex(BAR_WIDTH, height),
+            vertex(BAR_WIDTH, 0.0),
+        ]);
+    }
+    let mesh = graphics.inspect_geometry(&mesh, 0);
+
+    let mesh = graphics.upload(&graphics.geometry_mesh, &mesh);
+    let text_mesh = graphics.upload(&graphics.text_mesh, &text);
+
+    // render!
+    let mut command_buf = graphics
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("profiler_command_buf"),
+        });
+    {
+        let mut pass = frame.begin_pass(&mut command_buf);
+        pass.set_pipeline(graphics.geometry_pipeline());
+        pass.set_vertex_buffer(0, mesh.vertices());
+        pass.set_index_buffer(mesh.indices(), wgpu::IndexFormat::Uint32);
+        pass.set_bind_group(0, graphics.transform_bind_group(0), &[]);
+        pass.set_bind_group(1, graphics.text_bind_group(), &[]); // ignored by shader
+        pass.draw_indexed(0..mesh.index_count(), 0, 0..1);
+
+        if !graphics.wireframe {
+            pass.set_pipeline(&graphics.text_pipeline);
+            pass.set_vertex_buffer(0, text_mesh.vertices());
+            pass.set_index_buffer(text_mesh.indices(), wgpu::IndexFormat::Uint32);
+            pass.set_bind_group(0, graphics.transform_bind_group(0), &[]);
+            pass.set_bind_group(1, graphics.text_bind_group(), &[]);
+            pass.draw_indexed(0..text_mesh.index_count(), 0, 0..1);
+        }
+    }
+    graphics.queue.submit(std::iter::once(command_buf.finish()));
+}
@@ -0,0 +1,194 @@
+//! Saving what's on screen as a picture.
+//!
+//! The swap chain's images can't be copied from everywhere, so a screenshot is
+//! taken by drawing a frame a second time into a texture of the same size and
+//! format, then reading that back.
+
+use std::error::Error;
+use std::num::NonZeroU32;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::text::{self, Align, TextStyle};
+use super::{Frame, FrameTarget, GraphicsState};
+use crate::ui::layout::Rect;
+
+/// Where screenshots are written, relative to the working directory
+pub const SCREENSHOT_DIR: &str = "screenshots";
+
+const BYTES_PER_PIXEL: u32 = 4;
+
+/// A frame being drawn off screen, to be read back once it's done
+pub struct Capture {
+    texture: wgpu::Texture,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+}
+
+impl GraphicsState {
+    /// Starts a frame that's drawn off screen rather than to the window
+    pub fn begin_capture(&self) -> (Frame, Capture) {
+        let ((width, height), format) = (self.render_size(), self.sc_desc.format);
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("capture_texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let frame = self.frame_into(FrameTarget::Texture(view));
+        let capture = Capture {
+            texture,
+            width,
+            height,
+            format,
+        };
+        (frame, capture)
+    }
+}
+
+impl Capture {
+    /// Copies the finished frame back from the GPU, waiting for it
+    pub fn read(self, graphics: &GraphicsState) -> Result<image::RgbaImage, Box<dyn Error>> {
+        let swap_red_blue = match self.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => return Err(format!("can't read back {:?} frames", format).into()),
+        };
+
+        // rows in a copy have to start on an alignment, so each one is padded
+        let row_bytes = self.width * BYTES_PER_PIXEL;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_row_bytes = (row_bytes + align - 1) / align * align;
+
+        let buffer = graphics.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("capture_buffer"),
+            size: (padded_row_bytes * self.height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = graphics
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("capture_command_buf"),
+            });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+        );
+        graphics.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let mapped = slice.map_async(wgpu::MapMode::Read);
+        graphics.device.poll(wgpu::Maintain::Wait);
+        futures::executor::block_on(mapped).map_err(|_| "couldn't map the frame for reading")?;
+
+        let mut pixels = Vec::with_capacity((row_bytes * self.height) as usize);
+        for row in slice.get_mapped_range().chunks(padded_row_bytes as usize) {
+            pixels.extend_from_slice(&row[..row_bytes as usize]);
+        }
+        buffer.unmap();
+        if swap_red_blue {
+            for pixel in pixels.chunks_mut(BYTES_PER_PIXEL as usize) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        image::RgbaImage::from_raw(self.width, self.height, pixels)
+            .ok_or_else(|| "frame came back the wrong size".into())
+    }
+}
+
+/// Writes `image` to a new file named after the time, returning its path
+pub fn save(image: &image::RgbaImage) -> Result<PathBuf, Box<dyn Error>> {
+    std::fs::create_dir_all(SCREENSHOT_DIR)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+    let path = PathBuf::from(SCREENSHOT_DIR).join(format!(
+        "tetrs-{}-{:03}.png",
+        now.as_secs(),
+        now.subsec_millis()
+    ));
+    image.save(&path)?;
+    Ok(path)
+}
+
+/// Draws `message` in the bottom left corner, over whatever is in `frame`
+pub fn render_toast(graphics: &GraphicsState, frame: &mut Frame, message: &str) {
+    render_corner(graphics, frame, message, Align::Left);
+}
+
+/// Draws `message` in the bottom right corner, for a state that lasts, like
+/// the sound being muted
+pub fn render_badge(graphics: &GraphicsState, frame: &mut Frame, message: &str) {
+    render_corner(graphics, frame, message, Align::Right);
+}
+
+/// Draws `message` in the bottom corner on the side it's `align`ed to
+fn render_corner(graphics: &GraphicsState, frame: &mut Frame, message: &str, align: Align) {
+    const TEXT_SIZE: f32 = 0.03;
+    const MARGIN: f32 = 0.02;
+
+    // text isn't drawn in wireframe
+    if graphics.wireframe {
+        return;
+    }
+
+    graphics.update_projection();
+    let canvas = Rect::canvas(
+        graphics.sc_desc.width as f32,
+        graphics.sc_desc.height as f32,
+    );
+    let mut text = graphics.scratch_mesh();
+    let x = match align {
+        Align::Right => canvas.x + canvas.width - MARGIN,
+        _ => canvas.x + MARGIN,
+    };
+    text::render_styled(
+        &mut text,
+        message,
+        x,
+        canvas.y + canvas.height - MARGIN - TEXT_SIZE,
+        &TextStyle::new(TEXT_SIZE, [1.0, 1.0, 1.0]).align(align),
+    );
+    let mesh = graphics.upload(&graphics.text_mesh, &text);
+
+    let mut command_buf = graphics
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("toast_command_buf"),
+        });
+    {
+        let mut pass = frame.begin_pass(&mut command_buf);
+        pass.set_pipeline(&graphics.text_pipeline);
+        pass.set_vertex_buffer(0, mesh.vertices());
+        pass.set_index_buffer(mesh.indices(), wgpu::IndexFormat::Uint32);
+        pass.set_bind_group(0, graphics.transform_bind_group(0), &[]);
+        pass.set_bind_group(1, graphics.text_bind_group(), &[]);
+        pass.draw_indexed(0..mesh.index_count(), 0, 0..1);
+    }
+    graphics.queue.submit(std::iter::once(command_buf.finish()));
+}
@@ -1,16 +1,21 @@
-use std::path::Path;
+use std::borrow::Cow;
 
-pub fn create_shader(
-    device: &wgpu::Device,
-    path: impl AsRef<Path>,
-) -> Result<wgpu::ShaderModule, Box<dyn std::error::Error>> {
-    let data = std::fs::read(path.as_ref())?;
+use super::{assets::Asset, GraphicsInitError};
 
-    let module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+/// Compiles the WGSL in `source`, naming the module `label`
+pub fn create_shader(device: &wgpu::Device, label: &str, source: &str) -> wgpu::ShaderModule {
+    device.create_shader_module(&wgpu::ShaderModuleDescriptor {
         flags: wgpu::ShaderFlags::all(),
-        label: Some(&path.as_ref().to_string_lossy()),
-        source: wgpu::util::make_spirv(&data),
-    });
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(source)),
+    })
+}
 
-    Ok(module)
+/// Compiles the WGSL in `asset`, from whichever copy of it is found, naming
+/// the module after its path
+pub fn load(device: &wgpu::Device, asset: &Asset) -> Result<wgpu::ShaderModule, GraphicsInitError> {
+    let source = asset
+        .load_text()
+        .map_err(|e| GraphicsInitError::asset(asset, &e))?;
+    Ok(create_shader(device, asset.path, &source))
 }
@@ -1,63 +1,523 @@
-use super::Vertex;
+use super::atlas::{Atlas, Region, Sprite, SpriteBatch};
+use super::font;
+use super::mesh::MeshBuilder;
+use super::opaque;
 
-const TEXT_IMAGE_COLUMNS: i32 = 16;
-const TEXT_IMAGE_ROWS: i32 = 8;
-const TEXT_CHARACTERS: &str =
-    "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ 0123456789!?@#$%\"'&()*+,-./:;<>=[]{}|\\";
+/// Ways an outline is pushed out from the glyphs, in glyph pixels
+const OUTLINE_DIRECTIONS: [(f32, f32); 8] = [
+    (-1.0, -1.0),
+    (0.0, -1.0),
+    (1.0, -1.0),
+    (-1.0, 0.0),
+    (1.0, 0.0),
+    (-1.0, 1.0),
+    (0.0, 1.0),
+    (1.0, 1.0),
+];
 
-pub fn render_text(
+/// Which side of the x it's drawn at each line of a block lines up against
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Which edge of a block of text the y it's drawn at is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Anchor {
+    Top,
+    Middle,
+    Bottom,
+}
+
+/// Where a glyph is drawn, relative to where it starts along the line, and
+/// where in the atlas it's taken from. Lengths on screen are in glyph cells,
+/// `size` wide and `size / 2` tall
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlyphQuad {
+    pub offset: [f32; 2],
+    pub extent: [f32; 2],
+
+    pub region: Region,
+
+    /// How far along the line the next glyph starts
+    pub advance: f32,
+}
+
+/// How a block of text is drawn
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextStyle {
+    /// Width of a glyph. Glyphs are half as tall as they are wide
+    pub size: f32,
+
+    pub color: [f32; 3],
+
+    /// How far below the last line each line starts
+    pub line_height: f32,
+
+    pub align: Align,
+    pub anchor: Anchor,
+
+    /// Copy of the text drawn behind it, moved by the offset, if there is one
+    pub shadow: Option<([f32; 2], [f32; 3])>,
+
+    /// Colour of a glyph pixel wide rim around the text, if it has one
+    pub outline: Option<[f32; 3]>,
+
+    /// Whether every glyph takes the same room, for text lined up in columns
+    pub monospace: bool,
+}
+
+impl TextStyle {
+    /// Text `size` wide in `color`, its lines stacked right on top of each
+    /// other and hanging down and right from where it's drawn
+    pub fn new(size: f32, color: [f32; 3]) -> Self {
+        Self {
+            size,
+            color,
+            line_height: size / 2.0,
+            align: Align::Left,
+            anchor: Anchor::Top,
+            shadow: None,
+            outline: None,
+            monospace: false,
+        }
+    }
+
+    pub fn line_height(self, line_height: f32) -> Self {
+        Self {
+            line_height,
+            ..self
+        }
+    }
+
+    pub fn align(self, align: Align) -> Self {
+        Self { align, ..self }
+    }
+
+    pub fn anchor(self, anchor: Anchor) -> Self {
+        Self { anchor, ..self }
+    }
+
+    /// Gives the text a shadow in `color`, a glyph pixel down and right of it
+    pub fn shadow(self, color: [f32; 3]) -> Self {
+        Self {
+            shadow: Some((self.glyph_pixel(), color)),
+            ..self
+        }
+    }
+
+    pub fn outline(self, color: [f32; 3]) -> Self {
+        Self {
+            outline: Some(color),
+            ..self
+        }
+    }
+
+    pub fn monospace(self) -> Self {
+        Self {
+            monospace: true,
+            ..self
+        }
+    }
+
+    /// Width and height of a pixel of the font at this size
+    fn glyph_pixel(&self) -> [f32; 2] {
+        let width = self.size / font::GLYPH_SIZE as f32;
+        [width, width / 2.0]
+    }
+
+    /// Width of the longest line of `text` and height of all of them together
+    pub fn measure(&self, text: &str) -> (f32, f32) {
+        let width = text
+            .lines()
+            .map(|line| self.line_width(line))
+            .fold(0.0, f32::max);
+        (width, self.block_height(text.lines().count()))
+    }
+
+    /// Where the glyph for `c` goes, from the TrueType font if one is in use
+    fn glyph_quad(&self, c: char) -> GlyphQuad {
+        #[cfg(feature = "ttf")]
+        if let Some(glyph) = super::ttf::with_active(|font| font.glyph(c)) {
+            return if self.monospace {
+                // centred in a whole cell
+                GlyphQuad {
+                    offset: [
+                        glyph.offset[0] + (1.0 - glyph.advance) / 2.0,
+                        glyph.offset[1],
+                    ],
+                    advance: 1.0,
+                    ..glyph
+                }
+            } else {
+                glyph
+            };
+        }
+
+        let (first, columns) = if self.monospace {
+            (0, font::GLYPH_SIZE)
+        } else {
+            font::glyph_span(c)
+        };
+        let advance = if self.monospace {
+            font::GLYPH_SIZE
+        } else {
+            columns + font::GLYPH_GAP
+        };
+        let columns_per_cell = font::GLYPH_SIZE as f32;
+        let tile = Atlas::grid(font::ATLAS_COLUMNS, font::ATLAS_ROWS).tile(font::glyph_tile(c));
+        GlyphQuad {
+            offset: [0.0, 0.0],
+            extent: [columns as f32 / columns_per_cell, 1.0],
+            region: tile.columns(
+                first as f32 / columns_per_cell,
+                columns as f32 / columns_per_cell,
+            ),
+            advance: advance as f32 / columns_per_cell,
+        }
+    }
+
+    /// Distance from the start of `line` to the right edge of its last glyph
+    fn line_width(&self, line: &str) -> f32 {
+        let mut pen = 0.0;
+        let mut width = 0.0;
+        for c in line.chars() {
+            let glyph = self.glyph_quad(c);
+            width = pen + glyph.offset[0] + glyph.extent[0];
+            pen += glyph.advance;
+        }
+        width * self.size
+    }
+
+    fn block_height(&self, lines: usize) -> f32 {
+        match lines {
+            0 => 0.0,
+            n => (n - 1) as f32 * self.line_height + self.size / 2.0,
+        }
+    }
+}
+
+/// Width and height `text` takes up drawn `size` wide, its lines stacked right
+/// on top of each other
+pub fn measure_text(text: &str, size: f32) -> (f32, f32) {
+    TextStyle::new(size, [0.0; 3]).measure(text)
+}
+
+/// Breaks `text` into lines no wider than `max_width` when drawn in `style`.
+/// Lines break at spaces where they can, and words too long for a line of
+/// their own are cut wherever they run out of room. Every line fits at least
+/// one glyph, however narrow `max_width` is
+pub fn wrap_text(text: &str, style: &TextStyle, max_width: f32) -> Vec<String> {
+    let fits = |line: &str| style.line_width(line) <= max_width;
+    let mut lines = Vec::new();
+
+    for paragraph in text.lines() {
+        let mut line = String::new();
+
+        for word in paragraph.split(' ').filter(|word| !word.is_empty()) {
+            if !line.is_empty() {
+                let joined = format!("{} {}", line, word);
+                if fits(&joined) {
+                    line = joined;
+                    continue;
+                }
+                lines.push(std::mem::take(&mut line));
+            }
+
+            // what's left of a long word after cutting starts the next line
+            for c in word.chars() {
+                line.push(c);
+                if !fits(&line) && line.chars().count() > 1 {
+                    line.pop();
+                    lines.push(std::mem::replace(&mut line, c.to_string()));
+                }
+            }
+        }
+
+        // blank lines are kept, to space out paragraphs
+        lines.push(line);
+    }
+    lines
+}
+
+/// Adds `text` to `mesh` with its top left corner at (`x`, `y`), as
+/// [`render_styled`] does with a [`TextStyle::new`] style
+pub fn render_text(mesh: &mut MeshBuilder, text: &str, x: f32, y: f32, size: f32, color: [f32; 3]) {
+    render_styled(mesh, text, x, y, &TextStyle::new(size, color));
+}
+
+/// Adds `text` to `mesh` as [`render_text`] does, every glyph taking the same
+/// room so text padded to a width lines up in columns
+pub fn render_text_mono(
+    mesh: &mut MeshBuilder,
     text: &str,
     x: f32,
     y: f32,
     size: f32,
-    base_idx: usize,
     color: [f32; 3],
-) -> (Vec<Vertex>, Vec<u16>) {
-    let mut vertices = Vec::new();
-    let mut indices = Vec::new();
-
-    for (i, char) in text.chars().enumerate() {
-        let index = TEXT_CHARACTERS
-            .find(char)
-            .unwrap_or((TEXT_IMAGE_COLUMNS * TEXT_IMAGE_ROWS - 1) as usize)
-            as i32;
-        let char_x = (index % TEXT_IMAGE_COLUMNS) as f32 / TEXT_IMAGE_COLUMNS as f32;
-        let char_y = (index / TEXT_IMAGE_COLUMNS) as f32 / TEXT_IMAGE_ROWS as f32;
-        let tile_size_x = 1.0 / TEXT_IMAGE_COLUMNS as f32;
-        let tile_size_y = 1.0 / TEXT_IMAGE_ROWS as f32;
-
-        let base_idx = (vertices.len() + base_idx) as u16;
-        vertices.extend_from_slice(&[
-            Vertex {
-                position: [x + i as f32 * size, y, 0.0],
-                color,
-                tex_coords: [char_x, char_y],
-            },
-            Vertex {
-                position: [x + size + i as f32 * size, y, 0.0],
-                color,
-                tex_coords: [char_x + tile_size_x, char_y],
-            },
-            Vertex {
-                position: [x + i as f32 * size, y + size / 2.0, 0.0],
-                color,
-                tex_coords: [char_x, char_y + tile_size_y],
-            },
-            Vertex {
-                position: [x + size + i as f32 * size, y + size / 2.0, 0.0],
-                color,
-                tex_coords: [char_x + tile_size_x, char_y + tile_size_y],
-            },
-        ]);
-        indices.extend_from_slice(&[
-            base_idx,
-            base_idx + 2,
-            base_idx + 1,
-            base_idx + 1,
-            base_idx + 2,
-            base_idx + 3,
-        ]);
-    }
-
-    (vertices, indices)
+) {
+    render_styled(mesh, text, x, y, &TextStyle::new(size, color).monospace());
+}
+
+/// Adds `text` to `mesh`, placed against (`x`, `y`) as `style` aligns and
+/// anchors it. Lines end at every `\n` or `\r\n`, and each is aligned on its
+/// own. A newline at the very end doesn't start another line
+pub fn render_styled(mesh: &mut MeshBuilder, text: &str, x: f32, y: f32, style: &TextStyle) {
+    let height = style.block_height(text.lines().count());
+    let top = match style.anchor {
+        Anchor::Top => y,
+        Anchor::Middle => y - height / 2.0,
+        Anchor::Bottom => y - height,
+    };
+
+    let block = |mesh: &mut MeshBuilder, [dx, dy]: [f32; 2], color: [f32; 3]| {
+        for (row, line) in text.lines().enumerate() {
+            let width = style.line_width(line);
+            let left = match style.align {
+                Align::Left => x,
+                Align::Center => x - width / 2.0,
+                Align::Right => x - width,
+            };
+            let line_top = top + row as f32 * style.line_height;
+            render_line(mesh, line, left + dx, line_top + dy, style, opaque(color));
+        }
+    };
+
+    // the whole block's shadow and outline go in before any of its glyphs, so
+    // they're drawn under every character and not just their own
+    if let Some((offset, color)) = style.shadow {
+        block(mesh, offset, color);
+    }
+    if let Some(color) = style.outline {
+        let [pixel_x, pixel_y] = style.glyph_pixel();
+        for &(dx, dy) in &OUTLINE_DIRECTIONS {
+            block(mesh, [dx * pixel_x, dy * pixel_y], color);
+        }
+    }
+    block(mesh, [0.0, 0.0], style.color);
+}
+
+/// Adds `text` to `mesh` as [`render_styled`] does, first wrapping it as
+/// [`wrap_text`] does to fit in `max_width`. Returns the height of the lines
+/// drawn, for stacking whatever goes under them
+pub fn render_text_wrapped(
+    mesh: &mut MeshBuilder,
+    text: &str,
+    x: f32,
+    y: f32,
+    max_width: f32,
+    style: &TextStyle,
+) -> f32 {
+    let lines = wrap_text(text, style, max_width);
+    render_styled(mesh, &lines.join("\n"), x, y, style);
+    style.block_height(lines.len())
+}
+
+fn render_line(
+    mesh: &mut MeshBuilder,
+    line: &str,
+    x: f32,
+    y: f32,
+    style: &TextStyle,
+    color: [f32; 4],
+) {
+    let cell = [style.size, style.size / 2.0];
+
+    let mut batch = SpriteBatch::new(mesh);
+    let mut pen = x;
+    for char in line.chars() {
+        let glyph = style.glyph_quad(char);
+        batch.draw(&Sprite::new(
+            glyph.region,
+            [
+                pen + glyph.offset[0] * cell[0],
+                y + glyph.offset[1] * cell[1],
+            ],
+            [glyph.extent[0] * cell[0], glyph.extent[1] * cell[1]],
+            color,
+        ));
+        pen += glyph.advance * cell[0];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Top left corner of each glyph drawn into `mesh`
+    fn corners(mesh: &MeshBuilder) -> Vec<[f32; 2]> {
+        mesh.vertices()
+            .chunks(4)
+            .map(|quad| [quad[0].position[0], quad[0].position[1]])
+            .collect()
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn a_newline_starts_the_next_line_under_the_first() {
+        let mut mesh = MeshBuilder::new();
+        render_text(&mut mesh, "AB\nC", 0.5, 0.25, 0.1, [1.0; 3]);
+        let glyphs = corners(&mesh);
+        assert_eq!(glyphs.len(), 3);
+
+        let [a, b, c] = [glyphs[0], glyphs[1], glyphs[2]];
+        assert!(close(a[0], 0.5) && close(a[1], 0.25));
+        assert!(b[0] > a[0] && close(b[1], 0.25));
+        // back to the start, a glyph height down
+        assert!(close(c[0], 0.5) && close(c[1], 0.25 + 0.05), "{:?}", c);
+    }
+
+    #[test]
+    fn line_height_spaces_out_the_lines() {
+        let mut mesh = MeshBuilder::new();
+        let style = TextStyle::new(0.1, [1.0; 3]).line_height(0.2);
+        render_styled(&mut mesh, "A\nB\nC", 0.0, 0.0, &style);
+        let rows: Vec<f32> = corners(&mesh).iter().map(|c| c[1]).collect();
+        assert!(close(rows[0], 0.0) && close(rows[1], 0.2) && close(rows[2], 0.4));
+    }
+
+    #[test]
+    fn blank_lines_take_room_and_a_trailing_newline_doesnt() {
+        let mut mesh = MeshBuilder::new();
+        render_text(&mut mesh, "A\n\nB\n", 0.0, 0.0, 0.1, [1.0; 3]);
+        let glyphs = corners(&mesh);
+        assert_eq!(glyphs.len(), 2);
+        assert!(close(glyphs[1][1], 0.1), "{:?}", glyphs);
+        assert_eq!(measure_text("A\n\nB\n", 0.1), measure_text("A\n\nB", 0.1));
+    }
+
+    #[test]
+    fn windows_line_endings_draw_like_unix_ones() {
+        let mut unix = MeshBuilder::new();
+        let mut windows = MeshBuilder::new();
+        render_text(&mut unix, "AB\nC", 0.0, 0.0, 0.1, [1.0; 3]);
+        render_text(&mut windows, "AB\r\nC", 0.0, 0.0, 0.1, [1.0; 3]);
+        assert_eq!(corners(&unix), corners(&windows));
+    }
+
+    #[test]
+    fn measuring_nothing_is_nothing() {
+        assert_eq!(measure_text("", 0.1), (0.0, 0.0));
+    }
+
+    #[test]
+    fn a_line_is_as_tall_as_a_glyph() {
+        let (_, height) = measure_text("Hello", 0.1);
+        assert!(close(height, 0.05));
+        let (_, height) = measure_text("Hello\nthere\n!", 0.1);
+        assert!(close(height, 0.15));
+    }
+
+    #[test]
+    fn monospace_glyphs_are_a_cell_each() {
+        let style = TextStyle::new(0.1, [1.0; 3]).monospace();
+        let (width, _) = style.measure("i w!");
+        assert!(close(width, 0.4), "{}", width);
+    }
+
+    #[test]
+    fn the_widest_line_sets_the_width() {
+        let (wide, _) = measure_text("WWWW", 0.1);
+        let (narrow, _) = measure_text("ii", 0.1);
+        let (block, _) = measure_text("ii\nWWWW\nii", 0.1);
+        assert!(narrow < wide);
+        assert!(close(block, wide));
+    }
+
+    #[test]
+    fn width_grows_with_size_and_length() {
+        let (small, _) = measure_text("Score", 0.1);
+        let (large, _) = measure_text("Score", 0.2);
+        assert!(close(large, small * 2.0));
+        assert!(measure_text("Scores", 0.1).0 > small);
+    }
+
+    #[test]
+    fn aligned_text_lines_up_against_its_x() {
+        let size = 0.1;
+        let (width, height) = measure_text("Hi", size);
+        let left_edge = |align, anchor| {
+            let mut mesh = MeshBuilder::new();
+            let style = TextStyle::new(size, [1.0; 3]).align(align).anchor(anchor);
+            render_styled(&mut mesh, "Hi", 0.5, 0.5, &style);
+            corners(&mesh)[0]
+        };
+        let left = left_edge(Align::Left, Anchor::Top);
+        assert!(close(left[0], 0.5) && close(left[1], 0.5));
+        let centre = left_edge(Align::Center, Anchor::Middle);
+        assert!(close(centre[0], 0.5 - width / 2.0) && close(centre[1], 0.5 - height / 2.0));
+        let right = left_edge(Align::Right, Anchor::Bottom);
+        assert!(close(right[0], 0.5 - width) && close(right[1], 0.5 - height));
+    }
+
+    #[test]
+    fn each_line_is_centred_on_its_own() {
+        let mut mesh = MeshBuilder::new();
+        let style = TextStyle::new(0.1, [1.0; 3]).align(Align::Center);
+        render_styled(&mut mesh, "WWWW\nW", 0.5, 0.0, &style);
+        let glyphs = corners(&mesh);
+        let (short, _) = measure_text("W", 0.1);
+        assert!(close(glyphs[4][0], 0.5 - short / 2.0), "{:?}", glyphs[4]);
+    }
+
+    /// Monospace text a tenth wide, so each glyph is a tenth
+    fn mono() -> TextStyle {
+        TextStyle::new(0.1, [1.0; 3]).monospace()
+    }
+
+    #[test]
+    fn wrapping_breaks_at_spaces() {
+        assert_eq!(
+            wrap_text("the quick brown fox", &mono(), 1.05),
+            vec!["the quick", "brown fox"]
+        );
+        assert_eq!(
+            wrap_text("the quick brown fox", &mono(), 0.55),
+            vec!["the", "quick", "brown", "fox"]
+        );
+    }
+
+    #[test]
+    fn wrapping_cuts_words_too_long_for_a_line() {
+        assert_eq!(
+            wrap_text("go abcdefghijkl", &mono(), 0.55),
+            vec!["go", "abcde", "fghij", "kl"]
+        );
+    }
+
+    #[test]
+    fn wrapping_narrower_than_a_glyph_fits_one_a_line() {
+        assert_eq!(wrap_text("ab c", &mono(), 0.01), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn wrapping_keeps_blank_lines_and_drops_extra_spaces() {
+        assert_eq!(
+            wrap_text("ab  cd\n\nef", &mono(), 1.05),
+            vec!["ab cd", "", "ef"]
+        );
+        assert!(wrap_text("", &mono(), 1.0).is_empty());
+    }
+
+    #[test]
+    fn wrapped_text_gives_back_the_height_it_took() {
+        let mut mesh = MeshBuilder::new();
+        let style = mono().line_height(0.1);
+        let height = render_text_wrapped(&mut mesh, "one two three", 0.0, 0.0, 0.55, &style);
+        // three lines, the last only a glyph tall
+        assert!(close(height, 0.25), "{}", height);
+        assert_eq!(corners(&mesh).len(), "onetwothree".len());
+
+        let mut mesh = MeshBuilder::new();
+        assert_eq!(
+            render_text_wrapped(&mut mesh, "", 0.0, 0.0, 0.55, &style),
+            0.0
+        );
+        assert!(mesh.is_empty());
+    }
 }
@@ -0,0 +1,430 @@
+//! Colours everything is drawn in, swapped out as a whole.
+//!
+//! A few themes are built in, and more are read from TOML files in
+//! [`THEME_DIR`], each named after its file. A file only needs the colours it
+//! changes; the rest are taken from the classic look. Colours are red, green
+//! and blue from 0 to 1.
+//!
+//! Like the old consoles, the pieces change colour with every level. A theme
+//! lists a [`PaletteShift`] for each level from the first, going round again
+//! after the last, and [`Theme::palette_for_level`] applies it to `pieces`.
+
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+
+use crate::piece::Color;
+
+/// Where themes besides the built in ones are looked for
+pub const THEME_DIR: &str = "themes";
+
+/// Theme used when none is picked, or the one picked can't be found
+pub const DEFAULT_THEME: &str = "Classic";
+
+/// Theme the colourblind setting switches to, whatever theme is picked
+pub const COLORBLIND_THEME: &str = "Colorblind";
+
+macro_rules! rgb {
+    ($r:expr, $g:expr, $b:expr) => {
+        [
+            ($r as f32) / 255.0,
+            ($g as f32) / 255.0,
+            ($b as f32) / 255.0,
+        ]
+    };
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    /// What the screen is cleared to before anything is drawn
+    pub background: [f32; 3],
+
+    /// Behind the field and the next queue, and darkening along their walls
+    pub well: [f32; 3],
+    pub shadow: [f32; 3],
+
+    /// Lines between the field's cells
+    pub grid: [f32; 3],
+
+    /// Frame around the field and the next queue
+    pub border: [f32; 3],
+
+    /// Text that's selected or otherwise wants attention, and the rest
+    pub active_text: [f32; 3],
+    pub inactive_text: [f32; 3],
+
+    /// Text standing out from the rest, like the countdown's last word
+    pub highlight: [f32; 3],
+
+    /// Field border as it pulses with gravity, at its brightest
+    pub pulse: [f32; 3],
+
+    /// Piece flashed for being placed with a finesse fault
+    pub fault: [f32; 3],
+
+    pub pieces: PieceColors,
+
+    /// How `pieces` change on each level, starting from level 1. Empty to keep
+    /// them the same throughout
+    pub level_shifts: Vec<PaletteShift>,
+
+    /// How far cells are shaded towards white in their top left corner and
+    /// towards black in their bottom right, from 0 for flat cells
+    pub bevel: f32,
+
+    /// Whether cells are marked with their colour's pattern, so pieces can be
+    /// told apart without seeing colour
+    pub patterns: bool,
+}
+
+/// Colour of each kind of mino
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PieceColors {
+    pub red: [f32; 3],
+    pub orange: [f32; 3],
+    pub yellow: [f32; 3],
+    pub green: [f32; 3],
+    pub blue: [f32; 3],
+    pub purple: [f32; 3],
+    pub cyan: [f32; 3],
+    /// Garbage and other cells no piece left behind
+    pub grey: [f32; 3],
+}
+
+/// A change to every piece colour but grey
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PaletteShift {
+    /// How far round the colour wheel to turn, in degrees
+    pub hue: f32,
+
+    /// What to multiply the colour by, 1 to leave it as it is
+    pub brightness: f32,
+}
+
+impl PaletteShift {
+    /// `color` turned round the wheel keeping its luminance, then brightened
+    pub fn apply(self, [r, g, b]: [f32; 3]) -> [f32; 3] {
+        let (sin, cos) = self.hue.to_radians().sin_cos();
+        let shifted = [
+            (0.213 + 0.787 * cos - 0.213 * sin) * r
+                + (0.715 - 0.715 * cos - 0.715 * sin) * g
+                + (0.072 - 0.072 * cos + 0.928 * sin) * b,
+            (0.213 - 0.213 * cos + 0.143 * sin) * r
+                + (0.715 + 0.285 * cos + 0.140 * sin) * g
+                + (0.072 - 0.072 * cos - 0.283 * sin) * b,
+            (0.213 - 0.213 * cos - 0.787 * sin) * r
+                + (0.715 - 0.715 * cos + 0.715 * sin) * g
+                + (0.072 + 0.928 * cos + 0.072 * sin) * b,
+        ];
+        let channel = |c: f32| (c * self.brightness).clamp(0.0, 1.0);
+        [
+            channel(shifted[0]),
+            channel(shifted[1]),
+            channel(shifted[2]),
+        ]
+    }
+}
+
+/// Ten levels of colour, then round again. Every other level is a little
+/// darker so neighbouring levels don't look alike
+fn level_shifts() -> Vec<PaletteShift> {
+    [
+        (0.0, 1.0),
+        (40.0, 0.9),
+        (80.0, 1.0),
+        (120.0, 0.9),
+        (160.0, 1.0),
+        (200.0, 0.9),
+        (240.0, 1.0),
+        (280.0, 0.9),
+        (320.0, 1.0),
+        (0.0, 0.8),
+    ]
+    .iter()
+    .map(|&(hue, brightness)| PaletteShift { hue, brightness })
+    .collect()
+}
+
+impl PieceColors {
+    pub fn get(&self, color: Color) -> [f32; 3] {
+        match color {
+            Color::Red => self.red,
+            Color::Orange => self.orange,
+            Color::Yellow => self.yellow,
+            Color::Green => self.green,
+            Color::Blue => self.blue,
+            Color::Purple => self.purple,
+            Color::Cyan => self.cyan,
+            Color::Grey => self.grey,
+        }
+    }
+
+    /// Each colour of these and its counterpart in `other` put through `f`
+    fn zip(&self, other: &Self, f: impl Fn([f32; 3], [f32; 3]) -> [f32; 3]) -> Self {
+        Self {
+            red: f(self.red, other.red),
+            orange: f(self.orange, other.orange),
+            yellow: f(self.yellow, other.yellow),
+            green: f(self.green, other.green),
+            blue: f(self.blue, other.blue),
+            purple: f(self.purple, other.purple),
+            cyan: f(self.cyan, other.cyan),
+            grey: f(self.grey, other.grey),
+        }
+    }
+
+    /// Every colour `amount` of the way to its counterpart in `to`
+    pub fn mix(&self, to: &Self, amount: f32) -> Self {
+        self.zip(to, |from, to| mix(from, to, amount))
+    }
+
+    /// Every colour but grey changed by `shift`. Garbage looks the same on
+    /// every level
+    pub fn shifted(&self, shift: PaletteShift) -> Self {
+        Self {
+            grey: self.grey,
+            ..self.zip(self, |color, _| shift.apply(color))
+        }
+    }
+}
+
+impl Default for PieceColors {
+    fn default() -> Self {
+        Self {
+            red: rgb!(221, 55, 55),
+            orange: rgb!(255, 115, 25),
+            yellow: rgb!(255, 215, 5),
+            green: rgb!(30, 135, 30),
+            blue: rgb!(0, 90, 255),
+            purple: rgb!(110, 10, 225),
+            cyan: rgb!(15, 195, 215),
+            grey: rgb!(110, 110, 120),
+        }
+    }
+}
+
+/// The classic look
+impl Default for Theme {
+    fn default() -> Self {
+        let pieces = PieceColors::default();
+        Self {
+            background: [0.0, 0.0125, 0.05],
+            well: [0.0, 0.004, 0.016],
+            shadow: [0.0, 0.0, 0.0],
+            grid: [0.0, 0.00625, 0.025],
+            border: rgb!(70, 80, 110),
+            active_text: [1.0, 1.0, 1.0],
+            inactive_text: [0.5, 0.5, 0.5],
+            highlight: pieces.yellow,
+            pulse: [1.0, 1.0, 1.0],
+            fault: pieces.red,
+            pieces,
+            level_shifts: level_shifts(),
+            bevel: 0.3,
+            patterns: false,
+        }
+    }
+}
+
+impl Theme {
+    /// Black and bright, for telling pieces apart at a glance
+    fn contrast() -> Self {
+        let pieces = PieceColors {
+            red: rgb!(255, 40, 40),
+            orange: rgb!(255, 140, 0),
+            yellow: rgb!(255, 255, 0),
+            green: rgb!(0, 230, 0),
+            blue: rgb!(40, 110, 255),
+            purple: rgb!(200, 60, 255),
+            cyan: rgb!(0, 255, 255),
+            grey: rgb!(170, 170, 170),
+        };
+        Self {
+            background: [0.0, 0.0, 0.0],
+            well: [0.0, 0.0, 0.0],
+            shadow: [0.0, 0.0, 0.0],
+            grid: rgb!(40, 40, 40),
+            border: rgb!(200, 200, 200),
+            active_text: [1.0, 1.0, 1.0],
+            inactive_text: rgb!(160, 160, 160),
+            highlight: pieces.yellow,
+            pulse: [1.0, 1.0, 1.0],
+            fault: pieces.red,
+            pieces,
+            // the colours are picked to stand apart, so they stay put
+            level_shifts: Vec::new(),
+            bevel: 0.2,
+            patterns: false,
+        }
+    }
+
+    /// Colours told apart with any kind of colour blindness, with every piece
+    /// marked by a pattern too (Okabe and Ito's palette)
+    fn colorblind() -> Self {
+        let pieces = PieceColors {
+            red: rgb!(213, 94, 0),
+            orange: rgb!(230, 159, 0),
+            yellow: rgb!(240, 228, 66),
+            green: rgb!(0, 158, 115),
+            blue: rgb!(0, 114, 178),
+            purple: rgb!(204, 121, 167),
+            cyan: rgb!(86, 180, 233),
+            grey: rgb!(150, 150, 150),
+        };
+        Self {
+            background: [0.0, 0.0, 0.0],
+            well: [0.0, 0.0, 0.0],
+            shadow: [0.0, 0.0, 0.0],
+            grid: rgb!(40, 40, 40),
+            border: rgb!(160, 160, 160),
+            active_text: [1.0, 1.0, 1.0],
+            inactive_text: rgb!(160, 160, 160),
+            highlight: pieces.yellow,
+            pulse: [1.0, 1.0, 1.0],
+            fault: [1.0, 1.0, 1.0],
+            pieces,
+            // turning the hue would undo what the palette was chosen for
+            level_shifts: Vec::new(),
+            bevel: 0.2,
+            patterns: true,
+        }
+    }
+
+    /// Soft colours on a pale background
+    fn pastel() -> Self {
+        let pieces = PieceColors {
+            red: rgb!(240, 150, 160),
+            orange: rgb!(245, 190, 140),
+            yellow: rgb!(240, 225, 140),
+            green: rgb!(160, 215, 165),
+            blue: rgb!(150, 180, 235),
+            purple: rgb!(195, 165, 230),
+            cyan: rgb!(150, 220, 225),
+            grey: rgb!(175, 170, 180),
+        };
+        Self {
+            background: rgb!(250, 244, 238),
+            well: rgb!(243, 236, 229),
+            shadow: rgb!(222, 212, 204),
+            grid: rgb!(235, 226, 218),
+            border: rgb!(165, 155, 170),
+            active_text: rgb!(80, 70, 90),
+            inactive_text: rgb!(165, 155, 170),
+            highlight: rgb!(215, 120, 150),
+            pulse: rgb!(80, 70, 90),
+            fault: pieces.red,
+            pieces,
+            level_shifts: level_shifts(),
+            bevel: 0.15,
+            patterns: false,
+        }
+    }
+
+    /// Colour of `color` outside of a run, where there's no level to go by
+    pub fn piece(&self, color: Color) -> [f32; 3] {
+        self.pieces.get(color)
+    }
+
+    /// Piece colours on `level`, counting from 1
+    pub fn palette_for_level(&self, level: u32) -> PieceColors {
+        if self.level_shifts.is_empty() {
+            return self.pieces.clone();
+        }
+        let index = level.saturating_sub(1) as usize % self.level_shifts.len();
+        self.pieces.shifted(self.level_shifts[index])
+    }
+
+    /// `color` as shaded at (`u`, `v`) in a cell, each going from 0 at its top
+    /// left to 1 at its bottom right. It's lightest in the top left corner,
+    /// darkest in the bottom right and unchanged on the diagonal between, so
+    /// shading just the corners and blending across gives the same result
+    pub fn bevel(&self, color: [f32; 3], u: f32, v: f32) -> [f32; 3] {
+        let towards_light = 1.0 - u - v;
+        if towards_light >= 0.0 {
+            mix(color, [1.0, 1.0, 1.0], self.bevel * towards_light)
+        } else {
+            mix(color, [0.0, 0.0, 0.0], -self.bevel * towards_light)
+        }
+    }
+
+    /// `color` faded `amount` of the way into the background
+    pub fn fade(&self, color: [f32; 3], amount: f32) -> [f32; 3] {
+        mix(color, self.background, amount)
+    }
+
+    /// The background, to clear a frame to. Clearing skips the shaders, so
+    /// it has to be made `linear` here for a frame stored as sRGB
+    pub fn clear_color(&self, linear: bool) -> wgpu::Color {
+        let component = |c: f32| {
+            if linear {
+                super::srgb_to_linear(c) as f64
+            } else {
+                c as f64
+            }
+        };
+        wgpu::Color {
+            r: component(self.background[0]),
+            g: component(self.background[1]),
+            b: component(self.background[2]),
+            a: 1.0,
+        }
+    }
+
+    /// Reads the theme in the TOML file at `path`
+    pub fn load(path: &std::path::Path) -> Result<Self, Box<dyn Error>> {
+        Ok(toml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+/// The colour `amount` of the way from `from` to `to`
+pub fn mix(from: [f32; 3], to: [f32; 3], amount: f32) -> [f32; 3] {
+    [
+        from[0] + (to[0] - from[0]) * amount,
+        from[1] + (to[1] - from[1]) * amount,
+        from[2] + (to[2] - from[2]) * amount,
+    ]
+}
+
+/// Every theme there is, built in ones first, by name. Files that can't be
+/// read are left out with a warning
+pub fn all() -> Vec<(String, Theme)> {
+    let mut themes = vec![
+        (DEFAULT_THEME.to_string(), Theme::default()),
+        ("Contrast".to_string(), Theme::contrast()),
+        ("Pastel".to_string(), Theme::pastel()),
+        (COLORBLIND_THEME.to_string(), Theme::colorblind()),
+    ];
+
+    let mut paths: Vec<_> = match std::fs::read_dir(THEME_DIR) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().map_or(false, |ext| ext == "toml"))
+            .collect(),
+        Err(_) => return themes,
+    };
+    paths.sort();
+    for path in paths {
+        let name = match path.file_stem().and_then(|s| s.to_str()) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        match Theme::load(&path) {
+            Ok(theme) => themes.push((name, theme)),
+            Err(e) => eprintln!("Warning: couldn't load theme {}: {}", path.display(), e),
+        }
+    }
+    themes
+}
+
+/// The theme called `name`, or the default if there's none
+pub fn find(name: &str) -> Theme {
+    match all().into_iter().find(|(n, _)| n == name) {
+        Some((_, theme)) => theme,
+        None => {
+            eprintln!("Warning: no theme called {:?}, using the default", name);
+            Theme::default()
+        }
+    }
+}
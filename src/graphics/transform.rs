@@ -0,0 +1,130 @@
+//! Placing geometry drawn in a space of its own, like a board's cells, without
+//! moving its vertices.
+//!
+//! A [`Transform2D`] scales and then moves points into the space around them,
+//! and a [`Camera`] keeps a stack of them on top of a projection. Frames have
+//! a few [transform slots](super::TRANSFORM_SLOTS) each holding one matrix, so
+//! different draws in the same submit can each be placed by their own.
+
+use crate::ui::layout::Rect;
+
+/// Scales points about the origin, then moves them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform2D {
+    pub scale: [f32; 2],
+    pub offset: [f32; 2],
+}
+
+impl Default for Transform2D {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform2D {
+    /// Leaves points where they are
+    pub const IDENTITY: Transform2D = Transform2D {
+        scale: [1.0, 1.0],
+        offset: [0.0, 0.0],
+    };
+
+    pub fn translation(x: f32, y: f32) -> Self {
+        Self {
+            offset: [x, y],
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn scaling(x: f32, y: f32) -> Self {
+        Self {
+            scale: [x, y],
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Places points by `inner` and then by this, for a space inside this one
+    pub fn then(self, inner: Transform2D) -> Self {
+        Self {
+            scale: [
+                self.scale[0] * inner.scale[0],
+                self.scale[1] * inner.scale[1],
+            ],
+            offset: self.apply(inner.offset),
+        }
+    }
+
+    pub fn apply(&self, point: [f32; 2]) -> [f32; 2] {
+        [
+            point[0] * self.scale[0] + self.offset[0],
+            point[1] * self.scale[1] + self.offset[1],
+        ]
+    }
+
+    /// Where `rect` ends up, for scales that don't flip it
+    pub fn apply_rect(&self, rect: &Rect) -> Rect {
+        let [x, y] = self.apply([rect.x, rect.y]);
+        Rect {
+            x,
+            y,
+            width: rect.width * self.scale[0],
+            height: rect.height * self.scale[1],
+        }
+    }
+
+    pub fn matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::from_translation(cgmath::Vector3::new(self.offset[0], self.offset[1], 0.0))
+            * cgmath::Matrix4::from_nonuniform_scale(self.scale[0], self.scale[1], 1.0)
+    }
+}
+
+/// A projection and the transforms stacked on top of it. Moves and scales
+/// apply to whatever is placed afterwards, in the space set up so far
+#[derive(Clone, Debug)]
+pub struct Camera {
+    projection: cgmath::Matrix4<f32>,
+    current: Transform2D,
+    saved: Vec<Transform2D>,
+}
+
+impl Camera {
+    pub fn new(projection: cgmath::Matrix4<f32>) -> Self {
+        Self {
+            projection,
+            current: Transform2D::IDENTITY,
+            saved: Vec::new(),
+        }
+    }
+
+    pub fn translate(&mut self, x: f32, y: f32) {
+        self.current = self.current.then(Transform2D::translation(x, y));
+    }
+
+    pub fn scale(&mut self, x: f32, y: f32) {
+        self.current = self.current.then(Transform2D::scaling(x, y));
+    }
+
+    /// Remembers the current transform, to go back to with [`pop`](Self::pop)
+    pub fn push(&mut self) {
+        self.saved.push(self.current);
+    }
+
+    /// Goes back to the transform last pushed. Panics if there isn't one
+    pub fn pop(&mut self) {
+        self.current = self.saved.pop().expect("camera popped more than pushed");
+    }
+
+    /// From the current space to the world, leaving out the projection
+    pub fn transform(&self) -> Transform2D {
+        self.current
+    }
+
+    /// From the current space to the frame
+    pub fn matrix(&self) -> cgmath::Matrix4<f32> {
+        self.projection * self.current.matrix()
+    }
+
+    /// From `local`, a space inside the current one, to the frame
+    pub fn matrix_for(&self, local: Transform2D) -> cgmath::Matrix4<f32> {
+        self.projection * self.current.then(local).matrix()
+    }
+}
@@ -0,0 +1,207 @@
+//! TrueType text, for characters the bitmap font doesn't have and for text
+//! that stays sharp however big it's drawn.
+//!
+//! Glyphs are rasterized the first time they're drawn and packed into an atlas
+//! a shelf at a time. When the atlas fills up it's emptied and packing starts
+//! over, so glyphs laid out earlier that frame can show wrong for a frame.
+//!
+//! The font in use belongs to the thread rather than the graphics state, so
+//! text can be laid out wherever its mesh is built. The graphics state sends
+//! the atlas to the GPU whenever text is uploaded.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+
+use super::atlas::Region;
+use super::text::GlyphQuad;
+
+/// Width and height of the atlas, in pixels
+pub const ATLAS_SIZE: u32 = 1024;
+
+/// Size glyphs are rasterized at, in pixels. Text of any size is scaled from it
+const RASTER_PX: f32 = 64.0;
+
+/// Empty pixels kept around each glyph, so filtering doesn't bleed the
+/// neighbouring ones in
+const PADDING: u32 = 1;
+
+thread_local! {
+    static ACTIVE: RefCell<Option<TtfFont>> = RefCell::new(None);
+}
+
+/// Draws text on this thread with `font` from now on, or with the bitmap font
+/// if it's None
+pub fn set_active(font: Option<TtfFont>) {
+    ACTIVE.with(|active| *active.borrow_mut() = font);
+}
+
+/// Runs `f` on the font text is drawn with on this thread, if it's a TrueType
+/// one
+pub fn with_active<R>(f: impl FnOnce(&mut TtfFont) -> R) -> Option<R> {
+    ACTIVE.with(|active| active.borrow_mut().as_mut().map(f))
+}
+
+struct Shelf {
+    y: u32,
+    height: u32,
+    /// Width taken up from the left so far
+    used: u32,
+}
+
+/// Packs rectangles in rows, each as tall as the first one put in it
+#[derive(Default)]
+struct ShelfPacker {
+    shelves: Vec<Shelf>,
+    bottom: u32,
+}
+
+impl ShelfPacker {
+    /// Top left corner of room for a `width` by `height` rectangle, if there's
+    /// any left
+    fn pack(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if width > ATLAS_SIZE {
+            return None;
+        }
+        for shelf in &mut self.shelves {
+            if height <= shelf.height && shelf.used + width <= ATLAS_SIZE {
+                shelf.used += width;
+                return Some((shelf.used - width, shelf.y));
+            }
+        }
+        if self.bottom + height > ATLAS_SIZE {
+            return None;
+        }
+        let y = self.bottom;
+        self.shelves.push(Shelf {
+            y,
+            height,
+            used: width,
+        });
+        self.bottom += height;
+        Some((0, y))
+    }
+}
+
+pub struct TtfFont {
+    font: fontdue::Font,
+
+    /// Distance from the top of a line to its baseline, and the height of a
+    /// line, in pixels at `RASTER_PX`
+    ascent: f32,
+    line_size: f32,
+
+    glyphs: HashMap<char, GlyphQuad>,
+    packer: ShelfPacker,
+
+    /// White, with each pixel's coverage as its alpha
+    atlas: image::RgbaImage,
+
+    /// Whether `atlas` changed since it was last taken
+    changed: bool,
+}
+
+impl TtfFont {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let font = fontdue::Font::from_bytes(data, fontdue::FontSettings::default())?;
+        let line = font
+            .horizontal_line_metrics(RASTER_PX)
+            .ok_or("font has no horizontal metrics")?;
+        Ok(Self {
+            font,
+            ascent: line.ascent,
+            line_size: line.ascent - line.descent,
+            glyphs: HashMap::new(),
+            packer: ShelfPacker::default(),
+            atlas: image::RgbaImage::new(ATLAS_SIZE, ATLAS_SIZE),
+            changed: true,
+        })
+    }
+
+    /// Whether the font has a glyph of its own for `c`
+    pub fn has_glyph(&self, c: char) -> bool {
+        self.font.lookup_glyph_index(c) != 0
+    }
+
+    /// Where the glyph for `c` is drawn and taken from, rasterizing it into
+    /// the atlas if it isn't there yet
+    pub fn glyph(&mut self, c: char) -> GlyphQuad {
+        if let Some(glyph) = self.glyphs.get(&c) {
+            return *glyph;
+        }
+        let glyph = self.rasterize(c);
+        self.glyphs.insert(c, glyph);
+        glyph
+    }
+
+    fn rasterize(&mut self, c: char) -> GlyphQuad {
+        let (metrics, coverage) = self.font.rasterize(c, RASTER_PX);
+        let (width, height) = (metrics.width as u32, metrics.height as u32);
+        let cell = |px: f32| px / self.line_size;
+        let mut glyph = GlyphQuad {
+            offset: [
+                cell(metrics.xmin as f32),
+                cell(self.ascent - (metrics.ymin as f32 + metrics.height as f32)),
+            ],
+            extent: [cell(metrics.width as f32), cell(metrics.height as f32)],
+            region: Region {
+                origin: [0.0, 0.0],
+                size: [0.0, 0.0],
+            },
+            advance: cell(metrics.advance_width),
+        };
+        if width == 0 || height == 0 {
+            return glyph;
+        }
+
+        let (padded_width, padded_height) = (width + PADDING * 2, height + PADDING * 2);
+        let corner = self.packer.pack(padded_width, padded_height).or_else(|| {
+            self.clear();
+            self.packer.pack(padded_width, padded_height)
+        });
+        let (left, top) = match corner {
+            Some((x, y)) => (x + PADDING, y + PADDING),
+            None => {
+                glyph.extent = [0.0, 0.0];
+                return glyph;
+            }
+        };
+
+        for (i, alpha) in coverage.iter().enumerate() {
+            let (x, y) = (i as u32 % width, i as u32 / width);
+            self.atlas
+                .put_pixel(left + x, top + y, image::Rgba([255, 255, 255, *alpha]));
+        }
+        self.changed = true;
+
+        let texel = 1.0 / ATLAS_SIZE as f32;
+        glyph.region = Region {
+            origin: [left as f32 * texel, top as f32 * texel],
+            size: [width as f32 * texel, height as f32 * texel],
+        };
+        glyph
+    }
+
+    /// Empties the atlas, forgetting every glyph in it
+    fn clear(&mut self) {
+        self.glyphs.clear();
+        self.packer = ShelfPacker::default();
+        for pixel in self.atlas.pixels_mut() {
+            *pixel = image::Rgba([0; 4]);
+        }
+        self.changed = true;
+    }
+
+    /// The atlas, if it changed since it was last taken
+    pub fn take_changes(&mut self) -> Option<&image::RgbaImage> {
+        if std::mem::take(&mut self.changed) {
+            Some(&self.atlas)
+        } else {
+            None
+        }
+    }
+}
@@ -1,2 +1,21 @@
+pub mod ai;
+#[cfg(feature = "frontend")]
+pub mod audio;
+pub mod bench;
+pub mod board;
+#[cfg(feature = "frontend")]
+pub mod clipboard;
+#[cfg(feature = "frontend")]
+pub mod config;
+pub mod embed;
 pub mod game;
+#[cfg(feature = "frontend")]
 pub mod graphics;
+pub mod mode;
+pub mod paths;
+pub mod piece;
+pub mod profiles;
+pub mod scores;
+pub mod sim;
+#[cfg(feature = "frontend")]
+pub mod ui;
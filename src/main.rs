@@ -1,1452 +1,7211 @@
-use glfw::{Action, Key};
-use rand::prelude::*;
-use wgpu::util::DeviceExt;
-
-use std::array;
+use winit::{
+    dpi::{LogicalSize, PhysicalSize},
+    event::{
+        ElementState, KeyboardInput, ModifiersState, MouseScrollDelta, VirtualKeyCode as Key,
+        WindowEvent,
+    },
+    event_loop::{ControlFlow, EventLoop},
+    window::{Fullscreen, WindowBuilder},
+};
 
-use lib::{game::GameState, graphics::Vertex};
+use std::any::Any;
+use std::cell::{RefCell, RefMut};
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::num::{NonZeroU32, NonZeroU64};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::Rng;
+
+use lib::{
+    ai::{self, best_placement},
+    audio::{AudioSystem, Channel, Music, SoundId, Track, MAX_VOLUME},
+    bench::{BenchReport, FrameBench, SimBench, BENCH_SEED, DEFAULT_BENCH_TICKS},
+    board::{Cell, FIELD_HEIGHT, FIELD_WIDTH},
+    config::{Config, Metronome, SharedConfig, CONFIG_FILE},
+    embed::{Button, Game, GameEvent, Input},
+    game::{
+        achievements::{self, Achievement, Achievements, RunStats},
+        gamepad::Gamepads,
+        input::{Action, InputSnapshot, KeyMap, KeyState, PadButton, PadMap, TextField},
+        limiter::{self, FrameLimiter, FPS_CAPS},
+        net::{self, Connection, Message, NetError},
+        puzzle::{self, Puzzle},
+        session::SharedSession,
+        stats::{self, GameStats, Sample, StatsDelta, SPLIT_LINES},
+        timestep::FixedTimestep,
+        GameState,
+    },
+    graphics::{
+        assets,
+        chart::{Axis, Chart},
+        falling::FallingPieces,
+        lines::{LineCap, LineStyle, Outlines},
+        mesh::MeshBuilder,
+        opaque,
+        panel::{draw_panel, draw_panel_border, draw_panel_fill, PanelStyle},
+        particles::ParticleSystem,
+        post::{Effect, PIXEL_SCALES},
+        text::{Align, Anchor, TextStyle},
+        theme::{mix, PieceColors, Theme},
+        transform::{Camera, Transform2D},
+        with_alpha, Frame, Vertex,
+    },
+    mode::{
+        format_time, Clock, Finish, GameMode, HudStat, MetronomeRule, MARATHON_CLEAR_FLAG,
+        SPRINT_LINES,
+    },
+    paths,
+    piece::{
+        randomizer::RandomizerKind,
+        rotation::RotationKind,
+        set::{self, PieceSet},
+        Color, Piece, Tetromino, PATTERN_SIZE,
+    },
+    profiles::Profiles,
+    scores::{
+        self,
+        export::{self, Format},
+        ScoreEntry,
+    },
+    sim::{
+        dump::{self, DUMP_DIR, DUMP_INPUT_TICKS},
+        replay::{Ghost, GhostDisplay, Replay, GHOST_FILE},
+        save::{self, SAVE_FILE},
+        ticks_to_millis, BoardEffect, BoardEffectType, Ending, Inputs, Simulation, COUNTDOWN_TICKS,
+        DEFAULT_PREVIEWS, LEVEL_UP_TICKS, LINE_CLEAR_TICKS, MAX_PREVIEWS, MAX_START_LEVEL,
+        MIN_PREVIEWS, TICKS_PER_SECOND, TICK_MILLIS,
+    },
+    ui::{
+        callout::{Callouts, Clear},
+        help,
+        layout::{
+            mono_width, Layout, Rect, CALLOUT_SIZE, CALLOUT_Y, CENTER_X, CHART_LABEL_GAP,
+            CHART_LABEL_SIZE, CHART_MAX_TICKS, CONFIRM_TEXT_SIZE, CONFIRM_WIDTH, HELP_ROWS,
+            HELP_TEXT_SIZE, HELP_WIDTH, HUD_COLUMN_CHARS, POPUP_SIZE, PREVIEW_BOX_CELLS,
+            SUMMARY_COLUMN_CHARS, UI_SCALES, X_PER_Y,
+        },
+        popup::Popups,
+        unlock::UnlockToasts,
+    },
+};
 use tet_rs as lib;
 
-const FIELD_WIDTH: u32 = 10;
-const FIELD_HEIGHT: u32 = 20;
-const FRAME_TIME: f32 = 0.05;
-const ACTIVE_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
-const INACTIVE_COLOR: [f32; 3] = [0.5, 0.5, 0.5];
-
-#[rustfmt::skip = "readability"]
-static PIECES: &[&str] = &[
-    "....\
-     .##.\
-     .##.\
-     ....",
-    "..#.\
-     ..#.\
-     ..#.\
-     ..#.",
-    ".#..\
-     .##.\
-     ..#.\
-     ....",
-    "..#.\
-     .##.\
-     .#..\
-     ....",
-    ".#..\
-     .#..\
-     .##.\
-     ....",
-    "..#.\
-     ..#.\
-     .##.\
-     ....",
-    ".#..\
-     .##.\
-     .#..\
-     ....",
-];
-
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-enum Color {
-    Red,
-    Orange,
-    Yellow,
-    Green,
-    Blue,
-    Purple,
-    White,
-}
-
-macro_rules! rgb {
-    ($r:expr, $g:expr, $b:expr) => {
-        [
-            ($r as f32) / 255.0,
-            ($g as f32) / 255.0,
-            ($b as f32) / 255.0,
-        ]
-    };
-}
-
-impl Color {
-    fn rgb(self) -> [f32; 3] {
-        match self {
-            Self::Red => rgb!(221, 55, 55),
-            Self::Orange => rgb!(255, 115, 25),
-            Self::Yellow => rgb!(255, 215, 5),
-            Self::Green => rgb!(30, 135, 30),
-            Self::Blue => rgb!(0, 90, 255),
-            Self::Purple => rgb!(110, 10, 225),
-            Self::White => rgb!(255, 255, 255),
-        }
-    }
-}
-
-static PIECE_COLORS: &[Color] = {
-    &[
-        Color::Red,
-        Color::Orange,
-        Color::Yellow,
-        Color::Green,
-        Color::Blue,
-        Color::Purple,
-        Color::White,
-    ]
+const FRAME_TIME: f32 = TICK_MILLIS as f32 / 1000.0;
+const ACHIEVEMENTS_FILE: &str = "tetrs_achievements.bin";
+/// Consecutive outdated frames after which the swap chain format is re-queried
+const OUTDATED_FRAMES_BEFORE_REBUILD: u32 = 3;
+/// Smallest the window can be made, in logical pixels. Any smaller and the
+/// text can't be read, and some drivers won't make a swap chain that small
+const MIN_WINDOW_WIDTH: u32 = 320;
+const MIN_WINDOW_HEIGHT: u32 = 480;
+/// Shortest time between fitting the swap chain to a window being resized
+const RESIZE_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+/// Pixels a touchpad scrolls for each line a mouse wheel notch would
+const SCROLL_PIXELS_PER_LINE: f64 = 20.0;
+/// How long a message in the corner, like a screenshot's confirmation, stays up
+const TOAST_TIME: std::time::Duration = std::time::Duration::from_secs(2);
+/// How long a static screen is left alone before it's drawn at the low power
+/// rate
+const IDLE_BEFORE_LOW_POWER: std::time::Duration = std::time::Duration::from_secs(2);
+/// How many frames the metronome border takes to fade after a gravity step
+const METRONOME_PULSE_TICKS: u32 = 3;
+/// How far pattern marks are faded into the background from their cell's colour
+const PATTERN_FADE: f32 = 0.6;
+/// Part of a cell's side left clear around its pattern
+const PATTERN_MARGIN: f32 = 0.2;
+/// Opacity of the ghost showing where the active piece will land
+const GHOST_ALPHA: f32 = 0.3;
+/// Thickness of the line around the ghost, in world units
+const GHOST_OUTLINE_THICKNESS: f32 = 0.004;
+const GHOST_OUTLINE_STYLE: LineStyle = LineStyle::Dashed {
+    dash: 0.012,
+    gap: 0.008,
 };
-
+/// Opacity of the ghost's field and pieces in a sprint race
+const RACE_ALPHA: f32 = 0.5;
+/// Line across the well in sprint, marking how many lines are left to clear
+const GOAL_LINE_THICKNESS: f32 = 0.006;
+const GOAL_LINE_STYLE: LineStyle = LineStyle::Dotted { spacing: 0.02 };
+/// Last frames of a line clear, during which the rows above fall into place
+const LINE_COLLAPSE_TICKS: u64 = 6;
+/// How long the menu sits untouched before the demo starts playing
+const DEMO_IDLE_TICKS: u64 = 20 * TICKS_PER_SECOND;
+/// Pieces without an I after which the sidebar's count of them flashes
+const I_DROUGHT_WARNING: u32 = 12;
+/// How long a piece placed with a finesse fault flashes
+const FAULT_FLASH_TICKS: u64 = 8;
+/// Particles thrown off by each cell of a cleared line
+const PARTICLES_PER_CELL: u32 = 4;
+/// How long a particle from a cleared line lasts
+const PARTICLE_LIFE_TICKS: u32 = (TICKS_PER_SECOND / 2) as u32;
+/// Frames `--bench-render` times, after drawing a few first for things to
+/// settle
+const BENCH_FRAMES: usize = 600;
+const BENCH_WARMUP_FRAMES: u32 = 60;
+/// Rows at the top of the field the benchmarked board leaves empty
+const BENCH_EMPTY_ROWS: u32 = 4;
+/// Sideways jolt of the playfield on a four line clear, in playfield widths.
+/// Kept well inside the room the screen leaves on either side of the field
+const TETRIS_SHAKE: f32 = 0.03;
+/// Rows at the top of the field the stack has to reach to hurry the music
+/// and light up the edges of the well
+const DANGER_ROWS: u32 = 4;
+/// How long the danger glow takes to come on or go off
+const DANGER_FADE_TICKS: u32 = 10;
+/// How often the danger glow pulses
+const DANGER_PULSE_TICKS: u64 = TICKS_PER_SECOND;
+/// How far in from the walls the danger glow reaches, in cells
+const DANGER_GLOW_CELLS: f32 = 1.5;
+/// Opacity of the danger glow at the walls at the height of a pulse
+const DANGER_GLOW_ALPHA: f32 = 0.5;
+/// Share of a shake left after each tick
+const SHAKE_DECAY: f32 = 0.6;
+/// Share of the wait for a rising row left when its bar turns to warn
+const RISE_WARNING: f32 = 0.25;
+/// How long locked cells stay visible in invisible mode
+const INVISIBLE_FADE_TICKS: u64 = TICKS_PER_SECOND;
+/// How often an online board tells the opponent what its field looks like
+const SNAPSHOT_TICKS: u64 = TICKS_PER_SECOND;
+/// How long the pieces take to change colour on a level up
+const PALETTE_FADE_TICKS: u64 = 8;
+/// How far toward the text colour the grid lights up on a level up
+const LEVEL_UP_GRID_FLASH: f32 = 0.5;
+
+/// How long the restart key has to be held before the run starts over, so a
+/// stray press can't throw a good one away
+const RESTART_HOLD_TICKS: u64 = TICKS_PER_SECOND / 2;
+
+/// Entries of the main menu
 #[derive(Clone, Copy, PartialEq, Eq)]
-enum Cell {
-    Empty,
-    Full(Color),
+enum MenuItem {
+    Continue,
+    Play,
+    Scores,
+    Awards,
+    Profile,
+    HowToPlay,
+    Options,
+    Quit,
 }
 
-impl Default for Cell {
-    fn default() -> Self {
-        Self::Empty
-    }
-}
+struct TetrisMenu {
+    // Current menu selection
+    selection: u8,
 
-#[derive(Copy, Clone, Debug)]
-struct Piece {
-    shape: &'static str,
-    color: Color,
-    rot: u8,
-    x: i8,
-    y: i8,
-}
+    /// Whether there's a saved run to continue
+    has_save: bool,
 
-impl Piece {
-    fn new(index: usize) -> Self {
-        assert!(
-            index < PIECES.len(),
-            "Invalid index {} given, must be in range 0-{}",
-            index,
-            PIECES.len()
-        );
-        Self {
-            x: (FIELD_WIDTH / 2 - 2) as i8,
-            y: 0,
-            color: PIECE_COLORS[index],
-            rot: 0,
-            shape: PIECES[index],
-        }
-    }
+    /// Level games are started at, adjusted on the Play entry
+    start_level: u32,
 
-    fn filled_at(&self, x: usize, y: usize) -> bool {
-        assert!(x < 4 && y < 4, "Out of bounds index supplied");
+    /// Seed every game is dealt from, if forced on the command line
+    seed: Option<u64>,
 
-        let i = match self.rot % 4 {
-            0 => x + y * 4,
-            1 => (3 - y) + x * 4,
-            2 => 15 - (x + y * 4),
-            3 => (3 - x) * 4 + y,
-            _ => unreachable!(),
-        };
+    /// Options every game is started with
+    config: SharedConfig,
 
-        &self.shape[i..=i] == "#"
-    }
-}
+    /// Runs played since the game was opened, to compare the next against
+    session: SharedSession,
 
-type Field = [Cell; (FIELD_WIDTH * FIELD_HEIGHT) as usize];
+    /// Name of the profile playing
+    profile: String,
 
-fn was_pressed(input: KeyState, ticker: u64) -> bool {
-    match input {
-        KeyState::Pressed => true,
-        KeyState::Holding if ticker % 2 == 0 => true,
-        _ => false,
-    }
-}
+    /// Frames since a key was last pressed
+    idle: u64,
 
-struct TetrisMenu {
-    // Current menu selection
-    selection: u8,
+    /// Demos shown while the menu was left alone, taking turns between the
+    /// recorded one and the AI
+    demos: u64,
+
+    /// Pieces drifting down behind the entries
+    falling: FallingPieces,
 
     // Previous frame player input
     last_input: PlayerInput,
 
-    /// Time accumulator
-    accum: f32,
-
-    /// Current frame number
-    ticker: u64,
+    /// Paces updates to the tick rate and counts the ticks
+    clock: FixedTimestep,
 }
 
 impl Default for TetrisMenu {
     fn default() -> Self {
         TetrisMenu {
             selection: 0,
+            has_save: false,
+            start_level: 1,
+            seed: None,
+            config: SharedConfig::default(),
+            session: SharedSession::default(),
+            profile: String::new(),
+            idle: 0,
+            demos: 0,
+            falling: FallingPieces::new(&mut rand::thread_rng()),
             last_input: PlayerInput::all_pressed(),
-            accum: 0.0,
-            ticker: 0,
+            clock: FixedTimestep::default(),
         }
     }
 }
 
-impl GameState for TetrisMenu {
-    fn update(&mut self, window: &glfw::Window, dt: std::time::Duration) -> lib::game::StateChange {
-        self.accum += dt.as_secs_f32();
+impl TetrisMenu {
+    /// Entries currently on offer, top to bottom
+    fn items(&self) -> Vec<MenuItem> {
+        let mut items = Vec::with_capacity(8);
+        if self.has_save {
+            items.push(MenuItem::Continue);
+        }
+        items.extend_from_slice(&[
+            MenuItem::Play,
+            MenuItem::Scores,
+            MenuItem::Awards,
+            MenuItem::Profile,
+            MenuItem::HowToPlay,
+            MenuItem::Options,
+            MenuItem::Quit,
+        ]);
+        items
+    }
+
+    /// What each entry says, top to bottom
+    fn labels(&self) -> Vec<String> {
+        self.items()
+            .into_iter()
+            .map(|item| match item {
+                MenuItem::Continue => "Continue".to_string(),
+                MenuItem::Play => format!("Play < Lv {} >", self.start_level),
+                MenuItem::Scores => "Scores".to_string(),
+                MenuItem::Awards => "Awards".to_string(),
+                MenuItem::Profile => format!("Profile: {}", self.profile),
+                MenuItem::HowToPlay => "How to play".to_string(),
+                MenuItem::Options => "Options".to_string(),
+                MenuItem::Quit => "Quit".to_string(),
+            })
+            .collect()
+    }
+
+    /// Checks for a saved run, pointing the selection at it if there is one
+    fn find_save(&mut self) {
+        let had_save = self.has_save;
+        self.has_save = paths::data_file(SAVE_FILE).exists();
+        if self.has_save != had_save {
+            self.selection = 0;
+        }
+    }
+
+    /// Writes the start level back to the config for next time
+    fn remember_start_level(&self) {
+        let mut config = self.config.borrow_mut();
+        config.start_level = self.start_level;
+        config
+            .save(&paths::data_file(CONFIG_FILE))
+            .unwrap_or_else(|e| eprintln!("Couldn't save config: {}", e));
+    }
+
+    /// Draws the falling pieces, before anything else so they're behind it
+    fn render_background(&self, graphics: &lib::graphics::GraphicsState, frame: &mut Frame) {
+        let canvas = Rect::canvas(
+            graphics.sc_desc.width as f32,
+            graphics.sc_desc.height as f32,
+        );
+        let mut geometry = graphics.scratch_mesh();
+        self.falling.render(&canvas, &graphics.theme, &mut geometry);
+        let geometry = graphics.inspect_geometry(&geometry, 0);
+        let mesh = graphics.upload(&graphics.geometry_mesh, &geometry);
+
+        let mut command_buf =
+            graphics
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("background_command_buf"),
+                });
+        {
+            let mut pass = frame.begin_pass(&mut command_buf);
+            pass.set_pipeline(graphics.geometry_pipeline());
+            pass.set_vertex_buffer(0, mesh.vertices());
+            pass.set_index_buffer(mesh.indices(), wgpu::IndexFormat::Uint32);
+            pass.set_bind_group(0, graphics.transform_bind_group(0), &[]);
+            pass.set_bind_group(1, graphics.text_bind_group(), &[]);
+            pass.draw_indexed(0..mesh.index_count(), 0, 0..1);
+        }
+        graphics.queue.submit(std::iter::once(command_buf.finish()));
+    }
 
-        while self.accum >= FRAME_TIME {
-            self.accum -= FRAME_TIME;
-            self.ticker += 1;
+    /// Asks before closing the game
+    fn confirm_quit() -> lib::game::StateChange {
+        lib::game::StateChange::Push(Box::new(TetrisConfirm::new(
+            "Quit the game?",
+            MenuItem::Quit,
+        )))
+    }
+
+    /// Picks up the saved run, which is deleted so it can only be continued once.
+    /// One that can't be deleted isn't continued at all, as it could otherwise
+    /// be finished, and its score entered, again and again
+    fn resume(&mut self) -> lib::game::StateChange {
+        let path = paths::data_file(SAVE_FILE);
+        let loaded = save::load(&path);
+        if let Err(e) = std::fs::remove_file(&path) {
+            eprintln!("Couldn't delete saved game, leaving it be: {}", e);
+            return lib::game::StateChange::None;
+        }
+        self.find_save();
+        match loaded {
+            Ok((sim, assisted)) => lib::game::StateChange::Push(Box::new(TetrisMain {
+                assisted,
+                ..TetrisMain::new(sim, self.config.clone())
+            })),
+            Err(e) => {
+                eprintln!("Couldn't continue saved game: {}", e);
+                lib::game::StateChange::None
+            }
+        }
+    }
+}
 
-            let input = input(window, self.last_input);
+impl GameState for TetrisMenu {
+    fn update(
+        &mut self,
+        snapshot: &InputSnapshot,
+        dt: std::time::Duration,
+    ) -> lib::game::StateChange {
+        self.clock.accumulate(dt);
+
+        while self.clock.tick() {
+            let since = self.last_input.frame;
+            let input = input(snapshot, self.last_input);
             self.last_input = input;
-            if input.rot_left == KeyState::Pressed || input.rot_right == KeyState::Pressed {
+            self.falling.update(&mut rand::thread_rng());
+
+            self.idle = if input.any_pressed() || snapshot.cursor_moved_since(since) {
+                0
+            } else {
+                self.idle + 1
+            };
+            if self.idle >= DEMO_IDLE_TICKS {
+                // nobody's around, show off a little
+                self.idle = 0;
+                self.demos += 1;
+                let recorded = self.demos % 2 == 1;
+                return lib::game::StateChange::Push(Box::new(TetrisMain::attract(recorded)));
+            }
+
+            let labels = self.labels();
+            let entries: Vec<&str> = labels.iter().map(String::as_str).collect();
+            let mut clicked = false;
+            if let Some((hovered, click)) = mouse_selection(snapshot, &entries, since) {
+                self.selection = hovered as u8;
+                clicked = click;
+            }
+
+            let items = self.items();
+            let item = items[self.selection as usize];
+            let last = items.len() as u8 - 1;
+            if input.confirmed() || clicked {
                 // confirm choice.
-                match self.selection {
-                    0 => {
-                        // load game
-                        return lib::game::StateChange::Push(Box::new(TetrisMain::default()));
+                match item {
+                    MenuItem::Continue => {
+                        // pick up where the last run left off
+                        return self.resume();
                     }
-                    1 => {
+                    MenuItem::Play => {
+                        // pick a mode
+                        return lib::game::StateChange::Push(Box::new(TetrisModeSelect {
+                            start_level: self.start_level,
+                            seed: self.seed,
+                            config: self.config.clone(),
+                            session: self.session.clone(),
+                            ..TetrisModeSelect::default()
+                        }));
+                    }
+                    MenuItem::Scores => {
                         // show scores
                         return lib::game::StateChange::Push(Box::new(TetrisScores::default()));
                     }
-                    2 => {
-                        // quit game
-                        return lib::game::StateChange::Quit;
+                    MenuItem::Awards => {
+                        // show achievements
+                        return lib::game::StateChange::Push(Box::new(
+                            TetrisAchievements::default(),
+                        ));
+                    }
+                    MenuItem::Profile => {
+                        // pick who's playing
+                        return lib::game::StateChange::Push(Box::new(TetrisProfiles::new()));
                     }
-                    _ => unreachable!(),
+                    MenuItem::HowToPlay => {
+                        // explain the game
+                        return lib::game::StateChange::Push(Box::new(TetrisHelp::new(
+                            self.config.clone(),
+                        )));
+                    }
+                    MenuItem::Options => {
+                        // adjust settings
+                        return lib::game::StateChange::Push(Box::new(TetrisOptions::new(
+                            self.config.clone(),
+                        )));
+                    }
+                    MenuItem::Quit => {
+                        // quit game, once sure
+                        return Self::confirm_quit();
+                    }
+                }
+            } else if input.backed_out() {
+                // there's nothing to go back to, so head for the exit, and
+                // going back again from there takes it
+                if item == MenuItem::Quit {
+                    return Self::confirm_quit();
                 }
+                self.selection = last;
             } else if input.up == KeyState::Pressed {
                 // move selection up
                 if self.selection == 0 {
-                    self.selection = 2;
+                    self.selection = last;
                 } else {
                     self.selection -= 1;
                 }
             } else if input.down == KeyState::Pressed {
                 // move selection down
-                if self.selection == 2 {
+                if self.selection == last {
                     self.selection = 0;
                 } else {
                     self.selection += 1;
                 }
+            } else if item == MenuItem::Play && input.left == KeyState::Pressed {
+                self.start_level = u32::max(self.start_level - 1, 1);
+                self.remember_start_level();
+            } else if item == MenuItem::Play && input.right == KeyState::Pressed {
+                self.start_level = u32::min(self.start_level + 1, MAX_START_LEVEL);
+                self.remember_start_level();
             }
         }
 
         lib::game::StateChange::None
     }
 
-    fn render(&self, graphics: &lib::graphics::GraphicsState) -> Result<(), wgpu::SwapChainError> {
-        // create uniforms
-        let dimensions = (
-            graphics.sc_desc.width as f32,
-            graphics.sc_desc.height as f32,
-        );
-        let aspect_ratio = dimensions.0 / dimensions.1;
-        let offset = aspect_ratio / 2.0 - 0.5;
-        let proj = cgmath::Matrix4::from_nonuniform_scale(0.5, 1.0, 1.0)
-            * cgmath::ortho(-offset, 1.0 + offset, 1.0, 0.0, -1.0, 1.0);
-        let raw: [[f32; 4]; 4] = proj.into();
-        graphics
-            .queue
-            .write_buffer(&graphics.mat_buffer, 0, bytemuck::cast_slice(&raw));
+    fn on_enter(&mut self) {
+        self.find_save();
+        self.profile = Profiles::load_or_default().active().name.clone();
+    }
 
-        // render text
-        let mut vertices_text = Vec::new();
-        let mut indices_text = Vec::new();
-
-        let (vt, it) = lib::graphics::text::render_text(
-            "Tet.rs",
-            0.0,
-            0.2,
-            1.0 / 6.0,
-            vertices_text.len(),
-            ACTIVE_COLOR,
-        );
-        vertices_text.extend(vt);
-        indices_text.extend(it);
-
-        let (vt, it) = lib::graphics::text::render_text(
-            "Play",
-            0.25,
-            0.5,
-            0.5 / 4.0,
-            vertices_text.len(),
-            if self.selection == 0 {
-                ACTIVE_COLOR
-            } else {
-                INACTIVE_COLOR
-            },
-        );
-        vertices_text.extend(vt);
-        indices_text.extend(it);
+    fn on_resume(&mut self) {
+        // keys still held from the state above don't count as new presses
+        self.last_input = PlayerInput::all_pressed();
+        self.find_save();
+        self.profile = Profiles::load_or_default().active().name.clone();
+    }
 
-        let (vt, it) = lib::graphics::text::render_text(
-            "Scores",
-            0.25 - 0.5 / 4.0,
-            0.7,
-            0.5 / 4.0,
-            vertices_text.len(),
-            if self.selection == 1 {
-                ACTIVE_COLOR
-            } else {
-                INACTIVE_COLOR
-            },
-        );
-        vertices_text.extend(vt);
-        indices_text.extend(it);
-
-        let (vt, it) = lib::graphics::text::render_text(
-            "Quit",
-            0.25,
-            0.9,
-            0.5 / 4.0,
-            vertices_text.len(),
-            if self.selection == 2 {
-                ACTIVE_COLOR
-            } else {
-                INACTIVE_COLOR
-            },
+    fn on_result(&mut self, result: Box<dyn Any>) -> lib::game::StateChange {
+        match result.downcast::<Confirmed<MenuItem>>() {
+            Ok(confirmed) if confirmed.0 == MenuItem::Quit => lib::game::StateChange::Quit,
+            Ok(_) => lib::game::StateChange::None,
+            Err(result) => RunResult::reveal(result, &self.session),
+        }
+    }
+
+    /// The backdrop drifts slowly enough to be drawn at the low power rate
+    fn is_static(&self) -> bool {
+        true
+    }
+
+    fn render(&self, graphics: &lib::graphics::GraphicsState, frame: &mut Frame) {
+        let labels = self.labels();
+        let entries: Vec<&str> = labels.iter().map(String::as_str).collect();
+        if self.config.borrow().menu_background {
+            self.render_background(graphics, frame);
+        }
+        render_menu(graphics, frame, "Tet.rs", &entries, self.selection as usize);
+    }
+
+    fn music(&self) -> Option<Music> {
+        Some(Music::Play {
+            track: Track::Menu,
+            hurried: false,
+        })
+    }
+}
+
+/// Draws a screen of `entries` under `title`, with the `selected` one
+/// highlighted and pointed at
+fn render_menu(
+    graphics: &lib::graphics::GraphicsState,
+    frame: &mut Frame,
+    title: &str,
+    entries: &[&str],
+    selected: usize,
+) {
+    // render text
+    let vertices_scope = graphics.profiler.scope("vertices");
+    let mut text = graphics.scratch_mesh();
+
+    let layout = graphics.layout();
+
+    let hint = layout.menu_hint(MENU_HINT);
+    lib::graphics::text::render_text(
+        &mut text,
+        MENU_HINT,
+        hint.x,
+        hint.y,
+        hint.size,
+        graphics.theme.inactive_text,
+    );
+
+    let title_placement = layout.title(title);
+    lib::graphics::text::render_styled(
+        &mut text,
+        title,
+        CENTER_X,
+        title_placement.y,
+        &TextStyle::new(title_placement.size, graphics.theme.active_text).align(Align::Center),
+    );
+
+    for (i, entry) in entries.iter().enumerate() {
+        let placement = layout.menu_entry(i, entries.len(), entry);
+        lib::graphics::text::render_styled(
+            &mut text,
+            entry,
+            CENTER_X,
+            placement.y,
+            &TextStyle::new(
+                placement.size,
+                if selected == i {
+                    graphics.theme.active_text
+                } else {
+                    graphics.theme.inactive_text
+                },
+            )
+            .align(Align::Center),
         );
-        vertices_text.extend(vt);
-        indices_text.extend(it);
-
-        // render selection tick on highlighted thingie
-        let (y_offset, x_offset) = match self.selection {
-            0 => (0.5, 0.25),
-            1 => (0.7, 0.25 - 0.5 / 4.0),
-            2 => (0.9, 0.25),
-            _ => unreachable!(),
-        };
-        let tri_width = 0.5 / 4.0 / 2.0;
-        let x_offset = x_offset - tri_width * 1.5;
-        let vertices_tri = vec![
+    }
+
+    // render selection tick on highlighted thingie
+    let placement = layout.menu_entry(selected, entries.len(), entries[selected]);
+    let y_offset = placement.y;
+    let tri_width = placement.size / 2.0;
+    let x_offset = placement.x - tri_width * 1.5;
+    let mut tri = graphics.scratch_mesh();
+    tri.extend(
+        &[
             Vertex {
                 position: [x_offset, y_offset, 0.0],
-                color: [1.0, 1.0, 1.0],
+                color: [1.0, 1.0, 1.0, 1.0],
                 tex_coords: [0.0, 0.0],
             },
             Vertex {
                 position: [x_offset + tri_width, y_offset + tri_width / 2.0, 0.0],
-                color: [1.0, 1.0, 1.0],
+                color: [1.0, 1.0, 1.0, 1.0],
                 tex_coords: [0.0, 0.0],
             },
             Vertex {
                 position: [x_offset, y_offset + tri_width, 0.0],
-                color: [1.0, 1.0, 1.0],
+                color: [1.0, 1.0, 1.0, 1.0],
                 tex_coords: [0.0, 0.0],
             },
-        ];
-        let indices_tri: Vec<u16> = vec![0, 2, 1];
+        ],
+        &[0, 2, 1],
+    );
+    let tri = graphics.inspect_geometry(&tri, 0);
 
-        // create buffers
-        let v_text_buf = graphics
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                contents: bytemuck::cast_slice(&vertices_text),
-                label: Some("v_text_buf"),
-                usage: wgpu::BufferUsage::VERTEX,
-            });
-        let i_text_buf = graphics
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                contents: bytemuck::cast_slice(&indices_text),
-                label: Some("i_text_buf"),
-                usage: wgpu::BufferUsage::INDEX,
-            });
-        let v_tri_buf = graphics
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                contents: bytemuck::cast_slice(&vertices_tri),
-                label: Some("v_text_buf"),
-                usage: wgpu::BufferUsage::VERTEX,
-            });
-        let i_tri_buf = graphics
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                contents: bytemuck::cast_slice(&indices_tri),
-                label: Some("i_text_buf"),
-                usage: wgpu::BufferUsage::INDEX,
-            });
+    drop(vertices_scope);
 
-        // render!
-        let frame = graphics.swap_chain.get_current_frame()?.output;
-        let mut command_buf =
-            graphics
-                .device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label: Some("command_buf"),
-                });
-        {
-            let mut pass = command_buf.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("pass"),
-                color_attachments: &[wgpu::RenderPassColorAttachment {
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0,
-                            g: 0.0125,
-                            b: 0.05,
-                            a: 1.0,
-                        }),
-                        store: true,
-                    },
-                    resolve_target: None,
-                    view: &frame.view,
-                }],
-                depth_stencil_attachment: None,
-            });
+    // create buffers
+    let buffers_scope = graphics.profiler.scope("buffers");
+    let text_mesh = graphics.upload(&graphics.text_mesh, &text);
+    let tri_mesh = graphics.upload(&graphics.geometry_mesh, &tri);
 
-            // draw text
+    drop(buffers_scope);
+
+    // render!
+    let mut command_buf = graphics
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("command_buf"),
+        });
+    {
+        let mut pass = frame.begin_pass(&mut command_buf);
+
+        // draw text, which would be unreadable as a wireframe
+        if !graphics.wireframe {
             pass.set_pipeline(&graphics.text_pipeline);
-            pass.set_vertex_buffer(0, v_text_buf.slice(..));
-            pass.set_index_buffer(i_text_buf.slice(..), wgpu::IndexFormat::Uint16);
-            pass.set_bind_group(0, &graphics.mat_buffer_bind_group, &[]);
-            pass.set_bind_group(1, &graphics.text_texture_bind_group, &[]);
-            pass.draw_indexed(0..indices_text.len() as _, 0, 0..1);
-
-            // draw triangle
-            pass.set_pipeline(&graphics.pipeline);
-            pass.set_vertex_buffer(0, v_tri_buf.slice(..));
-            pass.set_index_buffer(i_tri_buf.slice(..), wgpu::IndexFormat::Uint16);
-            pass.set_bind_group(0, &graphics.mat_buffer_bind_group, &[]);
-            pass.set_bind_group(1, &graphics.text_texture_bind_group, &[]);
-            pass.draw_indexed(0..indices_tri.len() as _, 0, 0..1);
+            pass.set_vertex_buffer(0, text_mesh.vertices());
+            pass.set_index_buffer(text_mesh.indices(), wgpu::IndexFormat::Uint32);
+            pass.set_bind_group(0, graphics.transform_bind_group(0), &[]);
+            pass.set_bind_group(1, graphics.text_bind_group(), &[]);
+            pass.draw_indexed(0..text_mesh.index_count(), 0, 0..1);
         }
-        graphics.queue.submit(std::iter::once(command_buf.finish()));
 
-        Ok(())
+        // draw triangle
+        pass.set_pipeline(graphics.geometry_pipeline());
+        pass.set_vertex_buffer(0, tri_mesh.vertices());
+        pass.set_index_buffer(tri_mesh.indices(), wgpu::IndexFormat::Uint32);
+        pass.set_bind_group(0, graphics.transform_bind_group(0), &[]);
+        pass.set_bind_group(1, graphics.text_bind_group(), &[]);
+        pass.draw_indexed(0..tri_mesh.index_count(), 0, 0..1);
     }
+    let _scope = graphics.profiler.scope("submit");
+    graphics.queue.submit(std::iter::once(command_buf.finish()));
 }
 
-fn sort_scores(scores: &mut [(String, u64)]) {
-    scores.sort_by(|(_, score_a), (_, score_b)| score_a.cmp(score_b));
-}
-
-struct TetrisScores {
-    /// Vector containing scores of previous players
-    scores: Vec<(String, u64)>,
-
-    /// Whether the player is inputting a new score
-    inputting_score: Option<u64>,
-
-    /// Previous frame input
-    last_input: PlayerInput,
-
-    /// Current frame number
-    ticker: u64,
-
-    /// Accumulator
-    accum: f32,
-}
-
-fn load_scores() -> Result<Vec<(String, u64)>, Box<dyn std::error::Error>> {
-    use std::{convert::TryInto, fs, io::Read};
-
-    let mut file = fs::File::open("tetrs_scores.bin")?;
-    let mut contents = Vec::new();
-    let file_length = file.read_to_end(&mut contents)?;
-    let mut reader = &*contents;
-
-    let mut scores = Vec::new();
-
-    let mut bytes = 0;
-
-    // read file header
-    let mut buffer = [0_u8; 512];
-    reader.read_exact(&mut buffer[0..8])?;
-    bytes += 8;
-    let txt = &buffer[0..8];
-    if txt != b"tet.rs 1" {
-        return Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            "invalid format",
-        )));
-    }
-
-    // read the number of entries
-    reader.read_exact(&mut buffer[0..1])?;
-    bytes += 1;
-    let entries = u8::from_le_bytes(buffer[0..1].try_into()?);
-    for _ in 0..entries {
-        // read the name length
-        reader.read_exact(&mut buffer[0..1])?;
-        bytes += 1;
-        let length = u8::from_le_bytes(buffer[0..1].try_into()?) as usize;
-
-        // read the name
-        reader.read_exact(&mut buffer[0..length])?;
-        bytes += length;
-        let string = String::from_utf8_lossy(&buffer[0..length]).into_owned();
-
-        // read the score
-        reader.read_exact(&mut buffer[0..8])?;
-        bytes += 8;
-        let score = u64::from_le_bytes(buffer[0..8].try_into()?);
-
-        scores.push((string, score));
-    }
-
-    // have we read the whole file?
-    if bytes != file_length {
-        return Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            "extraneous data",
-        )));
+/// Keys every menu takes, shown along the bottom
+const MENU_HINT: &str = "Enter: select   Esc: back";
+const SCORES_HINT: &str = "E: export   Esc: back";
+
+/// Entry of a menu drawn by [`render_menu`] with `entries` that the mouse
+/// picked after frame `since`, by moving onto it or clicking it, and whether
+/// it was clicked. A cursor left sitting still picks nothing, so the arrow
+/// keys can take the selection back from it
+fn mouse_selection(
+    snapshot: &InputSnapshot,
+    entries: &[&str],
+    since: u64,
+) -> Option<(usize, bool)> {
+    let [x, y] = snapshot.cursor()?;
+    let layout = snapshot.layout()?;
+    let hovered = (0..entries.len()).find(|&i| {
+        layout
+            .menu_entry(i, entries.len(), entries[i])
+            .rect(entries[i])
+            .contains_point(x, y)
+    })?;
+    let clicked = snapshot.clicked_since(since);
+    if clicked || snapshot.cursor_moved_since(since) {
+        Some((hovered, clicked))
+    } else {
+        None
     }
+}
 
-    sort_scores(&mut scores[..]);
-    scores.reverse();
+/// Thickness of the summary chart's axes and of the lines drawn on it
+const CHART_AXIS_THICKNESS: f32 = 0.003;
+const CHART_LINE_THICKNESS: f32 = 0.006;
 
-    Ok(scores)
-}
+/// How much a confirmation dims the screen behind it
+const CONFIRM_DARKEN: f32 = 0.6;
+/// Width of the line around a confirmation, in y units
+const CONFIRM_BORDER_THICKNESS: f32 = 0.005;
 
-fn save_scores(scores: &[(String, u64)]) -> Result<(), Box<dyn std::error::Error>> {
-    use std::fs;
-    use std::io::{self, prelude::*};
+/// Handed to the state under a [`TetrisConfirm`] when Yes is picked
+struct Confirmed<T>(T);
 
-    let file = fs::OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .create(true)
-        .open("tetrs_scores.bin")?;
+/// Yes or no question in a box over the dimmed state underneath. Yes pops it
+/// with its answer as a [`Confirmed`], and No or backing out just pops it
+struct TetrisConfirm<T> {
+    message: String,
 
-    let mut writer = io::BufWriter::new(file);
+    /// Whether Yes is picked rather than No. No is to start with, so a stray
+    /// press leaves things as they are
+    yes: bool,
 
-    // write header
-    writer.write_all(b"tet.rs 1")?;
-    // write entries count
-    let n_entries = usize::min(scores.len(), 10) as u8;
-    writer.write_all(&n_entries.to_le_bytes())?;
-    // write entries
-    for (name, score) in scores.iter().rev().take(10) {
-        // write the length of the name
-        let name_len = usize::min(name.len(), u8::MAX as usize) as u8;
-        writer.write_all(&name_len.to_le_bytes())?;
-        // write the name itself (might generate invalid utf8, we handle it on load)
-        let shortened_name = &name.as_bytes()[0..name_len as usize];
-        writer.write_all(shortened_name)?;
-        // write the score
-        writer.write_all(&score.to_le_bytes())?;
-    }
+    /// What the state underneath gets back on a yes
+    answer: Option<T>,
 
-    // save to file
-    writer.flush()?;
+    /// Paces updates to the tick rate and counts the ticks
+    clock: FixedTimestep,
 
-    Ok(())
+    /// Previous frame input
+    last_input: PlayerInput,
 }
 
-impl Default for TetrisScores {
-    fn default() -> Self {
-        let scores = load_scores().unwrap_or_else(|e| {
-            eprintln!("Error loading scores: {}", e);
-            Vec::new()
-        });
-
+impl<T> TetrisConfirm<T> {
+    fn new(message: &str, answer: T) -> Self {
         Self {
-            scores,
-            accum: 0.0,
-            ticker: 0,
-            last_input: PlayerInput::default(),
-            inputting_score: None,
+            message: message.to_string(),
+            yes: false,
+            answer: Some(answer),
+            clock: FixedTimestep::default(),
+            last_input: PlayerInput::all_pressed(),
         }
     }
 }
 
-impl GameState for TetrisScores {
-    fn update(&mut self, window: &glfw::Window, dt: std::time::Duration) -> lib::game::StateChange {
-        self.accum += dt.as_secs_f32();
+impl<T: 'static> GameState for TetrisConfirm<T> {
+    fn update(
+        &mut self,
+        snapshot: &InputSnapshot,
+        dt: std::time::Duration,
+    ) -> lib::game::StateChange {
+        self.clock.accumulate(dt);
 
-        while self.accum > FRAME_TIME {
-            self.accum -= FRAME_TIME;
-            self.ticker += 1;
-            let input = input(window, self.last_input);
+        while self.clock.tick() {
+            let input = input(snapshot, self.last_input);
             self.last_input = input;
 
-            if let Some(score) = self.inputting_score {
-                // TODO: actually take in name inputs
-                let name = "PLR";
-
-                // make sure our scores are sorted
-                sort_scores(&mut self.scores[..]);
-
-                // find the index our score would have and insert it
-                if score > 0 {
-                    let i = self
-                        .scores
-                        .iter()
-                        .enumerate()
-                        .find(|(_, (_, s))| *s < score);
-                    if let Some((index, _)) = i {
-                        // yuh
-                        self.scores.insert(index, (name.to_string(), score));
-                        if self.scores.len() > 10 {
-                            self.scores.pop();
-                        }
-                    } else if i.is_none() {
-                        self.scores.push((name.to_string(), score));
-                    }
-                }
-                sort_scores(&mut self.scores[..]);
-
-                // save the file
-                save_scores(&self.scores[..])
-                    .unwrap_or_else(|e| eprintln!("Couldn't save scores: {}", e));
-
-                // we're done processing it
-                self.inputting_score = None;
-            }
-
-            if input.escape == KeyState::Pressed {
+            if input.backed_out() {
                 return lib::game::StateChange::Pop;
+            } else if input.confirmed() {
+                return match self.answer.take() {
+                    Some(answer) if self.yes => {
+                        lib::game::StateChange::PopWith(Box::new(Confirmed(answer)))
+                    }
+                    _ => lib::game::StateChange::Pop,
+                };
+            } else if input.left == KeyState::Pressed || input.right == KeyState::Pressed {
+                self.yes = !self.yes;
             }
         }
 
         lib::game::StateChange::None
     }
 
-    fn render(&self, graphics: &lib::graphics::GraphicsState) -> Result<(), wgpu::SwapChainError> {
-        // create uniforms
-        let dimensions = (
-            graphics.sc_desc.width as f32,
-            graphics.sc_desc.height as f32,
-        );
-        let aspect_ratio = dimensions.0 / dimensions.1;
-        let offset = aspect_ratio / 2.0 - 0.5;
-        let proj = cgmath::Matrix4::from_nonuniform_scale(0.5, 1.0, 1.0)
-            * cgmath::ortho(-offset, 1.0 + offset, 1.0, 0.0, -1.0, 1.0);
-        let raw: [[f32; 4]; 4] = proj.into();
-        graphics
-            .queue
-            .write_buffer(&graphics.mat_buffer, 0, bytemuck::cast_slice(&raw));
+    fn is_overlay(&self) -> bool {
+        true
+    }
 
-        // render text
-        let mut vertices_text = Vec::new();
-        let mut indices_text = Vec::new();
+    fn backdrop(&self) -> Option<Effect> {
+        Some(Effect::Darken {
+            amount: CONFIRM_DARKEN,
+        })
+    }
 
-        let (vt, it) = lib::graphics::text::render_text(
-            "Scores",
-            0.0,
-            0.2,
-            1.0 / 6.0,
-            vertices_text.len(),
-            ACTIVE_COLOR,
-        );
-        vertices_text.extend(vt);
-        indices_text.extend(it);
+    fn is_static(&self) -> bool {
+        true
+    }
 
-        for (i, (name, score)) in self.scores.iter().take(10).enumerate() {
-            let mut score_txt = score.to_string();
-            if score_txt.len() > 10 {
-                score_txt = score_txt.chars().take(7).chain("...".chars()).collect();
-            }
-            let txt = format!("{:.<10}{:.>10}", name, score_txt);
-            let (vt, it) = lib::graphics::text::render_text(
-                &txt,
-                -0.5,
-                0.2 + 1.0 / 6.0 + 0.055 * i as f32,
-                0.1,
-                vertices_text.len(),
-                ACTIVE_COLOR,
+    fn render(&self, graphics: &lib::graphics::GraphicsState, frame: &mut Frame) {
+        let theme = &graphics.theme;
+        let layout = graphics.layout();
+
+        let style = TextStyle::new(CONFIRM_TEXT_SIZE * layout.scale, theme.active_text);
+        let lines =
+            lib::graphics::text::wrap_text(&self.message, &style, CONFIRM_WIDTH * layout.scale);
+
+        let mut geometry = graphics.scratch_mesh();
+        let panel = PanelStyle {
+            fill: Some(theme.background),
+            border: theme.border,
+            thickness: CONFIRM_BORDER_THICKNESS,
+            shadow: None,
+        };
+        draw_panel(&mut geometry, &layout.confirm_panel(lines.len()), &panel);
+
+        let mut text = graphics.scratch_mesh();
+        for (i, line) in lines.iter().enumerate() {
+            let placement = layout.confirm_line(i, lines.len(), line);
+            lib::graphics::text::render_text(
+                &mut text,
+                line,
+                placement.x,
+                placement.y,
+                placement.size,
+                theme.active_text,
+            );
+        }
+        for (i, (option, picked)) in [("Yes", self.yes), ("No", !self.yes)].iter().enumerate() {
+            let placement = layout.confirm_option(i, lines.len(), option);
+            lib::graphics::text::render_text(
+                &mut text,
+                option,
+                placement.x,
+                placement.y,
+                placement.size,
+                if *picked {
+                    theme.highlight
+                } else {
+                    theme.inactive_text
+                },
             );
-            vertices_text.extend(vt);
-            indices_text.extend(it);
         }
 
         // create buffers
-        let v_text_buf = graphics
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                contents: bytemuck::cast_slice(&vertices_text),
-                label: Some("v_text_buf"),
-                usage: wgpu::BufferUsage::VERTEX,
-            });
-        let i_text_buf = graphics
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                contents: bytemuck::cast_slice(&indices_text),
-                label: Some("i_text_buf"),
-                usage: wgpu::BufferUsage::INDEX,
-            });
+        let geometry = graphics.inspect_geometry(&geometry, 0);
+        let geometry_mesh = graphics.upload(&graphics.geometry_mesh, &geometry);
+        let text_mesh = graphics.upload(&graphics.text_mesh, &text);
 
         // render!
-        let frame = graphics.swap_chain.get_current_frame()?.output;
         let mut command_buf =
             graphics
                 .device
                 .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label: Some("command_buf"),
+                    label: Some("confirm_command_buf"),
                 });
         {
-            let mut pass = command_buf.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("pass"),
-                color_attachments: &[wgpu::RenderPassColorAttachment {
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0,
-                            g: 0.0125,
-                            b: 0.05,
-                            a: 1.0,
-                        }),
-                        store: true,
-                    },
-                    resolve_target: None,
-                    view: &frame.view,
-                }],
-                depth_stencil_attachment: None,
-            });
+            let mut pass = frame.begin_pass(&mut command_buf);
+
+            // draw the box
+            pass.set_pipeline(graphics.geometry_pipeline());
+            pass.set_vertex_buffer(0, geometry_mesh.vertices());
+            pass.set_index_buffer(geometry_mesh.indices(), wgpu::IndexFormat::Uint32);
+            pass.set_bind_group(0, graphics.transform_bind_group(0), &[]);
+            pass.set_bind_group(1, graphics.text_bind_group(), &[]);
+            pass.draw_indexed(0..geometry_mesh.index_count(), 0, 0..1);
 
             // draw text
             pass.set_pipeline(&graphics.text_pipeline);
-            pass.set_vertex_buffer(0, v_text_buf.slice(..));
-            pass.set_index_buffer(i_text_buf.slice(..), wgpu::IndexFormat::Uint16);
-            pass.set_bind_group(0, &graphics.mat_buffer_bind_group, &[]);
-            pass.set_bind_group(1, &graphics.text_texture_bind_group, &[]);
-            pass.draw_indexed(0..indices_text.len() as _, 0, 0..1);
+            pass.set_vertex_buffer(0, text_mesh.vertices());
+            pass.set_index_buffer(text_mesh.indices(), wgpu::IndexFormat::Uint32);
+            pass.set_bind_group(0, graphics.transform_bind_group(0), &[]);
+            pass.set_bind_group(1, graphics.text_bind_group(), &[]);
+            pass.draw_indexed(0..text_mesh.index_count(), 0, 0..1);
         }
         graphics.queue.submit(std::iter::once(command_buf.finish()));
-
-        Ok(())
     }
 }
 
-struct TetrisMain {
-    /// Array containing all fixed cells
-    field: Field,
+/// Longest delay and repeat the options screen goes up to
+const MAX_DAS_MILLIS: u64 = 500;
+const MAX_ARR_MILLIS: u64 = 250;
 
-    /// Active piece being manipulated by the player
-    active_piece: Option<Piece>,
+/// How far the options screen turns a volume up or down, in percent
+const VOLUME_STEP: u32 = 10;
 
-    /// Determines how many game ticks before the active piece is forcibly moved down
-    fall_ticks: u32,
+/// `percent` as a bar with a mark for every step, like `[#######---]`
+fn volume_bar(percent: u32) -> String {
+    let steps = (MAX_VOLUME / VOLUME_STEP) as usize;
+    let filled = (percent.min(MAX_VOLUME) / VOLUME_STEP) as usize;
+    format!("[{}{}]", "#".repeat(filled), "-".repeat(steps - filled))
+}
 
-    /// Counter which
-    fall_counter: u32,
+/// How long the options screen shows how clearing some data went
+const DATA_NOTICE_TICKS: u64 = 3 * TICKS_PER_SECOND;
 
-    /// Determines how many game ticks fall_ticks_dec_counter starts at
-    fall_accel_ticks: u32,
+/// Something kept between runs that the options screen can throw away
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DataReset {
+    Scores,
+    Settings,
+    SavedGame,
+    Replays,
+}
 
-    /// Counter that decreases speed by 1 when it reaches 0
-    fall_accel_counter: u32,
+impl DataReset {
+    /// Order they're listed in on the options screen
+    const ALL: [DataReset; 4] = [
+        DataReset::Scores,
+        DataReset::Settings,
+        DataReset::SavedGame,
+        DataReset::Replays,
+    ];
 
-    /// Next pieces to fall
-    next_pieces: Vec<Piece>,
+    fn label(self) -> &'static str {
+        match self {
+            DataReset::Scores => "Reset scores",
+            DataReset::Settings => "Reset settings",
+            DataReset::SavedGame => "Delete saved game",
+            DataReset::Replays => "Delete replays",
+        }
+    }
 
-    /// Time accumulator
-    accum: f32,
+    /// What's asked before going ahead
+    fn question(self) -> &'static str {
+        match self {
+            DataReset::Scores => "Clear every leaderboard?",
+            DataReset::Settings => "Put every setting and key back to the default?",
+            DataReset::SavedGame => "Delete the saved game?",
+            DataReset::Replays => "Delete the sprint ghost?",
+        }
+    }
 
-    /// Whether we rotated last frame
-    rotated: bool,
+    /// Throws the data away, saying how it went. Settings go back to the
+    /// defaults in `config` as well as in the file, so they take effect at once
+    fn run(self, config: &SharedConfig) -> String {
+        let result = match self {
+            DataReset::Scores => scores::reset(),
+            DataReset::Settings => {
+                *config.borrow_mut() = Config::default();
+                config.borrow().save(&paths::data_file(CONFIG_FILE))
+            }
+            DataReset::SavedGame => remove_data_file(SAVE_FILE),
+            DataReset::Replays => remove_data_file(GHOST_FILE),
+        };
+        match result {
+            Ok(()) => match self {
+                DataReset::Scores => "Scores reset",
+                DataReset::Settings => "Settings reset",
+                DataReset::SavedGame => "Saved game deleted",
+                DataReset::Replays => "Replays deleted",
+            }
+            .to_string(),
+            Err(e) => {
+                let message = format!("Couldn't {}: {}", self.label().to_lowercase(), e);
+                eprintln!("{}", message);
+                message
+            }
+        }
+    }
+}
 
-    /// Previous frame input
-    last_input: PlayerInput,
+/// Deletes the file called `name` from the data directory. One that isn't
+/// there is as good as deleted
+fn remove_data_file(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    match std::fs::remove_file(paths::data_file(name)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
 
-    /// Current frame number
-    ticker: u64,
+/// Settings screen. Changes go into the shared config straight away and are
+/// written to the file on the way out
+struct TetrisOptions {
+    // Current menu selection
+    selection: usize,
 
-    /// Score
-    score: u64,
+    /// Options being adjusted
+    config: SharedConfig,
 
-    /// Board effect
-    effect: Option<BoardEffect>,
-}
+    /// Names of the themes that can be picked, as found on opening
+    themes: Vec<String>,
 
-struct BoardEffect {
-    ty: BoardEffectType,
-    life: u64,
-}
+    /// How the last data reset went, and ticks left to show it for
+    notice: Option<(String, u64)>,
+
+    // Previous frame player input
+    last_input: PlayerInput,
 
-enum BoardEffectType {
-    LinesCleared { lines: Vec<i8> },
-    GameOver,
+    /// Paces updates to the tick rate and counts the ticks
+    clock: FixedTimestep,
 }
 
-impl lib::game::GameState for TetrisMain {
-    fn update(&mut self, window: &glfw::Window, dt: std::time::Duration) -> lib::game::StateChange {
-        self.accum += dt.as_secs_f32();
+impl TetrisOptions {
+    /// Rows on the screen: DAS, ARR, randomizer, rotation system, vsync,
+    /// antialiasing, pixel scale, UI scale, frame rate cap, screen shake,
+    /// fullscreen, theme, colourblind mode, smooth movement, menu background,
+    /// sprint ghost, metronome, master, music and effects volume, keys, the
+    /// data resets and back
+    const ROWS: usize = 22 + DataReset::ALL.len();
 
-        while self.accum > FRAME_TIME {
-            self.ticker += 1;
+    /// Row of the first data reset, after keys
+    const FIRST_RESET: usize = 21;
 
-            let input = input(&window, self.last_input);
-            self.last_input = input;
-            self.accum -= FRAME_TIME;
+    fn new(config: SharedConfig) -> Self {
+        Self {
+            selection: 0,
+            config,
+            themes: lib::graphics::theme::all()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect(),
+            notice: None,
+            last_input: PlayerInput::all_pressed(),
+            clock: FixedTimestep::default(),
+        }
+    }
 
-            if was_pressed(input.escape, self.ticker) {
-                return lib::game::StateChange::Pop;
-            }
+    /// The data reset on the highlighted row, if it's one
+    fn selected_reset(&self) -> Option<DataReset> {
+        self.selection
+            .checked_sub(Self::FIRST_RESET)
+            .and_then(|i| DataReset::ALL.get(i).copied())
+    }
 
-            if let Some(effect) = &mut self.effect {
-                // handle effect and return early
-                effect.life -= 1;
-                match effect.ty {
-                    BoardEffectType::LinesCleared { ref lines } if effect.life == 0 => {
-                        // delete them lines
-                        for line_y in lines {
-                            for y in (0..=*line_y).rev() {
-                                for x in 0..FIELD_WIDTH {
-                                    // n^3 loop :woozy_face:
-                                    if y == 0 {
-                                        // last line, just clear it
-                                        self.field
-                                            [x as usize + y as usize * FIELD_WIDTH as usize] =
-                                            Cell::Empty;
-                                    } else {
-                                        // fill it with the contents of the line above
-                                        self.field
-                                            [x as usize + y as usize * FIELD_WIDTH as usize] = self
-                                            .field
-                                            [x as usize + (y - 1) as usize * FIELD_WIDTH as usize];
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    BoardEffectType::GameOver if effect.life == 0 => {
-                        // game over!
-                        // TODO: configure scores to add score
-                        let scores = TetrisScores {
-                            inputting_score: if self.score > 0 {
-                                Some(self.score)
-                            } else {
-                                None
-                            },
-                            ..Default::default()
-                        };
-                        return lib::game::StateChange::Swap(Box::new(scores));
-                    }
-                    _ => (),
-                }
-                if effect.life == 0 {
-                    self.effect = None;
-                }
-                continue;
+    /// Moves the highlighted option one step up or down, by `direction`
+    fn adjust(&mut self, direction: i64) {
+        let step = |millis: u64, max: u64| {
+            (millis as i64 + direction * TICK_MILLIS as i64).clamp(0, max as i64) as u64
+        };
+        let mut config = self.config.borrow_mut();
+        match self.selection {
+            0 => config.das_millis = step(config.das_millis, MAX_DAS_MILLIS),
+            1 => config.arr_millis = step(config.arr_millis, MAX_ARR_MILLIS),
+            2 => {
+                let count = RandomizerKind::ALL.len() as i64;
+                let current = RandomizerKind::ALL
+                    .iter()
+                    .position(|&r| r == config.randomizer)
+                    .unwrap_or(0);
+                let next = (current as i64 + direction).rem_euclid(count);
+                config.randomizer = RandomizerKind::ALL[next as usize];
             }
-
-            if self.active_piece.is_none() {
-                // check if we have enough space!
-                let test_piece = self.next_pieces.remove(0);
-                self.next_pieces
-                    .push(Piece::new(rand::thread_rng().gen_range(0..PIECES.len())));
-
-                if piece_fits(&test_piece, &self.field) {
-                    // ok :D
-                    self.active_piece = Some(test_piece);
-                } else {
-                    // failuree!!
-                    self.effect = Some(BoardEffect {
-                        ty: BoardEffectType::GameOver,
-                        life: ((1.0 / FRAME_TIME) * 3.0).trunc() as u64,
-                    });
-                    continue;
-                }
+            3 => {
+                let count = RotationKind::ALL.len() as i64;
+                let current = RotationKind::ALL
+                    .iter()
+                    .position(|&r| r == config.rotation)
+                    .unwrap_or(0);
+                let next = (current as i64 + direction).rem_euclid(count);
+                config.rotation = RotationKind::ALL[next as usize];
+            }
+            4 => config.vsync = !config.vsync,
+            5 => config.msaa = !config.msaa,
+            6 => {
+                let count = PIXEL_SCALES.len() as i64;
+                let current = PIXEL_SCALES
+                    .iter()
+                    .position(|&scale| scale == config.pixel_scale)
+                    .unwrap_or(0);
+                let next = (current as i64 + direction).rem_euclid(count);
+                config.pixel_scale = PIXEL_SCALES[next as usize];
+            }
+            7 => {
+                let count = UI_SCALES.len() as i64;
+                let current = UI_SCALES
+                    .iter()
+                    .position(|&scale| scale == config.ui_scale)
+                    .unwrap_or(0);
+                let next = (current as i64 + direction).rem_euclid(count);
+                config.ui_scale = UI_SCALES[next as usize];
+            }
+            8 => {
+                let count = FPS_CAPS.len() as i64;
+                let current = FPS_CAPS
+                    .iter()
+                    .position(|&cap| cap == config.fps_cap)
+                    .unwrap_or(0);
+                let next = (current as i64 + direction).rem_euclid(count);
+                config.fps_cap = FPS_CAPS[next as usize];
+            }
+            9 => config.screen_shake = !config.screen_shake,
+            10 => config.fullscreen = !config.fullscreen,
+            11 => {
+                // an unknown theme counts as the first, so stepping moves off it
+                let count = self.themes.len() as i64;
+                let current = self.themes.iter().position(|t| *t == config.theme);
+                let next = (current.unwrap_or(0) as i64 + direction).rem_euclid(count);
+                config.theme = self.themes[next as usize].clone();
+            }
+            12 => config.colorblind = !config.colorblind,
+            13 => config.smooth_movement = !config.smooth_movement,
+            14 => config.menu_background = !config.menu_background,
+            15 => {
+                let count = GhostDisplay::ALL.len() as i64;
+                let current = GhostDisplay::ALL
+                    .iter()
+                    .position(|&g| g == config.ghost)
+                    .unwrap_or(0);
+                let next = (current as i64 + direction).rem_euclid(count);
+                config.ghost = GhostDisplay::ALL[next as usize];
+            }
+            16 => {
+                let count = Metronome::ALL.len() as i64;
+                let current = Metronome::ALL
+                    .iter()
+                    .position(|&m| m == config.metronome)
+                    .unwrap_or(0);
+                let next = (current as i64 + direction).rem_euclid(count);
+                config.metronome = Metronome::ALL[next as usize];
             }
+            17..=19 => {
+                let channel = Channel::ALL[self.selection - 17];
+                let volume = config.volume_percent_mut(channel);
+                *volume = (*volume as i64 + direction * VOLUME_STEP as i64)
+                    .clamp(0, MAX_VOLUME as i64) as u32;
+            }
+            _ => (),
+        }
+    }
 
-            let mut active_piece = self.active_piece.as_mut().unwrap();
+    /// What each row says, top to bottom
+    fn entries(&self) -> Vec<String> {
+        let config = self.config.borrow();
+        let on_off = |on| if on { "On" } else { "Off" };
+        let volume = |name, channel| {
+            format!(
+                "{} < {} >",
+                name,
+                volume_bar(config.volume_percent(channel))
+            )
+        };
+        vec![
+            format!("DAS < {} ms >", config.das_millis),
+            format!("ARR < {} ms >", config.arr_millis),
+            format!("Pieces < {} >", config.randomizer.name()),
+            format!("Rotation < {} >", config.rotation.name()),
+            format!("VSync < {} >", on_off(config.vsync)),
+            format!("MSAA < {} >", on_off(config.msaa)),
+            match config.pixel_scale {
+                1 => "Pixels < Off >".to_string(),
+                scale => format!("Pixels < {}x >", scale),
+            },
+            format!("UI scale < {}% >", config.ui_scale),
+            match config.fps_cap {
+                0 => "FPS cap < Off >".to_string(),
+                cap => format!("FPS cap < {} >", cap),
+            },
+            format!("Shake < {} >", on_off(config.screen_shake)),
+            format!("Fullscreen < {} >", on_off(config.fullscreen)),
+            format!("Theme < {} >", config.theme),
+            format!("Colorblind < {} >", on_off(config.colorblind)),
+            format!("Smooth < {} >", on_off(config.smooth_movement)),
+            format!("Backdrop < {} >", on_off(config.menu_background)),
+            format!("Ghost < {} >", config.ghost.name()),
+            format!("Metronome < {} >", config.metronome.name()),
+            volume("Master", Channel::Master),
+            volume("Music", Channel::Music),
+            volume("Effects", Channel::Effects),
+            "Keys".to_string(),
+        ]
+        .into_iter()
+        .chain(DataReset::ALL.iter().map(|reset| reset.label().to_string()))
+        .chain(std::iter::once("Back".to_string()))
+        .collect()
+    }
 
-            // tick fall counter
-            self.fall_counter -= 1;
-            let should_fall = self.fall_counter == 0 || was_pressed(input.down, self.ticker);
+    fn save(&self) {
+        self.config
+            .borrow()
+            .save(&paths::data_file(CONFIG_FILE))
+            .unwrap_or_else(|e| eprintln!("Couldn't save config: {}", e));
+    }
+}
 
-            // tick down fall accelerator counter
-            if self.fall_accel_counter == 0 {
-                self.fall_ticks = u32::max(self.fall_ticks - 1, 1);
-                self.fall_accel_counter = self.fall_accel_ticks;
-            }
+impl GameState for TetrisOptions {
+    fn update(
+        &mut self,
+        snapshot: &InputSnapshot,
+        dt: std::time::Duration,
+    ) -> lib::game::StateChange {
+        self.clock.accumulate(dt);
+
+        while self.clock.tick() {
+            let since = self.last_input.frame;
+            let input = input(snapshot, self.last_input);
+            self.last_input = input;
 
-            // rotate brick if requested
-            if input.rot_right == KeyState::Pressed {
-                if !self.rotated {
-                    self.rotated = true;
-                    let mut test_piece = active_piece.to_owned();
-                    test_piece.rot = (test_piece.rot + 1) % 4;
-                    if piece_fits(&test_piece, &self.field) {
-                        active_piece.rot = test_piece.rot;
-                    }
-                }
-            } else if input.rot_left == KeyState::Pressed {
-                if !self.rotated {
-                    self.rotated = true;
-                    let mut test_piece = active_piece.to_owned();
-                    test_piece.rot = if test_piece.rot == 0 {
-                        3
-                    } else {
-                        test_piece.rot - 1
-                    };
-                    if piece_fits(&test_piece, &self.field) {
-                        active_piece.rot = test_piece.rot;
-                    }
+            if let Some((_, life)) = &mut self.notice {
+                *life -= 1;
+                if *life == 0 {
+                    self.notice = None;
                 }
-            } else {
-                self.rotated = false;
             }
 
-            // move brick left and right if requested
-            if was_pressed(input.right, self.ticker) {
-                let mut test_piece = active_piece.to_owned();
-                test_piece.x += 1;
-                if piece_fits(&test_piece, &self.field) {
-                    active_piece.x = test_piece.x;
-                }
-            } else if was_pressed(input.left, self.ticker) {
-                let mut test_piece = active_piece.to_owned();
-                test_piece.x -= 1;
-                if piece_fits(&test_piece, &self.field) {
-                    active_piece.x = test_piece.x;
-                }
+            if input.backed_out() {
+                return lib::game::StateChange::Pop;
             }
 
-            // make piece fall
-            if should_fall {
-                self.fall_counter = self.fall_ticks;
-
-                // verify if we can fall
-                let mut test_piece = active_piece.to_owned();
-                test_piece.y += 1;
-                if piece_fits(&test_piece, &self.field) {
-                    // fall
-                    active_piece.y += 1;
-                } else {
-                    // add to board
-                    add_piece(active_piece, &mut self.field);
-
-                    // check if any lines are deletable
-                    let mut deletable = Vec::new();
-                    'outer_loop: for y in active_piece.y..active_piece.y + 4 {
-                        if y < 0 {
-                            // there's nothing here; continue
-                            continue;
-                        }
-                        if i32::from(y) >= FIELD_HEIGHT as i32 {
-                            // we've already passed the whole board; stop
-                            break;
-                        }
-                        for x in 0..FIELD_WIDTH {
-                            let tile = self.field[x as usize + y as usize * FIELD_WIDTH as usize];
-                            if tile == Cell::Empty {
-                                // this line ain't it chief
-                                continue 'outer_loop;
-                            }
-                        }
-                        // if we got here this is a golden line
-                        deletable.push(y);
-                    }
-
-                    if !deletable.is_empty() {
-                        // add score
-                        self.score += match deletable.len() {
-                            1 => 1,
-                            2 => 3,
-                            3 => 5,
-                            4 => 8,
-                            _ => unreachable!(),
-                        } * 100;
-
-                        // decrease speed
-                        self.fall_accel_counter = self
-                            .fall_accel_counter
-                            .saturating_sub(deletable.len() as u32);
-
-                        // set effect and defer line deletion to later
-                        self.effect = Some(BoardEffect {
-                            ty: BoardEffectType::LinesCleared { lines: deletable },
-                            life: ((1.0 / FRAME_TIME) * 1.0).trunc() as u64,
-                        });
-                    }
+            let entries = self.entries();
+            let entries: Vec<&str> = entries.iter().map(String::as_str).collect();
+            let mut clicked = false;
+            if let Some((hovered, click)) = mouse_selection(snapshot, &entries, since) {
+                self.selection = hovered;
+                clicked = click;
+            }
 
-                    // invalidate piece
-                    self.active_piece = None;
+            if input.confirmed() || clicked {
+                if self.selection == Self::ROWS - 1 {
+                    // back to the menu
+                    return lib::game::StateChange::Pop;
                 }
+                if self.selection == 20 {
+                    return lib::game::StateChange::Push(Box::new(TetrisKeys::new(
+                        self.config.clone(),
+                    )));
+                }
+                if let Some(reset) = self.selected_reset() {
+                    return lib::game::StateChange::Push(Box::new(TetrisConfirm::new(
+                        reset.question(),
+                        reset,
+                    )));
+                }
+                self.adjust(1);
+            } else if input.up == KeyState::Pressed {
+                self.selection = (self.selection + Self::ROWS - 1) % Self::ROWS;
+            } else if input.down == KeyState::Pressed {
+                self.selection = (self.selection + 1) % Self::ROWS;
+            } else if input.left == KeyState::Pressed {
+                self.adjust(-1);
+            } else if input.right == KeyState::Pressed {
+                self.adjust(1);
             }
         }
 
         lib::game::StateChange::None
     }
 
-    fn render(&self, graphics: &lib::graphics::GraphicsState) -> Result<(), wgpu::SwapChainError> {
-        const LINE_THICKNESS: f32 = 0.01;
+    fn on_resume(&mut self) {
+        // keys still held from the state above don't count as new presses
+        self.last_input = PlayerInput::all_pressed();
+    }
 
-        // render fixed field
-        let mut vertices: Vec<Vertex> = Vec::new();
-        let mut indices: Vec<u16> = Vec::new();
+    fn on_result(&mut self, result: Box<dyn Any>) -> lib::game::StateChange {
+        if let Ok(confirmed) = result.downcast::<Confirmed<DataReset>>() {
+            let message = confirmed.0.run(&self.config);
+            self.notice = Some((message, DATA_NOTICE_TICKS));
+        }
+        lib::game::StateChange::None
+    }
 
-        let inc_x = 1.0 / FIELD_WIDTH as f32;
-        let inc_y = 1.0 / FIELD_HEIGHT as f32;
+    fn on_exit(&mut self) {
+        self.save();
+    }
 
-        // render lines
-        // the reason we split our line rendering pass in two is because the X direction
-        // is stretched with the global matrix. for simplicity, we render everything in
-        // a single pass, which means that we need two different thickness values so the
-        // lines maintain a uniform scale, with the Y thickness being half of the X thick-
-        // ness. There's probably a more elegant solution out there but...
+    fn on_quit(&mut self) {
+        self.save();
+    }
 
-        let mut vec_pairs =
-            Vec::with_capacity((((FIELD_HEIGHT - 1) + (FIELD_WIDTH - 1)) * 2) as usize);
-        for y in 1..FIELD_HEIGHT {
-            vec_pairs.push(cgmath::Vector2::<f32>::new(
-                0.0,
-                y as f32 / FIELD_HEIGHT as f32,
-            ));
-            vec_pairs.push(cgmath::Vector2::<f32>::new(
-                1.0,
-                y as f32 / FIELD_HEIGHT as f32,
-            ));
+    fn is_static(&self) -> bool {
+        true
+    }
+
+    fn render(&self, graphics: &lib::graphics::GraphicsState, frame: &mut Frame) {
+        let entries = self.entries();
+        let entries: Vec<&str> = entries.iter().map(String::as_str).collect();
+        render_menu(graphics, frame, "Options", &entries, self.selection);
+        if let Some((message, _)) = &self.notice {
+            lib::graphics::screenshot::render_toast(graphics, frame, message);
         }
-        let (l_vtx, l_indx) = lib::graphics::lines::render_lines_pairs(
-            &vec_pairs,
-            LINE_THICKNESS / 2.0,
-            vertices.len(),
-        );
-        vertices.extend(l_vtx);
-        indices.extend(l_indx);
-        vec_pairs.clear();
-
-        for x in 1..FIELD_WIDTH {
-            vec_pairs.push(cgmath::Vector2::<f32>::new(
-                x as f32 / FIELD_WIDTH as f32,
-                0.0,
-            ));
-            vec_pairs.push(cgmath::Vector2::<f32>::new(
-                x as f32 / FIELD_WIDTH as f32,
-                1.0,
-            ));
+    }
+}
+
+/// Rebinding screen. Picking an action waits for the next key or gamepad
+/// button to go down and binds it, swapping with whichever action had it before
+struct TetrisKeys {
+    // Current menu selection: an action, then reset, then back
+    selection: usize,
+
+    /// Whether the next key or button pressed is bound to the selected action
+    capturing: bool,
+
+    /// What the last binding did, if worth pointing out
+    notice: Option<String>,
+
+    /// Keys the bindings are kept in
+    config: SharedConfig,
+
+    // Previous frame player input
+    last_input: PlayerInput,
+
+    /// Paces updates to the tick rate and counts the ticks
+    clock: FixedTimestep,
+}
+
+impl TetrisKeys {
+    fn new(config: SharedConfig) -> Self {
+        Self {
+            selection: 0,
+            capturing: false,
+            notice: None,
+            config,
+            last_input: PlayerInput::all_pressed(),
+            clock: FixedTimestep::default(),
         }
-        let (l_vtx, l_indx) =
-            lib::graphics::lines::render_lines_pairs(&vec_pairs, LINE_THICKNESS, vertices.len());
-        vertices.extend(l_vtx);
-        indices.extend(l_indx);
-
-        // render cells
-        let mut add_cell = |x: u32, y: u32, col: Color| {
-            let bx = x as f32 * inc_x;
-            let by = y as f32 * inc_y;
-
-            let color = col.rgb();
-
-            let bi = vertices.len() as u16;
-            indices.extend(array::IntoIter::new([
-                bi,
-                bi + 1,
-                bi + 2,
-                bi + 2,
-                bi + 1,
-                bi + 3,
-            ]));
-
-            vertices.extend(array::IntoIter::new([
-                Vertex {
-                    position: [bx, by, 0.0],
-                    color,
-                    tex_coords: [0.0, 0.0],
-                },
-                Vertex {
-                    position: [bx, by + inc_y, 0.0],
-                    color,
-                    tex_coords: [0.0, 0.0],
-                },
-                Vertex {
-                    position: [bx + inc_x, by, 0.0],
-                    color,
-                    tex_coords: [0.0, 0.0],
-                },
-                Vertex {
-                    position: [bx + inc_x, by + inc_y, 0.0],
-                    color,
-                    tex_coords: [0.0, 0.0],
-                },
-            ]));
-        };
+    }
 
-        let (spooky_lines, ticker) = if let Some(BoardEffect {
-            ty: BoardEffectType::LinesCleared { ref lines },
-            life,
-        }) = &self.effect
-        {
-            (&lines[..], *life)
-        } else {
-            (&[][..], 0)
-        };
+    fn rows() -> usize {
+        Action::ALL.len() + 2
+    }
 
-        for y in 0..FIELD_HEIGHT {
-            for x in 0..FIELD_WIDTH {
-                if let Cell::Full(col) = self.field[(x + y * FIELD_WIDTH) as usize] {
-                    if ticker % 10 < 5
-                        || spooky_lines.is_empty()
-                        || !spooky_lines.contains(&(y as i8))
-                    {
-                        add_cell(x, y, col);
+    fn save(&self) {
+        self.config
+            .borrow()
+            .save(&paths::data_file(CONFIG_FILE))
+            .unwrap_or_else(|e| eprintln!("Couldn't save config: {}", e));
+    }
+}
+
+impl GameState for TetrisKeys {
+    fn update(
+        &mut self,
+        snapshot: &InputSnapshot,
+        dt: std::time::Duration,
+    ) -> lib::game::StateChange {
+        self.clock.accumulate(dt);
+
+        while self.clock.tick() {
+            let pressed = snapshot.presses_since(self.last_input.frame).next();
+            let pad_pressed = snapshot.pad_presses_since(self.last_input.frame).next();
+            let input = {
+                let config = self.config.borrow();
+                read_input(snapshot, &config.keys, &config.pad, self.last_input)
+            };
+            self.last_input = input;
+
+            if self.capturing {
+                let action = Action::ALL[self.selection];
+                let swapped = match (pressed, pad_pressed) {
+                    // escape never gets bound by accident, it cancels
+                    (Some(Key::Escape), _) => {
+                        self.capturing = false;
+                        continue;
                     }
-                }
+                    (Some(key), _) => self.config.borrow_mut().keys.bind(action, key),
+                    (None, Some(button)) => self.config.borrow_mut().pad.bind(action, button),
+                    (None, None) => continue,
+                };
+                self.notice = swapped.map(|other| format!("Swapped with {}", other.name()));
+                self.capturing = false;
+                // the new key is already down, it shouldn't act as a press
+                self.last_input = PlayerInput::all_pressed();
+                continue;
             }
-        }
 
-        // render active piece
-        if let Some(piece) = self.active_piece {
-            for y in 0..4 {
-                for x in 0..4 {
-                    if piece.filled_at(x, y) {
-                        add_cell(
-                            (i32::from(piece.x) + x as i32) as u32,
-                            (i32::from(piece.y) + y as i32) as u32,
-                            piece.color,
-                        );
-                    }
-                }
+            if input.backed_out() {
+                return lib::game::StateChange::Pop;
             }
-        }
 
-        // render next pieces
-        for (i, piece) in self.next_pieces.iter().enumerate() {
-            for y in 0..4 {
-                for x in 0..4 {
-                    if piece.filled_at(x, y) {
-                        add_cell(
-                            (x as i32 + 12) as u32,
-                            (y as i32 + 2 + 5 * (i as i32)) as u32,
-                            piece.color,
-                        );
-                    }
+            if input.confirmed() {
+                if self.selection < Action::ALL.len() {
+                    self.capturing = true;
+                    self.notice = None;
+                } else if self.selection == Action::ALL.len() {
+                    let mut config = self.config.borrow_mut();
+                    config.keys = KeyMap::default();
+                    config.pad = PadMap::default();
+                    self.notice = Some("Keys reset".to_string());
+                } else {
+                    // back to the options
+                    return lib::game::StateChange::Pop;
                 }
+            } else if input.up == KeyState::Pressed {
+                self.selection = (self.selection + Self::rows() - 1) % Self::rows();
+            } else if input.down == KeyState::Pressed {
+                self.selection = (self.selection + 1) % Self::rows();
             }
         }
 
-        // create uniforms
-        let dimensions = (
-            graphics.sc_desc.width as f32,
-            graphics.sc_desc.height as f32,
-        );
-        let aspect_ratio = dimensions.0 / dimensions.1;
-        let offset = aspect_ratio / 2.0 - 0.5;
-        let proj = cgmath::Matrix4::from_nonuniform_scale(0.5, 1.0, 1.0)
-            * cgmath::ortho(-offset, 1.0 + offset, 1.0, 0.0, -1.0, 1.0);
-        let raw: [[f32; 4]; 4] = proj.into();
-        graphics
-            .queue
-            .write_buffer(&graphics.mat_buffer, 0, bytemuck::cast_slice(&raw));
-
-        // render text
-        let mut vertices_text = Vec::new();
-        let mut indices_text = Vec::new();
-
-        let (vt, it) = lib::graphics::text::render_text(
-            &format!("Score: {:06}", self.score),
-            1.1,
-            0.9,
-            0.05,
-            vertices_text.len(),
-            ACTIVE_COLOR,
-        );
-        vertices_text.extend(vt);
-        indices_text.extend(it);
-
-        let level = 20 - self.fall_ticks + 1;
-
-        let (vt, it) = lib::graphics::text::render_text(
-            &format!("Level: {:2}", level),
-            1.1,
-            0.95,
-            0.05,
-            vertices_text.len(),
-            ACTIVE_COLOR,
-        );
-        vertices_text.extend(vt);
-        indices_text.extend(it);
-
-        if let Some(BoardEffect {
-            ty: BoardEffectType::GameOver,
-            ..
-        }) = &self.effect
-        {
-            let (vt, it) = lib::graphics::text::render_text(
-                "GAME OVER!",
-                0.0,
-                0.1,
-                0.1,
-                vertices_text.len(),
-                ACTIVE_COLOR,
-            );
-            vertices_text.extend(vt);
-            indices_text.extend(it);
-        }
+        lib::game::StateChange::None
+    }
 
-        // create buffers
-        let v_buf = graphics
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                contents: bytemuck::cast_slice(&vertices),
-                label: Some("v_buf"),
-                usage: wgpu::BufferUsage::VERTEX,
-            });
-        let i_buf = graphics
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                contents: bytemuck::cast_slice(&indices),
-                label: Some("i_buf"),
-                usage: wgpu::BufferUsage::INDEX,
-            });
-        let v_text_buf = graphics
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                contents: bytemuck::cast_slice(&vertices_text),
-                label: Some("v_text_buf"),
-                usage: wgpu::BufferUsage::VERTEX,
-            });
-        let i_text_buf = graphics
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                contents: bytemuck::cast_slice(&indices_text),
-                label: Some("i_text_buf"),
-                usage: wgpu::BufferUsage::INDEX,
-            });
+    fn on_exit(&mut self) {
+        self.save();
+    }
 
-        // render!
-        let frame = graphics.swap_chain.get_current_frame()?.output;
-        let mut command_buf =
-            graphics
-                .device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label: Some("command_buf"),
-                });
-        {
-            let mut pass = command_buf.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("pass"),
-                color_attachments: &[wgpu::RenderPassColorAttachment {
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0,
-                            g: 0.0125,
-                            b: 0.05,
-                            a: 1.0,
-                        }),
-                        store: true,
-                    },
-                    resolve_target: None,
-                    view: &frame.view,
-                }],
-                depth_stencil_attachment: None,
-            });
-            // draw objects
-            pass.set_pipeline(&graphics.pipeline);
-            pass.set_vertex_buffer(0, v_buf.slice(..));
-            pass.set_index_buffer(i_buf.slice(..), wgpu::IndexFormat::Uint16);
-            pass.set_bind_group(0, &graphics.mat_buffer_bind_group, &[]);
-            pass.set_bind_group(1, &graphics.text_texture_bind_group, &[]); // ignored by shader
-            pass.draw_indexed(0..indices.len() as _, 0, 0..1);
+    fn on_quit(&mut self) {
+        self.save();
+    }
 
-            // draw text
-            pass.set_pipeline(&graphics.text_pipeline);
-            pass.set_vertex_buffer(0, v_text_buf.slice(..));
-            pass.set_index_buffer(i_text_buf.slice(..), wgpu::IndexFormat::Uint16);
-            pass.set_bind_group(0, &graphics.mat_buffer_bind_group, &[]);
-            pass.set_bind_group(1, &graphics.text_texture_bind_group, &[]);
-            pass.draw_indexed(0..indices_text.len() as _, 0, 0..1);
+    fn render(&self, graphics: &lib::graphics::GraphicsState, frame: &mut Frame) {
+        let (keys, pad) = {
+            let config = self.config.borrow();
+            (config.keys, config.pad)
+        };
+        let mut rows: Vec<String> = Action::ALL
+            .iter()
+            .enumerate()
+            .map(|(i, &action)| {
+                if self.capturing && i == self.selection {
+                    format!("{} < press a key or button >", action.name())
+                } else {
+                    let button = pad.button(action).map_or("-", PadButton::name);
+                    format!("{} < {:?} | {} >", action.name(), keys.key(action), button)
+                }
+            })
+            .collect();
+        rows.push("Reset to defaults".to_string());
+        rows.push("Back".to_string());
+        if let Some(notice) = &self.notice {
+            rows.push(notice.clone());
         }
-        graphics.queue.submit(std::iter::once(command_buf.finish()));
+        let entries: Vec<&str> = rows.iter().map(String::as_str).collect();
+        render_menu(graphics, frame, "Keys", &entries, self.selection);
+    }
 
-        Ok(())
+    fn takes_text(&self) -> bool {
+        self.capturing
     }
 }
 
-impl Default for TetrisMain {
-    fn default() -> Self {
-        let mut s = Self {
-            field: [Cell::Empty; (FIELD_WIDTH * FIELD_HEIGHT) as usize],
-            active_piece: None,
-            fall_ticks: 20,
-            fall_accel_ticks: 10,
-            accum: 0.0,
-            rotated: false,
-            last_input: PlayerInput::default(),
-            ticker: 0,
-            score: 0,
-            effect: None,
-
-            // these will be set later
-            fall_counter: 0,
-            fall_accel_counter: 0,
-            next_pieces: Vec::with_capacity(3),
-        };
+/// What a name being typed on the profiles screen is for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ProfileEdit {
+    Create,
+    /// Renaming the profile at this index
+    Rename(usize),
+}
 
-        s.fall_counter = s.fall_ticks;
-        s.fall_accel_counter = s.fall_accel_ticks;
-        let mut rand = rand::thread_rng();
-        s.next_pieces.extend(std::array::IntoIter::new([
-            Piece::new(rand.gen_range(0..PIECES.len())),
-            Piece::new(rand.gen_range(0..PIECES.len())),
-            Piece::new(rand.gen_range(0..PIECES.len())),
-        ]));
+/// Profile screen: picks who's playing, and makes, renames and deletes
+/// profiles
+struct TetrisProfiles {
+    profiles: Profiles,
 
-        s
-    }
+    // Current menu selection: a profile, then new, then back
+    selection: usize,
+
+    /// Name being typed, and what for
+    editing: Option<(ProfileEdit, TextField)>,
+
+    /// Whether delete has been pressed once on the selected profile, and
+    /// needs pressing again to go through
+    confirm_delete: bool,
+
+    /// What the last change did, if worth pointing out
+    notice: Option<String>,
+
+    // Previous frame player input
+    last_input: PlayerInput,
+
+    /// Paces updates to the tick rate and counts the ticks
+    clock: FixedTimestep,
 }
 
-fn piece_fits(piece: &Piece, field: &Field) -> bool {
-    for y in 0..4 {
-        for x in 0..4 {
-            let rx = piece.x as isize + x;
-            let ry = piece.y as isize + y;
-            let offset = rx + ry * FIELD_WIDTH as isize;
-            if piece.filled_at(x as usize, y as usize) {
-                if offset < 0
-                    || offset >= field.len() as isize
-                    || rx < 0
-                    || rx >= FIELD_WIDTH as isize
-                    || ry < 0
-                    || ry >= FIELD_HEIGHT as isize
-                {
-                    // out of bounds
-                    return false;
-                }
+impl TetrisProfiles {
+    fn new() -> Self {
+        let profiles = Profiles::load_or_default();
+        let selection = profiles
+            .all()
+            .iter()
+            .position(|profile| profile == profiles.active())
+            .unwrap_or(0);
+        Self {
+            profiles,
+            selection,
+            editing: None,
+            confirm_delete: false,
+            notice: None,
+            last_input: PlayerInput::all_pressed(),
+            clock: FixedTimestep::default(),
+        }
+    }
 
-                if field[offset as usize] != Cell::Empty {
-                    // filled
-                    return false;
-                }
+    fn rows(&self) -> usize {
+        self.profiles.all().len() + 2
+    }
+
+    /// The selected profile's name, if a profile is selected
+    fn selected(&self) -> Option<String> {
+        self.profiles
+            .all()
+            .get(self.selection)
+            .map(|profile| profile.name.clone())
+    }
+
+    /// Carries out the edit whose name has been typed in
+    fn finish_edit(&mut self, edit: ProfileEdit, name: &str) {
+        let result = match edit {
+            ProfileEdit::Create => self.profiles.create(name),
+            ProfileEdit::Rename(index) => {
+                let old = self.profiles.all()[index].name.clone();
+                self.profiles.rename(&old, name).map(|()| {
+                    // the leaderboards follow the profile to its new name
+                    scores::rename_profile(&old, name)
+                        .unwrap_or_else(|e| eprintln!("Couldn't rename scores: {}", e));
+                })
+            }
+        };
+        match result {
+            Ok(()) => {
+                self.notice = None;
+                self.save();
             }
+            Err(e) => self.notice = Some(capitalize(&e.to_string())),
         }
     }
 
-    true
-}
+    fn delete_selected(&mut self) {
+        let name = match self.selected() {
+            Some(name) => name,
+            None => return,
+        };
+        if !self.confirm_delete {
+            self.confirm_delete = true;
+            self.notice = Some(format!("Press Delete again to delete {}", name));
+            return;
+        }
 
-fn add_piece(piece: &Piece, field: &mut Field) {
-    for y in 0..4 {
-        for x in 0..4 {
-            if piece.filled_at(x as usize, y as usize) {
-                let offset = (piece.x as isize + x) + (piece.y + y) as isize * FIELD_WIDTH as isize;
-                if offset >= 0 && offset < field.len() as isize {
-                    field[offset as usize] = Cell::Full(piece.color);
-                }
+        self.confirm_delete = false;
+        match self.profiles.delete(&name) {
+            Ok(()) => {
+                self.notice = Some(format!("Deleted {}", name));
+                self.selection = self.selection.min(self.profiles.all().len() - 1);
+                self.save();
             }
+            Err(e) => self.notice = Some(capitalize(&e.to_string())),
         }
     }
-}
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-enum KeyState {
-    Pressed,
-    Holding,
-    Released,
+    fn save(&self) {
+        self.profiles
+            .store()
+            .unwrap_or_else(|e| eprintln!("Couldn't save profiles: {}", e));
+    }
 }
 
-impl Default for KeyState {
-    fn default() -> Self {
-        Self::Released
+/// `message` starting with a capital, for putting an error on screen
+fn capitalize(message: &str) -> String {
+    let mut chars = message.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
     }
 }
 
-#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
-struct PlayerInput {
-    up: KeyState,
-    down: KeyState,
-    left: KeyState,
-    right: KeyState,
-    rot_right: KeyState,
-    rot_left: KeyState,
-    escape: KeyState,
-}
+impl GameState for TetrisProfiles {
+    fn update(
+        &mut self,
+        snapshot: &InputSnapshot,
+        dt: std::time::Duration,
+    ) -> lib::game::StateChange {
+        self.clock.accumulate(dt);
+
+        // text comes a frame at a time, not a tick at a time
+        if let Some((_, name)) = &mut self.editing {
+            name.type_text(&snapshot.text);
+        }
 
-impl PlayerInput {
-    fn all_pressed() -> Self {
-        Self {
-            up: KeyState::Holding,
-            down: KeyState::Holding,
-            left: KeyState::Holding,
-            right: KeyState::Holding,
-            rot_right: KeyState::Holding,
-            rot_left: KeyState::Holding,
-            escape: KeyState::Holding,
+        while self.clock.tick() {
+            let since = self.last_input.frame;
+            let input = input(snapshot, self.last_input);
+            self.last_input = input;
+
+            if let Some((edit, name)) = &mut self.editing {
+                if input.clear == KeyState::Pressed {
+                    name.backspace();
+                } else if input.enter == KeyState::Pressed {
+                    let (edit, name) = (*edit, name.text().to_string());
+                    self.editing = None;
+                    self.finish_edit(edit, &name);
+                } else if input.escape == KeyState::Pressed {
+                    self.editing = None;
+                }
+                continue;
+            }
+
+            let pressed = |key: Key| snapshot.pressed_since(key, since);
+            if pressed(Key::Delete) {
+                self.delete_selected();
+                continue;
+            }
+            if input.any_pressed() {
+                self.confirm_delete = false;
+            }
+
+            let count = self.profiles.all().len();
+            if input.backed_out() {
+                return lib::game::StateChange::Pop;
+            } else if input.confirmed() {
+                if let Some(name) = self.selected() {
+                    self.profiles
+                        .select(&name)
+                        .unwrap_or_else(|e| eprintln!("Couldn't pick profile: {}", e));
+                    self.notice = Some(format!("Playing as {}", name));
+                    self.save();
+                } else if self.selection == count {
+                    self.editing =
+                        Some((ProfileEdit::Create, TextField::new(scores::MAX_NAME_CHARS)));
+                    self.notice = None;
+                } else {
+                    return lib::game::StateChange::Pop;
+                }
+            } else if pressed(Key::R) && self.selection < count {
+                self.editing = Some((
+                    ProfileEdit::Rename(self.selection),
+                    TextField::new(scores::MAX_NAME_CHARS),
+                ));
+                self.notice = None;
+            } else if input.up == KeyState::Pressed {
+                self.selection = (self.selection + self.rows() - 1) % self.rows();
+                self.notice = None;
+            } else if input.down == KeyState::Pressed {
+                self.selection = (self.selection + 1) % self.rows();
+                self.notice = None;
+            }
         }
+
+        lib::game::StateChange::None
     }
-}
 
-fn input(window: &glfw::Window, last_input: PlayerInput) -> PlayerInput {
-    fn map(a: Action, prev: KeyState) -> KeyState {
-        let this = match a {
-            Action::Press | Action::Repeat => KeyState::Pressed,
-            Action::Release => KeyState::Released,
+    fn render(&self, graphics: &lib::graphics::GraphicsState, frame: &mut Frame) {
+        let cursor = if self.clock.ticker() % 20 < 10 {
+            "_"
+        } else {
+            ""
+        };
+        let typed = |edit: ProfileEdit| match &self.editing {
+            Some((editing, name)) if *editing == edit => {
+                Some(format!("< {}{} >", name.text(), cursor))
+            }
+            _ => None,
         };
 
-        match (this, prev) {
-            (KeyState::Pressed, KeyState::Pressed) | (KeyState::Pressed, KeyState::Holding) => {
-                KeyState::Holding
+        let active = self.profiles.active();
+        let mut rows: Vec<String> = self
+            .profiles
+            .all()
+            .iter()
+            .enumerate()
+            .map(|(i, profile)| {
+                typed(ProfileEdit::Rename(i)).unwrap_or_else(|| {
+                    if profile == active {
+                        format!("{} (playing)", profile.name)
+                    } else {
+                        profile.name.clone()
+                    }
+                })
+            })
+            .collect();
+        rows.push(typed(ProfileEdit::Create).unwrap_or_else(|| "New profile".to_string()));
+        rows.push("Back".to_string());
+
+        // the notice goes last, or else how the selected profile has been
+        // doing and what can be done with it
+        let selected = self.profiles.all().get(self.selection);
+        match (&self.editing, &self.notice, selected) {
+            (Some(_), _, _) => rows.push("Type a name, Enter to save".to_string()),
+            (_, Some(notice), _) => rows.push(notice.clone()),
+            (_, _, Some(profile)) => {
+                let sprint = profile
+                    .best_sprint_millis
+                    .map_or_else(|| "-".to_string(), format_time);
+                rows.push(format!(
+                    "{} games, {} lines, sprint {}",
+                    profile.games, profile.lines, sprint
+                ));
+                rows.push("R renames, Delete deletes".to_string());
             }
-            (KeyState::Pressed, KeyState::Released) => KeyState::Pressed,
-            (KeyState::Released, _) => KeyState::Released,
-            _ => unreachable!(),
+            _ => (),
         }
+
+        let entries: Vec<&str> = rows.iter().map(String::as_str).collect();
+        render_menu(graphics, frame, "Profiles", &entries, self.selection);
     }
 
-    PlayerInput {
-        up: map(window.get_key(Key::Up), last_input.up),
-        down: map(window.get_key(Key::Down), last_input.down),
-        left: map(window.get_key(Key::Left), last_input.left),
-        right: map(window.get_key(Key::Right), last_input.right),
-        rot_left: map(window.get_key(Key::X), last_input.rot_left),
-        rot_right: map(window.get_key(Key::Z), last_input.rot_right),
-        escape: map(window.get_key(Key::Escape), last_input.escape),
+    fn takes_text(&self) -> bool {
+        self.editing.is_some()
     }
 }
 
-fn main() {
-    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
-    glfw.window_hint(glfw::WindowHint::ClientApi(glfw::ClientApiHint::NoApi));
+struct TetrisModeSelect {
+    /// Index into `GameMode::ALL` of the highlighted mode
+    selection: usize,
 
-    let (mut window, events) = glfw
-        .create_window(800, 600, "tet.rs", glfw::WindowMode::Windowed)
-        .expect("Failed to create window.");
+    /// How many upcoming pieces the game will show
+    previews: usize,
 
-    window.set_key_polling(true);
-    window.set_size_polling(true);
+    /// Whether the game is played at 20G
+    instant_gravity: bool,
 
-    let mut states: Vec<Box<dyn GameState>> = vec![Box::new(TetrisMenu::default())];
-    let mut graphics = futures::executor::block_on(lib::graphics::GraphicsState::new(&window));
-    let mut last_frame = std::time::Instant::now();
+    /// Level the game starts at, in modes that level up
+    start_level: u32,
 
-    while !window.should_close() {
-        let state = states.last_mut().unwrap();
+    /// Seed to deal the game from, or None for a random one
+    seed: Option<u64>,
 
-        // timing
-        let frame = std::time::Instant::now();
-        let dt = frame - last_frame;
-        last_frame = frame;
+    /// Options the game is started with
+    config: SharedConfig,
 
-        // update
-        let update_result = state.update(&window, dt);
+    /// Runs played so far, to compare the next against
+    session: SharedSession,
 
-        // render
-        match state.render(&graphics) {
-            Err(wgpu::SwapChainError::OutOfMemory) => window.set_should_close(true),
-            Err(wgpu::SwapChainError::Lost) | Err(wgpu::SwapChainError::Outdated) => {
-                graphics.resize(graphics.sc_desc.width, graphics.sc_desc.height)
-            }
-            _ => (),
-        };
+    /// Previous frame player input
+    last_input: PlayerInput,
 
-        match update_result {
-            lib::game::StateChange::None => {} // do nothing
-            lib::game::StateChange::Quit => {
-                // quit the game
-                window.set_should_close(true)
-            }
-            lib::game::StateChange::Push(state) => {
-                // push a new state
-                states.push(state);
+    /// Paces updates to the tick rate and counts the ticks
+    clock: FixedTimestep,
+}
+
+impl Default for TetrisModeSelect {
+    fn default() -> Self {
+        Self {
+            selection: 0,
+            previews: DEFAULT_PREVIEWS,
+            instant_gravity: false,
+            start_level: 1,
+            seed: None,
+            config: SharedConfig::default(),
+            session: SharedSession::default(),
+            last_input: PlayerInput::all_pressed(),
+            clock: FixedTimestep::default(),
+        }
+    }
+}
+
+impl GameState for TetrisModeSelect {
+    fn update(
+        &mut self,
+        snapshot: &InputSnapshot,
+        dt: std::time::Duration,
+    ) -> lib::game::StateChange {
+        self.clock.accumulate(dt);
+
+        while self.clock.tick() {
+            let input = input(snapshot, self.last_input);
+            self.last_input = input;
+            let modes = GameMode::ALL.len();
+            if input.confirmed() {
+                // start a game in the chosen mode
+                let mode = GameMode::ALL[self.selection];
+                if mode == GameMode::Puzzle {
+                    // puzzles are picked from their own list
+                    return lib::game::StateChange::Swap(Box::new(TetrisPuzzleSelect {
+                        config: self.config.clone(),
+                        session: self.session.clone(),
+                        ..TetrisPuzzleSelect::default()
+                    }));
+                }
+                if mode == GameMode::Practice {
+                    // practice can be played with other pieces, picked first
+                    return lib::game::StateChange::Swap(Box::new(TetrisPieceSelect {
+                        previews: self.previews,
+                        start_level: self.start_level,
+                        instant_gravity: self.instant_gravity,
+                        seed: self.seed,
+                        config: self.config.clone(),
+                        session: self.session.clone(),
+                        ..TetrisPieceSelect::default()
+                    }));
+                }
+                if mode == GameMode::Versus {
+                    return lib::game::StateChange::Swap(Box::new(TetrisVersus::new(
+                        self.previews,
+                        self.start_level,
+                        self.instant_gravity,
+                        self.seed,
+                        self.config.clone(),
+                    )));
+                }
+                let mut sim = Simulation::with_level(
+                    mode,
+                    self.previews,
+                    self.start_level,
+                    self.seed,
+                    self.config.borrow().randomizer,
+                );
+                sim.instant_gravity = self.instant_gravity;
+                sim.set_rotation(self.config.borrow().rotation);
+                return lib::game::StateChange::Swap(Box::new(TetrisMain {
+                    seed: self.seed,
+                    ..TetrisMain::new(sim, self.config.clone())
+                }));
+            } else if input.backed_out() {
+                return lib::game::StateChange::Pop;
+            } else if input.up == KeyState::Pressed {
+                self.selection = (self.selection + modes - 1) % modes;
+            } else if input.down == KeyState::Pressed {
+                self.selection = (self.selection + 1) % modes;
+            } else if input.left == KeyState::Pressed {
+                self.previews = usize::max(self.previews - 1, MIN_PREVIEWS);
+            } else if input.right == KeyState::Pressed {
+                self.previews = usize::min(self.previews + 1, MAX_PREVIEWS);
+            } else if input.instant_gravity == KeyState::Pressed {
+                self.instant_gravity = !self.instant_gravity;
+            }
+        }
+
+        lib::game::StateChange::None
+    }
+
+    fn render(&self, graphics: &lib::graphics::GraphicsState, frame: &mut Frame) {
+        // render text
+        let mut text = graphics.scratch_mesh();
+
+        let layout = graphics.layout();
+
+        let title = layout.title("Mode");
+        lib::graphics::text::render_text(
+            &mut text,
+            "Mode",
+            title.x,
+            title.y,
+            title.size,
+            graphics.theme.active_text,
+        );
+
+        let next_txt = format!(
+            "< Next: {} >  G: 20G {}",
+            self.previews,
+            if self.instant_gravity { "on" } else { "off" }
+        );
+        let subtitle = layout.subtitle(&next_txt);
+        lib::graphics::text::render_text(
+            &mut text,
+            &next_txt,
+            subtitle.x,
+            subtitle.y,
+            subtitle.size,
+            graphics.theme.inactive_text,
+        );
+
+        for (i, mode) in GameMode::ALL.iter().enumerate() {
+            let name = mode.name();
+            let placement = layout.menu_entry(i, GameMode::ALL.len(), name);
+            lib::graphics::text::render_text(
+                &mut text,
+                name,
+                placement.x,
+                placement.y,
+                placement.size,
+                if self.selection == i {
+                    graphics.theme.active_text
+                } else {
+                    graphics.theme.inactive_text
+                },
+            );
+        }
+
+        // create buffers
+        let text_mesh = graphics.upload(&graphics.text_mesh, &text);
+
+        // render!
+        let mut command_buf =
+            graphics
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("command_buf"),
+                });
+        {
+            let mut pass = frame.begin_pass(&mut command_buf);
+
+            // draw text
+            pass.set_pipeline(&graphics.text_pipeline);
+            pass.set_vertex_buffer(0, text_mesh.vertices());
+            pass.set_index_buffer(text_mesh.indices(), wgpu::IndexFormat::Uint32);
+            pass.set_bind_group(0, graphics.transform_bind_group(0), &[]);
+            pass.set_bind_group(1, graphics.text_bind_group(), &[]);
+            pass.draw_indexed(0..text_mesh.index_count(), 0, 0..1);
+        }
+        graphics.queue.submit(std::iter::once(command_buf.finish()));
+    }
+}
+
+/// Lists the puzzle files to choose from
+struct TetrisPuzzleSelect {
+    /// Puzzle files found on disk
+    paths: Vec<std::path::PathBuf>,
+
+    /// Index into `paths` of the highlighted puzzle
+    selection: usize,
+
+    /// Why the last puzzle picked couldn't be played
+    error: Option<String>,
+
+    /// Options the puzzle is played with
+    config: SharedConfig,
+
+    /// Runs played so far, to compare the next against
+    session: SharedSession,
+
+    /// Previous frame player input
+    last_input: PlayerInput,
+
+    /// Paces updates to the tick rate and counts the ticks
+    clock: FixedTimestep,
+}
+
+impl Default for TetrisPuzzleSelect {
+    fn default() -> Self {
+        Self {
+            paths: puzzle::list(puzzle::PUZZLE_DIR),
+            selection: 0,
+            error: None,
+            config: SharedConfig::default(),
+            session: SharedSession::default(),
+            last_input: PlayerInput::all_pressed(),
+            clock: FixedTimestep::default(),
+        }
+    }
+}
+
+impl GameState for TetrisPuzzleSelect {
+    fn update(
+        &mut self,
+        snapshot: &InputSnapshot,
+        dt: std::time::Duration,
+    ) -> lib::game::StateChange {
+        self.clock.accumulate(dt);
+
+        while self.clock.tick() {
+            let input = input(snapshot, self.last_input);
+            self.last_input = input;
+            let count = self.paths.len();
+            if input.backed_out() {
+                return lib::game::StateChange::Pop;
+            } else if count == 0 {
+                continue;
+            } else if input.confirmed() {
+                let path = &self.paths[self.selection];
+                match Puzzle::load(path) {
+                    Ok(puzzle) => {
+                        self.error = None;
+                        let mut sim = Simulation::from_puzzle(puzzle);
+                        sim.set_rotation(self.config.borrow().rotation);
+                        return lib::game::StateChange::Push(Box::new(TetrisMain::new(
+                            sim,
+                            self.config.clone(),
+                        )));
+                    }
+                    Err(e) => {
+                        eprintln!("Could not load puzzle {}: {}", path.display(), e);
+                        self.error = Some(e.to_string());
+                    }
+                }
+            } else if input.up == KeyState::Pressed {
+                self.selection = (self.selection + count - 1) % count;
+            } else if input.down == KeyState::Pressed {
+                self.selection = (self.selection + 1) % count;
+            }
+        }
+
+        lib::game::StateChange::None
+    }
+
+    fn on_resume(&mut self) {
+        // keys still held from the state above don't count as new presses
+        self.last_input = PlayerInput::all_pressed();
+    }
+
+    fn on_result(&mut self, result: Box<dyn Any>) -> lib::game::StateChange {
+        RunResult::reveal(result, &self.session)
+    }
+
+    fn render(&self, graphics: &lib::graphics::GraphicsState, frame: &mut Frame) {
+        // render text
+        let mut text = graphics.scratch_mesh();
+
+        let layout = graphics.layout();
+
+        let title = layout.title("Puzzle");
+        lib::graphics::text::render_text(
+            &mut text,
+            "Puzzle",
+            title.x,
+            title.y,
+            title.size,
+            graphics.theme.active_text,
+        );
+
+        let notice = if self.paths.is_empty() {
+            Some(format!("No puzzles in {}/", puzzle::PUZZLE_DIR))
+        } else {
+            self.error.clone()
+        };
+        if let Some(notice) = notice {
+            let subtitle = layout.subtitle(&notice);
+            lib::graphics::text::render_text(
+                &mut text,
+                &notice,
+                subtitle.x,
+                subtitle.y,
+                subtitle.size,
+                graphics.theme.inactive_text,
+            );
+        }
+
+        for (i, path) in self.paths.iter().enumerate() {
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let placement = layout.menu_entry(i, self.paths.len(), &name);
+            lib::graphics::text::render_text(
+                &mut text,
+                &name,
+                placement.x,
+                placement.y,
+                placement.size,
+                if self.selection == i {
+                    graphics.theme.active_text
+                } else {
+                    graphics.theme.inactive_text
+                },
+            );
+        }
+
+        // create buffers
+        let text_mesh = graphics.upload(&graphics.text_mesh, &text);
+
+        // render!
+        let mut command_buf =
+            graphics
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("command_buf"),
+                });
+        {
+            let mut pass = frame.begin_pass(&mut command_buf);
+
+            // draw text
+            pass.set_pipeline(&graphics.text_pipeline);
+            pass.set_vertex_buffer(0, text_mesh.vertices());
+            pass.set_index_buffer(text_mesh.indices(), wgpu::IndexFormat::Uint32);
+            pass.set_bind_group(0, graphics.transform_bind_group(0), &[]);
+            pass.set_bind_group(1, graphics.text_bind_group(), &[]);
+            pass.draw_indexed(0..text_mesh.index_count(), 0, 0..1);
+        }
+        graphics.queue.submit(std::iter::once(command_buf.finish()));
+    }
+}
+
+/// Lists the sets of pieces practice can be played with
+struct TetrisPieceSelect {
+    /// Piece set files found on disk, listed after the built in tetrominoes
+    paths: Vec<std::path::PathBuf>,
+
+    /// Index of the highlighted set, 0 being the tetrominoes
+    selection: usize,
+
+    /// Why the last set picked couldn't be played
+    error: Option<String>,
+
+    /// Game settings carried over from the mode select
+    previews: usize,
+    start_level: u32,
+    instant_gravity: bool,
+    seed: Option<u64>,
+
+    /// Options the game is played with
+    config: SharedConfig,
+
+    /// Runs played so far, to compare the next against
+    session: SharedSession,
+
+    /// Previous frame player input
+    last_input: PlayerInput,
+
+    /// Paces updates to the tick rate and counts the ticks
+    clock: FixedTimestep,
+}
+
+impl Default for TetrisPieceSelect {
+    fn default() -> Self {
+        Self {
+            paths: assets::find(set::PIECE_SET_DIR)
+                .map(|dir| set::list(&dir))
+                .unwrap_or_default(),
+            selection: 0,
+            error: None,
+            previews: DEFAULT_PREVIEWS,
+            start_level: 1,
+            instant_gravity: false,
+            seed: None,
+            config: SharedConfig::default(),
+            session: SharedSession::default(),
+            last_input: PlayerInput::all_pressed(),
+            clock: FixedTimestep::default(),
+        }
+    }
+}
+
+impl TetrisPieceSelect {
+    /// The highlighted set. One that can't be loaded is reported and the
+    /// tetrominoes are played instead
+    fn chosen(&mut self) -> PieceSet {
+        self.error = None;
+        let path = match self.selection.checked_sub(1) {
+            Some(i) => &self.paths[i],
+            None => return set::STANDARD,
+        };
+        match PieceSet::load(path) {
+            Ok(pieces) => pieces,
+            Err(e) => {
+                eprintln!("Couldn't load piece set {}: {}", path.display(), e);
+                self.error = Some(e.to_string());
+                set::STANDARD
+            }
+        }
+    }
+}
+
+impl GameState for TetrisPieceSelect {
+    fn update(
+        &mut self,
+        snapshot: &InputSnapshot,
+        dt: std::time::Duration,
+    ) -> lib::game::StateChange {
+        self.clock.accumulate(dt);
+
+        while self.clock.tick() {
+            let input = input(snapshot, self.last_input);
+            self.last_input = input;
+            let count = self.paths.len() + 1;
+            if input.backed_out() {
+                return lib::game::StateChange::Pop;
+            } else if input.confirmed() {
+                let pieces = self.chosen();
+                let mut sim = Simulation::with_level(
+                    GameMode::Practice,
+                    self.previews,
+                    self.start_level,
+                    self.seed,
+                    self.config.borrow().randomizer,
+                );
+                sim.instant_gravity = self.instant_gravity;
+                sim.set_rotation(self.config.borrow().rotation);
+                sim.use_pieces(pieces);
+                return lib::game::StateChange::Push(Box::new(TetrisMain {
+                    seed: self.seed,
+                    ..TetrisMain::new(sim, self.config.clone())
+                }));
+            } else if input.up == KeyState::Pressed {
+                self.selection = (self.selection + count - 1) % count;
+            } else if input.down == KeyState::Pressed {
+                self.selection = (self.selection + 1) % count;
+            }
+        }
+
+        lib::game::StateChange::None
+    }
+
+    fn on_resume(&mut self) {
+        // keys still held from the state above don't count as new presses
+        self.last_input = PlayerInput::all_pressed();
+    }
+
+    fn on_result(&mut self, result: Box<dyn Any>) -> lib::game::StateChange {
+        RunResult::reveal(result, &self.session)
+    }
+
+    fn render(&self, graphics: &lib::graphics::GraphicsState, frame: &mut Frame) {
+        // render text
+        let mut text = graphics.scratch_mesh();
+
+        let layout = graphics.layout();
+
+        let title = layout.title("Pieces");
+        lib::graphics::text::render_text(
+            &mut text,
+            "Pieces",
+            title.x,
+            title.y,
+            title.size,
+            graphics.theme.active_text,
+        );
+
+        if let Some(error) = &self.error {
+            let subtitle = layout.subtitle(error);
+            lib::graphics::text::render_text(
+                &mut text,
+                error,
+                subtitle.x,
+                subtitle.y,
+                subtitle.size,
+                graphics.theme.inactive_text,
+            );
+        }
+
+        let names: Vec<String> = std::iter::once(set::STANDARD.name.to_string())
+            .chain(self.paths.iter().map(|path| {
+                path.file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            }))
+            .collect();
+        for (i, name) in names.iter().enumerate() {
+            let placement = layout.menu_entry(i, names.len(), name);
+            lib::graphics::text::render_text(
+                &mut text,
+                name,
+                placement.x,
+                placement.y,
+                placement.size,
+                if self.selection == i {
+                    graphics.theme.active_text
+                } else {
+                    graphics.theme.inactive_text
+                },
+            );
+        }
+
+        // create buffers
+        let text_mesh = graphics.upload(&graphics.text_mesh, &text);
+
+        // render!
+        let mut command_buf =
+            graphics
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("command_buf"),
+                });
+        {
+            let mut pass = frame.begin_pass(&mut command_buf);
+
+            // draw text
+            pass.set_pipeline(&graphics.text_pipeline);
+            pass.set_vertex_buffer(0, text_mesh.vertices());
+            pass.set_index_buffer(text_mesh.indices(), wgpu::IndexFormat::Uint32);
+            pass.set_bind_group(0, graphics.transform_bind_group(0), &[]);
+            pass.set_bind_group(1, graphics.text_bind_group(), &[]);
+            pass.draw_indexed(0..text_mesh.index_count(), 0, 0..1);
+        }
+        graphics.queue.submit(std::iter::once(command_buf.finish()));
+    }
+}
+
+/// Shown after a successful timed run, before the result goes to the leaderboard
+struct TetrisResults {
+    /// The run's final time (in milliseconds) and how it went
+    entry: ScoreEntry,
+
+    /// Best time before the run, to show how far off it the run was
+    best_millis: Option<u64>,
+
+    /// Splits of the profile's best time on the leaderboard, to show how far
+    /// off them the run's were
+    best_splits: Vec<u64>,
+
+    /// Previous frame input
+    last_input: PlayerInput,
+
+    /// Paces updates to the tick rate and counts the ticks
+    clock: FixedTimestep,
+}
+
+impl TetrisResults {
+    fn new(entry: ScoreEntry, best_millis: Option<u64>) -> Self {
+        // the run isn't on the leaderboard until it's named, so the best
+        // there is from before it
+        let best_splits = if entry.splits.is_empty() {
+            Vec::new()
+        } else {
+            scores::load(entry.mode)
+                .ok()
+                .and_then(|loaded| {
+                    loaded
+                        .entries
+                        .into_iter()
+                        .find(|e| e.mode == entry.mode && e.profile == entry.profile)
+                })
+                .map(|best| best.splits)
+                .unwrap_or_default()
+        };
+        Self {
+            entry,
+            best_millis,
+            best_splits,
+            last_input: PlayerInput::all_pressed(),
+            clock: FixedTimestep::default(),
+        }
+    }
+}
+
+impl GameState for TetrisResults {
+    fn update(
+        &mut self,
+        snapshot: &InputSnapshot,
+        dt: std::time::Duration,
+    ) -> lib::game::StateChange {
+        self.clock.accumulate(dt);
+
+        while self.clock.tick() {
+            let input = input(snapshot, self.last_input);
+            self.last_input = input;
+            if input.confirmed() || input.backed_out() {
+                // hand the result over to the leaderboard
+                let scores = TetrisScores {
+                    inputting_score: Some(self.entry.clone()),
+                    ..TetrisScores::new(self.entry.mode)
+                };
+                return lib::game::StateChange::Swap(Box::new(scores));
+            }
+        }
+
+        lib::game::StateChange::None
+    }
+
+    fn render(&self, graphics: &lib::graphics::GraphicsState, frame: &mut Frame) {
+        // render text
+        let mut text = graphics.scratch_mesh();
+
+        let layout = graphics.layout();
+
+        let title = self.entry.mode.name();
+        let placement = layout.title(title);
+        lib::graphics::text::render_text(
+            &mut text,
+            title,
+            placement.x,
+            placement.y,
+            placement.size,
+            graphics.theme.active_text,
+        );
+
+        for (i, &split) in self.entry.splits.iter().enumerate() {
+            let (delta, color) = match self.best_splits.get(i) {
+                Some(&best) if split < best => (
+                    format!("-{}", format_time(best - split)),
+                    graphics.theme.active_text,
+                ),
+                Some(&best) => (
+                    format!("+{}", format_time(split - best)),
+                    graphics.theme.inactive_text,
+                ),
+                None => (String::new(), graphics.theme.active_text),
+            };
+            let lines = (i as u32 + 1) * SPLIT_LINES;
+            let row = format!("{:>2}  {:>7}  {:>8}", lines, format_time(split), delta);
+            let placement = layout.split_row(i);
+            lib::graphics::text::render_text_mono(
+                &mut text,
+                &row,
+                placement.x,
+                placement.y,
+                placement.size,
+                color,
+            );
+        }
+
+        let value = self.entry.mode.format_value(self.entry.value);
+        let placement = layout.result(&value);
+        lib::graphics::text::render_text(
+            &mut text,
+            &value,
+            placement.x,
+            placement.y,
+            placement.size,
+            graphics.theme.active_text,
+        );
+
+        if let Some(best) = self.best_millis {
+            let time = self.entry.value;
+            let (delta, color) = if time < best {
+                (
+                    format!("-{} vs best", format_time(best - time)),
+                    graphics.theme.highlight,
+                )
+            } else {
+                (
+                    format!("+{} vs best", format_time(time - best)),
+                    graphics.theme.inactive_text,
+                )
+            };
+            let placement = layout.result_delta(&delta);
+            lib::graphics::text::render_text(
+                &mut text,
+                &delta,
+                placement.x,
+                placement.y,
+                placement.size,
+                color,
+            );
+        }
+
+        if self.clock.ticker() % 20 < 10 {
+            let placement = layout.prompt("Press Z");
+            lib::graphics::text::render_text(
+                &mut text,
+                "Press Z",
+                placement.x,
+                placement.y,
+                placement.size,
+                graphics.theme.inactive_text,
+            );
+        }
+
+        // create buffers
+        let text_mesh = graphics.upload(&graphics.text_mesh, &text);
+
+        // render!
+        let mut command_buf =
+            graphics
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("command_buf"),
+                });
+        {
+            let mut pass = frame.begin_pass(&mut command_buf);
+
+            // draw text
+            pass.set_pipeline(&graphics.text_pipeline);
+            pass.set_vertex_buffer(0, text_mesh.vertices());
+            pass.set_index_buffer(text_mesh.indices(), wgpu::IndexFormat::Uint32);
+            pass.set_bind_group(0, graphics.transform_bind_group(0), &[]);
+            pass.set_bind_group(1, graphics.text_bind_group(), &[]);
+            pass.draw_indexed(0..text_mesh.index_count(), 0, 0..1);
+        }
+        graphics.queue.submit(std::iter::once(command_buf.finish()));
+    }
+}
+
+struct TetrisSummary {
+    /// Mode the run was played in
+    mode: GameMode,
+
+    /// Statistics of the run that just ended
+    stats: GameStats,
+
+    /// How the run did against the last one of its mode this session, if
+    /// there was one
+    delta: Option<StatsDelta>,
+
+    /// How copying the summary went, and ticks left to show it for
+    notice: Option<(String, u64)>,
+
+    /// Seed the run was dealt from, how, and how its pieces turned
+    seed: u64,
+    randomizer: RandomizerKind,
+    rotation: RotationKind,
+
+    /// Achievements the run unlocked as it ended, still to be announced
+    unlocks: UnlockToasts,
+
+    /// Screen to move on to, or None to return to the menu
+    next: Option<Box<dyn GameState>>,
+
+    /// Whether the chart of the run is showing rather than its numbers
+    graph: bool,
+
+    /// Previous frame input
+    last_input: PlayerInput,
+
+    /// Paces updates to the tick rate and counts the ticks
+    clock: FixedTimestep,
+}
+
+impl TetrisSummary {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        mode: GameMode,
+        stats: GameStats,
+        delta: Option<StatsDelta>,
+        seed: u64,
+        randomizer: RandomizerKind,
+        rotation: RotationKind,
+        unlocked: Vec<&'static Achievement>,
+        next: Option<Box<dyn GameState>>,
+    ) -> Self {
+        let mut unlocks = UnlockToasts::default();
+        unlocks.extend(unlocked);
+        Self {
+            mode,
+            stats,
+            delta,
+            notice: None,
+            seed,
+            randomizer,
+            rotation,
+            unlocks,
+            next,
+            graph: false,
+            last_input: PlayerInput::all_pressed(),
+            clock: FixedTimestep::default(),
+        }
+    }
+
+    /// The run in a line, and against the last one when there was one, for
+    /// pasting somewhere
+    fn clipboard_text(&self) -> String {
+        let mut text = format!(
+            "tet.rs {}: {} points, {} lines, {:.2} PPS, {:.0}% tetrises, {}",
+            self.mode.name(),
+            self.stats.score(),
+            self.stats.lines,
+            self.stats.pieces_per_second(FRAME_TIME),
+            self.stats.tetris_rate() * 100.0,
+            format_time(self.stats.play_time(FRAME_TIME).as_millis() as u64)
+        );
+        if let Some(delta) = &self.delta {
+            let _ = write!(text, " (vs last run: {})", delta);
+        }
+        text
+    }
+
+    /// Pieces and clears in two columns, and where the pieces went, with how
+    /// it compares to the last run in a third
+    fn render_stats(
+        &self,
+        text: &mut MeshBuilder,
+        geometry: &mut MeshBuilder,
+        layout: &Layout,
+        theme: &Theme,
+    ) {
+        // pad each row with dots so the values line up on the right
+        let row = |label: &str, value: &dyn std::fmt::Display| {
+            format!(
+                "{}{:.>width$}",
+                label,
+                value,
+                width = SUMMARY_COLUMN_CHARS.saturating_sub(label.len())
+            )
+        };
+
+        // left column: pieces placed, each in its own color
+        let mut pieces: Vec<(String, [f32; 3])> = Tetromino::ALL
+            .iter()
+            .zip(self.stats.pieces.iter())
+            .map(|(t, count)| (row(t.name(), count), theme.piece(t.color())))
+            .collect();
+        if self.stats.other_pieces > 0 {
+            pieces.push((row("Others", &self.stats.other_pieces), theme.inactive_text));
+        }
+        pieces.push((row("Total", &self.stats.total_pieces()), theme.active_text));
+
+        // right column: how the lines came off
+        let millis = self.stats.play_time(FRAME_TIME).as_millis() as u64;
+        let clears = [
+            ("Singles", self.stats.clears[0].to_string()),
+            ("Doubles", self.stats.clears[1].to_string()),
+            ("Triples", self.stats.clears[2].to_string()),
+            ("Tetrises", self.stats.clears[3].to_string()),
+            ("Lines", self.stats.lines.to_string()),
+            ("Max combo", self.stats.max_combo.to_string()),
+            (
+                "PPS",
+                format!("{:.2}", self.stats.pieces_per_second(FRAME_TIME)),
+            ),
+            ("Time", format_time(millis)),
+            (
+                "Decision",
+                self.stats
+                    .median_decision_time(FRAME_TIME)
+                    .map(|t| format!("{:.2}s", t.as_secs_f32()))
+                    .unwrap_or_else(|| "-".to_string()),
+            ),
+        ];
+
+        let clears = clears
+            .iter()
+            .map(|(label, value)| (row(label, value), theme.active_text))
+            .collect();
+
+        // against the last run, a column of its own when there was one
+        let mut columns = vec![pieces, clears];
+        if let Some(delta) = &self.delta {
+            let mut changes = vec![(
+                format!("{:<width$}", "vs last", width = SUMMARY_COLUMN_CHARS),
+                theme.inactive_text,
+            )];
+            for (label, change, direction) in delta.changes().iter() {
+                let color = match direction {
+                    std::cmp::Ordering::Greater => theme.pieces.green,
+                    std::cmp::Ordering::Less => theme.pieces.red,
+                    std::cmp::Ordering::Equal => theme.inactive_text,
+                };
+                changes.push((row(label, change), color));
+            }
+            columns.push(changes);
+        }
+
+        for (column, rows) in columns.iter().enumerate() {
+            for (i, (txt, color)) in rows.iter().enumerate() {
+                let placement = layout.summary_row(column, columns.len(), i);
+                lib::graphics::text::render_text_mono(
+                    text,
+                    txt,
+                    placement.x,
+                    placement.y,
+                    placement.size,
+                    *color,
+                );
+            }
+        }
+
+        // where the pieces went, a cell for each column
+        let heatmap = &self.stats.columns;
+        for column in 0..FIELD_WIDTH as usize {
+            let cell = layout.heatmap_cell(column);
+            let color = if heatmap.count(column) == 0 {
+                theme.well
+            } else {
+                heat_color(theme, heatmap.heat(column))
+            };
+            push_cell(
+                geometry,
+                cell.x,
+                cell.y,
+                cell.width,
+                cell.height,
+                opaque(color),
+                theme,
+            );
+        }
+        if let (Some(busiest), Some(quietest)) =
+            (heatmap.busiest(), heatmap.quietest(FIELD_WIDTH as usize))
+        {
+            let note = format!(
+                "Most used: column {}   Least used: column {}",
+                busiest + 1,
+                quietest + 1
+            );
+            let placement = layout.heatmap_note(&note);
+            lib::graphics::text::render_text(
+                text,
+                &note,
+                placement.x,
+                placement.y,
+                placement.size,
+                theme.inactive_text,
+            );
+        }
+    }
+
+    /// Score and lines over the run, each against its own scale: the score's
+    /// on the left and the lines' on the right
+    fn render_chart(
+        &self,
+        text: &mut MeshBuilder,
+        geometry: &mut MeshBuilder,
+        layout: &Layout,
+        theme: &Theme,
+    ) {
+        let recolor = |geometry: &mut MeshBuilder, start: usize, color: [f32; 3]| {
+            for vertex in &mut geometry.vertices_mut()[start..] {
+                vertex.color = opaque(color);
+            }
+        };
+
+        // every run starts from nothing
+        let samples: Vec<Sample> = std::iter::once(Sample::default())
+            .chain(self.stats.samples.iter().copied())
+            .collect();
+        let seconds = |ticks: u64| ticks as f32 / TICKS_PER_SECOND as f32;
+        let max_ticks = samples.iter().map(|s| s.ticks).max().unwrap_or(0);
+        let max_score = samples.iter().map(|s| s.score).max().unwrap_or(0);
+        let max_lines = samples.iter().map(|s| s.lines).max().unwrap_or(0);
+
+        let area = layout.chart_area();
+        let time = Axis::nice(0.0, seconds(max_ticks), CHART_MAX_TICKS);
+        let score = Chart::new(
+            area,
+            time,
+            Axis::nice(0.0, max_score as f32, CHART_MAX_TICKS),
+        );
+        let lines = score.with_y(Axis::nice(0.0, max_lines as f32, CHART_MAX_TICKS));
+
+        // axes down both sides and along the bottom, with a mark at each tick
+        let start = geometry.vertices().len();
+        let corners = [
+            score.point(time.min, score.y.max),
+            score.point(time.min, score.y.min),
+            score.point(time.max, score.y.min),
+            score.point(time.max, score.y.max),
+        ];
+        lib::graphics::lines::render_polyline_even(
+            geometry,
+            &corners,
+            CHART_AXIS_THICKNESS,
+            false,
+            LineStyle::Solid,
+        );
+        let mark = CHART_LABEL_GAP * layout.chart_scale() / 2.0;
+        let mut marks = Vec::new();
+        for t in time.ticks() {
+            let p = score.point(t, score.y.min);
+            marks.extend_from_slice(&[p, p + cgmath::Vector2::new(0.0, mark)]);
+        }
+        for v in score.y.ticks() {
+            let p = score.point(time.min, v);
+            marks.extend_from_slice(&[p, p - cgmath::Vector2::new(mark * X_PER_Y, 0.0)]);
+        }
+        for v in lines.y.ticks() {
+            let p = lines.point(time.max, v);
+            marks.extend_from_slice(&[p, p + cgmath::Vector2::new(mark * X_PER_Y, 0.0)]);
+        }
+        lib::graphics::lines::render_lines_pairs(
+            geometry,
+            &marks,
+            CHART_AXIS_THICKNESS,
+            LineCap::Butt,
+        );
+        recolor(geometry, start, theme.inactive_text);
+
+        // the runs themselves
+        let series = [
+            (
+                &score,
+                samples
+                    .iter()
+                    .map(|s| (seconds(s.ticks), s.score as f32))
+                    .collect::<Vec<_>>(),
+                theme.highlight,
+            ),
+            (
+                &lines,
+                samples
+                    .iter()
+                    .map(|s| (seconds(s.ticks), s.lines as f32))
+                    .collect(),
+                theme.active_text,
+            ),
+        ];
+        for (chart, values, color) in &series {
+            let start = geometry.vertices().len();
+            lib::graphics::lines::render_polyline_even(
+                geometry,
+                &chart.series(values),
+                CHART_LINE_THICKNESS,
+                false,
+                LineStyle::Solid,
+            );
+            recolor(geometry, start, *color);
+        }
+
+        // labels at the ticks, times under the chart and values either side
+        let size = CHART_LABEL_SIZE * layout.chart_scale();
+        let gap = CHART_LABEL_GAP * layout.chart_scale();
+        for t in time.ticks() {
+            let p = score.point(t, score.y.min);
+            lib::graphics::text::render_styled(
+                text,
+                &format!("{}s", t as u64),
+                p.x,
+                p.y + gap,
+                &TextStyle::new(size, theme.inactive_text).align(Align::Center),
+            );
+        }
+        for v in score.y.ticks() {
+            let p = score.point(time.min, v);
+            lib::graphics::text::render_styled(
+                text,
+                &(v as u64).to_string(),
+                p.x - gap * X_PER_Y,
+                p.y,
+                &TextStyle::new(size, theme.highlight)
+                    .align(Align::Right)
+                    .anchor(Anchor::Middle),
+            );
+        }
+        for v in lines.y.ticks() {
+            let p = lines.point(time.max, v);
+            lib::graphics::text::render_styled(
+                text,
+                &(v as u64).to_string(),
+                p.x + gap * X_PER_Y,
+                p.y,
+                &TextStyle::new(size, theme.active_text).anchor(Anchor::Middle),
+            );
+        }
+
+        // which line is which, over the chart
+        let legend_y = area.y - gap - size / 2.0;
+        lib::graphics::text::render_text(text, "Score", area.x, legend_y, size, theme.highlight);
+        lib::graphics::text::render_styled(
+            text,
+            "Lines",
+            area.x + area.width,
+            legend_y,
+            &TextStyle::new(size, theme.active_text).align(Align::Right),
+        );
+    }
+}
+
+impl GameState for TetrisSummary {
+    fn update(
+        &mut self,
+        snapshot: &InputSnapshot,
+        dt: std::time::Duration,
+    ) -> lib::game::StateChange {
+        self.clock.accumulate(dt);
+
+        while self.clock.tick() {
+            let since = self.last_input.frame;
+            let input = input(snapshot, self.last_input);
+            self.last_input = input;
+            self.unlocks.tick();
+            if let Some((_, life)) = &mut self.notice {
+                *life -= 1;
+                if *life == 0 {
+                    self.notice = None;
+                }
+            }
+            if input.confirmed() || input.backed_out() {
+                return match self.next.take() {
+                    Some(next) => lib::game::StateChange::Swap(next),
+                    None => lib::game::StateChange::Pop,
+                };
+            } else if input.left == KeyState::Pressed || input.right == KeyState::Pressed {
+                self.graph = !self.graph;
+            } else if snapshot.pressed_since(Key::C, since) {
+                let message = match lib::clipboard::copy(&self.clipboard_text()) {
+                    Ok(()) => "Copied to the clipboard".to_string(),
+                    Err(e) => {
+                        eprintln!("Couldn't copy the summary: {}", e);
+                        format!("Couldn't copy: {}", e)
+                    }
+                };
+                self.notice = Some((message, DATA_NOTICE_TICKS));
+            }
+        }
+
+        lib::game::StateChange::None
+    }
+
+    /// Only the prompt blinks, which is as clear at the low power rate
+    fn is_static(&self) -> bool {
+        true
+    }
+
+    fn render(&self, graphics: &lib::graphics::GraphicsState, frame: &mut Frame) {
+        // render text
+        let mut text = graphics.scratch_mesh();
+
+        let layout = graphics.layout();
+
+        let placement = layout.title("Summary");
+        lib::graphics::text::render_text(
+            &mut text,
+            "Summary",
+            placement.x,
+            placement.y,
+            placement.size,
+            graphics.theme.active_text,
+        );
+
+        let seed_txt = format!(
+            "Seed: {} ({}, {})",
+            self.seed,
+            self.randomizer.name(),
+            self.rotation.name()
+        );
+        let placement = layout.subtitle(&seed_txt);
+        lib::graphics::text::render_text(
+            &mut text,
+            &seed_txt,
+            placement.x,
+            placement.y,
+            placement.size,
+            graphics.theme.inactive_text,
+        );
+
+        let mut geometry = graphics.scratch_mesh();
+        if self.graph {
+            self.render_chart(&mut text, &mut geometry, &layout, &graphics.theme);
+        } else {
+            self.render_stats(&mut text, &mut geometry, &layout, &graphics.theme);
+        }
+
+        if self.clock.ticker() % 20 < 10 {
+            let placement = layout.prompt("Press Z");
+            lib::graphics::text::render_text(
+                &mut text,
+                "Press Z",
+                placement.x,
+                placement.y,
+                placement.size,
+                graphics.theme.inactive_text,
+            );
+        }
+
+        let hint = if self.graph {
+            "Left/Right: stats   C: copy"
+        } else {
+            "Left/Right: graph   C: copy"
+        };
+        let placement = layout.menu_hint(hint);
+        lib::graphics::text::render_text(
+            &mut text,
+            hint,
+            placement.x,
+            placement.y,
+            placement.size,
+            graphics.theme.inactive_text,
+        );
+
+        self.unlocks.render(&mut text, &layout, &graphics.theme);
+
+        // create buffers
+        let geometry = graphics.inspect_geometry(&geometry, 0);
+        let geometry_mesh = graphics.upload(&graphics.geometry_mesh, &geometry);
+        let text_mesh = graphics.upload(&graphics.text_mesh, &text);
+
+        // render!
+        let mut command_buf =
+            graphics
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("command_buf"),
+                });
+        {
+            let mut pass = frame.begin_pass(&mut command_buf);
+
+            // draw the heatmap
+            pass.set_pipeline(graphics.geometry_pipeline());
+            pass.set_vertex_buffer(0, geometry_mesh.vertices());
+            pass.set_index_buffer(geometry_mesh.indices(), wgpu::IndexFormat::Uint32);
+            pass.set_bind_group(0, graphics.transform_bind_group(0), &[]);
+            pass.set_bind_group(1, graphics.text_bind_group(), &[]);
+            pass.draw_indexed(0..geometry_mesh.index_count(), 0, 0..1);
+
+            // draw text
+            pass.set_pipeline(&graphics.text_pipeline);
+            pass.set_vertex_buffer(0, text_mesh.vertices());
+            pass.set_index_buffer(text_mesh.indices(), wgpu::IndexFormat::Uint32);
+            pass.set_bind_group(0, graphics.transform_bind_group(0), &[]);
+            pass.set_bind_group(1, graphics.text_bind_group(), &[]);
+            pass.draw_indexed(0..text_mesh.index_count(), 0, 0..1);
+        }
+        graphics.queue.submit(std::iter::once(command_buf.finish()));
+        if let Some((message, _)) = &self.notice {
+            lib::graphics::screenshot::render_toast(graphics, frame, message);
+        }
+    }
+}
+
+struct TetrisScores {
+    /// Mode whose leaderboard is being shown
+    mode: GameMode,
+
+    /// Scores of previous players, best first
+    scores: Vec<ScoreEntry>,
+
+    /// Score the player is adding, if they're adding one
+    inputting_score: Option<ScoreEntry>,
+
+    /// Name being typed for the score being added, while it's typed
+    name: Option<TextField>,
+
+    /// Score added while on this screen, picked out on the leaderboard or
+    /// under it if it didn't make the cut
+    latest: Option<ScoreEntry>,
+
+    /// Name of the profile playing, and whether only its scores are shown
+    profile: String,
+    only_profile: bool,
+
+    /// How the last export or load went, and ticks left to show it for
+    notice: Option<(String, u64)>,
+
+    /// Lines the mouse wheel was turned by that haven't browsed a leaderboard
+    /// yet, as touchpads turn it a little at a time
+    scrolled: f32,
+
+    /// Previous frame input
+    last_input: PlayerInput,
+
+    /// Paces updates to the tick rate and counts the ticks
+    clock: FixedTimestep,
+}
+
+impl TetrisScores {
+    /// The leaderboard for `mode`, read from disk once it's shown
+    fn new(mode: GameMode) -> Self {
+        Self {
+            mode,
+            scores: Vec::new(),
+            clock: FixedTimestep::default(),
+            last_input: PlayerInput::default(),
+            inputting_score: None,
+            name: None,
+            latest: None,
+            profile: String::new(),
+            only_profile: false,
+            notice: None,
+            scrolled: 0.0,
+        }
+    }
+
+    /// Shows the leaderboard `step` modes along, wrapping around
+    fn browse(&mut self, step: i64) {
+        let modes = GameMode::RANKED.len() as i64;
+        let current = GameMode::RANKED
+            .iter()
+            .position(|&m| m == self.mode)
+            .unwrap_or(0) as i64;
+        let next = (current + step).rem_euclid(modes) as usize;
+        *self = Self {
+            last_input: self.last_input,
+            clock: self.clock,
+            notice: self.notice.take(),
+            latest: self.latest.take(),
+            only_profile: self.only_profile,
+            scrolled: self.scrolled,
+            ..Self::new(GameMode::RANKED[next])
+        };
+        self.load();
+    }
+
+    /// Adds the score being input to the leaderboard under the name typed for
+    /// it, if any, and saves it
+    fn record(&mut self) {
+        if let Some(mut entry) = self.inputting_score.take() {
+            if let Some(name) = self.name.take().filter(|name| !name.text().is_empty()) {
+                entry.name = name.text().to_string();
+            }
+            // insert our score, keeping the best ten
+            if entry.value > 0 {
+                scores::add(&mut self.scores, entry.clone());
+                self.latest = Some(entry);
+            }
+
+            // save the file
+            scores::save(self.mode, &self.scores)
+                .unwrap_or_else(|e| eprintln!("Couldn't save scores: {}", e));
+        }
+    }
+
+    /// Writes every leaderboard out as JSON, saying how it went
+    fn export(&mut self) {
+        let path = export::default_path(Format::Json);
+        let message = match export::export(Format::Json, &path) {
+            Ok(count) => {
+                println!("Exported {} scores to {}", count, path.display());
+                "Exported scores".to_string()
+            }
+            Err(e) => {
+                eprintln!("Couldn't export scores: {}", e);
+                "Couldn't export scores".to_string()
+            }
+        };
+        self.notice = Some((message, 2 * TICKS_PER_SECOND));
+    }
+
+    /// Whether entries set by `profile` are shown
+    fn shows_profile(&self, profile: &str) -> bool {
+        !self.only_profile || profile == self.profile
+    }
+
+    /// Reads the leaderboard from disk, picking up changes made elsewhere
+    fn load(&mut self) {
+        self.profile = Profiles::load_or_default().active().name.clone();
+        self.scores = match scores::load(self.mode) {
+            Ok(loaded) => {
+                if let Some(damage) = loaded.damage {
+                    eprintln!(
+                        "Score file was damaged ({}), moved it to {}",
+                        damage.error,
+                        damage.backup.display()
+                    );
+                    let message = format!(
+                        "Score file was damaged; recovered {} entries",
+                        damage.recovered
+                    );
+                    self.notice = Some((message, 4 * TICKS_PER_SECOND));
+                }
+                loaded.entries
+            }
+            Err(e) => {
+                eprintln!("Error loading scores: {}", e);
+                Vec::new()
+            }
+        };
+    }
+}
+
+impl Default for TetrisScores {
+    fn default() -> Self {
+        Self::new(GameMode::Endless)
+    }
+}
+
+impl GameState for TetrisScores {
+    fn update(
+        &mut self,
+        snapshot: &InputSnapshot,
+        dt: std::time::Duration,
+    ) -> lib::game::StateChange {
+        self.clock.accumulate(dt);
+
+        // text comes a frame at a time, not a tick at a time
+        if let Some(name) = &mut self.name {
+            name.type_text(&snapshot.text);
+        }
+
+        // and so does the wheel, a line of which browses a leaderboard. the
+        // ten scores shown are all there are, so there's nothing else to scroll
+        if self.name.is_none() {
+            self.scrolled += snapshot.scroll;
+            let lines = self.scrolled.trunc();
+            if lines != 0.0 {
+                self.scrolled -= lines;
+                // turning it away from you goes back, like scrolling up
+                self.browse(-lines as i64);
+            }
+        }
+
+        while self.clock.tick() {
+            let input = input(snapshot, self.last_input);
+            self.last_input = input;
+
+            if let Some((_, life)) = &mut self.notice {
+                *life -= 1;
+                if *life == 0 {
+                    self.notice = None;
+                }
+            }
+
+            if let Some(name) = &mut self.name {
+                // letters typed into the name don't do anything else
+                if input.clear == KeyState::Pressed {
+                    name.backspace();
+                }
+                if input.enter == KeyState::Pressed || input.escape == KeyState::Pressed {
+                    self.record();
+                }
+                continue;
+            }
+            self.record();
+            if input.export == KeyState::Pressed {
+                self.export();
+            }
+
+            if input.backed_out() {
+                return lib::game::StateChange::Pop;
+            } else if input.up == KeyState::Pressed || input.down == KeyState::Pressed {
+                // everyone's scores, or just the current profile's
+                self.only_profile = !self.only_profile;
+            } else if input.left == KeyState::Pressed {
+                // browse the other modes' leaderboards
+                self.browse(-1);
+            } else if input.right == KeyState::Pressed {
+                self.browse(1);
+            }
+        }
+
+        lib::game::StateChange::None
+    }
+
+    fn on_enter(&mut self) {
+        self.load();
+        // only a score that stays on the leaderboard gets a name
+        if let Some(entry) = &self.inputting_score {
+            if scores::makes_cut(&self.scores, entry) {
+                self.name = Some(TextField::new(scores::MAX_NAME_CHARS));
+            }
+        }
+    }
+
+    fn on_resume(&mut self) {
+        self.load();
+        self.last_input = PlayerInput::all_pressed();
+    }
+
+    fn is_static(&self) -> bool {
+        true
+    }
+
+    fn render(&self, graphics: &lib::graphics::GraphicsState, frame: &mut Frame) {
+        // render text
+        let mut text = graphics.scratch_mesh();
+
+        let layout = graphics.layout();
+
+        let title = layout.title("Scores");
+        lib::graphics::text::render_styled(
+            &mut text,
+            "Scores",
+            CENTER_X,
+            title.y,
+            &TextStyle::new(title.size, graphics.theme.active_text).align(Align::Center),
+        );
+
+        let mode_txt = if self.only_profile {
+            format!("< {}: {} >", self.mode.name(), self.profile)
+        } else {
+            format!("< {} >", self.mode.name())
+        };
+        let subtitle = layout.subtitle(&mode_txt);
+        lib::graphics::text::render_styled(
+            &mut text,
+            &mode_txt,
+            CENTER_X,
+            subtitle.y,
+            &TextStyle::new(subtitle.size, graphics.theme.inactive_text).align(Align::Center),
+        );
+
+        // the score being named is shown where it'll go, with a cursor after
+        // what's been typed
+        let mut listed = self.scores.clone();
+        let mut newest = self.latest.clone();
+        if let (Some(entry), Some(name)) = (&self.inputting_score, &self.name) {
+            let cursor = if self.clock.ticker() % 20 < 10 {
+                "_"
+            } else {
+                ""
+            };
+            let typing = ScoreEntry {
+                name: format!("{}{}", name.text(), cursor),
+                ..entry.clone()
+            };
+            scores::add(&mut listed, typing.clone());
+            newest = Some(typing);
+        }
+        let shown: Vec<&ScoreEntry> = listed
+            .iter()
+            .filter(|entry| entry.mode == self.mode && self.shows_profile(&entry.profile))
+            .take(scores::MAX_ENTRIES)
+            .collect();
+        let newest_row = newest
+            .as_ref()
+            .and_then(|newest| shown.iter().position(|&entry| entry == newest));
+        let mut rows: Vec<String> = shown
+            .iter()
+            .map(|entry| {
+                let mut score_txt = self.mode.format_value(entry.value);
+                if score_txt.len() > 10 {
+                    score_txt = score_txt.chars().take(7).chain("...".chars()).collect();
+                }
+                let date = entry.date().unwrap_or_default();
+                format!("{:.<10}{:.>10} {:>8}", entry.name, score_txt, date)
+            })
+            .collect();
+        let row_count = rows.len();
+
+        // the newest score is drawn on its own, in another colour and with
+        // an arrow flashing next to it
+        if let Some(index) = newest_row {
+            let row = layout.score_row(index);
+            let style = TextStyle::new(row.size, graphics.theme.highlight).monospace();
+            lib::graphics::text::render_styled(&mut text, &rows[index], row.x, row.y, &style);
+            if self.clock.ticker() % 20 < 10 {
+                let arrow_x = row.x - mono_width(2, row.size);
+                lib::graphics::text::render_styled(&mut text, ">", arrow_x, row.y, &style);
+            }
+            rows[index].clear();
+        }
+
+        let table = layout.score_row(0);
+        lib::graphics::text::render_styled(
+            &mut text,
+            &rows.join("\n"),
+            table.x,
+            table.y,
+            &TextStyle::new(table.size, graphics.theme.active_text)
+                .line_height(layout.score_spacing())
+                .monospace(),
+        );
+
+        let mut notes = Vec::new();
+        if self.name.is_some() {
+            notes.push("Type your name, Enter to save".to_string());
+        }
+        match &newest {
+            Some(newest)
+                if newest_row.is_none()
+                    && newest.mode == self.mode
+                    && self.shows_profile(&newest.profile) =>
+            {
+                notes.push(format!(
+                    "Your score: {} (not in top {})",
+                    self.mode.format_value(newest.value),
+                    scores::MAX_ENTRIES
+                ));
+            }
+            _ => (),
+        }
+        if shown.iter().any(|entry| entry.modified) {
+            notes.push("(modified)".to_string());
+        }
+        for (i, note_txt) in notes.iter().enumerate() {
+            let note = layout.score_note(row_count + i, note_txt);
+            lib::graphics::text::render_styled(
+                &mut text,
+                note_txt,
+                note.x,
+                note.y,
+                &TextStyle::new(note.size, graphics.theme.inactive_text),
+            );
+        }
+
+        // a full table with notes under it reaches the bottom, and those
+        // matter more than the hint
+        let hint = layout.menu_hint(SCORES_HINT);
+        let free = layout.score_note(row_count + notes.len(), SCORES_HINT);
+        if self.name.is_none() && free.y <= hint.y {
+            lib::graphics::text::render_text(
+                &mut text,
+                SCORES_HINT,
+                hint.x,
+                hint.y,
+                hint.size,
+                graphics.theme.inactive_text,
+            );
+        }
+
+        // create buffers
+        let text_mesh = graphics.upload(&graphics.text_mesh, &text);
+
+        // render!
+        let mut command_buf =
+            graphics
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("command_buf"),
+                });
+        {
+            let mut pass = frame.begin_pass(&mut command_buf);
+
+            // draw text
+            pass.set_pipeline(&graphics.text_pipeline);
+            pass.set_vertex_buffer(0, text_mesh.vertices());
+            pass.set_index_buffer(text_mesh.indices(), wgpu::IndexFormat::Uint32);
+            pass.set_bind_group(0, graphics.transform_bind_group(0), &[]);
+            pass.set_bind_group(1, graphics.text_bind_group(), &[]);
+            pass.draw_indexed(0..text_mesh.index_count(), 0, 0..1);
+        }
+        graphics.queue.submit(std::iter::once(command_buf.finish()));
+
+        if let Some((message, _)) = &self.notice {
+            lib::graphics::screenshot::render_toast(graphics, frame, message);
+        }
+    }
+
+    fn takes_text(&self) -> bool {
+        self.name.is_some()
+    }
+}
+
+/// Lifetime achievements and how close each one is
+struct TetrisAchievements {
+    achievements: Achievements,
+
+    /// Paces updates to the tick rate and counts the ticks
+    clock: FixedTimestep,
+
+    /// Previous frame input
+    last_input: PlayerInput,
+}
+
+impl Default for TetrisAchievements {
+    fn default() -> Self {
+        Self {
+            achievements: Achievements::load(&paths::data_file(ACHIEVEMENTS_FILE))
+                .unwrap_or_default(),
+            clock: FixedTimestep::default(),
+            last_input: PlayerInput::default(),
+        }
+    }
+}
+
+impl GameState for TetrisAchievements {
+    fn update(
+        &mut self,
+        snapshot: &InputSnapshot,
+        dt: std::time::Duration,
+    ) -> lib::game::StateChange {
+        self.clock.accumulate(dt);
+
+        while self.clock.tick() {
+            let input = input(snapshot, self.last_input);
+            self.last_input = input;
+
+            if input.backed_out() {
+                return lib::game::StateChange::Pop;
+            }
+        }
+
+        lib::game::StateChange::None
+    }
+
+    fn is_static(&self) -> bool {
+        true
+    }
+
+    fn render(&self, graphics: &lib::graphics::GraphicsState, frame: &mut Frame) {
+        // render text
+        let mut text = graphics.scratch_mesh();
+
+        let layout = graphics.layout();
+
+        let title = layout.title("Awards");
+        lib::graphics::text::render_text(
+            &mut text,
+            "Awards",
+            title.x,
+            title.y,
+            title.size,
+            graphics.theme.active_text,
+        );
+
+        for (i, achievement) in Achievement::ALL.iter().enumerate() {
+            // name, then a 5 character progress bar
+            let (current, goal) = self.achievements.progress(achievement);
+            let filled = (current * 5 / goal) as usize;
+            let bar = format!("{:.<5}", "#".repeat(filled));
+            let txt = format!("{:.<13}[{}]", achievement.name, bar);
+            let row = layout.score_row(i);
+            lib::graphics::text::render_text_mono(
+                &mut text,
+                &txt,
+                row.x,
+                row.y,
+                row.size,
+                if self.achievements.is_unlocked(achievement) {
+                    graphics.theme.active_text
+                } else {
+                    graphics.theme.inactive_text
+                },
+            );
+        }
+
+        // create buffers
+        let text_mesh = graphics.upload(&graphics.text_mesh, &text);
+
+        // render!
+        let mut command_buf =
+            graphics
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("command_buf"),
+                });
+        {
+            let mut pass = frame.begin_pass(&mut command_buf);
+
+            // draw text
+            pass.set_pipeline(&graphics.text_pipeline);
+            pass.set_vertex_buffer(0, text_mesh.vertices());
+            pass.set_index_buffer(text_mesh.indices(), wgpu::IndexFormat::Uint32);
+            pass.set_bind_group(0, graphics.transform_bind_group(0), &[]);
+            pass.set_bind_group(1, graphics.text_bind_group(), &[]);
+            pass.draw_indexed(0..text_mesh.index_count(), 0, 0..1);
+        }
+        graphics.queue.submit(std::iter::once(command_buf.finish()));
+    }
+}
+
+/// How to play: the controls as they're bound now, what clears are worth and
+/// a few tips, a page at a time
+struct TetrisHelp {
+    page: usize,
+
+    /// Keys the controls are shown from
+    config: SharedConfig,
+
+    /// Paces updates to the tick rate and counts the ticks
+    clock: FixedTimestep,
+
+    /// Previous frame input
+    last_input: PlayerInput,
+}
+
+impl TetrisHelp {
+    fn new(config: SharedConfig) -> Self {
+        Self {
+            page: 0,
+            config,
+            clock: FixedTimestep::default(),
+            last_input: PlayerInput::all_pressed(),
+        }
+    }
+
+    /// The screen laid out in pages, for the keys as they're bound now
+    fn pages(&self) -> Vec<Vec<help::Row>> {
+        let config = self.config.borrow();
+        help::pages(
+            help::SECTIONS,
+            &config.keys,
+            &config.pad,
+            &TextStyle::new(HELP_TEXT_SIZE, [0.0; 3]),
+            HELP_WIDTH,
+            HELP_ROWS,
+        )
+    }
+}
+
+impl GameState for TetrisHelp {
+    fn update(
+        &mut self,
+        snapshot: &InputSnapshot,
+        dt: std::time::Duration,
+    ) -> lib::game::StateChange {
+        self.clock.accumulate(dt);
+
+        while self.clock.tick() {
+            let input = input(snapshot, self.last_input);
+            self.last_input = input;
+
+            let last = self.pages().len() - 1;
+            if input.backed_out() {
+                return lib::game::StateChange::Pop;
+            } else if input.left == KeyState::Pressed {
+                self.page = self.page.saturating_sub(1);
+            } else if input.right == KeyState::Pressed {
+                self.page = usize::min(self.page + 1, last);
+            }
+        }
+
+        lib::game::StateChange::None
+    }
+
+    fn is_static(&self) -> bool {
+        true
+    }
+
+    fn render(&self, graphics: &lib::graphics::GraphicsState, frame: &mut Frame) {
+        let pages = self.pages();
+        let page = self.page.min(pages.len() - 1);
+        let theme = &graphics.theme;
+
+        let mut text = graphics.scratch_mesh();
+        let mut geometry = graphics.scratch_mesh();
+
+        let layout = graphics.layout();
+
+        let title = layout.title("How to play");
+        lib::graphics::text::render_text(
+            &mut text,
+            "How to play",
+            title.x,
+            title.y,
+            title.size,
+            theme.active_text,
+        );
+
+        let hint = if pages.len() > 1 {
+            format!("Left/Right: page {}/{}   Esc: back", page + 1, pages.len())
+        } else {
+            "Esc: back".to_string()
+        };
+        let placement = layout.menu_hint(&hint);
+        lib::graphics::text::render_text(
+            &mut text,
+            &hint,
+            placement.x,
+            placement.y,
+            placement.size,
+            theme.inactive_text,
+        );
+
+        let spacing = layout.help_spacing();
+        let mut index = 0;
+        for row in &pages[page] {
+            let placement = layout.help_row(index);
+            index += row.height();
+            match row {
+                help::Row::Heading(heading) => lib::graphics::text::render_text(
+                    &mut text,
+                    heading,
+                    placement.x,
+                    placement.y,
+                    placement.size,
+                    theme.highlight,
+                ),
+                help::Row::Text(line) => lib::graphics::text::render_text(
+                    &mut text,
+                    line,
+                    placement.x,
+                    placement.y,
+                    placement.size,
+                    theme.active_text,
+                ),
+                help::Row::Diagram(piece, caption) => {
+                    // square cells, the piece centred down the rows it has
+                    let cells = help::diagram_cells(*piece);
+                    let height = cells.iter().map(|&(_, y)| y + 1).max().unwrap_or(0);
+                    let cell_height = spacing;
+                    let cell_width = cell_height * X_PER_Y;
+                    let rows = help::DIAGRAM_ROWS;
+                    let top = placement.y + rows.saturating_sub(height) as f32 * cell_height / 2.0;
+                    let color = opaque(theme.piece(piece.color()));
+                    for (x, y) in cells {
+                        push_cell(
+                            &mut geometry,
+                            placement.x + x as f32 * cell_width,
+                            top + y as f32 * cell_height,
+                            cell_width,
+                            cell_height,
+                            color,
+                            theme,
+                        );
+                    }
+
+                    let caption_top =
+                        placement.y + rows as f32 * cell_height / 2.0 - placement.size / 4.0;
+                    lib::graphics::text::render_text(
+                        &mut text,
+                        caption,
+                        placement.x + cell_width * 5.0,
+                        caption_top,
+                        placement.size,
+                        theme.active_text,
+                    );
+                }
+                help::Row::Blank => (),
+            }
+        }
+
+        // create buffers
+        let geometry = graphics.inspect_geometry(&geometry, 0);
+        let geometry_mesh = graphics.upload(&graphics.geometry_mesh, &geometry);
+        let text_mesh = graphics.upload(&graphics.text_mesh, &text);
+
+        // render!
+        let mut command_buf =
+            graphics
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("command_buf"),
+                });
+        {
+            let mut pass = frame.begin_pass(&mut command_buf);
+
+            // draw diagrams
+            pass.set_pipeline(graphics.geometry_pipeline());
+            pass.set_vertex_buffer(0, geometry_mesh.vertices());
+            pass.set_index_buffer(geometry_mesh.indices(), wgpu::IndexFormat::Uint32);
+            pass.set_bind_group(0, graphics.transform_bind_group(0), &[]);
+            pass.set_bind_group(1, graphics.text_bind_group(), &[]);
+            pass.draw_indexed(0..geometry_mesh.index_count(), 0, 0..1);
+
+            // draw text
+            pass.set_pipeline(&graphics.text_pipeline);
+            pass.set_vertex_buffer(0, text_mesh.vertices());
+            pass.set_index_buffer(text_mesh.indices(), wgpu::IndexFormat::Uint32);
+            pass.set_bind_group(0, graphics.transform_bind_group(0), &[]);
+            pass.set_bind_group(1, graphics.text_bind_group(), &[]);
+            pass.draw_indexed(0..text_mesh.index_count(), 0, 0..1);
+        }
+        graphics.queue.submit(std::iter::once(command_buf.finish()));
+    }
+}
+
+/// Answer to leaving a run from the middle of it
+struct QuitToMenu;
+
+struct TetrisMain {
+    /// The run, driven through the same API other frontends embed it with
+    game: Game,
+
+    /// Paces updates to the tick rate and counts the ticks
+    clock: FixedTimestep,
+
+    /// Active piece as it was before the last tick, for drawing it part way
+    /// between there and where it is now
+    previous_piece: Option<Piece>,
+
+    /// Previous frame input
+    last_input: PlayerInput,
+
+    /// Keys held on the last few ticks as (tick, keys), for dumps
+    recent_inputs: VecDeque<(u64, Inputs)>,
+
+    /// Whether the field border pulses in time with gravity, and ticks too if
+    /// the config says so
+    metronome: bool,
+
+    /// Frames left until the metronome border fades out
+    pulse: u32,
+
+    /// Frames the danger glow has faded in for, up to [`DANGER_FADE_TICKS`]
+    danger: u32,
+
+    /// Lifetime achievement progress
+    achievements: Achievements,
+
+    /// What this run has done towards achievements
+    run: RunStats,
+
+    /// Unlocks waiting to be announced, the first one being shown
+    unlocks: UnlockToasts,
+
+    /// Whether an assist was used this run, which rules out achievements
+    assisted: bool,
+
+    /// Cells of the last piece placed with a fault, its colour and how much
+    /// longer it flashes
+    fault_flash: Option<(Vec<(isize, isize)>, Color, u64)>,
+
+    /// Debris from cleared lines
+    particles: ParticleSystem,
+
+    /// How much the playfield is jolting
+    shake: Shake,
+
+    /// Points and clear names rising off the field
+    popups: Popups,
+
+    /// Name of the last clear, big over the field
+    callouts: Callouts,
+
+    /// Whether the game plays itself, in the menu's attract mode
+    demo: bool,
+
+    /// Where the demo is steering the active piece, as (x, rotation)
+    demo_target: Option<(i8, u8)>,
+
+    /// Keys left to hold, one tick at a time, if the demo is a recording
+    /// rather than the AI
+    playback: Option<std::vec::IntoIter<Inputs>>,
+
+    /// Keys this board is played with
+    keys: KeyMap,
+
+    /// Gamepad buttons this board is played with
+    pad: PadMap,
+
+    /// Options the game was started with, passed on to a retry
+    config: SharedConfig,
+
+    /// Sounds waiting to be played, see [`GameState::take_sounds`]
+    sounds: Vec<SoundId>,
+
+    /// What the board was drawn as last frame, drawn over next frame
+    mesh: RefCell<BoardMesh>,
+
+    /// Sidebar text from last frame, written over the next
+    hud_lines: RefCell<Vec<(String, [f32; 3])>>,
+
+    /// Keys held on every tick so far, if the run is being recorded to be
+    /// raced against later
+    replay: Option<Replay>,
+
+    /// Fastest sprint so far, played back tick for tick alongside this one
+    ghost: Option<Ghost>,
+
+    /// Time of the fastest sprint on file when this one started
+    best_millis: Option<u64>,
+
+    /// Seed given for every game, if there was one, which a restart is dealt
+    /// from too
+    seed: Option<u64>,
+
+    /// Ticks the restart key has been held down for
+    restart_held: u64,
+
+    /// Ticks left of the countdown run again when play comes back after a
+    /// pause. The run stands still meanwhile, replay and ghost included
+    resume_countdown: u64,
+}
+
+/// Jolt of the playfield that dies down over a few ticks
+#[derive(Clone, Copy, Debug, Default)]
+struct Shake {
+    /// Furthest the field may be thrown from its place right now
+    magnitude: f32,
+}
+
+impl Shake {
+    /// Starts a shake of `magnitude`, unless a bigger one is already going
+    fn kick(&mut self, magnitude: f32) {
+        self.magnitude = self.magnitude.max(magnitude);
+    }
+
+    fn tick(&mut self) {
+        self.magnitude *= SHAKE_DECAY;
+        if self.magnitude < 0.001 {
+            self.magnitude = 0.0;
+        }
+    }
+
+    /// Where to throw the field this frame, if it's shaking. Only sideways,
+    /// since the field fills the screen from top to bottom
+    fn offset(&self) -> Option<f32> {
+        if self.magnitude > 0.0 {
+            Some(rand::thread_rng().gen_range(-self.magnitude..=self.magnitude))
+        } else {
+            None
+        }
+    }
+}
+
+/// Adds a `width` by `height` rectangle of `color` with its top left corner at
+/// (`left`, `top`)
+fn push_quad(
+    mesh: &mut MeshBuilder,
+    left: f32,
+    top: f32,
+    width: f32,
+    height: f32,
+    color: [f32; 4],
+) {
+    let vertex = |x: f32, y: f32| Vertex {
+        position: [x, y, 0.0],
+        color,
+        tex_coords: [0.0, 0.0],
+    };
+    mesh.quad([
+        vertex(left, top),
+        vertex(left, top + height),
+        vertex(left + width, top),
+        vertex(left + width, top + height),
+    ]);
+}
+
+/// Adds a glow of `color` along the inside of each edge of `rect`, fading
+/// out `depth_x` in from the sides and `depth_y` in from the top and bottom
+fn push_edge_glow(
+    mesh: &mut MeshBuilder,
+    rect: &Rect,
+    depth_x: f32,
+    depth_y: f32,
+    color: [f32; 4],
+) {
+    let clear = with_alpha([color[0], color[1], color[2]], 0.0);
+    let vertex = |x: f32, y: f32, color: [f32; 4]| Vertex {
+        position: [x, y, 0.0],
+        color,
+        tex_coords: [0.0, 0.0],
+    };
+    let (left, top) = (rect.x, rect.y);
+    let (right, bottom) = (rect.x + rect.width, rect.y + rect.height);
+    // top left, bottom left, top right, bottom right of each strip
+    mesh.quad([
+        vertex(left, top, color),
+        vertex(left, bottom, color),
+        vertex(left + depth_x, top, clear),
+        vertex(left + depth_x, bottom, clear),
+    ]);
+    mesh.quad([
+        vertex(right - depth_x, top, clear),
+        vertex(right - depth_x, bottom, clear),
+        vertex(right, top, color),
+        vertex(right, bottom, color),
+    ]);
+    mesh.quad([
+        vertex(left, top, color),
+        vertex(left, top + depth_y, clear),
+        vertex(right, top, color),
+        vertex(right, top + depth_y, clear),
+    ]);
+    mesh.quad([
+        vertex(left, bottom - depth_y, clear),
+        vertex(left, bottom, color),
+        vertex(right, bottom - depth_y, clear),
+        vertex(right, bottom, color),
+    ]);
+}
+
+/// Adds a cell of `color` like [`push_quad`] would, shaded with the bevel of
+/// `theme`
+fn push_cell(
+    mesh: &mut MeshBuilder,
+    left: f32,
+    top: f32,
+    width: f32,
+    height: f32,
+    color: [f32; 4],
+    theme: &Theme,
+) {
+    let rgb = [color[0], color[1], color[2]];
+    let vertex = |u: f32, v: f32| Vertex {
+        position: [left + width * u, top + height * v, 0.0],
+        color: with_alpha(theme.bevel(rgb, u, v), color[3]),
+        tex_coords: [0.0, 0.0],
+    };
+    mesh.quad([
+        vertex(0.0, 0.0),
+        vertex(0.0, 1.0),
+        vertex(1.0, 0.0),
+        vertex(1.0, 1.0),
+    ]);
+}
+
+/// Colour of a heatmap cell `heat` of the way from an unused column to the
+/// busiest: cold blue through yellow to red
+fn heat_color(theme: &Theme, heat: f32) -> [f32; 3] {
+    let pieces = &theme.pieces;
+    if heat < 0.5 {
+        mix(pieces.blue, pieces.yellow, heat * 2.0)
+    } else {
+        mix(pieces.yellow, pieces.red, heat * 2.0 - 1.0)
+    }
+}
+
+/// Moves on to the next of `lines` after the first `used`, emptied and given
+/// `color`, adding a line if they're all in use
+fn hud_line<'a>(
+    lines: &'a mut Vec<(String, [f32; 3])>,
+    used: &mut usize,
+    color: [f32; 3],
+) -> &'a mut String {
+    if *used == lines.len() {
+        lines.push((String::new(), color));
+    }
+    let (line, line_color) = &mut lines[*used];
+    *used += 1;
+    line.clear();
+    *line_color = color;
+    line
+}
+
+/// Pads the value after the first `label_len` bytes of `line` with spaces on
+/// the left, so it ends at the edge of the sidebar column
+fn right_align(line: &mut String, label_len: usize) {
+    let width = HUD_COLUMN_CHARS.saturating_sub(label_len);
+    let value_len = line.len() - label_len;
+    for _ in value_len..width {
+        line.insert(label_len, ' ');
+    }
+}
+
+/// Geometry drawn in a space of its own, placed in the board's by `transform`
+struct Layer {
+    transform: Transform2D,
+    geometry: MeshBuilder,
+
+    /// Indices of the geometry in the buffer it was last submitted in
+    range: std::ops::Range<u32>,
+}
+
+/// Everything a board draws, with text kept apart from the rest since the two
+/// go through different pipelines. Layers are drawn in order, and text over
+/// all of them in the board's space. Kept from frame to frame and
+/// [cleared](Self::clear) rather than built anew, so the room the geometry
+/// takes is only made once
+#[derive(Default)]
+struct BoardMesh {
+    /// Layers, the first `used` of them drawn this frame
+    layers: Vec<Layer>,
+    used: usize,
+    text: MeshBuilder,
+
+    /// Words put together for the frame, like the countdown's number
+    label: String,
+
+    /// Outline of the ghost piece, and its corners placed on the field
+    outlines: Outlines,
+    corners: Vec<cgmath::Vector2<f32>>,
+}
+
+impl BoardMesh {
+    /// Removes all layers and text, keeping the room they took
+    fn clear(&mut self) {
+        self.used = 0;
+        self.text.clear();
+    }
+
+    /// Starts a layer placed by `transform`, drawn over the ones before it
+    fn layer(&mut self, transform: Transform2D) -> &mut MeshBuilder {
+        if self.used == self.layers.len() {
+            self.layers.push(Layer {
+                transform,
+                geometry: MeshBuilder::new(),
+                range: 0..0,
+            });
+        }
+        let layer = &mut self.layers[self.used];
+        self.used += 1;
+        layer.transform = transform;
+        layer.geometry.clear();
+        &mut layer.geometry
+    }
+
+    /// Moves the layers `dx` to the right, leaving the text where it is
+    fn shake(&mut self, dx: f32) {
+        for layer in &mut self.layers[..self.used] {
+            layer.transform = Transform2D::translation(dx, 0.0).then(layer.transform);
+        }
+    }
+
+    /// Draws the mesh to the next frame, with the board placed by `camera`.
+    /// Takes a transform slot for every layer and one for the text
+    fn submit(
+        &mut self,
+        graphics: &lib::graphics::GraphicsState,
+        frame: &mut Frame,
+        camera: &Camera,
+    ) {
+        // every layer gets its own range of the one buffer, and its own slot.
+        // slot 0 keeps the usual projection for whatever is drawn after
+        let mut geometry = graphics.scratch_mesh();
+        let layers = &mut self.layers[..self.used];
+        for (i, layer) in layers.iter_mut().enumerate() {
+            let start = geometry.indices().len() as u32;
+            geometry.append(&graphics.inspect_geometry(&layer.geometry, 0));
+            layer.range = start..geometry.indices().len() as u32;
+            graphics.write_transform(i + 1, camera.matrix_for(layer.transform));
+        }
+        let text_slot = layers.len() + 1;
+        graphics.write_transform(text_slot, camera.matrix());
+
+        // create buffers
+        let buffers_scope = graphics.profiler.scope("buffers");
+        let mesh = graphics.upload(&graphics.geometry_mesh, &geometry);
+        let text_mesh = graphics.upload(&graphics.text_mesh, &self.text);
+
+        drop(buffers_scope);
+
+        // render!
+        let mut command_buf =
+            graphics
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("command_buf"),
+                });
+        {
+            let mut pass = frame.begin_pass(&mut command_buf);
+            // draw objects
+            pass.set_pipeline(graphics.geometry_pipeline());
+            pass.set_vertex_buffer(0, mesh.vertices());
+            pass.set_index_buffer(mesh.indices(), wgpu::IndexFormat::Uint32);
+            pass.set_bind_group(1, graphics.text_bind_group(), &[]); // ignored by shader
+            for (i, layer) in layers.iter().enumerate() {
+                pass.set_bind_group(0, graphics.transform_bind_group(i + 1), &[]);
+                pass.draw_indexed(layer.range.clone(), 0, 0..1);
+            }
+
+            // draw text, which would be unreadable as a wireframe
+            if !graphics.wireframe {
+                pass.set_pipeline(&graphics.text_pipeline);
+                pass.set_vertex_buffer(0, text_mesh.vertices());
+                pass.set_index_buffer(text_mesh.indices(), wgpu::IndexFormat::Uint32);
+                pass.set_bind_group(0, graphics.transform_bind_group(text_slot), &[]);
+                pass.set_bind_group(1, graphics.text_bind_group(), &[]);
+                pass.draw_indexed(0..text_mesh.index_count(), 0, 0..1);
+            }
+        }
+        let _scope = graphics.profiler.scope("submit");
+        graphics.queue.submit(std::iter::once(command_buf.finish()));
+    }
+}
+
+impl lib::game::GameState for TetrisMain {
+    fn update(
+        &mut self,
+        snapshot: &InputSnapshot,
+        dt: std::time::Duration,
+    ) -> lib::game::StateChange {
+        self.clock.accumulate(dt);
+
+        while self.clock.tick() {
+            let input = read_input(snapshot, &self.keys, &self.pad, self.last_input);
+            self.last_input = input;
+
+            let input = if self.demo {
+                if input.any_pressed() {
+                    // back to the menu
+                    return lib::game::StateChange::Pop;
+                }
+                self.demo_input()
+            } else {
+                input
+            };
+
+            if input.escape == KeyState::Pressed {
+                // the run stands still while the player makes up their mind
+                return lib::game::StateChange::Push(Box::new(TetrisConfirm::new(
+                    "Quit to the menu? This run won't be recorded.",
+                    QuitToMenu,
+                )));
+            }
+
+            // held from a fresh press, nothing is recorded for the run left
+            // behind: it never finishes
+            self.restart_held = match input.restart {
+                KeyState::Pressed if self.can_restart() => 1,
+                KeyState::Holding if self.restart_held > 0 => self.restart_held + 1,
+                _ => 0,
+            };
+            if self.restart_held >= RESTART_HOLD_TICKS {
+                return lib::game::StateChange::Swap(Box::new(self.restarted()));
+            }
+
+            if self.resume_countdown > 0 {
+                self.resume_countdown -= 1;
+                continue;
+            }
+
+            if self.game.countdown() == 0 {
+                self.assists(&input);
+            }
+
+            if input.dump == KeyState::Pressed
+                && (cfg!(debug_assertions) || self.config.borrow().debug_dumps)
+            {
+                match self.dump() {
+                    Ok(path) => println!("Dumped the game to {}", path.display()),
+                    Err(e) => eprintln!("Couldn't dump the game: {}", e),
+                }
+            }
+
+            self.previous_piece = self.game.active_piece();
+            let held = |key: KeyState| key != KeyState::Released;
+            let inputs = match &mut self.playback {
+                Some(playback) => match playback.next() {
+                    Some(inputs) => inputs,
+                    // the recording ran out before the run ended
+                    None => return lib::game::StateChange::Pop,
+                },
+                None => Inputs {
+                    left: held(input.left),
+                    right: held(input.right),
+                    down: held(input.down),
+                    rot_right: held(input.rot_right),
+                    rot_left: held(input.rot_left),
+                },
+            };
+            if self.recent_inputs.len() == DUMP_INPUT_TICKS {
+                self.recent_inputs.pop_front();
+            }
+            self.recent_inputs.push_back((self.game.ticker(), inputs));
+            if let Some(replay) = &mut self.replay {
+                replay.inputs.push(inputs);
+            }
+            for &button in Button::ALL {
+                let input = if inputs.is_held(button) {
+                    Input::Press(button)
+                } else {
+                    Input::Release(button)
+                };
+                self.game.apply(input);
+            }
+            self.game.tick();
+            if let Some(ghost) = &mut self.ghost {
+                ghost.step();
+            }
+            self.particles.update();
+            self.shake.tick();
+            self.popups.tick();
+            self.callouts.tick();
+            // the glow stays out of the way of clears and the end of the run
+            self.danger = if self.in_danger() && self.game.effect().is_none() {
+                u32::min(self.danger + 1, DANGER_FADE_TICKS)
+            } else {
+                self.danger.saturating_sub(1)
+            };
+            if self.game.instant_gravity() {
+                self.run.instant_gravity_millis =
+                    self.game.stats().play_time(FRAME_TIME).as_millis() as u64;
+            }
+            let events: Vec<GameEvent> = self.game.events().collect();
+            for event in events {
+                if let Some(change) = self.handle(event) {
+                    return change;
+                }
+            }
+            self.record(false);
+        }
+
+        lib::game::StateChange::None
+    }
+
+    fn render(&self, graphics: &lib::graphics::GraphicsState, frame: &mut Frame) {
+        let layout = graphics.layout();
+
+        let mut mesh = {
+            let _scope = graphics.profiler.scope("vertices");
+            self.draw(&layout, &graphics.theme)
+        };
+
+        // a shake throws the field around but leaves the text where it is
+        if let Some(dx) = self.shake.offset() {
+            mesh.shake(dx);
+        }
+        mesh.submit(graphics, frame, &Camera::new(graphics.projection()));
+    }
+
+    fn on_resume(&mut self) {
+        // the key that answered the confirmation doesn't carry into the run
+        self.last_input = PlayerInput::all_pressed();
+        self.count_down_again();
+    }
+
+    fn on_return(&mut self) {
+        self.last_input = PlayerInput::all_pressed();
+        self.count_down_again();
+    }
+
+    fn on_result(&mut self, result: Box<dyn Any>) -> lib::game::StateChange {
+        match result.downcast::<Confirmed<QuitToMenu>>() {
+            Ok(_) => lib::game::StateChange::Pop,
+            Err(_) => lib::game::StateChange::None,
+        }
+    }
+
+    fn on_quit(&mut self) {
+        // closing the window mid-run sets the run aside for later
+        if !self.demo && save::can_save(self.game.simulation()) {
+            save::save(
+                self.game.simulation(),
+                self.assisted,
+                &paths::data_file(SAVE_FILE),
+            )
+            .unwrap_or_else(|e| eprintln!("Couldn't save game: {}", e));
+        }
+    }
+
+    fn title_info(&self) -> Option<String> {
+        if self.demo {
+            return None;
+        }
+        let mode = self.game.mode();
+        Some(if mode.is_timed() {
+            format!("{} {}", mode.name(), format_time(self.clock()))
+        } else {
+            format!("{} {}", mode.name(), self.game.score())
+        })
+    }
+
+    fn take_sounds(&mut self) -> Vec<SoundId> {
+        std::mem::take(&mut self.sounds)
+    }
+
+    fn music(&self) -> Option<Music> {
+        // a demo plays under the menu's music
+        if self.demo {
+            return None;
+        }
+        Some(Music::Play {
+            track: Track::Game,
+            hurried: self.in_danger(),
+        })
+    }
+}
+
+impl TetrisMain {
+    /// Milliseconds on the window title's clock: the time left if the mode has
+    /// a limit, otherwise the time taken so far
+    fn clock(&self) -> u64 {
+        let elapsed = self
+            .game
+            .finish_time()
+            .unwrap_or_else(|| ticks_to_millis(self.game.elapsed_ticks()));
+        match self.game.mode().time_limit() {
+            Some(limit) => limit.saturating_sub(elapsed),
+            None => elapsed,
+        }
+    }
+
+    /// Plays `sim` from the keyboard, with the keys and handling in `config`
+    fn new(mut sim: Simulation, config: SharedConfig) -> Self {
+        let keys = config.borrow().keys;
+        let pad = config.borrow().pad;
+        sim.handling = config.borrow().handling();
+
+        // fresh sprints are recorded, and race the fastest one so far
+        let racing = sim.mode == GameMode::Sprint && Replay::can_record(&sim);
+        let path = paths::data_file(GHOST_FILE);
+        let best = if racing && path.exists() {
+            Replay::load(&path)
+                .map_err(|e| eprintln!("Couldn't read the ghost: {}", e))
+                .ok()
+        } else {
+            None
+        };
+        let best_millis = best.as_ref().and_then(|best| best.millis);
+        let ghost = best
+            .filter(|_| config.borrow().ghost != GhostDisplay::Off)
+            .map(Ghost::new);
+        let replay = if racing {
+            Some(Replay::record(&sim))
+        } else {
+            None
+        };
+        let metronome = sim.mode.metronome() == MetronomeRule::Free
+            && config.borrow().metronome != Metronome::Off;
+
+        Self {
+            game: Game::from_simulation(sim),
+            clock: FixedTimestep::default(),
+            previous_piece: None,
+            last_input: PlayerInput::default(),
+            recent_inputs: VecDeque::with_capacity(DUMP_INPUT_TICKS),
+            metronome,
+            pulse: 0,
+            danger: 0,
+            achievements: Achievements::load(&paths::data_file(ACHIEVEMENTS_FILE))
+                .unwrap_or_default(),
+            run: RunStats::default(),
+            unlocks: UnlockToasts::default(),
+            assisted: false,
+            fault_flash: None,
+            particles: ParticleSystem::default(),
+            shake: Shake::default(),
+            popups: Popups::default(),
+            callouts: Callouts::default(),
+            demo: false,
+            demo_target: None,
+            playback: None,
+            keys,
+            pad,
+            config,
+            sounds: Vec::new(),
+            mesh: RefCell::default(),
+            hud_lines: RefCell::default(),
+            replay,
+            ghost,
+            best_millis,
+            seed: None,
+            restart_held: 0,
+            resume_countdown: 0,
+        }
+    }
+
+    /// Counts down again before play carries on, so coming back from a pause
+    /// isn't an instant gotcha. Only a run under way needs it: one still
+    /// counting down or already over is left as it is
+    fn count_down_again(&mut self) {
+        let game = &self.game;
+        if !self.demo && game.countdown() == 0 && game.has_started() && !game.is_over() {
+            self.resume_countdown = COUNTDOWN_TICKS;
+        }
+    }
+
+    /// Whether the restart key can start this run over. Versus boards are
+    /// left alone, as the other player is still playing, and so are runs
+    /// already over, whose results are on their way
+    fn can_restart(&self) -> bool {
+        let running = self.game.effect().map_or(true, |e| !e.ty.ends_run());
+        running && !self.demo && self.game.mode() != GameMode::Versus
+    }
+
+    /// A fresh run set up like this one, for the restart key. It's dealt from
+    /// a new seed unless one was given, and counts down again, except for
+    /// puzzles, which start over straight away like they do when failed
+    fn restarted(&self) -> TetrisMain {
+        let mut sim = match self.game.puzzle() {
+            Some(puzzle) => {
+                let mut sim = Simulation::from_puzzle(puzzle.clone());
+                sim.countdown = 0;
+                sim
+            }
+            None => {
+                let mut sim = Simulation::with_level(
+                    self.game.mode(),
+                    self.game.next_pieces().len(),
+                    self.config.borrow().start_level,
+                    self.seed,
+                    self.game.randomizer(),
+                );
+                sim.instant_gravity = self.game.instant_gravity();
+                if !self.game.pieces().is_standard() {
+                    sim.use_pieces(self.game.pieces());
+                }
+                sim
+            }
+        };
+        sim.set_rotation(self.game.rotation());
+        TetrisMain {
+            seed: self.seed,
+            // the restart key is still down, it shouldn't start counting again
+            last_input: PlayerInput::all_pressed(),
+            ..TetrisMain::new(sim, self.config.clone())
+        }
+    }
+
+    /// Writes the sidebar lines, top first, in their colours into `lines`:
+    /// what the mode shows, then any assists that are on. Counts are read from
+    /// the same stats the summary shows at the end. Strings already in `lines`
+    /// are written over rather than replaced, and how many were written is
+    /// returned
+    fn hud(&self, theme: &Theme, lines: &mut Vec<(String, [f32; 3])>) -> usize {
+        let stats = self.game.stats();
+        let played = stats.play_time(FRAME_TIME).as_millis() as u64;
+        let mut used = 0;
+
+        for &stat in self.game.mode().hud() {
+            let line = hud_line(lines, &mut used, theme.active_text);
+            line.push_str(stat.label());
+            // writing to a String can't fail
+            match stat {
+                HudStat::Score => write!(line, "{:06}", self.game.score()),
+                HudStat::Time => match self.game.mode().time_limit() {
+                    Some(limit) => {
+                        // flash during the last few seconds
+                        let left = limit.saturating_sub(played);
+                        if left > 0 && left <= 10_000 && self.game.ticker() % 10 >= 5 {
+                            Ok(())
+                        } else {
+                            write!(line, "{}", Clock(left))
+                        }
+                    }
+                    None => write!(line, "{}", Clock(played)),
+                },
+                HudStat::Level => write!(line, "{}", self.game.level()),
+                HudStat::Lines => write!(line, "{}", stats.lines),
+                HudStat::LinesLeft => write!(line, "{}", SPRINT_LINES.saturating_sub(stats.lines)),
+                HudStat::GarbageLeft => write!(line, "{}", self.game.garbage_left()),
+                HudStat::PiecesLeft => write!(line, "{}", self.game.next_pieces().len()),
+                HudStat::Pieces => write!(line, "{}", stats.total_pieces()),
+            }
+            .unwrap();
+            right_align(line, stat.label().len());
+        }
+
+        if self.game.pieces().is_standard() && self.game.puzzle().is_none() {
+            // flashes once the wait gets long
+            let drought = stats.droughts.of(Tetromino::I as usize);
+            let color = if drought > I_DROUGHT_WARNING && self.game.ticker() % 10 >= 5 {
+                theme.highlight
+            } else {
+                theme.inactive_text
+            };
+            let line = hud_line(lines, &mut used, color);
+            write!(line, "I drought{}", drought).unwrap();
+            right_align(line, "I drought".len());
+        }
+        if self.game.finesse() {
+            let line = hud_line(lines, &mut used, theme.inactive_text);
+            write!(line, "Faults{}", self.game.faults()).unwrap();
+            right_align(line, "Faults".len());
+        }
+        if self.game.instant_gravity() {
+            hud_line(lines, &mut used, theme.highlight).push_str("20G");
+        }
+        used
+    }
+
+    /// Piece colours for the level being played, part way from the last
+    /// level's if it's only just been reached
+    fn palette(&self, theme: &Theme) -> PieceColors {
+        let to = theme.palette_for_level(self.game.level());
+        match self.level_up() {
+            Some((level, elapsed)) if elapsed < PALETTE_FADE_TICKS => {
+                let from = theme.palette_for_level(level - 1);
+                from.mix(&to, elapsed as f32 / PALETTE_FADE_TICKS as f32)
+            }
+            _ => to,
+        }
+    }
+
+    /// Level most recently reached and frames since, while it's being shown
+    fn level_up(&self) -> Option<(u32, u64)> {
+        self.game
+            .flourishes()
+            .iter()
+            .rev()
+            .find_map(|effect| match effect.ty {
+                BoardEffectType::LevelUp { level } => Some((level, LEVEL_UP_TICKS - effect.life)),
+                _ => None,
+            })
+    }
+
+    /// How far from `piece` to draw it, in cells, so it glides from where it
+    /// was a tick ago instead of jumping. Anything more than a step down or
+    /// sideways, like a new piece, a turn or a drop, snaps straight there
+    fn glide(&self, piece: Piece) -> (f32, f32) {
+        let previous = match self.previous_piece {
+            Some(previous) if self.config.borrow().smooth_movement => previous,
+            _ => return (0.0, 0.0),
+        };
+        if !std::ptr::eq(previous.kind, piece.kind)
+            || previous.rot != piece.rot
+            || previous.scale != piece.scale
+            || self.game.effect().is_some()
+        {
+            return (0.0, 0.0);
+        }
+
+        let step = piece.scale as i8;
+        let (dx, dy) = (previous.x - piece.x, previous.y - piece.y);
+        if dx.abs() > step || dy > 0 || dy < -step {
+            return (0.0, 0.0);
+        }
+        let behind = 1.0 - self.clock.progress();
+        (dx as f32 * behind, dy as f32 * behind)
+    }
+
+    /// Starts a game that plays itself until a key is pressed
+    fn demo() -> Self {
+        let mut s = Self {
+            demo: true,
+            // a demo can't win anything
+            assisted: true,
+            ..Self::default()
+        };
+        s.game.skip_countdown();
+        s
+    }
+
+    /// Starts a demo playing `replay` back tick for tick, countdown and all,
+    /// until it's over or a key is pressed
+    fn playback(replay: Replay) -> Self {
+        Self {
+            // played with the handling it was recorded with
+            game: Game::from_simulation(replay.simulation()),
+            demo: true,
+            assisted: true,
+            playback: Some(replay.inputs.into_iter()),
+            ..Self::default()
+        }
+    }
+
+    /// The demo shown when nobody's playing: the recording built into the
+    /// game if `recorded`, or the AI. The AI stands in for a recording made
+    /// before the replay format last changed
+    fn attract(recorded: bool) -> Self {
+        if recorded {
+            match Replay::demo() {
+                Ok(replay) => return Self::playback(replay),
+                Err(e) => eprintln!("Couldn't play the built-in demo: {}", e),
+            }
+        }
+        Self::demo()
+    }
+
+    /// Keys the demo presses this frame to steer the active piece to its target
+    fn demo_input(&self) -> PlayerInput {
+        let mut input = PlayerInput::default();
+        if let (Some(piece), Some(target)) = (self.game.active_piece(), self.demo_target) {
+            let inputs = ai::steer(&piece, target, self.game.ticker());
+            let key = |held| {
+                if held {
+                    KeyState::Pressed
+                } else {
+                    KeyState::Released
+                }
+            };
+            input.left = key(inputs.left);
+            input.right = key(inputs.right);
+            input.down = key(inputs.down);
+            input.rot_right = key(inputs.rot_right);
+            input.rot_left = key(inputs.rot_left);
+        }
+        input
+    }
+
+    /// Toggles and practice tools the player can reach for during a run
+    fn assists(&mut self, input: &PlayerInput) {
+        let rule = self.game.mode().metronome();
+        if input.metronome == KeyState::Pressed && rule != MetronomeRule::Off {
+            self.metronome = !self.metronome;
+            self.assisted |= self.metronome && rule == MetronomeRule::Assist;
+        }
+
+        if self.game.is_practice() {
+            if let Some(index) = input.pick.iter().position(|&k| k == KeyState::Pressed) {
+                self.game.pick_next(index);
+            }
+            if input.freeze == KeyState::Pressed {
+                let frozen = self.game.is_frozen();
+                self.game.set_frozen(!frozen);
+            }
+            if input.clear == KeyState::Pressed {
+                self.game.clear_field();
+            }
+            if input.undo == KeyState::Pressed {
+                self.game.undo();
+            }
+        }
+
+        if input.finesse == KeyState::Pressed {
+            let finesse = self.game.finesse();
+            self.game.set_finesse(!finesse);
+        }
+        if let Some((_, _, life)) = &mut self.fault_flash {
+            *life -= 1;
+            if *life == 0 {
+                self.fault_flash = None;
+            }
+        }
+
+        self.unlocks.tick();
+        self.pulse = self.pulse.saturating_sub(1);
+    }
+
+    /// Reacts to something that happened on the board, returning where to go
+    /// next if the run is over
+    fn handle(&mut self, event: GameEvent) -> Option<lib::game::StateChange> {
+        self.run.observe(self.game.mode(), &event);
+        match event {
+            GameEvent::Spawned => {
+                let steering = self.demo && self.playback.is_none();
+                if let (true, Some(piece)) = (steering, self.game.active_piece()) {
+                    self.demo_target = best_placement(self.game.board(), &piece);
+                }
+            }
+            GameEvent::Fell => {
+                self.pulse = METRONOME_PULSE_TICKS;
+                if self.metronome && self.config.borrow().metronome == Metronome::Tick {
+                    self.play(SoundId::Metronome);
+                }
+            }
+            GameEvent::Shifted => self.play(SoundId::Shift),
+            GameEvent::Rotated => self.play(SoundId::Rotate),
+            GameEvent::SoftDropped => self.play(SoundId::SoftDrop),
+            GameEvent::LeveledUp { .. } => self.play(SoundId::LevelUp),
+            GameEvent::FinesseFault { cells, color } => {
+                self.fault_flash = Some((cells, color, FAULT_FLASH_TICKS));
+            }
+            GameEvent::Locked => self.play(SoundId::Lock),
+            GameEvent::Cleared {
+                lines,
+                perfect,
+                score,
+                ..
+            } => {
+                self.play(if lines >= 4 {
+                    SoundId::Tetris
+                } else {
+                    SoundId::LineClear
+                });
+                self.announce_clear(lines, perfect, score);
+                self.shatter_cleared_rows();
+                if lines >= 4 && self.config.borrow().screen_shake {
+                    self.shake.kick(TETRIS_SHAKE);
+                }
+            }
+            GameEvent::ToppedOut => self.play(SoundId::GameOver),
+            GameEvent::Ended(ending) => {
+                if ending == Ending::TimeUp {
+                    self.play(SoundId::GameOver);
+                }
+                return Some(self.finished(ending));
+            }
+            _ => (),
+        }
+        None
+    }
+
+    /// Whether the stack reaches into the top rows, which hurries the music
+    fn in_danger(&self) -> bool {
+        self.game.board().highest_occupied_row() < DANGER_ROWS
+    }
+
+    /// Queues `sound` to be played, unless the game is playing itself
+    fn play(&mut self, sound: SoundId) {
+        if !self.demo {
+            self.sounds.push(sound);
+        }
+    }
+
+    /// Puts up what a clear was worth over the rows being cleared, and what
+    /// it's called over the field
+    fn announce_clear(&mut self, lines: u32, perfect: bool, score: u64) {
+        self.callouts.push(&Clear {
+            lines,
+            perfect,
+            combo: self.game.stats().combo,
+            ..Clear::default()
+        });
+
+        let rows = match self.game.effect() {
+            Some(BoardEffect {
+                ty: BoardEffectType::LinesCleared { lines },
+                ..
+            }) if !lines.is_empty() => lines,
+            _ => return,
+        };
+        let middle = rows.iter().map(|&y| y as f32 + 0.5).sum::<f32>() / rows.len() as f32;
+        let y = middle / FIELD_HEIGHT as f32 - POPUP_SIZE / 4.0;
+
+        self.popups
+            .push(&format!("+{}", score), CENTER_X, y, POPUP_SIZE);
+    }
+
+    /// Bursts every cell of the rows being cleared into particles of its colour
+    fn shatter_cleared_rows(&mut self) {
+        let rows = match self.game.effect() {
+            Some(BoardEffect {
+                ty: BoardEffectType::LinesCleared { lines },
+                ..
+            }) => lines,
+            _ => return,
+        };
+        let mut rng = rand::thread_rng();
+        for &y in rows {
+            for x in 0..FIELD_WIDTH {
+                let i = (x + y as u32 * FIELD_WIDTH) as usize;
+                if let Cell::Full(col) = self.game.board().cells[i] {
+                    self.particles.burst(
+                        x as f32,
+                        y as f32,
+                        col,
+                        PARTICLES_PER_CELL,
+                        PARTICLE_LIFE_TICKS,
+                        &mut rng,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Where to go once the run has ended: back to whoever started it, with
+    /// how it went
+    fn finished(&mut self, ending: Ending) -> lib::game::StateChange {
+        if self.demo {
+            return lib::game::StateChange::Pop;
+        }
+        if let (Some(puzzle), Ending::ToppedOut) = (self.game.puzzle(), ending) {
+            // failed puzzles start over straight away
+            let mut retry = Simulation::from_puzzle(puzzle.clone());
+            retry.set_rotation(self.game.rotation());
+            retry.countdown = 0;
+            return lib::game::StateChange::Swap(Box::new(TetrisMain::new(
+                retry,
+                self.config.clone(),
+            )));
+        }
+
+        self.record(true);
+
+        // the run counts towards whoever's playing
+        let mut profiles = Profiles::load_or_default();
+        let sprint_millis = match ending {
+            Ending::Cleared { millis } if self.game.mode() == GameMode::Sprint => Some(millis),
+            _ => None,
+        };
+        profiles
+            .active_mut()
+            .record_run(self.game.stats().lines, sprint_millis);
+        profiles
+            .store()
+            .unwrap_or_else(|e| eprintln!("Couldn't save profiles: {}", e));
+
+        // a new best time becomes the ghost to race next
+        if let (Some(mut replay), Some(millis)) = (self.replay.take(), sprint_millis) {
+            if self.best_millis.map_or(true, |best| millis < best) {
+                replay.millis = Some(millis);
+                replay
+                    .save(&paths::data_file(GHOST_FILE))
+                    .unwrap_or_else(|e| eprintln!("Couldn't save the ghost: {}", e));
+            }
+        }
+
+        lib::game::StateChange::PopWith(Box::new(RunResult {
+            profile: profiles.active().name.clone(),
+            mode: self.game.mode(),
+            stats: self.game.stats().clone(),
+            seed: self.game.seed(),
+            randomizer: self.game.randomizer(),
+            rotation: self.game.rotation(),
+            score: self.game.score(),
+            level: self.game.level(),
+            ending,
+            unlocked: self.unlocks.take(),
+            best_millis: self.best_millis,
+        }))
+    }
+
+    /// Writes the run and the keys held leading up to now to a new file named
+    /// after the time, returning its path
+    fn dump(&self) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let inputs: Vec<_> = self.recent_inputs.iter().copied().collect();
+        let text = dump::write(self.game.simulation(), &inputs);
+        std::fs::create_dir_all(DUMP_DIR)?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        let path = PathBuf::from(DUMP_DIR).join(format!(
+            "tetrs-{}-{:03}.txt",
+            now.as_secs(),
+            now.subsec_millis()
+        ));
+        std::fs::write(&path, text)?;
+        Ok(path)
+    }
+
+    /// Unlocks whatever the run has earned so far, queueing the unlocks to be
+    /// announced. A run that's `over` is added to the lifetime totals too.
+    /// Assisted runs and zen don't count.
+    fn record(&mut self, over: bool) {
+        if !achievements::counts(self.game.mode(), self.assisted) {
+            return;
+        }
+
+        let unlocked = if over {
+            self.achievements
+                .finish_run(&self.run, self.game.stats().lines)
+        } else {
+            self.achievements.record(&self.run)
+        };
+        if unlocked.is_empty() && !over {
+            return;
+        }
+        self.unlocks.extend(unlocked);
+        if let Err(e) = self.achievements.save(&paths::data_file(ACHIEVEMENTS_FILE)) {
+            eprintln!("Could not save achievements: {}", e);
+        }
+    }
+
+    /// Field, pieces and sidebar, in the playfield's own space, drawn into
+    /// the board's mesh from last frame
+    fn draw(&self, layout: &Layout, theme: &Theme) -> RefMut<'_, BoardMesh> {
+        const LINE_THICKNESS: f32 = 0.01;
+        const BORDER_THICKNESS: f32 = 0.01;
+
+        let palette = self.palette(theme);
+
+        // render fixed field
+        let mut mesh = self.mesh.borrow_mut();
+        mesh.clear();
+        let geometry = mesh.layer(Transform2D::IDENTITY);
+
+        // cells and pieces are drawn in playfield cells, and the next queue in
+        // cells from the top left of its first box
+        let cells = Transform2D::scaling(1.0 / FIELD_WIDTH as f32, 1.0 / FIELD_HEIGHT as f32);
+        let [column, row] = layout.preview_origin();
+        let previews = cells.then(Transform2D::translation(column, row));
+
+        // sink the field into a well. its border pulses on every gravity step, and at
+        // high levels the piece falls every frame, so the border just stays lit
+        let border = if self.metronome && self.pulse > 0 {
+            let intensity = self.pulse as f32 / METRONOME_PULSE_TICKS as f32;
+            mix(theme.border, theme.pulse, intensity)
+        } else {
+            theme.border
+        };
+        let field = Rect {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+        };
+        let mut well = PanelStyle {
+            fill: Some(theme.well),
+            border,
+            thickness: BORDER_THICKNESS,
+            shadow: Some(theme.shadow),
+        };
+        draw_panel_fill(geometry, &field, &well);
+
+        // render lines
+        // the reason we split our line rendering pass in two is because the X direction
+        // is stretched with the global matrix. for simplicity, we render everything in
+        // a single pass, which means that we need two different thickness values so the
+        // lines maintain a uniform scale, with the Y thickness being half of the X thick-
+        // ness. There's probably a more elegant solution out there but...
+
+        let grid_start = geometry.vertices().len();
+        let mut rows = [cgmath::Vector2::new(0.0, 0.0); 2 * (FIELD_HEIGHT as usize - 1)];
+        for (y, pair) in (1..FIELD_HEIGHT).zip(rows.chunks_exact_mut(2)) {
+            let y = y as f32 / FIELD_HEIGHT as f32;
+            pair[0] = cgmath::Vector2::new(0.0, y);
+            pair[1] = cgmath::Vector2::new(1.0, y);
+        }
+        lib::graphics::lines::render_lines_pairs(
+            geometry,
+            &rows,
+            LINE_THICKNESS / 2.0,
+            LineCap::Square,
+        );
+
+        let mut columns = [cgmath::Vector2::new(0.0, 0.0); 2 * (FIELD_WIDTH as usize - 1)];
+        for (x, pair) in (1..FIELD_WIDTH).zip(columns.chunks_exact_mut(2)) {
+            let x = x as f32 / FIELD_WIDTH as f32;
+            pair[0] = cgmath::Vector2::new(x, 0.0);
+            pair[1] = cgmath::Vector2::new(x, 1.0);
+        }
+        lib::graphics::lines::render_lines_pairs(
+            geometry,
+            &columns,
+            LINE_THICKNESS,
+            LineCap::Square,
+        );
+        // the grid lights up for a frame as a new level is reached
+        let grid = match self.level_up() {
+            Some((_, 0)) => mix(theme.grid, theme.active_text, LEVEL_UP_GRID_FLASH),
+            _ => theme.grid,
+        };
+        for vertex in &mut geometry.vertices_mut()[grid_start..] {
+            vertex.color = opaque(grid);
+        }
+        // the walls glow red while the stack is near the top, pulsing
+        if self.danger > 0 {
+            let fade = self.danger as f32 / DANGER_FADE_TICKS as f32;
+            let phase =
+                (self.game.ticker() % DANGER_PULSE_TICKS) as f32 / DANGER_PULSE_TICKS as f32;
+            let pulse = 0.75 + 0.25 * (phase * std::f32::consts::TAU).cos();
+            let alpha = DANGER_GLOW_ALPHA * fade * pulse;
+            push_edge_glow(
+                geometry,
+                &field,
+                DANGER_GLOW_CELLS / FIELD_WIDTH as f32,
+                DANGER_GLOW_CELLS / FIELD_HEIGHT as f32,
+                with_alpha(theme.fault, alpha),
+            );
+        }
+
+        // the grid's square ends reach under the border, so there's no gap
+        // where it meets the walls
+        draw_panel_border(geometry, &field, &well);
+
+        // clearing every row under the goal line finishes the sprint
+        let left = SPRINT_LINES.saturating_sub(self.game.lines());
+        if self.game.mode() == GameMode::Sprint && left > 0 && left < FIELD_HEIGHT {
+            let goal_start = geometry.vertices().len();
+            let y = (FIELD_HEIGHT - left) as f32 / FIELD_HEIGHT as f32;
+            lib::graphics::lines::render_polyline_even(
+                geometry,
+                &[cgmath::Vector2::new(0.0, y), cgmath::Vector2::new(1.0, y)],
+                GOAL_LINE_THICKNESS,
+                false,
+                GOAL_LINE_STYLE,
+            );
+            for vertex in &mut geometry.vertices_mut()[goal_start..] {
+                vertex.color = opaque(theme.highlight);
+            }
+        }
+
+        // the wait for the next rising row, shrinking towards the left
+        if let Some(left) = self.game.rise_left() {
+            let bar = layout.rise_bar();
+            let color = if left < RISE_WARNING {
+                theme.fault
+            } else {
+                theme.highlight
+            };
+            push_quad(
+                geometry,
+                bar.x,
+                bar.y,
+                bar.width * left,
+                bar.height,
+                opaque(color),
+            );
+        }
+
+        // frame the next queue to match. the frame's border is as thick as the
+        // well's, so it's placed here rather than drawn in the queue's cells
+        let queue = previews.apply_rect(&layout.preview_frame(self.game.next_pieces().len()));
+        well.border = theme.border;
+        draw_panel(geometry, &queue, &well);
+
+        // render cells, marked with their pattern if the theme asks for it.
+        // positions and scale are in cells of whichever layer they're added to
+        let add_cell = |geometry: &mut MeshBuilder,
+                        x: f32,
+                        y: f32,
+                        scale: f32,
+                        color: [f32; 3],
+                        alpha: f32,
+                        pattern: &str| {
+            push_cell(
+                geometry,
+                x,
+                y,
+                scale,
+                scale,
+                with_alpha(color, alpha),
+                theme,
+            );
+
+            if theme.patterns {
+                let mark = with_alpha(theme.fade(color, PATTERN_FADE), alpha);
+                let margin = scale * PATTERN_MARGIN;
+                let step = (scale - 2.0 * margin) / PATTERN_SIZE as f32;
+                for (i, _) in pattern.chars().enumerate().filter(|&(_, c)| c == '#') {
+                    let (column, row) = (i % PATTERN_SIZE, i / PATTERN_SIZE);
+                    push_quad(
+                        geometry,
+                        x + margin + column as f32 * step,
+                        y + margin + row as f32 * step,
+                        step,
+                        step,
+                        mark,
+                    );
+                }
+            }
+        };
+        let geometry = mesh.layer(cells);
+
+        let (spooky_lines, elapsed) = if let Some(BoardEffect {
+            ty: BoardEffectType::LinesCleared { ref lines },
+            life,
+        }) = self.game.effect()
+        {
+            (&lines[..], LINE_CLEAR_TICKS - *life)
+        } else {
+            (&[][..], 0)
+        };
+
+        // cleared rows wipe out from the middle, then whatever is above them
+        // slides down to where the field will be once they're deleted
+        let wipe_ticks = LINE_CLEAR_TICKS - LINE_COLLAPSE_TICKS;
+        let half_width = FIELD_WIDTH / 2;
+        let wiped = ((elapsed * half_width as u64 + wipe_ticks - 1) / wipe_ticks) as u32;
+        let fall = elapsed.saturating_sub(wipe_ticks - 1) as f32 / LINE_COLLAPSE_TICKS as f32;
+
+        // invisible stacks show up again while lines clear and once the run is over
+        let reveal = !self.game.mode().hides_stack()
+            || self.game.effect().map_or(false, |e| match e.ty {
+                BoardEffectType::LinesCleared { .. } => true,
+                ref ty => ty.ends_run(),
+            });
+
+        for y in 0..FIELD_HEIGHT {
+            for x in 0..FIELD_WIDTH {
+                let i = (x + y * FIELD_WIDTH) as usize;
+                if !reveal
+                    && self
+                        .game
+                        .ticker()
+                        .saturating_sub(self.game.locked_at(x as usize, y as usize))
+                        >= INVISIBLE_FADE_TICKS
+                {
+                    continue;
+                }
+                if let Cell::Full(col) = self.game.board().cells[i] {
+                    if spooky_lines.contains(&(y as i8)) {
+                        let from_middle = if x < half_width {
+                            half_width - 1 - x
+                        } else {
+                            x - half_width
+                        };
+                        if from_middle >= wiped {
+                            add_cell(
+                                geometry,
+                                x as f32,
+                                y as f32,
+                                1.0,
+                                palette.get(col),
+                                1.0,
+                                col.pattern(),
+                            );
+                        }
+                    } else {
+                        let below = spooky_lines.iter().filter(|&&l| l as u32 > y).count();
+                        add_cell(
+                            geometry,
+                            x as f32,
+                            y as f32 + below as f32 * fall,
+                            1.0,
+                            palette.get(col),
+                            1.0,
+                            col.pattern(),
+                        );
+                    }
+                }
+            }
+        }
+
+        // flash the last piece placed with a finesse fault
+        if let Some((cells, color, life)) = &self.fault_flash {
+            if life % 4 < 2 {
+                for &(x, y) in cells {
+                    add_cell(
+                        geometry,
+                        x as f32,
+                        y as f32,
+                        1.0,
+                        theme.fault,
+                        1.0,
+                        color.pattern(),
+                    );
+                }
+            }
+        }
+
+        // render active piece, over a ghost of where it would land. the ghost is see
+        // through, so it goes over the field but leaves the piece to cover it
+        if let Some(piece) = self.game.active_piece() {
+            let color = piece.kind.color;
+            let mut ghost = piece;
+            let mut below = piece;
+            below.y += below.scale as i8;
+            while self.game.board().fits(&below) {
+                ghost = below;
+                below.y += below.scale as i8;
+            }
+            if ghost.y != piece.y {
+                for (x, y) in ghost.cells() {
+                    add_cell(
+                        geometry,
+                        x as f32,
+                        y as f32,
+                        1.0,
+                        palette.get(color),
+                        GHOST_ALPHA,
+                        color.pattern(),
+                    );
+                }
+
+                // the outline is as thick as the field's lines, so it's drawn
+                // in the field's space
+                let mut outlines = std::mem::take(&mut mesh.outlines);
+                let mut corners = std::mem::take(&mut mesh.corners);
+                outlines.trace(ghost.cells().map(|(x, y)| (x as i32, y as i32)));
+                let geometry = mesh.layer(Transform2D::IDENTITY);
+                for outline in outlines.iter() {
+                    corners.clear();
+                    corners.extend(
+                        outline
+                            .iter()
+                            .map(|corner| cgmath::Vector2::from(cells.apply([corner.x, corner.y]))),
+                    );
+                    lib::graphics::lines::render_polyline_even(
+                        geometry,
+                        &corners,
+                        GHOST_OUTLINE_THICKNESS,
+                        true,
+                        GHOST_OUTLINE_STYLE,
+                    );
+                }
+                for vertex in geometry.vertices_mut() {
+                    vertex.color = opaque(palette.get(color));
+                }
+                mesh.outlines = outlines;
+                mesh.corners = corners;
+            }
+
+            let geometry = mesh.layer(cells);
+            let (dx, dy) = self.glide(piece);
+            for (x, y) in piece.cells() {
+                add_cell(
+                    geometry,
+                    x as f32 + dx,
+                    y as f32 + dy,
+                    1.0,
+                    palette.get(color),
+                    1.0,
+                    color.pattern(),
+                );
+            }
+        }
+
+        // render next pieces
+        let geometry = mesh.layer(previews);
+        let count = self.game.next_pieces().len();
+        for (i, piece) in self.game.next_pieces().iter().enumerate() {
+            let slot = layout.preview_slot(i, count);
+            // previews keep their size, so big minos and pieces in bigger boxes
+            // are drawn with smaller cells
+            let box_cells = piece.kind.size as f32 * piece.scale as f32;
+            let cell = slot.scale * PREVIEW_BOX_CELLS / box_cells;
+            let origin = Piece {
+                x: 0,
+                y: 0,
+                ..*piece
+            };
+            // centre the cells the piece fills in its box, not the whole shape
+            let (left, top, width, height) = piece.bounds();
+            let mino = piece.scale as f32 * cell;
+            let room = slot.scale * PREVIEW_BOX_CELLS;
+            let dx = (room - width as f32 * mino) / 2.0 - left as f32 * mino;
+            let dy = (room - height as f32 * mino) / 2.0 - top as f32 * mino;
+            for (x, y) in origin.cells() {
+                add_cell(
+                    geometry,
+                    dx + x as f32 * cell,
+                    slot.row + dy + y as f32 * cell,
+                    cell,
+                    palette.get(piece.kind.color),
+                    1.0,
+                    piece.kind.color.pattern(),
+                );
+            }
+        }
+
+        // particles go over the field, inside its cell grid
+        self.particles
+            .render((1.0, 1.0), &palette, theme, mesh.layer(cells));
+
+        // render text
+        let mut text = std::mem::take(&mut mesh.text);
+        let mut label = std::mem::take(&mut mesh.label);
+
+        self.draw_ghost(&mut mesh, &mut text, &mut label, layout, theme);
+
+        self.popups
+            .render(&mut text, POPUP_SIZE, theme.active_text, theme);
+        self.callouts
+            .render(&mut text, CENTER_X, CALLOUT_Y, CALLOUT_SIZE, theme);
+
+        let placement = layout.preview_label();
+        lib::graphics::text::render_styled(
+            &mut text,
+            "NEXT",
+            placement.x,
+            placement.y,
+            &TextStyle::new(placement.size, theme.active_text).shadow(theme.shadow),
+        );
+
+        // the sidebar stacks up from the bottom, so the last line goes lowest
+        let mut hud = self.hud_lines.borrow_mut();
+        let count = self.hud(theme, &mut hud);
+        for (i, (line, color)) in hud[..count].iter().rev().enumerate() {
+            let placement = layout.hud_line(i);
+            lib::graphics::text::render_styled(
+                &mut text,
+                line,
+                placement.x,
+                placement.y,
+                &TextStyle::new(placement.size, *color)
+                    .shadow(theme.shadow)
+                    .monospace(),
+            );
+        }
+
+        if let Some(BoardEffect {
+            ty: BoardEffectType::GameOver,
+            ..
+        }) = self.game.effect()
+        {
+            let banner = layout.banner("GAME OVER!");
+            lib::graphics::text::render_styled(
+                &mut text,
+                "GAME OVER!",
+                CENTER_X,
+                banner.y,
+                &TextStyle::new(banner.size, theme.active_text).align(Align::Center),
+            );
+        }
+
+        if let Some(BoardEffect {
+            ty: BoardEffectType::TimeUp,
+            ..
+        }) = self.game.effect()
+        {
+            let banner = layout.banner("TIME UP!");
+            lib::graphics::text::render_styled(
+                &mut text,
+                "TIME UP!",
+                CENTER_X,
+                banner.y,
+                &TextStyle::new(banner.size, theme.active_text).align(Align::Center),
+            );
+        }
+
+        if let Some(BoardEffect {
+            ty: BoardEffectType::Win,
+            life,
+        }) = self.game.effect()
+        {
+            let banner = layout.banner("YOU WIN!");
+            lib::graphics::text::render_styled(
+                &mut text,
+                "YOU WIN!",
+                CENTER_X,
+                banner.y,
+                &TextStyle::new(
+                    banner.size,
+                    if life % 10 < 5 {
+                        theme.active_text
+                    } else {
+                        theme.highlight
+                    },
+                )
+                .align(Align::Center),
+            );
+        }
+
+        if let Some((level, elapsed)) = self.level_up() {
+            // fades in from the well as it enters and back out as it leaves
+            label.clear();
+            let _ = write!(label, "LEVEL {}", level);
+            let progress = elapsed as f32 / LEVEL_UP_TICKS as f32;
+            let fade = (std::f32::consts::PI * progress).sin().min(0.5) * 2.0;
+            let banner = layout.level_banner(&label, progress);
+            lib::graphics::text::render_styled(
+                &mut text,
+                &label,
+                banner.x,
+                banner.y,
+                &TextStyle::new(banner.size, mix(theme.well, theme.highlight, fade)),
+            );
+        }
+
+        if self.game.mode() == GameMode::Practice {
+            let gravity = if self.game.is_frozen() {
+                "F: Gravity off"
+            } else {
+                "F: Gravity on"
+            };
+            let legend = ["U: Undo", "Bksp: Clear", gravity, "1-7: IOTSZJL"];
+            for (i, txt) in legend.iter().enumerate() {
+                let line = layout.legend_line(i, txt);
+                lib::graphics::text::render_text(
+                    &mut text,
+                    txt,
+                    line.x,
+                    line.y,
+                    line.size,
+                    theme.inactive_text,
+                );
+            }
+        }
+
+        let left = self.game.countdown().max(self.resume_countdown);
+        label.clear();
+        let countdown = if left > 0 {
+            // each number starts small and grows over its second
+            let number = (left + TICKS_PER_SECOND - 1) / TICKS_PER_SECOND;
+            let grow = 1.0 - ((left - 1) % TICKS_PER_SECOND) as f32 / TICKS_PER_SECOND as f32;
+            let _ = write!(label, "{}", number);
+            Some((grow, theme.active_text))
+        } else if self.game.has_started() && self.game.elapsed_ticks() < TICKS_PER_SECOND / 2 {
+            label.push_str("GO!");
+            Some((1.0, theme.highlight))
+        } else {
+            None
+        };
+        if let Some((grow, color)) = countdown {
+            let placement = layout.countdown(&label, grow);
+            lib::graphics::text::render_text(
+                &mut text,
+                &label,
+                placement.x,
+                placement.y,
+                placement.size,
+                color,
+            );
+        }
+
+        if self.demo && self.game.ticker() % 20 < 10 {
+            let demo_txt = "DEMO - PRESS ANY KEY";
+            let placement = layout.subtitle(demo_txt);
+            lib::graphics::text::render_text(
+                &mut text,
+                demo_txt,
+                placement.x,
+                placement.y,
+                placement.size,
+                theme.highlight,
+            );
+        }
+
+        if self.restart_held > 0 {
+            label.clear();
+            let _ = write!(label, "Hold {:?} to restart", self.keys.restart);
+            let placement = layout.restart_label(&label);
+            lib::graphics::text::render_styled(
+                &mut text,
+                &label,
+                placement.x,
+                placement.y,
+                &TextStyle::new(placement.size, theme.highlight).shadow(theme.shadow),
+            );
+            let bar = layout.restart_bar(&label);
+            let held = self.restart_held as f32 / RESTART_HOLD_TICKS as f32;
+            push_quad(
+                mesh.layer(Transform2D::IDENTITY),
+                bar.x,
+                bar.y,
+                bar.width * held,
+                bar.height,
+                opaque(theme.highlight),
+            );
+        }
+
+        self.unlocks.render(&mut text, layout, theme);
+
+        mesh.text = text;
+        mesh.label = label;
+        mesh
+    }
+
+    /// The ghost being raced, left of the playfield: its field if the config
+    /// asks for it, then a bar of the lines it's cleared and how it's doing.
+    /// The count is highlighted while the player is ahead, and in the fault
+    /// colour while they're behind
+    fn draw_ghost(
+        &self,
+        mesh: &mut BoardMesh,
+        text: &mut MeshBuilder,
+        label: &mut String,
+        layout: &Layout,
+        theme: &Theme,
+    ) {
+        const BORDER_THICKNESS: f32 = 0.005;
+
+        let ghost = match &self.ghost {
+            Some(ghost) => ghost,
+            None => return,
+        };
+        let style = PanelStyle {
+            fill: None,
+            border: theme.border,
+            thickness: BORDER_THICKNESS,
+            shadow: None,
+        };
+
+        // only the field the ghost has right now, without its animations
+        if self.config.borrow().ghost == GhostDisplay::Board {
+            let field = layout.ghost_field();
+            draw_panel(mesh.layer(Transform2D::IDENTITY), &field, &style);
+            let cells = mesh.layer(Transform2D::translation(field.x, field.y).then(
+                Transform2D::scaling(
+                    field.width / FIELD_WIDTH as f32,
+                    field.height / FIELD_HEIGHT as f32,
+                ),
+            ));
+            let palette = theme.palette_for_level(ghost.sim.level());
+            for (i, &cell) in ghost.sim.board.cells.iter().enumerate() {
+                if let Cell::Full(col) = cell {
+                    let x = (i as u32 % FIELD_WIDTH) as f32;
+                    let y = (i as u32 / FIELD_WIDTH) as f32;
+                    let color = with_alpha(palette.get(col), RACE_ALPHA);
+                    push_cell(cells, x, y, 1.0, 1.0, color, theme);
+                }
+            }
+            if let Some(piece) = ghost.sim.active_piece {
+                let color = with_alpha(palette.get(piece.kind.color), RACE_ALPHA);
+                for (x, y) in piece.cells() {
+                    push_cell(cells, x as f32, y as f32, 1.0, 1.0, color, theme);
+                }
+            }
+        }
+
+        let bar = layout.ghost_bar();
+        let geometry = mesh.layer(Transform2D::IDENTITY);
+        let progress = ghost.sim.lines.min(SPRINT_LINES) as f32 / SPRINT_LINES as f32;
+        push_quad(
+            geometry,
+            bar.x,
+            bar.y,
+            bar.width * progress,
+            bar.height,
+            with_alpha(theme.inactive_text, RACE_ALPHA),
+        );
+        draw_panel_border(geometry, &bar, &style);
+
+        label.clear();
+        let color = if ghost.failed() {
+            label.push_str("Ghost out");
+            theme.inactive_text
+        } else if let Some(millis) = ghost.sim.finish_time {
+            let _ = write!(label, "Ghost {}", Finish(millis));
+            theme.fault
+        } else {
+            let _ = write!(label, "Ghost {}", ghost.sim.lines);
+            match self.game.lines().cmp(&ghost.sim.lines) {
+                std::cmp::Ordering::Greater => theme.highlight,
+                std::cmp::Ordering::Less => theme.fault,
+                std::cmp::Ordering::Equal => theme.active_text,
+            }
+        };
+        let placement = layout.ghost_label();
+        lib::graphics::text::render_styled(
+            text,
+            label,
+            placement.x,
+            placement.y,
+            &TextStyle::new(placement.size, color).shadow(theme.shadow),
+        );
+    }
+}
+
+/// How a run went, handed back to the screen that started it
+struct RunResult {
+    /// Name of the profile that played it
+    profile: String,
+    mode: GameMode,
+    stats: GameStats,
+    seed: u64,
+    randomizer: RandomizerKind,
+    rotation: RotationKind,
+    score: u64,
+    /// Level the run ended on
+    level: u32,
+    ending: Ending,
+    /// Achievements unlocked that haven't been announced yet
+    unlocked: Vec<&'static Achievement>,
+    /// Fastest time on file before the run, if it was a sprint
+    best_millis: Option<u64>,
+}
+
+impl RunResult {
+    /// A leaderboard entry for the run, worth `value`
+    fn entry(&self, value: u64) -> ScoreEntry {
+        // named after the profile, unless the player types in another name
+        // on the scores screen
+        let entry = ScoreEntry::new(
+            &self.profile,
+            value,
+            self.mode,
+            self.stats.lines,
+            self.level,
+        )
+        .by(&self.profile);
+        if self.mode == GameMode::Sprint {
+            let splits = self
+                .stats
+                .splits
+                .iter()
+                .map(|&ticks| ticks_to_millis(ticks));
+            entry.with_splits(splits.collect())
+        } else {
+            entry
+        }
+    }
+
+    /// The run's summary, followed by its results or the leaderboard. The
+    /// run is kept in `session`, and compared against the last one of its
+    /// mode kept there
+    fn summary(self, session: &SharedSession) -> Box<dyn GameState> {
+        let mode = self.mode;
+        let delta = session
+            .borrow()
+            .last(mode)
+            .map(|last| stats::diff(&self.stats, last));
+        session.borrow_mut().record(mode, self.stats.clone());
+        let next: Option<Box<dyn GameState>> = match self.ending {
+            Ending::Cleared { millis } => Some(Box::new(TetrisResults::new(
+                self.entry(millis),
+                self.best_millis,
+            ))),
+            Ending::Won if mode.is_ranked() => Some(Box::new(TetrisScores {
+                inputting_score: Some(self.entry(self.score | MARATHON_CLEAR_FLAG)),
+                ..TetrisScores::new(mode)
+            })),
+            // topping out fails a race, nothing gets recorded
+            Ending::ToppedOut | Ending::TimeUp if !mode.ranks_by_time() && mode.is_ranked() => {
+                Some(Box::new(TetrisScores {
+                    inputting_score: if self.score > 0 {
+                        Some(self.entry(self.score))
+                    } else {
+                        None
+                    },
+                    ..TetrisScores::new(mode)
+                }))
+            }
+            _ => None,
+        };
+        Box::new(TetrisSummary::new(
+            mode,
+            self.stats,
+            delta,
+            self.seed,
+            self.randomizer,
+            self.rotation,
+            self.unlocked,
+            next,
+        ))
+    }
+
+    /// Shows how the run went if `result` is one, on top of the current state
+    fn reveal(result: Box<dyn Any>, session: &SharedSession) -> lib::game::StateChange {
+        match result.downcast::<RunResult>() {
+            Ok(run) => lib::game::StateChange::Push(run.summary(session)),
+            Err(_) => lib::game::StateChange::None,
+        }
+    }
+}
+
+impl Default for TetrisMain {
+    fn default() -> Self {
+        Self::new(
+            Simulation::new(
+                GameMode::Endless,
+                DEFAULT_PREVIEWS,
+                None,
+                RandomizerKind::default(),
+            ),
+            SharedConfig::default(),
+        )
+    }
+}
+
+/// Two boards side by side on one keyboard. Lines one player clears come up as
+/// garbage on the other's field, until someone tops out
+struct TetrisVersus {
+    /// Player one's board on the left, player two's on the right
+    boards: [TetrisMain; 2],
+
+    /// Set once someone tops out: the player left standing, or None if both
+    /// went at once
+    winner: Option<Option<usize>>,
+
+    /// Previous frame player input, read once the match is over
+    last_input: PlayerInput,
+
+    /// Paces updates to the tick rate and counts the ticks
+    clock: FixedTimestep,
+}
+
+impl TetrisVersus {
+    fn new(
+        previews: usize,
+        level: u32,
+        instant_gravity: bool,
+        seed: Option<u64>,
+        config: SharedConfig,
+    ) -> Self {
+        // both players are dealt the same pieces
+        let seed = seed.unwrap_or_else(rand::random);
+        let randomizer = config.borrow().randomizer;
+        let rotation = config.borrow().rotation;
+        // the gamepad plays on the first board
+        let board = |keys, pad| {
+            let mut sim =
+                Simulation::with_level(GameMode::Versus, previews, level, Some(seed), randomizer);
+            sim.instant_gravity = instant_gravity;
+            sim.set_rotation(rotation);
+            TetrisMain {
+                keys,
+                pad,
+                ..TetrisMain::new(sim, config.clone())
+            }
+        };
+        Self {
+            boards: [
+                board(KeyMap::PLAYER_ONE, config.borrow().pad),
+                board(KeyMap::PLAYER_TWO, PadMap::NONE),
+            ],
+            winner: None,
+            last_input: PlayerInput::all_pressed(),
+            clock: FixedTimestep::default(),
+        }
+    }
+}
+
+impl GameState for TetrisVersus {
+    fn update(
+        &mut self,
+        snapshot: &InputSnapshot,
+        dt: std::time::Duration,
+    ) -> lib::game::StateChange {
+        if self.winner.is_none() {
+            for board in &mut self.boards {
+                if let lib::game::StateChange::Pop = board.update(snapshot, dt) {
+                    return lib::game::StateChange::Pop;
+                }
+            }
+
+            let [one, two] = &mut self.boards;
+            let (one, two) = (&mut one.game, &mut two.game);
+            let sent = [one.take_garbage(), two.take_garbage()];
+            one.send_garbage(sent[1]);
+            two.send_garbage(sent[0]);
+
+            let topped_out = [one.topped_out(), two.topped_out()];
+            if topped_out.contains(&true) {
+                // freeze both boards where they are
+                self.winner = Some(topped_out.iter().position(|&t| !t));
+            }
+            return lib::game::StateChange::None;
+        }
+
+        self.clock.accumulate(dt);
+        while self.clock.tick() {
+            let input = input(snapshot, self.last_input);
+            self.last_input = input;
+            if input.confirmed() || input.backed_out() {
+                return lib::game::StateChange::Pop;
+            }
+        }
+
+        lib::game::StateChange::None
+    }
+
+    fn render(&self, graphics: &lib::graphics::GraphicsState, frame: &mut Frame) {
+        let layout = graphics.layout();
+
+        // zoomed out to fit both boards, each drawn in its own place
+        let mut camera = Camera::new(lib::graphics::view_projection(&layout.versus_view()));
+        for (player, board) in self.boards.iter().enumerate() {
+            let mut mesh = board.draw(&layout, &graphics.theme);
+            if Some(Some(player)) == self.winner && self.clock.ticker() % 10 < 5 {
+                let banner = layout.banner("WINNER!");
+                lib::graphics::text::render_styled(
+                    &mut mesh.text,
+                    "WINNER!",
+                    CENTER_X,
+                    banner.y,
+                    &TextStyle::new(banner.size, graphics.theme.highlight).align(Align::Center),
+                );
+            }
+
+            camera.push();
+            camera.translate(layout.versus_board_x(player), 0.0);
+            mesh.submit(graphics, frame, &camera);
+            camera.pop();
+        }
+    }
+
+    fn take_sounds(&mut self) -> Vec<SoundId> {
+        self.boards
+            .iter_mut()
+            .flat_map(|board| board.take_sounds())
+            .collect()
+    }
+
+    fn music(&self) -> Option<Music> {
+        Some(Music::Play {
+            track: Track::Game,
+            hurried: self.winner.is_none() && self.boards.iter().any(TetrisMain::in_danger),
+        })
+    }
+}
+
+/// Which side of an online game this instance plays, from the command line
+enum Role {
+    /// Wait for an opponent on this port
+    Host(u16),
+    /// Join the host at this address
+    Join(String),
+}
+
+/// Versus against another instance over the network. Each side plays its own
+/// board and only tells the other about garbage, its field and topping out
+struct TetrisOnline {
+    /// Listening for the opponent, until they join
+    host: Option<net::Host>,
+
+    /// Link to the opponent
+    connection: Option<Connection>,
+
+    /// This player's board, once the host has dealt the seed
+    board: Option<TetrisMain>,
+
+    /// What's drawn while there's no board, kept between frames
+    mesh: RefCell<BoardMesh>,
+
+    /// Seed to deal from if hosting, or None for a random one
+    seed: Option<u64>,
+
+    /// Opponent's field as last reported, row by row from the top
+    opponent: Vec<bool>,
+
+    /// Board frame the next snapshot is due on
+    next_snapshot: u64,
+
+    /// Shown while waiting for the game to start
+    status: String,
+
+    /// Why the game is over; any key then leaves
+    notice: Option<String>,
+
+    /// Options the game is played with
+    config: SharedConfig,
+
+    /// Previous frame player input, read while there's no game running
+    last_input: PlayerInput,
+
+    /// Paces updates to the tick rate and counts the ticks
+    clock: FixedTimestep,
+}
+
+impl TetrisOnline {
+    fn new(role: Role, seed: Option<u64>, config: SharedConfig) -> Self {
+        let mut s = Self {
+            host: None,
+            connection: None,
+            board: None,
+            mesh: RefCell::default(),
+            seed,
+            config,
+            opponent: Vec::new(),
+            next_snapshot: 0,
+            status: String::new(),
+            notice: None,
+            last_input: PlayerInput::all_pressed(),
+            clock: FixedTimestep::default(),
+        };
+        match role {
+            Role::Host(port) => match net::Host::bind(port) {
+                Ok(host) => {
+                    s.host = Some(host);
+                    s.status = format!("Waiting on port {}", port);
+                }
+                Err(e) => s.notice = Some(format!("Could not host: {}", e)),
+            },
+            Role::Join(address) => match Connection::connect(&address) {
+                Ok(connection) => {
+                    s.connection = Some(connection);
+                    s.status = "Waiting for the host".to_string();
+                }
+                Err(e) => s.notice = Some(format!("Could not join: {}", e)),
+            },
+        }
+        s
+    }
+
+    fn board(
+        seed: u64,
+        randomizer: RandomizerKind,
+        rotation: RotationKind,
+        config: SharedConfig,
+    ) -> TetrisMain {
+        let mut sim = Simulation::new(GameMode::Versus, DEFAULT_PREVIEWS, Some(seed), randomizer);
+        sim.set_rotation(rotation);
+        TetrisMain::new(sim, config)
+    }
+
+    /// Takes in the opponent, if hosting, and whatever they have sent
+    fn network(&mut self) -> Result<(), NetError> {
+        if let Some(host) = &self.host {
+            if let Some(mut connection) = host.accept()? {
+                // the joining side plays by the host's randomizer and rotation
+                let seed = self.seed.unwrap_or_else(rand::random);
+                let randomizer = self.config.borrow().randomizer;
+                let rotation = self.config.borrow().rotation;
+                connection.send(&Message::Start {
+                    version: net::PROTOCOL_VERSION,
+                    seed,
+                    randomizer,
+                    rotation,
+                });
+                let board = Self::board(seed, randomizer, rotation, self.config.clone());
+                self.board = Some(board);
+                self.connection = Some(connection);
+                // nobody else gets in
+                self.host = None;
+            }
+        }
+
+        let connection = match &mut self.connection {
+            Some(connection) => connection,
+            None => return Ok(()),
+        };
+        for message in connection.poll()? {
+            match message {
+                Message::Start {
+                    version,
+                    seed,
+                    randomizer,
+                    rotation,
+                } => {
+                    if version != net::PROTOCOL_VERSION {
+                        return Err(NetError::WrongVersion { theirs: version });
+                    }
+                    if self.board.is_none() {
+                        let board = Self::board(seed, randomizer, rotation, self.config.clone());
+                        self.board = Some(board);
+                    }
+                }
+                Message::Garbage { rows } => {
+                    if let Some(board) = &mut self.board {
+                        board.game.send_garbage(rows);
+                    }
+                }
+                Message::Snapshot { cells } => {
+                    self.opponent = cells.iter().map(|&c| c != 0).collect();
+                }
+                Message::ToppedOut => {
+                    if self.notice.is_none() {
+                        self.notice = Some("YOU WIN!".to_string());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GameState for TetrisOnline {
+    fn update(
+        &mut self,
+        snapshot: &InputSnapshot,
+        dt: std::time::Duration,
+    ) -> lib::game::StateChange {
+        if let Err(e) = self.network() {
+            if self.notice.is_none() {
+                self.notice = Some(format!("Game over: {}", e));
+            }
+            // nothing more to say to them
+            self.connection = None;
+        }
+
+        let playing = self.notice.is_none();
+        if let (Some(board), true) = (&mut self.board, playing) {
+            if let lib::game::StateChange::Pop = board.update(snapshot, dt) {
+                // dropping the connection tells the opponent we left
+                return lib::game::StateChange::Pop;
+            }
+
+            if let Some(connection) = &mut self.connection {
+                let rows = board.game.take_garbage();
+                if rows > 0 {
+                    connection.send(&Message::Garbage { rows });
+                }
+                if board.game.ticker() >= self.next_snapshot {
+                    self.next_snapshot = board.game.ticker() + SNAPSHOT_TICKS;
+                    let cells = board
+                        .game
+                        .board()
+                        .cells
+                        .iter()
+                        .map(|&c| (c != Cell::Empty) as u8)
+                        .collect();
+                    connection.send(&Message::Snapshot { cells });
+                }
+                if board.game.topped_out() {
+                    connection.send(&Message::ToppedOut);
+                    self.notice = Some("YOU LOSE".to_string());
+                }
+            }
+            return lib::game::StateChange::None;
+        }
+
+        // waiting for the game to start, or it's over
+        self.clock.accumulate(dt);
+        while self.clock.tick() {
+            let input = input(snapshot, self.last_input);
+            self.last_input = input;
+            if input.backed_out() || (input.confirmed() && self.notice.is_some()) {
+                return lib::game::StateChange::Pop;
+            }
+        }
+
+        lib::game::StateChange::None
+    }
+
+    fn render(&self, graphics: &lib::graphics::GraphicsState, frame: &mut Frame) {
+        const BORDER_THICKNESS: f32 = 0.005;
+
+        let layout = graphics.layout();
+
+        let mut mesh = match &self.board {
+            Some(board) => board.draw(&layout, &graphics.theme),
+            None => {
+                let mut mesh = self.mesh.borrow_mut();
+                mesh.clear();
+                mesh
+            }
+        };
+
+        // frame the opponent's field, then fill in what they last reported in
+        // its own cells
+        let field = layout.opponent_field();
+        let style = PanelStyle {
+            fill: Some(graphics.theme.well),
+            border: graphics.theme.border,
+            thickness: BORDER_THICKNESS,
+            shadow: None,
+        };
+        draw_panel(mesh.layer(Transform2D::IDENTITY), &field, &style);
+
+        let cells = mesh.layer(Transform2D::translation(field.x, field.y).then(
+            Transform2D::scaling(
+                field.width / FIELD_WIDTH as f32,
+                field.height / FIELD_HEIGHT as f32,
+            ),
+        ));
+        let color = opaque(graphics.theme.piece(Color::Grey));
+        for (i, _) in self
+            .opponent
+            .iter()
+            .enumerate()
+            .filter(|&(_, &filled)| filled)
+        {
+            let x = (i as u32 % FIELD_WIDTH) as f32;
+            let y = (i as u32 / FIELD_WIDTH) as f32;
+            push_cell(cells, x, y, 1.0, 1.0, color, &graphics.theme);
+        }
+
+        // waiting for the game, or the reason it's over
+        let message = match &self.notice {
+            Some(notice) => Some((notice.as_str(), graphics.theme.highlight)),
+            None if self.board.is_none() => {
+                Some((self.status.as_str(), graphics.theme.active_text))
+            }
+            None => None,
+        };
+        if let Some((txt, color)) = message {
+            let placement = layout.toast(txt);
+            lib::graphics::text::render_text(
+                &mut mesh.text,
+                txt,
+                placement.x,
+                placement.y,
+                placement.size,
+                color,
+            );
+        }
+
+        mesh.submit(graphics, frame, &Camera::new(graphics.projection()))
+    }
+
+    fn take_sounds(&mut self) -> Vec<SoundId> {
+        self.board
+            .as_mut()
+            .map_or_else(Vec::new, |board| board.take_sounds())
+    }
+
+    fn music(&self) -> Option<Music> {
+        // the menu's music plays on until the game starts
+        let board = self.board.as_ref()?;
+        Some(Music::Play {
+            track: Track::Game,
+            hurried: self.notice.is_none() && board.in_danger(),
+        })
+    }
+}
+
+/// Draws a board as busy as the game gets, over and over, for
+/// `--bench-render`: a nearly full field, every preview, the practice legend
+/// and particles flying the whole time. Frames are timed once the first few
+/// are out of the way, then the numbers are printed and the game quits
+struct TetrisBench {
+    /// Run drawn, which is never stepped, so every frame shows the same field
+    scene: TetrisMain,
+    /// Picks where particles come from, seeded so every run throws the same
+    rng: rand::rngs::StdRng,
+    /// Frames still to be drawn before timing starts
+    warmup: u32,
+    /// Time each timed frame took, and the bytes it sent to the GPU
+    times: Vec<std::time::Duration>,
+    uploads: Vec<u64>,
+    /// Bytes the last frame drawn sent to the GPU
+    uploaded: std::cell::Cell<u64>,
+    /// Simulation numbers, printed along with these
+    simulation: SimBench,
+    /// Whether to print JSON rather than text
+    json: bool,
+}
+
+impl TetrisBench {
+    fn new(config: SharedConfig, simulation: SimBench, json: bool) -> Self {
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(BENCH_SEED);
+        let mut sim = Simulation::new(
+            GameMode::Practice,
+            MAX_PREVIEWS,
+            Some(BENCH_SEED),
+            RandomizerKind::default(),
+        );
+        // every row but the top few full of every colour, a hole in each so
+        // none of them clears
+        for y in BENCH_EMPTY_ROWS..FIELD_HEIGHT {
+            let hole = rng.gen_range(0..FIELD_WIDTH);
+            for x in (0..FIELD_WIDTH).filter(|&x| x != hole) {
+                let color = Tetromino::random(&mut rng).color();
+                sim.board.cells[(x + y * FIELD_WIDTH) as usize] = Cell::Full(color);
+            }
+        }
+        // past the countdown, with a piece out
+        sim.countdown = 0;
+        sim.step(Inputs::default());
+
+        let scene = TetrisMain {
+            metronome: true,
+            assisted: true,
+            ..TetrisMain::new(sim, config)
+        };
+        Self {
+            scene,
+            rng,
+            warmup: BENCH_WARMUP_FRAMES,
+            times: Vec::with_capacity(BENCH_FRAMES),
+            uploads: Vec::with_capacity(BENCH_FRAMES),
+            uploaded: std::cell::Cell::new(0),
+            simulation,
+            json,
+        }
+    }
+
+    /// Keeps particles coming out of the field, about as many as a four line
+    /// clear throws, moving the ones already out along
+    fn stir_particles(&mut self) {
+        for _ in 0..FIELD_WIDTH {
+            let x = self.rng.gen_range(0..FIELD_WIDTH);
+            let y = self.rng.gen_range(BENCH_EMPTY_ROWS..FIELD_HEIGHT);
+            let color = Tetromino::random(&mut self.rng).color();
+            self.scene.particles.burst(
+                x as f32,
+                y as f32,
+                color,
+                PARTICLES_PER_CELL * 4,
+                PARTICLE_LIFE_TICKS,
+                &mut self.rng,
+            );
+        }
+        self.scene.particles.update();
+    }
+}
+
+impl GameState for TetrisBench {
+    fn update(
+        &mut self,
+        _snapshot: &InputSnapshot,
+        dt: std::time::Duration,
+    ) -> lib::game::StateChange {
+        // dt is the frame before this update, drawing and presenting included
+        if self.warmup > 0 {
+            self.warmup -= 1;
+        } else {
+            self.times.push(dt);
+            self.uploads.push(self.uploaded.get());
+        }
+
+        if self.times.len() >= BENCH_FRAMES {
+            let report = BenchReport {
+                simulation: self.simulation,
+                render: Some(FrameBench::new(&self.times, &self.uploads)),
+            };
+            print_bench(&report, self.json);
+            return lib::game::StateChange::Quit;
+        }
+
+        self.stir_particles();
+        lib::game::StateChange::None
+    }
+
+    fn render(&self, graphics: &lib::graphics::GraphicsState, frame: &mut Frame) {
+        graphics.take_uploaded_bytes();
+        self.scene.render(graphics, frame);
+        self.uploaded.set(graphics.take_uploaded_bytes());
+    }
+}
+
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+struct PlayerInput {
+    up: KeyState,
+    down: KeyState,
+    left: KeyState,
+    right: KeyState,
+    rot_right: KeyState,
+    rot_left: KeyState,
+    escape: KeyState,
+    confirm: KeyState,
+    restart: KeyState,
+    /// T, for tempo, since M mutes
+    metronome: KeyState,
+    /// Practice mode bindings: next piece, by position in `Tetromino::ALL`
+    pick: [KeyState; 7],
+    freeze: KeyState,
+    /// Backspace, which clears the board in practice and takes back a typed
+    /// character
+    clear: KeyState,
+    undo: KeyState,
+    finesse: KeyState,
+    instant_gravity: KeyState,
+    /// F8, which dumps the game for a bug report
+    dump: KeyState,
+    /// Writes the leaderboards out on the scores screen
+    export: KeyState,
+    /// Return, which finishes typing
+    enter: KeyState,
+    /// Snapshot frame this was read on, or 0 if it wasn't
+    frame: u64,
+}
+
+impl PlayerInput {
+    /// Whether any key went down this frame
+    fn any_pressed(&self) -> bool {
+        let keys = [
+            self.up,
+            self.down,
+            self.left,
+            self.right,
+            self.rot_right,
+            self.rot_left,
+            self.escape,
+            self.confirm,
+            self.restart,
+            self.metronome,
+            self.freeze,
+            self.clear,
+            self.undo,
+            self.finesse,
+            self.instant_gravity,
+            self.dump,
+            self.export,
+            self.enter,
+        ];
+        keys.iter()
+            .chain(self.pick.iter())
+            .any(|&k| k == KeyState::Pressed)
+    }
+
+    /// State of the key bound to `action`
+    fn action(&self, action: Action) -> KeyState {
+        match action {
+            Action::Up => self.up,
+            Action::Down => self.down,
+            Action::Left => self.left,
+            Action::Right => self.right,
+            Action::RotateRight => self.rot_right,
+            Action::RotateLeft => self.rot_left,
+            Action::Confirm => self.confirm,
+            Action::Back => self.escape,
+            Action::Restart => self.restart,
+        }
+    }
+
+    fn action_mut(&mut self, action: Action) -> &mut KeyState {
+        match action {
+            Action::Up => &mut self.up,
+            Action::Down => &mut self.down,
+            Action::Left => &mut self.left,
+            Action::Right => &mut self.right,
+            Action::RotateRight => &mut self.rot_right,
+            Action::RotateLeft => &mut self.rot_left,
+            Action::Confirm => &mut self.confirm,
+            Action::Back => &mut self.escape,
+            Action::Restart => &mut self.restart,
+        }
+    }
+
+    /// Whether a menu entry was picked, by confirming or, as menus always
+    /// have, by rotating
+    fn confirmed(&self) -> bool {
+        self.confirm == KeyState::Pressed
+            || self.rot_left == KeyState::Pressed
+            || self.rot_right == KeyState::Pressed
+    }
+
+    /// Whether a menu was left, by going back or with backspace. Not for
+    /// screens being typed on, where backspace takes back a character
+    fn backed_out(&self) -> bool {
+        self.escape == KeyState::Pressed || self.clear == KeyState::Pressed
+    }
+
+    fn all_pressed() -> Self {
+        Self {
+            up: KeyState::Holding,
+            down: KeyState::Holding,
+            left: KeyState::Holding,
+            right: KeyState::Holding,
+            rot_right: KeyState::Holding,
+            rot_left: KeyState::Holding,
+            escape: KeyState::Holding,
+            confirm: KeyState::Holding,
+            restart: KeyState::Holding,
+            metronome: KeyState::Holding,
+            pick: [KeyState::Holding; 7],
+            freeze: KeyState::Holding,
+            clear: KeyState::Holding,
+            undo: KeyState::Holding,
+            finesse: KeyState::Holding,
+            instant_gravity: KeyState::Holding,
+            dump: KeyState::Holding,
+            export: KeyState::Holding,
+            enter: KeyState::Holding,
+            frame: 0,
+        }
+    }
+}
+
+fn input(snapshot: &InputSnapshot, last_input: PlayerInput) -> PlayerInput {
+    read_input(snapshot, &KeyMap::PLAYER_ONE, &PadMap::STANDARD, last_input)
+}
+
+fn read_input(
+    snapshot: &InputSnapshot,
+    keys: &KeyMap,
+    pad: &PadMap,
+    last_input: PlayerInput,
+) -> PlayerInput {
+    // a key tapped since the last read counts as down for this one
+    let down = |key: Key| snapshot.is_down(key) || snapshot.pressed_since(key, last_input.frame);
+    let map = |key: Key, prev: KeyState| prev.next(down(key));
+
+    let pick_keys = [
+        Key::Key1,
+        Key::Key2,
+        Key::Key3,
+        Key::Key4,
+        Key::Key5,
+        Key::Key6,
+        Key::Key7,
+    ];
+    let mut pick = [KeyState::Released; 7];
+    for (i, key) in pick_keys.iter().enumerate() {
+        pick[i] = map(*key, last_input.pick[i]);
+    }
+
+    let mut input = PlayerInput {
+        frame: snapshot.frame(),
+        metronome: map(Key::T, last_input.metronome),
+        pick,
+        freeze: map(Key::F, last_input.freeze),
+        clear: map(Key::Back, last_input.clear),
+        undo: map(Key::U, last_input.undo),
+        finesse: map(Key::N, last_input.finesse),
+        instant_gravity: map(Key::G, last_input.instant_gravity),
+        dump: map(Key::F8, last_input.dump),
+        export: map(Key::E, last_input.export),
+        enter: map(Key::Return, last_input.enter),
+        ..PlayerInput::default()
+    };
+    for &action in Action::ALL {
+        let mut held = snapshot.either_down(keys.key(action), pad.button(action), last_input.frame);
+        if action == Action::Back {
+            // whatever back is bound to, escape still gets out
+            held |= down(Key::Escape);
+        } else if action == Action::Confirm {
+            held |= down(Key::Space);
+        }
+        *input.action_mut(action) = last_input.action(action).next(held);
+    }
+    input
+}
+
+const USAGE: &str = "\
+Usage: tet_rs [options]
+
+Options:
+  --width <pixels>    Width of the window when it opens
+  --height <pixels>   Height of the window when it opens
+  --fullscreen        Cover the whole screen
+  --seed <number>     Deal every game from this seed
+  --mode <mode>       Skip the menu and start a game right away. One of endless,
+                      sprint, ultra, marathon, cheese3, cheese6, cheese9, zen,
+                      big, invisible or practice
+  --scores            Print the endless leaderboard and exit
+  --export-scores <format> [path]
+                      Write every leaderboard out as json or csv and exit.
+                      The path defaults to tetrs_scores.<format> in the data
+                      directory
+  --import-scores <path>
+                      Merge the leaderboards exported to a .json or .csv file
+                      back in and exit
+  --host <port>       Host an online game
+  --join <address>    Join an online game
+  --load-dump <path>  Skip the menu and play on from a game dumped with F8
+  --demo              Play the built-in demo fullscreen and exit at its end
+  --backend <name>    Draw with this graphics backend rather than the usual
+                      one for the platform. One of vulkan, dx12, dx11, metal
+                      or gl. TETRS_BACKEND does the same
+  --bench             Time the game stepping through endless games with keys
+                      held at random, print how fast it went and exit
+  --bench-ticks <n>   Ticks --bench steps through, 2000000 by default
+  --bench-render      Have --bench also time drawing a busy board in a window
+  --bench-json        Have --bench print its numbers as JSON
+  --help              Print this message and exit";
+
+/// Options given on the command line, overriding the config
+#[derive(Default)]
+struct Args {
+    width: Option<u32>,
+    height: Option<u32>,
+    fullscreen: bool,
+    seed: Option<u64>,
+    /// Game to start instead of showing the menu
+    mode: Option<GameMode>,
+    /// Print the leaderboard instead of playing
+    scores: bool,
+    /// Write the leaderboards out in a format, to a path or the default one,
+    /// instead of playing
+    export_scores: Option<(Format, Option<PathBuf>)>,
+    /// Merge leaderboards from a file instead of playing
+    import_scores: Option<PathBuf>,
+    /// Online game to start instead of showing the menu
+    role: Option<Role>,
+    /// Dumped game to play on from instead of showing the menu
+    load_dump: Option<PathBuf>,
+    /// Play the built-in demo fullscreen instead of showing the menu, and
+    /// quit once it's over
+    demo: bool,
+    /// Graphics backend to use instead of the platform's usual ones
+    backend: Option<wgpu::BackendBit>,
+    /// Measure how fast the game runs instead of playing
+    bench: bool,
+    /// Ticks to step the simulation through when measuring
+    bench_ticks: Option<u64>,
+    /// Measure drawing as well, in a window
+    bench_render: bool,
+    /// Print the measurements as JSON rather than text
+    bench_json: bool,
+    help: bool,
+}
+
+impl Args {
+    /// Reads the arguments following the program name, or says what's wrong
+    /// with them
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.peekable();
+        let mut parsed = Self::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--width" => {
+                    parsed.width = Some(flag_value::<NonZeroU32>(&arg, args.next())?.get())
+                }
+                "--height" => {
+                    parsed.height = Some(flag_value::<NonZeroU32>(&arg, args.next())?.get())
+                }
+                "--fullscreen" => parsed.fullscreen = true,
+                "--seed" => parsed.seed = Some(flag_value(&arg, args.next())?),
+                "--mode" => {
+                    let name: String = flag_value(&arg, args.next())?;
+                    let mode = GameMode::ALL
+                        .iter()
+                        .copied()
+                        .filter(|&mode| mode != GameMode::Puzzle && mode != GameMode::Versus)
+                        .find(|mode| mode.name().replace(' ', "").eq_ignore_ascii_case(&name))
+                        .ok_or_else(|| format!("Unknown mode {:?}", name))?;
+                    parsed.mode = Some(mode);
+                }
+                "--scores" => parsed.scores = true,
+                "--export-scores" => {
+                    let name: String = flag_value(&arg, args.next())?;
+                    let format = Format::from_name(&name)
+                        .ok_or_else(|| format!("Unknown score format {:?}", name))?;
+                    // the path is optional, so only taken if it isn't a flag
+                    let path = match args.peek() {
+                        Some(next) if !next.starts_with("--") => args.next().map(PathBuf::from),
+                        _ => None,
+                    };
+                    parsed.export_scores = Some((format, path));
+                }
+                "--import-scores" => {
+                    parsed.import_scores = Some(flag_value(&arg, args.next())?);
+                }
+                "--host" => parsed.role = Some(Role::Host(flag_value(&arg, args.next())?)),
+                "--join" => parsed.role = Some(Role::Join(flag_value(&arg, args.next())?)),
+                "--load-dump" => parsed.load_dump = Some(flag_value(&arg, args.next())?),
+                "--demo" => parsed.demo = true,
+                "--backend" => {
+                    let name: String = flag_value(&arg, args.next())?;
+                    parsed.backend = Some(parse_backend(&name)?);
+                }
+                "--bench" => parsed.bench = true,
+                "--bench-ticks" => {
+                    parsed.bench_ticks = Some(flag_value::<NonZeroU64>(&arg, args.next())?.get())
+                }
+                "--bench-render" => parsed.bench_render = true,
+                "--bench-json" => parsed.bench_json = true,
+                "--help" | "-h" => parsed.help = true,
+                _ => return Err(format!("Unknown option {:?}", arg)),
+            }
+        }
+        if parsed.mode.is_some() && parsed.role.is_some() {
+            return Err("--mode can't be combined with --host or --join".to_string());
+        }
+        if parsed.load_dump.is_some() && (parsed.mode.is_some() || parsed.role.is_some()) {
+            return Err("--load-dump can't be combined with --mode, --host or --join".to_string());
+        }
+        let bench_options =
+            parsed.bench_ticks.is_some() || parsed.bench_render || parsed.bench_json;
+        if bench_options && !parsed.bench {
+            return Err("--bench-ticks, --bench-render and --bench-json need --bench".to_string());
+        }
+        let game = parsed.mode.is_some() || parsed.role.is_some() || parsed.load_dump.is_some();
+        if parsed.bench && game {
+            return Err(
+                "--bench can't be combined with --mode, --host, --join or --load-dump".to_string(),
+            );
+        }
+        if parsed.demo && (game || parsed.bench) {
+            return Err(
+                "--demo can't be combined with --mode, --host, --join, --load-dump or --bench"
+                    .to_string(),
+            );
+        }
+        Ok(parsed)
+    }
+}
+
+fn parse_backend(name: &str) -> Result<wgpu::BackendBit, String> {
+    lib::graphics::backend_from_name(name).ok_or_else(|| format!("Unknown backend {:?}", name))
+}
+
+/// Window title, followed by `info` about what's going on if there's any
+fn window_title(info: Option<&str>) -> String {
+    let name = concat!("tet.rs ", env!("CARGO_PKG_VERSION"));
+    match info {
+        Some(info) => format!("{} \u{2014} {}", name, info),
+        None => name.to_string(),
+    }
+}
+
+/// Parses `value`, the argument following `flag`
+fn flag_value<T>(flag: &str, value: Option<String>) -> Result<T, String>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    let value = value.ok_or_else(|| format!("{} needs a value", flag))?;
+    value
+        .parse()
+        .map_err(|e| format!("Invalid value {:?} for {}: {}", value, flag, e))
+}
+
+/// Moves files older versions kept in the working directory over to the data
+/// directory, so their scores and settings aren't lost
+fn migrate_files() {
+    let mut files = vec![CONFIG_FILE, SAVE_FILE, ACHIEVEMENTS_FILE];
+    files.extend(GameMode::RANKED.iter().map(|mode| mode.scores_file()));
+    for name in files {
+        match paths::migrate(name) {
+            Ok(true) => println!("Moved {} to {}", name, paths::data_file(name).display()),
+            Ok(false) => (),
+            Err(e) => eprintln!("Couldn't move {} to the data directory: {}", name, e),
+        }
+    }
+}
+
+/// Prints what `--bench` measured, as JSON if `json`
+fn print_bench(report: &BenchReport, json: bool) {
+    if json {
+        println!("{}", report.to_json());
+    } else {
+        print!("{}", report);
+    }
+}
+
+/// Prints the endless leaderboard, for checking on the score file
+fn print_scores() -> Result<(), Box<dyn std::error::Error>> {
+    let mode = GameMode::Endless;
+    let loaded = scores::load(mode)?;
+    if let Some(damage) = &loaded.damage {
+        eprintln!(
+            "Score file was damaged ({}); recovered {} entries, the original is at {}",
+            damage.error,
+            damage.recovered,
+            damage.backup.display()
+        );
+    }
+    for (i, entry) in loaded.entries.iter().enumerate() {
+        println!(
+            "{:>2}. {:<12} {:<12} {}",
+            i + 1,
+            entry.name,
+            mode.format_value(entry.value),
+            entry.date().unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+/// Carries out `change` on the state stack, letting the states involved know.
+/// Returns false once there's nothing left to run
+fn apply(states: &mut Vec<Box<dyn GameState>>, mut change: lib::game::StateChange) -> bool {
+    loop {
+        change = match change {
+            lib::game::StateChange::None => return true, // do nothing
+            lib::game::StateChange::Quit => return false,
+            lib::game::StateChange::Push(mut state) => {
+                // push a new state
+                if let Some(top) = states.last_mut() {
+                    top.on_pause();
+                }
+                state.on_enter();
+                states.push(state);
+                return true;
             }
             lib::game::StateChange::Pop => {
                 // pop state and quit if there are no more states
-                if states.pop().is_none() {
-                    window.set_should_close(true);
+                return pop(states);
+            }
+            lib::game::StateChange::PopWith(result) => {
+                // hand the result down, the state underneath may move on in turn
+                if !pop(states) {
+                    return false;
                 }
+                states.last_mut().unwrap().on_result(result)
             }
-            lib::game::StateChange::Swap(state) => {
+            lib::game::StateChange::Swap(mut state) => {
                 // replace the current state by another one
-                states.pop();
+                if let Some(mut top) = states.pop() {
+                    top.on_exit();
+                }
+                state.on_enter();
                 states.push(state);
+                return true;
+            }
+        };
+    }
+}
+
+/// Draws the states that can be seen, starting from the topmost one that isn't
+/// an overlay so the ones above it are drawn over what they cover
+fn render_states(
+    states: &[Box<dyn GameState>],
+    graphics: &lib::graphics::GraphicsState,
+    frame: &mut Frame,
+) {
+    let bottom = states.iter().rposition(|s| !s.is_overlay()).unwrap_or(0);
+    let visible = &states[bottom..];
+
+    // everything under the topmost overlay asking for an effect goes off
+    // screen first, then onto the frame through it
+    let split = visible
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(i, s)| s.backdrop().map(|effect| (i, effect)));
+    let top = match split {
+        Some((i, effect)) => {
+            let mut backdrop = graphics.begin_offscreen();
+            for state in &visible[..i] {
+                state.render(graphics, &mut backdrop);
+            }
+            drop(backdrop);
+            graphics.composite(frame, effect);
+            &visible[i..]
+        }
+        None => visible,
+    };
+    for state in top {
+        state.render(graphics, frame);
+    }
+}
+
+/// Fits the swap chain to the window's new `size`, in physical pixels, and
+/// lets the states know
+fn resize(
+    graphics: &mut lib::graphics::GraphicsState,
+    states: &mut [Box<dyn GameState>],
+    size: PhysicalSize<u32>,
+) {
+    graphics.resize(size.width, size.height);
+    for state in states {
+        state.on_resize(size.width, size.height);
+    }
+}
+
+/// Removes the top state, returning whether any are left
+fn pop(states: &mut Vec<Box<dyn GameState>>) -> bool {
+    if let Some(mut top) = states.pop() {
+        top.on_exit();
+    }
+    match states.last_mut() {
+        Some(top) => {
+            top.on_resume();
+            true
+        }
+        None => false,
+    }
+}
+
+fn main() {
+    let args = Args::parse(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        std::process::exit(2);
+    });
+    if args.help {
+        println!("{}", USAGE);
+        return;
+    }
+    // the simulation is timed before there's any window or GPU to slow it
+    let mut bench = None;
+    if args.bench {
+        let simulation = lib::bench::simulate(args.bench_ticks.unwrap_or(DEFAULT_BENCH_TICKS));
+        if !args.bench_render {
+            let report = BenchReport {
+                simulation,
+                render: None,
+            };
+            print_bench(&report, args.bench_json);
+            return;
+        }
+        bench = Some(simulation);
+    }
+    migrate_files();
+    if args.scores {
+        if let Err(e) = print_scores() {
+            eprintln!(
+                "Couldn't read {}: {}",
+                paths::data_file(GameMode::Endless.scores_file()).display(),
+                e
+            );
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some((format, path)) = args.export_scores {
+        let path = path.unwrap_or_else(|| export::default_path(format));
+        match export::export(format, &path) {
+            Ok(count) => println!("Exported {} scores to {}", count, path.display()),
+            Err(e) => {
+                eprintln!("Couldn't export scores: {}", e);
+                std::process::exit(1);
             }
         }
+        return;
+    }
+    if let Some(path) = args.import_scores {
+        match export::import(&path) {
+            Ok(count) => println!("Imported {} new scores from {}", count, path.display()),
+            Err(e) => {
+                eprintln!("Couldn't import scores from {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
-        // events
-        glfw.poll_events();
+    let config: SharedConfig = if bench.is_some() {
+        // the same settings every time, with nothing holding frames back
+        Rc::new(RefCell::new(Config {
+            vsync: false,
+            fps_cap: 0,
+            ..Config::default()
+        }))
+    } else {
+        Rc::new(RefCell::new(Config::load_or_create(&paths::data_file(
+            CONFIG_FILE,
+        ))))
+    };
+    // the arguments only apply to this run, the config file is left as it is
+    let width = args.width.unwrap_or(config.borrow().width);
+    let height = args.height.unwrap_or(config.borrow().height);
+    // the flag starts this run fullscreen, and sticks only if the settings
+    // get saved later on
+    config.borrow_mut().fullscreen |= args.fullscreen || args.demo;
+    let fullscreen = config.borrow().fullscreen;
+
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title(window_title(None))
+        .with_window_icon(match lib::graphics::window_icon() {
+            Ok(icon) => Some(icon),
+            Err(e) => {
+                eprintln!("Warning: couldn't load the window icon: {}", e);
+                None
+            }
+        })
+        .with_inner_size(PhysicalSize::new(width, height))
+        .with_min_inner_size(LogicalSize::new(MIN_WINDOW_WIDTH, MIN_WINDOW_HEIGHT))
+        .with_fullscreen(if fullscreen {
+            Some(Fullscreen::Borderless(None))
+        } else {
+            None
+        })
+        .build(&event_loop)
+        .expect("Failed to create window.");
 
-        #[allow(clippy::single_match)]
-        for (_, event) in glfw::flush_messages(&events) {
-            match event {
-                glfw::WindowEvent::Size(width, height) => {
-                    graphics.resize(width as u32, height as u32);
+    let start_level = config.borrow().start_level;
+    let menu = TetrisMenu {
+        start_level,
+        seed: args.seed,
+        config: config.clone(),
+        ..TetrisMenu::default()
+    };
+    let mut states: Vec<Box<dyn GameState>> = Vec::new();
+    let first: Box<dyn GameState> = match bench {
+        Some(simulation) => Box::new(TetrisBench::new(
+            config.clone(),
+            simulation,
+            args.bench_json,
+        )),
+        // with nothing underneath, the window closes when the demo's over
+        None if args.demo => Box::new(TetrisMain::attract(true)),
+        None => Box::new(menu),
+    };
+    apply(&mut states, lib::game::StateChange::Push(first));
+    // leaving a game started from the command line drops back to the menu
+    if let Some(mode) = args.mode {
+        let randomizer = config.borrow().randomizer;
+        let mut sim =
+            Simulation::with_level(mode, DEFAULT_PREVIEWS, start_level, args.seed, randomizer);
+        sim.set_rotation(config.borrow().rotation);
+        let game = TetrisMain {
+            seed: args.seed,
+            ..TetrisMain::new(sim, config.clone())
+        };
+        apply(&mut states, lib::game::StateChange::Push(Box::new(game)));
+    }
+    if let Some(path) = args.load_dump {
+        let loaded = match std::fs::read_to_string(&path) {
+            Ok(text) => dump::read(&text),
+            Err(e) => Err(e.into()),
+        };
+        match loaded {
+            Ok(loaded) => {
+                // a rebuilt run is for looking into, not for unlocking achievements
+                let game = TetrisMain {
+                    assisted: true,
+                    ..TetrisMain::new(loaded.sim, config.clone())
+                };
+                apply(&mut states, lib::game::StateChange::Push(Box::new(game)));
+            }
+            Err(e) => eprintln!("Couldn't load dump {}: {}", path.display(), e),
+        }
+    }
+    if let Some(role) = args.role {
+        let online = TetrisOnline::new(role, args.seed, config.clone());
+        apply(&mut states, lib::game::StateChange::Push(Box::new(online)));
+    }
+    // the flag wins over the environment, for debugging one run differently
+    let backend = match args.backend {
+        Some(backend) => Some(backend),
+        None => match std::env::var("TETRS_BACKEND") {
+            Ok(name) => Some(parse_backend(&name).unwrap_or_else(|e| {
+                eprintln!("{} in TETRS_BACKEND", e);
+                std::process::exit(2);
+            })),
+            Err(_) => None,
+        },
+    };
+    let present_mode = config.borrow().present_mode();
+    let sample_count = config.borrow().sample_count();
+    let mut graphics = match futures::executor::block_on(lib::graphics::GraphicsState::new(
+        &window,
+        backend,
+        present_mode,
+        sample_count,
+    )) {
+        Ok(graphics) => graphics,
+        Err(e) => {
+            eprintln!("Couldn't start graphics: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let mut audio = AudioSystem::new();
+    let mut gamepads = Gamepads::new();
+    let mut last_frame = std::time::Instant::now();
+    let mut outdated_frames = 0;
+    // size the window was last given that the swap chain hasn't caught up
+    // with, and when it last did
+    let mut pending_size: Option<PhysicalSize<u32>> = None;
+    let mut last_resize: Option<std::time::Instant> = None;
+    let mut snapshot = InputSnapshot::default();
+    snapshot.resize(graphics.sc_desc.width, graphics.sc_desc.height);
+    graphics.ui_scale = config.borrow().layout_scale();
+    snapshot.set_ui_scale(graphics.ui_scale);
+    let mut modifiers = ModifiersState::empty();
+    // whether the window was last made fullscreen, rather than asking it, as
+    // the switch can take a while to go through
+    let mut is_fullscreen = fullscreen;
+    let mut title = window_title(None);
+    // name of the theme graphics were last given, to notice it changing
+    let mut theme = String::new();
+    let mut font = config.borrow().font.clone();
+    graphics.set_font(&font);
+    let mut capture_requested = false;
+    // message in the corner, like a screenshot's confirmation, and when it
+    // was first shown
+    let mut toast: Option<(String, std::time::Instant)> = graphics
+        .present_mode_fallback()
+        .map(|notice| (notice, std::time::Instant::now()));
+    let mut limiter = FrameLimiter::default();
+    // whether the window has focus, and when input last came in, for drawing
+    // less often while nobody's looking
+    let mut focused = true;
+    let mut last_active = std::time::Instant::now();
+    // whether the window is minimised, or the app sent to the background. The
+    // game is paused meanwhile: states aren't updated or drawn, and the time
+    // away isn't handed to them afterwards, so a run picks up on the tick it
+    // was left on
+    let mut is_suspended = false;
+    // frames are timed flat out, wherever the window is
+    let benchmarking = bench.is_some();
+
+    event_loop.run(move |event, _, control_flow| {
+        // run frames back to back rather than waiting on events; states pace
+        // their updates by the clock, not by how often events come in
+        *control_flow = ControlFlow::Poll;
+
+        match event {
+            winit::event::Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                WindowEvent::ModifiersChanged(state) => modifiers = state,
+                WindowEvent::Focused(focus) => {
+                    if focus && !focused {
+                        states.last_mut().unwrap().on_return();
+                    }
+                    focused = focus;
+                }
+                // minimising shrinks the window to nothing, and bringing it back
+                // often reports the size it had, which the swap chain still fits
+                WindowEvent::Resized(size) => {
+                    let was_suspended = is_suspended;
+                    is_suspended = size.width == 0 || size.height == 0;
+                    let fitted = (size.width, size.height)
+                        == (graphics.sc_desc.width, graphics.sc_desc.height);
+                    if is_suspended {
+                        // the keys let go while minimised are never heard of
+                        snapshot.release_all();
+                    } else if !was_suspended || !fitted {
+                        pending_size = Some(size);
+                    }
+                    if was_suspended && !is_suspended {
+                        states.last_mut().unwrap().on_return();
+                    }
+                }
+                // moving to a screen with another scale resizes the window
+                // without a resize event of its own
+                WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                    pending_size = Some(*new_inner_size)
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(key),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } => match key {
+                    Key::Return if modifiers.alt() => {
+                        let mut config = config.borrow_mut();
+                        config.fullscreen = !config.fullscreen;
+                        config
+                            .save(&paths::data_file(CONFIG_FILE))
+                            .unwrap_or_else(|e| eprintln!("Couldn't save config: {}", e));
+                    }
+                    Key::F12 => capture_requested = true,
+                    // typing an m into a name still types it, and a player who
+                    // bound it to an action gets that instead
+                    Key::M
+                        if !states.last().map_or(false, |s| s.takes_text())
+                            && !config.borrow().keys.binds(Key::M) =>
+                    {
+                        let mut config = config.borrow_mut();
+                        config.muted = !config.muted;
+                        config
+                            .save(&paths::data_file(CONFIG_FILE))
+                            .unwrap_or_else(|e| eprintln!("Couldn't save config: {}", e));
+                    }
+                    Key::F3 => {
+                        // toggle the frame timing overlay
+                        let profiler = &graphics.profiler;
+                        profiler.set_enabled(!profiler.is_enabled());
+                    }
+                    Key::F4 if cfg!(debug_assertions) => {
+                        // toggle the geometry inspector
+                        graphics.wireframe = !graphics.wireframe;
+                    }
+                    Key::F5 if cfg!(debug_assertions) => {
+                        // swap between srgb and linear output to exercise format changes
+                        let format = match graphics.sc_desc.format {
+                            wgpu::TextureFormat::Bgra8UnormSrgb => wgpu::TextureFormat::Bgra8Unorm,
+                            _ => wgpu::TextureFormat::Bgra8UnormSrgb,
+                        };
+                        graphics.rebuild_swap_chain(Some(format));
+                    }
+                    _ => snapshot.press(key),
+                },
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(key),
+                            state: ElementState::Released,
+                            ..
+                        },
+                    ..
+                } => snapshot.release(key),
+                // backspace, enter and the like come through as keys
+                WindowEvent::ReceivedCharacter(c) if !c.is_control() => snapshot.text.push(c),
+                // kept as a fraction of the window, which the frame is
+                // stretched over even while the swap chain lags behind it,
+                // less the border a pixel scale leaves around it
+                WindowEvent::CursorMoved { position, .. } => {
+                    let size = window.inner_size();
+                    if size.width > 0 && size.height > 0 {
+                        snapshot.move_cursor(Some(graphics.frame_fraction([
+                            position.x as f32 / size.width as f32,
+                            position.y as f32 / size.height as f32,
+                        ])));
+                    }
+                }
+                WindowEvent::CursorLeft { .. } => snapshot.move_cursor(None),
+                WindowEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    button,
+                    ..
+                } => snapshot.press_mouse(button),
+                WindowEvent::MouseInput {
+                    state: ElementState::Released,
+                    button,
+                    ..
+                } => snapshot.release_mouse(button),
+                WindowEvent::MouseWheel { delta, .. } => {
+                    snapshot.scroll += match delta {
+                        MouseScrollDelta::LineDelta(_, lines) => lines,
+                        MouseScrollDelta::PixelDelta(position) => {
+                            (position.y / SCROLL_PIXELS_PER_LINE) as f32
+                        }
+                    }
                 }
                 _ => (),
+            },
+            winit::event::Event::Suspended => {
+                is_suspended = true;
+                snapshot.release_all();
+            }
+            winit::event::Event::Resumed if is_suspended => {
+                is_suspended = false;
+                pending_size = Some(window.inner_size());
+                states.last_mut().unwrap().on_return();
+            }
+            winit::event::Event::MainEventsCleared if is_suspended => {
+                // nothing is updated or drawn until the window is back, which
+                // some event will say
+                last_frame = std::time::Instant::now();
+                *control_flow = ControlFlow::Wait;
+            }
+            winit::event::Event::MainEventsCleared => {
+                // a present mode picked last frame takes effect now, with no
+                // frame from the old swap chain still held
+                if let Some(notice) = graphics.apply_present_mode() {
+                    eprintln!("Warning: {}", notice);
+                    toast = Some((notice, std::time::Instant::now()));
+                }
+
+                let state = states.last_mut().unwrap();
+
+                // timing
+                let frame = std::time::Instant::now();
+                let dt = frame - last_frame;
+                last_frame = frame;
+
+                // gamepads aren't window events, they're read once a frame
+                gamepads.poll(&mut snapshot);
+                if snapshot.is_active() {
+                    last_active = frame;
+                }
+
+                // update
+                let update_result = {
+                    let _scope = graphics.profiler.scope("update");
+                    state.update(&snapshot, dt)
+                };
+                // volumes may have been turned up or down, or muted, which
+                // music already playing follows straight away
+                for &channel in Channel::ALL {
+                    audio.set_volume(channel, config.borrow().volume(channel));
+                }
+                audio.set_muted(config.borrow().muted);
+                for sound in state.take_sounds() {
+                    audio.play(sound, config.borrow().sound_volume(sound));
+                }
+                let music = states.iter().rev().find_map(|s| s.music());
+                audio.update_music(music, dt);
+                snapshot.end_frame();
+                graphics
+                    .profiler
+                    .record_frame(dt, lib::game::timestep::take_ticks_run());
+
+                // options may have turned vsync or antialiasing on or off,
+                // changed the pixel scale or picked another theme or font
+                graphics.set_present_mode(config.borrow().present_mode());
+                graphics.set_sample_count(config.borrow().sample_count());
+                graphics.set_pixel_scale(config.borrow().pixel_scale);
+                graphics.ui_scale = config.borrow().layout_scale();
+                snapshot.set_ui_scale(graphics.ui_scale);
+                let picked = config.borrow().theme_name().to_string();
+                if picked != theme {
+                    graphics.theme = lib::graphics::theme::find(&picked);
+                    theme = picked;
+                }
+                if config.borrow().font != font {
+                    font = config.borrow().font.clone();
+                    graphics.set_font(&font);
+                }
+
+                // going back to a window restores its old size and place, and
+                // either way the resize that follows rebuilds the swap chain
+                let fullscreen = config.borrow().fullscreen;
+                if fullscreen != is_fullscreen {
+                    window.set_fullscreen(if fullscreen {
+                        Some(Fullscreen::Borderless(window.current_monitor()))
+                    } else {
+                        None
+                    });
+                    is_fullscreen = fullscreen;
+                }
+
+                // only touched when it changes, as some platforms redraw the
+                // whole title bar each time
+                let info = if config.borrow().title_info {
+                    states.iter().rev().find_map(|s| s.title_info())
+                } else {
+                    None
+                };
+                let new_title = window_title(info.as_deref());
+                if new_title != title {
+                    window.set_title(&new_title);
+                    title = new_title;
+                }
+
+                // dragging the window's edge sends a stream of sizes, which the
+                // swap chain only catches up with every so often. a window with
+                // no room has nothing to fit, and nothing is drawn until it has
+                if let Some(size) = pending_size {
+                    let due = last_resize.map_or(true, |at| at.elapsed() >= RESIZE_INTERVAL);
+                    if due && size.width > 0 && size.height > 0 {
+                        resize(&mut graphics, &mut states, size);
+                        snapshot.resize(size.width, size.height);
+                        pending_size = None;
+                        last_resize = Some(std::time::Instant::now());
+                    }
+                }
+                let size = window.inner_size();
+                let drawable = pending_size.is_none() && size.width > 0 && size.height > 0;
+                if drawable {
+                    graphics.update_projection();
+
+                    // a screenshot draws the frame again off screen, without the
+                    // overlays, as the window's own image can't always be read
+                    if std::mem::take(&mut capture_requested) {
+                        let (mut frame, capture) = graphics.begin_capture();
+                        render_states(&states, &graphics, &mut frame);
+                        let saved = capture
+                            .read(&graphics)
+                            .and_then(|image| lib::graphics::screenshot::save(&image));
+                        match saved {
+                            Ok(path) => {
+                                println!("Saved screenshot to {}", path.display());
+                                toast = Some((
+                                    "Saved screenshot".to_string(),
+                                    std::time::Instant::now(),
+                                ));
+                            }
+                            Err(e) => eprintln!("Couldn't save screenshot: {}", e),
+                        }
+                    }
+                    if matches!(&toast, Some((_, shown)) if shown.elapsed() > TOAST_TIME) {
+                        toast = None;
+                    }
+
+                    // render
+                    let rendered = graphics.begin_frame().map(|mut frame| {
+                        render_states(&states, &graphics, &mut frame);
+                        if let Some((message, _)) = &toast {
+                            lib::graphics::screenshot::render_toast(&graphics, &mut frame, message);
+                        }
+                        if audio.is_muted() {
+                            lib::graphics::screenshot::render_badge(
+                                &graphics, &mut frame, "[MUTED]",
+                            );
+                        }
+                        lib::graphics::profiler::render_overlay(&graphics, &mut frame);
+                        graphics.end_frame(frame);
+                    });
+                    graphics.profiler.end_frame();
+                    match rendered {
+                        Err(wgpu::SwapChainError::OutOfMemory) => *control_flow = ControlFlow::Exit,
+                        Err(wgpu::SwapChainError::Outdated)
+                            if outdated_frames >= OUTDATED_FRAMES_BEFORE_REBUILD =>
+                        {
+                            // recreating it as it was didn't help; the surface probably wants
+                            // another format now (e.g. the window moved to a different monitor)
+                            graphics.rebuild_swap_chain(None);
+                            outdated_frames = 0;
+                        }
+                        // the window may have changed size without saying so yet, so
+                        // the swap chain is fitted to what it is now
+                        Err(wgpu::SwapChainError::Outdated) => {
+                            outdated_frames += 1;
+                            pending_size = Some(window.inner_size());
+                        }
+                        Err(wgpu::SwapChainError::Lost) => pending_size = Some(window.inner_size()),
+                        // the compositor didn't hand over an image in time, as when
+                        // the window is hidden; this frame is skipped and the next
+                        // one asks again
+                        Err(wgpu::SwapChainError::Timeout) => (),
+                        Ok(()) => outdated_frames = 0,
+                    };
+
+                    // sleep away what's left of the frame. A static screen
+                    // nobody has touched in a while, or a window in the
+                    // background, is drawn at the low power rate
+                    let idle = states.last().map_or(false, |s| s.is_static())
+                        && last_active.elapsed() >= IDLE_BEFORE_LOW_POWER;
+                    let low_power = (idle || !focused) && !benchmarking;
+                    let fps = limiter::frame_rate(config.borrow().fps_cap, low_power);
+                    graphics.profiler.set_cap(fps);
+                    limiter.wait(fps);
+                } else {
+                    graphics.profiler.end_frame();
+                    // no frame to wait on for pacing, so wait on the clock
+                    *control_flow =
+                        ControlFlow::WaitUntil(std::time::Instant::now() + RESIZE_INTERVAL);
+                }
+
+                if !apply(&mut states, update_result) {
+                    *control_flow = ControlFlow::Exit;
+                }
+            }
+            winit::event::Event::LoopDestroyed => {
+                for state in states.iter_mut().rev() {
+                    state.on_quit();
+                }
             }
+            _ => (),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::{GlobalAlloc, System};
+
+    /// Hands allocations on to the system, counting them on each thread so
+    /// tests running alongside don't add to each other's counts
+    struct Counting;
+
+    thread_local! {
+        static ALLOCATIONS: std::cell::Cell<u64> = const { std::cell::Cell::new(0) };
+    }
+
+    fn count() {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+    }
+
+    /// Allocations made on this thread so far
+    fn allocations() -> u64 {
+        ALLOCATIONS.with(std::cell::Cell::get)
+    }
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            count();
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(
+            &self,
+            ptr: *mut u8,
+            layout: std::alloc::Layout,
+            new_size: usize,
+        ) -> *mut u8 {
+            count();
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: Counting = Counting;
+
+    /// A run of the recorded sprint, racing itself for the ghost's field
+    /// and label
+    fn demo_race(replay: &Replay) -> TetrisMain {
+        let mut game = TetrisMain::playback(replay.clone());
+        game.ghost = Some(Ghost::new(replay.clone()));
+        game
+    }
+
+    #[test]
+    fn building_a_frame_of_a_run_in_play_allocates_nothing() {
+        let replay = Replay::demo().unwrap();
+        let layout = Layout::for_framebuffer(1280, 720, 1.0);
+        let theme = Theme::default();
+        let snapshot = InputSnapshot::default();
+        let tick = std::time::Duration::from_millis(TICK_MILLIS);
+        let ticks = replay.inputs.len() as u64;
+
+        let frame = |game: &mut TetrisMain| {
+            game.update(&snapshot, tick);
+            let before = allocations();
+            drop(game.draw(&layout, &theme));
+            allocations() - before
+        };
+        let countdown = |game: &mut TetrisMain| {
+            // counting down again as after a pause
+            frame(game);
+            game.resume_countdown = COUNTDOWN_TICKS;
+        };
+
+        // the first time through, the mesh, the sidebar and the line buffers
+        // grow to fit the busiest frame of the run
+        let mut warm = demo_race(&replay);
+        countdown(&mut warm);
+        for _ in 0..ticks + COUNTDOWN_TICKS {
+            frame(&mut warm);
+        }
+
+        // the second time through, they're reused as they would be
+        let mut game = demo_race(&replay);
+        game.mesh = warm.mesh;
+        game.hud_lines = warm.hud_lines;
+        countdown(&mut game);
+        for i in 0..ticks + COUNTDOWN_TICKS {
+            assert_eq!(frame(&mut game), 0, "{} ticks in", i);
         }
     }
 }
@@ -0,0 +1,390 @@
+//! The ways a game can be played, and what each one asks of the player.
+
+use std::fmt;
+
+use crate::board::FIELD_HEIGHT;
+
+pub const SPRINT_LINES: u32 = 40;
+pub const ULTRA_MILLIS: u64 = 120_000;
+pub const MARATHON_LINES: u32 = 150;
+pub const MARATHON_LEVEL_CAP: u32 = 15;
+pub const MARATHON_BONUS: u64 = 10_000;
+/// Set on marathon leaderboard entries whose run reached the final level. Being
+/// the top bit, it also ranks completed runs above every unfinished one.
+pub const MARATHON_CLEAR_FLAG: u64 = 1 << 63;
+/// Time between garbage rows rising in rising mode. It starts at ten seconds
+/// and gets half a second shorter every fifteen seconds of play, down to two.
+/// Every line cleared puts the next row off by another second
+pub const RISING_START_MILLIS: u64 = 10_000;
+pub const RISING_STEP_MILLIS: u64 = 500;
+pub const RISING_STEP_EVERY_MILLIS: u64 = 15_000;
+pub const RISING_MIN_MILLIS: u64 = 2_000;
+pub const RISING_LINE_BONUS_MILLIS: u64 = 1_000;
+
+/// A line of the sidebar during a run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HudStat {
+    Score,
+    /// Time played, or in modes with a time limit the time left
+    Time,
+    Level,
+    Lines,
+    /// Lines still to clear in sprint
+    LinesLeft,
+    /// Garbage rows still to dig out in cheese
+    GarbageLeft,
+    /// Pieces still in a puzzle's queue
+    PiecesLeft,
+    Pieces,
+}
+
+impl HudStat {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Score => "Score",
+            Self::Time => "Time",
+            Self::Level => "Level",
+            Self::Lines => "Lines",
+            Self::LinesLeft | Self::GarbageLeft | Self::PiecesLeft => "Left",
+            Self::Pieces => "Pieces",
+        }
+    }
+}
+
+/// What turning the metronome on during a run means for it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetronomeRule {
+    /// It can't be turned on
+    Off,
+    /// It's a practice aid like any other, free to be left on
+    Free,
+    /// It can be turned on, but the run then counts as assisted and isn't
+    /// ranked
+    Assist,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GameMode {
+    /// Speeds up forever until the player tops out
+    Endless,
+    /// Clear 40 lines as fast as possible
+    Sprint,
+    /// Score as many points as possible in two minutes
+    Ultra,
+    /// Survive until level 15
+    Marathon,
+    /// Dig through `rows` rows of garbage as fast as possible
+    Cheese { rows: u32 },
+    /// Stack at a gentle pace with no way to lose
+    Zen,
+    /// Endless with every mino twice as big
+    Big,
+    /// Endless where the stack vanishes shortly after each placement
+    Invisible,
+    /// Sandbox with hand-picked pieces and optional gravity
+    Practice,
+    /// Clear a prepared field using exactly the pieces given
+    Puzzle,
+    /// Two players side by side, clearing lines to send each other garbage
+    Versus,
+    /// Survive rows of garbage pushing up from the bottom, faster and faster
+    Rising,
+}
+
+impl GameMode {
+    /// Every mode. Saves and leaderboards store a mode by where it is in
+    /// here, so new ones go at the end
+    pub const ALL: &'static [GameMode] = &[
+        GameMode::Endless,
+        GameMode::Sprint,
+        GameMode::Ultra,
+        GameMode::Marathon,
+        GameMode::Cheese { rows: 3 },
+        GameMode::Cheese { rows: 6 },
+        GameMode::Cheese { rows: 9 },
+        GameMode::Zen,
+        GameMode::Big,
+        GameMode::Invisible,
+        GameMode::Practice,
+        GameMode::Puzzle,
+        GameMode::Versus,
+        GameMode::Rising,
+    ];
+
+    /// Modes that keep a leaderboard
+    pub const RANKED: &'static [GameMode] = &[
+        GameMode::Endless,
+        GameMode::Sprint,
+        GameMode::Ultra,
+        GameMode::Marathon,
+        GameMode::Cheese { rows: 3 },
+        GameMode::Cheese { rows: 6 },
+        GameMode::Cheese { rows: 9 },
+        GameMode::Big,
+        GameMode::Invisible,
+        GameMode::Rising,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Endless => "Endless",
+            Self::Sprint => "Sprint",
+            Self::Ultra => "Ultra",
+            Self::Marathon => "Marathon",
+            Self::Cheese { rows: 3 } => "Cheese 3",
+            Self::Cheese { rows: 6 } => "Cheese 6",
+            Self::Cheese { rows: 9 } => "Cheese 9",
+            Self::Cheese { .. } => "Cheese",
+            Self::Zen => "Zen",
+            Self::Big => "Big",
+            Self::Invisible => "Invisible",
+            Self::Practice => "Practice",
+            Self::Puzzle => "Puzzle",
+            Self::Versus => "Versus",
+            Self::Rising => "Rising",
+        }
+    }
+
+    /// Name of the file in the data directory the leaderboard for this mode
+    /// is persisted to
+    pub fn scores_file(self) -> &'static str {
+        match self {
+            Self::Endless => "tetrs_scores.bin",
+            Self::Sprint => "tetrs_sprint.bin",
+            Self::Ultra => "tetrs_ultra.bin",
+            Self::Marathon => "tetrs_marathon.bin",
+            Self::Cheese { rows: 3 } => "tetrs_cheese3.bin",
+            Self::Cheese { rows: 6 } => "tetrs_cheese6.bin",
+            Self::Cheese { rows: 9 } => "tetrs_cheese9.bin",
+            Self::Cheese { .. } => "tetrs_cheese.bin",
+            Self::Zen | Self::Practice | Self::Puzzle | Self::Versus => {
+                unreachable!("{} has no leaderboard", self.name())
+            }
+            Self::Big => "tetrs_big.bin",
+            Self::Invisible => "tetrs_invisible.bin",
+            Self::Rising => "tetrs_rising.bin",
+        }
+    }
+
+    /// Whether leaderboard entries are times (lower is better) rather than points
+    pub fn ranks_by_time(self) -> bool {
+        match self {
+            Self::Endless
+            | Self::Ultra
+            | Self::Marathon
+            | Self::Zen
+            | Self::Big
+            | Self::Invisible
+            | Self::Practice
+            | Self::Puzzle
+            | Self::Versus
+            | Self::Rising => false,
+            Self::Sprint | Self::Cheese { .. } => true,
+        }
+    }
+
+    /// Whether a run is measured by its clock rather than its score
+    pub fn is_timed(self) -> bool {
+        match self {
+            Self::Endless
+            | Self::Marathon
+            | Self::Zen
+            | Self::Big
+            | Self::Invisible
+            | Self::Practice
+            | Self::Puzzle
+            | Self::Versus
+            | Self::Rising => false,
+            Self::Sprint | Self::Ultra | Self::Cheese { .. } => true,
+        }
+    }
+
+    /// How long a run lasts before time is called, in milliseconds
+    pub fn time_limit(self) -> Option<u64> {
+        match self {
+            Self::Endless
+            | Self::Sprint
+            | Self::Marathon
+            | Self::Cheese { .. }
+            | Self::Zen
+            | Self::Big
+            | Self::Invisible
+            | Self::Practice
+            | Self::Puzzle
+            | Self::Versus
+            | Self::Rising => None,
+            Self::Ultra => Some(ULTRA_MILLIS),
+        }
+    }
+
+    /// Level past which the fall speed stops increasing
+    pub fn level_cap(self) -> Option<u32> {
+        match self {
+            Self::Endless
+            | Self::Sprint
+            | Self::Ultra
+            | Self::Cheese { .. }
+            | Self::Zen
+            | Self::Big
+            | Self::Invisible
+            | Self::Practice
+            | Self::Puzzle
+            | Self::Versus
+            | Self::Rising => None,
+            Self::Marathon => Some(MARATHON_LEVEL_CAP),
+        }
+    }
+
+    /// Whether clearing lines increases the fall speed
+    pub fn levels_up(self) -> bool {
+        match self {
+            Self::Endless
+            | Self::Ultra
+            | Self::Marathon
+            | Self::Big
+            | Self::Invisible
+            | Self::Versus => true,
+            Self::Sprint
+            | Self::Cheese { .. }
+            | Self::Zen
+            | Self::Practice
+            | Self::Puzzle
+            | Self::Rising => false,
+        }
+    }
+
+    /// What the sidebar shows during a run, top first
+    pub fn hud(self) -> &'static [HudStat] {
+        use HudStat::*;
+        match self {
+            Self::Endless
+            | Self::Ultra
+            | Self::Marathon
+            | Self::Big
+            | Self::Invisible
+            | Self::Versus => &[Score, Time, Level, Lines, Pieces],
+            Self::Sprint => &[Score, Time, LinesLeft, Pieces],
+            Self::Cheese { .. } => &[Score, Time, GarbageLeft, Pieces],
+            Self::Zen | Self::Practice | Self::Rising => &[Score, Time, Lines, Pieces],
+            Self::Puzzle => &[Score, Time, PiecesLeft, Pieces],
+        }
+    }
+
+    /// Rows of garbage the field starts with
+    pub fn garbage_rows(self) -> u32 {
+        match self {
+            Self::Endless
+            | Self::Sprint
+            | Self::Ultra
+            | Self::Marathon
+            | Self::Zen
+            | Self::Big
+            | Self::Invisible
+            | Self::Practice
+            | Self::Puzzle
+            | Self::Versus
+            | Self::Rising => 0,
+            Self::Cheese { rows } => rows.min(FIELD_HEIGHT),
+        }
+    }
+
+    /// Time until the next garbage row rises for a wait starting `elapsed`
+    /// milliseconds into the run, in milliseconds, or None if rows don't
+    /// rise in this mode
+    pub fn rise_millis(self, elapsed: u64) -> Option<u64> {
+        match self {
+            Self::Rising => {
+                let shorter = elapsed / RISING_STEP_EVERY_MILLIS * RISING_STEP_MILLIS;
+                Some(
+                    RISING_START_MILLIS
+                        .saturating_sub(shorter)
+                        .max(RISING_MIN_MILLIS),
+                )
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the metronome can be turned on, and what that costs
+    pub fn metronome(self) -> MetronomeRule {
+        match self {
+            Self::Zen | Self::Practice => MetronomeRule::Free,
+            Self::Endless
+            | Self::Sprint
+            | Self::Ultra
+            | Self::Marathon
+            | Self::Cheese { .. }
+            | Self::Big
+            | Self::Invisible
+            | Self::Rising => MetronomeRule::Assist,
+            Self::Puzzle | Self::Versus => MetronomeRule::Off,
+        }
+    }
+
+    /// Field cells along each side of a mino
+    pub fn piece_scale(self) -> u8 {
+        match self {
+            Self::Big => 2,
+            _ => 1,
+        }
+    }
+
+    /// Whether runs in this mode are ranked and count towards achievements
+    pub fn is_ranked(self) -> bool {
+        Self::RANKED.contains(&self)
+    }
+
+    /// Whether locked cells fade out after a moment
+    pub fn hides_stack(self) -> bool {
+        self == Self::Invisible
+    }
+
+    /// Formats a leaderboard value for this mode
+    pub fn format_value(self, value: u64) -> String {
+        if self.ranks_by_time() {
+            format_time(value)
+        } else if self == Self::Marathon && value & MARATHON_CLEAR_FLAG != 0 {
+            format!("*{}", value & !MARATHON_CLEAR_FLAG)
+        } else {
+            value.to_string()
+        }
+    }
+}
+
+/// Milliseconds shown as minutes, seconds and tenths, for a clock that's
+/// still running. Written straight into whatever it's formatted into
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Clock(pub u64);
+
+impl fmt::Display for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}.{}",
+            self.0 / 60_000,
+            (self.0 / 1000) % 60,
+            (self.0 % 1000) / 100
+        )
+    }
+}
+
+/// Milliseconds shown as minutes, seconds and hundredths, for a time that's
+/// been set, like [`Clock`] written straight into whatever it's formatted into
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Finish(pub u64);
+
+impl fmt::Display for Finish {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{:02}.{:02}",
+            self.0 / 60_000,
+            (self.0 / 1000) % 60,
+            (self.0 % 1000) / 10
+        )
+    }
+}
+
+pub fn format_time(millis: u64) -> String {
+    Finish(millis).to_string()
+}
@@ -0,0 +1,52 @@
+//! Where the game keeps what it writes between runs.
+//!
+//! Everything goes in one directory under the platform's data directory:
+//! `~/.local/share/tetrs` on Linux, `%APPDATA%\tetrs` on Windows and
+//! `~/Library/Application Support/tetrs` on macOS. Without one, as when no
+//! home directory is set, files go in the working directory like they used to.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Directory made under the platform's data directory
+const APP_DIR: &str = "tetrs";
+
+/// Directory the game's files are kept in. It's only made once something is
+/// written to it, see [`create_parent`]
+pub fn data_dir() -> PathBuf {
+    dirs::data_dir().map_or_else(PathBuf::new, |dir| dir.join(APP_DIR))
+}
+
+/// Where the file called `name` is kept
+pub fn data_file(name: &str) -> PathBuf {
+    data_dir().join(name)
+}
+
+/// Makes the directory `path` goes in, if it's not there yet. Called before
+/// writing anything, since the data directory starts out missing
+pub fn create_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) => fs::create_dir_all(dir),
+        None => Ok(()),
+    }
+}
+
+/// Moves `name` from the working directory into the data directory, where
+/// older versions left it. Returns whether anything was moved; a file that's
+/// in the data directory already is newer, so the old one is left alone
+pub fn migrate(name: &str) -> io::Result<bool> {
+    let old = Path::new(name);
+    let new = data_file(name);
+    if !old.is_file() || new.exists() {
+        return Ok(false);
+    }
+
+    create_parent(&new)?;
+    // renaming fails across file systems, where it has to be copied over
+    if fs::rename(old, &new).is_err() {
+        fs::copy(old, &new)?;
+        fs::remove_file(old)?;
+    }
+    Ok(true)
+}
@@ -0,0 +1,354 @@
+//! The seven tetrominoes and pieces of them in play.
+//!
+//! What a piece looks like and how it moves is a [`PieceDef`], so other sets of
+//! pieces, like the pentominoes, can be played with the same rules. The seven
+//! are built in and are what every mode deals; [`set`] loads the others. How
+//! the seven turn is up to the run's [`rotation`] system instead.
+
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::board::FIELD_WIDTH;
+use crate::piece::rotation::{RotationKind, NO_KICKS};
+
+pub mod randomizer;
+pub mod rotation;
+pub mod set;
+
+/// Widest box a piece can be drawn in
+pub const MAX_PIECE_SIZE: u8 = 5;
+
+/// One kind of piece
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PieceDef {
+    pub name: &'static str,
+
+    /// Side of the box the shape is drawn in
+    pub size: u8,
+
+    /// Shape in a `size` by `size` box read row by row, `#` where it's filled
+    pub shape: &'static str,
+
+    pub color: Color,
+
+    /// Columns right of the middle of the field the box spawns, or left if
+    /// negative
+    pub spawn_offset: i8,
+
+    /// Where a rotation that's blocked tries instead, in minos right and down,
+    /// in order. The first is usually no move at all
+    pub kicks: &'static [(i8, i8)],
+}
+
+impl PieceDef {
+    /// Whether the cell `x` across and `y` down the box is filled, turned
+    /// `rot` quarter turns clockwise
+    fn filled_at(&self, x: usize, y: usize, rot: u8) -> bool {
+        let n = self.size as usize;
+        assert!(x < n && y < n, "Out of bounds index supplied");
+
+        let i = match rot % 4 {
+            0 => x + y * n,
+            1 => (n - 1 - y) + x * n,
+            2 => n * n - 1 - (x + y * n),
+            3 => (n - 1 - x) * n + y,
+            _ => unreachable!(),
+        };
+
+        &self.shape[i..=i] == "#"
+    }
+}
+
+/// The seven, in the order of [`Tetromino`]. In play they turn and kick by the
+/// run's [rotation system](rotation::RotationSystem); the shapes here are how
+/// they're drawn elsewhere, like falling behind the menu
+static STANDARD_PIECES: [PieceDef; 7] = [
+    PieceDef {
+        name: "I",
+        size: 4,
+        shape: "..#.\
+                ..#.\
+                ..#.\
+                ..#.",
+        color: Color::Cyan,
+        spawn_offset: 0,
+        kicks: NO_KICKS,
+    },
+    PieceDef {
+        name: "O",
+        size: 4,
+        shape: "....\
+                .##.\
+                .##.\
+                ....",
+        color: Color::Yellow,
+        spawn_offset: 0,
+        kicks: NO_KICKS,
+    },
+    PieceDef {
+        name: "T",
+        size: 4,
+        shape: ".#..\
+                .##.\
+                .#..\
+                ....",
+        color: Color::Purple,
+        spawn_offset: 0,
+        kicks: NO_KICKS,
+    },
+    PieceDef {
+        name: "S",
+        size: 4,
+        shape: ".#..\
+                .##.\
+                ..#.\
+                ....",
+        color: Color::Green,
+        spawn_offset: 0,
+        kicks: NO_KICKS,
+    },
+    PieceDef {
+        name: "Z",
+        size: 4,
+        shape: "..#.\
+                .##.\
+                .#..\
+                ....",
+        color: Color::Red,
+        spawn_offset: 0,
+        kicks: NO_KICKS,
+    },
+    PieceDef {
+        name: "J",
+        size: 4,
+        shape: "..#.\
+                ..#.\
+                .##.\
+                ....",
+        color: Color::Blue,
+        spawn_offset: 0,
+        kicks: NO_KICKS,
+    },
+    PieceDef {
+        name: "L",
+        size: 4,
+        shape: ".#..\
+                .#..\
+                .##.\
+                ....",
+        color: Color::Orange,
+        spawn_offset: 0,
+        kicks: NO_KICKS,
+    },
+];
+
+/// The seven pieces. The order matches the per-piece counts in `GameStats`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tetromino {
+    I,
+    O,
+    T,
+    S,
+    Z,
+    J,
+    L,
+}
+
+impl Tetromino {
+    pub const ALL: &'static [Tetromino] = &[
+        Tetromino::I,
+        Tetromino::O,
+        Tetromino::T,
+        Tetromino::S,
+        Tetromino::Z,
+        Tetromino::J,
+        Tetromino::L,
+    ];
+
+    /// How the piece looks and moves
+    pub fn def(self) -> &'static PieceDef {
+        &STANDARD_PIECES[self as usize]
+    }
+
+    pub fn name(self) -> &'static str {
+        self.def().name
+    }
+
+    pub fn shape(self) -> &'static str {
+        self.def().shape
+    }
+
+    pub fn color(self) -> Color {
+        self.def().color
+    }
+
+    /// Picks one of the seven pieces at random
+    pub fn random(rng: &mut impl Rng) -> Self {
+        Self::ALL[rng.gen_range(0..Self::ALL.len())]
+    }
+
+    /// The piece named `letter`, as written in puzzle files
+    pub fn from_letter(letter: char) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|t| t.name().starts_with(letter))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum Color {
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+    Cyan,
+    Grey,
+}
+
+/// Side of the grid a [`Color::pattern`] is laid out on
+pub const PATTERN_SIZE: usize = 3;
+
+impl Color {
+    /// Marks drawn on cells of this colour so they can be told apart without
+    /// it, in a 3x3 box read row by row
+    pub fn pattern(self) -> &'static str {
+        match self {
+            // I, a bar down the middle
+            Self::Cyan => {
+                ".#.\
+                 .#.\
+                 .#."
+            }
+            // O, a ring
+            Self::Yellow => {
+                "###\
+                 #.#\
+                 ###"
+            }
+            // T, a dot
+            Self::Purple => {
+                "...\
+                 .#.\
+                 ..."
+            }
+            // S, a rising diagonal
+            Self::Green => {
+                "..#\
+                 .#.\
+                 #.."
+            }
+            // Z, a falling diagonal
+            Self::Red => {
+                "#..\
+                 .#.\
+                 ..#"
+            }
+            // J, the corners
+            Self::Blue => {
+                "#.#\
+                 ...\
+                 #.#"
+            }
+            // L, a bar across the middle
+            Self::Orange => {
+                "...\
+                 ###\
+                 ..."
+            }
+            // garbage is left plain
+            Self::Grey => ".........",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Piece {
+    pub kind: &'static PieceDef,
+    pub rot: u8,
+    pub x: i8,
+    pub y: i8,
+    /// Field cells along each side of a mino. Position and movement stay
+    /// multiples of this, so the piece lives on a coarser grid
+    pub scale: u8,
+    /// How the piece turns, if it's one of the seven
+    pub rotation: RotationKind,
+}
+
+impl Piece {
+    pub fn new(kind: &'static PieceDef, scale: u8, rotation: RotationKind) -> Self {
+        let columns = (FIELD_WIDTH / scale as u32) as i8;
+        let column = columns / 2 - kind.size as i8 / 2 + kind.spawn_offset;
+        Self {
+            x: column * scale as i8,
+            y: 0,
+            scale,
+            kind,
+            rot: 0,
+            rotation,
+        }
+    }
+
+    /// Which of the seven this is, if it's one of them
+    pub fn tetromino(&self) -> Option<Tetromino> {
+        Tetromino::ALL
+            .iter()
+            .copied()
+            .find(|t| std::ptr::eq(t.def(), self.kind))
+    }
+
+    /// Whether the cell `x` across and `y` down the piece's box is filled, as
+    /// it's turned now
+    pub fn filled_at(&self, x: usize, y: usize) -> bool {
+        match self.tetromino() {
+            Some(t) => self.rotation.system().filled_at(t, x, y, self.rot),
+            None => self.kind.filled_at(x, y, self.rot),
+        }
+    }
+
+    /// Where turning the piece `turn` quarter turns clockwise tries, in minos
+    /// right and down, in order
+    pub fn kicks(&self, turn: u8) -> &'static [(i8, i8)] {
+        match self.tetromino() {
+            Some(t) => self
+                .rotation
+                .system()
+                .kicks(t, self.rot % 4, (self.rot + turn) % 4),
+            None => self.kind.kicks,
+        }
+    }
+
+    /// Cells of the box the piece covers as it's turned now, as the first
+    /// column and row and the number of each
+    pub fn bounds(&self) -> (usize, usize, usize, usize) {
+        let n = self.kind.size as usize;
+        let filled = (0..n * n)
+            .map(|i| (i % n, i / n))
+            .filter(|&(x, y)| self.filled_at(x, y));
+        let (left, top, right, bottom) = filled.fold(
+            (usize::MAX, usize::MAX, 0, 0),
+            |(left, top, right, bottom), (x, y)| {
+                (left.min(x), top.min(y), right.max(x + 1), bottom.max(y + 1))
+            },
+        );
+        if right == 0 {
+            return (0, 0, 0, 0);
+        }
+        (left, top, right - left, bottom - top)
+    }
+
+    /// Field cells covered by the piece
+    pub fn cells(self) -> impl Iterator<Item = (isize, isize)> {
+        let scale = self.scale as isize;
+        let n = self.kind.size as usize;
+        (0..n * n)
+            .filter(move |i| self.filled_at(i % n, i / n))
+            .flat_map(move |i| {
+                let x = self.x as isize + (i % n) as isize * scale;
+                let y = self.y as isize + (i / n) as isize * scale;
+                (0..scale * scale).map(move |j| (x + j % scale, y + j / scale))
+            })
+    }
+}
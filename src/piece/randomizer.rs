@@ -0,0 +1,233 @@
+//! Ways of picking the next piece.
+//!
+//! A [`Randomizer`] deals pieces one at a time, drawing on the run's random
+//! number generator, so the seed and the [`RandomizerKind`] are all it takes
+//! to deal the same pieces again.
+
+use std::collections::VecDeque;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+use super::Tetromino;
+
+/// Pieces the history randomizer remembers
+pub const HISTORY_LEN: usize = 4;
+
+/// Times the history randomizer rerolls a piece it remembers before dealing it
+/// anyway
+pub const HISTORY_REROLLS: u32 = 4;
+
+pub trait Randomizer {
+    /// Picks the next piece from `rng`
+    fn next(&mut self, rng: &mut StdRng) -> Tetromino;
+}
+
+/// Every piece as likely as any other every time, however recently it came
+#[derive(Clone, Debug, Default)]
+pub struct Classic;
+
+impl Randomizer for Classic {
+    fn next(&mut self, rng: &mut StdRng) -> Tetromino {
+        Tetromino::random(rng)
+    }
+}
+
+/// All seven shuffled into a bag and dealt out before the next bag is
+/// opened, so there are never more than twelve pieces between two of a kind
+#[derive(Clone, Debug, Default)]
+pub struct Bag {
+    bag: Vec<Tetromino>,
+}
+
+impl Randomizer for Bag {
+    fn next(&mut self, rng: &mut StdRng) -> Tetromino {
+        if self.bag.is_empty() {
+            self.bag.extend_from_slice(Tetromino::ALL);
+            self.bag.shuffle(rng);
+        }
+        self.bag.pop().unwrap()
+    }
+}
+
+/// Deals like TGM: a piece among the last [`HISTORY_LEN`] is rerolled, up to
+/// [`HISTORY_REROLLS`] times, so repeats are rare without being ruled out.
+/// The first piece is never an S, Z or O
+#[derive(Clone, Debug)]
+pub struct History {
+    recent: VecDeque<Tetromino>,
+    dealt_any: bool,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        // starting out as if S and Z just came keeps them away early on
+        Self {
+            recent: [Tetromino::Z, Tetromino::S, Tetromino::S, Tetromino::Z]
+                .iter()
+                .copied()
+                .collect(),
+            dealt_any: false,
+        }
+    }
+}
+
+impl Randomizer for History {
+    fn next(&mut self, rng: &mut StdRng) -> Tetromino {
+        let piece = if !std::mem::replace(&mut self.dealt_any, true) {
+            *[Tetromino::I, Tetromino::T, Tetromino::J, Tetromino::L]
+                .choose(rng)
+                .unwrap()
+        } else {
+            let mut piece = Tetromino::random(rng);
+            for _ in 0..HISTORY_REROLLS {
+                if !self.recent.contains(&piece) {
+                    break;
+                }
+                piece = Tetromino::random(rng);
+            }
+            piece
+        };
+        if self.recent.len() == HISTORY_LEN {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(piece);
+        piece
+    }
+}
+
+/// Which randomizer a run deals its pieces with
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RandomizerKind {
+    Classic,
+    Bag,
+    History,
+}
+
+impl Default for RandomizerKind {
+    fn default() -> Self {
+        Self::Classic
+    }
+}
+
+impl RandomizerKind {
+    pub const ALL: &'static [RandomizerKind] = &[
+        RandomizerKind::Classic,
+        RandomizerKind::Bag,
+        RandomizerKind::History,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Classic => "Random",
+            Self::Bag => "7-bag",
+            Self::History => "History",
+        }
+    }
+
+    /// A randomizer of this kind that hasn't dealt anything yet
+    pub fn build(self) -> Box<dyn Randomizer> {
+        match self {
+            Self::Classic => Box::new(Classic),
+            Self::Bag => Box::new(Bag::default()),
+            Self::History => Box::new(History::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    /// `count` pieces dealt by a fresh `kind` from `seed`
+    fn deal(kind: RandomizerKind, seed: u64, count: usize) -> Vec<Tetromino> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut randomizer = kind.build();
+        (0..count).map(|_| randomizer.next(&mut rng)).collect()
+    }
+
+    /// Share of pieces in `pieces` that came up in the [`HISTORY_LEN`] before
+    fn repeats(pieces: &[Tetromino]) -> f64 {
+        let repeated = (HISTORY_LEN..pieces.len())
+            .filter(|&i| pieces[i - HISTORY_LEN..i].contains(&pieces[i]))
+            .count();
+        repeated as f64 / (pieces.len() - HISTORY_LEN) as f64
+    }
+
+    #[test]
+    fn the_same_seed_deals_the_same_pieces() {
+        for &kind in RandomizerKind::ALL {
+            assert_eq!(deal(kind, 7, 200), deal(kind, 7, 200), "{:?}", kind);
+            assert_ne!(deal(kind, 7, 200), deal(kind, 8, 200), "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn random_pieces_come_up_evenly() {
+        let pieces = deal(RandomizerKind::Classic, 1, 7000);
+        for &kind in Tetromino::ALL {
+            let count = pieces.iter().filter(|&&p| p == kind).count();
+            assert!(
+                (850..1150).contains(&count),
+                "{:?} came {} times",
+                kind,
+                count
+            );
+        }
+        // with nothing keeping them apart, repeats are common
+        assert!(repeats(&pieces) > 0.4);
+    }
+
+    #[test]
+    fn each_bag_holds_one_of_every_piece() {
+        for seed in 0..20 {
+            let pieces = deal(RandomizerKind::Bag, seed, 7 * 50);
+            for bag in pieces.chunks(7) {
+                let mut bag = bag.to_vec();
+                bag.sort_by_key(|&p| p as u8);
+                assert_eq!(bag, Tetromino::ALL, "seed {}", seed);
+            }
+        }
+    }
+
+    #[test]
+    fn the_bag_never_deals_three_of_a_kind_within_seven() {
+        for seed in 0..20 {
+            let pieces = deal(RandomizerKind::Bag, seed, 7 * 50);
+            for window in pieces.windows(7) {
+                for &kind in Tetromino::ALL {
+                    let count = window.iter().filter(|&&p| p == kind).count();
+                    assert!(count <= 2, "seed {}: {:?} x{}", seed, kind, count);
+                }
+            }
+            // and never more than twelve between two of a kind
+            for window in pieces.windows(14) {
+                assert!(window[1..].contains(&window[0]), "seed {}", seed);
+            }
+        }
+    }
+
+    #[test]
+    fn history_rarely_repeats_within_four() {
+        let pieces = deal(RandomizerKind::History, 1, 7000);
+        let share = repeats(&pieces);
+        // a piece is only dealt again after five draws all land in history
+        assert!(share < 0.1, "{:.3} of pieces repeated", share);
+        assert!(share > 0.0, "repeats should be rare, not ruled out");
+    }
+
+    #[test]
+    fn history_never_starts_with_an_s_z_or_o() {
+        for seed in 0..200 {
+            let first = deal(RandomizerKind::History, seed, 1)[0];
+            assert!(
+                ![Tetromino::S, Tetromino::Z, Tetromino::O].contains(&first),
+                "seed {} started with {:?}",
+                seed,
+                first
+            );
+        }
+    }
+}
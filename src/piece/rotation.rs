@@ -0,0 +1,495 @@
+//! How the seven turn: the cells each covers in each of its four rotations,
+//! and where a rotation that's blocked tries instead.
+//!
+//! Rotation 0 is how a piece spawns and each one after it is a quarter turn
+//! clockwise. Cells are `(x, y)` in the piece's 4x4 box, x across and y down,
+//! and kicks are in minos right and down, tried in order. Pieces with only
+//! two distinct rotations list each of them twice. Pieces from other
+//! [sets](super::set) turn their whole box and kick the way their file says,
+//! whatever the system.
+
+use serde::{Deserialize, Serialize};
+
+use super::Tetromino;
+
+/// Cells a piece covers in one of its rotations
+pub type Cells = [(u8, u8); 4];
+
+/// A rotation tried only where the piece is
+pub const NO_KICKS: &[(i8, i8)] = &[(0, 0)];
+
+pub trait RotationSystem {
+    /// Cells `piece` covers turned `rot` quarter turns clockwise from how it
+    /// spawns
+    fn cells(&self, piece: Tetromino, rot: u8) -> &'static Cells;
+
+    /// Where turning `piece` from rotation `from` to rotation `to` tries, in
+    /// order
+    fn kicks(&self, piece: Tetromino, from: u8, to: u8) -> &'static [(i8, i8)];
+
+    /// Whether the cell `x` across and `y` down the box of `piece` is filled,
+    /// turned `rot` quarter turns clockwise
+    fn filled_at(&self, piece: Tetromino, x: usize, y: usize, rot: u8) -> bool {
+        self.cells(piece, rot)
+            .iter()
+            .any(|&(cx, cy)| cx as usize == x && cy as usize == y)
+    }
+}
+
+/// Which rotation system a run turns its pieces with
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RotationKind {
+    Srs,
+    Classic,
+    Ars,
+}
+
+impl Default for RotationKind {
+    fn default() -> Self {
+        Self::Srs
+    }
+}
+
+impl RotationKind {
+    pub const ALL: &'static [RotationKind] =
+        &[RotationKind::Srs, RotationKind::Classic, RotationKind::Ars];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Srs => "SRS",
+            Self::Classic => "Classic",
+            Self::Ars => "ARS",
+        }
+    }
+
+    pub fn system(self) -> &'static dyn RotationSystem {
+        match self {
+            Self::Srs => &Srs,
+            Self::Classic => &Classic,
+            Self::Ars => &Ars,
+        }
+    }
+}
+
+/// The guideline's Super Rotation System: pieces spawn flat side down and
+/// turn about the middle of their box, trying four kicks when that's blocked
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Srs;
+
+/// Cells of each of the seven by rotation, in the order of [`Tetromino`]
+#[rustfmt::skip]
+static SRS_CELLS: [[Cells; 4]; 7] = [
+    // I
+    [
+        [(0, 1), (1, 1), (2, 1), (3, 1)],
+        [(2, 0), (2, 1), (2, 2), (2, 3)],
+        [(0, 2), (1, 2), (2, 2), (3, 2)],
+        [(1, 0), (1, 1), (1, 2), (1, 3)],
+    ],
+    // O
+    [
+        [(1, 0), (2, 0), (1, 1), (2, 1)],
+        [(1, 0), (2, 0), (1, 1), (2, 1)],
+        [(1, 0), (2, 0), (1, 1), (2, 1)],
+        [(1, 0), (2, 0), (1, 1), (2, 1)],
+    ],
+    // T
+    [
+        [(1, 0), (0, 1), (1, 1), (2, 1)],
+        [(1, 0), (1, 1), (2, 1), (1, 2)],
+        [(0, 1), (1, 1), (2, 1), (1, 2)],
+        [(1, 0), (0, 1), (1, 1), (1, 2)],
+    ],
+    // S
+    [
+        [(1, 0), (2, 0), (0, 1), (1, 1)],
+        [(1, 0), (1, 1), (2, 1), (2, 2)],
+        [(1, 1), (2, 1), (0, 2), (1, 2)],
+        [(0, 0), (0, 1), (1, 1), (1, 2)],
+    ],
+    // Z
+    [
+        [(0, 0), (1, 0), (1, 1), (2, 1)],
+        [(2, 0), (1, 1), (2, 1), (1, 2)],
+        [(0, 1), (1, 1), (1, 2), (2, 2)],
+        [(1, 0), (0, 1), (1, 1), (0, 2)],
+    ],
+    // J
+    [
+        [(0, 0), (0, 1), (1, 1), (2, 1)],
+        [(1, 0), (2, 0), (1, 1), (1, 2)],
+        [(0, 1), (1, 1), (2, 1), (2, 2)],
+        [(1, 0), (1, 1), (0, 2), (1, 2)],
+    ],
+    // L
+    [
+        [(2, 0), (0, 1), (1, 1), (2, 1)],
+        [(1, 0), (1, 1), (1, 2), (2, 2)],
+        [(0, 1), (1, 1), (2, 1), (0, 2)],
+        [(0, 0), (1, 0), (1, 1), (1, 2)],
+    ],
+];
+
+/// Kicks of J, L, S, T and Z, by turn: 0 to 1, 1 to 0, 1 to 2, 2 to 1, 2 to
+/// 3, 3 to 2, 3 to 0 and 0 to 3
+#[rustfmt::skip]
+static SRS_KICKS: [[(i8, i8); 5]; 8] = [
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+];
+
+/// Kicks of I, by turn like [`SRS_KICKS`]
+#[rustfmt::skip]
+static SRS_I_KICKS: [[(i8, i8); 5]; 8] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+    [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+    [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+    [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+    [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+    [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+    [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+    [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+];
+
+impl RotationSystem for Srs {
+    fn cells(&self, piece: Tetromino, rot: u8) -> &'static Cells {
+        &SRS_CELLS[piece as usize][rot as usize % 4]
+    }
+
+    fn kicks(&self, piece: Tetromino, from: u8, to: u8) -> &'static [(i8, i8)] {
+        let turn = match (from % 4, to % 4) {
+            (0, 1) => 0,
+            (1, 0) => 1,
+            (1, 2) => 2,
+            (2, 1) => 3,
+            (2, 3) => 4,
+            (3, 2) => 5,
+            (3, 0) => 6,
+            (0, 3) => 7,
+            // half turns and no turn at all
+            _ => return NO_KICKS,
+        };
+        match piece {
+            Tetromino::O => NO_KICKS,
+            Tetromino::I => &SRS_I_KICKS[turn],
+            _ => &SRS_KICKS[turn],
+        }
+    }
+}
+
+/// Like the NES: no kicks at all, T, J and L spawn pointing down, and I, S
+/// and Z flip between two rotations
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Classic;
+
+/// Rotation of [`SRS_CELLS`] each of the seven takes in each of its own, as
+/// the pieces only spawn turned another way
+#[rustfmt::skip]
+static CLASSIC_ROTATIONS: [[u8; 4]; 7] = [
+    [2, 1, 2, 1], // I
+    [0, 0, 0, 0], // O
+    [2, 3, 0, 1], // T
+    [2, 1, 2, 1], // S
+    [2, 1, 2, 1], // Z
+    [2, 3, 0, 1], // J
+    [2, 3, 0, 1], // L
+];
+
+impl RotationSystem for Classic {
+    fn cells(&self, piece: Tetromino, rot: u8) -> &'static Cells {
+        let rot = CLASSIC_ROTATIONS[piece as usize][rot as usize % 4];
+        Srs.cells(piece, rot)
+    }
+
+    fn kicks(&self, _piece: Tetromino, _from: u8, _to: u8) -> &'static [(i8, i8)] {
+        NO_KICKS
+    }
+}
+
+/// Arika's, from the TGM games: T, J and L spawn pointing down, every
+/// rotation rests on the bottom of its box, and a blocked turn tries a step
+/// right and then a step left. I never kicks
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Ars;
+
+/// Cells of each of the seven by rotation, in the order of [`Tetromino`]
+#[rustfmt::skip]
+static ARS_CELLS: [[Cells; 4]; 7] = [
+    // I
+    [
+        [(0, 1), (1, 1), (2, 1), (3, 1)],
+        [(2, 0), (2, 1), (2, 2), (2, 3)],
+        [(0, 1), (1, 1), (2, 1), (3, 1)],
+        [(2, 0), (2, 1), (2, 2), (2, 3)],
+    ],
+    // O
+    [
+        [(1, 1), (2, 1), (1, 2), (2, 2)],
+        [(1, 1), (2, 1), (1, 2), (2, 2)],
+        [(1, 1), (2, 1), (1, 2), (2, 2)],
+        [(1, 1), (2, 1), (1, 2), (2, 2)],
+    ],
+    // T
+    [
+        [(0, 1), (1, 1), (2, 1), (1, 2)],
+        [(1, 0), (0, 1), (1, 1), (1, 2)],
+        [(1, 1), (0, 2), (1, 2), (2, 2)],
+        [(1, 0), (1, 1), (2, 1), (1, 2)],
+    ],
+    // S
+    [
+        [(1, 1), (2, 1), (0, 2), (1, 2)],
+        [(0, 0), (0, 1), (1, 1), (1, 2)],
+        [(1, 1), (2, 1), (0, 2), (1, 2)],
+        [(0, 0), (0, 1), (1, 1), (1, 2)],
+    ],
+    // Z
+    [
+        [(0, 1), (1, 1), (1, 2), (2, 2)],
+        [(2, 0), (1, 1), (2, 1), (1, 2)],
+        [(0, 1), (1, 1), (1, 2), (2, 2)],
+        [(2, 0), (1, 1), (2, 1), (1, 2)],
+    ],
+    // J
+    [
+        [(0, 1), (1, 1), (2, 1), (2, 2)],
+        [(1, 0), (1, 1), (0, 2), (1, 2)],
+        [(0, 1), (0, 2), (1, 2), (2, 2)],
+        [(1, 0), (2, 0), (1, 1), (1, 2)],
+    ],
+    // L
+    [
+        [(0, 1), (1, 1), (2, 1), (0, 2)],
+        [(0, 0), (1, 0), (1, 1), (1, 2)],
+        [(2, 1), (0, 2), (1, 2), (2, 2)],
+        [(1, 0), (1, 1), (1, 2), (2, 2)],
+    ],
+];
+
+/// Where every piece but I tries, whichever way it turns
+const ARS_KICKS: &[(i8, i8)] = &[(0, 0), (1, 0), (-1, 0)];
+
+impl RotationSystem for Ars {
+    fn cells(&self, piece: Tetromino, rot: u8) -> &'static Cells {
+        &ARS_CELLS[piece as usize][rot as usize % 4]
+    }
+
+    fn kicks(&self, piece: Tetromino, _from: u8, _to: u8) -> &'static [(i8, i8)] {
+        match piece {
+            Tetromino::I => NO_KICKS,
+            _ => ARS_KICKS,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{Board, FIELD_WIDTH};
+    use crate::piece::Piece;
+
+    /// A system, a piece, the rotations it turns from and to, and the kicks
+    /// it tries
+    type KickCase = (RotationKind, Tetromino, u8, u8, &'static [(i8, i8)]);
+
+    /// A system, a piece, its column and rotation, a turn, and the column it
+    /// ends up in if it turns at all
+    type WallCase = (RotationKind, Tetromino, (i8, u8), u8, Option<i8>);
+
+    /// Every way of turning from one rotation to another
+    fn turns() -> impl Iterator<Item = (u8, u8)> {
+        (0..4).flat_map(|from| (0..4).map(move |to| (from, to)))
+    }
+
+    fn sorted(cells: &Cells) -> Vec<(u8, u8)> {
+        let mut cells = cells.to_vec();
+        cells.sort_unstable();
+        cells
+    }
+
+    #[test]
+    fn every_rotation_is_four_cells_in_the_box() {
+        for &kind in RotationKind::ALL {
+            for &piece in Tetromino::ALL {
+                for rot in 0..4 {
+                    let cells = sorted(kind.system().cells(piece, rot));
+                    assert!(cells.iter().all(|&(x, y)| x < 4 && y < 4));
+                    assert!(
+                        cells.windows(2).all(|pair| pair[0] != pair[1]),
+                        "{:?} {:?} rotation {} covers a cell twice",
+                        kind,
+                        piece,
+                        rot
+                    );
+                    // turning all the way round ends up where it started
+                    assert_eq!(
+                        kind.system().cells(piece, rot + 4),
+                        kind.system().cells(piece, rot)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn srs_turns_each_piece_about_the_middle_of_its_box() {
+        for &piece in Tetromino::ALL {
+            // the I turns in all four cells of its box, the rest in three
+            let last = match piece {
+                Tetromino::O => continue,
+                Tetromino::I => 3,
+                _ => 2,
+            };
+            for rot in 0..4 {
+                let mut turned: Cells = *Srs.cells(piece, rot);
+                for cell in turned.iter_mut() {
+                    *cell = (last - cell.1, cell.0);
+                }
+                assert_eq!(
+                    sorted(&turned),
+                    sorted(Srs.cells(piece, rot + 1)),
+                    "{:?} rotation {}",
+                    piece,
+                    rot
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn srs_kicks_back_the_way_they_came() {
+        for &piece in Tetromino::ALL {
+            for (from, to) in turns() {
+                let there = Srs.kicks(piece, from, to);
+                let back = Srs.kicks(piece, to, from);
+                let undone: Vec<(i8, i8)> = back.iter().map(|&(x, y)| (-x, -y)).collect();
+                assert_eq!(there, &undone[..], "{:?} {} to {}", piece, from, to);
+                assert_eq!(there[0], (0, 0), "{:?} doesn't try in place first", piece);
+            }
+        }
+    }
+
+    #[test]
+    fn each_system_tries_its_own_kicks() {
+        use Tetromino::*;
+        #[rustfmt::skip]
+        let table: &[KickCase] = &[
+            (RotationKind::Srs, T, 0, 1, &[(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)]),
+            (RotationKind::Srs, L, 3, 0, &[(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]),
+            (RotationKind::Srs, I, 0, 1, &[(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)]),
+            (RotationKind::Srs, I, 1, 2, &[(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)]),
+            (RotationKind::Srs, O, 0, 1, NO_KICKS),
+            (RotationKind::Srs, T, 0, 2, NO_KICKS),
+            (RotationKind::Classic, T, 0, 1, NO_KICKS),
+            (RotationKind::Classic, I, 1, 2, NO_KICKS),
+            // a step right comes before a step left
+            (RotationKind::Ars, T, 0, 1, &[(0, 0), (1, 0), (-1, 0)]),
+            (RotationKind::Ars, S, 1, 0, &[(0, 0), (1, 0), (-1, 0)]),
+            (RotationKind::Ars, O, 2, 3, &[(0, 0), (1, 0), (-1, 0)]),
+            (RotationKind::Ars, I, 0, 1, NO_KICKS),
+        ];
+        for &(kind, piece, from, to, kicks) in table {
+            assert_eq!(
+                kind.system().kicks(piece, from, to),
+                kicks,
+                "{:?} {:?} {} to {}",
+                kind,
+                piece,
+                from,
+                to
+            );
+        }
+        for (from, to) in turns() {
+            for &piece in Tetromino::ALL {
+                assert_eq!(Classic.kicks(piece, from, to), NO_KICKS);
+                if piece == I {
+                    assert_eq!(Ars.kicks(piece, from, to), NO_KICKS);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn classic_and_ars_spawn_pointing_down() {
+        for &piece in &[Tetromino::T, Tetromino::J, Tetromino::L] {
+            assert_eq!(Classic.cells(piece, 0), Srs.cells(piece, 2));
+            // flat side up, in the lowest rows of the box it covers
+            let flat = Ars.cells(piece, 0);
+            assert_eq!(flat.iter().filter(|&&(_, y)| y == 1).count(), 3);
+            assert_eq!(flat.iter().filter(|&&(_, y)| y == 2).count(), 1);
+        }
+        for &piece in &[Tetromino::I, Tetromino::S, Tetromino::Z] {
+            for &kind in &[RotationKind::Classic, RotationKind::Ars] {
+                let system = kind.system();
+                assert_eq!(system.cells(piece, 0), system.cells(piece, 2));
+                assert_eq!(system.cells(piece, 1), system.cells(piece, 3));
+            }
+        }
+    }
+
+    #[test]
+    fn ars_rests_every_rotation_on_the_bottom_of_its_box() {
+        for &piece in Tetromino::ALL {
+            if piece == Tetromino::I {
+                continue;
+            }
+            for rot in 0..4 {
+                let bottom = Ars.cells(piece, rot).iter().map(|&(_, y)| y).max();
+                assert_eq!(bottom, Some(2), "{:?} rotation {}", piece, rot);
+            }
+        }
+    }
+
+    #[test]
+    fn turning_against_the_walls_kicks_the_way_each_system_does() {
+        use RotationKind::*;
+        use Tetromino::*;
+        let board = Board::default();
+        let right = FIELD_WIDTH as i8 - 2;
+        #[rustfmt::skip]
+        let table: &[WallCase] = &[
+            // standing on end in the first column
+            (Srs, I, (-2, 1), 1, Some(0)),
+            (Srs, I, (-2, 1), 3, Some(0)),
+            (Classic, I, (-2, 1), 1, None),
+            (Ars, I, (-2, 1), 1, None),
+            // pointing away from the left wall, turned to lie along it
+            (Srs, T, (-1, 1), 3, Some(0)),
+            (Classic, T, (-1, 3), 1, None),
+            (Ars, T, (-1, 3), 1, Some(0)),
+            // and from the right wall, where ARS steps back left
+            (Srs, T, (right, 3), 1, Some(right - 1)),
+            (Classic, T, (right, 1), 1, None),
+            (Ars, T, (right, 1), 1, Some(right - 1)),
+            // never blocked turning in place
+            (Srs, O, (-1, 0), 1, Some(-1)),
+            (Classic, O, (-1, 0), 1, Some(-1)),
+            (Ars, O, (-1, 0), 1, Some(-1)),
+        ];
+        for &(kind, piece, (x, rot), turn, end) in table {
+            let at = Piece {
+                x,
+                y: 8,
+                rot,
+                ..Piece::new(piece.def(), 1, kind)
+            };
+            assert!(board.fits(&at), "{:?} {:?} doesn't fit", kind, piece);
+            assert_eq!(
+                board.rotate(&at, turn).map(|p| (p.x, p.y, p.rot)),
+                end.map(|x| (x, 8, (rot + turn) % 4)),
+                "{:?} {:?} at {} rotation {} turned {}",
+                kind,
+                piece,
+                x,
+                rot,
+                turn
+            );
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Sets of pieces other than the seven tetrominoes, loaded from TOML files.
+//!
+//! A set file has a `name` and a list of `[[pieces]]`, each with a `name`, the
+//! `size` of the box it's drawn in, its `shape` as `size` rows of `.` for an
+//! empty cell and `#` for a filled one, and a [`Color`]. A piece may also give
+//! a `spawn_offset` in columns and the `kicks` its rotations try, as `[x, y]`
+//! pairs; without them it spawns in the middle and only turns where it is.
+//!
+//! ```toml
+//! name = "Dominoes"
+//!
+//! [[pieces]]
+//! name = "D"
+//! size = 2
+//! color = "Grey"
+//! shape = """
+//! #.
+//! #.
+//! """
+//! ```
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use super::{Color, PieceDef, MAX_PIECE_SIZE, STANDARD_PIECES};
+
+/// Where piece set files are looked for, among the
+/// [assets](crate::graphics::assets)
+pub const PIECE_SET_DIR: &str = "pieces";
+
+/// Pieces a run deals from
+#[derive(Clone, Copy, Debug)]
+pub struct PieceSet {
+    pub name: &'static str,
+    pub pieces: &'static [PieceDef],
+}
+
+/// The seven tetrominoes
+pub static STANDARD: PieceSet = PieceSet {
+    name: "Tetrominoes",
+    pieces: &STANDARD_PIECES,
+};
+
+#[derive(Debug)]
+pub enum PieceSetError {
+    /// The file isn't valid TOML, or is missing a field
+    Syntax(toml::de::Error),
+    /// There are no pieces in the set
+    NoPieces,
+    /// Piece `piece` has a box too small or too big to play
+    WrongSize { piece: String, size: u8 },
+    /// The shape of piece `piece` doesn't fill its box
+    WrongCells {
+        piece: String,
+        expected: usize,
+        found: usize,
+    },
+    /// The shape of piece `piece` has something other than `.` or `#` in it
+    UnknownCell { piece: String, found: char },
+    /// Piece `piece` has no filled cells
+    EmptyShape { piece: String },
+}
+
+impl fmt::Display for PieceSetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax(e) => write!(f, "{}", e),
+            Self::NoPieces => write!(f, "the set has no pieces"),
+            Self::WrongSize { piece, size } => write!(
+                f,
+                "piece {}: size {} isn't between 1 and {}",
+                piece, size, MAX_PIECE_SIZE
+            ),
+            Self::WrongCells {
+                piece,
+                expected,
+                found,
+            } => write!(
+                f,
+                "piece {}: expected {} cells, found {}",
+                piece, expected, found
+            ),
+            Self::UnknownCell { piece, found } => write!(
+                f,
+                "piece {}: unknown cell {:?}, expected '.' or '#'",
+                piece, found
+            ),
+            Self::EmptyShape { piece } => write!(f, "piece {}: no filled cells", piece),
+        }
+    }
+}
+
+impl std::error::Error for PieceSetError {}
+
+/// A set file as written
+#[derive(Deserialize)]
+struct RawSet {
+    name: String,
+    pieces: Vec<RawPiece>,
+}
+
+#[derive(Deserialize)]
+struct RawPiece {
+    name: String,
+    size: u8,
+    shape: String,
+    color: Color,
+    #[serde(default)]
+    spawn_offset: i8,
+    #[serde(default)]
+    kicks: Vec<(i8, i8)>,
+}
+
+impl PieceSet {
+    /// Whether these are the seven tetrominoes
+    pub fn is_standard(&self) -> bool {
+        std::ptr::eq(self.pieces, STANDARD.pieces)
+    }
+
+    /// Reads a set from the text of a set file. What it loads lives for the
+    /// rest of the program, like the built in pieces, so sets should only be
+    /// loaded when the player picks one
+    pub fn parse(text: &str) -> Result<Self, PieceSetError> {
+        let raw: RawSet = toml::from_str(text).map_err(PieceSetError::Syntax)?;
+        if raw.pieces.is_empty() {
+            return Err(PieceSetError::NoPieces);
+        }
+
+        let mut pieces = Vec::with_capacity(raw.pieces.len());
+        for piece in raw.pieces {
+            if piece.size == 0 || piece.size > MAX_PIECE_SIZE {
+                return Err(PieceSetError::WrongSize {
+                    piece: piece.name,
+                    size: piece.size,
+                });
+            }
+            // rows may be laid out however reads best, only the cells count
+            let shape: String = piece.shape.split_whitespace().collect();
+            if let Some(found) = shape.chars().find(|&c| c != '.' && c != '#') {
+                return Err(PieceSetError::UnknownCell {
+                    piece: piece.name,
+                    found,
+                });
+            }
+            let expected = piece.size as usize * piece.size as usize;
+            if shape.len() != expected {
+                return Err(PieceSetError::WrongCells {
+                    piece: piece.name,
+                    expected,
+                    found: shape.len(),
+                });
+            }
+            if !shape.contains('#') {
+                return Err(PieceSetError::EmptyShape { piece: piece.name });
+            }
+
+            let kicks = if piece.kicks.is_empty() {
+                vec![(0, 0)]
+            } else {
+                piece.kicks
+            };
+            pieces.push(PieceDef {
+                name: Box::leak(piece.name.into_boxed_str()),
+                size: piece.size,
+                shape: Box::leak(shape.into_boxed_str()),
+                color: piece.color,
+                spawn_offset: piece.spawn_offset,
+                kicks: Box::leak(kicks.into_boxed_slice()),
+            });
+        }
+
+        Ok(Self {
+            name: Box::leak(raw.name.into_boxed_str()),
+            pieces: Box::leak(pieces.into_boxed_slice()),
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(Self::parse(&text)?)
+    }
+}
+
+impl Default for PieceSet {
+    fn default() -> Self {
+        STANDARD
+    }
+}
+
+/// Piece set files in `dir`, sorted by name
+pub fn list(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension().map_or(false, |ext| ext == "toml"))
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths
+}
@@ -0,0 +1,326 @@
+//! Players sharing the game, kept in `tetrs_profiles.toml` in the data
+//! directory.
+//!
+//! Every score is tagged with the profile that was playing, and each profile
+//! counts its own lifetime statistics. There's always at least one profile,
+//! and one of them is the one playing. The file is versioned like the config
+//! is, see [`crate::config`].
+
+use std::error::Error;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::paths;
+use crate::scores::{DEFAULT_NAME, MAX_NAME_CHARS};
+
+/// Name of the profiles file in the [data directory](paths::data_dir)
+pub const PROFILES_FILE: &str = "tetrs_profiles.toml";
+
+/// Version of the profiles file written by this build
+pub const PROFILES_VERSION: u32 = 1;
+
+/// Most profiles there can be, as many as fit on the profiles screen
+pub const MAX_PROFILES: usize = 8;
+
+/// A player and how much they've played
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+
+    /// Runs played to the end
+    pub games: u64,
+
+    /// Lines cleared over every run
+    pub lines: u64,
+
+    /// Fastest sprint, in milliseconds, if one's been finished
+    pub best_sprint_millis: Option<u64>,
+}
+
+impl Profile {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Counts a finished run that cleared `lines`, and took `sprint_millis` if
+    /// it was a finished sprint
+    pub fn record_run(&mut self, lines: u32, sprint_millis: Option<u64>) {
+        self.games = self.games.saturating_add(1);
+        self.lines = self.lines.saturating_add(lines as u64);
+        if let Some(millis) = sprint_millis {
+            self.best_sprint_millis = Some(
+                self.best_sprint_millis
+                    .map_or(millis, |best| best.min(millis)),
+            );
+        }
+    }
+}
+
+/// Every profile, and which one is playing
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profiles {
+    /// Version of the file the profiles were read from
+    pub version: u32,
+
+    /// Name of the profile playing
+    active: String,
+
+    profiles: Vec<Profile>,
+}
+
+impl Default for Profiles {
+    fn default() -> Self {
+        Self {
+            version: PROFILES_VERSION,
+            active: DEFAULT_NAME.to_string(),
+            profiles: vec![Profile::new(DEFAULT_NAME)],
+        }
+    }
+}
+
+impl Profiles {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut profiles: Self = toml::from_str(&std::fs::read_to_string(path)?)?;
+        profiles.version = PROFILES_VERSION;
+        // a file edited down to nothing still leaves someone to play as
+        if profiles.profiles.is_empty() {
+            profiles.profiles.push(Profile::new(DEFAULT_NAME));
+        }
+        if profiles.index_of(&profiles.active).is_none() {
+            profiles.active = profiles.profiles[0].name.clone();
+        }
+        Ok(profiles)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        paths::create_parent(path)?;
+        std::fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// The profiles in the data directory, or just the default one if there
+    /// aren't any. A file that can't be read is moved aside to `<file>.bad`
+    /// so saving doesn't lose it
+    pub fn load_or_default() -> Self {
+        let path = paths::data_file(PROFILES_FILE);
+        if !path.exists() {
+            return Self::default();
+        }
+
+        Self::load(&path).unwrap_or_else(|e| {
+            let mut aside = path.as_os_str().to_owned();
+            aside.push(".bad");
+            eprintln!("Couldn't read profiles, moving them aside: {}", e);
+            if let Err(e) = std::fs::rename(&path, aside) {
+                eprintln!("Couldn't move profiles aside: {}", e);
+            }
+            Self::default()
+        })
+    }
+
+    /// Writes the profiles to the data directory
+    pub fn store(&self) -> Result<(), Box<dyn Error>> {
+        self.save(&paths::data_file(PROFILES_FILE))
+    }
+
+    pub fn all(&self) -> &[Profile] {
+        &self.profiles
+    }
+
+    /// The profile playing
+    pub fn active(&self) -> &Profile {
+        let index = self.index_of(&self.active).unwrap_or(0);
+        &self.profiles[index]
+    }
+
+    pub fn active_mut(&mut self) -> &mut Profile {
+        let index = self.index_of(&self.active).unwrap_or(0);
+        &mut self.profiles[index]
+    }
+
+    /// Makes the profile called `name` the one playing
+    pub fn select(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        self.find(name)?;
+        self.active = name.to_string();
+        Ok(())
+    }
+
+    /// Adds a profile called `name`, without making it the one playing
+    pub fn create(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        if self.profiles.len() >= MAX_PROFILES {
+            return Err(format!("there can only be {} profiles", MAX_PROFILES).into());
+        }
+        self.check_name(name)?;
+        self.profiles.push(Profile::new(name));
+        Ok(())
+    }
+
+    /// Calls the profile `old` `new` instead, keeping its statistics
+    pub fn rename(&mut self, old: &str, new: &str) -> Result<(), Box<dyn Error>> {
+        let index = self.find(old)?;
+        if old != new {
+            self.check_name(new)?;
+        }
+        self.profiles[index].name = new.to_string();
+        if self.active == old {
+            self.active = new.to_string();
+        }
+        Ok(())
+    }
+
+    /// Removes the profile called `name`. The last one can't go, and if the
+    /// one playing does, the first one left takes over
+    pub fn delete(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        let index = self.find(name)?;
+        if self.profiles.len() == 1 {
+            return Err("the last profile can't be deleted".into());
+        }
+        self.profiles.remove(index);
+        if self.active == name {
+            self.active = self.profiles[0].name.clone();
+        }
+        Ok(())
+    }
+
+    fn index_of(&self, name: &str) -> Option<usize> {
+        self.profiles.iter().position(|p| p.name == name)
+    }
+
+    fn find(&self, name: &str) -> Result<usize, Box<dyn Error>> {
+        self.index_of(name)
+            .ok_or_else(|| format!("no profile called {}", name).into())
+    }
+
+    /// Whether `name` can be given to a profile: not blank, short enough for
+    /// the leaderboard and not taken
+    fn check_name(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        if name.trim().is_empty() {
+            Err("profiles need a name".into())
+        } else if name.chars().count() > MAX_NAME_CHARS {
+            Err(format!("names are at most {} characters", MAX_NAME_CHARS).into())
+        } else if self.index_of(name).is_some() {
+            Err(format!("there's a profile called {} already", name).into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(profiles: &Profiles) -> Vec<&str> {
+        profiles.all().iter().map(|p| p.name.as_str()).collect()
+    }
+
+    #[test]
+    fn there_is_always_someone_playing() {
+        let profiles = Profiles::default();
+        assert_eq!(names(&profiles), vec![DEFAULT_NAME]);
+        assert_eq!(profiles.active().name, DEFAULT_NAME);
+    }
+
+    #[test]
+    fn profiles_are_created_and_deleted() {
+        let mut profiles = Profiles::default();
+        profiles.create("ANA").unwrap();
+        profiles.create("BEN").unwrap();
+        assert_eq!(names(&profiles), vec![DEFAULT_NAME, "ANA", "BEN"]);
+        // creating one doesn't switch to it
+        assert_eq!(profiles.active().name, DEFAULT_NAME);
+
+        profiles.select("BEN").unwrap();
+        profiles.delete("ANA").unwrap();
+        assert_eq!(names(&profiles), vec![DEFAULT_NAME, "BEN"]);
+        assert_eq!(profiles.active().name, "BEN");
+
+        // deleting the one playing hands over to the first left
+        profiles.delete("BEN").unwrap();
+        assert_eq!(profiles.active().name, DEFAULT_NAME);
+        assert!(profiles.delete(DEFAULT_NAME).is_err());
+        assert!(profiles.delete("BEN").is_err());
+        assert!(profiles.select("BEN").is_err());
+        assert_eq!(names(&profiles), vec![DEFAULT_NAME]);
+    }
+
+    #[test]
+    fn bad_names_and_too_many_profiles_are_turned_away() {
+        let mut profiles = Profiles::default();
+        assert!(profiles.create("").is_err());
+        assert!(profiles.create("   ").is_err());
+        assert!(profiles.create(DEFAULT_NAME).is_err());
+        assert!(profiles.create(&"X".repeat(MAX_NAME_CHARS + 1)).is_err());
+        profiles.create(&"Ü".repeat(MAX_NAME_CHARS)).unwrap();
+
+        for i in profiles.all().len()..MAX_PROFILES {
+            profiles.create(&format!("P{}", i)).unwrap();
+        }
+        assert!(profiles.create("ONE MORE").is_err());
+        assert_eq!(profiles.all().len(), MAX_PROFILES);
+    }
+
+    #[test]
+    fn renaming_keeps_the_statistics_and_who_is_playing() {
+        let mut profiles = Profiles::default();
+        profiles.create("ANA").unwrap();
+        profiles.active_mut().record_run(12, None);
+        profiles.rename(DEFAULT_NAME, "CAL").unwrap();
+        assert_eq!(names(&profiles), vec!["CAL", "ANA"]);
+        assert_eq!(profiles.active().name, "CAL");
+        assert_eq!(profiles.active().lines, 12);
+
+        assert!(profiles.rename("CAL", "ANA").is_err());
+        assert!(profiles.rename("NOBODY", "DAN").is_err());
+        // keeping the same name is no clash with itself
+        profiles.rename("CAL", "CAL").unwrap();
+    }
+
+    #[test]
+    fn statistics_add_up_over_runs() {
+        let mut profiles = Profiles::default();
+        profiles.create("ANA").unwrap();
+        // an endless run, a slow sprint, a topped out sprint and a quick one
+        profiles.active_mut().record_run(57, None);
+        profiles.active_mut().record_run(40, Some(95_000));
+        profiles.active_mut().record_run(22, None);
+        profiles.active_mut().record_run(41, Some(71_500));
+        profiles.active_mut().record_run(40, Some(80_000));
+
+        let played = profiles.active();
+        assert_eq!(played.games, 5);
+        assert_eq!(played.lines, 57 + 40 + 22 + 41 + 40);
+        assert_eq!(played.best_sprint_millis, Some(71_500));
+        // the other profile played none of them
+        assert_eq!(profiles.all()[1], Profile::new("ANA"));
+
+        let mut maxed = Profile::new("MAX");
+        maxed.lines = u64::MAX;
+        maxed.record_run(10, None);
+        assert_eq!(maxed.lines, u64::MAX);
+    }
+
+    #[test]
+    fn profiles_read_back_the_same() {
+        let path = std::env::temp_dir().join(format!("tetrs_profiles_{}.toml", std::process::id()));
+        let mut profiles = Profiles::default();
+        profiles.create("ANA").unwrap();
+        profiles.select("ANA").unwrap();
+        profiles.active_mut().record_run(40, Some(71_500));
+        profiles.save(&path).unwrap();
+        assert_eq!(Profiles::load(&path).unwrap(), profiles);
+
+        // someone playing who isn't there, or nobody at all, still loads
+        std::fs::write(&path, "active = \"GONE\"\n").unwrap();
+        let loaded = Profiles::load(&path).unwrap();
+        assert_eq!(names(&loaded), vec![DEFAULT_NAME]);
+        assert_eq!(loaded.active().name, DEFAULT_NAME);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,749 @@
+//! Leaderboards, kept in a file per mode in the data directory.
+//!
+//! Files start with an 8 byte header naming their version. Version 1
+//! (`tet.rs 1`) holds a name and a value per entry. Version 2 (`tet.rs 2`)
+//! adds when the entry was set, the lines and level the run reached and the
+//! mode it was played in. Version 3 (`tet.rs 3`) adds a flags byte to each
+//! entry and ends in a CRC-32 of everything before it. Version 4 (`tet.rs 4`)
+//! adds the [profile](crate::profiles) that set each entry. Version 5
+//! (`tet.rs 5`) adds the [splits](ScoreEntry::splits) of each entry. All five
+//! are read, but only version 5 is written, so an old file is upgraded the
+//! first time a score is added to it.
+//!
+//! The checksum only catches the file being edited by hand, since anyone set
+//! on it can work out a new one. Entries in a file whose checksum doesn't
+//! match are still shown, but marked [modified](ScoreEntry::modified) from
+//! then on.
+//!
+//! A file that can't be read all the way through is never just overwritten.
+//! It's moved aside to a backup named after it and the time, and whatever
+//! entries came before the damage are written back in its place.
+
+use std::error::Error;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::mode::GameMode;
+use crate::paths;
+use crate::sim::save::{invalid, Reader, Writer};
+
+pub mod export;
+
+/// Entries kept for each mode
+pub const MAX_ENTRIES: usize = 10;
+
+/// Name an entry goes by if none is typed in for it
+pub const DEFAULT_NAME: &str = "PLR";
+
+/// Longest name that can be typed in for an entry, which is as much as the
+/// scores screen has room for
+pub const MAX_NAME_CHARS: usize = 10;
+
+const HEADER_V1: &[u8] = b"tet.rs 1";
+const HEADER_V2: &[u8] = b"tet.rs 2";
+const HEADER_V3: &[u8] = b"tet.rs 3";
+const HEADER_V4: &[u8] = b"tet.rs 4";
+const HEADER_V5: &[u8] = b"tet.rs 5";
+
+/// Entry flag set once it's been found in a file that was changed by hand
+const FLAG_MODIFIED: u8 = 1;
+
+/// One line of a leaderboard
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScoreEntry {
+    pub name: String,
+    /// Score or time, see [`GameMode::format_value`]
+    pub value: u64,
+    /// Seconds since the UNIX epoch when it was set, or 0 if that's unknown
+    pub timestamp: u64,
+    /// How far the run got, 0 if unknown
+    pub lines: u32,
+    pub level: u32,
+    pub mode: GameMode,
+
+    /// Whether the entry was in a file changed outside the game, or came from
+    /// an import. It's shown, but shouldn't be sent anywhere as a real score
+    pub modified: bool,
+
+    /// Name of the profile that set it, or empty for entries from before
+    /// there were profiles
+    pub profile: String,
+
+    /// Milliseconds it took to clear every
+    /// [`SPLIT_LINES`](crate::game::stats::SPLIT_LINES) lines, for sprints.
+    /// Empty for other modes and entries from before splits were kept
+    pub splits: Vec<u64>,
+}
+
+impl ScoreEntry {
+    /// An entry set just now
+    pub fn new(name: &str, value: u64, mode: GameMode, lines: u32, level: u32) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        Self {
+            name: name.to_string(),
+            value,
+            timestamp,
+            lines,
+            level,
+            mode,
+            modified: false,
+            profile: String::new(),
+            splits: Vec::new(),
+        }
+    }
+
+    /// The same entry, set by `profile`
+    pub fn by(self, profile: &str) -> Self {
+        Self {
+            profile: profile.to_string(),
+            ..self
+        }
+    }
+
+    /// The same entry, with its run's `splits`
+    pub fn with_splits(self, splits: Vec<u64>) -> Self {
+        Self { splits, ..self }
+    }
+
+    /// The day the entry was set, in UTC, as `yy-mm-dd`. `None` for entries
+    /// from before dates were kept
+    pub fn date(&self) -> Option<String> {
+        if self.timestamp == 0 {
+            return None;
+        }
+        let (year, month, day) = civil_date(self.timestamp / 86_400);
+        Some(format!("{:02}-{:02}-{:02}", year % 100, month, day))
+    }
+}
+
+/// Year, month and day of the `days`th day since 1970-01-01, by the proleptic
+/// Gregorian calendar
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    // counted in 400 year eras starting on the 1st of March, so the leap day
+    // falls at the end of each year
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as u64;
+    (year, month, day)
+}
+
+/// Sorts entries so that the best one for `mode` comes first
+pub fn sort(entries: &mut [ScoreEntry], mode: GameMode) {
+    if mode.ranks_by_time() {
+        entries.sort_by(|a, b| a.value.cmp(&b.value));
+    } else {
+        entries.sort_by(|a, b| b.value.cmp(&a.value));
+    }
+}
+
+/// A leaderboard as read from its file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Loaded {
+    /// Best first within each mode it holds
+    pub entries: Vec<ScoreEntry>,
+
+    /// Set if the file was damaged
+    pub damage: Option<Damage>,
+}
+
+/// What became of a damaged leaderboard file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Damage {
+    /// Where the file as it was found was moved to
+    pub backup: PathBuf,
+
+    /// Entries read before the damage, which replace the file
+    pub recovered: usize,
+
+    /// What was wrong with it
+    pub error: String,
+}
+
+/// Reads the leaderboard for `mode`. A damaged file is backed up and replaced
+/// by the entries that could still be read, see [`Damage`]
+pub fn load(mode: GameMode) -> Result<Loaded, Box<dyn Error>> {
+    load_file(&paths::data_file(mode.scores_file()), mode)
+}
+
+/// Reads the leaderboard at `path`, as [`load`] does
+fn load_file(path: &Path, mode: GameMode) -> Result<Loaded, Box<dyn Error>> {
+    let contents = std::fs::read(path)?;
+    let mut entries = Vec::new();
+    let damage = match read_entries(&contents, mode, &mut entries) {
+        Ok(()) => None,
+        Err(e) => {
+            // moved before anything's written, so the original is kept even if
+            // writing what was recovered goes wrong
+            let backup = back_up(path)?;
+            write(path, &encode(&best(&entries)))?;
+            Some(Damage {
+                backup,
+                recovered: entries.len(),
+                error: e.to_string(),
+            })
+        }
+    };
+    Ok(Loaded {
+        entries: best(&entries),
+        damage,
+    })
+}
+
+/// Moves `path` aside to `<path>.bak-<seconds since the epoch>`, returning
+/// where it went
+fn back_up(path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let mut name = path.file_name().ok_or_else(invalid)?.to_os_string();
+    name.push(format!(".bak-{}", now));
+    let backup = path.with_file_name(name);
+    std::fs::rename(path, &backup)?;
+    Ok(backup)
+}
+
+/// Writes the leaderboard for `mode`, keeping the best [`MAX_ENTRIES`] of
+/// each mode in `entries`
+pub fn save(mode: GameMode, entries: &[ScoreEntry]) -> Result<(), Box<dyn Error>> {
+    let path = paths::data_file(mode.scores_file());
+    write(&path, &encode(&best(entries)))
+}
+
+/// Empties every leaderboard. Each is left as a file with no entries rather
+/// than deleted, so reading it afterwards finds nothing instead of failing
+pub fn reset() -> Result<(), Box<dyn Error>> {
+    for &mode in GameMode::RANKED {
+        save(mode, &[])?;
+    }
+    Ok(())
+}
+
+fn write(path: &Path, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+    paths::create_parent(path)?;
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Tags every entry set by the profile `old` as set by `new` instead, in every
+/// leaderboard
+pub fn rename_profile(old: &str, new: &str) -> Result<(), Box<dyn Error>> {
+    for &mode in GameMode::RANKED {
+        let mut entries = match load(mode) {
+            Ok(loaded) => loaded.entries,
+            // modes nobody has played have nothing to rename
+            Err(e) if is_missing(&*e) => continue,
+            Err(e) => return Err(e),
+        };
+        let mut renamed = false;
+        for entry in entries.iter_mut().filter(|entry| entry.profile == old) {
+            entry.profile = new.to_string();
+            renamed = true;
+        }
+        if renamed {
+            save(mode, &entries)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether `e` is down to a file not being there
+pub(crate) fn is_missing(e: &(dyn Error + 'static)) -> bool {
+    matches!(e.downcast_ref::<io::Error>(), Some(e) if e.kind() == io::ErrorKind::NotFound)
+}
+
+/// Adds `entry` to `entries`, dropping whatever no longer makes the cut
+pub fn add(entries: &mut Vec<ScoreEntry>, entry: ScoreEntry) {
+    entries.push(entry);
+    *entries = best(entries);
+}
+
+/// Whether `entry` would be kept if it was added to `entries`
+pub fn makes_cut(entries: &[ScoreEntry], entry: &ScoreEntry) -> bool {
+    let mut with = entries.to_vec();
+    add(&mut with, entry.clone());
+    entry.value > 0 && with.contains(entry)
+}
+
+/// The best [`MAX_ENTRIES`] of each mode in `entries`, in the order the modes
+/// first turn up
+fn best(entries: &[ScoreEntry]) -> Vec<ScoreEntry> {
+    let mut modes: Vec<GameMode> = Vec::new();
+    for entry in entries {
+        if !modes.contains(&entry.mode) {
+            modes.push(entry.mode);
+        }
+    }
+
+    let mut kept = Vec::new();
+    for mode in modes {
+        let mut of_mode: Vec<ScoreEntry> = entries
+            .iter()
+            .filter(|entry| entry.mode == mode)
+            .cloned()
+            .collect();
+        sort(&mut of_mode, mode);
+        of_mode.truncate(MAX_ENTRIES);
+        kept.extend(of_mode);
+    }
+    kept
+}
+
+/// `entries` in the version 5 format
+pub fn encode(entries: &[ScoreEntry]) -> Vec<u8> {
+    let mut w = Writer(Vec::new());
+    w.0.extend_from_slice(HEADER_V5);
+    let count = entries.len().min(u8::MAX as usize);
+    w.u8(count as u8);
+    for entry in &entries[..count] {
+        write_text(&mut w, &entry.name);
+        w.u64(entry.value);
+        w.u64(entry.timestamp);
+        w.u32(entry.lines);
+        w.u32(entry.level);
+        let mode = GameMode::ALL.iter().position(|&m| m == entry.mode).unwrap();
+        w.u8(mode as u8);
+        w.u8(if entry.modified { FLAG_MODIFIED } else { 0 });
+        write_text(&mut w, &entry.profile);
+        let splits = &entry.splits[..entry.splits.len().min(u8::MAX as usize)];
+        w.u8(splits.len() as u8);
+        for &millis in splits {
+            w.u64(millis);
+        }
+    }
+    let checksum = crc32(&w.0);
+    w.u32(checksum);
+    w.0
+}
+
+/// Writes `text` after its length in a byte
+fn write_text(w: &mut Writer, text: &str) {
+    // cut short at a byte, which may leave invalid UTF-8 that's replaced on
+    // the way back in
+    let bytes = &text.as_bytes()[..text.len().min(u8::MAX as usize)];
+    w.u8(bytes.len() as u8);
+    w.0.extend_from_slice(bytes);
+}
+
+fn read_text(r: &mut Reader) -> Result<String, Box<dyn Error>> {
+    let length = r.u8()? as usize;
+    Ok(String::from_utf8_lossy(r.take(length)?).into_owned())
+}
+
+/// CRC-32 of `bytes`, as zlib and PNG work it out
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Entries from a file of any version. Version 1 files don't say which mode
+/// they're for, so their entries are taken to be `mode`'s
+pub fn decode(contents: &[u8], mode: GameMode) -> Result<Vec<ScoreEntry>, Box<dyn Error>> {
+    let mut entries = Vec::new();
+    read_entries(contents, mode, &mut entries)?;
+    Ok(entries)
+}
+
+/// Adds the entries in `contents` to `entries` one at a time, so the ones
+/// before any damage are kept when it fails
+fn read_entries(
+    contents: &[u8],
+    mode: GameMode,
+    entries: &mut Vec<ScoreEntry>,
+) -> Result<(), Box<dyn Error>> {
+    let mut r = Reader(contents);
+    let version = match r.take(8)? {
+        HEADER_V1 => 1,
+        HEADER_V2 => 2,
+        HEADER_V3 => 3,
+        HEADER_V4 => 4,
+        HEADER_V5 => 5,
+        _ => return Err(invalid()),
+    };
+
+    // entries read from a file that doesn't match its checksum, even a
+    // truncated one, can't be told apart from ones made up
+    let mut verified = true;
+    if version >= 3 {
+        if contents.len() < 12 {
+            return Err(invalid());
+        }
+        let (body, checksum) = contents.split_at(contents.len() - 4);
+        verified = Reader(checksum).u32()? == crc32(body);
+        r = Reader(&body[8..]);
+    }
+
+    let count = r.u8()?;
+    for _ in 0..count {
+        let name = read_text(&mut r)?;
+        let value = r.u64()?;
+        let entry = if version == 1 {
+            ScoreEntry {
+                name,
+                value,
+                timestamp: 0,
+                lines: 0,
+                level: 0,
+                mode,
+                modified: false,
+                profile: String::new(),
+                splits: Vec::new(),
+            }
+        } else {
+            ScoreEntry {
+                name,
+                value,
+                timestamp: r.u64()?,
+                lines: r.u32()?,
+                level: r.u32()?,
+                mode: *GameMode::ALL.get(r.u8()? as usize).ok_or_else(invalid)?,
+                modified: (version >= 3 && r.u8()? & FLAG_MODIFIED != 0) || !verified,
+                profile: if version >= 4 {
+                    read_text(&mut r)?
+                } else {
+                    String::new()
+                },
+                splits: if version >= 5 {
+                    let count = r.u8()?;
+                    (0..count).map(|_| r.u64()).collect::<Result<_, _>>()?
+                } else {
+                    Vec::new()
+                },
+            }
+        };
+        entries.push(entry);
+    }
+
+    // anything left over means the file isn't what it claims to be
+    if !r.0.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An entry with every field set, as a version 5 file holds it
+    fn entry(name: &str, value: u64, mode: GameMode) -> ScoreEntry {
+        ScoreEntry {
+            name: name.to_string(),
+            value,
+            timestamp: 1_700_000_000 + value,
+            lines: 40,
+            level: 5,
+            mode,
+            modified: false,
+            profile: "P1".to_string(),
+            splits: vec![12_000, 25_000],
+        }
+    }
+
+    /// A version 1 file holding each name and value in `entries`
+    fn v1_file(entries: &[(&str, u64)]) -> Vec<u8> {
+        let mut w = Writer(HEADER_V1.to_vec());
+        w.u8(entries.len() as u8);
+        for &(name, value) in entries {
+            write_text(&mut w, name);
+            w.u64(value);
+        }
+        w.0
+    }
+
+    /// A version 2 file holding `entries`, which has no room for their flags,
+    /// profiles or splits
+    fn v2_file(entries: &[ScoreEntry]) -> Vec<u8> {
+        let mut w = Writer(HEADER_V2.to_vec());
+        w.u8(entries.len() as u8);
+        for entry in entries {
+            write_text(&mut w, &entry.name);
+            w.u64(entry.value);
+            w.u64(entry.timestamp);
+            w.u32(entry.lines);
+            w.u32(entry.level);
+            let mode = GameMode::ALL.iter().position(|&m| m == entry.mode).unwrap();
+            w.u8(mode as u8);
+        }
+        w.0
+    }
+
+    /// `entries` as they read back from a [`v2_file`]
+    fn as_v2(entries: &[ScoreEntry]) -> Vec<ScoreEntry> {
+        entries
+            .iter()
+            .cloned()
+            .map(|e| ScoreEntry {
+                profile: String::new(),
+                splits: Vec::new(),
+                ..e
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_version_1_file_reads_with_unknown_metadata() {
+        let file = v1_file(&[("AAA", 3000), ("BBB", 1000)]);
+        let entries = decode(&file, GameMode::Ultra).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].name.as_str(), entries[0].value), ("AAA", 3000));
+        assert_eq!((entries[1].name.as_str(), entries[1].value), ("BBB", 1000));
+        for entry in &entries {
+            assert_eq!(entry.mode, GameMode::Ultra);
+            assert_eq!((entry.timestamp, entry.lines, entry.level), (0, 0, 0));
+            assert_eq!(entry.date(), None);
+            assert!(!entry.modified);
+            assert!(entry.profile.is_empty() && entry.splits.is_empty());
+        }
+    }
+
+    #[test]
+    fn a_version_2_file_reads_every_field_it_has() {
+        let written = vec![
+            entry("AAA", 3000, GameMode::Marathon),
+            entry("BBB", 41_000, GameMode::Sprint),
+        ];
+        let entries = decode(&v2_file(&written), GameMode::Endless).unwrap();
+        assert_eq!(entries, as_v2(&written));
+    }
+
+    #[test]
+    fn what_is_written_reads_back_the_same() {
+        let mut modified = entry("CCC", 500, GameMode::Big);
+        modified.modified = true;
+        let entries = vec![
+            entry("AAA", 3000, GameMode::Marathon),
+            entry("Ünï, \"cödé\"", 41_000, GameMode::Sprint),
+            modified,
+            ScoreEntry::new("DDD", 10, GameMode::Cheese { rows: 9 }, 9, 1),
+        ];
+        let file = encode(&entries);
+        assert_eq!(&file[..8], HEADER_V5);
+        assert_eq!(decode(&file, GameMode::Endless).unwrap(), entries);
+        assert_eq!(decode(&encode(&[]), GameMode::Endless).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn a_version_1_file_is_upgraded_when_saved_again() {
+        let file = v1_file(&[("OLD", 2000)]);
+        let mut entries = decode(&file, GameMode::Endless).unwrap();
+        add(&mut entries, entry("NEW", 3000, GameMode::Endless));
+
+        let upgraded = encode(&entries);
+        assert_eq!(&upgraded[..8], HEADER_V5);
+        let reloaded = decode(&upgraded, GameMode::Sprint).unwrap();
+        assert_eq!(reloaded, entries);
+        assert_eq!(reloaded[0], entry("NEW", 3000, GameMode::Endless));
+        // what the old file didn't know stays unknown, and the mode it was
+        // taken to be for is kept rather than read back as the new default
+        assert_eq!(reloaded[1].name, "OLD");
+        assert_eq!(reloaded[1].mode, GameMode::Endless);
+        assert_eq!(reloaded[1].timestamp, 0);
+    }
+
+    #[test]
+    fn the_best_ten_of_each_mode_are_kept() {
+        let mut entries = Vec::new();
+        for value in 1..=12 {
+            entries.push(entry("END", value * 100, GameMode::Endless));
+            entries.push(entry("SPR", value * 1000, GameMode::Sprint));
+        }
+        let kept = best(&entries);
+        assert_eq!(kept.len(), 2 * MAX_ENTRIES);
+
+        let values = |mode| -> Vec<u64> {
+            kept.iter()
+                .filter(|e| e.mode == mode)
+                .map(|e| e.value)
+                .collect()
+        };
+        // highest scores first, and quickest times
+        let endless: Vec<u64> = (3..=12).rev().map(|v| v * 100).collect();
+        let sprint: Vec<u64> = (1..=10).map(|v| v * 1000).collect();
+        assert_eq!(values(GameMode::Endless), endless);
+        assert_eq!(values(GameMode::Sprint), sprint);
+    }
+
+    #[test]
+    fn the_date_is_the_utc_day_an_entry_was_set() {
+        let on = |timestamp| ScoreEntry {
+            timestamp,
+            ..entry("AAA", 0, GameMode::Endless)
+        };
+        assert_eq!(on(0).date(), None);
+        assert_eq!(on(1).date().unwrap(), "70-01-01");
+        // a leap day, and the last second of the year
+        assert_eq!(on(951_782_400).date().unwrap(), "00-02-29");
+        assert_eq!(on(1_704_067_199).date().unwrap(), "23-12-31");
+        assert_eq!(on(1_704_067_200).date().unwrap(), "24-01-01");
+    }
+
+    /// A fresh directory for a test called `name` to keep a leaderboard in
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tetrs-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Writes `contents` as a leaderboard in a fresh directory and loads it,
+    /// giving back what was loaded and the directory
+    fn load_damaged(name: &str, contents: &[u8]) -> (Loaded, PathBuf) {
+        let dir = temp_dir(name);
+        let path = dir.join("scores.bin");
+        std::fs::write(&path, contents).unwrap();
+        (load_file(&path, GameMode::Sprint).unwrap(), dir)
+    }
+
+    /// Checks `loaded` recovered `entries`, that the file as it was is kept
+    /// in a backup, and that what was recovered is written in its place
+    fn assert_recovered(loaded: &Loaded, dir: &Path, contents: &[u8], entries: &[ScoreEntry]) {
+        let damage = loaded.damage.as_ref().expect("no damage found");
+        assert_eq!(loaded.entries, entries);
+        assert_eq!(damage.recovered, entries.len());
+
+        let backup = damage.backup.file_name().unwrap().to_str().unwrap();
+        assert!(backup.starts_with("scores.bin.bak-"), "{}", backup);
+        assert_eq!(damage.backup.parent(), Some(dir));
+        assert_eq!(std::fs::read(&damage.backup).unwrap(), contents);
+
+        let rewritten = load_file(&dir.join("scores.bin"), GameMode::Sprint).unwrap();
+        assert_eq!(rewritten.entries, entries);
+        assert_eq!(rewritten.damage, None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn sprints() -> Vec<ScoreEntry> {
+        vec![
+            entry("AAA", 41_000, GameMode::Sprint),
+            entry("BBB", 42_000, GameMode::Sprint),
+            entry("CCC", 43_000, GameMode::Sprint),
+        ]
+    }
+
+    #[test]
+    fn a_file_cut_off_mid_entry_keeps_the_entries_before() {
+        let file = v2_file(&sprints());
+        let cut = &file[..file.len() - 5];
+        let (loaded, dir) = load_damaged("cut-off", cut);
+        assert_recovered(&loaded, &dir, cut, &as_v2(&sprints()[..2]));
+    }
+
+    #[test]
+    fn a_file_with_the_wrong_header_is_backed_up_and_emptied() {
+        let mut file = encode(&sprints());
+        file[..8].copy_from_slice(b"tet.rs 9");
+        let (loaded, dir) = load_damaged("wrong-header", &file);
+        assert_recovered(&loaded, &dir, &file, &[]);
+    }
+
+    #[test]
+    fn a_file_with_trailing_garbage_keeps_every_entry() {
+        let mut file = v2_file(&sprints()[..1]);
+        file.extend_from_slice(b"garbage");
+        let (loaded, dir) = load_damaged("trailing", &file);
+        assert_recovered(&loaded, &dir, &file, &as_v2(&sprints()[..1]));
+    }
+
+    #[test]
+    fn entries_recovered_past_a_checksum_are_marked_modified() {
+        let mut file = encode(&sprints());
+        file.extend_from_slice(b"garbage");
+        let (loaded, dir) = load_damaged("checksummed", &file);
+        let kept: Vec<ScoreEntry> = sprints()
+            .into_iter()
+            .map(|e| ScoreEntry {
+                modified: true,
+                ..e
+            })
+            .collect();
+        assert_recovered(&loaded, &dir, &file, &kept);
+    }
+
+    #[test]
+    fn an_intact_file_is_left_alone() {
+        let file = encode(&sprints());
+        let (loaded, dir) = load_damaged("intact", &file);
+        assert_eq!(loaded.damage, None);
+        assert_eq!(loaded.entries, sprints());
+        let files: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(files.len(), 1);
+        assert_eq!(std::fs::read(dir.join("scores.bin")).unwrap(), file);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn the_checksum_is_zlibs_crc_32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn any_flipped_bit_is_caught() {
+        let file = encode(&sprints());
+        for byte in 0..file.len() {
+            for bit in 0..8 {
+                let mut flipped = file.clone();
+                flipped[byte] ^= 1 << bit;
+                // it either can't be read at all, or everything is marked
+                if let Ok(entries) = decode(&flipped, GameMode::Sprint) {
+                    assert!(byte >= 8, "header byte {} read", byte);
+                    assert!(
+                        entries.iter().all(|e| e.modified),
+                        "bit {} of byte {} went unnoticed",
+                        bit,
+                        byte
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn an_edited_value_is_shown_but_marked() {
+        let file = encode(&sprints());
+        // the first entry's value, just after its name
+        let at = 8 + 1 + 1 + "AAA".len();
+        let mut edited = file.clone();
+        edited[at..at + 8].copy_from_slice(&1u64.to_le_bytes());
+        let entries = decode(&edited, GameMode::Sprint).unwrap();
+        assert_eq!(entries[0].value, 1);
+        assert!(entries.iter().all(|e| e.modified));
+
+        // and it stays marked once the file is written again, checksum and all
+        let resaved = decode(&encode(&entries), GameMode::Sprint).unwrap();
+        assert_eq!(resaved, entries);
+    }
+
+    #[test]
+    fn files_from_before_checksums_are_not_marked() {
+        let v1 = decode(&v1_file(&[("AAA", 1)]), GameMode::Sprint).unwrap();
+        assert!(!v1[0].modified);
+        let v2 = decode(&v2_file(&sprints()), GameMode::Sprint).unwrap();
+        assert!(v2.iter().all(|e| !e.modified));
+    }
+}
@@ -0,0 +1,484 @@
+//! Leaderboards as JSON or CSV, to be read by other programs and brought back.
+//!
+//! Both hold the same fields, named alike: `name`, `value`, `timestamp`,
+//! `lines`, `level`, `mode`, `profile` and `splits`, the mode by its
+//! [name](GameMode::name). JSON is an array with an object per entry, and CSV
+//! a header row and then a row per entry, quoted as RFC 4180 has it. Splits are
+//! an array in JSON and separated by spaces in CSV.
+
+use std::borrow::Cow;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::{is_missing, ScoreEntry};
+use crate::mode::GameMode;
+use crate::paths;
+
+/// Fields of an entry, in the order CSV columns are written
+const FIELDS: [&str; 8] = [
+    "name",
+    "value",
+    "timestamp",
+    "lines",
+    "level",
+    "mode",
+    "profile",
+    "splits",
+];
+
+/// Fields exported before there were profiles, and before there were splits,
+/// which are still read
+const FIELDS_WITHOUT_PROFILE: usize = 6;
+const FIELDS_WITHOUT_SPLITS: usize = 7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+impl Format {
+    /// The format called `name`, as given on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+
+    /// The format a file is in, going by its extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        Self::from_name(path.extension()?.to_str()?)
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+        }
+    }
+
+    pub fn write(self, entries: &[ScoreEntry]) -> String {
+        match self {
+            Self::Json => to_json(entries),
+            Self::Csv => to_csv(entries),
+        }
+    }
+
+    pub fn read(self, text: &str) -> Result<Vec<ScoreEntry>, Box<dyn Error>> {
+        match self {
+            Self::Json => from_json(text),
+            Self::Csv => from_csv(text),
+        }
+    }
+}
+
+/// An entry as it's exported, keeping the field names stable whatever happens
+/// to [`ScoreEntry`]
+#[derive(Serialize, Deserialize)]
+struct Record {
+    name: String,
+    value: u64,
+    timestamp: u64,
+    lines: u32,
+    level: u32,
+    mode: String,
+    #[serde(default)]
+    profile: String,
+    #[serde(default)]
+    splits: Vec<u64>,
+}
+
+impl From<&ScoreEntry> for Record {
+    fn from(entry: &ScoreEntry) -> Self {
+        Self {
+            name: entry.name.clone(),
+            value: entry.value,
+            timestamp: entry.timestamp,
+            lines: entry.lines,
+            level: entry.level,
+            mode: entry.mode.name().to_string(),
+            profile: entry.profile.clone(),
+            splits: entry.splits.clone(),
+        }
+    }
+}
+
+impl Record {
+    fn into_entry(self) -> Result<ScoreEntry, Box<dyn Error>> {
+        Ok(ScoreEntry {
+            mode: mode_named(&self.mode)?,
+            name: self.name,
+            value: self.value,
+            timestamp: self.timestamp,
+            lines: self.lines,
+            level: self.level,
+            // nothing vouches for an exported file, which is easily edited
+            modified: true,
+            profile: self.profile,
+            splits: self.splits,
+        })
+    }
+}
+
+/// The mode with a leaderboard called `name`
+fn mode_named(name: &str) -> Result<GameMode, Box<dyn Error>> {
+    GameMode::RANKED
+        .iter()
+        .copied()
+        .find(|mode| mode.name() == name)
+        .ok_or_else(|| format!("no leaderboard for mode {:?}", name).into())
+}
+
+pub fn to_json(entries: &[ScoreEntry]) -> String {
+    let records: Vec<Record> = entries.iter().map(Record::from).collect();
+    serde_json::to_string_pretty(&records).expect("records are plain data")
+}
+
+pub fn from_json(text: &str) -> Result<Vec<ScoreEntry>, Box<dyn Error>> {
+    let records: Vec<Record> = serde_json::from_str(text)?;
+    records.into_iter().map(Record::into_entry).collect()
+}
+
+pub fn to_csv(entries: &[ScoreEntry]) -> String {
+    let mut csv = FIELDS.join(",");
+    csv.push_str("\r\n");
+    for entry in entries {
+        let splits: Vec<String> = entry.splits.iter().map(u64::to_string).collect();
+        let splits = splits.join(" ");
+        let row = [
+            csv_field(&entry.name),
+            entry.value.to_string().into(),
+            entry.timestamp.to_string().into(),
+            entry.lines.to_string().into(),
+            entry.level.to_string().into(),
+            csv_field(entry.mode.name()),
+            csv_field(&entry.profile),
+            splits.into(),
+        ];
+        csv.push_str(&row.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+pub fn from_csv(text: &str) -> Result<Vec<ScoreEntry>, Box<dyn Error>> {
+    let mut rows = csv_rows(text)?.into_iter();
+    let fields = match rows.next() {
+        Some(header)
+            if [FIELDS_WITHOUT_PROFILE, FIELDS_WITHOUT_SPLITS, FIELDS.len()]
+                .iter()
+                .any(|&len| header == &FIELDS[..len]) =>
+        {
+            header.len()
+        }
+        _ => return Err(format!("CSV should start with {}", FIELDS.join(",")).into()),
+    };
+
+    rows.enumerate()
+        .map(|(i, row)| -> Result<ScoreEntry, Box<dyn Error>> {
+            if row.len() != fields {
+                return Err(format!("row {} has {} fields", i + 2, row.len()).into());
+            }
+            Record {
+                name: row[0].clone(),
+                value: row[1].parse()?,
+                timestamp: row[2].parse()?,
+                lines: row[3].parse()?,
+                level: row[4].parse()?,
+                mode: row[5].clone(),
+                profile: row.get(6).cloned().unwrap_or_default(),
+                splits: match row.get(7) {
+                    Some(splits) => splits
+                        .split_whitespace()
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()?,
+                    None => Vec::new(),
+                },
+            }
+            .into_entry()
+        })
+        .collect()
+}
+
+/// `field` as a CSV field, quoted if it holds anything that would end it
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains(&[',', '"', '\r', '\n'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\"")).into()
+    } else {
+        field.into()
+    }
+}
+
+/// Fields of every row of `text`. Quoted fields may hold commas, line breaks
+/// and doubled quotes, and a blank line at the end is no row
+fn csv_rows(text: &str) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                c => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() => quoted = true,
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => (),
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+
+    if quoted {
+        return Err("CSV ends inside a quoted field".into());
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Adds the entries of `incoming` that aren't in `entries` already, going by
+/// name, value and timestamp. Returns how many were added
+pub fn merge(entries: &mut Vec<ScoreEntry>, incoming: Vec<ScoreEntry>) -> usize {
+    let mut added = 0;
+    for entry in incoming {
+        let known = entries.iter().any(|e| {
+            e.name == entry.name && e.value == entry.value && e.timestamp == entry.timestamp
+        });
+        if !known {
+            entries.push(entry);
+            added += 1;
+        }
+    }
+    added
+}
+
+/// Where the scores screen exports to, next to the leaderboards
+pub fn default_path(format: Format) -> PathBuf {
+    paths::data_file(&format!("tetrs_scores.{}", format.extension()))
+}
+
+/// Every leaderboard's entries. Modes nobody has set a score in yet have none
+pub fn load_all() -> Result<Vec<ScoreEntry>, Box<dyn Error>> {
+    let mut entries = Vec::new();
+    for &mode in GameMode::RANKED {
+        match super::load(mode) {
+            Ok(loaded) => entries.extend(loaded.entries.into_iter().filter(|e| e.mode == mode)),
+            Err(e) if is_missing(&*e) => (),
+            Err(e) => return Err(format!("{}: {}", mode.scores_file(), e).into()),
+        }
+    }
+    Ok(entries)
+}
+
+/// Writes every leaderboard to `path` in `format`, returning how many entries
+/// there were
+pub fn export(format: Format, path: &Path) -> Result<usize, Box<dyn Error>> {
+    let entries = load_all()?;
+    paths::create_parent(path)?;
+    std::fs::write(path, format.write(&entries))?;
+    Ok(entries.len())
+}
+
+/// Merges the entries exported to `path` into the leaderboards, going by its
+/// extension for the format. Returns how many weren't there already; whether
+/// they made the cut is up to the usual [`MAX_ENTRIES`](super::MAX_ENTRIES)
+pub fn import(path: &Path) -> Result<usize, Box<dyn Error>> {
+    let format = Format::from_path(path).ok_or("scores can only be imported from .json or .csv")?;
+    let incoming = format.read(&std::fs::read_to_string(path)?)?;
+
+    let mut added = 0;
+    for &mode in GameMode::RANKED {
+        let of_mode: Vec<ScoreEntry> = incoming
+            .iter()
+            .filter(|e| e.mode == mode)
+            .cloned()
+            .collect();
+        if of_mode.is_empty() {
+            continue;
+        }
+        let mut entries = match super::load(mode) {
+            Ok(loaded) => loaded.entries,
+            Err(e) if is_missing(&*e) => Vec::new(),
+            Err(e) => return Err(format!("{}: {}", mode.scores_file(), e).into()),
+        };
+        added += merge(&mut entries, of_mode);
+        super::save(mode, &entries)?;
+    }
+    Ok(added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, value: u64, timestamp: u64) -> ScoreEntry {
+        ScoreEntry {
+            name: name.to_string(),
+            value,
+            timestamp,
+            lines: 40,
+            level: 5,
+            mode: GameMode::Sprint,
+            modified: false,
+            profile: "P1".to_string(),
+            splits: vec![12_000, 25_000],
+        }
+    }
+
+    /// `entries` as they come back from an export, which nothing vouches for
+    fn imported(entries: &[ScoreEntry]) -> Vec<ScoreEntry> {
+        entries
+            .iter()
+            .cloned()
+            .map(|e| ScoreEntry {
+                modified: true,
+                ..e
+            })
+            .collect()
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_only_when_they_need_it() {
+        assert!(matches!(csv_field("PLR"), Cow::Borrowed("PLR")));
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("cr\r"), "\"cr\r\"");
+        assert_eq!(csv_field(""), "");
+    }
+
+    #[test]
+    fn awkward_names_come_back_from_csv_unchanged() {
+        let entries = vec![
+            entry("a,b", 41_000, 1),
+            entry("\"quoted\"", 42_000, 2),
+            entry("two\r\nlines", 43_000, 3),
+            entry("\"", 44_000, 4),
+            entry(" spaced ", 45_000, 5),
+            entry("", 46_000, 6),
+        ];
+        let csv = to_csv(&entries);
+        assert!(csv.starts_with("name,value,timestamp,lines,level,mode,profile,splits\r\n"));
+        assert!(csv.contains("\r\n\"a,b\",41000,1,40,5,"));
+        assert_eq!(from_csv(&csv).unwrap(), imported(&entries));
+    }
+
+    #[test]
+    fn json_has_an_object_per_entry_with_stable_field_names() {
+        let entries = vec![entry("a\"b", 41_000, 1), entry("c", 42_000, 2)];
+        let json = to_json(&entries);
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let objects = value.as_array().unwrap();
+        assert_eq!(objects.len(), 2);
+        let mut keys: Vec<&str> = objects[0]
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        keys.sort_unstable();
+        let mut fields = FIELDS.to_vec();
+        fields.sort_unstable();
+        assert_eq!(keys, fields);
+        assert_eq!(objects[0]["mode"], GameMode::Sprint.name());
+
+        assert_eq!(from_json(&json).unwrap(), imported(&entries));
+    }
+
+    #[test]
+    fn exports_from_before_profiles_and_splits_still_read() {
+        let sprint = GameMode::Sprint.name();
+        let csv = format!(
+            "name,value,timestamp,lines,level,mode\r\nAAA,41000,1,40,5,{}\r\n",
+            sprint
+        );
+        let entries = from_csv(&csv).unwrap();
+        assert_eq!(entries[0].name, "AAA");
+        assert!(entries[0].profile.is_empty() && entries[0].splits.is_empty());
+
+        let json = format!(
+            r#"[{{"name":"AAA","value":41000,"timestamp":1,"lines":40,"level":5,"mode":"{}"}}]"#,
+            sprint
+        );
+        assert_eq!(from_json(&json).unwrap(), entries);
+    }
+
+    #[test]
+    fn broken_csv_is_turned_away() {
+        let good = to_csv(&[entry("AAA", 41_000, 1)]);
+        assert!(from_csv("").is_err());
+        assert!(from_csv(&good.replacen("name", "nom", 1)).is_err());
+        assert!(from_csv(&good.replacen("41000", "41000,1", 1)).is_err());
+        assert!(from_csv(&good.replacen("41000", "lots", 1)).is_err());
+        assert!(from_csv(&good.replacen("AAA", "\"AAA", 1)).is_err());
+        assert!(from_csv(&good.replacen(GameMode::Sprint.name(), "Nope", 1)).is_err());
+    }
+
+    #[test]
+    fn merging_skips_entries_already_there() {
+        let mut entries = vec![entry("AAA", 41_000, 1), entry("BBB", 42_000, 2)];
+        let incoming = vec![
+            // the same run, even from another profile
+            ScoreEntry {
+                profile: "P2".to_string(),
+                ..entry("AAA", 41_000, 1)
+            },
+            // but not the same name, value or time
+            entry("aaa", 41_000, 1),
+            entry("AAA", 41_001, 1),
+            entry("AAA", 41_000, 9),
+            // and only once if it's in there twice
+            entry("CCC", 43_000, 3),
+            entry("CCC", 43_000, 3),
+        ];
+        assert_eq!(merge(&mut entries, incoming), 4);
+        let names: Vec<(&str, u64, u64)> = entries
+            .iter()
+            .map(|e| (e.name.as_str(), e.value, e.timestamp))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("AAA", 41_000, 1),
+                ("BBB", 42_000, 2),
+                ("aaa", 41_000, 1),
+                ("AAA", 41_001, 1),
+                ("AAA", 41_000, 9),
+                ("CCC", 43_000, 3),
+            ]
+        );
+        assert_eq!(entries[0].profile, "P1");
+    }
+
+    #[test]
+    fn formats_go_by_name_or_extension() {
+        assert_eq!(Format::from_name("JSON"), Some(Format::Json));
+        assert_eq!(Format::from_name("csv"), Some(Format::Csv));
+        assert_eq!(Format::from_name("xml"), None);
+        assert_eq!(
+            Format::from_path(Path::new("out/scores.Csv")),
+            Some(Format::Csv)
+        );
+        assert_eq!(Format::from_path(Path::new("scores")), None);
+    }
+}
@@ -0,0 +1,1869 @@
+//! The game rules, run tick by tick without a window, a clock or a GPU.
+//!
+//! A [`Simulation`] is advanced one tick at a time with [`Simulation::step`],
+//! given which keys are held on that tick, and reports what happened as a list
+//! of [`Event`]s. Everything it needs to play out the same way twice is in the
+//! seed and the inputs, so it can be driven by a player, a replay or a bot.
+
+use std::collections::{HashMap, VecDeque};
+
+use rand::prelude::*;
+
+use crate::board::{self, Board, Cell, FIELD_HEIGHT, FIELD_WIDTH};
+use crate::game::puzzle::Puzzle;
+use crate::game::stats::{GameStats, SAMPLE_TICKS};
+use crate::mode::{
+    GameMode, MARATHON_BONUS, MARATHON_LINES, RISING_LINE_BONUS_MILLIS, SPRINT_LINES,
+};
+use crate::piece::randomizer::{Randomizer, RandomizerKind};
+use crate::piece::rotation::RotationKind;
+use crate::piece::set::PieceSet;
+use crate::piece::{Color, Piece, PieceDef, Tetromino};
+
+pub mod dump;
+pub mod replay;
+pub mod save;
+
+// Game state only ever advances in whole ticks and is counted in integers (ticks,
+// lines, cells), so a run plays out the same on every platform given the same
+// inputs per tick. Floats are only used to decide when the next tick is due from
+// the wall clock, and for rendering.
+pub const TICK_MILLIS: u64 = 50;
+pub const TICKS_PER_SECOND: u64 = 1000 / TICK_MILLIS;
+/// Range and default of how many upcoming pieces are previewed
+pub const MIN_PREVIEWS: usize = 1;
+pub const MAX_PREVIEWS: usize = 6;
+pub const DEFAULT_PREVIEWS: usize = 5;
+/// Highest level a game can be started at, where gravity is one cell a frame
+pub const MAX_START_LEVEL: u32 = 20;
+/// How long the 3-2-1 countdown before a run lasts
+pub const COUNTDOWN_TICKS: u64 = 3 * TICKS_PER_SECOND;
+/// How long a line clear animates before the field catches up
+pub const LINE_CLEAR_TICKS: u64 = TICKS_PER_SECOND;
+/// How long reaching a new level is shown for, play going on underneath
+pub const LEVEL_UP_TICKS: u64 = TICKS_PER_SECOND;
+/// Placements practice mode remembers for undoing
+pub const UNDO_HISTORY: usize = 20;
+/// How long a rotation tapped while there's no piece to turn waits for the
+/// next one
+pub const INPUT_BUFFER_TICKS: u64 = 6;
+
+pub fn ticks_to_millis(ticks: u64) -> u64 {
+    ticks * TICK_MILLIS
+}
+
+/// Keys held down on a tick. Presses and auto-repeat are worked out from how
+/// this compares to the tick before
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Inputs {
+    pub left: bool,
+    pub right: bool,
+    pub down: bool,
+    pub rot_right: bool,
+    pub rot_left: bool,
+}
+
+/// How a sideways key repeats while it's held, in ticks
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Handling {
+    /// Ticks after the first step before the piece starts sliding
+    pub das: u64,
+    /// Ticks between steps while sliding, or 0 to slide all the way at once
+    pub arr: u64,
+}
+
+impl Default for Handling {
+    fn default() -> Self {
+        Self { das: 2, arr: 2 }
+    }
+}
+
+impl Handling {
+    /// Cells to move on the tick a sideways key has been held for `ticks`
+    /// ticks: one as it goes down, then one every `arr` ticks from `das` on
+    pub fn steps(self, ticks: u64) -> u32 {
+        if ticks == 0 {
+            1
+        } else if ticks < self.das {
+            0
+        } else if self.arr == 0 {
+            FIELD_WIDTH
+        } else if (ticks - self.das) % self.arr == 0 {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+/// What the piece does while left and right are both held
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpposingInputs {
+    /// The directions cancel out and the piece stays put
+    Neutral,
+    /// Whichever key went down most recently wins
+    LastPressed,
+    /// Left always wins
+    PreferLeft,
+}
+
+impl Default for OpposingInputs {
+    fn default() -> Self {
+        Self::LastPressed
+    }
+}
+
+impl OpposingInputs {
+    /// Horizontal direction to move in: -1 for left, 1 for right, 0 for neither.
+    /// `last` is the direction of the most recently pressed key.
+    pub fn resolve(self, left: bool, right: bool, last: i8) -> i8 {
+        match (left, right) {
+            (false, false) => 0,
+            (true, false) => -1,
+            (false, true) => 1,
+            (true, true) => match self {
+                Self::Neutral => 0,
+                Self::LastPressed => last,
+                Self::PreferLeft => -1,
+            },
+        }
+    }
+}
+
+/// Something that happened during a tick
+#[derive(Clone, Debug)]
+pub enum Event {
+    /// `piece` entered the field at the top
+    PieceSpawned { piece: Piece },
+    /// Gravity moved the active piece down a row
+    GravityStep,
+    /// The player slid the active piece sideways, by one cell or more
+    PieceShifted,
+    /// The player turned the active piece
+    PieceRotated,
+    /// Holding down moved the active piece down a row
+    SoftDropStep,
+    /// The fall speed went up to that of `level`
+    LevelUp { level: u32 },
+    /// `piece` was placed with more inputs than it needed
+    FinesseFault { piece: Piece },
+    /// `piece` locked into the stack
+    PieceLocked { piece: Piece },
+    /// A single piece cleared `lines` lines, worth `score` points. `perfect` if
+    /// nothing is left on the field afterwards
+    LinesCleared {
+        lines: u32,
+        perfect: bool,
+        score: u64,
+    },
+    /// The mode's goal was met, the clock stopping at `millis`
+    GoalReached { millis: u64 },
+    /// Marathon reached its final line
+    MarathonCleared,
+    /// The run is over; the simulation won't change any more
+    Finished(Ending),
+}
+
+/// How a run ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Ending {
+    /// The goal was met in `millis`
+    Cleared { millis: u64 },
+    /// The mode was beaten outright
+    Won,
+    /// The stack reached the top
+    ToppedOut,
+    /// The mode's time limit ran out
+    TimeUp,
+}
+
+/// What the board looked like before a piece was placed
+pub struct Placement {
+    pub kind: &'static PieceDef,
+    pub board: Board,
+    pub score: u64,
+    pub lines: u32,
+    /// Statistics from before the piece spawned
+    pub stats: GameStats,
+}
+
+pub struct BoardEffect {
+    pub ty: BoardEffectType,
+    pub life: u64,
+}
+
+pub enum BoardEffectType {
+    LinesCleared {
+        lines: Vec<i8>,
+    },
+    GameOver,
+    TimeUp,
+    Win,
+    /// The fall speed went up to that of `level`
+    LevelUp {
+        level: u32,
+    },
+}
+
+impl BoardEffectType {
+    /// Whether the run is over once this effect is shown
+    pub fn ends_run(&self) -> bool {
+        match self {
+            Self::LinesCleared { .. } | Self::LevelUp { .. } => false,
+            Self::GameOver | Self::TimeUp | Self::Win => true,
+        }
+    }
+
+    /// Whether play waits for this effect to finish. One that doesn't runs
+    /// down alongside the game instead
+    pub fn blocks(&self) -> bool {
+        !matches!(self, Self::LevelUp { .. })
+    }
+}
+
+/// One run of the game: the field, the pieces and the clocks, and the rules
+/// that move them along
+pub struct Simulation {
+    /// Cells that pieces have locked into
+    pub board: Board,
+
+    /// Frame each cell of `board` was filled on
+    pub placed_at: [u64; (FIELD_WIDTH * FIELD_HEIGHT) as usize],
+
+    /// Active piece being manipulated by the player
+    pub active_piece: Option<Piece>,
+
+    /// Determines how many game ticks before the active piece is forcibly moved down
+    pub fall_ticks: u32,
+
+    /// Counter which
+    fall_counter: u32,
+
+    /// Determines how many game ticks fall_ticks_dec_counter starts at
+    fall_accel_ticks: u32,
+
+    /// Counter that decreases speed by 1 when it reaches 0
+    fall_accel_counter: u32,
+
+    /// Next pieces to fall
+    pub next_pieces: Vec<Piece>,
+
+    /// Whether we rotated last frame
+    rotated: bool,
+
+    /// Rotations tapped while there was no piece to turn, in quarter turns
+    /// clockwise, with the ticks each has left to wait for the next piece
+    buffered_turns: Vec<(u8, u64)>,
+
+    /// How to settle left and right being held together
+    pub opposing_inputs: OpposingInputs,
+
+    /// Direction of the horizontal key that went down most recently
+    last_horizontal: i8,
+
+    /// How held sideways keys repeat
+    pub handling: Handling,
+
+    /// Ticks the sideways key in effect has been held for
+    shift_ticks: u64,
+
+    /// Keys held on the previous tick
+    last_inputs: Inputs,
+
+    /// Current frame number
+    pub ticker: u64,
+
+    /// Score
+    pub score: u64,
+
+    /// Board effect play waits on
+    pub effect: Option<BoardEffect>,
+
+    /// Effects shown while play goes on, oldest first
+    pub flourishes: Vec<BoardEffect>,
+
+    /// Rules this game is played by
+    pub mode: GameMode,
+
+    /// Total lines cleared
+    pub lines: u32,
+
+    /// Frame number at which the first piece spawned
+    pub start_tick: Option<u64>,
+
+    /// Final time of a completed timed run, in milliseconds
+    pub finish_time: Option<u64>,
+
+    /// How the run ended, once it has
+    pub ending: Option<Ending>,
+
+    /// How the run has been played so far
+    pub stats: GameStats,
+
+    /// Rows of garbage that haven't been cleared yet
+    pub garbage_left: u32,
+
+    /// Frames left before the run starts
+    pub countdown: u64,
+
+    /// Whether gravity is switched off, in practice mode
+    pub frozen: bool,
+
+    /// Setup being played, in puzzle mode
+    pub puzzle: Option<Puzzle>,
+
+    /// Whether placements are checked for finesse
+    pub finesse: bool,
+
+    /// The active piece as it spawned
+    pub spawned: Option<Piece>,
+
+    /// Rotations and sideways steps spent on the active piece
+    piece_inputs: u32,
+
+    /// Placements that took more inputs than needed
+    pub faults: u32,
+
+    /// Recent placements that can be taken back, in practice mode
+    history: Vec<Placement>,
+
+    /// Statistics from before the piece in play spawned, in practice mode,
+    /// for its placement to go back to
+    spawn_stats: GameStats,
+
+    /// Seed the piece sequence and garbage are dealt from
+    pub seed: u64,
+
+    /// Source of every random choice in the game, so a seed replays the same run
+    rng: StdRng,
+
+    /// How pieces are picked
+    pub randomizer: RandomizerKind,
+
+    /// How the seven turn and kick. Set it with
+    /// [`set_rotation`](Self::set_rotation) so the queue turns the same way
+    pub rotation: RotationKind,
+
+    /// What pieces there are to deal
+    pub pieces: PieceSet,
+
+    /// Picks pieces the way `randomizer` says, drawing on `rng`
+    dealer: Box<dyn Randomizer>,
+
+    /// Pieces `rng` has dealt, so a restored run can deal them again to catch up
+    dealt: u64,
+
+    /// Whether pieces drop straight onto the stack (20G). Gravity steps then
+    /// only decide when the piece locks
+    pub instant_gravity: bool,
+
+    /// Garbage rows cleared lines have earned against the opponent in versus,
+    /// waiting to be passed on
+    pub garbage_out: u32,
+
+    /// Garbage rows the opponent has sent, pushed in from the bottom as soon
+    /// as the field isn't busy clearing lines
+    pub garbage_in: u32,
+
+    /// Ticks until the next garbage row rises, in modes where rows rise
+    pub rise_left: u64,
+
+    /// Ticks the wait for the next rising row started out at, 0 if rows
+    /// don't rise
+    pub rise_ticks: u64,
+
+    /// Rows the stack has been raised by, in order, each with how many pieces
+    /// had been dealt before, so a saved run can draw the same garbage again
+    pub raises: Vec<(u64, u32)>,
+}
+
+impl Simulation {
+    /// Starts a game in `mode` that shows `previews` upcoming pieces, dealt by
+    /// `randomizer` from `seed` or from a random seed if None
+    pub fn new(
+        mode: GameMode,
+        previews: usize,
+        seed: Option<u64>,
+        randomizer: RandomizerKind,
+    ) -> Self {
+        let previews = previews.clamp(MIN_PREVIEWS, MAX_PREVIEWS);
+        let seed = seed.unwrap_or_else(rand::random);
+        let mut s = Self {
+            board: Board::default(),
+            placed_at: [0; (FIELD_WIDTH * FIELD_HEIGHT) as usize],
+            active_piece: None,
+            fall_ticks: 20,
+            fall_accel_ticks: 10,
+            rotated: false,
+            buffered_turns: Vec::new(),
+            opposing_inputs: OpposingInputs::default(),
+            last_horizontal: 0,
+            handling: Handling::default(),
+            shift_ticks: 0,
+            last_inputs: Inputs::default(),
+            ticker: 0,
+            score: 0,
+            effect: None,
+            flourishes: Vec::new(),
+            mode,
+            lines: 0,
+            start_tick: None,
+            finish_time: None,
+            ending: None,
+            stats: GameStats::new(),
+            garbage_left: mode.garbage_rows(),
+            countdown: COUNTDOWN_TICKS,
+            frozen: false,
+            puzzle: None,
+            finesse: true,
+            spawned: None,
+            piece_inputs: 0,
+            faults: 0,
+            history: Vec::new(),
+            spawn_stats: GameStats::new(),
+            instant_gravity: false,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            randomizer,
+            rotation: RotationKind::default(),
+            pieces: PieceSet::default(),
+            dealer: randomizer.build(),
+            dealt: 0,
+            garbage_out: 0,
+            garbage_in: 0,
+            rise_left: 0,
+            rise_ticks: 0,
+            raises: Vec::new(),
+
+            // these will be set later
+            fall_counter: 0,
+            fall_accel_counter: 0,
+            next_pieces: Vec::with_capacity(previews),
+        };
+
+        s.fall_counter = s.fall_ticks;
+        s.fall_accel_counter = s.fall_accel_ticks;
+        s.restart_rise();
+        s.board.add_garbage(s.garbage_left, &mut s.rng);
+        for _ in 0..previews {
+            let piece = Piece::new(s.deal(), mode.piece_scale(), s.rotation);
+            s.next_pieces.push(piece);
+        }
+
+        s
+    }
+
+    /// Picks the next piece to join the queue. The randomizer only knows the
+    /// seven; other sets are dealt uniformly
+    fn deal(&mut self) -> &'static PieceDef {
+        self.dealt += 1;
+        if self.pieces.is_standard() {
+            self.dealer.next(&mut self.rng).def()
+        } else {
+            self.pieces.pieces.choose(&mut self.rng).unwrap()
+        }
+    }
+
+    /// Plays with `pieces` from now on, dealing the queue again from them
+    pub fn use_pieces(&mut self, pieces: PieceSet) {
+        self.pieces = pieces;
+        let scale = self.mode.piece_scale();
+        for i in 0..self.next_pieces.len() {
+            self.next_pieces[i] = Piece::new(self.deal(), scale, self.rotation);
+        }
+    }
+
+    /// Turns pieces with `rotation` from now on, the queued ones included
+    pub fn set_rotation(&mut self, rotation: RotationKind) {
+        self.rotation = rotation;
+        for piece in &mut self.next_pieces {
+            piece.rotation = rotation;
+        }
+    }
+
+    /// Like `new`, but starting at `level` in modes where the level goes up
+    pub fn with_level(
+        mode: GameMode,
+        previews: usize,
+        level: u32,
+        seed: Option<u64>,
+        randomizer: RandomizerKind,
+    ) -> Self {
+        let mut s = Self::new(mode, previews, seed, randomizer);
+        if mode.levels_up() {
+            let level = level.clamp(1, mode.level_cap().unwrap_or(MAX_START_LEVEL));
+            s.fall_ticks -= level - 1;
+            s.fall_counter = s.fall_ticks;
+        }
+        s
+    }
+
+    /// Starts `puzzle`, its queue standing in for the usual random pieces
+    pub fn from_puzzle(puzzle: Puzzle) -> Self {
+        let mut s = Self::new(
+            GameMode::Puzzle,
+            MIN_PREVIEWS,
+            None,
+            RandomizerKind::default(),
+        );
+        for (cell, &filled) in s.board.cells.iter_mut().zip(puzzle.cells.iter()) {
+            if filled {
+                *cell = Cell::Full(Color::Grey);
+            }
+        }
+        s.next_pieces = puzzle
+            .queue
+            .iter()
+            .filter_map(|&letter| Tetromino::from_letter(letter))
+            .map(|t| Piece::new(t.def(), 1, RotationKind::default()))
+            .collect();
+        s.puzzle = Some(puzzle);
+        s
+    }
+
+    /// Level as displayed to the player, derived from the fall speed
+    pub fn level(&self) -> u32 {
+        20 - self.fall_ticks + 1
+    }
+
+    /// Starts the wait for the next garbage row to rise, as long as the mode
+    /// says for how far into the run it is
+    fn restart_rise(&mut self) {
+        let elapsed = ticks_to_millis(self.elapsed_ticks());
+        self.rise_ticks = self.mode.rise_millis(elapsed).unwrap_or(0) / TICK_MILLIS;
+        self.rise_left = self.rise_ticks;
+    }
+
+    /// Number of frames since the first piece spawned
+    pub fn elapsed_ticks(&self) -> u64 {
+        self.start_tick
+            .map(|start| self.ticker - start)
+            .unwrap_or(0)
+    }
+
+    /// Whether the stack has reached the top, ending the run
+    pub fn topped_out(&self) -> bool {
+        matches!(
+            self.effect,
+            Some(BoardEffect {
+                ty: BoardEffectType::GameOver,
+                ..
+            })
+        )
+    }
+
+    /// Starts showing `effect`, holding play up until it's over if it
+    /// [`blocks`](BoardEffectType::blocks)
+    fn show(&mut self, effect: BoardEffect) {
+        if effect.ty.blocks() {
+            self.effect = Some(effect);
+        } else {
+            self.flourishes.push(effect);
+        }
+    }
+
+    /// Swaps out the front of the queue for `kind`
+    pub fn pick_next(&mut self, kind: &'static PieceDef) {
+        self.next_pieces[0] = Piece::new(kind, self.mode.piece_scale(), self.rotation);
+    }
+
+    /// Empties the field, forgetting everything there was to undo
+    pub fn clear_board(&mut self) {
+        if self.effect.is_none() {
+            self.board = Board::default();
+            self.history.clear();
+        }
+    }
+
+    /// Takes back the last placement. The undone piece comes next, then the
+    /// one that was falling
+    pub fn undo(&mut self) {
+        if self.effect.is_some() {
+            return;
+        }
+        if let Some(placement) = self.history.pop() {
+            let scale = self.mode.piece_scale();
+            let count = self.next_pieces.len();
+            if let Some(active) = self.active_piece.take() {
+                let piece = Piece::new(active.kind, scale, self.rotation);
+                self.next_pieces.insert(0, piece);
+            }
+            let piece = Piece::new(placement.kind, scale, self.rotation);
+            self.next_pieces.insert(0, piece);
+            self.next_pieces.truncate(count);
+
+            self.board = placement.board;
+            self.score = placement.score;
+            self.lines = placement.lines;
+            self.stats.rewind_to(placement.stats);
+        }
+    }
+
+    /// Advances the game by one tick with `inputs` held, returning what happened
+    pub fn step(&mut self, inputs: Inputs) -> Vec<Event> {
+        let last = std::mem::replace(&mut self.last_inputs, inputs);
+        if inputs.left && !last.left {
+            self.last_horizontal = -1;
+        } else if inputs.right && !last.right {
+            self.last_horizontal = 1;
+        }
+
+        // a tap between one piece locking and the next spawning, or while lines
+        // clear, would otherwise be lost, so it's kept for the next piece
+        for (_, life) in &mut self.buffered_turns {
+            *life -= 1;
+        }
+        self.buffered_turns.retain(|&(_, life)| life > 0);
+        if self.active_piece.is_none() {
+            if inputs.rot_right && !last.rot_right {
+                self.buffered_turns.push((1, INPUT_BUFFER_TICKS));
+            } else if inputs.rot_left && !last.rot_left {
+                self.buffered_turns.push((3, INPUT_BUFFER_TICKS));
+            }
+        }
+
+        let mut events = Vec::new();
+        if self.ending.is_some() {
+            return events;
+        }
+        if self.countdown > 0 {
+            // hands off the board, and the clocks, until the countdown is over
+            self.countdown -= 1;
+            return events;
+        }
+        self.ticker += 1;
+        self.advance(inputs, last, &mut events);
+        events
+    }
+
+    fn finish(&mut self, ending: Ending, events: &mut Vec<Event>) {
+        self.ending = Some(ending);
+        self.stats.sample(self.score);
+        events.push(Event::Finished(ending));
+    }
+
+    fn advance(&mut self, inputs: Inputs, last: Inputs, events: &mut Vec<Event>) {
+        // a key acts when it goes down, then every other frame while it's held
+        let ticker = self.ticker;
+        let repeats = |held: bool, was_held: bool| held && (!was_held || ticker % 2 == 0);
+
+        let running = self.effect.as_ref().map_or(true, |e| !e.ty.ends_run());
+        if self.start_tick.is_some() && running {
+            self.stats.tick();
+            if self.stats.ticks % SAMPLE_TICKS == 0 {
+                self.stats.sample(self.score);
+            }
+        }
+
+        // effects that don't block play run down whatever else is going on
+        for effect in &mut self.flourishes {
+            effect.life -= 1;
+        }
+        self.flourishes.retain(|effect| effect.life > 0);
+
+        if let Some(effect) = &mut self.effect {
+            // handle effect and return early
+            effect.life -= 1;
+            match effect.ty {
+                BoardEffectType::LinesCleared { ref lines } if effect.life == 0 => {
+                    // delete them lines
+                    self.board.clear_rows(lines);
+                    board::remove_rows(&mut self.placed_at, lines, 0);
+
+                    if let Some(millis) = self.finish_time {
+                        // run complete
+                        return self.finish(Ending::Cleared { millis }, events);
+                    }
+
+                    if self.mode == GameMode::Marathon && self.lines >= MARATHON_LINES {
+                        // marathon complete, celebrate before finishing
+                        *effect = BoardEffect {
+                            ty: BoardEffectType::Win,
+                            life: 5 * TICKS_PER_SECOND,
+                        };
+                        events.push(Event::MarathonCleared);
+                        return;
+                    }
+                }
+                BoardEffectType::Win if effect.life == 0 => {
+                    if self.mode == GameMode::Marathon {
+                        self.score += MARATHON_BONUS;
+                    }
+                    return self.finish(Ending::Won, events);
+                }
+                BoardEffectType::GameOver if effect.life == 0 => {
+                    return self.finish(Ending::ToppedOut, events);
+                }
+                BoardEffectType::TimeUp if effect.life == 0 => {
+                    return self.finish(Ending::TimeUp, events);
+                }
+                _ => (),
+            }
+            if effect.life == 0 {
+                self.effect = None;
+            }
+            return;
+        }
+
+        if self.rise_ticks > 0 && self.start_tick.is_some() {
+            // a row comes up from below whenever the wait runs out
+            self.rise_left = self.rise_left.saturating_sub(1);
+            if self.rise_left == 0 {
+                self.garbage_in += 1;
+                self.restart_rise();
+            }
+        }
+
+        if self.garbage_in > 0 {
+            // incoming garbage lifts the whole stack, anything at the top
+            // included, off the field
+            let rows = std::mem::take(&mut self.garbage_in).min(FIELD_HEIGHT);
+            let mut topped_out = self.board.raise(rows, &mut self.rng);
+            self.raises.push((self.dealt, rows));
+            self.placed_at
+                .copy_within((rows * FIELD_WIDTH) as usize.., 0);
+
+            // a falling piece the stack comes up into rides up with it
+            if let Some(piece) = &mut self.active_piece {
+                if !self.board.fits(piece) {
+                    let raised = Piece {
+                        y: piece.y - rows as i8,
+                        ..*piece
+                    };
+                    if self.board.fits(&raised) {
+                        *piece = raised;
+                    } else {
+                        topped_out = true;
+                    }
+                }
+            }
+
+            if topped_out {
+                self.active_piece = None;
+                self.effect = Some(BoardEffect {
+                    ty: BoardEffectType::GameOver,
+                    life: 3 * TICKS_PER_SECOND,
+                });
+                return;
+            }
+        }
+
+        if let Some(limit) = self.mode.time_limit() {
+            if ticks_to_millis(self.elapsed_ticks()) >= limit {
+                // time's up! whatever is still falling doesn't count
+                self.active_piece = None;
+                self.finish_time = Some(limit);
+                self.effect = Some(BoardEffect {
+                    ty: BoardEffectType::TimeUp,
+                    life: 3 * TICKS_PER_SECOND,
+                });
+                return;
+            }
+        }
+
+        if self.active_piece.is_none() {
+            if self.puzzle.is_some() {
+                // puzzles are won on an empty field and lost once the queue runs out
+                let ty = if self.board.is_empty() {
+                    Some(BoardEffectType::Win)
+                } else if self.next_pieces.is_empty() {
+                    Some(BoardEffectType::GameOver)
+                } else {
+                    None
+                };
+                if let Some(ty) = ty {
+                    self.effect = Some(BoardEffect {
+                        ty,
+                        life: 3 * TICKS_PER_SECOND,
+                    });
+                    return;
+                }
+            }
+
+            // check if we have enough space!
+            let next = self.next_pieces.remove(0);
+            if self.puzzle.is_none() {
+                let piece = Piece::new(self.deal(), self.mode.piece_scale(), self.rotation);
+                self.next_pieces.push(piece);
+            }
+
+            self.spawned = Some(next);
+            self.piece_inputs = 0;
+
+            // a rotation key held through the spawn turns the piece as it
+            // enters (IRS), unless that would leave it blocked. taps waiting
+            // for the piece take the place of the key held
+            let buffered = std::mem::take(&mut self.buffered_turns);
+            let turn = if !buffered.is_empty() {
+                0
+            } else if inputs.rot_right {
+                1
+            } else if inputs.rot_left {
+                3
+            } else {
+                0
+            };
+            if turn != 0 || !buffered.is_empty() {
+                // the key press has been used up, don't rotate again below
+                self.rotated = true;
+            }
+
+            let spawn = |board: &Board| board.spawn(next.kind, next.scale, next.rotation, turn);
+            if self.mode == GameMode::Zen && spawn(&self.board).is_none() {
+                // nobody loses in zen, just make some room
+                self.board.drop_bottom_half();
+            }
+
+            if let Some(mut piece) = spawn(&self.board) {
+                // ok :D
+                if piece.rot != next.rot {
+                    self.piece_inputs += 1;
+                }
+                // in the order they were tapped, each if the piece still fits
+                for &(turn, _) in &buffered {
+                    if let Some(turned) = self.board.rotate(&piece, turn) {
+                        piece = turned;
+                        self.piece_inputs += 1;
+                    }
+                }
+                self.active_piece = Some(piece);
+                if self.mode == GameMode::Practice {
+                    self.spawn_stats = self.stats.clone();
+                }
+                self.stats
+                    .piece_spawned(piece.tetromino().map(|t| t as usize));
+                if self.start_tick.is_none() {
+                    // the clock starts with the first piece
+                    self.start_tick = Some(self.ticker);
+                }
+                events.push(Event::PieceSpawned { piece });
+            } else {
+                // failuree!!
+                self.effect = Some(BoardEffect {
+                    ty: BoardEffectType::GameOver,
+                    life: 3 * TICKS_PER_SECOND,
+                });
+                return;
+            }
+        }
+
+        let level = self.level();
+
+        // tick fall counter
+        if !self.frozen {
+            self.fall_counter -= 1;
+        }
+        let gravity_step = self.fall_counter == 0;
+        let should_fall = gravity_step || repeats(inputs.down, last.down);
+
+        // tick down fall accelerator counter
+        if self.mode.levels_up() && self.fall_accel_counter == 0 {
+            // past the cap the speed plateaus
+            if self.mode.level_cap().map_or(true, |cap| level < cap) {
+                let fall_ticks = u32::max(self.fall_ticks - 1, 1);
+                if fall_ticks != self.fall_ticks {
+                    events.push(Event::LevelUp { level: level + 1 });
+                    self.show(BoardEffect {
+                        ty: BoardEffectType::LevelUp { level: level + 1 },
+                        life: LEVEL_UP_TICKS,
+                    });
+                }
+                self.fall_ticks = fall_ticks;
+            }
+            self.fall_accel_counter = self.fall_accel_ticks;
+        }
+
+        let active_piece = self.active_piece.as_mut().unwrap();
+
+        // rotate brick if requested
+        if inputs.rot_right && !last.rot_right {
+            if !self.rotated {
+                self.rotated = true;
+                if let Some(turned) = self.board.rotate(active_piece, 1) {
+                    *active_piece = turned;
+                    self.piece_inputs += 1;
+                    events.push(Event::PieceRotated);
+                }
+            }
+        } else if inputs.rot_left && !last.rot_left {
+            if !self.rotated {
+                self.rotated = true;
+                if let Some(turned) = self.board.rotate(active_piece, 3) {
+                    *active_piece = turned;
+                    self.piece_inputs += 1;
+                    events.push(Event::PieceRotated);
+                }
+            }
+        } else {
+            self.rotated = false;
+        }
+
+        // move brick left and right if requested
+        let direction =
+            self.opposing_inputs
+                .resolve(inputs.left, inputs.right, self.last_horizontal);
+        let (held, was_held) = if direction < 0 {
+            (inputs.left, last.left)
+        } else {
+            (inputs.right, last.right)
+        };
+        if direction != 0 && held {
+            self.shift_ticks = if was_held { self.shift_ticks + 1 } else { 0 };
+            let mut moved = false;
+            for _ in 0..self.handling.steps(self.shift_ticks) {
+                let mut test_piece = active_piece.to_owned();
+                test_piece.x += direction * test_piece.scale as i8;
+                if !self.board.fits(&test_piece) {
+                    break;
+                }
+                active_piece.x = test_piece.x;
+                moved = true;
+            }
+            if moved {
+                self.piece_inputs += 1;
+                events.push(Event::PieceShifted);
+            }
+        }
+
+        // at 20G the piece sits on the stack after every move, so it can
+        // still be slid and turned before snapping down
+        if self.instant_gravity {
+            let mut test_piece = active_piece.to_owned();
+            test_piece.y += test_piece.scale as i8;
+            while self.board.fits(&test_piece) {
+                active_piece.y = test_piece.y;
+                test_piece.y += test_piece.scale as i8;
+            }
+        }
+
+        if !should_fall {
+            return;
+        }
+
+        // make piece fall
+        self.fall_counter = self.fall_ticks;
+
+        // verify if we can fall
+        let mut test_piece = active_piece.to_owned();
+        test_piece.y += test_piece.scale as i8;
+        if self.board.fits(&test_piece) {
+            // fall
+            active_piece.y = test_piece.y;
+            if gravity_step {
+                // soft drops don't count as a beat
+                events.push(Event::GravityStep);
+            } else {
+                events.push(Event::SoftDropStep);
+            }
+            return;
+        }
+
+        let piece = *active_piece;
+
+        // was there a shorter way to get here?
+        if let (true, Some(spawned)) = (self.finesse, self.spawned) {
+            let needed = minimal_inputs(spawned, &piece, &self.board);
+            if needed.map_or(false, |needed| self.piece_inputs > needed) {
+                self.faults += 1;
+                events.push(Event::FinesseFault { piece });
+            }
+        }
+
+        if self.mode == GameMode::Practice {
+            if self.history.len() == UNDO_HISTORY {
+                self.history.remove(0);
+            }
+            self.history.push(Placement {
+                kind: piece.kind,
+                board: self.board,
+                score: self.score,
+                lines: self.lines,
+                stats: std::mem::take(&mut self.spawn_stats),
+            });
+        }
+
+        // add to board
+        self.board.lock(&piece);
+        for (x, y) in piece.cells() {
+            self.placed_at[(x + y * FIELD_WIDTH as isize) as usize] = self.ticker;
+            self.stats.columns.add(x as usize);
+        }
+        events.push(Event::PieceLocked { piece });
+
+        // check if any lines are deletable
+        let deletable = self.board.full_rows();
+
+        // big minos fill their rows in pairs, which count as one line
+        let cleared = deletable.len() / piece.scale as usize;
+        self.stats
+            .piece_placed(piece.tetromino().map(|t| t as usize), cleared);
+
+        // invalidate piece
+        self.active_piece = None;
+
+        if deletable.is_empty() {
+            if board::locked_out(&piece) {
+                if self.mode == GameMode::Zen {
+                    // nobody loses in zen, just make some room
+                    self.board.drop_bottom_half();
+                } else {
+                    // locked out
+                    self.effect = Some(BoardEffect {
+                        ty: BoardEffectType::GameOver,
+                        life: 3 * TICKS_PER_SECOND,
+                    });
+                }
+            }
+            return;
+        }
+
+        // everything left over is about to be cleared
+        let perfect = {
+            let mut after = self.board;
+            after.clear_rows(&deletable);
+            after.is_empty()
+        };
+
+        // add score
+        let score = board::clear_score(cleared);
+        self.score += score;
+        self.lines += cleared as u32;
+        if cleared > 0 {
+            events.push(Event::LinesCleared {
+                lines: cleared as u32,
+                perfect,
+                score,
+            });
+        }
+
+        if self.rise_ticks > 0 {
+            // clearing lines holds the next row back, up to a full wait
+            let bonus = cleared as u64 * RISING_LINE_BONUS_MILLIS / TICK_MILLIS;
+            self.rise_left = u64::min(self.rise_left + bonus, self.rise_ticks);
+        }
+
+        if self.mode == GameMode::Versus {
+            // every line past the first goes over, a tetris sends all four
+            self.garbage_out += match cleared {
+                4 => 4,
+                n => n as u32 - 1,
+            };
+        }
+
+        // garbage always sits in one block at the bottom of the field
+        let garbage_top = FIELD_HEIGHT - self.garbage_left;
+        let dug = deletable
+            .iter()
+            .filter(|&&y| y as u32 >= garbage_top)
+            .count();
+        self.garbage_left -= dug as u32;
+
+        let goal_reached = match self.mode {
+            GameMode::Sprint => self.lines >= SPRINT_LINES,
+            GameMode::Cheese { .. } => self.garbage_left == 0,
+            _ => false,
+        };
+        if goal_reached && self.finish_time.is_none() {
+            // stop the clock on the final clear
+            let millis = ticks_to_millis(self.elapsed_ticks());
+            self.finish_time = Some(millis);
+            events.push(Event::GoalReached { millis });
+        }
+
+        // decrease speed
+        self.fall_accel_counter = self.fall_accel_counter.saturating_sub(cleared as u32);
+
+        // set effect and defer line deletion to later
+        self.effect = Some(BoardEffect {
+            ty: BoardEffectType::LinesCleared { lines: deletable },
+            life: LINE_CLEAR_TICKS,
+        });
+    }
+}
+
+/// Fewest rotations and sideways steps that take `piece` from where it spawned
+/// to covering the same cells as `target`, letting it fall for free on the way.
+/// `None` if `target` can't be reached at all.
+pub fn minimal_inputs(piece: Piece, target: &Piece, board: &Board) -> Option<u32> {
+    let covered = |p: &Piece| {
+        let mut cells: Vec<_> = p.cells().collect();
+        cells.sort_unstable();
+        cells
+    };
+    let goal = covered(target);
+
+    // breadth first over (x, y, rotation); falling costs nothing so it jumps the queue
+    let mut best = HashMap::new();
+    let mut queue = VecDeque::new();
+    best.insert((piece.x, piece.y, piece.rot), 0);
+    queue.push_back((piece, 0));
+    while let Some((p, cost)) = queue.pop_front() {
+        if best[&(p.x, p.y, p.rot)] < cost {
+            continue;
+        }
+        if covered(&p) == goal {
+            return Some(cost);
+        }
+
+        let step = p.scale as i8;
+        let shifted = |x: i8, y: i8| {
+            let next = Piece {
+                x: p.x + x,
+                y: p.y + y,
+                ..p
+            };
+            Some(next).filter(|next| board.fits(next))
+        };
+        let moves = [
+            (shifted(0, step), 0),
+            (shifted(-step, 0), 1),
+            (shifted(step, 0), 1),
+            (board.rotate(&p, 1), 1),
+            (board.rotate(&p, 3), 1),
+        ];
+        for &(next, extra) in moves.iter() {
+            let next = match next {
+                Some(next) => next,
+                None => continue,
+            };
+            let cost = cost + extra;
+            let key = (next.x, next.y, next.rot);
+            if best.get(&key).map_or(true, |&b| cost < b) {
+                best.insert(key, cost);
+                if extra == 0 {
+                    queue.push_front((next, cost));
+                } else {
+                    queue.push_back((next, cost));
+                }
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::stats::PIECE_KINDS;
+
+    /// Keys mashed at random, the same ones for the same `seed`
+    fn random_inputs(seed: u64, ticks: usize) -> Vec<Inputs> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..ticks)
+            .map(|_| Inputs {
+                left: rng.gen_bool(0.2),
+                right: rng.gen_bool(0.2),
+                down: rng.gen_bool(0.3),
+                rot_right: rng.gen_bool(0.1),
+                rot_left: rng.gen_bool(0.1),
+            })
+            .collect()
+    }
+
+    /// Everything about `sim` that play depends on, boiled down to a number
+    /// that comes out the same on every platform and compiler (FNV-1a)
+    fn state_hash(sim: &Simulation) -> u64 {
+        let mut bytes = sim.board.to_ascii().into_bytes();
+        bytes.extend(format!("{:?}", &sim.board.cells[..]).bytes());
+        for &tick in &sim.placed_at[..] {
+            bytes.extend_from_slice(&tick.to_le_bytes());
+        }
+        if let Some(piece) = sim.active_piece {
+            bytes.extend(piece.kind.name.bytes());
+            bytes.extend_from_slice(&[piece.x as u8, piece.y as u8, piece.rot]);
+        }
+        for piece in &sim.next_pieces {
+            bytes.extend(piece.kind.name.bytes());
+        }
+        for n in &[sim.ticker, sim.score, sim.lines as u64, sim.dealt] {
+            bytes.extend_from_slice(&n.to_le_bytes());
+        }
+        for n in &[sim.fall_ticks, sim.fall_counter, sim.level()] {
+            bytes.extend_from_slice(&n.to_le_bytes());
+        }
+        bytes.extend(format!("{:?}", sim.ending).bytes());
+
+        bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    /// Plays `inputs` through a fresh endless run dealt from `seed`
+    fn play(seed: u64, inputs: &[Inputs]) -> Simulation {
+        let mut sim = Simulation::new(
+            GameMode::Endless,
+            DEFAULT_PREVIEWS,
+            Some(seed),
+            RandomizerKind::default(),
+        );
+        for &keys in inputs {
+            sim.step(keys);
+        }
+        sim
+    }
+
+    #[test]
+    fn the_same_inputs_play_out_the_same() {
+        let inputs = random_inputs(7, 5_000);
+        let first = play(42, &inputs);
+        let second = play(42, &inputs);
+        assert!(first.stats.total_pieces() > 10, "the run went nowhere");
+        assert_eq!(state_hash(&first), state_hash(&second));
+    }
+
+    #[test]
+    fn another_seed_plays_out_differently() {
+        let inputs = random_inputs(7, 2_000);
+        assert_ne!(
+            state_hash(&play(42, &inputs)),
+            state_hash(&play(43, &inputs))
+        );
+    }
+
+    /// A run of `mode` dealt from a fixed seed, stepped until its first piece
+    /// is in play
+    fn spawned(mode: GameMode) -> Simulation {
+        let mut sim = Simulation::new(mode, DEFAULT_PREVIEWS, Some(1), RandomizerKind::default());
+        while sim.active_piece.is_none() {
+            sim.step(Inputs::default());
+        }
+        sim
+    }
+
+    fn column(sim: &Simulation) -> i8 {
+        sim.active_piece.unwrap().x
+    }
+
+    #[test]
+    fn opposing_inputs_resolve_by_policy() {
+        use OpposingInputs::*;
+        for &policy in &[Neutral, LastPressed, PreferLeft] {
+            assert_eq!(policy.resolve(false, false, 1), 0);
+            assert_eq!(policy.resolve(true, false, 1), -1);
+            assert_eq!(policy.resolve(false, true, -1), 1);
+        }
+        assert_eq!(Neutral.resolve(true, true, 1), 0);
+        assert_eq!(LastPressed.resolve(true, true, 1), 1);
+        assert_eq!(LastPressed.resolve(true, true, -1), -1);
+        assert_eq!(PreferLeft.resolve(true, true, 1), -1);
+    }
+
+    #[test]
+    fn letting_go_of_the_last_key_pressed_goes_back_the_other_way() {
+        let mut sim = spawned(GameMode::Zen);
+        sim.frozen = true;
+        let left = Inputs {
+            left: true,
+            ..Inputs::default()
+        };
+        let both = Inputs {
+            right: true,
+            ..left
+        };
+
+        let start = column(&sim);
+        sim.step(left);
+        assert_eq!(column(&sim), start - 1);
+        sim.step(both);
+        assert_eq!(column(&sim), start, "right went down last, so it wins");
+        sim.step(left);
+        assert_eq!(column(&sim), start, "left is still in its delay");
+        for _ in 0..sim.handling.das {
+            sim.step(left);
+        }
+        assert!(column(&sim) < start, "left took over again");
+    }
+
+    #[test]
+    fn holding_both_with_the_neutral_policy_stays_put() {
+        let mut sim = spawned(GameMode::Zen);
+        sim.frozen = true;
+        sim.opposing_inputs = OpposingInputs::Neutral;
+        let both = Inputs {
+            left: true,
+            right: true,
+            ..Inputs::default()
+        };
+        let start = column(&sim);
+        for _ in 0..10 {
+            sim.step(both);
+        }
+        assert_eq!(column(&sim), start);
+    }
+
+    /// `piece` let fall as far as it goes on `board`
+    fn landed(board: &Board, mut piece: Piece) -> Piece {
+        while board.fits(&Piece {
+            y: piece.y + 1,
+            ..piece
+        }) {
+            piece.y += 1;
+        }
+        piece
+    }
+
+    fn t_piece() -> Piece {
+        Piece::new(Tetromino::T.def(), 1, RotationKind::default())
+    }
+
+    #[test]
+    fn dropping_straight_down_takes_no_inputs() {
+        let board = Board::default();
+        let spawn = t_piece();
+        assert_eq!(
+            minimal_inputs(spawn, &landed(&board, spawn), &board),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn minimal_inputs_counts_each_step_and_turn() {
+        let board = Board::default();
+        let spawn = t_piece();
+        let target = |x: i8, rot: u8| {
+            landed(
+                &board,
+                Piece {
+                    x: spawn.x + x,
+                    rot,
+                    ..spawn
+                },
+            )
+        };
+        assert_eq!(minimal_inputs(spawn, &target(-3, 0), &board), Some(3));
+        // turning on the floor a column short kicks it the rest of the way
+        assert_eq!(minimal_inputs(spawn, &target(-3, 1), &board), Some(3));
+        assert_eq!(minimal_inputs(spawn, &target(2, 3), &board), Some(2));
+        assert_eq!(minimal_inputs(spawn, &target(0, 2), &board), Some(2));
+    }
+
+    #[test]
+    fn turning_an_o_is_never_needed() {
+        let board = Board::default();
+        let spawn = Piece::new(Tetromino::O.def(), 1, RotationKind::default());
+        let turned = landed(&board, Piece { rot: 1, ..spawn });
+        assert_eq!(minimal_inputs(spawn, &turned, &board), Some(0));
+    }
+
+    #[test]
+    fn a_sealed_off_spot_cannot_be_reached() {
+        let board = Board::from_ascii(
+            "#########.
+             ..........
+             ..........",
+        )
+        .unwrap();
+        let spawn = t_piece();
+        let target = Piece {
+            x: 0,
+            y: FIELD_HEIGHT as i8 - 3,
+            rot: 2,
+            ..spawn
+        };
+        assert!(board.fits(&target));
+        assert_eq!(minimal_inputs(spawn, &target, &board), None);
+    }
+
+    /// Events up to and including the lock of the piece in play, tapping
+    /// `taps` one after another first and then holding down
+    fn place(sim: &mut Simulation, taps: &[Inputs]) -> Vec<Event> {
+        let mut events = Vec::new();
+        for &tap in taps {
+            events.extend(sim.step(tap));
+            events.extend(sim.step(Inputs::default()));
+        }
+        let down = Inputs {
+            down: true,
+            ..Inputs::default()
+        };
+        while !events
+            .iter()
+            .any(|e| matches!(e, Event::PieceLocked { .. }))
+        {
+            events.extend(sim.step(down));
+        }
+        events
+    }
+
+    #[test]
+    fn wasted_inputs_are_a_finesse_fault() {
+        let left = Inputs {
+            left: true,
+            ..Inputs::default()
+        };
+        let right = Inputs {
+            right: true,
+            ..Inputs::default()
+        };
+        let faults = |taps: &[Inputs]| {
+            let mut sim = spawned(GameMode::Zen);
+            place(&mut sim, taps)
+                .iter()
+                .filter(|e| matches!(e, Event::FinesseFault { .. }))
+                .count()
+        };
+        assert_eq!(faults(&[]), 0);
+        assert_eq!(faults(&[left, left]), 0);
+        assert_eq!(faults(&[left, right]), 1);
+    }
+
+    #[test]
+    fn undoing_a_clear_puts_back_the_exact_field() {
+        let mut sim = Simulation::new(
+            GameMode::Practice,
+            DEFAULT_PREVIEWS,
+            Some(1),
+            RandomizerKind::default(),
+        );
+        sim.board = Board::from_ascii(
+            "#.........
+             ###....###",
+        )
+        .unwrap();
+        sim.pick_next(Tetromino::I.def());
+        let before = sim.board;
+        while sim.active_piece.is_none() {
+            sim.step(Inputs::default());
+        }
+
+        let events = place(&mut sim, &[]);
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, Event::LinesCleared { lines: 1, .. })));
+        while sim.effect.is_some() {
+            sim.step(Inputs::default());
+        }
+        assert_ne!(sim.board.to_ascii(), before.to_ascii());
+
+        sim.undo();
+        assert_eq!(sim.board.to_ascii(), before.to_ascii());
+        assert!(sim.board == before, "the colours came back too");
+        assert_eq!((sim.score, sim.lines), (0, 0));
+        assert_eq!(sim.next_pieces[0].tetromino(), Some(Tetromino::I));
+    }
+
+    #[test]
+    fn undoing_puts_back_the_statistics_but_not_the_clock() {
+        let mut sim = Simulation::new(
+            GameMode::Practice,
+            DEFAULT_PREVIEWS,
+            Some(1),
+            RandomizerKind::default(),
+        );
+        sim.board = Board::from_ascii("###....###").unwrap();
+        sim.pick_next(Tetromino::I.def());
+        let before = sim.stats.clone();
+        while sim.active_piece.is_none() {
+            sim.step(Inputs::default());
+        }
+
+        place(&mut sim, &[]);
+        while sim.active_piece.is_none() {
+            sim.step(Inputs::default());
+        }
+        place(&mut sim, &[]);
+        assert_eq!(sim.stats.lines, 1);
+        assert_eq!(sim.stats.total_pieces(), 2);
+        let ticks = sim.stats.ticks;
+
+        sim.undo();
+        sim.undo();
+        // as if the I was never placed, the HUD's lines and pieces included
+        assert_eq!(sim.stats.lines, sim.lines);
+        assert_eq!(sim.stats.total_pieces(), 0);
+        assert_eq!(sim.stats.clears, [0; 4]);
+        assert_eq!(sim.stats.droughts, before.droughts);
+        assert_eq!(sim.stats.ticks, ticks);
+
+        // and placing it again counts it once
+        while sim.active_piece.is_none() {
+            sim.step(Inputs::default());
+        }
+        assert_eq!(sim.active_piece.unwrap().tetromino(), Some(Tetromino::I));
+        assert_eq!(sim.stats.droughts.of(Tetromino::I as usize), 0);
+        place(&mut sim, &[]);
+        assert_eq!(sim.stats.lines, 1);
+        assert_eq!(sim.stats.total_pieces(), 1);
+    }
+
+    #[test]
+    fn droughts_count_the_pieces_dealt_by_every_randomizer() {
+        for &kind in RandomizerKind::ALL {
+            let mut sim = Simulation::new(GameMode::Zen, DEFAULT_PREVIEWS, Some(5), kind);
+            let mut dealt = Vec::new();
+            for keys in random_inputs(3, 3_000) {
+                let next = sim.next_pieces.first().map(|p| p.kind.name);
+                for event in sim.step(keys) {
+                    if let Event::PieceSpawned { piece } = event {
+                        // the piece is the one that was up next
+                        assert_eq!(Some(piece.kind.name), next);
+                        dealt.push(piece.tetromino().unwrap() as usize);
+                        // counted as soon as it's in the field
+                        assert_eq!(sim.stats.droughts.of(*dealt.last().unwrap()), 0);
+                    }
+                }
+            }
+            assert!(dealt.len() > 20, "{:?} dealt {}", kind, dealt.len());
+
+            for shape in 0..PIECE_KINDS {
+                let since = match dealt.iter().rposition(|&d| d == shape) {
+                    Some(at) => dealt.len() - 1 - at,
+                    None => dealt.len(),
+                };
+                assert_eq!(
+                    sim.stats.droughts.of(shape) as usize,
+                    since,
+                    "{:?} shape {}",
+                    kind,
+                    shape
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn the_heatmap_counts_each_cell_placed_by_its_column() {
+        let left = Inputs {
+            left: true,
+            ..Inputs::default()
+        };
+        let mut sim = Simulation::new(
+            GameMode::Zen,
+            DEFAULT_PREVIEWS,
+            Some(1),
+            RandomizerKind::default(),
+        );
+        sim.pick_next(Tetromino::I.def());
+        for &(next, taps) in &[(Tetromino::O, 3), (Tetromino::T, 0)] {
+            while sim.active_piece.is_none() {
+                sim.step(Inputs::default());
+            }
+            sim.pick_next(next.def());
+            place(&mut sim, &vec![left; taps]);
+        }
+
+        // the I went three to the left, and the O dropped where it spawned
+        assert_eq!(sim.stats.columns.0.iter().sum::<u32>(), 8);
+        assert_eq!(sim.stats.columns.count(0), 1);
+        assert_eq!(sim.stats.columns.busiest(), Some(4));
+        // with nothing cleared, it's what's on the board
+        for column in 0..FIELD_WIDTH {
+            let filled = (0..FIELD_HEIGHT)
+                .filter(|&y| sim.board.cells[(column + y * FIELD_WIDTH) as usize] != Cell::Empty)
+                .count();
+            assert_eq!(
+                sim.stats.columns.count(column as usize) as usize,
+                filled,
+                "column {}",
+                column
+            );
+        }
+    }
+
+    #[test]
+    fn undo_only_works_in_practice() {
+        let mut sim = spawned(GameMode::Zen);
+        place(&mut sim, &[]);
+        let after = sim.board;
+        sim.undo();
+        assert!(sim.board == after);
+    }
+
+    #[test]
+    fn locking_inside_the_spawn_rows_tops_out() {
+        let mut sim = Simulation::new(
+            GameMode::Endless,
+            DEFAULT_PREVIEWS,
+            Some(1),
+            RandomizerKind::default(),
+        );
+        let mut field = "..........\n".repeat(2);
+        field.push_str(&"#.#.#####.\n".repeat(FIELD_HEIGHT as usize - 2));
+        sim.board = Board::from_ascii(&field).unwrap();
+        sim.pick_next(Tetromino::I.def());
+        while sim.active_piece.is_none() {
+            sim.step(Inputs::default());
+        }
+        assert!(!sim.topped_out(), "it spawned fine");
+
+        place(&mut sim, &[]);
+        assert!(sim.topped_out());
+    }
+
+    #[test]
+    fn locking_below_the_spawn_rows_plays_on() {
+        let mut sim = Simulation::new(
+            GameMode::Endless,
+            DEFAULT_PREVIEWS,
+            Some(1),
+            RandomizerKind::default(),
+        );
+        let mut field = "..........\n".repeat(3);
+        field.push_str(&"#.#.#####.\n".repeat(FIELD_HEIGHT as usize - 3));
+        sim.board = Board::from_ascii(&field).unwrap();
+        sim.pick_next(Tetromino::I.def());
+        while sim.active_piece.is_none() {
+            sim.step(Inputs::default());
+        }
+
+        place(&mut sim, &[]);
+        assert!(!sim.topped_out());
+    }
+
+    /// A zen run with an I lying flat in row `row`, over `field`
+    fn i_over(row: i8, field: &str) -> Simulation {
+        let mut sim = spawned(GameMode::Zen);
+        sim.frozen = true;
+        sim.board = Board::from_ascii(field).unwrap();
+        sim.active_piece = Some(Piece {
+            x: 3,
+            y: row - 1,
+            rot: 0,
+            ..Piece::new(Tetromino::I.def(), 1, sim.rotation)
+        });
+        assert!(sim.board.fits(&sim.active_piece.unwrap()));
+        sim
+    }
+
+    #[test]
+    fn a_falling_piece_rides_up_with_the_stack() {
+        let row = FIELD_HEIGHT as i8 - 2;
+        let mut sim = i_over(row, "#########.");
+        sim.garbage_in = 1;
+        sim.step(Inputs::default());
+
+        // the stack came up into it, so up it went
+        let piece = sim.active_piece.expect("the piece is gone");
+        assert_eq!(piece.y, row - 2);
+        assert_eq!(sim.raises, vec![(sim.dealt, 1)]);
+        assert!(sim.effect.is_none());
+
+        // with room to spare it stays where it is
+        let mut sim = i_over(row - 5, "#########.");
+        sim.garbage_in = 2;
+        sim.step(Inputs::default());
+        assert_eq!(sim.active_piece.unwrap().y, row - 6);
+    }
+
+    #[test]
+    fn a_piece_with_no_room_above_tops_out() {
+        let field = "#########.\n".repeat(FIELD_HEIGHT as usize - 1);
+        let mut sim = i_over(0, &field);
+        sim.garbage_in = 1;
+        sim.step(Inputs::default());
+        assert!(sim.active_piece.is_none());
+        assert!(sim.topped_out());
+    }
+
+    #[test]
+    fn gravity_carries_on_while_a_level_up_is_shown() {
+        let mut sim = spawned(GameMode::Endless);
+        // as if the lines for the next level had just been cleared, fast
+        // enough that the piece falls a few rows while it's shown
+        sim.fall_ticks = 4;
+        sim.fall_counter = 4;
+        sim.fall_accel_counter = 0;
+        let events = sim.step(Inputs::default());
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, Event::LevelUp { level: 18 })));
+        assert!(sim.effect.is_none(), "play is held up");
+        assert_eq!(sim.flourishes.len(), 1);
+
+        let start = sim.active_piece.unwrap().y;
+        let mut steps = 0;
+        for _ in 1..LEVEL_UP_TICKS {
+            assert_eq!(sim.flourishes.len(), 1);
+            let events = sim.step(Inputs::default());
+            steps += events
+                .iter()
+                .filter(|e| matches!(e, Event::GravityStep))
+                .count();
+        }
+        assert!(steps >= 4, "only {} gravity steps", steps);
+        assert_eq!(sim.active_piece.unwrap().y, start + steps as i8);
+
+        // and it runs out by itself
+        sim.step(Inputs::default());
+        assert!(sim.flourishes.is_empty());
+    }
+
+    #[test]
+    fn a_big_piece_filling_a_pair_of_rows_clears_one_line() {
+        let mut sim = spawned(GameMode::Big);
+        sim.board = Board::from_ascii(
+            "####....##
+             ####....##",
+        )
+        .unwrap();
+        let piece = Piece::new(Tetromino::O.def(), 2, sim.rotation);
+        let (left, top, _, _) = piece.bounds();
+        sim.active_piece = Some(Piece {
+            x: 4 - 2 * left as i8,
+            y: -2 * top as i8,
+            ..piece
+        });
+
+        let events = place(&mut sim, &[]);
+        let cleared: Vec<u32> = events
+            .iter()
+            .filter_map(|e| match e {
+                Event::LinesCleared { lines, .. } => Some(*lines),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(cleared, vec![1]);
+        assert_eq!(sim.stats.lines, 1);
+    }
+
+    #[test]
+    fn random_play_keeps_the_field_sound_and_the_score_rising() {
+        let modes = [
+            GameMode::Endless,
+            GameMode::Big,
+            GameMode::Cheese { rows: 9 },
+            GameMode::Rising,
+            GameMode::Versus,
+        ];
+        for (seed, &mode) in modes.iter().enumerate() {
+            let mut sim = Simulation::new(
+                mode,
+                DEFAULT_PREVIEWS,
+                Some(seed as u64),
+                RandomizerKind::default(),
+            );
+            let mut score = 0;
+            for (tick, keys) in random_inputs(seed as u64, 4_000).into_iter().enumerate() {
+                if mode == GameMode::Versus && tick % 300 == 0 {
+                    sim.garbage_in += 1;
+                }
+                sim.step(keys);
+
+                let filled = sim
+                    .board
+                    .cells
+                    .iter()
+                    .filter(|&&c| c != Cell::Empty)
+                    .count();
+                let falling = sim.active_piece.map_or(0, |p| p.cells().count());
+                assert!(
+                    filled + falling <= (FIELD_WIDTH * FIELD_HEIGHT) as usize,
+                    "{:?} tick {}",
+                    mode,
+                    tick
+                );
+                if let Some(piece) = sim.active_piece {
+                    assert!(sim.board.fits(&piece), "{:?} tick {}", mode, tick);
+                }
+                assert!(sim.score >= score, "{:?} tick {}", mode, tick);
+                score = sim.score;
+            }
+        }
+    }
+
+    /// Names of the pieces shown next and the `spawns` dealt after them
+    fn dealing(seed: Option<u64>, spawns: usize) -> (u64, Vec<&'static str>) {
+        let mut sim = Simulation::new(
+            GameMode::Endless,
+            DEFAULT_PREVIEWS,
+            seed,
+            RandomizerKind::default(),
+        );
+        let mut names: Vec<_> = sim.next_pieces.iter().map(|p| p.kind.name).collect();
+        names.extend((0..spawns).map(|_| sim.deal().name));
+        (sim.seed, names)
+    }
+
+    #[test]
+    fn the_same_seed_deals_the_same_pieces() {
+        let (_, first) = dealing(Some(1234), 100);
+        let (_, second) = dealing(Some(1234), 100);
+        assert_eq!(first, second);
+        assert_ne!(first, dealing(Some(1235), 100).1);
+    }
+
+    #[test]
+    fn an_unseeded_run_can_be_dealt_again_from_its_seed() {
+        let (seed, first) = dealing(None, 100);
+        assert_eq!(dealing(Some(seed), 100).1, first);
+    }
+
+    /// A zen run just after an I cleared the only row, with a T up next
+    fn after_a_clear() -> Simulation {
+        let mut sim = Simulation::new(
+            GameMode::Zen,
+            DEFAULT_PREVIEWS,
+            Some(1),
+            RandomizerKind::default(),
+        );
+        sim.board = Board::from_ascii("###....###").unwrap();
+        sim.pick_next(Tetromino::I.def());
+        while sim.active_piece.is_none() {
+            sim.step(Inputs::default());
+        }
+        sim.pick_next(Tetromino::T.def());
+        place(&mut sim, &[]);
+        assert!(sim.active_piece.is_none());
+        sim
+    }
+
+    /// The T spawned [`after_a_clear`], with turns tapped `taps` ticks before
+    /// the tick it spawns on, clockwise for `true`
+    fn spawned_after(taps: &[(usize, bool)]) -> Piece {
+        let mut sim = after_a_clear();
+        let mut untouched = after_a_clear();
+        let mut ticks = 0;
+        while untouched.active_piece.is_none() {
+            untouched.step(Inputs::default());
+            ticks += 1;
+        }
+        assert!(ticks > INPUT_BUFFER_TICKS as usize, "the gap is too short");
+
+        for tick in 1..=ticks {
+            let mut inputs = Inputs::default();
+            for &(before, clockwise) in taps {
+                if tick + before == ticks {
+                    inputs.rot_right = clockwise;
+                    inputs.rot_left = !clockwise;
+                }
+            }
+            assert!(sim.active_piece.is_none());
+            sim.step(inputs);
+        }
+        sim.active_piece.expect("the T didn't spawn on time")
+    }
+
+    #[test]
+    fn a_turn_tapped_before_the_spawn_turns_the_next_piece() {
+        let plain = spawned_after(&[]).rot;
+        assert_eq!(spawned_after(&[(2, true)]).rot, (plain + 1) % 4);
+        assert_eq!(spawned_after(&[(2, false)]).rot, (plain + 3) % 4);
+        // and each of several, in the order they were tapped
+        let taps = [(5, true), (3, true), (1, false)];
+        assert_eq!(spawned_after(&taps).rot, (plain + 1) % 4);
+        let taps = [(4, true), (2, true)];
+        assert_eq!(spawned_after(&taps).rot, (plain + 2) % 4);
+    }
+
+    #[test]
+    fn a_turn_tapped_too_long_before_the_spawn_is_forgotten() {
+        let plain = spawned_after(&[]).rot;
+        let early = INPUT_BUFFER_TICKS as usize;
+        assert_eq!(spawned_after(&[(early, true)]).rot, plain);
+        assert_eq!(spawned_after(&[(early - 1, true)]).rot, (plain + 1) % 4);
+    }
+
+    /// The run above as it played out when the rules last changed on purpose.
+    /// Running the tests with and without `--release`, and on each platform,
+    /// checks every build plays it out the same; update it along with any
+    /// change to the rules or the dealing
+    const FINGERPRINT: u64 = 0xd324_e835_6a5a_f02d;
+
+    #[test]
+    fn every_build_plays_out_the_same() {
+        let sim = play(42, &random_inputs(7, 5_000));
+        assert_eq!(state_hash(&sim), FINGERPRINT, "{:#x}", state_hash(&sim));
+    }
+}
@@ -0,0 +1,396 @@
+//! Plain text snapshots of a run, for reporting something that went wrong.
+//!
+//! A dump is meant to be read by a person first: a list of `name: value`
+//! lines, the field drawn with [`Board::to_ascii`] and the keys held over the
+//! last few ticks, one tick a line. [`read`] builds the run back from one so
+//! it can be played on from the same spot. Colors aren't kept, so the stack
+//! comes back grey, and neither are the stats.
+//!
+//! ```text
+//! tet.rs dump 2
+//! mode: Endless
+//! seed: 1234
+//! randomizer: 7-bag
+//! rotation: SRS
+//! dealt: 9
+//! ticker: 812
+//! score: 400
+//! level: 2
+//! lines: 4
+//! active: T 4 0 0
+//! next: I O S Z L
+//! field:
+//! ..........
+//! (20 rows in all)
+//! inputs:
+//! 811 L----
+//! 812 -----
+//! ```
+
+use std::error::Error;
+use std::fmt::Write;
+use std::io;
+
+use rand::prelude::*;
+
+use super::{Inputs, Simulation, COUNTDOWN_TICKS, MAX_PREVIEWS};
+use crate::board::{Board, FIELD_HEIGHT};
+use crate::mode::GameMode;
+use crate::piece::randomizer::RandomizerKind;
+use crate::piece::rotation::RotationKind;
+use crate::piece::Piece;
+
+/// Where dumps are written, relative to the working directory
+pub const DUMP_DIR: &str = "dumps";
+
+/// Ticks of held keys a dump keeps
+pub const DUMP_INPUT_TICKS: usize = 60;
+
+/// First line of every dump
+const HEADER: &str = "tet.rs dump 2";
+
+/// Letters the keys held on a tick are written as, in the order of
+/// [`Inputs`]' fields
+const KEY_LETTERS: [char; 5] = ['L', 'R', 'D', 'C', 'A'];
+
+/// A run read back from a dump, and the keys held up to the moment it was
+/// taken, oldest first
+pub struct Dump {
+    pub sim: Simulation,
+    pub inputs: Vec<(u64, Inputs)>,
+}
+
+/// Writes `sim` out, followed by `inputs`, the keys held on each of the
+/// ticks leading up to now as (tick, keys)
+pub fn write(sim: &Simulation, inputs: &[(u64, Inputs)]) -> String {
+    let piece =
+        |piece: &Piece| format!("{} {} {} {}", piece.kind.name, piece.x, piece.y, piece.rot);
+
+    let mut text = String::new();
+    // writing to a String can't fail
+    writeln!(text, "{}", HEADER).unwrap();
+    writeln!(text, "mode: {}", sim.mode.name()).unwrap();
+    writeln!(text, "seed: {}", sim.seed).unwrap();
+    writeln!(text, "randomizer: {}", sim.randomizer.name()).unwrap();
+    writeln!(text, "rotation: {}", sim.rotation.name()).unwrap();
+    writeln!(text, "pieces: {}", sim.pieces.name).unwrap();
+    writeln!(text, "dealt: {}", sim.dealt).unwrap();
+    writeln!(text, "ticker: {}", sim.ticker).unwrap();
+    writeln!(text, "score: {}", sim.score).unwrap();
+    writeln!(text, "level: {}", sim.level()).unwrap();
+    writeln!(text, "lines: {}", sim.lines).unwrap();
+    match &sim.active_piece {
+        Some(active) => writeln!(text, "active: {}", piece(active)).unwrap(),
+        None => writeln!(text, "active: none").unwrap(),
+    }
+    let next: Vec<&str> = sim.next_pieces.iter().map(|p| p.kind.name).collect();
+    writeln!(text, "next: {}", next.join(" ")).unwrap();
+    // there's no hold yet, the line is there for when there is
+    writeln!(text, "hold: none").unwrap();
+
+    writeln!(text, "field:").unwrap();
+    writeln!(text, "{}", sim.board.to_ascii()).unwrap();
+
+    writeln!(text, "inputs:").unwrap();
+    for (tick, keys) in inputs {
+        let held = [
+            keys.left,
+            keys.right,
+            keys.down,
+            keys.rot_right,
+            keys.rot_left,
+        ];
+        let letters: String = held
+            .iter()
+            .zip(KEY_LETTERS.iter())
+            .map(|(&held, &letter)| if held { letter } else { '-' })
+            .collect();
+        writeln!(text, "{} {}", tick, letters).unwrap();
+    }
+    text
+}
+
+/// Builds back the run dumped to `text`. It counts down again before it
+/// carries on
+pub fn read(text: &str) -> Result<Dump, Box<dyn Error>> {
+    let mut lines = text.lines();
+    if lines.next().map(str::trim) != Some(HEADER) {
+        return Err(invalid("not a dump"));
+    }
+
+    // name: value lines, up to the field
+    let mut values = Vec::new();
+    for line in &mut lines {
+        if line.trim() == "field:" {
+            break;
+        }
+        let mut parts = line.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some(name), Some(value)) => values.push((name.trim(), value.trim())),
+            _ => return Err(invalid(&format!("expected name: value, found {:?}", line))),
+        }
+    }
+    let value = |name: &str| {
+        values
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|&(_, v)| v)
+            .ok_or_else(|| invalid(&format!("{} is missing", name)))
+    };
+    let number = |name: &str| -> Result<u64, Box<dyn Error>> {
+        value(name)?
+            .parse()
+            .map_err(|e| invalid(&format!("{}: {}", name, e)))
+    };
+
+    let mode_name = value("mode")?;
+    let mode = *GameMode::ALL
+        .iter()
+        .find(|m| m.name() == mode_name)
+        .ok_or_else(|| invalid(&format!("unknown mode {:?}", mode_name)))?;
+    if mode == GameMode::Puzzle || mode == GameMode::Versus {
+        return Err(invalid("puzzle and versus runs can't be built back"));
+    }
+    let randomizer_name = value("randomizer")?;
+    let randomizer = *RandomizerKind::ALL
+        .iter()
+        .find(|r| r.name() == randomizer_name)
+        .ok_or_else(|| invalid(&format!("unknown randomizer {:?}", randomizer_name)))?;
+    let rotation_name = value("rotation")?;
+    let rotation = *RotationKind::ALL
+        .iter()
+        .find(|r| r.name() == rotation_name)
+        .ok_or_else(|| invalid(&format!("unknown rotation {:?}", rotation_name)))?;
+    let seed = number("seed")?;
+    let mut sim = Simulation::new(mode, MAX_PREVIEWS, Some(seed), randomizer);
+    sim.rotation = rotation;
+    if value("pieces")? != sim.pieces.name {
+        return Err(invalid(
+            "only runs of the seven tetrominoes can be built back",
+        ));
+    }
+
+    // deal the same pieces again to get the generator back where it was
+    sim.rng = StdRng::seed_from_u64(seed);
+    sim.dealer = randomizer.build();
+    sim.dealt = 0;
+    Board::default().add_garbage(mode.garbage_rows(), &mut sim.rng);
+    for _ in 0..number("dealt")? {
+        sim.deal();
+    }
+
+    sim.ticker = number("ticker")?;
+    sim.score = number("score")?;
+    sim.lines = number("lines")? as u32;
+    sim.stats.lines = sim.lines;
+    let level = number("level")? as u32;
+    if level == 0 || level > 20 {
+        return Err(invalid(&format!("level {} is out of range", level)));
+    }
+    sim.fall_ticks = 20 - (level - 1);
+    sim.fall_counter = sim.fall_ticks;
+
+    let scale = mode.piece_scale();
+    let pieces = sim.pieces.pieces;
+    let find = |name: &str| {
+        pieces
+            .iter()
+            .find(|def| def.name == name)
+            .ok_or_else(|| invalid(&format!("unknown piece {:?}", name)))
+    };
+    sim.active_piece = match value("active")? {
+        "none" => None,
+        active => {
+            let fields: Vec<&str> = active.split_whitespace().collect();
+            let (name, x, y, rot) = match fields.as_slice() {
+                [name, x, y, rot] => (
+                    *name,
+                    x.parse::<i8>()?,
+                    y.parse::<i8>()?,
+                    rot.parse::<u8>()?,
+                ),
+                _ => return Err(invalid("expected active: <piece> <x> <y> <rotation>")),
+            };
+            if rot >= 4 {
+                return Err(invalid("rotation is out of range"));
+            }
+            Some(Piece {
+                x,
+                y,
+                rot,
+                ..Piece::new(find(name)?, scale, rotation)
+            })
+        }
+    };
+    let next = value("next")?
+        .split_whitespace()
+        .map(|name| Ok(Piece::new(find(name)?, scale, rotation)))
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+    if next.is_empty() || next.len() > MAX_PREVIEWS {
+        return Err(invalid("expected between 1 and 6 next pieces"));
+    }
+    sim.next_pieces = next;
+
+    // field, then the inputs
+    let field: Vec<&str> = (&mut lines).take(FIELD_HEIGHT as usize).collect();
+    sim.board = Board::from_ascii(&field.join("\n")).ok_or_else(|| invalid("bad field"))?;
+    if let Some(piece) = &sim.active_piece {
+        if !sim.board.fits(piece) {
+            return Err(invalid("the active piece overlaps the stack"));
+        }
+    }
+    sim.start_tick = Some(sim.ticker);
+    sim.countdown = COUNTDOWN_TICKS;
+
+    let mut inputs = Vec::new();
+    if lines.next().map(str::trim) == Some("inputs:") {
+        for line in lines.filter(|l| !l.trim().is_empty()) {
+            let (tick, letters) = match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [tick, letters] => (tick, letters),
+                _ => {
+                    return Err(invalid(&format!(
+                        "expected <tick> <keys>, found {:?}",
+                        line
+                    )))
+                }
+            };
+            let held: Vec<bool> = letters.chars().map(|c| c != '-').collect();
+            if held.len() != KEY_LETTERS.len() {
+                return Err(invalid(&format!("expected 5 keys, found {:?}", letters)));
+            }
+            inputs.push((
+                tick.parse()?,
+                Inputs {
+                    left: held[0],
+                    right: held[1],
+                    down: held[2],
+                    rot_right: held[3],
+                    rot_left: held[4],
+                },
+            ));
+        }
+    }
+
+    Ok(Dump { sim, inputs })
+}
+
+fn invalid(message: &str) -> Box<dyn Error> {
+    Box::new(io::Error::new(
+        io::ErrorKind::InvalidData,
+        message.to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A run of `mode` played on random keys for `ticks` ticks, with the keys
+    /// held over the last [`DUMP_INPUT_TICKS`] of them
+    fn played(mode: GameMode, ticks: usize) -> (Simulation, Vec<(u64, Inputs)>) {
+        let mut sim = Simulation::new(mode, MAX_PREVIEWS, Some(77), RandomizerKind::Bag);
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut inputs = Vec::new();
+        for _ in 0..ticks {
+            let keys = Inputs {
+                left: rng.gen_bool(0.2),
+                right: rng.gen_bool(0.2),
+                down: rng.gen_bool(0.5),
+                rot_right: rng.gen_bool(0.1),
+                rot_left: rng.gen_bool(0.1),
+            };
+            sim.step(keys);
+            inputs.push((sim.ticker, keys));
+        }
+        assert!(sim.ending.is_none() && sim.effect.is_none());
+        let held = inputs.len().saturating_sub(DUMP_INPUT_TICKS);
+        (sim, inputs.split_off(held))
+    }
+
+    fn names(pieces: &[Piece]) -> Vec<&'static str> {
+        pieces.iter().map(|p| p.kind.name).collect()
+    }
+
+    #[test]
+    fn a_dump_reads_back_as_the_run_it_was_taken_from() {
+        let (mut sim, inputs) = played(GameMode::Endless, 400);
+        assert!(sim.lines > 0 || sim.board.to_ascii().contains('#'));
+        let text = write(&sim, &inputs);
+        let mut dump = read(&text).unwrap();
+
+        assert_eq!(dump.inputs, inputs);
+        assert_eq!(dump.sim.board.to_ascii(), sim.board.to_ascii());
+        let place = |p: &Option<Piece>| p.map(|p| (p.kind.name, p.x, p.y, p.rot));
+        assert_eq!(place(&dump.sim.active_piece), place(&sim.active_piece));
+        assert_eq!(names(&dump.sim.next_pieces), names(&sim.next_pieces));
+        assert_eq!(
+            (dump.sim.score, dump.sim.lines, dump.sim.level()),
+            (sim.score, sim.lines, sim.level())
+        );
+        assert_eq!(dump.sim.countdown, COUNTDOWN_TICKS);
+
+        // and dumped again it's just the same
+        assert_eq!(write(&dump.sim, &dump.inputs), text);
+        // with the same pieces still to come
+        for _ in 0..30 {
+            assert_eq!(dump.sim.deal().name, sim.deal().name);
+        }
+    }
+
+    #[test]
+    fn the_dump_lists_each_tick_of_keys() {
+        let (sim, inputs) = played(GameMode::Endless, 100);
+        let text = write(&sim, &inputs);
+        let listed = text.lines().skip_while(|l| *l != "inputs:").skip(1);
+        assert_eq!(listed.count(), DUMP_INPUT_TICKS);
+        assert!(text.contains(&format!("\n{} ", sim.ticker)));
+
+        let keys = Inputs {
+            left: true,
+            rot_left: true,
+            ..Inputs::default()
+        };
+        assert!(write(&sim, &[(9, keys)]).ends_with("inputs:\n9 L---A\n"));
+    }
+
+    #[test]
+    fn damaged_dumps_are_turned_away() {
+        let (sim, inputs) = played(GameMode::Endless, 200);
+        let text = write(&sim, &inputs);
+        let with = |from: &str, to: &str| {
+            assert!(text.contains(from), "{:?} isn't in the dump", from);
+            read(&text.replacen(from, to, 1))
+        };
+
+        assert!(read("").is_err());
+        assert!(with(HEADER, "tet.rs dump 1").is_err());
+        assert!(with("mode: Endless", "mode: Nope").is_err());
+        assert!(with("mode: Endless", "mode: Puzzle").is_err());
+        assert!(with("randomizer: 7-bag", "randomizer: Loaded").is_err());
+        assert!(with("seed: 77", "seed: lots").is_err());
+        assert!(with("seed: 77\n", "").is_err());
+        assert!(with(&format!("level: {}", sim.level()), "level: 0").is_err());
+        assert!(with("next: ", "next: X ").is_err());
+        assert!(with("inputs:\n", "inputs:\n1 LR\n").is_err());
+    }
+
+    #[test]
+    fn an_active_piece_in_the_stack_is_turned_away() {
+        let (mut sim, inputs) = played(GameMode::Endless, 200);
+        let piece = sim.active_piece.expect("nothing in play");
+        let (x, y) = piece.cells().next().unwrap();
+        let mut field: Vec<Vec<u8>> = sim
+            .board
+            .to_ascii()
+            .lines()
+            .map(|l| l.as_bytes().to_vec())
+            .collect();
+        field[y as usize][x as usize] = b'#';
+        let field: Vec<String> = field
+            .into_iter()
+            .map(|row| String::from_utf8(row).unwrap())
+            .collect();
+        sim.board = Board::from_ascii(&field.join("\n")).unwrap();
+        assert!(read(&write(&sim, &inputs)).is_err());
+    }
+}
@@ -0,0 +1,349 @@
+//! Recording the keys held on every tick of a run, and playing them back.
+//!
+//! A [`Replay`] is everything a fresh [`Simulation`] needs to play a run out
+//! again: how it was set up, then the keys held on each call to
+//! [`Simulation::step`], countdown included. The fastest sprint is kept in
+//! [`GHOST_FILE`] and raced as a [`Ghost`], stepped alongside the live run so
+//! both boards are always on the same tick.
+//!
+//! A curated sprint is built into the game as [`DEMO`], for the menu's attract
+//! mode and `--demo` to play back the same way every time. It's recorded by
+//! `cargo run --example gen_demo`, which has to be run again whenever
+//! [`VERSION`] goes up.
+
+use std::error::Error;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::save::{invalid, Reader, Writer};
+use super::{Ending, Handling, Inputs, OpposingInputs, Simulation, MIN_PREVIEWS};
+use crate::mode::GameMode;
+use crate::paths;
+use crate::piece::randomizer::RandomizerKind;
+use crate::piece::rotation::RotationKind;
+
+/// Name of the fastest sprint's replay in the [data directory](paths::data_dir)
+pub const GHOST_FILE: &str = "tetrs_ghost.bin";
+
+/// Bumped whenever the layout below changes, turning older replays away
+pub const VERSION: u8 = 2;
+
+/// Replay of a sprint played by the attract mode's AI, built into the game
+pub const DEMO: &[u8] = include_bytes!("../../res/replays/demo.bin");
+
+/// How much of a ghost race is shown beside the playfield
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GhostDisplay {
+    /// No ghost is raced
+    Off,
+    /// Only a bar of the lines the ghost has cleared, and their count
+    Progress,
+    /// The ghost's field too, small and see-through
+    Board,
+}
+
+impl Default for GhostDisplay {
+    fn default() -> Self {
+        Self::Board
+    }
+}
+
+impl GhostDisplay {
+    pub const ALL: &'static [GhostDisplay] = &[
+        GhostDisplay::Off,
+        GhostDisplay::Progress,
+        GhostDisplay::Board,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Progress => "Progress",
+            Self::Board => "Board",
+        }
+    }
+}
+
+/// How a run was set up and the keys held on each of its ticks
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Replay {
+    pub mode: GameMode,
+    pub seed: u64,
+    pub randomizer: RandomizerKind,
+    pub rotation: RotationKind,
+    pub handling: Handling,
+    pub opposing_inputs: OpposingInputs,
+    pub instant_gravity: bool,
+
+    /// Keys held on each tick, from the first tick of the countdown
+    pub inputs: Vec<Inputs>,
+
+    /// Time the run finished in, once it has
+    pub millis: Option<u64>,
+}
+
+impl Replay {
+    /// Starts recording `sim`, which has to be fresh: nothing can be recorded
+    /// of the ticks it's already been stepped through
+    pub fn record(sim: &Simulation) -> Self {
+        Self {
+            mode: sim.mode,
+            seed: sim.seed,
+            randomizer: sim.randomizer,
+            rotation: sim.rotation,
+            handling: sim.handling,
+            opposing_inputs: sim.opposing_inputs,
+            instant_gravity: sim.instant_gravity,
+            inputs: Vec::new(),
+            millis: None,
+        }
+    }
+
+    /// Whether `sim` can be recorded from here: a single player run of the
+    /// seven tetrominoes that hasn't been stepped yet
+    pub fn can_record(sim: &Simulation) -> bool {
+        sim.ticker == 0
+            && sim.start_tick.is_none()
+            && sim.puzzle.is_none()
+            && sim.mode != GameMode::Puzzle
+            && sim.mode != GameMode::Versus
+            && sim.pieces.is_standard()
+    }
+
+    /// A fresh run set up like the recorded one, for the inputs to be played
+    /// into. Finesse isn't checked, as nobody sees the faults
+    pub fn simulation(&self) -> Simulation {
+        let mut sim = Simulation::new(self.mode, MIN_PREVIEWS, Some(self.seed), self.randomizer);
+        sim.set_rotation(self.rotation);
+        sim.handling = self.handling;
+        sim.opposing_inputs = self.opposing_inputs;
+        sim.instant_gravity = self.instant_gravity;
+        sim.finesse = false;
+        sim
+    }
+
+    /// The replay built into the game, or why it can't be played: one
+    /// recorded before the format last changed is turned away
+    pub fn demo() -> Result<Self, Box<dyn Error>> {
+        Self::from_bytes(DEMO)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        paths::create_parent(path)?;
+        std::fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    /// The replay as written to a file
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer(Vec::new());
+        w.0.extend_from_slice(b"tet.rs r");
+        w.u8(VERSION);
+
+        let mode = GameMode::ALL.iter().position(|&m| m == self.mode).unwrap();
+        w.u8(mode as u8);
+        w.u64(self.seed);
+        let randomizer = RandomizerKind::ALL
+            .iter()
+            .position(|&r| r == self.randomizer)
+            .unwrap();
+        w.u8(randomizer as u8);
+        let rotation = RotationKind::ALL
+            .iter()
+            .position(|&r| r == self.rotation)
+            .unwrap();
+        w.u8(rotation as u8);
+        w.u64(self.handling.das);
+        w.u64(self.handling.arr);
+        w.u8(match self.opposing_inputs {
+            OpposingInputs::Neutral => 0,
+            OpposingInputs::LastPressed => 1,
+            OpposingInputs::PreferLeft => 2,
+        });
+        w.u8(self.instant_gravity as u8);
+        w.u64(self.millis.unwrap_or(0));
+
+        // one byte a tick, a bit a key
+        w.u32(self.inputs.len() as u32);
+        for inputs in &self.inputs {
+            let keys = [
+                inputs.left,
+                inputs.right,
+                inputs.down,
+                inputs.rot_right,
+                inputs.rot_left,
+            ];
+            let bits = keys
+                .iter()
+                .enumerate()
+                .fold(0, |bits, (i, &held)| bits | ((held as u8) << i));
+            w.u8(bits);
+        }
+        w.0
+    }
+
+    /// Reads back a finished run's replay written to `path`
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// Reads back a finished run's replay from what was written to a file
+    pub fn from_bytes(contents: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut r = Reader(contents);
+
+        if r.take(8)? != b"tet.rs r" {
+            return Err(invalid());
+        }
+        let version = r.u8()?;
+        if version != VERSION {
+            return Err(Box::new(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("replay is version {}, expected {}", version, VERSION),
+            )));
+        }
+
+        let mode = *GameMode::ALL.get(r.u8()? as usize).ok_or_else(invalid)?;
+        if mode == GameMode::Puzzle || mode == GameMode::Versus {
+            return Err(invalid());
+        }
+        let seed = r.u64()?;
+        let randomizer = *RandomizerKind::ALL
+            .get(r.u8()? as usize)
+            .ok_or_else(invalid)?;
+        let rotation = *RotationKind::ALL
+            .get(r.u8()? as usize)
+            .ok_or_else(invalid)?;
+        let handling = Handling {
+            das: r.u64()?,
+            arr: r.u64()?,
+        };
+        let opposing_inputs = match r.u8()? {
+            0 => OpposingInputs::Neutral,
+            1 => OpposingInputs::LastPressed,
+            2 => OpposingInputs::PreferLeft,
+            _ => return Err(invalid()),
+        };
+        let instant_gravity = match r.u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(invalid()),
+        };
+        let millis = r.u64()?;
+
+        let count = r.u32()? as usize;
+        if r.0.len() != count {
+            return Err(invalid());
+        }
+        let inputs =
+            r.0.iter()
+                .map(|&bits| {
+                    let held = |i: u8| bits & (1 << i) != 0;
+                    Inputs {
+                        left: held(0),
+                        right: held(1),
+                        down: held(2),
+                        rot_right: held(3),
+                        rot_left: held(4),
+                    }
+                })
+                .collect();
+
+        Ok(Self {
+            mode,
+            seed,
+            randomizer,
+            rotation,
+            handling,
+            opposing_inputs,
+            instant_gravity,
+            inputs,
+            millis: Some(millis),
+        })
+    }
+}
+
+/// A recorded run played back a tick at a time alongside a live one. It
+/// doesn't draw anything or make any sound; whoever races it looks at
+/// [`sim`](Self::sim)
+pub struct Ghost {
+    pub sim: Simulation,
+
+    /// Keys to hold on each tick, and how many have been played
+    inputs: Vec<Inputs>,
+    played: usize,
+
+    /// Time the recorded run finished in
+    pub millis: Option<u64>,
+}
+
+impl Ghost {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            sim: replay.simulation(),
+            inputs: replay.inputs,
+            played: 0,
+            millis: replay.millis,
+        }
+    }
+
+    /// Plays the next tick of the recording. Once the recording runs out, or
+    /// the run ends some other way, the ghost stays as it is
+    pub fn step(&mut self) {
+        if self.sim.ending.is_some() {
+            return;
+        }
+        if let Some(&inputs) = self.inputs.get(self.played) {
+            self.played += 1;
+            self.sim.step(inputs);
+        }
+    }
+
+    /// Whether the ghost stopped without reaching the goal: it topped out,
+    /// or the recording ran out before the clock stopped
+    pub fn failed(&self) -> bool {
+        let ran_out = self.played == self.inputs.len() && self.sim.finish_time.is_none();
+        self.sim.topped_out() || self.sim.ending == Some(Ending::ToppedOut) || ran_out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_demo_plays_out_its_sprint_to_the_recorded_time() {
+        let replay = Replay::demo().expect("regenerate it with cargo run --example gen_demo");
+        let millis = replay.millis.expect("the demo has no time");
+
+        let mut sim = replay.simulation();
+        let (last, before) = replay.inputs.split_last().expect("the demo is empty");
+        for &inputs in before {
+            sim.step(inputs);
+            assert_eq!(sim.ending, None, "ended on tick {}", sim.ticker);
+        }
+        sim.step(*last);
+        assert_eq!(sim.ending, Some(Ending::Cleared { millis }));
+    }
+
+    #[test]
+    fn what_is_written_reads_back_the_same() {
+        let mut replay = Replay::demo().unwrap();
+        replay.inputs.truncate(100);
+        assert_eq!(Replay::from_bytes(&replay.to_bytes()).unwrap(), replay);
+    }
+
+    #[test]
+    fn another_version_is_turned_away() {
+        let mut bytes = DEMO.to_vec();
+        bytes[8] = VERSION - 1;
+        let error = Replay::from_bytes(&bytes).err().unwrap();
+        assert!(error.to_string().contains("version"), "{}", error);
+    }
+
+    #[test]
+    fn a_replay_cut_short_is_turned_away() {
+        assert!(Replay::from_bytes(&DEMO[..DEMO.len() - 1]).is_err());
+    }
+}
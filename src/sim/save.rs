@@ -0,0 +1,754 @@
+//! Setting a run aside and picking it up again later.
+//!
+//! Only what can't be worked out again is written down. The random number
+//! generator and randomizer, for one, are brought back by reseeding, then
+//! dealing as many pieces as they had dealt and drawing the garbage the stack
+//! was raised by in between, and the undo history is left behind.
+
+use std::convert::TryInto;
+use std::error::Error;
+use std::io;
+use std::path::Path;
+
+use rand::prelude::*;
+
+use super::{BoardEffect, BoardEffectType, Simulation, COUNTDOWN_TICKS, MAX_PREVIEWS};
+use crate::board::{Board, Cell, FIELD_HEIGHT, FIELD_WIDTH};
+use crate::game::stats::{Sample, MAX_CLEAR};
+use crate::mode::GameMode;
+use crate::paths;
+use crate::piece::randomizer::RandomizerKind;
+use crate::piece::rotation::RotationKind;
+use crate::piece::{Color, Piece, Tetromino};
+
+/// Name of the saved run in the [data directory](paths::data_dir)
+pub const SAVE_FILE: &str = "tetrs_save.bin";
+
+/// Bumped whenever the layout below changes, turning older saves away
+const VERSION: u8 = 9;
+
+/// Cell colors by the number they're saved as, less one
+const COLORS: [Color; 8] = [
+    Color::Red,
+    Color::Orange,
+    Color::Yellow,
+    Color::Green,
+    Color::Blue,
+    Color::Purple,
+    Color::Cyan,
+    Color::Grey,
+];
+
+/// Whether `sim` can be set aside: a single player run of the seven
+/// tetrominoes that isn't over yet
+pub fn can_save(sim: &Simulation) -> bool {
+    let running = sim.effect.as_ref().map_or(true, |e| !e.ty.ends_run());
+    running
+        && sim.ending.is_none()
+        && sim.puzzle.is_none()
+        && sim.mode != GameMode::Puzzle
+        && sim.mode != GameMode::Versus
+        && sim.pieces.is_standard()
+}
+
+/// Writes `sim` to `path`, along with whether the run was `assisted`
+pub fn save(sim: &Simulation, assisted: bool, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut w = Writer(Vec::new());
+    w.0.extend_from_slice(b"tet.rs s");
+    w.u8(VERSION);
+
+    // where the pieces come from
+    let mode = GameMode::ALL.iter().position(|&m| m == sim.mode).unwrap();
+    w.u8(mode as u8);
+    w.u64(sim.seed);
+    let randomizer = RandomizerKind::ALL
+        .iter()
+        .position(|&r| r == sim.randomizer)
+        .unwrap();
+    w.u8(randomizer as u8);
+    w.u64(sim.dealt);
+    w.u32(sim.raises.len() as u32);
+    for &(dealt, rows) in &sim.raises {
+        w.u64(dealt);
+        w.u32(rows);
+    }
+    let rotation = RotationKind::ALL
+        .iter()
+        .position(|&r| r == sim.rotation)
+        .unwrap();
+    w.u8(rotation as u8);
+
+    // field
+    for &cell in sim.board.cells.iter() {
+        w.u8(match cell {
+            Cell::Empty => 0,
+            Cell::Full(color) => COLORS.iter().position(|&c| c == color).unwrap() as u8 + 1,
+        });
+    }
+    for &tick in sim.placed_at.iter() {
+        w.u64(tick);
+    }
+
+    // pieces
+    w.piece(sim.active_piece);
+    w.piece(sim.spawned);
+    w.u8(sim.next_pieces.len() as u8);
+    for &piece in &sim.next_pieces {
+        w.piece(Some(piece));
+    }
+
+    // gravity
+    w.u32(sim.fall_ticks);
+    w.u32(sim.fall_counter);
+    w.u32(sim.fall_accel_ticks);
+    w.u32(sim.fall_accel_counter);
+    w.bool(sim.rotated);
+    w.u8(sim.last_horizontal as u8);
+
+    // progress
+    w.u64(sim.ticker);
+    w.u64(sim.score);
+    w.u32(sim.lines);
+    w.opt_u64(sim.start_tick);
+    w.opt_u64(sim.finish_time);
+    w.u32(sim.garbage_left);
+    w.u64(sim.rise_left);
+    w.u64(sim.rise_ticks);
+    w.bool(sim.frozen);
+    w.bool(sim.finesse);
+    w.bool(sim.instant_gravity);
+    w.u32(sim.faults);
+    w.u32(sim.piece_inputs);
+
+    // stats
+    let stats = &sim.stats;
+    for &count in stats.pieces.iter().chain(stats.clears.iter()) {
+        w.u32(count);
+    }
+    w.u32(stats.lines);
+    w.u32(stats.combo);
+    w.u32(stats.max_combo);
+    w.u64(stats.ticks);
+    w.u32(stats.decision_ticks.len() as u32);
+    for &ticks in &stats.decision_ticks {
+        w.u64(ticks);
+    }
+    w.u64(stats.spawned_at);
+    w.u32(stats.splits.len() as u32);
+    for &ticks in &stats.splits {
+        w.u64(ticks);
+    }
+    for &since in &stats.droughts.0 {
+        w.u32(since);
+    }
+    w.u32(stats.columns.0.len() as u32);
+    for &count in &stats.columns.0 {
+        w.u32(count);
+    }
+    w.u32(stats.samples.len() as u32);
+    for sample in &stats.samples {
+        w.u64(sample.ticks);
+        w.u64(sample.score);
+        w.u32(sample.lines);
+    }
+
+    // a line clear still animating
+    match &sim.effect {
+        Some(BoardEffect {
+            ty: BoardEffectType::LinesCleared { lines },
+            life,
+        }) => {
+            w.u8(lines.len() as u8);
+            for &row in lines {
+                w.u8(row as u8);
+            }
+            w.u64(*life);
+        }
+        _ => w.u8(0),
+    }
+
+    w.bool(assisted);
+
+    paths::create_parent(path)?;
+    std::fs::write(path, w.0)?;
+    Ok(())
+}
+
+/// Reads back a run saved to `path` and whether it was assisted. The run
+/// counts down again before it carries on
+pub fn load(path: &Path) -> Result<(Simulation, bool), Box<dyn Error>> {
+    let contents = std::fs::read(path)?;
+    let mut r = Reader(&contents);
+
+    if r.take(8)? != b"tet.rs s" {
+        return Err(invalid());
+    }
+    let version = r.u8()?;
+    if version != VERSION {
+        return Err(Box::new(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("save is version {}, expected {}", version, VERSION),
+        )));
+    }
+
+    // where the pieces come from
+    let mode = *GameMode::ALL.get(r.u8()? as usize).ok_or_else(invalid)?;
+    if mode == GameMode::Puzzle || mode == GameMode::Versus {
+        return Err(invalid());
+    }
+    let seed = r.u64()?;
+    let randomizer = *RandomizerKind::ALL
+        .get(r.u8()? as usize)
+        .ok_or_else(invalid)?;
+    let dealt = r.u64()?;
+    let mut raises = Vec::new();
+    for _ in 0..r.u32()? {
+        let raise = (r.u64()?, r.u32()?);
+        // in the order they came, each before the pieces still to be dealt
+        let after = raises.last().map_or(0, |&(before, _)| before);
+        if raise.0 < after || raise.0 > dealt || raise.1 == 0 || raise.1 > FIELD_HEIGHT {
+            return Err(invalid());
+        }
+        raises.push(raise);
+    }
+    let rotation = *RotationKind::ALL
+        .get(r.u8()? as usize)
+        .ok_or_else(invalid)?;
+    let mut sim = Simulation::new(mode, MAX_PREVIEWS, Some(seed), randomizer);
+    sim.rotation = rotation;
+    let scale = mode.piece_scale();
+
+    // field
+    for cell in sim.board.cells.iter_mut() {
+        *cell = match r.u8()? {
+            0 => Cell::Empty,
+            n => Cell::Full(*COLORS.get(n as usize - 1).ok_or_else(invalid)?),
+        };
+    }
+    for tick in sim.placed_at.iter_mut() {
+        *tick = r.u64()?;
+    }
+
+    // pieces
+    sim.active_piece = r.piece(scale, rotation)?;
+    sim.spawned = r.piece(scale, rotation)?;
+    let previews = r.u8()? as usize;
+    if previews == 0 || previews > MAX_PREVIEWS {
+        return Err(invalid());
+    }
+    sim.next_pieces.clear();
+    for _ in 0..previews {
+        let piece = r.piece(scale, rotation)?.ok_or_else(invalid)?;
+        sim.next_pieces.push(piece);
+    }
+    if let Some(piece) = &sim.active_piece {
+        if !sim.board.fits(piece) {
+            return Err(invalid());
+        }
+    }
+
+    // gravity
+    sim.fall_ticks = r.u32()?;
+    sim.fall_counter = r.u32()?;
+    sim.fall_accel_ticks = r.u32()?;
+    sim.fall_accel_counter = r.u32()?;
+    sim.rotated = r.bool()?;
+    sim.last_horizontal = r.u8()? as i8;
+    if sim.fall_ticks == 0 || sim.fall_ticks > 20 || sim.fall_counter == 0 {
+        return Err(invalid());
+    }
+
+    // progress
+    sim.ticker = r.u64()?;
+    sim.score = r.u64()?;
+    sim.lines = r.u32()?;
+    sim.start_tick = r.opt_u64()?;
+    sim.finish_time = r.opt_u64()?;
+    sim.garbage_left = r.u32()?;
+    sim.rise_left = r.u64()?;
+    sim.rise_ticks = r.u64()?;
+    sim.frozen = r.bool()?;
+    sim.finesse = r.bool()?;
+    sim.instant_gravity = r.bool()?;
+    sim.faults = r.u32()?;
+    sim.piece_inputs = r.u32()?;
+    if sim.start_tick.map_or(false, |start| start > sim.ticker)
+        || sim.placed_at.iter().any(|&tick| tick > sim.ticker)
+        || sim.garbage_left > FIELD_HEIGHT
+        || sim.rise_left > sim.rise_ticks
+    {
+        return Err(invalid());
+    }
+
+    // stats
+    let stats = &mut sim.stats;
+    for count in stats.pieces.iter_mut().chain(stats.clears.iter_mut()) {
+        *count = r.u32()?;
+    }
+    stats.lines = r.u32()?;
+    stats.combo = r.u32()?;
+    stats.max_combo = r.u32()?;
+    stats.ticks = r.u64()?;
+    for _ in 0..r.u32()? {
+        stats.decision_ticks.push(r.u64()?);
+    }
+    stats.spawned_at = r.u64()?;
+    if stats.spawned_at > stats.ticks {
+        return Err(invalid());
+    }
+    for _ in 0..r.u32()? {
+        stats.splits.push(r.u64()?);
+    }
+    for since in stats.droughts.0.iter_mut() {
+        *since = r.u32()?;
+    }
+    let columns = r.u32()?;
+    if columns > FIELD_WIDTH {
+        return Err(invalid());
+    }
+    for _ in 0..columns {
+        stats.columns.0.push(r.u32()?);
+    }
+    for _ in 0..r.u32()? {
+        stats.samples.push(Sample {
+            ticks: r.u64()?,
+            score: r.u64()?,
+            lines: r.u32()?,
+        });
+    }
+
+    // a line clear still animating
+    let cleared = r.u8()? as usize;
+    if cleared > 0 {
+        if cleared > MAX_CLEAR * scale as usize {
+            return Err(invalid());
+        }
+        let mut lines = Vec::with_capacity(cleared);
+        for _ in 0..cleared {
+            let row = r.u8()? as i8;
+            if row < 0 || row as u32 >= FIELD_HEIGHT {
+                return Err(invalid());
+            }
+            lines.push(row);
+        }
+        let life = r.u64()?;
+        if life == 0 {
+            return Err(invalid());
+        }
+        sim.effect = Some(BoardEffect {
+            ty: BoardEffectType::LinesCleared { lines },
+            life,
+        });
+    }
+
+    let assisted = r.bool()?;
+    if !r.0.is_empty() {
+        // extraneous data
+        return Err(invalid());
+    }
+
+    // deal the same pieces and raise the same garbage again, in the same
+    // order, to get the generator back where it was. Every piece dealt is
+    // either placed, falling or waiting in the queue
+    let most = sim.stats.total_pieces() as u64 + MAX_PREVIEWS as u64 + 1;
+    if dealt > most {
+        return Err(invalid());
+    }
+    sim.rng = StdRng::seed_from_u64(seed);
+    sim.dealer = randomizer.build();
+    sim.dealt = 0;
+    Board::default().add_garbage(mode.garbage_rows(), &mut sim.rng);
+    let mut garbage = Board::default();
+    for &(before, rows) in &raises {
+        while sim.dealt < before {
+            sim.deal();
+        }
+        garbage.raise(rows, &mut sim.rng);
+    }
+    while sim.dealt < dealt {
+        sim.deal();
+    }
+    sim.raises = raises;
+    sim.countdown = COUNTDOWN_TICKS;
+
+    Ok((sim, assisted))
+}
+
+pub(crate) fn invalid() -> Box<dyn Error> {
+    Box::new(io::Error::new(io::ErrorKind::InvalidData, "invalid format"))
+}
+
+/// Builds up a file, numbers little endian
+pub(crate) struct Writer(pub(crate) Vec<u8>);
+
+impl Writer {
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn opt_u64(&mut self, value: Option<u64>) {
+        self.bool(value.is_some());
+        self.u64(value.unwrap_or(0));
+    }
+
+    /// A piece as its shape, one past its index so 0 can stand for None, then
+    /// its rotation, position and scale
+    fn piece(&mut self, piece: Option<Piece>) {
+        match piece {
+            Some(piece) => {
+                let kind = piece.tetromino().unwrap();
+                self.u8(kind as u8 + 1);
+                self.u8(piece.rot);
+                self.u8(piece.x as u8);
+                self.u8(piece.y as u8);
+                self.u8(piece.scale);
+            }
+            None => self.u8(0),
+        }
+    }
+}
+
+/// Reads back what a [`Writer`] wrote, holding what's left unread
+pub(crate) struct Reader<'a>(pub(crate) &'a [u8]);
+
+impl<'a> Reader<'a> {
+    /// The next `len` bytes, or an error if the file ends before them
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], Box<dyn Error>> {
+        if self.0.len() < len {
+            return Err(invalid());
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, Box<dyn Error>> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, Box<dyn Error>> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid()),
+        }
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, Box<dyn Error>> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, Box<dyn Error>> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn opt_u64(&mut self) -> Result<Option<u64>, Box<dyn Error>> {
+        let some = self.bool()?;
+        let value = self.u64()?;
+        Ok(if some { Some(value) } else { None })
+    }
+
+    /// A piece written by [`Writer::piece`], which has to be played at `scale`
+    fn piece(
+        &mut self,
+        scale: u8,
+        rotation: RotationKind,
+    ) -> Result<Option<Piece>, Box<dyn Error>> {
+        let kind = self.u8()? as usize;
+        if kind == 0 {
+            return Ok(None);
+        }
+        let kind = Tetromino::ALL.get(kind - 1).ok_or_else(invalid)?.def();
+        let rot = self.u8()?;
+        let x = self.u8()? as i8;
+        let y = self.u8()? as i8;
+        if rot >= 4 || self.u8()? != scale {
+            return Err(invalid());
+        }
+        Ok(Some(Piece {
+            kind,
+            rot,
+            x,
+            y,
+            scale,
+            rotation,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::best_placement;
+    use crate::sim::{Event, Inputs};
+
+    /// A run of `mode` after `ticks` of the demo player at the keys
+    fn played(mode: GameMode, seed: u64, ticks: u32) -> Simulation {
+        let mut sim = Simulation::new(mode, MAX_PREVIEWS, Some(seed), RandomizerKind::Bag);
+        let mut target = None;
+        for tick in 0..ticks {
+            // let go every other tick so each press counts
+            let press = tick % 2 == 0;
+            let mut keys = Inputs::default();
+            if let (Some(piece), Some((x, rot))) = (sim.active_piece, target) {
+                if piece.rot != rot {
+                    keys.rot_right = press;
+                } else if piece.x != x {
+                    keys.left = press && piece.x > x;
+                    keys.right = press && piece.x < x;
+                } else {
+                    keys.down = true;
+                }
+            }
+            for event in sim.step(keys) {
+                if let Event::PieceSpawned { piece } = event {
+                    target = best_placement(&sim.board, &piece);
+                }
+            }
+        }
+        assert!(can_save(&sim), "{:?} topped out", mode);
+        sim
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("tetrs_save_{}_{}.bin", name, std::process::id()))
+    }
+
+    /// A piece boiled down to what it is and where
+    fn placed(piece: Option<Piece>) -> Option<(&'static str, u8, i8, i8, u8)> {
+        piece.map(|p| (p.kind.name, p.rot, p.x, p.y, p.scale))
+    }
+
+    /// The rows and time left of a line clear still animating
+    fn clearing(sim: &Simulation) -> Option<(Vec<i8>, u64)> {
+        match &sim.effect {
+            Some(BoardEffect {
+                ty: BoardEffectType::LinesCleared { lines },
+                life,
+            }) => Some((lines.clone(), *life)),
+            _ => None,
+        }
+    }
+
+    fn assert_same(a: &mut Simulation, b: &mut Simulation) {
+        assert_eq!(a.mode, b.mode);
+        assert_eq!(a.seed, b.seed);
+        assert_eq!(a.randomizer, b.randomizer);
+        assert_eq!(a.rotation, b.rotation);
+        assert_eq!(a.dealt, b.dealt);
+        assert!(a.board == b.board);
+        assert_eq!(&a.placed_at[..], &b.placed_at[..]);
+        assert_eq!(placed(a.active_piece), placed(b.active_piece));
+        assert_eq!(placed(a.spawned), placed(b.spawned));
+        let queue =
+            |s: &Simulation| -> Vec<_> { s.next_pieces.iter().map(|&p| placed(Some(p))).collect() };
+        assert_eq!(queue(a), queue(b));
+        assert_eq!(
+            (
+                a.fall_ticks,
+                a.fall_counter,
+                a.fall_accel_ticks,
+                a.fall_accel_counter
+            ),
+            (
+                b.fall_ticks,
+                b.fall_counter,
+                b.fall_accel_ticks,
+                b.fall_accel_counter
+            )
+        );
+        assert_eq!(
+            (a.rotated, a.last_horizontal),
+            (b.rotated, b.last_horizontal)
+        );
+        assert_eq!((a.ticker, a.score, a.lines), (b.ticker, b.score, b.lines));
+        assert_eq!(
+            (a.start_tick, a.finish_time, a.garbage_left),
+            (b.start_tick, b.finish_time, b.garbage_left)
+        );
+        assert_eq!((a.rise_left, a.rise_ticks), (b.rise_left, b.rise_ticks));
+        assert_eq!(a.raises, b.raises);
+        assert_eq!(
+            (a.frozen, a.finesse, a.instant_gravity),
+            (b.frozen, b.finesse, b.instant_gravity)
+        );
+        assert_eq!((a.faults, a.piece_inputs), (b.faults, b.piece_inputs));
+        assert_eq!(a.stats, b.stats);
+        assert_eq!(clearing(a), clearing(b));
+
+        // and the pieces still to come
+        let dealt = |s: &mut Simulation| -> Vec<_> { (0..50).map(|_| s.deal().name).collect() };
+        assert_eq!(dealt(a), dealt(b));
+    }
+
+    #[test]
+    fn a_saved_run_loads_back_the_same() {
+        for (seed, &mode) in [
+            GameMode::Endless,
+            GameMode::Marathon,
+            GameMode::Cheese { rows: 6 },
+            GameMode::Rising,
+        ]
+        .iter()
+        .enumerate()
+        {
+            for &ticks in &[0, 700, 3_000] {
+                let mut sim = played(mode, seed as u64, ticks);
+                let path = temp_path("round_trip");
+                save(&sim, seed % 2 == 0, &path).unwrap();
+                let (mut loaded, assisted) = load(&path).unwrap();
+                std::fs::remove_file(&path).unwrap();
+
+                assert_eq!(assisted, seed % 2 == 0);
+                assert_eq!(loaded.countdown, COUNTDOWN_TICKS);
+                assert_same(&mut sim, &mut loaded);
+            }
+        }
+    }
+
+    #[test]
+    fn a_rising_run_deals_on_as_if_it_was_never_set_aside() {
+        let mut sim = played(GameMode::Rising, 4, 3_000);
+        assert!(sim.raises.len() > 2, "the stack never rose");
+        let path = temp_path("rising");
+        save(&sim, false, &path).unwrap();
+        let (mut loaded, _) = load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.raises, sim.raises);
+        assert_same(&mut sim, &mut loaded);
+
+        // and the garbage still to come
+        let mut sim_rows = Board::default();
+        let mut loaded_rows = Board::default();
+        sim_rows.raise(FIELD_HEIGHT, &mut sim.rng);
+        loaded_rows.raise(FIELD_HEIGHT, &mut loaded.rng);
+        assert!(sim_rows == loaded_rows);
+    }
+
+    #[test]
+    fn raises_out_of_order_are_turned_away() {
+        let mut sim = played(GameMode::Rising, 4, 3_000);
+        let dealt = sim.dealt;
+        for raises in &[
+            vec![(3, 1), (2, 1)],
+            vec![(dealt + 1, 1)],
+            vec![(1, 0)],
+            vec![(1, FIELD_HEIGHT + 1)],
+        ] {
+            let path = temp_path("raises");
+            sim.raises = raises.clone();
+            save(&sim, false, &path).unwrap();
+            assert!(load(&path).is_err(), "{:?}", raises);
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn cells_placed_after_the_clock_are_turned_away() {
+        let mut sim = played(GameMode::Endless, 5, 1_000);
+        let last = sim.placed_at.len() - 1;
+        sim.placed_at[last] = sim.ticker + 1;
+        let path = temp_path("placed_at");
+        save(&sim, false, &path).unwrap();
+        assert!(load(&path).is_err());
+
+        // placed right as the clock stands is fine
+        sim.placed_at[last] = sim.ticker;
+        save(&sim, false, &path).unwrap();
+        assert!(load(&path).is_ok());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_loaded_run_plays_on_the_same() {
+        let mut sim = played(GameMode::Endless, 9, 1_500);
+        let path = temp_path("plays_on");
+        save(&sim, false, &path).unwrap();
+        let (mut loaded, _) = load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        while loaded.countdown > 0 {
+            loaded.step(Inputs::default());
+        }
+        let down = Inputs {
+            down: true,
+            ..Inputs::default()
+        };
+        for _ in 0..500 {
+            sim.step(down);
+            loaded.step(down);
+        }
+        assert_eq!(sim.board.to_ascii(), loaded.board.to_ascii());
+        assert_eq!(sim.score, loaded.score);
+    }
+
+    /// The bytes of a run saved part way through
+    fn saved_bytes() -> Vec<u8> {
+        let path = temp_path("bytes");
+        save(&played(GameMode::Endless, 3, 1_000), false, &path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    fn load_bytes(name: &str, bytes: &[u8]) -> Result<(Simulation, bool), Box<dyn Error>> {
+        let path = temp_path(name);
+        std::fs::write(&path, bytes).unwrap();
+        let result = load(&path);
+        std::fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn a_cut_short_file_is_turned_away() {
+        let bytes = saved_bytes();
+        for len in (0..bytes.len()).step_by(7) {
+            assert!(load_bytes("short", &bytes[..len]).is_err(), "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn trailing_bytes_are_turned_away() {
+        let mut bytes = saved_bytes();
+        bytes.push(0);
+        assert!(load_bytes("trailing", &bytes).is_err());
+    }
+
+    #[test]
+    fn another_file_is_turned_away() {
+        let mut bytes = saved_bytes();
+        bytes[..8].copy_from_slice(b"tet.rs h");
+        assert!(load_bytes("magic", &bytes).is_err());
+    }
+
+    #[test]
+    fn another_version_is_turned_away() {
+        let mut bytes = saved_bytes();
+        bytes[8] = VERSION - 1;
+        let error = load_bytes("version", &bytes).err().unwrap();
+        assert!(error.to_string().contains("version"), "{}", error);
+    }
+
+    #[test]
+    fn garbage_in_the_fields_is_turned_away() {
+        let bytes = saved_bytes();
+        // the mode, the randomizer and the first cell of the field
+        for &at in &[9, 18, 28] {
+            let mut bytes = bytes.clone();
+            bytes[at] = 0xff;
+            assert!(load_bytes("garbage", &bytes).is_err(), "byte {}", at);
+        }
+    }
+}
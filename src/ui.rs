@@ -0,0 +1,5 @@
+pub mod callout;
+pub mod help;
+pub mod layout;
+pub mod popup;
+pub mod unlock;
@@ -0,0 +1,292 @@
+//! Names of clears, called out big over the middle of the playfield.
+//!
+//! What a clear is called is worked out by [`callout`] from the clear alone,
+//! so anything that names clears, like a results screen, can do it the same
+//! way. [`Callouts`] shows one at a time: a clear called out while the last
+//! one is still up replaces it, since two on top of each other can't be read.
+
+use crate::graphics::{
+    mesh::MeshBuilder,
+    text::{self, Align, Anchor, TextStyle},
+    theme::Theme,
+};
+
+/// Ticks a callout stays on screen
+pub const CALLOUT_LIFE_TICKS: u32 = 20;
+
+/// How much bigger a callout has grown by the time it's gone
+const GROWTH: f32 = 0.25;
+
+/// Size of lines that aren't the clear's name, like the combo, relative to it
+const MINOR_SIZE: f32 = 0.6;
+
+/// A clear, as far as what it's called goes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Clear {
+    pub lines: u32,
+
+    /// Whether the piece was spun into place
+    pub spin: bool,
+
+    /// Whether it follows a tetris or a spin with nothing smaller between
+    pub back_to_back: bool,
+
+    /// Line clearing placements in a row, this one included
+    pub combo: u32,
+
+    /// Whether nothing was left on the field
+    pub perfect: bool,
+}
+
+/// How much a line of a callout stands out
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Emphasis {
+    /// Said on the side, small and in the text colour
+    Minor,
+    /// The clear itself, big and in the highlight colour
+    Major,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CalloutLine {
+    pub text: String,
+    pub emphasis: Emphasis,
+}
+
+impl CalloutLine {
+    fn new(text: &str, emphasis: Emphasis) -> Self {
+        Self {
+            text: text.to_string(),
+            emphasis,
+        }
+    }
+}
+
+/// Lines calling out `clear`, top first. Empty if there's nothing to say
+pub fn callout(clear: &Clear) -> Vec<CalloutLine> {
+    let mut lines = Vec::new();
+    if clear.back_to_back {
+        lines.push(CalloutLine::new("BACK-TO-BACK", Emphasis::Minor));
+    }
+
+    let name = match clear.lines {
+        0 => None,
+        1 => Some("SINGLE"),
+        2 => Some("DOUBLE"),
+        3 => Some("TRIPLE"),
+        4 => Some("TETRIS"),
+        // only pentominoes clear more
+        _ => Some("PENTRIS"),
+    };
+    match (clear.spin, name) {
+        (true, Some(name)) => lines.push(CalloutLine::new(
+            &format!("T-SPIN {}", name),
+            Emphasis::Major,
+        )),
+        (true, None) => lines.push(CalloutLine::new("T-SPIN", Emphasis::Major)),
+        (false, Some(name)) => lines.push(CalloutLine::new(name, Emphasis::Major)),
+        (false, None) => (),
+    }
+
+    if clear.perfect {
+        lines.push(CalloutLine::new("PERFECT CLEAR", Emphasis::Major));
+    }
+    if clear.combo > 1 {
+        lines.push(CalloutLine::new(
+            &format!("COMBO x{}", clear.combo),
+            Emphasis::Minor,
+        ));
+    }
+    lines
+}
+
+/// The callout showing, if there is one
+#[derive(Clone, Debug, Default)]
+pub struct Callouts {
+    current: Option<(Vec<CalloutLine>, u32)>,
+}
+
+impl Callouts {
+    /// Calls out `clear`, in place of whatever was up
+    pub fn push(&mut self, clear: &Clear) {
+        let lines = callout(clear);
+        self.current = if lines.is_empty() {
+            None
+        } else {
+            Some((lines, 0))
+        };
+    }
+
+    /// Ages the callout by a tick, dropping it once it's done
+    pub fn tick(&mut self) {
+        if let Some((_, age)) = &mut self.current {
+            *age += 1;
+            if *age >= CALLOUT_LIFE_TICKS {
+                self.current = None;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.current = None;
+    }
+
+    /// Adds the callout to `mesh`, centred on `x` and `y`, its name `size`
+    /// wide. It grows as it ages, fading into the `theme`'s background
+    pub fn render(&self, mesh: &mut MeshBuilder, x: f32, y: f32, size: f32, theme: &Theme) {
+        let (lines, age) = match &self.current {
+            Some((lines, age)) => (lines, *age),
+            None => return,
+        };
+        let progress = age as f32 / CALLOUT_LIFE_TICKS as f32;
+        let size = size * (1.0 + GROWTH * progress);
+        let line_size = |line: &CalloutLine| match line.emphasis {
+            Emphasis::Minor => size * MINOR_SIZE,
+            Emphasis::Major => size,
+        };
+
+        // glyphs are half as tall as they're wide
+        let height: f32 = lines.iter().map(|line| line_size(line) / 2.0).sum();
+        let mut top = y - height / 2.0;
+        for line in lines {
+            let color = match line.emphasis {
+                Emphasis::Minor => theme.active_text,
+                Emphasis::Major => theme.highlight,
+            };
+            let style = TextStyle::new(line_size(line), theme.fade(color, progress))
+                .align(Align::Center)
+                .anchor(Anchor::Top)
+                .shadow(theme.fade(theme.shadow, progress));
+            text::render_styled(mesh, &line.text, x, top, &style);
+            top += line_size(line) / 2.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn said(clear: Clear) -> Vec<(String, Emphasis)> {
+        callout(&clear)
+            .into_iter()
+            .map(|line| (line.text, line.emphasis))
+            .collect()
+    }
+
+    fn major(text: &str) -> (String, Emphasis) {
+        (text.to_string(), Emphasis::Major)
+    }
+
+    fn minor(text: &str) -> (String, Emphasis) {
+        (text.to_string(), Emphasis::Minor)
+    }
+
+    fn lines(lines: u32) -> Clear {
+        Clear {
+            lines,
+            ..Clear::default()
+        }
+    }
+
+    #[test]
+    fn each_clear_is_called_by_its_size() {
+        assert_eq!(said(lines(0)), vec![]);
+        assert_eq!(said(lines(1)), vec![major("SINGLE")]);
+        assert_eq!(said(lines(2)), vec![major("DOUBLE")]);
+        assert_eq!(said(lines(3)), vec![major("TRIPLE")]);
+        assert_eq!(said(lines(4)), vec![major("TETRIS")]);
+        assert_eq!(said(lines(5)), vec![major("PENTRIS")]);
+    }
+
+    #[test]
+    fn spins_are_called_even_without_a_clear() {
+        let spin = |lines| Clear {
+            lines,
+            spin: true,
+            ..Clear::default()
+        };
+        assert_eq!(said(spin(0)), vec![major("T-SPIN")]);
+        assert_eq!(said(spin(2)), vec![major("T-SPIN DOUBLE")]);
+    }
+
+    #[test]
+    fn back_to_back_goes_above_and_the_combo_below() {
+        let clear = Clear {
+            lines: 4,
+            back_to_back: true,
+            combo: 3,
+            perfect: true,
+            spin: false,
+        };
+        assert_eq!(
+            said(clear),
+            vec![
+                minor("BACK-TO-BACK"),
+                major("TETRIS"),
+                major("PERFECT CLEAR"),
+                minor("COMBO x3"),
+            ]
+        );
+
+        // a first clear is no combo yet
+        let first = Clear {
+            combo: 1,
+            ..lines(1)
+        };
+        assert_eq!(said(first), vec![major("SINGLE")]);
+    }
+
+    #[test]
+    fn a_callout_lasts_its_life_then_goes() {
+        let mut callouts = Callouts::default();
+        callouts.push(&lines(4));
+        for _ in 1..CALLOUT_LIFE_TICKS {
+            callouts.tick();
+        }
+        assert!(callouts.current.is_some());
+        callouts.tick();
+        assert_eq!(callouts.current, None);
+    }
+
+    #[test]
+    fn a_new_callout_replaces_the_last() {
+        let mut callouts = Callouts::default();
+        callouts.push(&lines(4));
+        for _ in 0..5 {
+            callouts.tick();
+        }
+        callouts.push(&lines(2));
+        let (shown, age) = callouts.current.clone().unwrap();
+        assert_eq!(shown, callout(&lines(2)));
+        assert_eq!(age, 0);
+
+        // and one with nothing to say takes the last away
+        callouts.push(&lines(0));
+        assert_eq!(callouts.current, None);
+    }
+
+    #[test]
+    fn a_callout_grows_as_it_ages() {
+        let theme = Theme::default();
+        let width = |callouts: &Callouts| {
+            let mut mesh = MeshBuilder::new();
+            callouts.render(&mut mesh, 0.5, 0.5, 0.1, &theme);
+            let xs = mesh.vertices().iter().map(|v| v.position[0]);
+            xs.clone().fold(f32::MIN, f32::max) - xs.fold(f32::MAX, f32::min)
+        };
+
+        let mut callouts = Callouts::default();
+        let mut mesh = MeshBuilder::new();
+        callouts.render(&mut mesh, 0.5, 0.5, 0.1, &theme);
+        assert!(mesh.is_empty());
+
+        callouts.push(&lines(4));
+        let young = width(&callouts);
+        for _ in 1..CALLOUT_LIFE_TICKS {
+            callouts.tick();
+        }
+        let old = width(&callouts);
+        assert!(old > young * (1.0 + GROWTH * 0.9), "{} then {}", young, old);
+    }
+}
@@ -0,0 +1,185 @@
+//! What the How to play screen says.
+//!
+//! The screen is written as [`SECTIONS`], plain data, and [`pages`] lays it
+//! out in rows: paragraphs wrapped to the page, the keys each action is bound
+//! to right now, and the points clears are worth. Editing or translating the
+//! text never touches how it's drawn.
+
+use crate::board::clear_score;
+use crate::game::input::{Action, KeyMap, PadButton, PadMap};
+use crate::graphics::text::{wrap_text, TextStyle};
+use crate::piece::{rotation::RotationKind, Piece, Tetromino};
+use crate::ui::callout::{callout, Clear};
+
+/// Rows a diagram takes up, enough for any of the seven lying flat
+pub const DIAGRAM_ROWS: usize = 2;
+
+/// Something a section says
+pub enum Entry {
+    /// A paragraph, wrapped to the width of the page
+    Text(&'static str),
+    /// A line of text with a piece drawn before it
+    Diagram(Tetromino, &'static str),
+    /// The key and button each action is bound to, a line each
+    Keys(&'static [Action]),
+    /// Points for each size of clear, a line each
+    Scoring,
+}
+
+/// A heading and what's under it
+pub struct Section {
+    pub title: &'static str,
+    pub entries: &'static [Entry],
+}
+
+pub const SECTIONS: &[Section] = &[
+    Section {
+        title: "Goal",
+        entries: &[
+            Entry::Text(
+                "Pieces fall into the well one at a time. Fill a row from wall to wall \
+                 and it clears. The game ends when the stack reaches the top.",
+            ),
+            Entry::Diagram(Tetromino::I, "Clear four rows at once for a tetris"),
+        ],
+    },
+    Section {
+        title: "Controls",
+        entries: &[
+            Entry::Diagram(Tetromino::T, "Rotate to fit the piece in place"),
+            Entry::Keys(&[
+                Action::Left,
+                Action::Right,
+                Action::RotateRight,
+                Action::RotateLeft,
+                Action::Down,
+                Action::Back,
+                Action::Restart,
+            ]),
+        ],
+    },
+    Section {
+        title: "Scoring",
+        entries: &[
+            Entry::Scoring,
+            Entry::Text("The level goes up every ten lines, and pieces fall faster with each."),
+        ],
+    },
+    Section {
+        title: "Tips",
+        entries: &[
+            Entry::Text("Keep the stack flat, and leave one column open for the long piece."),
+            Entry::Diagram(Tetromino::S, "Watch the next pieces and plan ahead"),
+            Entry::Text("Holding a direction moves the piece faster after a moment."),
+        ],
+    },
+];
+
+/// One row of a page, top to bottom
+#[derive(Clone, Debug, PartialEq)]
+pub enum Row {
+    Heading(&'static str),
+    Text(String),
+    /// A caption with a piece drawn before it, [`DIAGRAM_ROWS`] tall
+    Diagram(Tetromino, &'static str),
+    /// Room between sections
+    Blank,
+}
+
+impl Row {
+    /// Rows of the page this takes up
+    pub fn height(&self) -> usize {
+        match self {
+            Row::Diagram(..) => DIAGRAM_ROWS,
+            _ => 1,
+        }
+    }
+}
+
+impl Entry {
+    /// The rows this entry takes, text wrapped to fit `width` drawn in `style`
+    fn rows(&self, keys: &KeyMap, pad: &PadMap, style: &TextStyle, width: f32) -> Vec<Row> {
+        match self {
+            Entry::Text(text) => wrap_text(text, style, width)
+                .into_iter()
+                .map(Row::Text)
+                .collect(),
+            Entry::Diagram(piece, caption) => vec![Row::Diagram(*piece, caption)],
+            Entry::Keys(actions) => actions
+                .iter()
+                .map(|&action| {
+                    let button = pad.button(action).map_or("-", PadButton::name);
+                    Row::Text(format!(
+                        "{}: {:?} / {}",
+                        action.name(),
+                        keys.key(action),
+                        button
+                    ))
+                })
+                .collect(),
+            Entry::Scoring => (1..=4)
+                .map(|lines| {
+                    let clear = Clear {
+                        lines,
+                        ..Clear::default()
+                    };
+                    let name = callout(&clear)
+                        .into_iter()
+                        .next()
+                        .map_or(String::new(), |line| line.text);
+                    Row::Text(format!("{}: {}", name, clear_score(lines as usize)))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// `sections` laid out in pages of at most `rows_per_page` rows, for the keys
+/// as they're bound now. A section starts on a new page unless it fits whole
+/// on the current one, and only a section too long for any page is split
+pub fn pages(
+    sections: &[Section],
+    keys: &KeyMap,
+    pad: &PadMap,
+    style: &TextStyle,
+    width: f32,
+    rows_per_page: usize,
+) -> Vec<Vec<Row>> {
+    let height = |rows: &[Row]| rows.iter().map(Row::height).sum::<usize>();
+    let mut pages: Vec<Vec<Row>> = vec![Vec::new()];
+
+    for section in sections {
+        let mut rows = vec![Row::Heading(section.title)];
+        for entry in section.entries {
+            rows.extend(entry.rows(keys, pad, style, width));
+        }
+
+        let page = pages.last_mut().unwrap();
+        let gap = if page.is_empty() { 0 } else { 1 };
+        if height(page) + gap + height(&rows) > rows_per_page && !page.is_empty() {
+            pages.push(Vec::new());
+        } else if gap > 0 {
+            page.push(Row::Blank);
+        }
+
+        for row in rows {
+            let page = pages.last_mut().unwrap();
+            if height(page) + row.height() > rows_per_page && !page.is_empty() {
+                pages.push(Vec::new());
+            }
+            pages.last_mut().unwrap().push(row);
+        }
+    }
+    pages
+}
+
+/// Cells `piece` covers lying as it spawns, as columns and rows from the top
+/// left of the smallest box around it
+pub fn diagram_cells(piece: Tetromino) -> Vec<(usize, usize)> {
+    let piece = Piece::new(piece.def(), 1, RotationKind::default());
+    let (left, top, width, height) = piece.bounds();
+    (0..width * height)
+        .map(|i| (i % width, i / width))
+        .filter(|&(x, y)| piece.filled_at(left + x, top + y))
+        .collect()
+}
@@ -0,0 +1,953 @@
+//! Screen positions shared by the game states.
+//!
+//! Everything is in the world space set up by the states' projection: the
+//! playfield spans 0..1 on both axes, y grows downwards, and one x unit is
+//! half as wide on screen as one y unit (so a glyph of `size` is `size` wide
+//! and `size / 2` tall, less its blank columns outside monospace text). The
+//! visible area grows sideways with the aspect ratio.
+
+use crate::board::{FIELD_HEIGHT, FIELD_WIDTH};
+use crate::graphics::text::measure_text;
+
+/// Default UI scale factor
+pub const DEFAULT_SCALE: f32 = 1.0;
+
+/// Smallest and largest UI scale asked for. Whatever is still too big for the
+/// canvas at the scale asked for is brought down to the largest that fits
+pub const MIN_SCALE: f32 = 0.75;
+pub const MAX_SCALE: f32 = 1.5;
+
+/// UI scales that can be picked in the options, in percent
+pub const UI_SCALES: &[u32] = &[75, 100, 125, 150];
+
+/// Room kept clear along the left and right edges of the canvas
+pub const EDGE_MARGIN: f32 = 0.02;
+
+/// Width of a y unit on screen, in x units
+pub const X_PER_Y: f32 = 2.0;
+
+/// Horizontal centre of the screen (and of the playfield)
+pub const CENTER_X: f32 = 0.5;
+
+/// Narrowest window shape everything is laid out to fit
+pub const MIN_ASPECT_RATIO: f32 = 4.0 / 3.0;
+
+/// Room shown above and below the playfield, so its walls stay on screen
+pub const FIELD_MARGIN: f32 = 0.02;
+
+/// Big screen titles ("Tet.rs", "Scores")
+pub const TITLE_Y: f32 = 0.2;
+pub const TITLE_SIZE: f32 = 1.0 / 6.0;
+
+/// Small caption right under a title
+pub const SUBTITLE_Y: f32 = 0.3;
+pub const SUBTITLE_SIZE: f32 = 0.05;
+
+/// Selectable menu entries are spread between these two heights
+pub const MENU_TOP: f32 = 0.5;
+pub const MENU_BOTTOM: f32 = 0.9;
+pub const MENU_SPACING: f32 = 0.2;
+pub const MENU_ENTRY_SIZE: f32 = 0.5 / 4.0;
+
+/// Line along the bottom saying which keys a menu takes
+pub const MENU_HINT_Y: f32 = 0.975;
+pub const MENU_HINT_SIZE: f32 = 0.04;
+
+/// Leaderboard rows, laid out as a fixed 29 character wide table: name,
+/// value, and the date it was set
+pub const SCORES_TOP: f32 = TITLE_Y + TITLE_SIZE;
+pub const SCORE_ROW_SPACING: f32 = 0.055;
+pub const SCORE_ROW_SIZE: f32 = 0.07;
+pub const SCORE_ROW_CHARS: usize = 29;
+pub const SCORE_NOTE_SIZE: f32 = 0.05;
+/// Most rows on the scores screen: a full table, then two notes under it
+pub const SCORE_ROWS: usize = crate::scores::MAX_ENTRIES + 2;
+
+/// Post-game summary, two 13 character columns side by side
+pub const SUMMARY_TOP: f32 = TITLE_Y + TITLE_SIZE;
+pub const SUMMARY_ROW_SPACING: f32 = 0.05;
+pub const SUMMARY_ROW_SIZE: f32 = 0.075;
+pub const SUMMARY_COLUMN_CHARS: usize = 13;
+pub const SUMMARY_COLUMN_GAP_CHARS: usize = 1;
+/// Most rows in a summary column, which end above the heatmap
+pub const SUMMARY_ROWS: usize = 9;
+
+/// Strip of the field's columns under the summary, each tinted by how many
+/// cells went in it, with a note under it naming the busiest and quietest
+pub const HEATMAP_TOP: f32 = 0.86;
+pub const HEATMAP_CELL: f32 = 0.04;
+pub const HEATMAP_NOTE_GAP: f32 = 0.01;
+pub const HEATMAP_NOTE_SIZE: f32 = 0.04;
+
+/// Chart of score and lines over the run on the summary's second page, with
+/// room for labels on either side and below
+pub const CHART_TOP: f32 = 0.4;
+pub const CHART_WIDTH: f32 = 1.6;
+pub const CHART_HEIGHT: f32 = 0.33;
+pub const CHART_LABEL_SIZE: f32 = 0.04;
+/// Room between the chart and its labels
+pub const CHART_LABEL_GAP: f32 = 0.015;
+/// Longest value labelled either side of the chart
+pub const CHART_LABEL_CHARS: usize = 7;
+pub const CHART_MAX_TICKS: usize = 5;
+
+/// Split times on the results screen, a row for every ten lines between the
+/// title and the final time: lines, time, and how far off the best's it was
+pub const SPLITS_TOP: f32 = 0.3;
+pub const SPLIT_ROW_SPACING: f32 = 0.045;
+pub const SPLIT_ROW_SIZE: f32 = 0.07;
+pub const SPLIT_ROW_CHARS: usize = 21;
+
+/// How to play screen, a column of rows under the title turned over a page
+/// at a time
+pub const HELP_TOP: f32 = TITLE_Y + TITLE_SIZE;
+pub const HELP_ROW_SPACING: f32 = 0.045;
+pub const HELP_TEXT_SIZE: f32 = 0.06;
+pub const HELP_ROWS: usize = 12;
+pub const HELP_WIDTH: f32 = 2.0;
+
+/// Yes or no question in a box over the middle of the screen: the message
+/// wrapped to the box, then the two answers side by side under it
+pub const CONFIRM_WIDTH: f32 = 1.2;
+pub const CONFIRM_PADDING: f32 = 0.04;
+pub const CONFIRM_TEXT_SIZE: f32 = 0.06;
+pub const CONFIRM_ROW_SPACING: f32 = 0.045;
+pub const CONFIRM_OPTION_SIZE: f32 = 0.08;
+/// Room between the message and the answers
+pub const CONFIRM_OPTION_GAP: f32 = 0.04;
+/// Distance between the answers' centres
+pub const CONFIRM_OPTION_SPACING: f32 = 0.5;
+
+/// Large centred value on the results screen
+pub const RESULT_Y: f32 = 0.5;
+pub const RESULT_SIZE: f32 = 0.2;
+/// How far off the best time it was, under the value
+pub const RESULT_DELTA_Y: f32 = 0.65;
+pub const RESULT_DELTA_SIZE: f32 = 0.075;
+
+/// Blinking prompt at the bottom of a screen
+pub const PROMPT_Y: f32 = 0.8;
+pub const PROMPT_SIZE: f32 = 0.1;
+
+/// Sidebar text to the right of the playfield, stacked up from the bottom in
+/// a column of labels on the left and values on the right
+pub const HUD_X: f32 = 1.1;
+pub const HUD_BOTTOM: f32 = 0.95;
+pub const HUD_TEXT_SIZE: f32 = 0.05;
+pub const HUD_COLUMN_CHARS: usize = 13;
+/// Most lines the sidebar stacks up, which the next queue stays clear of: the
+/// five a mode shows at most, and the I drought, faults and 20G lines
+pub const HUD_LINES: usize = 8;
+
+/// Key bindings to the left of the playfield, right-aligned against it and
+/// stacked up from the bottom like the sidebar
+pub const LEGEND_RIGHT: f32 = -0.1;
+
+/// Messages drawn over the playfield ("GAME OVER!")
+pub const BANNER_Y: f32 = 0.1;
+pub const BANNER_SIZE: f32 = 0.1;
+
+/// Level reached, sweeping across the playfield under the banners
+pub const LEVEL_BANNER_Y: f32 = 0.3;
+pub const LEVEL_BANNER_SIZE: f32 = 0.08;
+
+/// Notice over the middle of the playfield
+pub const TOAST_Y: f32 = 0.45;
+pub const TOAST_SIZE: f32 = 0.075;
+
+/// Achievement unlocks, in the top right corner of the screen
+pub const UNLOCK_SIZE: f32 = 0.05;
+pub const UNLOCK_MARGIN: f32 = 0.03;
+
+/// Points and the like rising off cleared lines
+pub const POPUP_SIZE: f32 = 0.06;
+
+/// Name of a clear over the playfield, centred on this height above notices
+pub const CALLOUT_Y: f32 = 0.3;
+pub const CALLOUT_SIZE: f32 = 0.05;
+
+/// Restart key being held, centred over the playfield with a bar filling up
+/// under it
+pub const RESTART_Y: f32 = 0.6;
+pub const RESTART_SIZE: f32 = 0.05;
+pub const RESTART_BAR_GAP: f32 = 0.01;
+pub const RESTART_BAR_HEIGHT: f32 = 0.015;
+
+/// Countdown before a run, centred on this height
+pub const COUNTDOWN_Y: f32 = 0.45;
+pub const COUNTDOWN_SIZE: f32 = 0.3;
+
+/// Split-screen versus: player two's playfield starts this far right of player
+/// one's, clear of player one's sidebar. The view zooms out as needed to show
+/// `VERSUS_WIDTH` across, centred on `VERSUS_CENTER_X`
+pub const VERSUS_BOARD_SPACING: f32 = 2.25;
+pub const VERSUS_WIDTH: f32 = 4.5;
+pub const VERSUS_CENTER_X: f32 = 2.0;
+
+/// Online versus: the opponent's field, shrunk down left of the playfield
+pub const OPPONENT_RIGHT: f32 = -0.15;
+pub const OPPONENT_SCALE: f32 = 0.5;
+
+/// Sprint ghost races: the ghost's field goes where an online opponent's
+/// would, with a bar of its lines cleared and their count under it
+pub const GHOST_BAR_GAP: f32 = 0.02;
+pub const GHOST_BAR_HEIGHT: f32 = 0.02;
+
+/// Bar just under the field in rising mode, between its border and the
+/// bottom of the screen, for the wait until the next garbage row
+pub const RISE_BAR_GAP: f32 = 0.012;
+pub const RISE_BAR_HEIGHT: f32 = 0.006;
+
+/// Next piece previews, in playfield cells. The queue sits under its label
+/// and above the sidebar's lines, and previews shrink once they no longer fit
+/// between at full size. They're placed from the top left of the first box.
+pub const PREVIEW_COLUMN: f32 = 12.0;
+pub const PREVIEW_LABEL_Y: f32 = 0.0;
+pub const PREVIEW_SPACING_ROWS: f32 = 5.0;
+pub const PREVIEW_BOX_CELLS: f32 = 4.0;
+/// Room between the queue's frame and the text above and below it, in rows
+pub const PREVIEW_CLEARANCE_ROWS: f32 = 0.25;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    /// Area visible on a framebuffer of the given size. Windows narrower than
+    /// [`MIN_ASPECT_RATIO`] see as much across as one that shape would, with
+    /// the playfield centred between bars above and below it
+    pub fn canvas(width: f32, height: f32) -> Self {
+        let aspect_ratio = width / height;
+        let height = f32::max(1.0, MIN_ASPECT_RATIO / aspect_ratio) + FIELD_MARGIN * 2.0;
+        let width = aspect_ratio * 2.0 * height;
+        Self {
+            x: CENTER_X - width / 2.0,
+            y: 0.5 - height / 2.0,
+            width,
+            height,
+        }
+    }
+
+    pub fn right(&self) -> f32 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> f32 {
+        self.y + self.height
+    }
+
+    pub fn contains_point(&self, x: f32, y: f32) -> bool {
+        x >= self.x && y >= self.y && x <= self.x + self.width && y <= self.y + self.height
+    }
+
+    pub fn contains(&self, other: &Rect) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.x + other.width <= self.x + self.width
+            && other.y + other.height <= self.y + self.height
+    }
+}
+
+/// Where and how big to draw one next piece preview, in playfield cells down
+/// from the top of the first box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PreviewSlot {
+    pub row: f32,
+    /// Size of a preview cell relative to a playfield cell
+    pub scale: f32,
+}
+
+/// Where and how big to draw a line of text
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextPlacement {
+    pub x: f32,
+    pub y: f32,
+    pub size: f32,
+}
+
+impl TextPlacement {
+    /// Area covered by `text` drawn at this placement
+    pub fn rect(&self, text: &str) -> Rect {
+        Rect {
+            x: self.x,
+            y: self.y,
+            width: text_width(text, self.size),
+            height: self.size / 2.0,
+        }
+    }
+}
+
+/// Width of `text` drawn `size` wide, glyphs taking the room they need
+pub fn text_width(text: &str, size: f32) -> f32 {
+    measure_text(text, size).0
+}
+
+/// Width of `chars` characters drawn `size` wide in monospace
+pub fn mono_width(chars: usize, size: f32) -> f32 {
+    chars as f32 * size
+}
+
+/// Layout of the screens for a given canvas and UI scale
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    pub canvas: Rect,
+    /// Scale asked for, within [`MIN_SCALE`] and [`MAX_SCALE`]. Parts of the
+    /// screen that wouldn't fit at it are drawn at the largest scale that does
+    pub scale: f32,
+}
+
+impl Layout {
+    pub fn new(canvas: Rect, scale: f32) -> Self {
+        Self {
+            canvas,
+            scale: scale.max(MIN_SCALE).min(MAX_SCALE),
+        }
+    }
+
+    /// Layout for a framebuffer of the given size at UI scale `scale`
+    pub fn for_framebuffer(width: u32, height: u32, scale: f32) -> Self {
+        Self::new(Rect::canvas(width as f32, height as f32), scale)
+    }
+
+    /// The scale, brought down as far as needed for something `needed` long
+    /// at scale 1 to fit in `room`
+    fn fit(&self, room: f32, needed: f32) -> f32 {
+        self.scale.min(room / needed)
+    }
+
+    /// Room across the canvas, between the margins
+    fn across(&self) -> f32 {
+        self.canvas.width - EDGE_MARGIN * 2.0
+    }
+
+    fn centered(&self, text: &str, y: f32, size: f32) -> TextPlacement {
+        self.centered_by(|size| text_width(text, size), y, size * self.scale)
+    }
+
+    fn centered_mono(&self, chars: usize, y: f32, size: f32) -> TextPlacement {
+        self.centered_by(|size| mono_width(chars, size), y, size)
+    }
+
+    /// Text `width` wide at `size`, centred, shrunk if it'd run off the sides
+    fn centered_by(&self, width: impl Fn(f32) -> f32, y: f32, size: f32) -> TextPlacement {
+        let full = width(size);
+        let size = if full > self.across() {
+            size * self.across() / full
+        } else {
+            size
+        };
+        TextPlacement {
+            x: CENTER_X - width(size) / 2.0,
+            y,
+            size,
+        }
+    }
+
+    pub fn title(&self, text: &str) -> TextPlacement {
+        self.centered(text, TITLE_Y, TITLE_SIZE)
+    }
+
+    pub fn subtitle(&self, text: &str) -> TextPlacement {
+        self.centered(text, SUBTITLE_Y, SUBTITLE_SIZE)
+    }
+
+    /// Entry `index` out of `count` in a vertical menu
+    pub fn menu_entry(&self, index: usize, count: usize, text: &str) -> TextPlacement {
+        let spacing = if count > 1 {
+            f32::min(
+                MENU_SPACING * self.scale,
+                (MENU_BOTTOM - MENU_TOP) / (count - 1) as f32,
+            )
+        } else {
+            0.0
+        };
+        // long menus shrink their entries so rows don't run into each other
+        let size = if count > 1 {
+            f32::min(MENU_ENTRY_SIZE, 1.6 * spacing / self.scale)
+        } else {
+            MENU_ENTRY_SIZE
+        };
+        self.centered(text, MENU_TOP + spacing * index as f32, size)
+    }
+
+    pub fn menu_hint(&self, text: &str) -> TextPlacement {
+        self.centered(text, MENU_HINT_Y, MENU_HINT_SIZE)
+    }
+
+    /// Scale of the scores screen, down to what fits a full table and its
+    /// notes above the bottom edge
+    fn score_scale(&self) -> f32 {
+        let needed = SCORE_ROW_SPACING * (SCORE_ROWS - 1) as f32 + SCORE_NOTE_SIZE / 2.0;
+        self.fit(self.canvas.bottom() - SCORES_TOP, needed)
+    }
+
+    pub fn score_row(&self, index: usize) -> TextPlacement {
+        self.centered_mono(
+            SCORE_ROW_CHARS,
+            SCORES_TOP + self.score_spacing() * index as f32,
+            SCORE_ROW_SIZE * self.score_scale(),
+        )
+    }
+
+    /// A line under `rows` score rows, saying something about them
+    pub fn score_note(&self, rows: usize, text: &str) -> TextPlacement {
+        self.centered_by(
+            |size| text_width(text, size),
+            SCORES_TOP + self.score_spacing() * rows as f32,
+            SCORE_NOTE_SIZE * self.score_scale(),
+        )
+    }
+
+    /// Distance from the top of one score row to the next
+    pub fn score_spacing(&self) -> f32 {
+        SCORE_ROW_SPACING * self.score_scale()
+    }
+
+    /// Scale of the summary's columns, down to what ends above the heatmap
+    fn summary_scale(&self) -> f32 {
+        let needed = SUMMARY_ROW_SPACING * (SUMMARY_ROWS - 1) as f32 + SUMMARY_ROW_SIZE / 2.0;
+        self.fit(HEATMAP_TOP - SUMMARY_TOP, needed)
+    }
+
+    /// Row `index` of summary column `column` out of `columns`, 0 being the
+    /// left one
+    pub fn summary_row(&self, column: usize, columns: usize, index: usize) -> TextPlacement {
+        let scale = self.summary_scale();
+        let row_chars =
+            SUMMARY_COLUMN_CHARS * columns + SUMMARY_COLUMN_GAP_CHARS * (columns.max(1) - 1);
+        let left = self.centered_mono(
+            row_chars,
+            SUMMARY_TOP + SUMMARY_ROW_SPACING * scale * index as f32,
+            SUMMARY_ROW_SIZE * scale,
+        );
+        TextPlacement {
+            x: left.x
+                + mono_width(
+                    (SUMMARY_COLUMN_CHARS + SUMMARY_COLUMN_GAP_CHARS) * column,
+                    left.size,
+                ),
+            ..left
+        }
+    }
+
+    /// Cell of the heatmap strip for `column` of the field, square on screen
+    pub fn heatmap_cell(&self, column: usize) -> Rect {
+        let height = HEATMAP_CELL * self.scale;
+        let width = height * X_PER_Y;
+        Rect {
+            x: CENTER_X + (column as f32 - FIELD_WIDTH as f32 / 2.0) * width,
+            y: HEATMAP_TOP,
+            width,
+            height,
+        }
+    }
+
+    /// Note under the heatmap strip
+    pub fn heatmap_note(&self, text: &str) -> TextPlacement {
+        let y = HEATMAP_TOP + (HEATMAP_CELL + HEATMAP_NOTE_GAP) * self.scale;
+        self.centered(text, y, HEATMAP_NOTE_SIZE)
+    }
+
+    /// Scale of the summary's chart, down to what fits across with the labels
+    /// either side of it
+    pub fn chart_scale(&self) -> f32 {
+        let labels = CHART_LABEL_GAP * X_PER_Y + mono_width(CHART_LABEL_CHARS, CHART_LABEL_SIZE);
+        self.fit(self.across(), CHART_WIDTH + labels * 2.0)
+    }
+
+    /// Area the summary's chart is drawn in, its labels going around it
+    pub fn chart_area(&self) -> Rect {
+        let scale = self.chart_scale();
+        let width = CHART_WIDTH * scale;
+        Rect {
+            x: CENTER_X - width / 2.0,
+            y: CHART_TOP,
+            width,
+            height: CHART_HEIGHT * scale,
+        }
+    }
+
+    /// Split row `index` on the results screen
+    pub fn split_row(&self, index: usize) -> TextPlacement {
+        self.centered_mono(
+            SPLIT_ROW_CHARS,
+            SPLITS_TOP + SPLIT_ROW_SPACING * self.scale * index as f32,
+            SPLIT_ROW_SIZE * self.scale,
+        )
+    }
+
+    /// Scale of a How to play page, down to what fits across and above the
+    /// hint at the bottom
+    fn help_scale(&self) -> f32 {
+        let needed = HELP_ROW_SPACING * (HELP_ROWS - 1) as f32 + HELP_TEXT_SIZE / 2.0;
+        self.fit(self.across(), HELP_WIDTH)
+            .min(self.fit(MENU_HINT_Y - HELP_TOP, needed))
+    }
+
+    /// Left edge of row `index` of a How to play page
+    pub fn help_row(&self, index: usize) -> TextPlacement {
+        let scale = self.help_scale();
+        TextPlacement {
+            x: CENTER_X - HELP_WIDTH * scale / 2.0,
+            y: HELP_TOP + HELP_ROW_SPACING * scale * index as f32,
+            size: HELP_TEXT_SIZE * scale,
+        }
+    }
+
+    /// Distance from the top of one How to play row to the next
+    pub fn help_spacing(&self) -> f32 {
+        HELP_ROW_SPACING * self.help_scale()
+    }
+
+    /// Box a confirmation with a message `lines` long is drawn in
+    pub fn confirm_panel(&self, lines: usize) -> Rect {
+        let padding = CONFIRM_PADDING * self.scale;
+        let height = padding * 2.0
+            + CONFIRM_ROW_SPACING * self.scale * lines as f32
+            + CONFIRM_OPTION_GAP * self.scale
+            + CONFIRM_OPTION_SIZE * self.scale / 2.0;
+        let width = CONFIRM_WIDTH * self.scale + padding * 2.0 * X_PER_Y;
+        Rect {
+            x: CENTER_X - width / 2.0,
+            y: 0.5 - height / 2.0,
+            width,
+            height,
+        }
+    }
+
+    /// Line `index` of a confirmation's message `lines` long, centred
+    pub fn confirm_line(&self, index: usize, lines: usize, text: &str) -> TextPlacement {
+        let top = self.confirm_panel(lines).y + CONFIRM_PADDING * self.scale;
+        self.centered(
+            text,
+            top + CONFIRM_ROW_SPACING * self.scale * index as f32,
+            CONFIRM_TEXT_SIZE,
+        )
+    }
+
+    /// Answer `index` reading `text` under a confirmation's message `lines`
+    /// long, 0 being the left one
+    pub fn confirm_option(&self, index: usize, lines: usize, text: &str) -> TextPlacement {
+        let panel = self.confirm_panel(lines);
+        let size = CONFIRM_OPTION_SIZE * self.scale;
+        let offset = (index as f32 - 0.5) * CONFIRM_OPTION_SPACING * self.scale;
+        TextPlacement {
+            x: CENTER_X + offset - text_width(text, size) / 2.0,
+            y: panel.y + panel.height - CONFIRM_PADDING * self.scale - size / 2.0,
+            size,
+        }
+    }
+
+    pub fn result(&self, text: &str) -> TextPlacement {
+        self.centered(text, RESULT_Y, RESULT_SIZE)
+    }
+
+    pub fn result_delta(&self, text: &str) -> TextPlacement {
+        self.centered(text, RESULT_DELTA_Y, RESULT_DELTA_SIZE)
+    }
+
+    pub fn prompt(&self, text: &str) -> TextPlacement {
+        self.centered(text, PROMPT_Y, PROMPT_SIZE)
+    }
+
+    /// Scale of the sidebar, down to what fits between the playfield and the
+    /// right edge
+    fn hud_scale(&self) -> f32 {
+        let room = self.canvas.right() - EDGE_MARGIN - HUD_X;
+        self.fit(room, mono_width(HUD_COLUMN_CHARS, HUD_TEXT_SIZE))
+    }
+
+    /// Sidebar line `index`, counting up from the bottom one
+    pub fn hud_line(&self, index: usize) -> TextPlacement {
+        let scale = self.hud_scale();
+        let size = HUD_TEXT_SIZE * scale;
+        TextPlacement {
+            x: HUD_X,
+            y: 1.0 - (1.0 - HUD_BOTTOM) * scale - size * index as f32,
+            size,
+        }
+    }
+
+    /// Legend line `index` reading `text`, counting up from the bottom one,
+    /// shrunk if it'd run off the left edge
+    pub fn legend_line(&self, index: usize, text: &str) -> TextPlacement {
+        let line = self.hud_line(index);
+        let room = LEGEND_RIGHT - (self.canvas.x + EDGE_MARGIN);
+        let width = text_width(text, line.size);
+        let size = if width > room {
+            line.size * room / width
+        } else {
+            line.size
+        };
+        TextPlacement {
+            x: LEGEND_RIGHT - text_width(text, size),
+            size,
+            ..line
+        }
+    }
+
+    /// Message centred over the playfield
+    pub fn banner(&self, text: &str) -> TextPlacement {
+        self.centered(text, BANNER_Y, BANNER_SIZE)
+    }
+
+    /// Level up banner `progress` of the way across the field, from its left
+    /// wall to its right, lingering in the middle
+    pub fn level_banner(&self, text: &str, progress: f32) -> TextPlacement {
+        let centered = self.centered(text, LEVEL_BANNER_Y, LEVEL_BANNER_SIZE);
+        let room = (1.0 - text_width(text, centered.size)).max(0.0);
+        let offset = 4.0 * (progress.max(0.0).min(1.0) - 0.5).powi(3);
+        TextPlacement {
+            x: centered.x + offset * room,
+            ..centered
+        }
+    }
+
+    pub fn toast(&self, text: &str) -> TextPlacement {
+        self.centered(text, TOAST_Y, TOAST_SIZE)
+    }
+
+    pub fn restart_label(&self, text: &str) -> TextPlacement {
+        self.centered(text, RESTART_Y, RESTART_SIZE)
+    }
+
+    /// Bar under the restart label reading `text`, as wide as it
+    pub fn restart_bar(&self, text: &str) -> Rect {
+        let label = self.restart_label(text).rect(text);
+        Rect {
+            y: label.y + label.height + RESTART_BAR_GAP * self.scale,
+            height: RESTART_BAR_HEIGHT * self.scale,
+            ..label
+        }
+    }
+
+    /// Unlock notice reading `text`, `shown` of the way from just off the
+    /// right edge of the screen to its top right corner
+    pub fn unlock_toast(&self, text: &str, shown: f32) -> TextPlacement {
+        let room = self.canvas.width - UNLOCK_MARGIN * 2.0;
+        let size = UNLOCK_SIZE * self.scale;
+        let size = size.min(size * room / text_width(text, size).max(f32::EPSILON));
+        let width = text_width(text, size) + UNLOCK_MARGIN;
+        TextPlacement {
+            x: self.canvas.right() - width * shown,
+            y: self.canvas.y + UNLOCK_MARGIN,
+            size,
+        }
+    }
+
+    /// Countdown text, `grow` going from 0 to 1 as it scales up to full size
+    pub fn countdown(&self, text: &str, grow: f32) -> TextPlacement {
+        let placement = self.centered(text, COUNTDOWN_Y, COUNTDOWN_SIZE * (0.5 + 0.5 * grow));
+        // grow from the middle rather than the top edge
+        TextPlacement {
+            y: COUNTDOWN_Y - placement.size / 4.0,
+            ..placement
+        }
+    }
+
+    /// Left edge of `player`'s playfield in split-screen versus
+    pub fn versus_board_x(&self, player: usize) -> f32 {
+        VERSUS_BOARD_SPACING * player as f32
+    }
+
+    /// Area shown in split-screen versus, wide enough for both boards
+    pub fn versus_view(&self) -> Rect {
+        let zoom = f32::max(1.0, VERSUS_WIDTH / self.canvas.width);
+        Rect {
+            x: VERSUS_CENTER_X - self.canvas.width * zoom / 2.0,
+            y: 0.5 - self.canvas.height * zoom / 2.0,
+            width: self.canvas.width * zoom,
+            height: self.canvas.height * zoom,
+        }
+    }
+
+    /// Where the opponent's field is shown in online versus, as big as fits
+    /// between the left edge and the playfield
+    pub fn opponent_field(&self) -> Rect {
+        let room = OPPONENT_RIGHT - (self.canvas.x + EDGE_MARGIN);
+        let size = OPPONENT_SCALE * self.fit(room, OPPONENT_SCALE);
+        Rect {
+            x: OPPONENT_RIGHT - size,
+            y: 0.0,
+            width: size,
+            height: size,
+        }
+    }
+
+    /// Where the ghost's field is shown in a sprint
+    pub fn ghost_field(&self) -> Rect {
+        self.opponent_field()
+    }
+
+    /// Bar under the ghost's field, filling up as it clears lines
+    pub fn ghost_bar(&self) -> Rect {
+        let field = self.ghost_field();
+        Rect {
+            x: field.x,
+            y: field.y + field.height + GHOST_BAR_GAP * self.scale,
+            width: field.width,
+            height: GHOST_BAR_HEIGHT * self.scale,
+        }
+    }
+
+    /// Bar under the field, as wide as it, full when a garbage row has just
+    /// risen
+    pub fn rise_bar(&self) -> Rect {
+        Rect {
+            x: 0.0,
+            y: 1.0 + RISE_BAR_GAP,
+            width: 1.0,
+            height: RISE_BAR_HEIGHT,
+        }
+    }
+
+    /// How the ghost is doing, under its bar
+    pub fn ghost_label(&self) -> TextPlacement {
+        let bar = self.ghost_bar();
+        TextPlacement {
+            x: bar.x,
+            y: bar.y + bar.height + GHOST_BAR_GAP * self.scale,
+            size: HUD_TEXT_SIZE * self.scale,
+        }
+    }
+
+    /// Label over the next queue, at the top of the sidebar
+    pub fn preview_label(&self) -> TextPlacement {
+        TextPlacement {
+            x: PREVIEW_COLUMN / FIELD_WIDTH as f32,
+            y: PREVIEW_LABEL_Y,
+            size: HUD_TEXT_SIZE * self.hud_scale(),
+        }
+    }
+    /// Rows the previews' boxes may take up, from under the label to over the
+    /// sidebar's lines, leaving room for the frame's padding
+    fn preview_rows(&self) -> (f32, f32) {
+        let padding = (PREVIEW_SPACING_ROWS - PREVIEW_BOX_CELLS) / 2.0 + PREVIEW_CLEARANCE_ROWS;
+        let label = self.preview_label();
+        let top = (label.y + label.size / X_PER_Y) * FIELD_HEIGHT as f32 + padding;
+        let bottom = self.hud_line(HUD_LINES - 1).y * FIELD_HEIGHT as f32 - padding;
+        (top, bottom)
+    }
+
+    /// Rows between the tops of two consecutive previews when showing `count`
+    fn preview_spacing(&self, count: usize) -> f32 {
+        let (top, bottom) = self.preview_rows();
+        f32::min(PREVIEW_SPACING_ROWS, (bottom - top) / count.max(1) as f32)
+    }
+
+    /// Top left of the first preview's box, in playfield cells. Everything
+    /// else about the previews is measured from here
+    pub fn preview_origin(&self) -> [f32; 2] {
+        [PREVIEW_COLUMN, self.preview_rows().0]
+    }
+
+    /// Top and scale of preview `index`'s 4x4 box out of `count`
+    pub fn preview_slot(&self, index: usize, count: usize) -> PreviewSlot {
+        let spacing = self.preview_spacing(count);
+        PreviewSlot {
+            row: spacing * index as f32,
+            scale: spacing / PREVIEW_SPACING_ROWS,
+        }
+    }
+
+    /// Frame around `count` previews, with half a cell of padding on every
+    /// side
+    pub fn preview_frame(&self, count: usize) -> Rect {
+        let gap = PREVIEW_SPACING_ROWS - PREVIEW_BOX_CELLS;
+        let last = self.preview_slot(count.max(1) - 1, count);
+        Rect {
+            x: -gap / 2.0,
+            y: -gap / 2.0,
+            width: PREVIEW_BOX_CELLS + gap,
+            height: last.row + PREVIEW_BOX_CELLS * last.scale + gap,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCALES: &[f32] = &[0.75, 1.0, 1.25, 1.5];
+    const ASPECT_RATIOS: &[f32] = &[4.0 / 3.0, 16.0 / 10.0, 16.0 / 9.0, 21.0 / 9.0];
+
+    /// Text as long as any the screens show in that place, and far longer
+    const SHORT: &str = "Tet.rs";
+    const LONG: &str = "Your score: 1,234,567 (not in top 10), press Enter to go on";
+
+    fn layouts() -> impl Iterator<Item = Layout> {
+        ASPECT_RATIOS.iter().flat_map(|&aspect_ratio| {
+            let canvas = Rect::canvas(aspect_ratio * 1080.0, 1080.0);
+            SCALES.iter().map(move |&scale| Layout::new(canvas, scale))
+        })
+    }
+
+    /// Every rect worth checking a layout makes
+    fn rects(layout: &Layout) -> Vec<(String, Rect)> {
+        let mut rects = Vec::new();
+        let mut text = |name: &str, placement: TextPlacement, text: &str| {
+            rects.push((format!("{} {:?}", name, text), placement.rect(text)));
+        };
+        for &txt in &[SHORT, LONG] {
+            text("title", layout.title(txt), txt);
+            text("subtitle", layout.subtitle(txt), txt);
+            for count in 1..=25 {
+                for i in 0..count {
+                    text("menu entry", layout.menu_entry(i, count, txt), txt);
+                }
+            }
+            text("menu hint", layout.menu_hint(txt), txt);
+            text("score note", layout.score_note(SCORE_ROWS - 1, txt), txt);
+            text("heatmap note", layout.heatmap_note(txt), txt);
+            for lines in 1..=4 {
+                for i in 0..lines {
+                    text("confirm line", layout.confirm_line(i, lines, txt), txt);
+                }
+            }
+            text("result", layout.result(txt), txt);
+            text("result delta", layout.result_delta(txt), txt);
+            text("prompt", layout.prompt(txt), txt);
+            text("banner", layout.banner(txt), txt);
+            for &progress in &[0.0, 0.5, 1.0] {
+                text("level banner", layout.level_banner(txt, progress), txt);
+            }
+            text("toast", layout.toast(txt), txt);
+            text("restart label", layout.restart_label(txt), txt);
+            text("unlock", layout.unlock_toast(txt, 1.0), txt);
+            text("countdown", layout.countdown(txt, 1.0), txt);
+            for i in 0..4 {
+                text("legend", layout.legend_line(i, txt), txt);
+            }
+        }
+        text("preview label", layout.preview_label(), "Next");
+
+        let mut mono = |name: &str, placement: TextPlacement, chars: usize| {
+            let rect = Rect {
+                width: mono_width(chars, placement.size),
+                ..placement.rect("")
+            };
+            rects.push((name.to_string(), rect));
+        };
+        for i in 0..SCORE_ROWS - 2 {
+            mono("score row", layout.score_row(i), SCORE_ROW_CHARS);
+        }
+        for columns in 2..=3 {
+            for column in 0..columns {
+                for i in 0..SUMMARY_ROWS {
+                    let row = layout.summary_row(column, columns, i);
+                    mono("summary row", row, SUMMARY_COLUMN_CHARS);
+                }
+            }
+        }
+        for i in 0..4 {
+            mono("split row", layout.split_row(i), SPLIT_ROW_CHARS);
+        }
+        for i in 0..HUD_LINES {
+            mono("hud line", layout.hud_line(i), HUD_COLUMN_CHARS);
+        }
+        // How to play rows are wrapped to the page's width
+        for i in 0..HELP_ROWS {
+            let row = layout.help_row(i);
+            rects.push((
+                "help row".to_string(),
+                Rect {
+                    width: HELP_WIDTH * row.size / HELP_TEXT_SIZE,
+                    ..row.rect("")
+                },
+            ));
+        }
+
+        for column in 0..FIELD_WIDTH as usize {
+            rects.push((
+                format!("heatmap cell {}", column),
+                layout.heatmap_cell(column),
+            ));
+        }
+        // the chart with its labels either side and under it
+        let chart = layout.chart_area();
+        let scale = layout.chart_scale();
+        let labels =
+            (CHART_LABEL_GAP * X_PER_Y + mono_width(CHART_LABEL_CHARS, CHART_LABEL_SIZE)) * scale;
+        rects.push((
+            "chart".to_string(),
+            Rect {
+                x: chart.x - labels,
+                y: chart.y - (CHART_LABEL_GAP + CHART_LABEL_SIZE / 2.0) * scale,
+                width: chart.width + labels * 2.0,
+                height: chart.height + (CHART_LABEL_GAP + CHART_LABEL_SIZE / 2.0) * scale * 2.0,
+            },
+        ));
+        for lines in 1..=4 {
+            rects.push(("confirm panel".to_string(), layout.confirm_panel(lines)));
+        }
+        rects.push(("restart bar".to_string(), layout.restart_bar(SHORT)));
+        rects.push(("opponent field".to_string(), layout.opponent_field()));
+        rects.push(("ghost bar".to_string(), layout.ghost_bar()));
+        rects.push(("rise bar".to_string(), layout.rise_bar()));
+        let label = layout.ghost_label();
+        rects.push(("ghost label".to_string(), label.rect("40/40")));
+
+        // the previews' frame is measured in playfield cells
+        for count in 1..=6 {
+            let frame = layout.preview_frame(count);
+            let [x, y] = layout.preview_origin();
+            let cell = |cells: f32, across: u32| cells / across as f32;
+            rects.push((
+                format!("preview frame of {}", count),
+                Rect {
+                    x: cell(x + frame.x, FIELD_WIDTH),
+                    y: cell(y + frame.y, FIELD_HEIGHT),
+                    width: cell(frame.width, FIELD_WIDTH),
+                    height: cell(frame.height, FIELD_HEIGHT),
+                },
+            ));
+        }
+        rects
+    }
+
+    #[test]
+    fn everything_stays_on_the_canvas_at_every_scale_and_shape() {
+        for layout in layouts() {
+            for (name, rect) in rects(&layout) {
+                assert!(
+                    layout.canvas.contains(&rect),
+                    "{} at scale {} runs off {:?}: {:?}",
+                    name,
+                    layout.scale,
+                    layout.canvas,
+                    rect
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn the_scale_asked_for_is_kept_within_bounds() {
+        let canvas = Rect::canvas(1920.0, 1080.0);
+        assert_eq!(Layout::new(canvas, 0.1).scale, MIN_SCALE);
+        assert_eq!(Layout::new(canvas, 1.25).scale, 1.25);
+        assert_eq!(Layout::new(canvas, 4.0).scale, MAX_SCALE);
+    }
+
+    #[test]
+    fn what_fits_grows_with_the_scale() {
+        let canvas = Rect::canvas(1920.0, 1080.0);
+        let small = Layout::new(canvas, 0.75);
+        let big = Layout::new(canvas, 1.5);
+        assert!(big.title(SHORT).size > small.title(SHORT).size);
+        assert!(big.hud_line(0).size > small.hud_line(0).size);
+        assert!(big.score_row(0).size > small.score_row(0).size);
+    }
+
+    #[test]
+    fn the_sidebar_shrinks_to_fit_a_narrow_window() {
+        let narrow = Layout::new(Rect::canvas(1440.0, 1080.0), MAX_SCALE);
+        let wide = Layout::new(Rect::canvas(2520.0, 1080.0), MAX_SCALE);
+        assert!(narrow.hud_line(0).size < HUD_TEXT_SIZE * MAX_SCALE);
+        assert_eq!(wide.hud_line(0).size, HUD_TEXT_SIZE * MAX_SCALE);
+    }
+}
@@ -0,0 +1,86 @@
+//! Short messages that rise from where something happened and fade away.
+//!
+//! Popups are aged once a tick. A popup pushed where another is still showing
+//! goes above it, so a run of them stacks up instead of piling on one spot.
+
+use crate::graphics::{
+    mesh::MeshBuilder,
+    text::{self, TextStyle},
+    theme::Theme,
+};
+
+use super::layout::text_width;
+
+/// Ticks a popup stays on screen
+pub const POPUP_LIFE_TICKS: u32 = 20;
+
+/// How far a popup rises over its life, in world units
+const RISE: f32 = 0.1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Popup {
+    pub text: String,
+
+    /// Top left corner of the text when it appeared
+    pub x: f32,
+    pub y: f32,
+
+    /// Ticks since it appeared
+    pub age: u32,
+}
+
+impl Popup {
+    /// Height of the text above where it appeared, by now
+    fn rise(&self) -> f32 {
+        RISE * self.age as f32 / POPUP_LIFE_TICKS as f32
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Popups {
+    popups: Vec<Popup>,
+}
+
+impl Popups {
+    /// Shows `text` centred on `x`, with its top at `y` unless that's taken.
+    /// `size` is the text size it'll be drawn at
+    pub fn push(&mut self, text: &str, x: f32, mut y: f32, size: f32) {
+        let height = size / 2.0;
+        while self
+            .popups
+            .iter()
+            .any(|p| (p.y - p.rise() - y).abs() < height)
+        {
+            y -= height;
+        }
+        self.popups.push(Popup {
+            text: text.to_string(),
+            x: x - text_width(text, size) / 2.0,
+            y,
+            age: 0,
+        });
+    }
+
+    /// Ages every popup by a tick, dropping the ones that are done
+    pub fn tick(&mut self) {
+        for popup in &mut self.popups {
+            popup.age += 1;
+        }
+        self.popups.retain(|p| p.age < POPUP_LIFE_TICKS);
+    }
+
+    pub fn clear(&mut self) {
+        self.popups.clear();
+    }
+
+    /// Adds text for every popup to `mesh` at `size`, in `color` over the
+    /// `theme`'s shadow, both fading into its background as it ages
+    pub fn render(&self, mesh: &mut MeshBuilder, size: f32, color: [f32; 3], theme: &Theme) {
+        for popup in &self.popups {
+            let fade = popup.age as f32 / POPUP_LIFE_TICKS as f32;
+            let style = TextStyle::new(size, theme.fade(color, fade))
+                .shadow(theme.fade(theme.shadow, fade));
+            text::render_styled(mesh, &popup.text, popup.x, popup.y - popup.rise(), &style);
+        }
+    }
+}
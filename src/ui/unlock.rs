@@ -0,0 +1,68 @@
+//! Achievements just unlocked, announced one at a time in a corner.
+//!
+//! Each notice slides in from the right edge of the screen, sits in the top
+//! right corner for a moment and slides back out before the next one comes.
+
+use crate::game::achievements::Achievement;
+use crate::graphics::{
+    mesh::MeshBuilder,
+    text::{self, TextStyle},
+    theme::Theme,
+};
+
+use super::layout::Layout;
+
+/// Ticks a notice stays on screen, sliding in and out included
+pub const UNLOCK_LIFE_TICKS: u64 = 40;
+
+/// Ticks spent sliding in, and again sliding out
+const SLIDE_TICKS: u64 = 5;
+
+#[derive(Clone, Debug, Default)]
+pub struct UnlockToasts {
+    /// Unlocks waiting to be announced, the first one being shown
+    queue: Vec<&'static Achievement>,
+
+    /// Ticks the first one has been shown for
+    age: u64,
+}
+
+impl UnlockToasts {
+    pub fn extend(&mut self, unlocked: impl IntoIterator<Item = &'static Achievement>) {
+        self.queue.extend(unlocked);
+    }
+
+    /// Takes every unlock not announced yet, to be shown somewhere else
+    pub fn take(&mut self) -> Vec<&'static Achievement> {
+        self.age = 0;
+        std::mem::take(&mut self.queue)
+    }
+
+    /// Ages the notice shown by a tick, moving on to the next once it's done
+    pub fn tick(&mut self) {
+        if self.queue.is_empty() {
+            return;
+        }
+        self.age += 1;
+        if self.age >= UNLOCK_LIFE_TICKS {
+            self.queue.remove(0);
+            self.age = 0;
+        }
+    }
+
+    /// How far the notice is slid in, from 0 off screen to 1 in its corner
+    fn shown(&self) -> f32 {
+        let out = UNLOCK_LIFE_TICKS - self.age;
+        (self.age.min(out).min(SLIDE_TICKS) as f32) / SLIDE_TICKS as f32
+    }
+
+    /// Adds the notice being shown, if any, to `mesh`
+    pub fn render(&self, mesh: &mut MeshBuilder, layout: &Layout, theme: &Theme) {
+        if let Some(achievement) = self.queue.first() {
+            let txt = format!("Unlocked: {}", achievement.name);
+            let placement = layout.unlock_toast(&txt, self.shown());
+            let style = TextStyle::new(placement.size, theme.highlight).shadow(theme.shadow);
+            text::render_styled(mesh, &txt, placement.x, placement.y, &style);
+        }
+    }
+}
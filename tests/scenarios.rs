@@ -0,0 +1,252 @@
+//! Whole plays scripted from a drawn field and a few keys, checking what the
+//! field looks like afterwards and what happened on the way.
+
+use tet_rs::board::Board;
+use tet_rs::mode::GameMode;
+use tet_rs::piece::randomizer::RandomizerKind;
+use tet_rs::piece::rotation::RotationKind;
+use tet_rs::piece::Tetromino;
+use tet_rs::sim::{Ending, Event, Inputs, Simulation, DEFAULT_PREVIEWS};
+
+/// Most ticks a piece is given to land before a scenario gives up on it
+const MAX_TICKS: usize = 10_000;
+
+/// A run being scripted, with everything that's happened in it so far
+struct Scenario {
+    sim: Simulation,
+    events: Vec<Event>,
+}
+
+impl Scenario {
+    /// An endless run on `field`, drawn as for [`Board::from_ascii`]
+    fn new(field: &str) -> Self {
+        let mut sim = Simulation::new(
+            GameMode::Endless,
+            DEFAULT_PREVIEWS,
+            Some(0),
+            RandomizerKind::default(),
+        );
+        sim.board = Board::from_ascii(field).expect("bad field");
+        sim.countdown = 0;
+        Self {
+            sim,
+            events: Vec::new(),
+        }
+    }
+
+    fn rotation(mut self, rotation: RotationKind) -> Self {
+        self.sim.set_rotation(rotation);
+        self
+    }
+
+    fn step(&mut self, inputs: Inputs) {
+        let events = self.sim.step(inputs);
+        self.events.extend(events);
+    }
+
+    /// Brings `piece` in next, waiting for it to spawn
+    fn piece(mut self, piece: Tetromino) -> Self {
+        self.sim.pick_next(piece.def());
+        for _ in 0..MAX_TICKS {
+            if self.sim.active_piece.is_some() {
+                return self;
+            }
+            self.step(Inputs::default());
+        }
+        panic!("{} never spawned", piece.name());
+    }
+
+    /// Taps each of `keys` in turn: `<` and `>` move, `c` turns clockwise
+    /// and `a` anticlockwise
+    fn tap(mut self, keys: &str) -> Self {
+        for key in keys.chars() {
+            self.step(keys_for(key));
+            self.step(Inputs::default());
+        }
+        self
+    }
+
+    /// Holds `key`, as for [`tap`](Self::tap), for `ticks` ticks
+    fn hold(mut self, key: char, ticks: usize) -> Self {
+        for _ in 0..ticks {
+            self.step(keys_for(key));
+        }
+        self.step(Inputs::default());
+        self
+    }
+
+    /// Holds down until the piece locks
+    fn drop(mut self) -> Self {
+        let locked = self.count(|e| matches!(e, Event::PieceLocked { .. }));
+        for _ in 0..MAX_TICKS {
+            if self.count(|e| matches!(e, Event::PieceLocked { .. })) > locked {
+                return self;
+            }
+            self.step(keys_for('v'));
+        }
+        panic!("the piece never locked");
+    }
+
+    /// Lets any line clear finish
+    fn settle(mut self) -> Self {
+        while self.sim.effect.is_some() && self.sim.ending.is_none() {
+            self.step(Inputs::default());
+        }
+        self
+    }
+
+    fn count(&self, pred: impl Fn(&Event) -> bool) -> usize {
+        self.events.iter().filter(|e| pred(e)).count()
+    }
+
+    /// Checks the field against the bottom rows drawn in `field`
+    fn assert_field(&self, field: &str) {
+        let expected = Board::from_ascii(field).expect("bad field").to_ascii();
+        assert_eq!(
+            self.sim.board.to_ascii(),
+            expected,
+            "\ngot:\n{}\nexpected:\n{}",
+            self.sim.board.to_ascii(),
+            expected
+        );
+    }
+
+    /// Lines cleared by each piece that cleared any
+    fn clears(&self) -> Vec<u32> {
+        self.events
+            .iter()
+            .filter_map(|e| match e {
+                Event::LinesCleared { lines, .. } => Some(*lines),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Keys held for the letter `key` stands for in a script
+fn keys_for(key: char) -> Inputs {
+    let mut inputs = Inputs::default();
+    match key {
+        '<' => inputs.left = true,
+        '>' => inputs.right = true,
+        'v' => inputs.down = true,
+        'c' => inputs.rot_right = true,
+        'a' => inputs.rot_left = true,
+        _ => panic!("unknown key {:?}", key),
+    }
+    inputs
+}
+
+#[test]
+fn a_tetris_clears_the_well() {
+    let scenario = Scenario::new(
+        "#.........
+         #########.
+         #########.
+         #########.
+         #########.",
+    )
+    .piece(Tetromino::I)
+    .tap("c")
+    .hold('>', 20)
+    .drop()
+    .settle();
+
+    assert_eq!(scenario.clears(), vec![4]);
+    assert_eq!(scenario.sim.lines, 4);
+    let scored: u64 = scenario
+        .events
+        .iter()
+        .filter_map(|e| match e {
+            Event::LinesCleared { score, .. } => Some(*score),
+            _ => None,
+        })
+        .sum();
+    assert!(scored > 0);
+    assert_eq!(scenario.sim.score, scored);
+    scenario.assert_field("#.........");
+}
+
+#[test]
+fn stacking_up_the_middle_tops_out() {
+    let mut scenario = Scenario::new("");
+    for _ in 0..10 {
+        scenario = scenario.piece(Tetromino::O).drop();
+        if scenario.sim.topped_out() {
+            break;
+        }
+    }
+    assert!(scenario.sim.topped_out());
+    // nine fill the rows below the spawn rows, the tenth locks out in them
+    assert_eq!(
+        scenario.count(|e| matches!(e, Event::PieceLocked { .. })),
+        10
+    );
+
+    for _ in 0..MAX_TICKS {
+        if scenario.sim.ending.is_some() {
+            break;
+        }
+        scenario.step(Inputs::default());
+    }
+    assert_eq!(scenario.sim.ending, Some(Ending::ToppedOut));
+    assert!(scenario
+        .events
+        .iter()
+        .any(|e| matches!(e, Event::Finished(Ending::ToppedOut))));
+}
+
+#[test]
+fn turning_into_the_wall_without_kicks_is_blocked() {
+    let mut scenario = Scenario::new("")
+        .rotation(RotationKind::Classic)
+        .piece(Tetromino::I)
+        .tap("c")
+        .hold('<', 20);
+    let standing = scenario.sim.active_piece.unwrap();
+    assert!(standing.cells().any(|(x, _)| x == 0), "not against the wall");
+
+    let turns = scenario.count(|e| matches!(e, Event::PieceRotated));
+    scenario = scenario.tap("c").tap("a");
+    let piece = scenario.sim.active_piece.unwrap();
+    assert_eq!((piece.x, piece.rot), (standing.x, standing.rot));
+    assert_eq!(
+        scenario.count(|e| matches!(e, Event::PieceRotated)),
+        turns
+    );
+}
+
+#[test]
+fn turning_into_the_wall_kicks_off_it() {
+    let scenario = Scenario::new("")
+        .piece(Tetromino::I)
+        .tap("c")
+        .hold('<', 20)
+        .tap("c");
+    let piece = scenario.sim.active_piece.unwrap();
+    assert_eq!(piece.rot, 2);
+    assert!(piece.cells().all(|(x, _)| x >= 0));
+}
+
+#[test]
+fn rows_apart_clear_together_and_the_rest_close_up() {
+    let scenario = Scenario::new(
+        "#.........
+         #########.
+         ##.######.
+         #########.
+         .########.",
+    )
+    .piece(Tetromino::I)
+    .tap("c")
+    .hold('>', 20)
+    .drop()
+    .settle();
+
+    assert_eq!(scenario.clears(), vec![2]);
+    scenario.assert_field(
+        "#.........
+         ##.#######
+         .#########",
+    );
+}